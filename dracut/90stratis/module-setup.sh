@@ -27,7 +27,7 @@ depends() {
 
 # called by dracut
 installkernel() {
-	instmods xfs dm_crypt dm-thin-pool
+	instmods xfs dm_crypt dm-thin-pool dm-integrity
 }
 
 # called by dracut
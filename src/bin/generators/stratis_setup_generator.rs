@@ -34,7 +34,10 @@ fn make_wanted_by_initrd(unit_path: &Path) -> Result<(), io::Error> {
     Ok(())
 }
 
-fn unit_template(pool_uuid: Uuid) -> String {
+fn unit_template(pool_uuid: Uuid, keyfile: Option<&Path>) -> String {
+    let keyfile_env = keyfile
+        .map(|path| format!("Environment='STRATIS_ROOTFS_KEYFILE={}'\n", path.display()))
+        .unwrap_or_default();
     format!(
         r"[Unit]
 Description=setup for Stratis root filesystem
@@ -49,7 +52,7 @@ Before=initrd.target
 [Service]
 Type=oneshot
 Environment='STRATIS_ROOTFS_UUID={pool_uuid}'
-ExecStart=/usr/lib/systemd/stratis-rootfs-setup
+{keyfile_env}ExecStart=/usr/lib/systemd/stratis-rootfs-setup
 RemainAfterExit=yes
 "
     )
@@ -72,7 +75,24 @@ fn generator_with_err(early_dir: String) -> Result<(), Box<dyn Error>> {
     };
 
     let parsed_pool_uuid = Uuid::parse_str(pool_uuid)?;
-    let file_contents = unit_template(parsed_pool_uuid);
+
+    // The keyfile must be included in the initrd.
+    let keyfile_key = "stratis.rootfs.keyfile";
+    let keyfile = kernel_cmdline
+        .get(keyfile_key)
+        .and_then(|opt_vec| opt_vec.as_ref())
+        .and_then(|vec| vec.iter().next())
+        .map(Path::new);
+    if let Some(path) = keyfile {
+        if !path.is_absolute() {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{keyfile_key} must be an absolute path"),
+            )));
+        }
+    }
+
+    let file_contents = unit_template(parsed_pool_uuid, keyfile);
     let mut path = PathBuf::from(early_dir);
     path.push("stratis-setup.service");
     lib::write_unit_file(&path, file_contents)?;
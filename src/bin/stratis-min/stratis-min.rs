@@ -2,15 +2,16 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::{error::Error, path::PathBuf};
+use std::{env, error::Error, path::PathBuf};
 
-use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use clap::{value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
 use serde_json::{json, Map, Value};
 
 use stratisd::{
     engine::{
-        EncryptionInfo, KeyDescription, Name, PoolIdentifier, PoolUuid, UnlockMethod,
-        CLEVIS_TANG_TRUST_URL,
+        CacheMode, CacheSettings, CryptParams, DevUuid, EncryptionInfo, EraseMode, IntegrityHash,
+        KeyDescription, Name, Pbkdf, PoolIdentifier, PoolUuid, Redundancy, SnapshotSchedule,
+        UnlockMethod, CLEVIS_TANG_TRUST_URL,
     },
     jsonrpc::client::{filesystem, key, pool, report},
     stratis::{StratisError, VERSION},
@@ -47,6 +48,12 @@ fn parse_args() -> Command {
                             .long("prompt")
                             .num_args(0)
                             .requires("unlock_method"),
+                    )
+                    .arg(
+                        Arg::new("keyfile_path")
+                            .long("keyfile-path")
+                            .num_args(1)
+                            .conflicts_with("unlock_method"),
                     ),
                 Command::new("stop")
                     .arg(Arg::new("id").required(true))
@@ -58,36 +65,55 @@ fn parse_args() -> Command {
                             .action(ArgAction::Append)
                             .required(true),
                     )
-                    .arg(Arg::new("key_desc").long("key-desc").num_args(1))
                     .arg(
-                        Arg::new("clevis")
-                            .long("clevis")
+                        Arg::new("redundancy")
+                            .long("redundancy")
                             .num_args(1)
-                            .value_parser(["nbde", "tang", "tpm2"])
-                            .requires_if("nbde", "tang_args")
-                            .requires_if("tang", "tang_args"),
+                            .value_parser(["none", "raid1"]),
                     )
                     .arg(
-                        Arg::new("tang_url")
-                            .long("tang-url")
+                        Arg::new("integrity")
+                            .long("integrity")
                             .num_args(1)
-                            .required_if_eq("clevis", "nbde")
-                            .required_if_eq("clevis", "tang"),
+                            .value_parser(["crc32c", "sha256"]),
                     )
-                    .arg(Arg::new("thumbprint").long("thumbprint").num_args(1))
-                    .arg(Arg::new("trust_url").long("trust-url").num_args(0))
+                    .args(encryption_args())
+                    .group(tang_args_group())
+                    .args(crypt_args()),
+                Command::new("encrypt")
+                    .arg(Arg::new("name").required(true))
+                    .args(encryption_args())
+                    .group(tang_args_group())
+                    .args(crypt_args())
                     .group(
-                        ArgGroup::new("tang_args")
-                            .arg("thumbprint")
-                            .arg("trust_url"),
+                        ArgGroup::new("encryption")
+                            .arg("key_desc")
+                            .arg("clevis")
+                            .multiple(true)
+                            .required(true),
                     ),
+                Command::new("reencrypt")
+                    .arg(Arg::new("name").required(true))
+                    .args(crypt_args()),
+                Command::new("back-up-headers")
+                    .arg(Arg::new("name").required(true))
+                    .arg(Arg::new("archive").required(true)),
+                Command::new("restore-header")
+                    .arg(Arg::new("pool_uuid").required(true))
+                    .arg(Arg::new("dev_uuid").required(true))
+                    .arg(Arg::new("blockdev").required(true))
+                    .arg(Arg::new("archive").required(true)),
                 Command::new("init-cache")
                     .arg(Arg::new("name").required(true))
                     .arg(
                         Arg::new("blockdevs")
                             .action(ArgAction::Append)
                             .required(true),
-                    ),
+                    )
+                    .args(cache_settings_args()),
+                Command::new("set-cache")
+                    .arg(Arg::new("name").required(true))
+                    .args(cache_settings_args()),
                 Command::new("rename")
                     .arg(Arg::new("current_name").required(true))
                     .arg(Arg::new("new_name").required(true)),
@@ -98,6 +124,17 @@ fn parse_args() -> Command {
                             .action(ArgAction::Append)
                             .required(true),
                     ),
+                Command::new("remove-data")
+                    .arg(Arg::new("name").required(true))
+                    .arg(
+                        Arg::new("blockdevs")
+                            .action(ArgAction::Append)
+                            .required(true),
+                    ),
+                Command::new("replace-data")
+                    .arg(Arg::new("name").required(true))
+                    .arg(Arg::new("old").required(true))
+                    .arg(Arg::new("new").required(true)),
                 Command::new("add-cache")
                     .arg(Arg::new("name").required(true))
                     .arg(
@@ -105,7 +142,15 @@ fn parse_args() -> Command {
                             .action(ArgAction::Append)
                             .required(true),
                     ),
-                Command::new("destroy").arg(Arg::new("name").required(true)),
+                Command::new("remove-cache").arg(Arg::new("name").required(true)),
+                Command::new("destroy")
+                    .arg(Arg::new("name").required(true))
+                    .arg(
+                        Arg::new("erase")
+                            .long("erase")
+                            .num_args(1)
+                            .value_parser(["keyslots", "discard", "zero"]),
+                    ),
                 Command::new("is-encrypted")
                     .arg(Arg::new("name").long("name").num_args(0))
                     .arg(Arg::new("id").required(true)),
@@ -169,6 +214,27 @@ fn parse_args() -> Command {
                         .arg(Arg::new("name").long("name").num_args(0))
                         .arg(Arg::new("id").required(true)),
                 ]),
+                Command::new("add-keyring-binding")
+                    .arg(Arg::new("name").long("name").num_args(0))
+                    .arg(Arg::new("id").required(true))
+                    .arg(
+                        Arg::new("key_desc")
+                            .long("key-desc")
+                            .num_args(1)
+                            .required(true),
+                    ),
+                Command::new("remove-keyring-binding")
+                    .arg(Arg::new("name").long("name").num_args(0))
+                    .arg(Arg::new("id").required(true))
+                    .arg(
+                        Arg::new("key_desc")
+                            .long("key-desc")
+                            .num_args(1)
+                            .required(true),
+                    ),
+                Command::new("keyring-bindings")
+                    .arg(Arg::new("name").long("name").num_args(0))
+                    .arg(Arg::new("id").required(true)),
             ]),
             Command::new("filesystem").subcommands(vec![
                 Command::new("create")
@@ -181,11 +247,206 @@ fn parse_args() -> Command {
                     .arg(Arg::new("pool_name").required(true))
                     .arg(Arg::new("fs_name").required(true))
                     .arg(Arg::new("new_fs_name").required(true)),
+                Command::new("set-snapshot-schedule")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true))
+                    .arg(
+                        Arg::new("interval")
+                            .long("interval")
+                            .num_args(1)
+                            .required(true)
+                            .value_parser(value_parser!(u64)),
+                    )
+                    .arg(
+                        Arg::new("keep_hourly")
+                            .long("keep-hourly")
+                            .num_args(1)
+                            .default_value("0")
+                            .value_parser(value_parser!(u32)),
+                    )
+                    .arg(
+                        Arg::new("keep_daily")
+                            .long("keep-daily")
+                            .num_args(1)
+                            .default_value("0")
+                            .value_parser(value_parser!(u32)),
+                    )
+                    .arg(
+                        Arg::new("keep_weekly")
+                            .long("keep-weekly")
+                            .num_args(1)
+                            .default_value("0")
+                            .value_parser(value_parser!(u32)),
+                    ),
+                Command::new("unset-snapshot-schedule")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true)),
+                Command::new("snapshot-schedule")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true)),
             ]),
             Command::new("report"),
         ])
 }
 
+fn cache_settings_args() -> Vec<Arg> {
+    vec![
+        Arg::new("mode").long("mode").num_args(1).value_parser([
+            "writethrough",
+            "writeback",
+            "passthrough",
+        ]),
+        Arg::new("policy").long("policy").num_args(1),
+        Arg::new("policy_args")
+            .long("policy-arg")
+            .num_args(1)
+            .action(ArgAction::Append),
+    ]
+}
+
+fn encryption_args() -> Vec<Arg> {
+    vec![
+        Arg::new("key_desc").long("key-desc").num_args(1),
+        Arg::new("clevis")
+            .long("clevis")
+            .num_args(1)
+            .value_parser(["nbde", "tang", "tpm2"])
+            .requires_if("nbde", "tang_args")
+            .requires_if("tang", "tang_args"),
+        Arg::new("tang_url")
+            .long("tang-url")
+            .num_args(1)
+            .required_if_eq("clevis", "nbde")
+            .required_if_eq("clevis", "tang"),
+        Arg::new("thumbprint").long("thumbprint").num_args(1),
+        Arg::new("trust_url").long("trust-url").num_args(0),
+    ]
+}
+
+fn crypt_args() -> Vec<Arg> {
+    vec![
+        Arg::new("cipher").long("cipher").num_args(1),
+        Arg::new("key_size")
+            .long("key-size")
+            .num_args(1)
+            .value_parser(value_parser!(u32)),
+        Arg::new("sector_size")
+            .long("sector-size")
+            .num_args(1)
+            .value_parser(value_parser!(u32)),
+        Arg::new("pbkdf")
+            .long("pbkdf")
+            .num_args(1)
+            .value_parser(["pbkdf2", "argon2i", "argon2id"]),
+        Arg::new("pbkdf_memory")
+            .long("pbkdf-memory")
+            .num_args(1)
+            .value_parser(value_parser!(u32)),
+        Arg::new("pbkdf_iterations")
+            .long("pbkdf-iterations")
+            .num_args(1)
+            .value_parser(value_parser!(u32)),
+        Arg::new("pbkdf_parallel")
+            .long("pbkdf-parallel")
+            .num_args(1)
+            .value_parser(value_parser!(u32)),
+    ]
+}
+
+fn tang_args_group() -> ArgGroup {
+    ArgGroup::new("tang_args")
+        .arg("thumbprint")
+        .arg("trust_url")
+}
+
+fn get_encryption_info_from_args(
+    args: &ArgMatches,
+) -> Result<Option<EncryptionInfo>, StratisError> {
+    let key_description = match args.get_one::<String>("key_desc").map(|s| s.to_owned()) {
+        Some(string) => Some(KeyDescription::try_from(string)?),
+        None => None,
+    };
+    let pin = args.get_one::<String>("clevis").map(|s| s.as_str());
+    let clevis_info = match pin {
+        Some("nbde" | "tang") => {
+            let mut json = Map::new();
+            json.insert(
+                "url".to_string(),
+                Value::from(
+                    args.get_one::<String>("tang_url")
+                        .map(|s| s.as_str())
+                        .expect("Required"),
+                ),
+            );
+            if args.get_flag("trust_url") {
+                json.insert(CLEVIS_TANG_TRUST_URL.to_string(), Value::from(true));
+            } else if let Some(thp) = args.get_one::<String>("thumbprint").map(|s| s.as_str()) {
+                json.insert("thp".to_string(), Value::from(thp));
+            }
+            pin.map(|p| (p.to_string(), Value::from(json)))
+        }
+        Some("tpm2") => Some(("tpm2".to_string(), json!({}))),
+        Some(_) => unreachable!("Validated by parser"),
+        None => None,
+    };
+    Ok(EncryptionInfo::from_options((key_description, clevis_info)))
+}
+
+fn get_crypt_params_from_args(args: &ArgMatches) -> Result<Option<CryptParams>, StratisError> {
+    let params = CryptParams {
+        cipher: args.get_one::<String>("cipher").cloned(),
+        key_size: args.get_one::<u32>("key_size").copied(),
+        sector_size: args.get_one::<u32>("sector_size").copied(),
+        pbkdf: args
+            .get_one::<String>("pbkdf")
+            .map(|pbkdf| Pbkdf::try_from(pbkdf.as_str()))
+            .transpose()?,
+        pbkdf_memory: args.get_one::<u32>("pbkdf_memory").copied(),
+        pbkdf_iterations: args.get_one::<u32>("pbkdf_iterations").copied(),
+        pbkdf_parallel: args.get_one::<u32>("pbkdf_parallel").copied(),
+    };
+    Ok(if params == CryptParams::default() {
+        None
+    } else {
+        Some(params)
+    })
+}
+
+fn get_cache_settings_from_args(args: &ArgMatches) -> Result<CacheSettings, StratisError> {
+    let mode = match args.get_one::<String>("mode") {
+        Some(mode) => CacheMode::try_from(mode.as_str())?,
+        None => CacheMode::default(),
+    };
+    let policy = args
+        .get_one::<String>("policy")
+        .cloned()
+        .unwrap_or_else(|| CacheSettings::default().policy);
+    let policy_args = args
+        .get_many::<String>("policy_args")
+        .map(|policy_args| {
+            policy_args
+                .map(|arg| {
+                    arg.split_once('=')
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .ok_or_else(|| {
+                            StratisError::Msg(format!(
+                                "Cache policy argument {arg} is not of the form key=value"
+                            ))
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+    CacheSettings::new(mode, policy, policy_args)
+}
+
+// The paths are resolved by stratisd, the working directory of which is not
+// that of stratis-min.
+fn get_absolute_path(args: &ArgMatches, id: &str) -> Result<PathBuf, StratisError> {
+    Ok(env::current_dir()?.join(args.get_one::<String>(id).expect("required")))
+}
+
 fn get_paths_from_args(args: &ArgMatches) -> Vec<PathBuf> {
     args.get_many::<String>("blockdevs")
         .expect("required")
@@ -229,11 +490,16 @@ fn main() -> Result<(), String> {
                             .expect("required"),
                     )?)
                 };
-                let unlock_method =
-                    match args.get_one::<String>("unlock_method").map(|s| s.as_str()) {
-                        Some(um) => Some(UnlockMethod::try_from(um)?),
-                        None => None,
-                    };
+                let unlock_method = match (
+                    args.get_one::<String>("unlock_method").map(|s| s.as_str()),
+                    args.get_one::<String>("keyfile_path"),
+                ) {
+                    (Some(um), _) => Some(UnlockMethod::try_from(um)?),
+                    (None, Some(path)) => {
+                        Some(UnlockMethod::Keyfile(env::current_dir()?.join(path)))
+                    }
+                    (None, None) => None,
+                };
                 let prompt = args.get_flag("prompt");
                 if prompt && unlock_method == Some(UnlockMethod::Clevis) {
                     return Err(Box::new(StratisError::Msg(
@@ -258,50 +524,81 @@ fn main() -> Result<(), String> {
                 Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("create") {
                 let paths = get_paths_from_args(args);
-                let key_description = match args.get_one::<String>("key_desc").map(|s| s.to_owned())
-                {
-                    Some(string) => Some(KeyDescription::try_from(string)?),
-                    None => None,
-                };
-                let pin = args.get_one::<String>("clevis").map(|s| s.as_str());
-                let clevis_info = match pin {
-                    Some("nbde" | "tang") => {
-                        let mut json = Map::new();
-                        json.insert(
-                            "url".to_string(),
-                            Value::from(
-                                args.get_one::<String>("tang_url")
-                                    .map(|s| s.as_str())
-                                    .expect("Required"),
-                            ),
-                        );
-                        if args.get_flag("trust_url") {
-                            json.insert(CLEVIS_TANG_TRUST_URL.to_string(), Value::from(true));
-                        } else if let Some(thp) =
-                            args.get_one::<String>("thumbprint").map(|s| s.as_str())
-                        {
-                            json.insert("thp".to_string(), Value::from(thp));
-                        }
-                        pin.map(|p| (p.to_string(), Value::from(json)))
-                    }
-                    Some("tpm2") => Some(("tpm2".to_string(), json!({}))),
-                    Some(_) => unreachable!("Validated by parser"),
-                    None => None,
+                let redundancy = match args.get_one::<String>("redundancy") {
+                    Some(redundancy) => Redundancy::try_from(redundancy.as_str())?,
+                    None => Redundancy::default(),
                 };
+                let integrity = args
+                    .get_one::<String>("integrity")
+                    .map(|hash| IntegrityHash::try_from(hash.as_str()))
+                    .transpose()?;
                 pool::pool_create(
                     args.get_one::<String>("name").expect("required").to_owned(),
                     paths,
-                    EncryptionInfo::from_options((key_description, clevis_info)),
+                    redundancy,
+                    get_encryption_info_from_args(args)?,
+                    get_crypt_params_from_args(args)?,
+                    integrity,
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("encrypt") {
+                pool::pool_encrypt(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                    get_encryption_info_from_args(args)?
+                        .expect("at least one of key_desc and clevis is required by the parser"),
+                    get_crypt_params_from_args(args)?,
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("reencrypt") {
+                pool::pool_reencrypt(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                    get_crypt_params_from_args(args)?,
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("back-up-headers") {
+                pool::pool_back_up_headers(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                    get_absolute_path(args, "archive")?,
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("restore-header") {
+                pool::pool_restore_header(
+                    PoolUuid::parse_str(
+                        args.get_one::<String>("pool_uuid")
+                            .map(|s| s.as_str())
+                            .expect("required"),
+                    )?,
+                    DevUuid::parse_str(
+                        args.get_one::<String>("dev_uuid")
+                            .map(|s| s.as_str())
+                            .expect("required"),
+                    )?,
+                    get_absolute_path(args, "blockdev")?,
+                    get_absolute_path(args, "archive")?,
                 )?;
                 Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("destroy") {
-                pool::pool_destroy(args.get_one::<String>("name").expect("required").to_owned())?;
+                let erase_mode = args
+                    .get_one::<String>("erase")
+                    .map(|mode| EraseMode::try_from(mode.as_str()))
+                    .transpose()?;
+                pool::pool_destroy(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                    erase_mode,
+                )?;
                 Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("init-cache") {
                 let paths = get_paths_from_args(args);
                 pool::pool_init_cache(
                     args.get_one::<String>("name").expect("required").to_owned(),
                     paths,
+                    get_cache_settings_from_args(args)?,
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("set-cache") {
+                pool::pool_set_cache_settings(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                    get_cache_settings_from_args(args)?,
                 )?;
                 Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("rename") {
@@ -321,6 +618,20 @@ fn main() -> Result<(), String> {
                     paths,
                 )?;
                 Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("remove-data") {
+                let paths = get_paths_from_args(args);
+                pool::pool_remove_data(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                    paths,
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("replace-data") {
+                pool::pool_replace_data(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                    PathBuf::from(args.get_one::<String>("old").expect("required")),
+                    PathBuf::from(args.get_one::<String>("new").expect("required")),
+                )?;
+                Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("add-cache") {
                 let paths = get_paths_from_args(args);
                 pool::pool_add_cache(
@@ -328,6 +639,11 @@ fn main() -> Result<(), String> {
                     paths,
                 )?;
                 Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("remove-cache") {
+                pool::pool_remove_cache(
+                    args.get_one::<String>("name").expect("required").to_owned(),
+                )?;
+                Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("is-encrypted") {
                 let id = if args.get_flag("name") {
                     PoolIdentifier::Name(Name::new(
@@ -538,6 +854,54 @@ fn main() -> Result<(), String> {
                 } else {
                     unreachable!("Parser requires a subcommand")
                 }
+            } else if let Some(args) = subcommand.subcommand_matches("add-keyring-binding") {
+                let id = if args.get_flag("name") {
+                    PoolIdentifier::Name(Name::new(
+                        args.get_one::<String>("id").expect("required").to_owned(),
+                    ))
+                } else {
+                    PoolIdentifier::Uuid(PoolUuid::parse_str(
+                        args.get_one::<String>("id")
+                            .map(|s| s.as_str())
+                            .expect("required"),
+                    )?)
+                };
+                let key_desc = KeyDescription::try_from(
+                    args.get_one::<String>("key_desc").expect("required"),
+                )?;
+                pool::pool_add_keyring_binding(id, key_desc)?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("remove-keyring-binding") {
+                let id = if args.get_flag("name") {
+                    PoolIdentifier::Name(Name::new(
+                        args.get_one::<String>("id").expect("required").to_owned(),
+                    ))
+                } else {
+                    PoolIdentifier::Uuid(PoolUuid::parse_str(
+                        args.get_one::<String>("id")
+                            .map(|s| s.as_str())
+                            .expect("required"),
+                    )?)
+                };
+                let key_desc = KeyDescription::try_from(
+                    args.get_one::<String>("key_desc").expect("required"),
+                )?;
+                pool::pool_remove_keyring_binding(id, key_desc)?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("keyring-bindings") {
+                let id = if args.get_flag("name") {
+                    PoolIdentifier::Name(Name::new(
+                        args.get_one::<String>("id").expect("required").to_owned(),
+                    ))
+                } else {
+                    PoolIdentifier::Uuid(PoolUuid::parse_str(
+                        args.get_one::<String>("id")
+                            .map(|s| s.as_str())
+                            .expect("required"),
+                    )?)
+                };
+                pool::pool_keyring_bindings(id)?;
+                Ok(())
             } else {
                 pool::pool_list()?;
                 Ok(())
@@ -576,6 +940,44 @@ fn main() -> Result<(), String> {
                         .to_owned(),
                 )?;
                 Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("set-snapshot-schedule") {
+                let schedule = SnapshotSchedule::new(
+                    *args.get_one::<u64>("interval").expect("required"),
+                    *args.get_one::<u32>("keep_hourly").expect("default value"),
+                    *args.get_one::<u32>("keep_daily").expect("default value"),
+                    *args.get_one::<u32>("keep_weekly").expect("default value"),
+                )?;
+                filesystem::filesystem_set_snapshot_schedule(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("fs_name")
+                        .expect("required")
+                        .to_owned(),
+                    Some(schedule),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("unset-snapshot-schedule") {
+                filesystem::filesystem_set_snapshot_schedule(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("fs_name")
+                        .expect("required")
+                        .to_owned(),
+                    None,
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("snapshot-schedule") {
+                filesystem::filesystem_snapshot_schedule(
+                    args.get_one::<String>("pool_name")
+                        .expect("required")
+                        .to_owned(),
+                    args.get_one::<String>("fs_name")
+                        .expect("required")
+                        .to_owned(),
+                )?;
+                Ok(())
             } else {
                 filesystem::filesystem_list()?;
                 Ok(())
@@ -22,8 +22,8 @@ use crate::{
     },
     engine::{
         CreateAction, DeleteAction, EncryptionInfo, EngineAction, KeyDescription,
-        MappingCreateAction, MappingDeleteAction, PoolIdentifier, PoolUuid, SetUnlockAction,
-        UnlockMethod,
+        MappingCreateAction, MappingDeleteAction, PoolIdentifier, PoolUuid, Redundancy,
+        SetUnlockAction, UnlockMethod,
     },
     stratis::StratisError,
 };
@@ -57,7 +57,9 @@ pub fn destroy_pool(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
         }
     };
 
-    let msg = match handle_action!(block_on(dbus_context.engine.destroy_pool(pool_uuid))) {
+    let msg = match handle_action!(
+        block_on(dbus_context.engine.destroy_pool(pool_uuid, None)).map(|(action, _)| action)
+    ) {
         Ok(DeleteAction::Deleted(uuid)) => {
             dbus_context.push_remove(&pool_path, consts::pool_interface_list());
             return_message.append3(
@@ -289,16 +291,16 @@ pub fn create_pool(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let default_return: (bool, (dbus::Path<'static>, Vec<dbus::Path<'static>>)) =
         (false, (dbus::Path::default(), Vec::new()));
 
-    match tuple_to_option(redundancy_tuple) {
-        None | Some(0) => {}
-        Some(n) => {
-            return Ok(vec![return_message.append3(
-                default_return,
-                1u16,
-                format!("code {n} does not correspond to any redundancy"),
-            )]);
-        }
-    }
+    let redundancy = match tuple_to_option(redundancy_tuple) {
+        Some(code) => match Redundancy::try_from(code) {
+            Ok(r) => r,
+            Err(e) => {
+                let (rc, rs) = engine_to_dbus_err_tuple(&e);
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        },
+        None => Redundancy::default(),
+    };
 
     let key_desc = match key_desc_tuple.and_then(tuple_to_option) {
         Some(kds) => match KeyDescription::try_from(kds) {
@@ -326,7 +328,10 @@ pub fn create_pool(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
     let create_result = handle_action!(block_on(dbus_context.engine.create_pool(
         name,
         &devs.map(Path::new).collect::<Vec<&Path>>(),
+        redundancy,
         EncryptionInfo::from_options((key_desc, clevis_info)).as_ref(),
+        None,
+        None,
     )));
     match create_result {
         Ok(pool_uuid_action) => match pool_uuid_action {
@@ -0,0 +1,97 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::{Factory, MTSync, Method};
+
+use crate::dbus_api::{
+    api::manager_3_7::methods::{create_pool, destroy_pool},
+    types::TData,
+};
+
+pub fn create_pool_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("CreatePool", (), create_pool)
+        .in_arg(("name", "s"))
+        .in_arg(("devices", "as"))
+        // Optional redundancy code for the data tier
+        // b: true if a redundancy code is specified
+        // q: redundancy code; 0 for none, 1 for raid1
+        //
+        // Rust representation: (bool, u16)
+        .in_arg(("redundancy", "(bq)"))
+        // Optional key description of key in the kernel keyring
+        // b: true if the pool should be encrypted and able to be
+        // unlocked with a passphrase associated with this key description.
+        // s: key description
+        //
+        // Rust representation: (bool, String)
+        .in_arg(("key_desc", "(bs)"))
+        // Optional Clevis information for binding on initialization.
+        // b: true if the pool should be encrypted and able to be unlocked
+        // using Clevis.
+        // s: pin name
+        // s: JSON config for Clevis use
+        //
+        // Rust representation: (bool, (String, String))
+        .in_arg(("clevis_info", "(b(ss))"))
+        // Optional parameters for the LUKS2 format of the devices of an
+        // encrypted pool; parameters that are not specified are chosen by
+        // libcryptsetup.
+        // b: true if the parameter is specified
+        // s: cipher and mode, e.g. "aes-xts-plain64"
+        // u: key size in bits
+        // u: encryption sector size in bytes
+        // s: PBKDF; "pbkdf2", "argon2i" or "argon2id"
+        // u: memory cost of an Argon2 PBKDF in KiB
+        // u: number of iterations of the PBKDF
+        // u: number of parallel threads of an Argon2 PBKDF
+        //
+        // Rust representation: (bool, String) or (bool, u32)
+        .in_arg(("cipher", "(bs)"))
+        .in_arg(("key_size", "(bu)"))
+        .in_arg(("sector_size", "(bu)"))
+        .in_arg(("pbkdf", "(bs)"))
+        .in_arg(("pbkdf_memory", "(bu)"))
+        .in_arg(("pbkdf_iterations", "(bu)"))
+        .in_arg(("pbkdf_parallel", "(bu)"))
+        // Optional hash with which the blockdevs of the data tier are given
+        // integrity protection
+        // b: true if the data tier should be given integrity protection
+        // s: hash; "crc32c" or "sha256"
+        //
+        // Rust representation: (bool, String)
+        .in_arg(("integrity", "(bs)"))
+        // In order from left to right:
+        // b: true if a pool was created and object paths were returned
+        // o: Object path for Pool
+        // a(o): Array of object paths for block devices
+        //
+        // Rust representation: (bool, (dbus::Path, Vec<dbus::Path>))
+        .out_arg(("result", "(b(oao))"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn destroy_pool_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("DestroyPool", (), destroy_pool)
+        .in_arg(("pool", "o"))
+        // Optional mode in which the devices of the pool are erased
+        // b: true if the devices of the pool should be erased
+        // s: erase mode; "keyslots" for an encrypted pool, "discard" or
+        // "zero" for an unencrypted pool
+        //
+        // Rust representation: (bool, String)
+        .in_arg(("erase_mode", "(bs)"))
+        // In order from left to right:
+        // b: true if a valid UUID is returned - otherwise no action was performed
+        // s: String representation of pool UUID that was destroyed
+        // o: Object path of the job that destroys the pool and erases its
+        // devices if an erase mode was specified, otherwise "/"; the Result
+        // property of the job reports how the data on each device was erased,
+        // including any device that could not be discarded securely
+        //
+        // Rust representation: (bool, (String, dbus::Path))
+        .out_arg(("result", "(b(so))"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
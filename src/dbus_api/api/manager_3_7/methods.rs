@@ -0,0 +1,240 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::path::Path;
+
+use dbus::{arg::Array, Message};
+use dbus_tree::{MTSync, MethodInfo, MethodResult};
+use futures::executor::block_on;
+
+use crate::{
+    dbus_api::{
+        blockdev::create_dbus_blockdev,
+        consts,
+        job::spawn_dbus_job,
+        pool::create_dbus_pool,
+        types::{DbusErrorEnum, TData, OK_STRING},
+        util::{engine_to_dbus_err_tuple, get_crypt_params_args, get_next_arg, tuple_to_option},
+    },
+    engine::{
+        CreateAction, DeleteAction, EncryptionInfo, EraseMode, IntegrityHash, JobProgress,
+        KeyDescription, PoolIdentifier, PoolUuid, Redundancy,
+    },
+    stratis::StratisError,
+};
+
+type EncryptionParams = (Option<(bool, String)>, Option<(bool, (String, String))>);
+
+pub fn create_pool(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let base_path = m.path.get_name();
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let name: &str = get_next_arg(&mut iter, 0)?;
+    let devs: Array<'_, &str, _> = get_next_arg(&mut iter, 1)?;
+    let redundancy_tuple: (bool, u16) = get_next_arg(&mut iter, 2)?;
+    let (key_desc_tuple, clevis_tuple): EncryptionParams = (
+        Some(get_next_arg(&mut iter, 3)?),
+        Some(get_next_arg(&mut iter, 4)?),
+    );
+    let crypt_params_res = get_crypt_params_args(&mut iter, 5)?;
+    let integrity_tuple: (bool, &str) = get_next_arg(&mut iter, 12)?;
+
+    let return_message = message.method_return();
+
+    let default_return: (bool, (dbus::Path<'static>, Vec<dbus::Path<'static>>)) =
+        (false, (dbus::Path::default(), Vec::new()));
+
+    let redundancy = match tuple_to_option(redundancy_tuple) {
+        Some(code) => match Redundancy::try_from(code) {
+            Ok(r) => r,
+            Err(e) => {
+                let (rc, rs) = engine_to_dbus_err_tuple(&e);
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        },
+        None => Redundancy::default(),
+    };
+
+    let key_desc = match key_desc_tuple.and_then(tuple_to_option) {
+        Some(kds) => match KeyDescription::try_from(kds) {
+            Ok(kd) => Some(kd),
+            Err(e) => {
+                let (rc, rs) = engine_to_dbus_err_tuple(&e);
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        },
+        None => None,
+    };
+
+    let clevis_info = match clevis_tuple.and_then(tuple_to_option) {
+        Some((pin, json_string)) => match serde_json::from_str(json_string.as_str()) {
+            Ok(j) => Some((pin, j)),
+            Err(e) => {
+                let (rc, rs) = engine_to_dbus_err_tuple(&StratisError::Serde(e));
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        },
+        None => None,
+    };
+
+    let crypt_params = match crypt_params_res {
+        Ok(params) => params,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let integrity = match tuple_to_option(integrity_tuple) {
+        Some(hash) => match IntegrityHash::try_from(hash) {
+            Ok(h) => Some(h),
+            Err(e) => {
+                let (rc, rs) = engine_to_dbus_err_tuple(&e);
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        },
+        None => None,
+    };
+
+    let dbus_context = m.tree.get_data();
+    let create_result = handle_action!(block_on(dbus_context.engine.create_pool(
+        name,
+        &devs.map(Path::new).collect::<Vec<&Path>>(),
+        redundancy,
+        EncryptionInfo::from_options((key_desc, clevis_info)).as_ref(),
+        crypt_params.as_ref(),
+        integrity,
+    )));
+    match create_result {
+        Ok(pool_uuid_action) => match pool_uuid_action {
+            CreateAction::Created(uuid) => {
+                let guard = match block_on(dbus_context.engine.get_pool(PoolIdentifier::Uuid(uuid)))
+                {
+                    Some(g) => g,
+                    None => {
+                        let (rc, rs) = engine_to_dbus_err_tuple(&StratisError::Msg(
+                            format!("Pool with UUID {uuid} was successfully started but appears to have been removed before it could be exposed on the D-Bus")
+                        ));
+                        return Ok(vec![return_message.append3(default_return, rc, rs)]);
+                    }
+                };
+
+                let (pool_name, pool_uuid, pool) = guard.as_tuple();
+                let pool_path =
+                    create_dbus_pool(dbus_context, base_path.clone(), &pool_name, pool_uuid, pool);
+                let mut bd_paths = Vec::new();
+                for (bd_uuid, tier, bd) in pool.blockdevs() {
+                    bd_paths.push(create_dbus_blockdev(
+                        dbus_context,
+                        pool_path.clone(),
+                        bd_uuid,
+                        tier,
+                        bd,
+                    ));
+                }
+
+                Ok(vec![return_message.append3(
+                    (true, (pool_path, bd_paths)),
+                    DbusErrorEnum::OK as u16,
+                    OK_STRING.to_string(),
+                )])
+            }
+            CreateAction::Identity => Ok(vec![return_message.append3(
+                default_return,
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )]),
+        },
+        Err(x) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&x);
+            Ok(vec![return_message.append3(default_return, rc, rs)])
+        }
+    }
+}
+
+pub fn destroy_pool(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let pool_path: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let erase_mode_tuple: (bool, &str) = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+
+    let default_return = (
+        false,
+        (uuid_to_string!(PoolUuid::nil()), dbus::Path::from("/")),
+    );
+    let return_message = message.method_return();
+
+    let erase_mode = match tuple_to_option(erase_mode_tuple) {
+        Some(mode) => match EraseMode::try_from(mode) {
+            Ok(mode) => Some(mode),
+            Err(e) => {
+                let (rc, rs) = engine_to_dbus_err_tuple(&e);
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        },
+        None => None,
+    };
+
+    let pool_uuid = match m
+        .tree
+        .get(&pool_path)
+        .and_then(|op| op.get_data().as_ref())
+        .map(|d| &d.uuid)
+    {
+        Some(uuid) => *typed_uuid!(uuid; Pool; default_return; return_message),
+        None => {
+            return Ok(vec![return_message.append3(
+                default_return,
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )]);
+        }
+    };
+
+    let msg = match block_on(dbus_context.engine.destroy_pool(pool_uuid, erase_mode)) {
+        Ok((action @ DeleteAction::Deleted(uuid), erasure)) => {
+            info!("{action}");
+            dbus_context.push_remove(&pool_path, consts::pool_interface_list());
+            // Erasing the devices of a pool may take a long time, so it is
+            // done in a job whose progress can be watched on the D-Bus.
+            let job_path = match erase_mode {
+                Some(_) => {
+                    let progress = JobProgress::default();
+                    let job_progress = progress.clone();
+                    spawn_dbus_job(dbus_context, progress, async move {
+                        match erasure {
+                            Some(erasure) => {
+                                spawn_blocking!(erasure.run(&job_progress)).and_then(|res| res)
+                            }
+                            None => {
+                                job_progress.set(100);
+                                Ok("Every keyslot of every device was destroyed".to_string())
+                            }
+                        }
+                    })
+                }
+                None => dbus::Path::from("/"),
+            };
+            return_message.append3(
+                (true, (uuid_to_string!(uuid), job_path)),
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )
+        }
+        Ok((DeleteAction::Identity, _)) => return_message.append3(
+            default_return,
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        ),
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
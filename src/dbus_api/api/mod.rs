@@ -14,6 +14,7 @@ mod manager_3_2;
 mod manager_3_4;
 mod manager_3_5;
 mod manager_3_6;
+mod manager_3_7;
 pub mod prop_conv;
 mod report_3_0;
 mod shared;
@@ -137,11 +138,11 @@ pub fn get_base_tree<'a>(
         )
         .add(
             f.interface(consts::MANAGER_INTERFACE_NAME_3_7, ())
-                .add_m(manager_3_5::create_pool_method(&f))
+                .add_m(manager_3_7::create_pool_method(&f))
                 .add_m(manager_3_0::set_key_method(&f))
                 .add_m(manager_3_0::unset_key_method(&f))
                 .add_m(manager_3_0::list_keys_method(&f))
-                .add_m(manager_3_0::destroy_pool_method(&f))
+                .add_m(manager_3_7::destroy_pool_method(&f))
                 .add_m(manager_3_0::engine_state_report_method(&f))
                 .add_m(manager_3_4::start_pool_method(&f))
                 .add_m(manager_3_6::stop_pool_method(&f))
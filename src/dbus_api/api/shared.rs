@@ -15,6 +15,7 @@ use crate::{
         api::prop_conv::{self, StoppedOrLockedPools},
         blockdev::get_blockdev_properties,
         filesystem::get_fs_properties,
+        job::get_job_properties,
         pool::get_pool_properties,
         types::{GetManagedObjects, InterfacesAddedThreadSafe, TData},
         util::thread_safe_to_dbus_sendable,
@@ -92,7 +93,7 @@ pub fn get_managed_objects_method(
 
         let table = block_on(dbus_context.engine.pools());
 
-        let properties: GetManagedObjects = m
+        let mut properties: GetManagedObjects = m
             .tree
             .iter()
             .filter_map(|op| {
@@ -120,6 +121,19 @@ pub fn get_managed_objects_method(
                 props.extend(prop);
                 props
             });
+        properties.extend(
+            dbus_context
+                .jobs
+                .lock()
+                .expect("Mutex only locked internally")
+                .iter()
+                .map(|(path, job)| {
+                    (
+                        path.clone(),
+                        thread_safe_to_dbus_sendable(get_job_properties(job)),
+                    )
+                }),
+        );
 
         Ok(vec![m.msg.method_return().append1(properties)])
     }
@@ -0,0 +1,27 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::{Access, EmitsChangedSignal, Factory, MTSync, Property};
+
+use crate::dbus_api::{
+    blockdev::blockdev_3_7::props::{get_blockdev_integrity_errors, get_blockdev_shrunk},
+    consts,
+    types::TData,
+};
+
+pub fn shrunk_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<bool, _>(consts::BLOCKDEV_SHRUNK_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_blockdev_shrunk)
+}
+
+pub fn integrity_errors_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, u64), _>(consts::BLOCKDEV_INTEGRITY_ERRORS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_blockdev_integrity_errors)
+}
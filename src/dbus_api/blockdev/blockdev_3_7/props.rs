@@ -0,0 +1,28 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus::arg::IterAppend;
+use dbus_tree::{MTSync, MethodErr, PropInfo};
+
+use crate::dbus_api::{
+    blockdev::shared::{self, get_blockdev_property},
+    types::TData,
+};
+
+/// Get whether the block device represented by an object path has shrunk.
+pub fn get_blockdev_shrunk(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_blockdev_property(i, p, |_, p| Ok(shared::blockdev_shrunk_prop(p)))
+}
+
+/// Get the number of integrity errors of the block device represented by an
+/// object path.
+pub fn get_blockdev_integrity_errors(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_blockdev_property(i, p, |_, p| Ok(shared::blockdev_integrity_errors_prop(p)))
+}
@@ -15,6 +15,7 @@ use crate::{
 
 mod blockdev_3_0;
 mod blockdev_3_3;
+mod blockdev_3_7;
 pub mod prop_conv;
 mod shared;
 
@@ -137,7 +138,9 @@ pub fn create_dbus_blockdev<'a>(
                 .add_p(blockdev_3_0::uuid_property(&f))
                 .add_p(blockdev_3_0::physical_path_property(&f))
                 .add_p(blockdev_3_0::size_property(&f))
-                .add_p(blockdev_3_3::new_size_property(&f)),
+                .add_p(blockdev_3_3::new_size_property(&f))
+                .add_p(blockdev_3_7::shrunk_property(&f))
+                .add_p(blockdev_3_7::integrity_errors_property(&f)),
         );
 
     let path = object_path.get_name().to_owned();
@@ -245,7 +248,9 @@ pub fn get_blockdev_properties(
             consts::BLOCKDEV_TIER_PROP => shared::blockdev_tier_prop(tier),
             consts::BLOCKDEV_PHYSICAL_PATH_PROP => shared::blockdev_physical_path_prop(dev),
             consts::BLOCKDEV_TOTAL_SIZE_PROP => shared::blockdev_size_prop(dev),
-            consts::BLOCKDEV_NEW_SIZE_PROP => shared::blockdev_new_size_prop(dev)
+            consts::BLOCKDEV_NEW_SIZE_PROP => shared::blockdev_new_size_prop(dev),
+            consts::BLOCKDEV_SHRUNK_PROP => shared::blockdev_shrunk_prop(dev),
+            consts::BLOCKDEV_INTEGRITY_ERRORS_PROP => shared::blockdev_integrity_errors_prop(dev)
         }
     }
 }
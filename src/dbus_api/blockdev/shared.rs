@@ -213,3 +213,16 @@ pub fn blockdev_size_prop(dev: &dyn BlockDev) -> String {
 pub fn blockdev_new_size_prop(dev: &dyn BlockDev) -> (bool, String) {
     prop_conv::blockdev_new_size_to_prop(dev.new_size())
 }
+
+/// Generate D-Bus representation of whether the block device has shrunk.
+#[inline]
+pub fn blockdev_shrunk_prop(dev: &dyn BlockDev) -> bool {
+    dev.is_shrunk()
+}
+
+/// Generate D-Bus representation of the number of integrity errors of the
+/// block device.
+#[inline]
+pub fn blockdev_integrity_errors_prop(dev: &dyn BlockDev) -> (bool, u64) {
+    prop_conv::blockdev_integrity_errors_to_prop(dev.integrity_errors())
+}
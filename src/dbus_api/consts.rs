@@ -48,6 +48,24 @@ pub const POOL_ALLOC_SIZE_PROP: &str = "AllocatedSize";
 pub const POOL_FS_LIMIT_PROP: &str = "FsLimit";
 pub const POOL_OVERPROV_PROP: &str = "Overprovisioning";
 pub const POOL_NO_ALLOCABLE_SPACE_PROP: &str = "NoAllocSpace";
+pub const POOL_REPLACEMENT_PROGRESS_PROP: &str = "ReplacementProgress";
+pub const POOL_CACHE_MODE_PROP: &str = "CacheMode";
+pub const POOL_CACHE_POLICY_PROP: &str = "CachePolicy";
+pub const POOL_CACHE_POLICY_ARGS_PROP: &str = "CachePolicyArgs";
+pub const POOL_CACHE_READ_HITS_PROP: &str = "CacheReadHits";
+pub const POOL_CACHE_READ_MISSES_PROP: &str = "CacheReadMisses";
+pub const POOL_CACHE_WRITE_HITS_PROP: &str = "CacheWriteHits";
+pub const POOL_CACHE_WRITE_MISSES_PROP: &str = "CacheWriteMisses";
+pub const POOL_CACHE_DIRTY_BLOCKS_PROP: &str = "CacheDirtyBlocks";
+pub const POOL_CACHE_PROMOTIONS_PROP: &str = "CachePromotions";
+pub const POOL_CACHE_DEMOTIONS_PROP: &str = "CacheDemotions";
+pub const POOL_REDUNDANCY_PROP: &str = "Redundancy";
+pub const POOL_DEGRADED_PROP: &str = "Degraded";
+pub const POOL_STRIPE_SIZE_PROP: &str = "StripeSize";
+pub const POOL_ENCRYPTION_PROGRESS_PROP: &str = "EncryptionProgress";
+pub const POOL_REENCRYPTION_PROGRESS_PROP: &str = "ReencryptionProgress";
+pub const POOL_KEYRING_BINDINGS_PROP: &str = "KeyringBindings";
+pub const POOL_DATA_SHRINK_PROP: &str = "DataShrink";
 
 pub const FILESYSTEM_INTERFACE_NAME_3_0: &str = "org.storage.stratis3.filesystem.r0";
 pub const FILESYSTEM_INTERFACE_NAME_3_1: &str = "org.storage.stratis3.filesystem.r1";
@@ -65,6 +83,12 @@ pub const FILESYSTEM_POOL_PROP: &str = "Pool";
 pub const FILESYSTEM_CREATED_PROP: &str = "Created";
 pub const FILESYSTEM_SIZE_PROP: &str = "Size";
 pub const FILESYSTEM_SIZE_LIMIT_PROP: &str = "SizeLimit";
+pub const FILESYSTEM_ORIGIN_PROP: &str = "Origin";
+pub const FILESYSTEM_SNAPSHOT_SCHEDULE_PROP: &str = "SnapshotSchedule";
+pub const FILESYSTEM_SNAPSHOT_SCHEDULE_STATUS_PROP: &str = "SnapshotScheduleStatus";
+pub const FILESYSTEM_READ_ONLY_PROP: &str = "ReadOnly";
+pub const FILESYSTEM_EXCLUSIVE_PROP: &str = "Exclusive";
+pub const FILESYSTEM_SHARED_PROP: &str = "Shared";
 
 pub const BLOCKDEV_INTERFACE_NAME_3_0: &str = "org.storage.stratis3.blockdev.r0";
 pub const BLOCKDEV_INTERFACE_NAME_3_1: &str = "org.storage.stratis3.blockdev.r1";
@@ -84,6 +108,14 @@ pub const BLOCKDEV_TIER_PROP: &str = "Tier";
 pub const BLOCKDEV_PHYSICAL_PATH_PROP: &str = "PhysicalPath";
 pub const BLOCKDEV_NEW_SIZE_PROP: &str = "NewPhysicalSize";
 pub const BLOCKDEV_TOTAL_SIZE_PROP: &str = "TotalPhysicalSize";
+pub const BLOCKDEV_SHRUNK_PROP: &str = "Shrunk";
+pub const BLOCKDEV_INTEGRITY_ERRORS_PROP: &str = "IntegrityErrors";
+
+pub const JOB_INTERFACE_NAME_3_7: &str = "org.storage.stratis3.job.r7";
+pub const JOB_PROGRESS_PROP: &str = "Progress";
+pub const JOB_FINISHED_PROP: &str = "Finished";
+pub const JOB_ERROR_PROP: &str = "Error";
+pub const JOB_RESULT_PROP: &str = "Result";
 
 /// Get a list of all the standard pool interfaces
 pub fn standard_pool_interfaces() -> Vec<String> {
@@ -150,3 +182,8 @@ pub fn filesystem_interface_list() -> InterfacesRemoved {
 pub fn blockdev_interface_list() -> InterfacesRemoved {
     standard_blockdev_interfaces()
 }
+
+/// Get a list of all interfaces supported by a job object.
+pub fn job_interface_list() -> InterfacesRemoved {
+    vec![JOB_INTERFACE_NAME_3_7.to_string()]
+}
@@ -0,0 +1,67 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::{Access, EmitsChangedSignal, Factory, MTSync, Property};
+
+use crate::dbus_api::{
+    consts,
+    filesystem::filesystem_3_7::props::{
+        get_fs_exclusive, get_fs_origin, get_fs_read_only, get_fs_shared, get_fs_snapshot_schedule,
+        get_fs_snapshot_schedule_status, set_fs_read_only, set_fs_snapshot_schedule,
+    },
+    types::TData,
+};
+
+pub fn origin_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, String), _>(consts::FILESYSTEM_ORIGIN_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_fs_origin)
+}
+
+pub fn snapshot_schedule_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, (u64, u32, u32, u32)), _>(consts::FILESYSTEM_SNAPSHOT_SCHEDULE_PROP, ())
+        .access(Access::ReadWrite)
+        .emits_changed(EmitsChangedSignal::True)
+        .auto_emit_on_set(false)
+        .on_get(get_fs_snapshot_schedule)
+        .on_set(set_fs_snapshot_schedule)
+}
+
+pub fn snapshot_schedule_status_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, (String, bool, String)), _>(
+        consts::FILESYSTEM_SNAPSHOT_SCHEDULE_STATUS_PROP,
+        (),
+    )
+    .access(Access::Read)
+    .emits_changed(EmitsChangedSignal::False)
+    .on_get(get_fs_snapshot_schedule_status)
+}
+
+pub fn read_only_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<bool, _>(consts::FILESYSTEM_READ_ONLY_PROP, ())
+        .access(Access::ReadWrite)
+        .emits_changed(EmitsChangedSignal::True)
+        .auto_emit_on_set(false)
+        .on_get(get_fs_read_only)
+        .on_set(set_fs_read_only)
+}
+
+pub fn exclusive_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, &str), _>(consts::FILESYSTEM_EXCLUSIVE_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_fs_exclusive)
+}
+
+pub fn shared_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, &str), _>(consts::FILESYSTEM_SHARED_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_fs_shared)
+}
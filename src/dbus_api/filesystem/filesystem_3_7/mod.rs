@@ -0,0 +1,11 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+mod api;
+mod props;
+
+pub use api::{
+    exclusive_property, origin_property, read_only_property, shared_property,
+    snapshot_schedule_property, snapshot_schedule_status_property,
+};
@@ -0,0 +1,123 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus::arg::{Iter, IterAppend};
+use dbus_tree::{MTSync, MethodErr, PropInfo};
+
+use crate::{
+    dbus_api::{
+        consts,
+        filesystem::shared::{self, get_filesystem_property},
+        types::TData,
+        util::tuple_to_option,
+    },
+    engine::{PropChangeAction, SnapshotSchedule},
+};
+
+/// Get the UUID of the filesystem of which the filesystem represented by an
+/// object path is a snapshot.
+pub fn get_fs_origin(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_filesystem_property(i, p, |(_, _, f)| Ok(shared::fs_origin_prop(f)))
+}
+
+pub fn get_fs_snapshot_schedule(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_filesystem_property(i, p, |(_, _, f)| Ok(shared::fs_snapshot_schedule_prop(f)))
+}
+
+pub fn set_fs_snapshot_schedule(
+    i: &mut Iter<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    let schedule_opt: (bool, (u64, u32, u32, u32)) = i
+        .get()
+        .ok_or_else(|| MethodErr::failed("New snapshot schedule required as argument to set it"))?;
+    let schedule = match tuple_to_option(schedule_opt) {
+        Some((interval, keep_hourly, keep_daily, keep_weekly)) => Some(
+            SnapshotSchedule::new(interval, keep_hourly, keep_daily, keep_weekly)
+                .map_err(|e| MethodErr::failed(&e.to_string()))?,
+        ),
+        None => None,
+    };
+
+    let res = shared::set_fs_property_to_display(
+        p,
+        consts::FILESYSTEM_SNAPSHOT_SCHEDULE_PROP,
+        |(_, uuid, p)| shared::set_fs_snapshot_schedule_prop(uuid, p, schedule),
+    );
+    match res {
+        Ok(PropChangeAction::NewValue(v)) => {
+            p.tree
+                .get_data()
+                .push_fs_snapshot_schedule_change(p.path.get_name(), v);
+            Ok(())
+        }
+        Ok(PropChangeAction::Identity) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Get the outcome of the most recent run of the snapshot schedule of the
+/// filesystem represented by an object path.
+pub fn get_fs_snapshot_schedule_status(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_filesystem_property(i, p, |(_, _, f)| {
+        Ok(shared::fs_snapshot_schedule_status_prop(f))
+    })
+}
+
+pub fn get_fs_read_only(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_filesystem_property(i, p, |(_, _, f)| Ok(shared::fs_read_only_prop(f)))
+}
+
+pub fn set_fs_read_only(
+    i: &mut Iter<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    let read_only: bool = i
+        .get()
+        .ok_or_else(|| MethodErr::failed("New read-only setting required as argument to set it"))?;
+
+    let res = shared::set_fs_property(p, consts::FILESYSTEM_READ_ONLY_PROP, |(_, uuid, p)| {
+        shared::set_fs_read_only_prop(uuid, p, read_only)
+    });
+    match res {
+        Ok(PropChangeAction::NewValue(v)) => {
+            p.tree
+                .get_data()
+                .push_fs_read_only_change(p.path.get_name(), v);
+            Ok(())
+        }
+        Ok(PropChangeAction::Identity) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Get the space mapped only by the filesystem represented by an object
+/// path, which would be freed if the filesystem were destroyed.
+pub fn get_fs_exclusive(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_filesystem_property(i, p, |(_, _, f)| Ok(shared::fs_exclusive_prop(f)))
+}
+
+/// Get the space that the filesystem represented by an object path shares
+/// with other filesystems.
+pub fn get_fs_shared(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_filesystem_property(i, p, |(_, _, f)| Ok(shared::fs_shared_prop(f)))
+}
@@ -15,6 +15,7 @@ use crate::{
 
 mod filesystem_3_0;
 mod filesystem_3_6;
+mod filesystem_3_7;
 pub mod prop_conv;
 mod shared;
 
@@ -124,7 +125,13 @@ pub fn create_dbus_filesystem<'a>(
                 .add_p(filesystem_3_0::created_property(&f))
                 .add_p(filesystem_3_0::size_property(&f))
                 .add_p(filesystem_3_0::used_property(&f))
-                .add_p(filesystem_3_6::size_limit_property(&f)),
+                .add_p(filesystem_3_6::size_limit_property(&f))
+                .add_p(filesystem_3_7::origin_property(&f))
+                .add_p(filesystem_3_7::snapshot_schedule_property(&f))
+                .add_p(filesystem_3_7::snapshot_schedule_status_property(&f))
+                .add_p(filesystem_3_7::read_only_property(&f))
+                .add_p(filesystem_3_7::exclusive_property(&f))
+                .add_p(filesystem_3_7::shared_property(&f)),
         );
 
     let path = object_path.get_name().to_owned();
@@ -214,7 +221,13 @@ pub fn get_fs_properties(
             consts::FILESYSTEM_CREATED_PROP => shared::fs_created_prop(fs),
             consts::FILESYSTEM_SIZE_PROP => shared::fs_size_prop(fs),
             consts::FILESYSTEM_USED_PROP => shared::fs_used_prop(fs),
-            consts::FILESYSTEM_SIZE_LIMIT_PROP => shared::fs_size_limit_prop(fs)
+            consts::FILESYSTEM_SIZE_LIMIT_PROP => shared::fs_size_limit_prop(fs),
+            consts::FILESYSTEM_ORIGIN_PROP => shared::fs_origin_prop(fs),
+            consts::FILESYSTEM_SNAPSHOT_SCHEDULE_PROP => shared::fs_snapshot_schedule_prop(fs),
+            consts::FILESYSTEM_SNAPSHOT_SCHEDULE_STATUS_PROP => shared::fs_snapshot_schedule_status_prop(fs),
+            consts::FILESYSTEM_READ_ONLY_PROP => shared::fs_read_only_prop(fs),
+            consts::FILESYSTEM_EXCLUSIVE_PROP => shared::fs_exclusive_prop(fs),
+            consts::FILESYSTEM_SHARED_PROP => shared::fs_shared_prop(fs)
         }
     }
 }
@@ -2,9 +2,14 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use chrono::SecondsFormat;
+
 use devicemapper::{Bytes, Sectors};
 
-use crate::dbus_api::util::option_to_tuple;
+use crate::{
+    dbus_api::util::option_to_tuple,
+    engine::{FilesystemUuid, SnapshotSchedule, SnapshotScheduleStatus},
+};
 
 /// Generate D-Bus representation of filesystem size property.
 #[inline]
@@ -23,3 +28,41 @@ pub fn fs_used_to_prop(used: Option<Bytes>) -> (bool, String) {
 pub fn fs_size_limit_to_prop(limit: Option<Sectors>) -> (bool, String) {
     option_to_tuple(limit.map(|u| (*u.bytes()).to_string()), String::new())
 }
+
+/// Generate D-Bus representation of filesystem origin property.
+#[inline]
+pub fn fs_origin_to_prop(origin: Option<FilesystemUuid>) -> (bool, String) {
+    option_to_tuple(origin.map(|u| uuid_to_string!(u)), String::new())
+}
+
+/// Generate D-Bus representation of filesystem snapshot schedule property:
+/// the interval in seconds and the numbers of hourly, daily, and weekly
+/// snapshots to retain.
+#[inline]
+pub fn fs_snapshot_schedule_to_prop(
+    schedule: Option<SnapshotSchedule>,
+) -> (bool, (u64, u32, u32, u32)) {
+    option_to_tuple(
+        schedule.map(|s| (s.interval, s.keep_hourly, s.keep_daily, s.keep_weekly)),
+        (0, 0, 0, 0),
+    )
+}
+
+/// Generate D-Bus representation of filesystem snapshot schedule status
+/// property: the time of the last run, whether it succeeded, and the error
+/// if it did not.
+#[inline]
+pub fn fs_snapshot_schedule_status_to_prop(
+    status: Option<SnapshotScheduleStatus>,
+) -> (bool, (String, bool, String)) {
+    option_to_tuple(
+        status.map(|s| {
+            (
+                s.time.to_rfc3339_opts(SecondsFormat::Secs, true),
+                s.error.is_none(),
+                s.error.unwrap_or_default(),
+            )
+        }),
+        (String::new(), false, String::new()),
+    )
+}
@@ -2,6 +2,8 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::fmt::Display;
+
 use chrono::SecondsFormat;
 use dbus::{arg::IterAppend, Path};
 use dbus_tree::{MTSync, MethodErr, PropInfo, Tree};
@@ -11,7 +13,10 @@ use devicemapper::{Bytes, Sectors};
 
 use crate::{
     dbus_api::{filesystem::prop_conv, types::TData},
-    engine::{Filesystem, FilesystemUuid, Name, Pool, PoolIdentifier, PropChangeAction, ToDisplay},
+    engine::{
+        Filesystem, FilesystemUuid, Name, Pool, PoolIdentifier, PropChangeAction, SnapshotSchedule,
+        ToDisplay,
+    },
 };
 
 /// Get execute a given closure providing a filesystem object and return
@@ -137,6 +142,24 @@ where
     closure((name, fs_uuid, &mut *guard))
 }
 
+/// Set a filesystem property whose value can be displayed directly. The
+/// property is found by means of the setter method which takes a mutable
+/// reference to a Pool and sets the property on the filesystem.
+pub fn set_fs_property<F, R>(
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+    prop_name: &str,
+    setter: F,
+) -> Result<PropChangeAction<R>, MethodErr>
+where
+    F: Fn((Name, FilesystemUuid, &mut dyn Pool)) -> Result<PropChangeAction<R>, String>,
+    R: Display,
+{
+    info!("Setting property {}", prop_name);
+    let res =
+        fs_set_operation(p.tree, p.path.get_name(), setter).map_err(|ref e| MethodErr::failed(e));
+    handle_action!(res)
+}
+
 /// Set a filesystem property. The property is found by means of the setter method which
 /// takes a mutable reference to a Filesystem and sets the property on the filesystem.
 pub fn set_fs_property_to_display<F, R>(
@@ -173,6 +196,52 @@ pub fn set_fs_size_limit_prop(
         .map_err(|e| e.to_string())
 }
 
+/// Generate D-Bus representation of snapshot schedule property.
+#[inline]
+pub fn fs_snapshot_schedule_prop(fs: &dyn Filesystem) -> (bool, (u64, u32, u32, u32)) {
+    prop_conv::fs_snapshot_schedule_to_prop(fs.snapshot_schedule())
+}
+
+/// Set the snapshot schedule for a given filesystem.
+#[inline]
+pub fn set_fs_snapshot_schedule_prop(
+    uuid: FilesystemUuid,
+    pool: &mut dyn Pool,
+    schedule: Option<SnapshotSchedule>,
+) -> Result<PropChangeAction<Option<SnapshotSchedule>>, String> {
+    pool.set_fs_snapshot_schedule(uuid, schedule)
+        .map_err(|e| e.to_string())
+}
+
+/// Generate D-Bus representation of snapshot schedule status property.
+#[inline]
+pub fn fs_snapshot_schedule_status_prop(fs: &dyn Filesystem) -> (bool, (String, bool, String)) {
+    prop_conv::fs_snapshot_schedule_status_to_prop(fs.snapshot_schedule_status())
+}
+
+/// Generate D-Bus representation of read-only property.
+#[inline]
+pub fn fs_read_only_prop(fs: &dyn Filesystem) -> bool {
+    fs.read_only()
+}
+
+/// Make a given filesystem read-only or writable.
+#[inline]
+pub fn set_fs_read_only_prop(
+    uuid: FilesystemUuid,
+    pool: &mut dyn Pool,
+    read_only: bool,
+) -> Result<PropChangeAction<bool>, String> {
+    pool.set_fs_read_only(uuid, read_only)
+        .map_err(|e| e.to_string())
+}
+
+/// Generate D-Bus representation of origin property.
+#[inline]
+pub fn fs_origin_prop(fs: &dyn Filesystem) -> (bool, String) {
+    prop_conv::fs_origin_to_prop(fs.origin())
+}
+
 /// Generate D-Bus representation of name property.
 #[inline]
 pub fn fs_name_prop(name: &Name) -> String {
@@ -202,3 +271,14 @@ pub fn fs_size_prop(fs: &dyn Filesystem) -> String {
 pub fn fs_used_prop(fs: &dyn Filesystem) -> (bool, String) {
     prop_conv::fs_used_to_prop(fs.used().ok())
 }
+
+/// Generate D-Bus representation of the space mapped only by the filesystem.
+pub fn fs_exclusive_prop(fs: &dyn Filesystem) -> (bool, String) {
+    prop_conv::fs_used_to_prop(fs.space_usage().map(|u| u.exclusive))
+}
+
+/// Generate D-Bus representation of the space that the filesystem shares
+/// with other filesystems.
+pub fn fs_shared_prop(fs: &dyn Filesystem) -> (bool, String) {
+    prop_conv::fs_used_to_prop(fs.space_usage().map(|u| u.shared))
+}
@@ -0,0 +1,44 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::{Access, EmitsChangedSignal, Factory, MTSync, Property};
+
+use crate::dbus_api::{
+    consts,
+    job::job_3_7::props::{get_job_error, get_job_finished, get_job_progress, get_job_result},
+    types::TData,
+};
+
+pub fn progress_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<u8, _>(consts::JOB_PROGRESS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_job_progress)
+}
+
+pub fn finished_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<bool, _>(consts::JOB_FINISHED_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_job_finished)
+}
+
+pub fn error_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    // b: true if the job failed
+    // s: the error that the job failed with
+    //
+    // Rust representation: (bool, String)
+    f.property::<(bool, &str), _>(consts::JOB_ERROR_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_job_error)
+}
+
+pub fn result_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    // s: a report of what the job did, once it has finished successfully
+    f.property::<&str, _>(consts::JOB_RESULT_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_job_result)
+}
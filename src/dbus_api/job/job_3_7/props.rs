@@ -0,0 +1,44 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus::arg::IterAppend;
+use dbus_tree::{MTSync, MethodErr, PropInfo};
+
+use crate::dbus_api::{
+    job::shared::{self, get_job_property},
+    types::TData,
+};
+
+/// Get the progress of the job represented by an object path as a
+/// percentage.
+pub fn get_job_progress(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_job_property(i, p, |job| job.progress.get())
+}
+
+/// Get whether the job represented by an object path has finished.
+pub fn get_job_finished(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_job_property(i, p, shared::job_finished_prop)
+}
+
+/// Get the report of what the job represented by an object path did.
+pub fn get_job_result(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_job_property(i, p, shared::job_result_prop)
+}
+
+/// Get the error that the job represented by an object path failed with.
+pub fn get_job_error(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_job_property(i, p, shared::job_error_prop)
+}
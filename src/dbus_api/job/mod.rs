@@ -0,0 +1,139 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{future::Future, time::Duration};
+
+use dbus_tree::Factory;
+use tokio::{
+    pin, select, spawn,
+    time::{interval, sleep},
+};
+
+use crate::{
+    dbus_api::{
+        consts,
+        types::{DbusContext, InterfacesAddedThreadSafe, JobState},
+        util::{engine_to_dbus_err_tuple, make_object_path},
+    },
+    engine::JobProgress,
+    stratis::StratisResult,
+};
+
+mod job_3_7;
+mod shared;
+
+/// The interval at which the progress of a running job is checked and a
+/// signal is sent if it has changed.
+const JOB_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the object of a finished job remains on the D-Bus so that
+/// clients can read its result.
+const JOB_RETENTION: Duration = Duration::from_secs(300);
+
+/// Create a D-Bus object for a job whose progress is recorded in progress.
+fn create_dbus_job(dbus_context: &DbusContext, progress: JobProgress) -> dbus::Path<'static> {
+    let f = Factory::new_sync();
+
+    let object_name = make_object_path(dbus_context);
+
+    let object_path = f.object_path(object_name, None).introspectable().add(
+        f.interface(consts::JOB_INTERFACE_NAME_3_7, ())
+            .add_p(job_3_7::progress_property(&f))
+            .add_p(job_3_7::finished_property(&f))
+            .add_p(job_3_7::error_property(&f))
+            .add_p(job_3_7::result_property(&f)),
+    );
+
+    let path = object_path.get_name().to_owned();
+    let job = JobState {
+        progress,
+        result: None,
+    };
+    let interfaces = get_job_properties(&job);
+    dbus_context
+        .jobs
+        .lock()
+        .expect("Mutex only locked internally")
+        .insert(path.clone(), job);
+    dbus_context.push_add(object_path, interfaces);
+    path
+}
+
+/// Get the current state of all properties associated with a job object.
+pub fn get_job_properties(job: &JobState) -> InterfacesAddedThreadSafe {
+    initial_properties! {
+        consts::JOB_INTERFACE_NAME_3_7 => {
+            consts::JOB_PROGRESS_PROP => job.progress.get(),
+            consts::JOB_FINISHED_PROP => shared::job_finished_prop(job),
+            consts::JOB_ERROR_PROP => shared::job_error_prop(job),
+            consts::JOB_RESULT_PROP => shared::job_result_prop(job)
+        }
+    }
+}
+
+/// Run future in the background as a job that is represented by a D-Bus
+/// object. The object reports the progress that the future records in
+/// progress and, once the future has completed, its result: the report that
+/// it returns, or the error that it failed with.
+///
+/// Returns the object path of the job.
+pub fn spawn_dbus_job<F>(
+    dbus_context: &DbusContext,
+    progress: JobProgress,
+    future: F,
+) -> dbus::Path<'static>
+where
+    F: Future<Output = StratisResult<String>> + Send + 'static,
+{
+    let job_path = create_dbus_job(dbus_context, progress.clone());
+
+    let context = dbus_context.clone();
+    let path = job_path.clone();
+    spawn(async move {
+        pin!(future);
+        let mut ticks = interval(JOB_PROGRESS_INTERVAL);
+        let mut last_progress = progress.get();
+        let result = loop {
+            select! {
+                result = &mut future => break result,
+                _ = ticks.tick() => {
+                    let current = progress.get();
+                    if current != last_progress {
+                        context.push_job_progress_change(&path, current);
+                        last_progress = current;
+                    }
+                }
+            }
+        };
+
+        let current = progress.get();
+        if current != last_progress {
+            context.push_job_progress_change(&path, current);
+        }
+
+        let result = result.map_err(|e| engine_to_dbus_err_tuple(&e).1);
+        if let Err(ref e) = result {
+            warn!("Job with path {} failed: {}", path, e);
+        }
+        if let Some(job) = context
+            .jobs
+            .lock()
+            .expect("Mutex only locked internally")
+            .get_mut(&path)
+        {
+            job.result = Some(result.clone());
+        }
+        context.push_job_finished(&path, result);
+
+        sleep(JOB_RETENTION).await;
+        context
+            .jobs
+            .lock()
+            .expect("Mutex only locked internally")
+            .remove(&path);
+        context.push_remove(&path, consts::job_interface_list());
+    });
+
+    job_path
+}
@@ -0,0 +1,57 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus::arg::IterAppend;
+use dbus_tree::{MTSync, MethodErr, PropInfo};
+
+use crate::dbus_api::types::{JobState, TData};
+
+/// Get a job property and place it on the D-Bus. The property is found by
+/// means of the getter method which takes the state of the job and obtains
+/// the property from it.
+pub fn get_job_property<F, R>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+    getter: F,
+) -> Result<(), MethodErr>
+where
+    F: Fn(&JobState) -> R,
+    R: dbus::arg::Append,
+{
+    let object_path = p.path.get_name();
+    let jobs = p
+        .tree
+        .get_data()
+        .jobs
+        .lock()
+        .expect("Mutex only locked internally");
+    let job = jobs
+        .get(object_path)
+        .ok_or_else(|| MethodErr::failed(&format!("no job with object path {object_path}")))?;
+    i.append(getter(job));
+    Ok(())
+}
+
+/// Get whether a job has finished.
+pub fn job_finished_prop(job: &JobState) -> bool {
+    job.result.is_some()
+}
+
+/// Get the report of what a job did.
+/// Returns "" if the job is running or failed.
+pub fn job_result_prop(job: &JobState) -> String {
+    match job.result {
+        Some(Ok(ref report)) => report.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Get the error that a job failed with.
+/// Returns (false, "") if the job is running or succeeded.
+pub fn job_error_prop(job: &JobState) -> (bool, String) {
+    match job.result {
+        Some(Err(ref e)) => (true, e.clone()),
+        _ => (false, String::new()),
+    }
+}
@@ -10,6 +10,7 @@ mod blockdev;
 mod connection;
 mod consts;
 mod filesystem;
+mod job;
 mod pool;
 mod tree;
 mod types;
@@ -18,6 +18,7 @@ mod pool_3_1;
 mod pool_3_3;
 mod pool_3_5;
 mod pool_3_6;
+mod pool_3_7;
 pub mod prop_conv;
 mod shared;
 
@@ -246,11 +247,11 @@ pub fn create_dbus_pool<'a>(
             f.interface(consts::POOL_INTERFACE_NAME_3_7, ())
                 .add_m(pool_3_6::create_filesystems_method(&f))
                 .add_m(pool_3_0::destroy_filesystems_method(&f))
-                .add_m(pool_3_0::snapshot_filesystem_method(&f))
+                .add_m(pool_3_7::snapshot_filesystem_method(&f))
                 .add_m(pool_3_0::add_blockdevs_method(&f))
                 .add_m(pool_3_0::bind_clevis_method(&f))
                 .add_m(pool_3_0::unbind_clevis_method(&f))
-                .add_m(pool_3_5::init_cache_method(&f))
+                .add_m(pool_3_7::init_cache_method(&f))
                 .add_m(pool_3_0::add_cachedevs_method(&f))
                 .add_m(pool_3_0::bind_keyring_method(&f))
                 .add_m(pool_3_0::unbind_keyring_method(&f))
@@ -258,6 +259,16 @@ pub fn create_dbus_pool<'a>(
                 .add_m(pool_3_0::rebind_clevis_method(&f))
                 .add_m(pool_3_0::rename_method(&f))
                 .add_m(pool_3_3::grow_physical_device_method(&f))
+                .add_m(pool_3_7::remove_blockdevs_method(&f))
+                .add_m(pool_3_7::remove_cache_method(&f))
+                .add_m(pool_3_7::replace_blockdev_method(&f))
+                .add_m(pool_3_7::set_cache_settings_method(&f))
+                .add_m(pool_3_7::encrypt_pool_method(&f))
+                .add_m(pool_3_7::reencrypt_pool_method(&f))
+                .add_m(pool_3_7::revert_filesystem_method(&f))
+                .add_m(pool_3_7::snapshot_filesystems_method(&f))
+                .add_m(pool_3_7::add_keyring_binding_method(&f))
+                .add_m(pool_3_7::remove_keyring_binding_method(&f))
                 .add_p(pool_3_0::name_property(&f))
                 .add_p(pool_3_0::uuid_property(&f))
                 .add_p(pool_3_0::encrypted_property(&f))
@@ -270,7 +281,25 @@ pub fn create_dbus_pool<'a>(
                 .add_p(pool_3_0::total_size_property(&f))
                 .add_p(pool_3_1::fs_limit_property(&f))
                 .add_p(pool_3_1::enable_overprov_property(&f))
-                .add_p(pool_3_1::no_alloc_space_property(&f)),
+                .add_p(pool_3_1::no_alloc_space_property(&f))
+                .add_p(pool_3_7::replacement_progress_property(&f))
+                .add_p(pool_3_7::cache_mode_property(&f))
+                .add_p(pool_3_7::cache_policy_property(&f))
+                .add_p(pool_3_7::cache_policy_args_property(&f))
+                .add_p(pool_3_7::cache_read_hits_property(&f))
+                .add_p(pool_3_7::cache_read_misses_property(&f))
+                .add_p(pool_3_7::cache_write_hits_property(&f))
+                .add_p(pool_3_7::cache_write_misses_property(&f))
+                .add_p(pool_3_7::cache_dirty_blocks_property(&f))
+                .add_p(pool_3_7::cache_promotions_property(&f))
+                .add_p(pool_3_7::cache_demotions_property(&f))
+                .add_p(pool_3_7::redundancy_property(&f))
+                .add_p(pool_3_7::degraded_property(&f))
+                .add_p(pool_3_7::data_shrink_property(&f))
+                .add_p(pool_3_7::stripe_size_property(&f))
+                .add_p(pool_3_7::encryption_progress_property(&f))
+                .add_p(pool_3_7::reencryption_progress_property(&f))
+                .add_p(pool_3_7::keyring_bindings_property(&f)),
         );
 
     let path = object_path.get_name().to_owned();
@@ -401,7 +430,25 @@ pub fn get_pool_properties(
             consts::POOL_TOTAL_SIZE_PROP => shared::pool_total_size(pool),
             consts::POOL_FS_LIMIT_PROP => shared::pool_fs_limit(pool),
             consts::POOL_OVERPROV_PROP => shared::pool_overprov_enabled(pool),
-            consts::POOL_NO_ALLOCABLE_SPACE_PROP => shared::pool_no_alloc_space(pool)
+            consts::POOL_NO_ALLOCABLE_SPACE_PROP => shared::pool_no_alloc_space(pool),
+            consts::POOL_REPLACEMENT_PROGRESS_PROP => shared::pool_replacement_progress(pool),
+            consts::POOL_CACHE_MODE_PROP => shared::pool_cache_mode(pool),
+            consts::POOL_CACHE_POLICY_PROP => shared::pool_cache_policy(pool),
+            consts::POOL_CACHE_POLICY_ARGS_PROP => shared::pool_cache_policy_args(pool),
+            consts::POOL_CACHE_READ_HITS_PROP => shared::pool_cache_read_hits(pool),
+            consts::POOL_CACHE_READ_MISSES_PROP => shared::pool_cache_read_misses(pool),
+            consts::POOL_CACHE_WRITE_HITS_PROP => shared::pool_cache_write_hits(pool),
+            consts::POOL_CACHE_WRITE_MISSES_PROP => shared::pool_cache_write_misses(pool),
+            consts::POOL_CACHE_DIRTY_BLOCKS_PROP => shared::pool_cache_dirty_blocks(pool),
+            consts::POOL_CACHE_PROMOTIONS_PROP => shared::pool_cache_promotions(pool),
+            consts::POOL_CACHE_DEMOTIONS_PROP => shared::pool_cache_demotions(pool),
+            consts::POOL_REDUNDANCY_PROP => shared::pool_redundancy(pool),
+            consts::POOL_DEGRADED_PROP => shared::pool_degraded(pool),
+            consts::POOL_DATA_SHRINK_PROP => shared::pool_data_shrink(pool),
+            consts::POOL_STRIPE_SIZE_PROP => shared::pool_stripe_size(pool),
+            consts::POOL_ENCRYPTION_PROGRESS_PROP => shared::pool_encryption_progress(pool),
+            consts::POOL_REENCRYPTION_PROGRESS_PROP => shared::pool_reencryption_progress(pool),
+            consts::POOL_KEYRING_BINDINGS_PROP => shared::pool_keyring_bindings(pool)
         }
     }
 }
@@ -246,7 +246,7 @@ pub fn snapshot_filesystem(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodRe
     let (pool_name, _, pool) = guard.as_mut_tuple();
 
     let msg = match handle_action!(
-        pool.snapshot_filesystem(&pool_name, pool_uuid, fs_uuid, snapshot_name),
+        pool.snapshot_filesystem(&pool_name, pool_uuid, fs_uuid, snapshot_name, false),
         dbus_context,
         pool_path.get_name()
     ) {
@@ -4,11 +4,14 @@
 
 use dbus_tree::{MTSync, MethodInfo, MethodResult};
 
-use crate::dbus_api::{
-    pool::shared::{add_blockdevs, BlockDevOp},
-    types::TData,
+use crate::{
+    dbus_api::{
+        pool::shared::{add_blockdevs, BlockDevOp},
+        types::TData,
+    },
+    engine::CacheSettings,
 };
 
 pub fn init_cache(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
-    add_blockdevs(m, BlockDevOp::InitCacheWithEnc)
+    add_blockdevs(m, BlockDevOp::InitCacheWithEnc(CacheSettings::default()))
 }
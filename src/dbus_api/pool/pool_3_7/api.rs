@@ -0,0 +1,394 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::{Access, EmitsChangedSignal, Factory, MTSync, Method, Property};
+
+use crate::dbus_api::{
+    consts,
+    pool::pool_3_7::{
+        methods::{
+            add_keyring_binding, encrypt_pool, init_cache, reencrypt_pool, remove_blockdevs,
+            remove_cache, remove_keyring_binding, replace_blockdev, revert_filesystem,
+            set_cache_settings, snapshot_filesystem, snapshot_filesystems,
+        },
+        props::{
+            get_cache_demotions, get_cache_dirty_blocks, get_cache_mode, get_cache_policy,
+            get_cache_policy_args, get_cache_promotions, get_cache_read_hits,
+            get_cache_read_misses, get_cache_write_hits, get_cache_write_misses,
+            get_encryption_progress, get_keyring_bindings, get_pool_data_shrink, get_pool_degraded,
+            get_pool_redundancy, get_pool_stripe_size, get_reencryption_progress,
+            get_replacement_progress, set_pool_stripe_size,
+        },
+    },
+    types::TData,
+};
+
+pub fn init_cache_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("InitCache", (), init_cache)
+        .in_arg(("devices", "as"))
+        // One of "writethrough", "writeback", or "passthrough"
+        .in_arg(("mode", "s"))
+        .in_arg(("policy", "s"))
+        // Array of (key, value) pairs passed to the policy
+        .in_arg(("policy_args", "a(ss)"))
+        // b: Indicates if any cache devices were added
+        // ao: Array of object paths of created cache devices
+        //
+        // Rust representation: (bool, Vec<dbus::path>)
+        .out_arg(("results", "(bao)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn remove_blockdevs_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("RemoveBlockdevs", (), remove_blockdevs)
+        .in_arg(("blockdevs", "ao"))
+        // b: true if blockdevs are being removed
+        // as: Array of UUIDs of blockdevs being removed; their data is
+        //     copied to the remaining blockdevs in the background
+        //     (see ReplacementProgress)
+        //
+        // Rust representation: (bool, Vec<String>)
+        .out_arg(("results", "(bas)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn remove_cache_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("RemoveCache", (), remove_cache)
+        // b: true if the cache was removed
+        // as: Array of UUIDs of removed cache blockdevs
+        //
+        // Rust representation: (bool, Vec<String>)
+        .out_arg(("results", "(bas)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn replace_blockdev_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("ReplaceBlockdev", (), replace_blockdev)
+        .in_arg(("blockdev", "o"))
+        .in_arg(("device", "s"))
+        // b: true if a replacement was started
+        // o: Object path of the blockdev that replaces the given blockdev
+        //
+        // Rust representation: (bool, dbus::Path)
+        .out_arg(("results", "(bo)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn set_cache_settings_method(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Method<MTSync<TData>, TData> {
+    f.method("SetCacheSettings", (), set_cache_settings)
+        // One of "writethrough", "writeback", or "passthrough"
+        .in_arg(("mode", "s"))
+        .in_arg(("policy", "s"))
+        // Array of (key, value) pairs passed to the policy
+        .in_arg(("policy_args", "a(ss)"))
+        // b: true if the cache settings were changed
+        .out_arg(("results", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn replacement_progress_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, u8), _>(consts::POOL_REPLACEMENT_PROGRESS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_replacement_progress)
+}
+
+pub fn cache_mode_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, &str), _>(consts::POOL_CACHE_MODE_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Invalidates)
+        .on_get(get_cache_mode)
+}
+
+pub fn cache_policy_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, &str), _>(consts::POOL_CACHE_POLICY_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Invalidates)
+        .on_get(get_cache_policy)
+}
+
+pub fn cache_policy_args_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, Vec<(&str, &str)>), _>(consts::POOL_CACHE_POLICY_ARGS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Invalidates)
+        .on_get(get_cache_policy_args)
+}
+
+pub fn encrypt_pool_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("EncryptPool", (), encrypt_pool)
+        // Optional key description of key in the kernel keyring
+        // b: true if the pool should be able to be unlocked with a
+        // passphrase associated with this key description.
+        // s: key description
+        //
+        // Rust representation: (bool, String)
+        .in_arg(("key_desc", "(bs)"))
+        // Optional Clevis information for binding.
+        // b: true if the pool should be able to be unlocked using Clevis.
+        // s: pin name
+        // s: JSON config for Clevis use
+        //
+        // Rust representation: (bool, (String, String))
+        .in_arg(("clevis_info", "(b(ss))"))
+        // Optional parameters for the LUKS2 format of the devices; each
+        // parameter that is not specified is chosen as for a new pool.
+        // b: true if the parameter is specified
+        // s: cipher and mode, e.g. "aes-xts-plain64"
+        // u: key size in bits
+        // u: encryption sector size in bytes; defaults to the logical
+        //    sector size of the devices
+        // s: PBKDF; "pbkdf2", "argon2i" or "argon2id"
+        // u: memory cost of an Argon2 PBKDF in KiB
+        // u: number of iterations of the PBKDF
+        // u: number of parallel threads of an Argon2 PBKDF
+        //
+        // Rust representation: (bool, String) or (bool, u32)
+        .in_arg(("cipher", "(bs)"))
+        .in_arg(("key_size", "(bu)"))
+        .in_arg(("sector_size", "(bu)"))
+        .in_arg(("pbkdf", "(bs)"))
+        .in_arg(("pbkdf_memory", "(bu)"))
+        .in_arg(("pbkdf_iterations", "(bu)"))
+        .in_arg(("pbkdf_parallel", "(bu)"))
+        // b: true if the encryption of the pool was started
+        .out_arg(("results", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn reencrypt_pool_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("ReencryptPool", (), reencrypt_pool)
+        // Optional parameters for the new volume keys and the keyslots that
+        // are added for them; the cipher and the key size default to those
+        // of the current volume keys. The encryption sector size can not be
+        // changed.
+        // b: true if the parameter is specified
+        // s: cipher and mode, e.g. "aes-xts-plain64"
+        // u: key size in bits
+        // u: encryption sector size in bytes
+        // s: PBKDF; "pbkdf2", "argon2i" or "argon2id"
+        // u: memory cost of an Argon2 PBKDF in KiB
+        // u: number of iterations of the PBKDF
+        // u: number of parallel threads of an Argon2 PBKDF
+        //
+        // Rust representation: (bool, String) or (bool, u32)
+        .in_arg(("cipher", "(bs)"))
+        .in_arg(("key_size", "(bu)"))
+        .in_arg(("sector_size", "(bu)"))
+        .in_arg(("pbkdf", "(bs)"))
+        .in_arg(("pbkdf_memory", "(bu)"))
+        .in_arg(("pbkdf_iterations", "(bu)"))
+        .in_arg(("pbkdf_parallel", "(bu)"))
+        // b: true if the reencryption of the pool was started
+        .out_arg(("results", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn revert_filesystem_method(f: &Factory<MTSync<TData>, TData>) -> Method<MTSync<TData>, TData> {
+    f.method("RevertFilesystem", (), revert_filesystem)
+        // The filesystem to revert; it keeps its name, UUID and devlinks
+        .in_arg(("origin", "o"))
+        // A snapshot of the origin; it is left with the previous contents
+        // of the origin
+        .in_arg(("snapshot", "o"))
+        // b: true if the origin was reverted to the snapshot
+        .out_arg(("results", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn snapshot_filesystem_method(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Method<MTSync<TData>, TData> {
+    f.method("SnapshotFilesystem", (), snapshot_filesystem)
+        .in_arg(("origin", "o"))
+        .in_arg(("snapshot_name", "s"))
+        // true if the snapshot should be read-only
+        .in_arg(("read_only", "b"))
+        // b: false if no new snapshot was created
+        // s: Object path of new snapshot
+        //
+        // Rust representation: (bool, String)
+        .out_arg(("result", "(bo)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn snapshot_filesystems_method(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Method<MTSync<TData>, TData> {
+    f.method("SnapshotFilesystems", (), snapshot_filesystems)
+        // The filesystems to snapshot at the same point in time
+        // o: Object path of the origin filesystem
+        // s: Name of the snapshot of the origin
+        //
+        // Rust representation: Vec<(dbus::Path, String)>
+        .in_arg(("specs", "a(os)"))
+        // b: true if the snapshots were created
+        // a(os): Array of tuples with object paths and names of the new
+        // snapshots, in the order of specs
+        //
+        // Rust representation: (bool, Vec<(dbus::Path, String)>)
+        .out_arg(("results", "(ba(os))"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn add_keyring_binding_method(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Method<MTSync<TData>, TData> {
+    f.method("AddKeyringBinding", (), add_keyring_binding)
+        // The key description of a key in the kernel keyring whose
+        // passphrase should unlock the pool in addition to the existing
+        // bindings
+        .in_arg(("key_desc", "s"))
+        // b: true if the binding was added
+        .out_arg(("results", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn remove_keyring_binding_method(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Method<MTSync<TData>, TData> {
+    f.method("RemoveKeyringBinding", (), remove_keyring_binding)
+        // The key description of an additional keyring binding
+        .in_arg(("key_desc", "s"))
+        // b: true if the binding was removed
+        .out_arg(("results", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn cache_read_hits_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, u64), _>(consts::POOL_CACHE_READ_HITS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_cache_read_hits)
+}
+
+pub fn cache_read_misses_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, u64), _>(consts::POOL_CACHE_READ_MISSES_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_cache_read_misses)
+}
+
+pub fn cache_write_hits_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, u64), _>(consts::POOL_CACHE_WRITE_HITS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_cache_write_hits)
+}
+
+pub fn cache_write_misses_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, u64), _>(consts::POOL_CACHE_WRITE_MISSES_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_cache_write_misses)
+}
+
+pub fn cache_dirty_blocks_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, u64), _>(consts::POOL_CACHE_DIRTY_BLOCKS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_cache_dirty_blocks)
+}
+
+pub fn cache_promotions_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, u64), _>(consts::POOL_CACHE_PROMOTIONS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_cache_promotions)
+}
+
+pub fn cache_demotions_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, u64), _>(consts::POOL_CACHE_DEMOTIONS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_cache_demotions)
+}
+
+pub fn redundancy_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<u16, _>(consts::POOL_REDUNDANCY_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_pool_redundancy)
+}
+
+pub fn degraded_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<bool, _>(consts::POOL_DEGRADED_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_pool_degraded)
+}
+
+pub fn data_shrink_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, String), _>(consts::POOL_DATA_SHRINK_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_pool_data_shrink)
+}
+
+pub fn encryption_progress_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, u8), _>(consts::POOL_ENCRYPTION_PROGRESS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_encryption_progress)
+}
+
+pub fn reencryption_progress_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, u8), _>(consts::POOL_REENCRYPTION_PROGRESS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_reencryption_progress)
+}
+
+pub fn stripe_size_property(f: &Factory<MTSync<TData>, TData>) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, String), _>(consts::POOL_STRIPE_SIZE_PROP, ())
+        .access(Access::ReadWrite)
+        .emits_changed(EmitsChangedSignal::True)
+        .auto_emit_on_set(false)
+        .on_get(get_pool_stripe_size)
+        .on_set(set_pool_stripe_size)
+}
+
+pub fn keyring_bindings_property(
+    f: &Factory<MTSync<TData>, TData>,
+) -> Property<MTSync<TData>, TData> {
+    f.property::<(bool, Vec<String>), _>(consts::POOL_KEYRING_BINDINGS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_keyring_bindings)
+}
@@ -0,0 +1,881 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{collections::HashMap, path::Path};
+
+use dbus::{
+    arg::{Array, Iter},
+    Message,
+};
+use dbus_tree::MethodErr;
+use dbus_tree::{MTSync, MethodInfo, MethodResult};
+use futures::executor::block_on;
+
+use crate::{
+    dbus_api::{
+        blockdev::create_dbus_blockdev,
+        consts::blockdev_interface_list,
+        filesystem::create_dbus_filesystem,
+        pool::shared::{add_blockdevs, BlockDevOp},
+        types::{DbusErrorEnum, TData, OK_STRING},
+        util::{engine_to_dbus_err_tuple, get_crypt_params_args, get_next_arg, tuple_to_option},
+    },
+    engine::{
+        total_allocated, total_used, BlockDevTier, CacheMode, CacheSettings, CreateAction,
+        DeleteAction, DevUuid, Diff, EncryptionInfo, EngineAction, KeyDescription, Name,
+        StartAction, StratisUuid,
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// Read the cache mode, the cache policy, and the policy arguments from the
+/// arguments of a method, beginning with the argument at index loc.
+fn get_cache_settings(
+    iter: &mut Iter<'_>,
+    loc: u16,
+) -> Result<StratisResult<CacheSettings>, MethodErr> {
+    let mode: &str = get_next_arg(iter, loc)?;
+    let policy: &str = get_next_arg(iter, loc + 1)?;
+    let policy_args: Array<'_, (&str, &str), _> = get_next_arg(iter, loc + 2)?;
+
+    Ok(CacheMode::try_from(mode).and_then(|mode| {
+        CacheSettings::new(
+            mode,
+            policy.to_string(),
+            policy_args
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }))
+}
+
+pub fn init_cache(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    // The devices are read by add_blockdevs().
+    iter.next();
+    let settings = match get_cache_settings(&mut iter, 1)? {
+        Ok(settings) => settings,
+        Err(err) => {
+            let default_return: (bool, Vec<dbus::Path<'_>>) = (false, Vec::new());
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return Ok(vec![message.method_return().append3(
+                default_return,
+                rc,
+                rs,
+            )]);
+        }
+    };
+
+    add_blockdevs(m, BlockDevOp::InitCacheWithEnc(settings))
+}
+
+pub fn remove_blockdevs(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let blockdevs: Array<'_, dbus::Path<'static>, _> = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return: (bool, Vec<String>) = (false, Vec::new());
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let mut blockdev_map: HashMap<DevUuid, dbus::Path<'static>> = HashMap::new();
+    for path in blockdevs {
+        if let Some((u, path)) = m.tree.get(&path).and_then(|op| {
+            op.get_data()
+                .as_ref()
+                .map(|d| (&d.uuid, op.get_name().clone()))
+        }) {
+            let uuid = *typed_uuid!(u; Dev; default_return; return_message);
+            blockdev_map.insert(uuid, path);
+        }
+    }
+
+    let result = handle_action!(
+        pool.remove_blockdevs(
+            pool_uuid,
+            &pool_name,
+            &blockdev_map.keys().cloned().collect::<Vec<_>>(),
+        )
+        .map(|(act, diff)| {
+            if act.is_changed() {
+                if let Some(d) = diff {
+                    dbus_context.push_pool_foreground_change(
+                        pool_path.get_name(),
+                        total_used(&d.thin_pool.used, &d.pool.metadata_size),
+                        total_allocated(&d.thin_pool.allocated_size, &d.pool.metadata_size),
+                        Diff::Changed(pool.total_physical_size().bytes()),
+                        d.pool.out_of_alloc_space,
+                    )
+                }
+            }
+            act
+        }),
+        dbus_context,
+        pool_path.get_name()
+    );
+    let msg = match result {
+        Ok(uuids) => {
+            // Only get changed values here as non-existent blockdevs will have been filtered out
+            // before calling remove_blockdevs
+            let uuid_vec: Vec<String> = if let Some(ref changed_uuids) = uuids.changed() {
+                for uuid in changed_uuids {
+                    let op = blockdev_map
+                        .get(uuid)
+                        .expect("'uuids' is a subset of blockdev_map.keys()");
+                    dbus_context.push_remove(op, blockdev_interface_list());
+                }
+                changed_uuids
+                    .iter()
+                    .map(|uuid| uuid_to_string!(uuid))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            return_message.append3(
+                (true, uuid_vec),
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )
+        }
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
+
+pub fn replace_blockdev(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let blockdev: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let device: &str = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = (false, dbus::Path::default());
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let old_uuid = match m.tree.get(&blockdev) {
+        Some(op) => typed_uuid!(
+            get_data!(op; default_return; return_message).uuid;
+            Dev;
+            default_return;
+            return_message
+        ),
+        None => {
+            let message = format!("no data for object path {blockdev}");
+            let (rc, rs) = (DbusErrorEnum::ERROR as u16, message);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let result = handle_action!(
+        pool.replace_blockdev(pool_uuid, &pool_name, old_uuid, Path::new(device))
+            .map(|(act, diff)| {
+                if act.is_changed() {
+                    if let Some(d) = diff {
+                        dbus_context.push_pool_foreground_change(
+                            pool_path.get_name(),
+                            total_used(&d.thin_pool.used, &d.pool.metadata_size),
+                            total_allocated(&d.thin_pool.allocated_size, &d.pool.metadata_size),
+                            Diff::Changed(pool.total_physical_size().bytes()),
+                            d.pool.out_of_alloc_space,
+                        )
+                    }
+                }
+                act
+            }),
+        dbus_context,
+        pool_path.get_name()
+    );
+    let msg = match result {
+        Ok(CreateAction::Created(uuid)) => {
+            // The replaced blockdev no longer holds any data that the pool
+            // will use once the copy completes, so it is removed from the
+            // D-Bus API immediately.
+            dbus_context.push_remove(&blockdev, blockdev_interface_list());
+            let bd_object_path: dbus::Path<'_> = create_dbus_blockdev(
+                dbus_context,
+                object_path.clone(),
+                uuid,
+                BlockDevTier::Data,
+                pool.get_blockdev(uuid)
+                    .expect("just inserted by replace_blockdev")
+                    .1,
+            );
+            return_message.append3(
+                (true, bd_object_path),
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )
+        }
+        Ok(CreateAction::Identity) => return_message.append3(
+            default_return,
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        ),
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+
+    Ok(vec![msg])
+}
+
+pub fn remove_cache(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return: (bool, Vec<String>) = (false, Vec::new());
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    // The engine locks the pool only for parts of the removal, so that
+    // other requests for the pool are served while the cache is flushed.
+    let result = handle_action!(
+        block_on(dbus_context.engine.remove_cache(pool_uuid)),
+        dbus_context,
+        pool_path.get_name()
+    );
+    let msg = match result {
+        Ok(uuids) => {
+            let uuid_vec: Vec<String> = if let Some(ref changed_uuids) = uuids.changed() {
+                for uuid in changed_uuids {
+                    match m.tree.iter().find(|op| {
+                        op.get_data()
+                            .as_ref()
+                            .map(|data| match data.uuid {
+                                StratisUuid::Dev(u) => u == *uuid,
+                                _ => false,
+                            })
+                            .unwrap_or(false)
+                    }) {
+                        Some(op) => {
+                            dbus_context.push_remove(op.get_name(), blockdev_interface_list())
+                        }
+                        None => {
+                            warn!("Could not find object path for blockdev uuid {uuid}; the object will not be removed");
+                        }
+                    }
+                }
+                dbus_context.push_pool_cache_change(pool_path.get_name(), false);
+                changed_uuids
+                    .iter()
+                    .map(|uuid| uuid_to_string!(uuid))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            return_message.append3(
+                (true, uuid_vec),
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )
+        }
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
+
+pub fn set_cache_settings(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let settings = match get_cache_settings(&mut iter, 0)? {
+        Ok(settings) => settings,
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let result = handle_action!(
+        pool.set_cache_settings(&pool_name, settings),
+        dbus_context,
+        pool_path.get_name()
+    );
+    let msg = match result {
+        Ok(action) => {
+            let changed = action.is_changed();
+            if changed {
+                dbus_context.push_pool_cache_change(pool_path.get_name(), true);
+            }
+            return_message.append3(changed, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
+
+pub fn encrypt_pool(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let key_desc_tuple: (bool, String) = get_next_arg(&mut iter, 0)?;
+    let clevis_tuple: (bool, (String, String)) = get_next_arg(&mut iter, 1)?;
+    let crypt_params_res = get_crypt_params_args(&mut iter, 2)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let key_desc = match tuple_to_option(key_desc_tuple) {
+        Some(kds) => match KeyDescription::try_from(kds) {
+            Ok(kd) => Some(kd),
+            Err(e) => {
+                let (rc, rs) = engine_to_dbus_err_tuple(&e);
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        },
+        None => None,
+    };
+
+    let clevis_info = match tuple_to_option(clevis_tuple) {
+        Some((pin, json_string)) => match serde_json::from_str(json_string.as_str()) {
+            Ok(j) => Some((pin, j)),
+            Err(e) => {
+                let (rc, rs) = engine_to_dbus_err_tuple(&StratisError::Serde(e));
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        },
+        None => None,
+    };
+
+    let encryption_info = match EncryptionInfo::from_options((key_desc, clevis_info)) {
+        Some(ei) => ei,
+        None => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&StratisError::Msg(
+                "Either a key description or Clevis info must be specified to encrypt a pool"
+                    .to_string(),
+            ));
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let crypt_params = match crypt_params_res {
+        Ok(params) => params,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let result = handle_action!(
+        pool.encrypt_pool(
+            pool_uuid,
+            &pool_name,
+            &encryption_info,
+            crypt_params.as_ref(),
+        )
+        .map(|(act, diff)| {
+            if act.is_changed() {
+                if let Some(d) = diff {
+                    dbus_context.push_pool_foreground_change(
+                        pool_path.get_name(),
+                        total_used(&d.thin_pool.used, &d.pool.metadata_size),
+                        total_allocated(&d.thin_pool.allocated_size, &d.pool.metadata_size),
+                        Diff::Changed(pool.total_physical_size().bytes()),
+                        d.pool.out_of_alloc_space,
+                    )
+                }
+            }
+            act
+        }),
+        dbus_context,
+        pool_path.get_name()
+    );
+    let msg = match result {
+        Ok(CreateAction::Identity) => {
+            return_message.append3(false, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Ok(CreateAction::Created(_)) => {
+            dbus_context.push_pool_key_desc_change(pool_path.get_name(), pool.encryption_info());
+            dbus_context.push_pool_clevis_info_change(pool_path.get_name(), pool.encryption_info());
+            return_message.append3(true, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
+
+pub fn reencrypt_pool(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let crypt_params_res = get_crypt_params_args(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let crypt_params = match crypt_params_res {
+        Ok(params) => params,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let result = handle_action!(
+        pool.reencrypt_pool(&pool_name, crypt_params.as_ref())
+            .map(|(act, _)| act),
+        dbus_context,
+        pool_path.get_name()
+    );
+    let msg = match result {
+        Ok(StartAction::Identity) => {
+            return_message.append3(false, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Ok(StartAction::Started(_)) => {
+            return_message.append3(true, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
+
+pub fn revert_filesystem(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let origin: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let snapshot: dbus::Path<'static> = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let origin_uuid = match m.tree.get(&origin) {
+        Some(op) => typed_uuid!(
+            get_data!(op; default_return; return_message).uuid;
+            Fs;
+            default_return;
+            return_message
+        ),
+        None => {
+            let message = format!("no data for object path {origin}");
+            let (rc, rs) = (DbusErrorEnum::ERROR as u16, message);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+    let snapshot_uuid = match m.tree.get(&snapshot) {
+        Some(op) => typed_uuid!(
+            get_data!(op; default_return; return_message).uuid;
+            Fs;
+            default_return;
+            return_message
+        ),
+        None => {
+            let message = format!("no data for object path {snapshot}");
+            let (rc, rs) = (DbusErrorEnum::ERROR as u16, message);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let result = handle_action!(
+        pool.revert_filesystem(&pool_name, origin_uuid, snapshot_uuid)
+            .map(|(act, diffs)| {
+                dbus_context.push_fs_changes(diffs);
+                act
+            }),
+        dbus_context,
+        pool_path.get_name()
+    );
+    let msg = match result {
+        Ok(_) => return_message.append3(true, DbusErrorEnum::OK as u16, OK_STRING.to_string()),
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
+
+pub fn snapshot_filesystem(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let filesystem: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let snapshot_name: &str = get_next_arg(&mut iter, 1)?;
+    let read_only: bool = get_next_arg(&mut iter, 2)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = (false, dbus::Path::default());
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let fs_uuid = match m.tree.get(&filesystem) {
+        Some(op) => typed_uuid!(
+            get_data!(op; default_return; return_message).uuid;
+            Fs;
+            default_return;
+            return_message
+        ),
+        None => {
+            let message = format!("no data for object path {filesystem}");
+            let (rc, rs) = (DbusErrorEnum::ERROR as u16, message);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let msg = match handle_action!(
+        pool.snapshot_filesystem(&pool_name, pool_uuid, fs_uuid, snapshot_name, read_only),
+        dbus_context,
+        pool_path.get_name()
+    ) {
+        Ok(CreateAction::Created((uuid, fs))) => {
+            let fs_object_path: dbus::Path<'_> = create_dbus_filesystem(
+                dbus_context,
+                object_path.clone(),
+                &pool_name,
+                &Name::new(snapshot_name.to_string()),
+                uuid,
+                fs,
+            );
+            return_message.append3(
+                (true, fs_object_path),
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )
+        }
+        Ok(CreateAction::Identity) => return_message.append3(
+            default_return,
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        ),
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+
+    Ok(vec![msg])
+}
+
+pub fn snapshot_filesystems(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let specs: Array<'_, (dbus::Path<'static>, &str), _> = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return: (bool, Vec<(dbus::Path<'_>, &str)>) = (false, Vec::new());
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut snapshot_specs = Vec::new();
+    for (origin, snapshot_name) in specs {
+        let origin_uuid = match m.tree.get(&origin) {
+            Some(op) => typed_uuid!(
+                get_data!(op; default_return; return_message).uuid;
+                Fs;
+                default_return;
+                return_message
+            ),
+            None => {
+                let message = format!("no data for object path {origin}");
+                let (rc, rs) = (DbusErrorEnum::ERROR as u16, message);
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        };
+        snapshot_specs.push((origin_uuid, snapshot_name));
+    }
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let result = handle_action!(
+        pool.snapshot_filesystems(&pool_name, pool_uuid, &snapshot_specs),
+        dbus_context,
+        pool_path.get_name()
+    );
+
+    let created = match result {
+        Ok(created_set) => created_set.changed(),
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let return_value = match created {
+        Some(snapshots) => {
+            let v = snapshots
+                .into_iter()
+                .map(|(name, uuid)| {
+                    let snapshot = pool
+                        .get_filesystem(uuid)
+                        .expect("just inserted by snapshot_filesystems")
+                        .1;
+                    (
+                        create_dbus_filesystem(
+                            dbus_context,
+                            object_path.clone(),
+                            &pool_name,
+                            &Name::new(name.to_string()),
+                            uuid,
+                            snapshot,
+                        ),
+                        name,
+                    )
+                })
+                .collect::<Vec<_>>();
+            (true, v)
+        }
+        None => default_return,
+    };
+
+    Ok(vec![return_message.append3(
+        return_value,
+        DbusErrorEnum::OK as u16,
+        OK_STRING.to_string(),
+    )])
+}
+
+pub fn add_keyring_binding(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let key_desc_str: String = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let key_desc = match KeyDescription::try_from(key_desc_str) {
+        Ok(kd) => kd,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut pool = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+
+    let msg = match handle_action!(
+        pool.add_keyring_binding(&key_desc),
+        dbus_context,
+        pool_path.get_name()
+    ) {
+        Ok(CreateAction::Identity) => {
+            return_message.append3(false, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Ok(CreateAction::Created(_)) => {
+            dbus_context.push_pool_key_desc_change(pool_path.get_name(), pool.encryption_info());
+            return_message.append3(true, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
+
+pub fn remove_keyring_binding(m: &MethodInfo<'_, MTSync<TData>, TData>) -> MethodResult {
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let key_desc_str: String = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let key_desc = match KeyDescription::try_from(key_desc_str) {
+        Ok(kd) => kd,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut pool = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+
+    let msg = match handle_action!(
+        pool.remove_keyring_binding(&key_desc),
+        dbus_context,
+        pool_path.get_name()
+    ) {
+        Ok(DeleteAction::Identity) => {
+            return_message.append3(false, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Ok(DeleteAction::Deleted(_)) => {
+            dbus_context.push_pool_key_desc_change(pool_path.get_name(), pool.encryption_info());
+            return_message.append3(true, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
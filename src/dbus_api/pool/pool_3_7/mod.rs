@@ -0,0 +1,20 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+mod api;
+mod methods;
+mod props;
+
+pub use api::{
+    add_keyring_binding_method, cache_demotions_property, cache_dirty_blocks_property,
+    cache_mode_property, cache_policy_args_property, cache_policy_property,
+    cache_promotions_property, cache_read_hits_property, cache_read_misses_property,
+    cache_write_hits_property, cache_write_misses_property, data_shrink_property,
+    degraded_property, encrypt_pool_method, encryption_progress_property, init_cache_method,
+    keyring_bindings_property, redundancy_property, reencrypt_pool_method,
+    reencryption_progress_property, remove_blockdevs_method, remove_cache_method,
+    remove_keyring_binding_method, replace_blockdev_method, replacement_progress_property,
+    revert_filesystem_method, set_cache_settings_method, snapshot_filesystem_method,
+    snapshot_filesystems_method, stripe_size_property,
+};
@@ -0,0 +1,196 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus::arg::{Iter, IterAppend};
+use dbus_tree::{MTSync, MethodErr, PropInfo};
+
+use devicemapper::{Sectors, SECTOR_SIZE};
+
+use crate::{
+    dbus_api::{
+        consts,
+        pool::shared::{self, get_pool_property, set_pool_property},
+        types::TData,
+        util::tuple_to_option,
+    },
+    engine::{AllocationPolicy, PropChangeAction},
+};
+
+pub fn get_replacement_progress(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_replacement_progress(pool))
+    })
+}
+
+pub fn get_cache_mode(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(shared::pool_cache_mode(pool)))
+}
+
+pub fn get_cache_policy(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(shared::pool_cache_policy(pool)))
+}
+
+pub fn get_cache_policy_args(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_cache_policy_args(pool))
+    })
+}
+
+pub fn get_cache_read_hits(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(shared::pool_cache_read_hits(pool)))
+}
+
+pub fn get_cache_read_misses(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_cache_read_misses(pool))
+    })
+}
+
+pub fn get_cache_write_hits(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(shared::pool_cache_write_hits(pool)))
+}
+
+pub fn get_cache_write_misses(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_cache_write_misses(pool))
+    })
+}
+
+pub fn get_cache_dirty_blocks(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_cache_dirty_blocks(pool))
+    })
+}
+
+pub fn get_cache_promotions(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(shared::pool_cache_promotions(pool)))
+}
+
+pub fn get_cache_demotions(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(shared::pool_cache_demotions(pool)))
+}
+
+pub fn get_pool_redundancy(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(shared::pool_redundancy(pool)))
+}
+
+pub fn get_pool_degraded(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(shared::pool_degraded(pool)))
+}
+
+pub fn get_pool_data_shrink(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(shared::pool_data_shrink(pool)))
+}
+
+pub fn get_encryption_progress(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_encryption_progress(pool))
+    })
+}
+
+pub fn get_keyring_bindings(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(shared::pool_keyring_bindings(pool)))
+}
+
+pub fn get_reencryption_progress(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_reencryption_progress(pool))
+    })
+}
+
+pub fn get_pool_stripe_size(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    get_pool_property(i, p, |(_, _, pool)| Ok(shared::pool_stripe_size(pool)))
+}
+
+pub fn set_pool_stripe_size(
+    i: &mut Iter<'_>,
+    p: &PropInfo<'_, MTSync<TData>, TData>,
+) -> Result<(), MethodErr> {
+    let stripe_size_opt: (bool, &str) = i.get().ok_or_else(|| {
+        MethodErr::failed("New stripe size required as argument to change allocation policy")
+    })?;
+    let policy = match tuple_to_option(stripe_size_opt) {
+        Some(size) => {
+            let bytes = size.parse::<u64>().map_err(|e| {
+                MethodErr::failed(&format!("Failed to parse {size} as unsigned integer: {e}"))
+            })?;
+            if bytes % SECTOR_SIZE as u64 != 0 {
+                return Err(MethodErr::failed(&format!(
+                    "Stripe size {bytes} is not a multiple of the sector size {SECTOR_SIZE}"
+                )));
+            }
+            AllocationPolicy::striped(Sectors(bytes / SECTOR_SIZE as u64))
+                .map_err(|e| MethodErr::failed(&e.to_string()))?
+        }
+        None => AllocationPolicy::Linear,
+    };
+
+    let res = set_pool_property(p, consts::POOL_STRIPE_SIZE_PROP, |(name, _, pool)| {
+        shared::pool_set_allocation_policy(pool, &name, policy)
+    });
+    match res {
+        Ok(PropChangeAction::NewValue(v)) => {
+            p.tree
+                .get_data()
+                .push_pool_stripe_size_change(p.path.get_name(), v);
+            Ok(())
+        }
+        Ok(PropChangeAction::Identity) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
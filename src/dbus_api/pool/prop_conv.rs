@@ -6,7 +6,10 @@ use devicemapper::Bytes;
 
 use crate::{
     dbus_api::util::option_to_tuple,
-    engine::{ActionAvailability, PoolEncryptionInfo},
+    engine::{
+        ActionAvailability, AllocationPolicy, CacheSettings, CacheStats, DataShrink,
+        PoolEncryptionInfo,
+    },
     stratis::StratisResult,
 };
 
@@ -39,6 +42,24 @@ pub fn key_desc_to_prop(ei: Option<PoolEncryptionInfo>) -> (bool, (bool, String)
     )
 }
 
+/// Fetch the additional keyring bindings and handle converting them into a
+/// D-Bus type. The bindings are invalid if the pool is not encrypted or the
+/// bindings are inconsistent across its devices.
+pub fn keyring_bindings_to_prop(ei: Option<PoolEncryptionInfo>) -> (bool, Vec<String>) {
+    option_to_tuple(
+        ei.and_then(|ei| {
+            ei.keyring_bindings()
+                .map(|kds| {
+                    kds.iter()
+                        .map(|kd| kd.as_application_str().to_string())
+                        .collect()
+                })
+                .ok()
+        }),
+        Vec::new(),
+    )
+}
+
 /// Fetch the Clevis information and handle converting it into a
 /// D-Bus type.
 pub fn clevis_info_to_prop(ei: Option<PoolEncryptionInfo>) -> (bool, (bool, (String, String))) {
@@ -74,3 +95,99 @@ pub fn pool_alloc_to_prop(alloc: Bytes) -> String {
 pub fn pool_used_to_prop(used: Option<Bytes>) -> (bool, String) {
     option_to_tuple(used.map(|u| (*u).to_string()), String::new())
 }
+
+/// Generate a D-Bus representation of the progress of a blockdev replacement
+/// in percent.
+pub fn replacement_progress_to_prop(progress: Option<u8>) -> (bool, u8) {
+    option_to_tuple(progress, 0)
+}
+
+/// Generate a D-Bus representation of the progress of the encryption of a
+/// pool in percent.
+pub fn encryption_progress_to_prop(progress: Option<u8>) -> (bool, u8) {
+    option_to_tuple(progress, 0)
+}
+
+/// Generate a D-Bus representation of the progress of the reencryption of a
+/// pool with new volume keys in percent.
+pub fn reencryption_progress_to_prop(progress: Option<u8>) -> (bool, u8) {
+    option_to_tuple(progress, 0)
+}
+
+/// Generate a D-Bus representation of whether unused space in the data device
+/// of a pool is to be returned to the backstore: when it will be, or why it
+/// can not be.
+pub fn data_shrink_to_prop(data_shrink: Option<DataShrink>) -> (bool, String) {
+    option_to_tuple(data_shrink.map(|d| d.to_string()), String::new())
+}
+
+/// Generate a D-Bus representation of the stripe size of the allocation
+/// policy, in bytes.
+pub fn stripe_size_to_prop(policy: AllocationPolicy) -> (bool, String) {
+    option_to_tuple(
+        policy.stripe_size().map(|s| (*s.bytes()).to_string()),
+        String::new(),
+    )
+}
+
+/// Generate a D-Bus representation of the mode of the cache.
+pub fn cache_mode_to_prop(settings: Option<&CacheSettings>) -> (bool, String) {
+    option_to_tuple(settings.map(|s| s.mode.to_string()), String::new())
+}
+
+/// Generate a D-Bus representation of the policy of the cache.
+pub fn cache_policy_to_prop(settings: Option<&CacheSettings>) -> (bool, String) {
+    option_to_tuple(settings.map(|s| s.policy.clone()), String::new())
+}
+
+/// Generate a D-Bus representation of the arguments to the policy of the
+/// cache.
+pub fn cache_policy_args_to_prop(
+    settings: Option<&CacheSettings>,
+) -> (bool, Vec<(String, String)>) {
+    option_to_tuple(settings.map(|s| s.policy_args.clone()), Vec::new())
+}
+
+/// Generate a D-Bus representation of a single counter of the statistics of
+/// the cache.
+fn cache_stat_to_prop<F>(stats: Option<CacheStats>, f: F) -> (bool, u64)
+where
+    F: Fn(CacheStats) -> u64,
+{
+    option_to_tuple(stats.map(f), 0)
+}
+
+/// Generate a D-Bus representation of the number of reads that hit in the cache.
+pub fn cache_read_hits_to_prop(stats: Option<CacheStats>) -> (bool, u64) {
+    cache_stat_to_prop(stats, |s| s.read_hits)
+}
+
+/// Generate a D-Bus representation of the number of reads that missed the cache.
+pub fn cache_read_misses_to_prop(stats: Option<CacheStats>) -> (bool, u64) {
+    cache_stat_to_prop(stats, |s| s.read_misses)
+}
+
+/// Generate a D-Bus representation of the number of writes that hit in the cache.
+pub fn cache_write_hits_to_prop(stats: Option<CacheStats>) -> (bool, u64) {
+    cache_stat_to_prop(stats, |s| s.write_hits)
+}
+
+/// Generate a D-Bus representation of the number of writes that missed the cache.
+pub fn cache_write_misses_to_prop(stats: Option<CacheStats>) -> (bool, u64) {
+    cache_stat_to_prop(stats, |s| s.write_misses)
+}
+
+/// Generate a D-Bus representation of the number of dirty blocks in the cache.
+pub fn cache_dirty_blocks_to_prop(stats: Option<CacheStats>) -> (bool, u64) {
+    cache_stat_to_prop(stats, |s| s.dirty_blocks)
+}
+
+/// Generate a D-Bus representation of the number of blocks promoted to the cache.
+pub fn cache_promotions_to_prop(stats: Option<CacheStats>) -> (bool, u64) {
+    cache_stat_to_prop(stats, |s| s.promotions)
+}
+
+/// Generate a D-Bus representation of the number of blocks demoted from the cache.
+pub fn cache_demotions_to_prop(stats: Option<CacheStats>) -> (bool, u64) {
+    cache_stat_to_prop(stats, |s| s.demotions)
+}
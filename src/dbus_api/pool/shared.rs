@@ -19,14 +19,14 @@ use crate::{
         util::{engine_to_dbus_err_tuple, get_next_arg},
     },
     engine::{
-        total_allocated, total_used, BlockDevTier, Diff, EngineAction, Name, Pool, PoolIdentifier,
-        PoolUuid, PropChangeAction,
+        total_allocated, total_used, AllocationPolicy, BlockDevTier, CacheSettings, Diff,
+        EngineAction, Name, Pool, PoolIdentifier, PoolUuid, PropChangeAction,
     },
 };
 
 pub enum BlockDevOp {
     InitCache,
-    InitCacheWithEnc,
+    InitCacheWithEnc(CacheSettings),
     AddCache,
     AddData,
 }
@@ -133,19 +133,25 @@ pub fn add_blockdevs(m: &MethodInfo<'_, MTSync<TData>, TData>, op: BlockDevOp) -
 
     let blockdevs = devs.map(Path::new).collect::<Vec<&Path>>();
 
-    let result = match op {
+    let result = match &op {
         BlockDevOp::InitCache => {
             let res = handle_action!(
-                pool.init_cache(pool_uuid, &pool_name, &blockdevs, false),
+                pool.init_cache(
+                    pool_uuid,
+                    &pool_name,
+                    &blockdevs,
+                    false,
+                    CacheSettings::default()
+                ),
                 dbus_context,
                 pool_path.get_name()
             );
             dbus_context.push_pool_cache_change(pool_path.get_name(), true);
             res
         }
-        BlockDevOp::InitCacheWithEnc => {
+        BlockDevOp::InitCacheWithEnc(settings) => {
             let res = handle_action!(
-                pool.init_cache(pool_uuid, &pool_name, &blockdevs, true),
+                pool.init_cache(pool_uuid, &pool_name, &blockdevs, true, settings.clone()),
                 dbus_context,
                 pool_path.get_name()
             );
@@ -286,6 +292,13 @@ pub fn pool_key_desc_prop(pool: &dyn Pool) -> (bool, (bool, String)) {
     prop_conv::key_desc_to_prop(pool.encryption_info())
 }
 
+/// Generate D-Bus representation of the key descriptions of the keyring
+/// bindings of a pool in addition to the one in the key description
+/// property.
+pub fn pool_keyring_bindings(pool: &dyn Pool) -> (bool, Vec<String>) {
+    prop_conv::keyring_bindings_to_prop(pool.encryption_info())
+}
+
 /// Generate D-Bus representation of a pool Clevis info property.
 pub fn pool_clevis_info_prop(pool: &dyn Pool) -> (bool, (bool, (String, String))) {
     prop_conv::clevis_info_to_prop(pool.encryption_info())
@@ -367,3 +380,123 @@ pub fn pool_set_overprov_mode(
 pub fn pool_no_alloc_space(pool: &dyn Pool) -> bool {
     pool.out_of_alloc_space()
 }
+
+/// Generate a D-Bus representation of the progress of the replacement of a
+/// blockdev in the pool.
+#[inline]
+pub fn pool_replacement_progress(pool: &dyn Pool) -> (bool, u8) {
+    prop_conv::replacement_progress_to_prop(pool.replacement_progress())
+}
+
+/// Generate a D-Bus representation of the progress of the online encryption
+/// of the pool.
+#[inline]
+pub fn pool_encryption_progress(pool: &dyn Pool) -> (bool, u8) {
+    prop_conv::encryption_progress_to_prop(pool.encryption_progress())
+}
+
+/// Generate a D-Bus representation of the progress of the reencryption of
+/// the pool with new volume keys.
+#[inline]
+pub fn pool_reencryption_progress(pool: &dyn Pool) -> (bool, u8) {
+    prop_conv::reencryption_progress_to_prop(pool.reencryption_progress())
+}
+
+/// Generate a D-Bus representation of the mode of the cache of the pool.
+#[inline]
+pub fn pool_cache_mode(pool: &dyn Pool) -> (bool, String) {
+    prop_conv::cache_mode_to_prop(pool.cache_settings())
+}
+
+/// Generate a D-Bus representation of the policy of the cache of the pool.
+#[inline]
+pub fn pool_cache_policy(pool: &dyn Pool) -> (bool, String) {
+    prop_conv::cache_policy_to_prop(pool.cache_settings())
+}
+
+/// Generate a D-Bus representation of the arguments to the policy of the
+/// cache of the pool.
+#[inline]
+pub fn pool_cache_policy_args(pool: &dyn Pool) -> (bool, Vec<(String, String)>) {
+    prop_conv::cache_policy_args_to_prop(pool.cache_settings())
+}
+
+/// Generate a D-Bus representation of the number of reads that hit in the cache of the pool.
+#[inline]
+pub fn pool_cache_read_hits(pool: &dyn Pool) -> (bool, u64) {
+    prop_conv::cache_read_hits_to_prop(pool.cache_stats())
+}
+
+/// Generate a D-Bus representation of the number of reads that missed the cache of the pool.
+#[inline]
+pub fn pool_cache_read_misses(pool: &dyn Pool) -> (bool, u64) {
+    prop_conv::cache_read_misses_to_prop(pool.cache_stats())
+}
+
+/// Generate a D-Bus representation of the number of writes that hit in the cache of the pool.
+#[inline]
+pub fn pool_cache_write_hits(pool: &dyn Pool) -> (bool, u64) {
+    prop_conv::cache_write_hits_to_prop(pool.cache_stats())
+}
+
+/// Generate a D-Bus representation of the number of writes that missed the cache of the pool.
+#[inline]
+pub fn pool_cache_write_misses(pool: &dyn Pool) -> (bool, u64) {
+    prop_conv::cache_write_misses_to_prop(pool.cache_stats())
+}
+
+/// Generate a D-Bus representation of the number of dirty blocks in the cache of the pool.
+#[inline]
+pub fn pool_cache_dirty_blocks(pool: &dyn Pool) -> (bool, u64) {
+    prop_conv::cache_dirty_blocks_to_prop(pool.cache_stats())
+}
+
+/// Generate a D-Bus representation of the number of blocks promoted to the cache of the pool.
+#[inline]
+pub fn pool_cache_promotions(pool: &dyn Pool) -> (bool, u64) {
+    prop_conv::cache_promotions_to_prop(pool.cache_stats())
+}
+
+/// Generate a D-Bus representation of the number of blocks demoted from the cache of the pool.
+#[inline]
+pub fn pool_cache_demotions(pool: &dyn Pool) -> (bool, u64) {
+    prop_conv::cache_demotions_to_prop(pool.cache_stats())
+}
+
+/// Generate a D-Bus representation of the redundancy of the data tier of the pool.
+#[inline]
+pub fn pool_redundancy(pool: &dyn Pool) -> u16 {
+    pool.redundancy() as u16
+}
+
+/// Generate a D-Bus representation of whether the pool has lost redundancy.
+#[inline]
+pub fn pool_degraded(pool: &dyn Pool) -> bool {
+    pool.is_degraded()
+}
+
+/// Generate a D-Bus representation of whether unused space in the data
+/// device of the pool is to be returned to the backstore.
+#[inline]
+pub fn pool_data_shrink(pool: &dyn Pool) -> (bool, String) {
+    prop_conv::data_shrink_to_prop(pool.data_shrink())
+}
+
+/// Generate a D-Bus representation of the stripe size with which new extents
+/// are allocated from the data tier of the pool.
+#[inline]
+pub fn pool_stripe_size(pool: &dyn Pool) -> (bool, String) {
+    prop_conv::stripe_size_to_prop(pool.allocation_policy())
+}
+
+/// Set the policy by which new extents are allocated from the data tier of
+/// the pool.
+#[inline]
+pub fn pool_set_allocation_policy(
+    pool: &mut dyn Pool,
+    name: &Name,
+    policy: AllocationPolicy,
+) -> Result<PropChangeAction<AllocationPolicy>, String> {
+    pool.set_allocation_policy(name, policy)
+        .map_err(|e| e.to_string())
+}
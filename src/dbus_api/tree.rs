@@ -26,24 +26,35 @@ use crate::{
     dbus_api::{
         api::prop_conv::{locked_pools_to_prop, stopped_pools_to_prop},
         blockdev::prop_conv::{
-            blockdev_new_size_to_prop, blockdev_total_physical_size_to_prop,
-            blockdev_user_info_to_prop,
+            blockdev_integrity_errors_to_prop, blockdev_new_size_to_prop,
+            blockdev_total_physical_size_to_prop, blockdev_user_info_to_prop,
         },
         consts,
-        filesystem::prop_conv::{fs_size_limit_to_prop, fs_size_to_prop, fs_used_to_prop},
+        filesystem::{
+            create_dbus_filesystem,
+            prop_conv::{
+                fs_size_limit_to_prop, fs_size_to_prop, fs_snapshot_schedule_to_prop,
+                fs_used_to_prop,
+            },
+        },
         pool::prop_conv::{
-            avail_actions_to_prop, clevis_info_to_prop, key_desc_to_prop, pool_alloc_to_prop,
-            pool_size_to_prop, pool_used_to_prop,
+            avail_actions_to_prop, cache_demotions_to_prop, cache_dirty_blocks_to_prop,
+            cache_promotions_to_prop, cache_read_hits_to_prop, cache_read_misses_to_prop,
+            cache_write_hits_to_prop, cache_write_misses_to_prop, clevis_info_to_prop,
+            data_shrink_to_prop, encryption_progress_to_prop, key_desc_to_prop,
+            keyring_bindings_to_prop, pool_alloc_to_prop, pool_size_to_prop, pool_used_to_prop,
+            reencryption_progress_to_prop, replacement_progress_to_prop, stripe_size_to_prop,
         },
         types::{
             DbusAction, InterfacesAddedThreadSafe, InterfacesRemoved, LockableTree, SignalChange,
             TData, TreeReadLock, TreeWriteLock,
         },
-        util::{poll_exit_and_future, thread_safe_to_dbus_sendable},
+        util::{option_to_tuple, poll_exit_and_future, thread_safe_to_dbus_sendable},
     },
     engine::{
-        ActionAvailability, DevUuid, FilesystemUuid, LockedPoolsInfo, PoolEncryptionInfo, PoolUuid,
-        StoppedPoolsInfo, StratisUuid,
+        ActionAvailability, AllocationPolicy, CacheStats, DataShrink, DevUuid, FilesystemUuid,
+        LockedPoolsInfo, PoolEncryptionInfo, PoolIdentifier, PoolUuid, SnapshotSchedule,
+        SnapshotScheduleRun, StoppedPoolsInfo, StratisUuid,
     },
     stratis::{StratisError, StratisResult},
 };
@@ -368,8 +379,10 @@ impl DbusTreeHandler {
         }
     }
 
-    /// Handle a change of the key description for a pool in the engine.
+    /// Handle a change of the key description or the additional keyring
+    /// bindings for a pool in the engine.
     fn handle_pool_key_desc_change(&self, item: Path<'static>, ei: Option<PoolEncryptionInfo>) {
+        let kb_prop = keyring_bindings_to_prop(ei.clone());
         let kd_prop = key_desc_to_prop(ei);
         if self
             .property_changed_invalidated_signal(
@@ -413,7 +426,9 @@ impl DbusTreeHandler {
                     consts::POOL_INTERFACE_NAME_3_7 => {
                         Vec::new(),
                         consts::POOL_KEY_DESC_PROP.to_string() =>
-                        box_variant!(kd_prop)
+                        box_variant!(kd_prop),
+                        consts::POOL_KEYRING_BINDINGS_PROP.to_string() =>
+                        box_variant!(kb_prop)
                     }
                 },
             )
@@ -513,7 +528,18 @@ impl DbusTreeHandler {
                         consts::POOL_HAS_CACHE_PROP.to_string() => box_variant!(b)
                     },
                     consts::POOL_INTERFACE_NAME_3_7 => {
-                        Vec::new(),
+                        vec![
+                            consts::POOL_CACHE_MODE_PROP.into(),
+                            consts::POOL_CACHE_POLICY_PROP.into(),
+                            consts::POOL_CACHE_POLICY_ARGS_PROP.into(),
+                            consts::POOL_CACHE_READ_HITS_PROP.into(),
+                            consts::POOL_CACHE_READ_MISSES_PROP.into(),
+                            consts::POOL_CACHE_WRITE_HITS_PROP.into(),
+                            consts::POOL_CACHE_WRITE_MISSES_PROP.into(),
+                            consts::POOL_CACHE_DIRTY_BLOCKS_PROP.into(),
+                            consts::POOL_CACHE_PROMOTIONS_PROP.into(),
+                            consts::POOL_CACHE_DEMOTIONS_PROP.into(),
+                        ],
                         consts::POOL_HAS_CACHE_PROP.to_string() => box_variant!(b)
                     }
                 },
@@ -600,6 +626,8 @@ impl DbusTreeHandler {
         uuid: FilesystemUuid,
         new_used: SignalChange<Option<Bytes>>,
         new_size: SignalChange<Bytes>,
+        new_exclusive: SignalChange<Option<Bytes>>,
+        new_shared: SignalChange<Option<Bytes>>,
     ) {
         handle_background_change!(
             self,
@@ -669,13 +697,20 @@ impl DbusTreeHandler {
                 new_used,
                 consts::FILESYSTEM_SIZE_PROP.to_string(),
                 fs_size_to_prop,
-                new_size
+                new_size,
+                consts::FILESYSTEM_EXCLUSIVE_PROP.to_string(),
+                fs_used_to_prop,
+                new_exclusive,
+                consts::FILESYSTEM_SHARED_PROP.to_string(),
+                fs_used_to_prop,
+                new_shared
             }
         );
     }
 
     /// Look up the pool path of the pool and notify clients of any changes to
     /// properties that change in the background.
+    #[allow(clippy::too_many_arguments)]
     fn handle_pool_background_change(
         &self,
         read_lock: TreeReadLock,
@@ -683,6 +718,12 @@ impl DbusTreeHandler {
         new_used: SignalChange<Option<Bytes>>,
         new_alloc: SignalChange<Bytes>,
         new_no_space: SignalChange<bool>,
+        new_progress: SignalChange<Option<u8>>,
+        new_cache_stats: SignalChange<Option<CacheStats>>,
+        new_degraded: SignalChange<bool>,
+        new_encryption_progress: SignalChange<Option<u8>>,
+        new_reencryption_progress: SignalChange<Option<u8>>,
+        new_data_shrink: SignalChange<Option<DataShrink>>,
     ) {
         handle_background_change!(
             self,
@@ -773,7 +814,43 @@ impl DbusTreeHandler {
                 new_alloc,
                 consts::POOL_NO_ALLOCABLE_SPACE_PROP.to_string(),
                 |x| x,
-                new_no_space
+                new_no_space,
+                consts::POOL_REPLACEMENT_PROGRESS_PROP.to_string(),
+                replacement_progress_to_prop,
+                new_progress,
+                consts::POOL_CACHE_READ_HITS_PROP.to_string(),
+                cache_read_hits_to_prop,
+                new_cache_stats,
+                consts::POOL_CACHE_READ_MISSES_PROP.to_string(),
+                cache_read_misses_to_prop,
+                new_cache_stats,
+                consts::POOL_CACHE_WRITE_HITS_PROP.to_string(),
+                cache_write_hits_to_prop,
+                new_cache_stats,
+                consts::POOL_CACHE_WRITE_MISSES_PROP.to_string(),
+                cache_write_misses_to_prop,
+                new_cache_stats,
+                consts::POOL_CACHE_DIRTY_BLOCKS_PROP.to_string(),
+                cache_dirty_blocks_to_prop,
+                new_cache_stats,
+                consts::POOL_CACHE_PROMOTIONS_PROP.to_string(),
+                cache_promotions_to_prop,
+                new_cache_stats,
+                consts::POOL_CACHE_DEMOTIONS_PROP.to_string(),
+                cache_demotions_to_prop,
+                new_cache_stats,
+                consts::POOL_DEGRADED_PROP.to_string(),
+                |x| x,
+                new_degraded,
+                consts::POOL_DATA_SHRINK_PROP.to_string(),
+                data_shrink_to_prop,
+                new_data_shrink,
+                consts::POOL_ENCRYPTION_PROGRESS_PROP.to_string(),
+                encryption_progress_to_prop,
+                new_encryption_progress,
+                consts::POOL_REENCRYPTION_PROGRESS_PROP.to_string(),
+                reencryption_progress_to_prop,
+                new_reencryption_progress
             }
         );
     }
@@ -852,6 +929,117 @@ impl DbusTreeHandler {
         }
     }
 
+    /// Send a signal indicating that the filesystem snapshot schedule has changed.
+    fn handle_fs_snapshot_schedule_change(
+        &self,
+        path: Path<'static>,
+        new_schedule: Option<SnapshotSchedule>,
+    ) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::FILESYSTEM_INTERFACE_NAME_3_7 => {
+                    Vec::new(),
+                    consts::FILESYSTEM_SNAPSHOT_SCHEDULE_PROP.to_string() =>
+                    box_variant!(fs_snapshot_schedule_to_prop(new_schedule))
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating filesystem snapshot schedule change: {}",
+                e
+            );
+        }
+    }
+
+    /// Send a signal indicating that the filesystem read-only setting has
+    /// changed.
+    fn handle_fs_read_only_change(&self, path: Path<'static>, new_read_only: bool) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::FILESYSTEM_INTERFACE_NAME_3_7 => {
+                    Vec::new(),
+                    consts::FILESYSTEM_READ_ONLY_PROP.to_string() =>
+                    box_variant!(new_read_only)
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating filesystem read-only change: {}",
+                e
+            );
+        }
+    }
+
+    /// Add the snapshots that were taken according to the snapshot schedules
+    /// of the filesystems in a pool to the D-Bus tree and remove the snapshots
+    /// that were destroyed. The tree lock is released before the pool is
+    /// locked to look up the new snapshots.
+    fn handle_scheduled_snapshots(
+        &mut self,
+        read_lock: TreeReadLock,
+        pool_uuid: PoolUuid,
+        run: SnapshotScheduleRun,
+    ) -> StratisResult<bool> {
+        let dbus_context = read_lock.get_data().clone();
+        let pool_path = match uuid_to_path!(read_lock, pool_uuid, Pool) {
+            Some(path) => path.clone(),
+            None => {
+                warn!(
+                    "Snapshots were taken in pool with UUID {} but no pool with that UUID could be found in the D-Bus layer",
+                    pool_uuid
+                );
+                return Ok(true);
+            }
+        };
+        let destroyed = run
+            .destroyed
+            .iter()
+            .filter_map(|uuid| uuid_to_path!(read_lock, *uuid, Fs).cloned())
+            .collect::<Vec<_>>();
+        drop(read_lock);
+
+        for path in destroyed {
+            dbus_context.push_remove(&path, consts::filesystem_interface_list());
+        }
+
+        if run.created.is_empty() {
+            return Ok(true);
+        }
+        match poll_exit_and_future(
+            self.should_exit.recv(),
+            dbus_context
+                .engine
+                .get_pool(PoolIdentifier::Uuid(pool_uuid)),
+        )? {
+            Some(Some(guard)) => {
+                let (pool_name, _, pool) = guard.as_tuple();
+                for uuid in run.created {
+                    if let Some((fs_name, fs)) = pool.get_filesystem(uuid) {
+                        create_dbus_filesystem(
+                            &dbus_context,
+                            pool_path.clone(),
+                            &pool_name,
+                            &fs_name,
+                            uuid,
+                            fs,
+                        );
+                    }
+                }
+                Ok(true)
+            }
+            Some(None) => {
+                warn!(
+                    "Pool with UUID {} could not be found when registering its new snapshots on the D-Bus",
+                    pool_uuid
+                );
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
     /// Send a signal indicating that the blockdev user info has changed.
     fn handle_blockdev_user_info_change(&self, path: Path<'static>, new_user_info: Option<String>) {
         let user_info_prop = blockdev_user_info_to_prop(new_user_info);
@@ -1002,6 +1190,71 @@ impl DbusTreeHandler {
         }
     }
 
+    /// Send a signal indicating that the pool allocation policy has changed.
+    fn handle_pool_stripe_size_change(&self, path: Path<'static>, policy: AllocationPolicy) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::POOL_INTERFACE_NAME_3_7 => {
+                    Vec::new(),
+                    consts::POOL_STRIPE_SIZE_PROP.to_string() =>
+                    box_variant!(stripe_size_to_prop(policy))
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating pool stripe size change: {}",
+                e
+            );
+        }
+    }
+
+    /// Send a signal indicating that the progress of a job has changed.
+    fn handle_job_progress_change(&self, path: Path<'static>, progress: u8) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::JOB_INTERFACE_NAME_3_7 => {
+                    Vec::new(),
+                    consts::JOB_PROGRESS_PROP.to_string() =>
+                    box_variant!(progress)
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating job progress change: {}",
+                e
+            );
+        }
+    }
+
+    /// Send a signal indicating that a job has finished.
+    fn handle_job_finished(&self, path: Path<'static>, result: Result<String, String>) {
+        let (report, error) = match result {
+            Ok(report) => (report, None),
+            Err(error) => (String::new(), Some(error)),
+        };
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::JOB_INTERFACE_NAME_3_7 => {
+                    Vec::new(),
+                    consts::JOB_FINISHED_PROP.to_string() =>
+                    box_variant!(true),
+                    consts::JOB_ERROR_PROP.to_string() =>
+                    box_variant!(option_to_tuple(error, String::new())),
+                    consts::JOB_RESULT_PROP.to_string() =>
+                    box_variant!(report)
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating that a job finished: {}",
+                e
+            );
+        }
+    }
+
     /// Send a signal indicating that the pool total allocated size has changed.
     fn handle_pool_foreground_change(
         &self,
@@ -1132,6 +1385,8 @@ impl DbusTreeHandler {
         read_lock: TreeReadLock,
         uuid: DevUuid,
         new_size: SignalChange<Option<Sectors>>,
+        new_shrunk: SignalChange<bool>,
+        new_integrity_errors: SignalChange<Option<u64>>,
     ) {
         handle_background_change!(
             self,
@@ -1162,7 +1417,13 @@ impl DbusTreeHandler {
             consts::BLOCKDEV_INTERFACE_NAME_3_7 => {
                 consts::BLOCKDEV_NEW_SIZE_PROP.to_string(),
                 blockdev_new_size_to_prop,
-                new_size
+                new_size,
+                consts::BLOCKDEV_SHRUNK_PROP.to_string(),
+                |x| x,
+                new_shrunk,
+                consts::BLOCKDEV_INTEGRITY_ERRORS_PROP.to_string(),
+                blockdev_integrity_errors_to_prop,
+                new_integrity_errors
             }
         )
     }
@@ -1229,10 +1490,31 @@ impl DbusTreeHandler {
                 self.handle_fs_size_limit_change(path, new_limit);
                 Ok(true)
             }
+            DbusAction::FsSnapshotScheduleChange(path, new_schedule) => {
+                self.handle_fs_snapshot_schedule_change(path, new_schedule);
+                Ok(true)
+            }
+            DbusAction::FsReadOnlyChange(path, new_read_only) => {
+                self.handle_fs_read_only_change(path, new_read_only);
+                Ok(true)
+            }
+            DbusAction::ScheduledSnapshots(pool_uuid, run) => {
+                if let Some(read_lock) =
+                    poll_exit_and_future(self.should_exit.recv(), self.tree.read())?
+                {
+                    self.handle_scheduled_snapshots(read_lock, pool_uuid, run)
+                } else {
+                    Ok(false)
+                }
+            }
             DbusAction::PoolOverprovModeChange(path, new_mode) => {
                 self.handle_pool_overprov_mode_change(path, new_mode);
                 Ok(true)
             }
+            DbusAction::PoolStripeSizeChange(path, policy) => {
+                self.handle_pool_stripe_size_change(path, policy);
+                Ok(true)
+            }
             DbusAction::LockedPoolsChange(pools) => {
                 self.handle_locked_pools_change(pools);
                 Ok(true)
@@ -1259,33 +1541,62 @@ impl DbusTreeHandler {
                 );
                 Ok(true)
             }
-            DbusAction::FsBackgroundChange(uuid, new_used, new_size) => {
+            DbusAction::FsBackgroundChange(uuid, new_used, new_size, new_exclusive, new_shared) => {
                 background_arm! {
                     self,
                     uuid,
                     handle_fs_background_change,
                     new_used,
-                    new_size
+                    new_size,
+                    new_exclusive,
+                    new_shared
                 }
             }
-            DbusAction::PoolBackgroundChange(uuid, new_used, new_alloc, new_no_space) => {
+            DbusAction::PoolBackgroundChange(
+                uuid,
+                new_used,
+                new_alloc,
+                new_no_space,
+                new_progress,
+                new_cache_stats,
+                new_degraded,
+                new_encryption_progress,
+                new_reencryption_progress,
+                new_data_shrink,
+            ) => {
                 background_arm! {
                     self,
                     uuid,
                     handle_pool_background_change,
                     new_used,
                     new_alloc,
-                    new_no_space
+                    new_no_space,
+                    new_progress,
+                    new_cache_stats,
+                    new_degraded,
+                    new_encryption_progress,
+                    new_reencryption_progress,
+                    new_data_shrink
                 }
             }
-            DbusAction::UdevBackgroundChange(uuid, new_size) => {
+            DbusAction::UdevBackgroundChange(uuid, new_size, new_shrunk, new_integrity_errors) => {
                 background_arm! {
                     self,
                     uuid,
                     handle_udev_background_change,
-                    new_size
+                    new_size,
+                    new_shrunk,
+                    new_integrity_errors
                 }
             }
+            DbusAction::JobProgressChange(path, progress) => {
+                self.handle_job_progress_change(path, progress);
+                Ok(true)
+            }
+            DbusAction::JobFinished(path, result) => {
+                self.handle_job_finished(path, result);
+                Ok(true)
+            }
         }
     }
 
@@ -6,9 +6,10 @@ use std::{
     any::type_name,
     collections::HashMap,
     fmt::{self, Debug},
+    iter::once,
     sync::{
         atomic::{AtomicU64, Ordering},
-        Arc,
+        Arc, Mutex,
     },
 };
 
@@ -27,10 +28,11 @@ use devicemapper::{Bytes, Sectors};
 use crate::{
     dbus_api::{connection::DbusConnectionHandler, tree::DbusTreeHandler, udev::DbusUdevHandler},
     engine::{
-        total_allocated, total_used, ActionAvailability, DevUuid, Diff, Engine, ExclusiveGuard,
-        FilesystemUuid, Lockable, LockedPoolsInfo, PoolDiff, PoolEncryptionInfo, PoolUuid,
-        SharedGuard, StoppedPoolsInfo, StratBlockDevDiff, StratFilesystemDiff, StratPoolDiff,
-        StratisUuid, ThinPoolDiff,
+        total_allocated, total_used, ActionAvailability, AllocationPolicy, CacheStats, DataShrink,
+        DevUuid, Diff, Engine, ExclusiveGuard, FilesystemUuid, JobProgress, Lockable,
+        LockedPoolsInfo, PoolDiff, PoolEncryptionInfo, PoolUuid, SharedGuard, SnapshotSchedule,
+        SnapshotScheduleRun, StoppedPoolsInfo, StratBlockDevDiff, StratFilesystemDiff,
+        StratPoolDiff, StratisUuid, ThinPoolDiff,
     },
 };
 
@@ -102,21 +104,33 @@ pub enum DbusAction {
     PoolCacheChange(Path<'static>, bool),
     PoolFsLimitChange(Path<'static>, u64),
     PoolOverprovModeChange(Path<'static>, bool),
+    PoolStripeSizeChange(Path<'static>, AllocationPolicy),
     LockedPoolsChange(LockedPoolsInfo),
     StoppedPoolsChange(StoppedPoolsInfo),
     BlockdevUserInfoChange(Path<'static>, Option<String>),
     BlockdevTotalPhysicalSizeChange(Path<'static>, Sectors),
     FsSizeLimitChange(Path<'static>, Option<Sectors>),
+    FsSnapshotScheduleChange(Path<'static>, Option<SnapshotSchedule>),
+    FsReadOnlyChange(Path<'static>, bool),
+    ScheduledSnapshots(PoolUuid, SnapshotScheduleRun),
     FsBackgroundChange(
         FilesystemUuid,
         SignalChange<Option<Bytes>>,
         SignalChange<Bytes>,
+        SignalChange<Option<Bytes>>,
+        SignalChange<Option<Bytes>>,
     ),
     PoolBackgroundChange(
         PoolUuid,
         SignalChange<Option<Bytes>>,
         SignalChange<Bytes>,
         SignalChange<bool>,
+        SignalChange<Option<u8>>,
+        SignalChange<Option<CacheStats>>,
+        SignalChange<bool>,
+        SignalChange<Option<u8>>,
+        SignalChange<Option<u8>>,
+        SignalChange<Option<DataShrink>>,
     ),
     PoolForegroundChange(
         Path<'static>,
@@ -125,34 +139,56 @@ pub enum DbusAction {
         SignalChange<Bytes>,
         SignalChange<bool>,
     ),
-    UdevBackgroundChange(DevUuid, SignalChange<Option<Sectors>>),
+    UdevBackgroundChange(
+        DevUuid,
+        SignalChange<Option<Sectors>>,
+        SignalChange<bool>,
+        SignalChange<Option<u64>>,
+    ),
+    JobProgressChange(Path<'static>, u8),
+    JobFinished(Path<'static>, Result<String, String>),
 }
 
 impl DbusAction {
-    /// Convert changed properties from a pool to a series of D-Bus actions.
+    /// Convert changed properties from a pool and its blockdevs to a series
+    /// of D-Bus actions.
     pub fn from_pool_diffs(diffs: HashMap<PoolUuid, PoolDiff>) -> Vec<Self> {
         diffs
             .into_iter()
-            .map(|(uuid, diff)| {
+            .flat_map(|(uuid, diff)| {
                 let PoolDiff {
                     pool:
                         StratPoolDiff {
                             metadata_size,
                             out_of_alloc_space,
+                            replacement_progress,
+                            encryption_progress,
+                            reencryption_progress,
+                            cache_stats,
+                            degraded,
                         },
                     thin_pool:
                         ThinPoolDiff {
                             used,
                             allocated_size,
+                            data_shrink,
                         },
+                    blockdevs,
                 } = diff;
 
-                DbusAction::PoolBackgroundChange(
+                once(DbusAction::PoolBackgroundChange(
                     uuid,
                     SignalChange::from(total_used(&used, &metadata_size)),
                     SignalChange::from(total_allocated(&allocated_size, &metadata_size)),
                     SignalChange::from(out_of_alloc_space),
-                )
+                    SignalChange::from(replacement_progress),
+                    SignalChange::from(cache_stats),
+                    SignalChange::from(degraded),
+                    SignalChange::from(encryption_progress),
+                    SignalChange::from(reencryption_progress),
+                    SignalChange::from(data_shrink),
+                ))
+                .chain(Self::from_bd_diffs(blockdevs))
             })
             .collect()
     }
@@ -162,12 +198,19 @@ impl DbusAction {
         diffs
             .into_iter()
             .map(|(uuid, diff)| {
-                let StratFilesystemDiff { size, used } = diff;
+                let StratFilesystemDiff {
+                    size,
+                    used,
+                    exclusive,
+                    shared,
+                } = diff;
 
                 DbusAction::FsBackgroundChange(
                     uuid,
                     SignalChange::from(used),
                     SignalChange::from(size),
+                    SignalChange::from(exclusive),
+                    SignalChange::from(shared),
                 )
             })
             .collect()
@@ -178,9 +221,18 @@ impl DbusAction {
         diffs
             .into_iter()
             .map(|(uuid, diff)| {
-                let StratBlockDevDiff { size } = diff;
+                let StratBlockDevDiff {
+                    size,
+                    shrunk,
+                    integrity_errors,
+                } = diff;
 
-                DbusAction::UdevBackgroundChange(uuid, SignalChange::from(size))
+                DbusAction::UdevBackgroundChange(
+                    uuid,
+                    SignalChange::from(size),
+                    SignalChange::from(shrunk),
+                    SignalChange::from(integrity_errors),
+                )
             })
             .collect()
     }
@@ -201,10 +253,21 @@ impl OPContext {
     }
 }
 
+/// The state of a long running operation that is represented on the D-Bus
+/// by a job object.
+#[derive(Clone, Debug, Default)]
+pub struct JobState {
+    pub progress: JobProgress,
+    /// None while the job is running; the outcome of the job once it has
+    /// finished: a report of what the job did, or the error it failed with.
+    pub result: Option<Result<String, String>>,
+}
+
 pub struct DbusContext {
     next_index: Arc<AtomicU64>,
     pub(super) engine: Arc<dyn Engine>,
     pub(super) sender: TokioSender<DbusAction>,
+    pub(super) jobs: Arc<Mutex<HashMap<Path<'static>, JobState>>>,
     connection: Arc<SyncConnection>,
 }
 
@@ -214,6 +277,7 @@ impl Clone for DbusContext {
             next_index: Arc::clone(&self.next_index),
             engine: self.engine.clone(),
             sender: self.sender.clone(),
+            jobs: Arc::clone(&self.jobs),
             connection: Arc::clone(&self.connection),
         }
     }
@@ -225,6 +289,7 @@ impl Debug for DbusContext {
             .field("next_index", &self.next_index)
             .field("engine", &type_name::<Arc<dyn Engine>>())
             .field("sender", &self.sender)
+            .field("jobs", &self.jobs)
             .finish()
     }
 }
@@ -239,6 +304,7 @@ impl DbusContext {
             engine,
             next_index: Arc::new(AtomicU64::new(0)),
             sender,
+            jobs: Arc::new(Mutex::new(HashMap::new())),
             connection,
         }
     }
@@ -366,6 +432,19 @@ impl DbusContext {
         }
     }
 
+    /// Send changed signal for pool StripeSize property.
+    pub fn push_pool_stripe_size_change(&self, item: &Path<'static>, policy: AllocationPolicy) {
+        if let Err(e) = self
+            .sender
+            .send(DbusAction::PoolStripeSizeChange(item.clone(), policy))
+        {
+            warn!(
+                "D-Bus pool stripe size change event could not be sent to the processing thread; no signal will be sent out for the allocation policy change of pool with path {}: {}",
+                item, e,
+            )
+        }
+    }
+
     /// Send changed signal for pool SizeLimit property.
     pub fn push_fs_size_limit_change(&self, item: &Path<'static>, new_size_limit: Option<Sectors>) {
         if let Err(e) = self
@@ -379,6 +458,36 @@ impl DbusContext {
         }
     }
 
+    /// Send changed signal for filesystem SnapshotSchedule property.
+    pub fn push_fs_snapshot_schedule_change(
+        &self,
+        item: &Path<'static>,
+        new_schedule: Option<SnapshotSchedule>,
+    ) {
+        if let Err(e) = self.sender.send(DbusAction::FsSnapshotScheduleChange(
+            item.clone(),
+            new_schedule,
+        )) {
+            warn!(
+                "D-Bus filesystem snapshot schedule change event could not be sent to the processing thread; no signal will be sent out for the snapshot schedule change of filesystem with path {}: {}",
+                item, e,
+            )
+        }
+    }
+
+    /// Send changed signal for filesystem ReadOnly property.
+    pub fn push_fs_read_only_change(&self, item: &Path<'static>, new_read_only: bool) {
+        if let Err(e) = self
+            .sender
+            .send(DbusAction::FsReadOnlyChange(item.clone(), new_read_only))
+        {
+            warn!(
+                "D-Bus filesystem read-only change event could not be sent to the processing thread; no signal will be sent out for the read-only change of filesystem with path {}: {}",
+                item, e,
+            )
+        }
+    }
+
     /// Send changed signal for pool overprovisioning mode property.
     pub fn push_pool_overprov_mode_change(&self, item: &Path<'static>, new_mode: bool) {
         if let Err(e) = self
@@ -487,6 +596,46 @@ impl DbusContext {
             )
         }
     }
+
+    /// Send changed signals for the properties of filesystems that were
+    /// changed by an operation on their pool.
+    pub fn push_fs_changes(&self, diffs: HashMap<FilesystemUuid, StratFilesystemDiff>) {
+        for action in DbusAction::from_fs_diffs(diffs) {
+            if let Err(e) = self.sender.send(action) {
+                warn!(
+                    "Filesystem change event could not be sent to the processing thread; no signal will be sent out for the changed filesystem properties: {}",
+                    e,
+                )
+            }
+        }
+    }
+
+    /// Send changed signal for the progress of a job.
+    pub fn push_job_progress_change(&self, item: &Path<'static>, progress: u8) {
+        if let Err(e) = self
+            .sender
+            .send(DbusAction::JobProgressChange(item.clone(), progress))
+        {
+            warn!(
+                "Job progress change event could not be sent to the processing thread; no signal will be sent out for the progress of job with path {}: {}",
+                item, e,
+            )
+        }
+    }
+
+    /// Send changed signal for a job that has finished, with the error that
+    /// it failed with, if any.
+    pub fn push_job_finished(&self, item: &Path<'static>, result: Result<String, String>) {
+        if let Err(e) = self
+            .sender
+            .send(DbusAction::JobFinished(item.clone(), result))
+        {
+            warn!(
+                "Job finished event could not be sent to the processing thread; no signal will be sent out for the completion of job with path {}: {}",
+                item, e,
+            )
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -33,7 +33,7 @@ use crate::{
         },
         udev::DbusUdevHandler,
     },
-    engine::{Engine, Lockable, UdevEngineEvent},
+    engine::{CryptParams, Engine, Lockable, Pbkdf, UdevEngineEvent},
     stratis::{StratisError, StratisResult},
 };
 
@@ -92,6 +92,48 @@ where
     Ok(value)
 }
 
+/// Get the optional crypt parameters of a method from its arguments, which
+/// begin at location loc. Each parameter is a (bool, T) tuple, in which the
+/// bool is true if the parameter is specified; in order, they are the cipher
+/// (s), the key size in bits (u), the sector size in bytes (u), the PBKDF
+/// (s), its memory cost in KiB (u), its number of iterations (u), and its
+/// number of parallel threads (u). The inner result is an error if the
+/// PBKDF is invalid; it contains None if no parameter is specified.
+pub fn get_crypt_params_args(
+    iter: &mut Iter<'_>,
+    loc: u16,
+) -> Result<StratisResult<Option<CryptParams>>, MethodErr> {
+    let cipher: (bool, String) = get_next_arg(iter, loc)?;
+    let key_size: (bool, u32) = get_next_arg(iter, loc + 1)?;
+    let sector_size: (bool, u32) = get_next_arg(iter, loc + 2)?;
+    let pbkdf: (bool, String) = get_next_arg(iter, loc + 3)?;
+    let pbkdf_memory: (bool, u32) = get_next_arg(iter, loc + 4)?;
+    let pbkdf_iterations: (bool, u32) = get_next_arg(iter, loc + 5)?;
+    let pbkdf_parallel: (bool, u32) = get_next_arg(iter, loc + 6)?;
+
+    let pbkdf = match tuple_to_option(pbkdf)
+        .map(|pbkdf| Pbkdf::try_from(pbkdf.as_str()))
+        .transpose()
+    {
+        Ok(pbkdf) => pbkdf,
+        Err(e) => return Ok(Err(e)),
+    };
+    let params = CryptParams {
+        cipher: tuple_to_option(cipher),
+        key_size: tuple_to_option(key_size),
+        sector_size: tuple_to_option(sector_size),
+        pbkdf,
+        pbkdf_memory: tuple_to_option(pbkdf_memory),
+        pbkdf_iterations: tuple_to_option(pbkdf_iterations),
+        pbkdf_parallel: tuple_to_option(pbkdf_parallel),
+    };
+    Ok(Ok(if params == CryptParams::default() {
+        None
+    } else {
+        Some(params)
+    }))
+}
+
 /// Generate a new object path which is guaranteed unique wrt. all previously
 /// generated object paths.
 pub fn make_object_path(context: &DbusContext) -> String {
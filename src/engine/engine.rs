@@ -20,11 +20,14 @@ use crate::{
     engine::{
         structures::{AllLockReadGuard, AllLockWriteGuard, SomeLockReadGuard, SomeLockWriteGuard},
         types::{
-            ActionAvailability, BlockDevTier, Clevis, CreateAction, DeleteAction, DevUuid,
-            EncryptionInfo, FilesystemUuid, GrowAction, Key, KeyDescription, LockedPoolsInfo,
-            MappingCreateAction, MappingDeleteAction, Name, PoolDiff, PoolEncryptionInfo,
-            PoolIdentifier, PoolUuid, RegenAction, RenameAction, ReportType, SetCreateAction,
-            SetDeleteAction, SetUnlockAction, StartAction, StopAction, StoppedPoolsInfo,
+            ActionAvailability, AllocationPolicy, BlockDevTier, CacheSettings, CacheStats, Clevis,
+            CreateAction, CryptParams, DataShrink, DeleteAction, DevUuid, EncryptedDevice,
+            EncryptionInfo, EraseMode, Erasure, FilesystemSpaceUsage, FilesystemUuid, GrowAction,
+            IntegrityHash, Key, KeyDescription, LockedPoolsInfo, MappingCreateAction,
+            MappingDeleteAction, Name, PoolDiff, PoolEncryptionInfo, PoolIdentifier, PoolUuid,
+            Redundancy, Reencryption, RegenAction, RenameAction, ReportType, RevertAction,
+            SetCreateAction, SetDeleteAction, SetUnlockAction, SnapshotSchedule,
+            SnapshotScheduleRun, SnapshotScheduleStatus, StartAction, StopAction, StoppedPoolsInfo,
             StratFilesystemDiff, UdevEngineEvent, UnlockMethod,
         },
     },
@@ -93,6 +96,27 @@ pub trait Filesystem: Debug {
 
     /// Get filesystem size limit.
     fn size_limit(&self) -> Option<Sectors>;
+
+    /// Get the UUID of the filesystem of which this filesystem was created
+    /// as a snapshot, if any. The origin may have been destroyed since.
+    fn origin(&self) -> Option<FilesystemUuid>;
+
+    /// Get the schedule by which snapshots of the filesystem are taken
+    /// automatically, if any.
+    fn snapshot_schedule(&self) -> Option<SnapshotSchedule>;
+
+    /// Get the outcome of the most recent run of the snapshot schedule of
+    /// the filesystem since stratisd was started, if any.
+    fn snapshot_schedule_status(&self) -> Option<SnapshotScheduleStatus>;
+
+    /// Whether the filesystem is read-only, i.e., its device can not be
+    /// written.
+    fn read_only(&self) -> bool;
+
+    /// Get the space exclusively mapped by the filesystem and the space it
+    /// shares with other filesystems, as last computed by
+    /// Engine::refresh_space_usage, if it has been computed.
+    fn space_usage(&self) -> Option<FilesystemSpaceUsage>;
 }
 
 pub trait BlockDev: Debug {
@@ -123,6 +147,15 @@ pub trait BlockDev: Debug {
     /// If internally the new size is None, the block device size is equal to that
     /// registered in the BDA.
     fn new_size(&self) -> Option<Sectors>;
+
+    /// Whether the newly registered size of the block device is less than
+    /// the size registered in the BDA.
+    fn is_shrunk(&self) -> bool;
+
+    /// The number of reads from the block device that have failed their
+    /// integrity check since the pool was started, if the block device has
+    /// integrity protection.
+    fn integrity_errors(&self) -> Option<u64>;
 }
 
 pub trait Pool: Debug + Send + Sync {
@@ -140,14 +173,34 @@ pub trait Pool: Debug + Send + Sync {
     /// can only be initialized once and if an attempt is made to initialize it
     /// twice with different sets of block devices, the user should be notified
     /// of their error.
+    ///
+    /// The cache is created with the given mode, policy, and policy arguments.
+    /// If the cache has already been initialized, settings is ignored.
     fn init_cache(
         &mut self,
         pool_uuid: PoolUuid,
         pool_name: &str,
         blockdevs: &[&Path],
         supports_encrypted: bool,
+        settings: CacheSettings,
     ) -> StratisResult<SetCreateAction<DevUuid>>;
 
+    /// Remove the cache from the pool, leaving the pool and its data intact.
+    /// The dirty blocks in the cache must already have been written back to
+    /// the data tier, as Engine::remove_cache() does without holding the
+    /// lock on the pool; otherwise an error is returned and the cache is
+    /// left in place with its settings unchanged. Only the blocks dirtied
+    /// since are written back, for a short bounded time, while the pool is
+    /// suspended. The Stratis metadata on the cache tier blockdevs is then
+    /// wiped.
+    /// Returns a list of uuids corresponding to the cache tier blockdevs
+    /// that were removed; the list is empty if the pool has no cache.
+    fn remove_cache(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+    ) -> StratisResult<SetDeleteAction<DevUuid>>;
+
     /// Creates the filesystems specified by specs.
     /// Returns a list of the names of filesystems actually created.
     /// Returns an error if any of the specified names are already in use
@@ -174,6 +227,96 @@ pub trait Pool: Debug + Send + Sync {
         tier: BlockDevTier,
     ) -> StratisResult<(SetCreateAction<DevUuid>, Option<PoolDiff>)>;
 
+    /// Removes the data tier blockdevs specified by uuids from the pool.
+    /// All data allocated from the blockdevs is copied to space reserved on
+    /// the remaining blockdevs in the data tier while the pool remains in
+    /// use; the progress of the copy is reported by replacement_progress().
+    /// Once it is complete, the blockdevs are removed and the Stratis
+    /// metadata on them is wiped.
+    /// Returns a list of uuids corresponding to devices being removed.
+    /// Returns an error if any of the blockdevs belongs to the cache tier,
+    /// if every blockdev in the data tier would be removed, or if the
+    /// remaining blockdevs do not have enough free space to hold the data
+    /// to be moved.
+    /// Every piece of an extent allocated with the striped allocation policy
+    /// is moved to a single range, so that the extent remains striped; an
+    /// error is returned if the free space is too fragmented for that.
+    /// If the pool has redundancy, the copy of the data held by the
+    /// blockdevs is instead rebuilt from the other copies on blockdevs that
+    /// hold no other copy, and the blockdevs may be missing; an error is
+    /// returned if the blockdevs hold parts of more than one copy.
+    fn remove_blockdevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        uuids: &[DevUuid],
+    ) -> StratisResult<(SetDeleteAction<DevUuid>, Option<PoolDiff>)>;
+
+    /// Replaces the data tier blockdev specified by old with the device
+    /// specified by new while the pool remains in use.
+    /// The new device is added to the data tier and the data allocated from
+    /// old is copied to it in the background; once the copy is complete,
+    /// old is removed from the pool and its Stratis metadata is wiped.
+    /// Returns the uuid of the blockdev that replaces old.
+    /// Returns an error if old does not belong to the data tier, if another
+    /// replacement is in progress, or if new is too small to hold the data
+    /// allocated from old.
+    /// Every piece of an extent allocated with the striped allocation policy
+    /// is moved to a single range of new, so that the extent remains
+    /// striped.
+    /// If the pool has redundancy, old may be missing; old is removed at
+    /// once and the copy of the data that it held is rebuilt on new from
+    /// the other copies in the background.
+    fn replace_blockdev(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        old: DevUuid,
+        new: &Path,
+    ) -> StratisResult<(CreateAction<DevUuid>, Option<PoolDiff>)>;
+
+    /// Encrypt all devices in the given unencrypted pool while the pool
+    /// remains in use, so that they can be unlocked with the methods in
+    /// encryption_info. Each device is formatted with a LUKS2 header, which
+    /// takes up space at the end of the device that must not be allocated;
+    /// the data is encrypted in the background. The devices are formatted
+    /// and encrypted with crypt_params, if specified, as those of a new pool
+    /// are, and the key derivation parameters among them are recorded; the
+    /// encryption sector size defaults to the logical sector size of the
+    /// devices. The integrity protection of the devices, if any, becomes
+    /// the layer beneath the encryption.
+    /// Returns an error if the pool has a cache, has redundancy, or is
+    /// already encrypted with different encryption info.
+    fn encrypt_pool(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        encryption_info: &EncryptionInfo,
+        crypt_params: Option<&CryptParams>,
+    ) -> StratisResult<(CreateAction<EncryptedDevice>, Option<PoolDiff>)>;
+
+    /// Reencrypt all datadevs and cachedevs in the given encrypted pool with
+    /// new volume keys while the pool remains in use. The unlock methods to
+    /// which the pool is bound are unchanged. The new volume keys are used
+    /// with the cipher and the key size in crypt_params, if specified, and
+    /// otherwise with those of the current ones; the encryption sector size
+    /// can not be changed. Key derivation parameters in crypt_params replace
+    /// those recorded for the pool. The blockdevs are reencrypted one at a time in
+    /// the background; an interrupted reencryption is resumed when the pool
+    /// is next set up.
+    /// Returns an error if the pool is not encrypted or is being encrypted.
+    fn reencrypt_pool(
+        &mut self,
+        pool_name: &str,
+        crypt_params: Option<&CryptParams>,
+    ) -> StratisResult<(StartAction<Reencryption>, Option<PoolDiff>)>;
+
+    /// Back up the LUKS2 headers of all blockdevs of the given encrypted pool
+    /// to a single archive file at archive_path, which must not exist.
+    /// Returns an error if the pool is not encrypted or is being encrypted
+    /// or reencrypted.
+    fn back_up_luks_headers(&self, pool_uuid: PoolUuid, archive_path: &Path) -> StratisResult<()>;
+
     /// Bind all devices in the given pool for automated unlocking
     /// using clevis.
     fn bind_clevis(
@@ -196,6 +339,25 @@ pub trait Pool: Debug + Send + Sync {
     fn rebind_keyring(&mut self, new_key_desc: &KeyDescription)
         -> StratisResult<RenameAction<Key>>;
 
+    /// Bind all devices in the given pool to an additional key description
+    /// in the kernel keyring. Each additional binding is stored in its own
+    /// LUKS2 token and keyslot, independently of the primary binding.
+    fn add_keyring_binding(
+        &mut self,
+        key_desc: &KeyDescription,
+    ) -> StratisResult<CreateAction<Key>>;
+
+    /// Remove the additional keyring binding with the given key description
+    /// from all devices in the given pool.
+    fn remove_keyring_binding(
+        &mut self,
+        key_desc: &KeyDescription,
+    ) -> StratisResult<DeleteAction<Key>>;
+
+    /// List the key descriptions of the additional keyring bindings of the
+    /// pool.
+    fn keyring_bindings(&self) -> StratisResult<Vec<KeyDescription>>;
+
     /// Regenerate the Clevis bindings associated with a pool.
     fn rebind_clevis(&mut self) -> StratisResult<RegenAction>;
 
@@ -221,16 +383,42 @@ pub trait Pool: Debug + Send + Sync {
         new_name: &str,
     ) -> StratisResult<RenameAction<FilesystemUuid>>;
 
+    /// Revert a filesystem to the contents of one of its snapshots.
+    /// The thin devices of the origin and the snapshot are exchanged so that
+    /// the origin keeps its name, UUID and devlinks, while the snapshot holds
+    /// the previous contents of the origin.
+    /// Precondition: Both filesystems must be unmounted.
+    fn revert_filesystem(
+        &mut self,
+        pool_name: &str,
+        origin_uuid: FilesystemUuid,
+        snapshot_uuid: FilesystemUuid,
+    ) -> StratisResult<(RevertAction, HashMap<FilesystemUuid, StratFilesystemDiff>)>;
+
     /// Snapshot filesystem
-    /// Create a CoW snapshot of the origin
+    /// Create a CoW snapshot of the origin. If read_only is true, the
+    /// snapshot is created read-only.
     fn snapshot_filesystem(
         &mut self,
         pool_name: &str,
         pool_uuid: PoolUuid,
         origin_uuid: FilesystemUuid,
         snapshot_name: &str,
+        read_only: bool,
     ) -> StratisResult<CreateAction<(FilesystemUuid, &mut dyn Filesystem)>>;
 
+    /// Snapshot several filesystems at the same point in time, so that
+    /// together the snapshots form a crash-consistent image of all the
+    /// origins. specs pairs the UUID of each origin with the name of its
+    /// snapshot. Either all the snapshots are created or none are.
+    /// Returns an error if any of the snapshot names is already in use.
+    fn snapshot_filesystems<'b>(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        specs: &[(FilesystemUuid, &'b str)],
+    ) -> StratisResult<SetCreateAction<(&'b str, FilesystemUuid)>>;
+
     /// The total number of Sectors belonging to this pool.
     /// There are no exclusions, so this number includes overhead sectors
     /// of all sorts, sectors allocated for every sort of metadata by
@@ -291,6 +479,19 @@ pub trait Pool: Debug + Send + Sync {
     /// true if the pool has a cache, otherwise false
     fn has_cache(&self) -> bool;
 
+    /// The mode, policy, and policy arguments of the cache, if the pool has
+    /// a cache.
+    fn cache_settings(&self) -> Option<&CacheSettings>;
+
+    /// Change the mode, policy, and policy arguments of the cache while the
+    /// pool is in use.
+    /// Returns an error if the pool has no cache.
+    fn set_cache_settings(
+        &mut self,
+        pool_name: &str,
+        settings: CacheSettings,
+    ) -> StratisResult<PropChangeAction<CacheSettings>>;
+
     /// Determine if the pool's data is encrypted
     fn is_encrypted(&self) -> bool;
 
@@ -322,6 +523,52 @@ pub trait Pool: Debug + Send + Sync {
     /// Returns a boolean indicating whether the pool is out of allocation space.
     fn out_of_alloc_space(&self) -> bool;
 
+    /// Returns the percentage of the data copied by the replacement or
+    /// removal of blockdevs, if one is in progress.
+    fn replacement_progress(&self) -> Option<u8>;
+
+    /// Returns the percentage of the data that has been encrypted, if an
+    /// online encryption of the pool is in progress.
+    fn encryption_progress(&self) -> Option<u8>;
+
+    /// Returns the percentage of the data that has been reencrypted with new
+    /// volume keys, if a reencryption of the pool is in progress.
+    fn reencryption_progress(&self) -> Option<u8>;
+
+    /// Returns the statistics of the cache, as most recently read from the
+    /// cache device, if the pool has a cache.
+    fn cache_stats(&self) -> Option<CacheStats>;
+
+    /// Returns the redundancy of the data tier.
+    fn redundancy(&self) -> Redundancy;
+
+    /// Returns the hash with which the data on the blockdevs in the data
+    /// tier is checked, if they have integrity protection.
+    fn integrity(&self) -> Option<IntegrityHash>;
+
+    /// Returns true if the data tier is degraded, i.e., a datadev has
+    /// disappeared or a copy of the data can no longer be written.
+    fn is_degraded(&self) -> bool;
+
+    /// Returns whether unused space in the thin pool data device is to be
+    /// returned to the backstore, and if it can not be, why not. None if
+    /// the data device has no substantial unused space.
+    fn data_shrink(&self) -> Option<DataShrink>;
+
+    /// Returns the policy by which new extents are allocated from the data
+    /// tier.
+    fn allocation_policy(&self) -> AllocationPolicy;
+
+    /// Change the policy by which new extents are allocated from the data
+    /// tier. Extents that are already allocated are not changed.
+    /// Returns an error if the policy is not supported with the redundancy
+    /// of the data tier.
+    fn set_allocation_policy(
+        &mut self,
+        pool_name: &str,
+        policy: AllocationPolicy,
+    ) -> StratisResult<PropChangeAction<AllocationPolicy>>;
+
     /// Grow either a specified device or all devices in a pool if the underlying
     /// physical device or devices have changed in size.
     #[allow(clippy::type_complexity)]
@@ -338,6 +585,34 @@ pub trait Pool: Debug + Send + Sync {
         fs: FilesystemUuid,
         limit: Option<Bytes>,
     ) -> StratisResult<PropChangeAction<Option<Sectors>>>;
+
+    /// Make a filesystem read-only or writable.
+    /// Precondition: The filesystem must be unmounted to make it read-only.
+    fn set_fs_read_only(
+        &mut self,
+        fs: FilesystemUuid,
+        read_only: bool,
+    ) -> StratisResult<PropChangeAction<bool>>;
+
+    /// Set or remove the schedule by which snapshots of a filesystem are
+    /// taken automatically.
+    fn set_fs_snapshot_schedule(
+        &mut self,
+        fs: FilesystemUuid,
+        schedule: Option<SnapshotSchedule>,
+    ) -> StratisResult<PropChangeAction<Option<SnapshotSchedule>>>;
+
+    /// Take the snapshots that are due according to the snapshot schedules
+    /// of the filesystems in the pool and destroy the scheduled snapshots
+    /// that are no longer retained. A failure to run the schedule of one
+    /// filesystem is recorded in its schedule status and does not prevent
+    /// the schedules of other filesystems from being run.
+    fn run_snapshot_schedules(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        now: DateTime<Utc>,
+    ) -> StratisResult<SnapshotScheduleRun>;
 }
 
 pub type HandleEvents<P> = (
@@ -349,11 +624,26 @@ pub type HandleEvents<P> = (
 pub trait Engine: Debug + Report + Send + Sync {
     /// Create a Stratis pool.
     /// Returns the UUID of the newly created pool.
+    /// If redundancy requires more than one copy of the data, the copies
+    /// are stored on distinct blockdevs. A pool with redundancy can not be
+    /// encrypted, and its data can not be striped or moved off blockdevs,
+    /// nor can unused space be returned to its backstore.
+    /// If the pool is encrypted, its devices are formatted with the given
+    /// crypt parameters; it is an error to specify crypt parameters for an
+    /// unencrypted pool. The key derivation parameters among them are
+    /// recorded and also applied to the keyring keyslots that are added to
+    /// the pool later.
+    /// If an integrity hash is given, every blockdev in the data tier is
+    /// given integrity protection with that hash; if the pool is encrypted,
+    /// the integrity protection lies beneath the encryption.
     async fn create_pool(
         &self,
         name: &str,
         blockdev_paths: &[&Path],
+        redundancy: Redundancy,
         encryption_info: Option<&EncryptionInfo>,
+        crypt_params: Option<&CryptParams>,
+        integrity: Option<IntegrityHash>,
     ) -> StratisResult<CreateAction<PoolUuid>>;
 
     /// Handle a libudev event.
@@ -365,8 +655,26 @@ pub trait Engine: Debug + Report + Send + Sync {
 
     /// Destroy a pool.
     /// Ensures that the pool of the given UUID is absent on completion.
+    /// If the erase mode is EraseMode::Keyslots, the keyslots of the pool are
+    /// erased before it is destroyed. For the other erase modes, the devices
+    /// of the pool are opened before it is destroyed and the erasure of their
+    /// allocated ranges is returned, to be run once the pool is gone.
     /// Returns true if some action was necessary, otherwise false.
-    async fn destroy_pool(&self, uuid: PoolUuid) -> StratisResult<DeleteAction<PoolUuid>>;
+    async fn destroy_pool(
+        &self,
+        uuid: PoolUuid,
+        erase_mode: Option<EraseMode>,
+    ) -> StratisResult<(DeleteAction<PoolUuid>, Option<Erasure>)>;
+
+    /// Remove the cache from the pool with the given UUID, as
+    /// Pool::remove_cache() does. The dirty blocks in the cache are first
+    /// written back to the data tier without holding the lock on the pool,
+    /// so that other requests for the pool are not held up meanwhile. If
+    /// they are not all written back within a short bounded time, an error
+    /// is returned and the cache is left in place with its settings
+    /// unchanged; the blocks written back meanwhile remain clean, so a
+    /// later attempt has fewer left to write back.
+    async fn remove_cache(&self, uuid: PoolUuid) -> StratisResult<SetDeleteAction<DevUuid>>;
 
     /// Rename pool with uuid to new_name.
     /// Raises an error if the mapping can't be applied because
@@ -430,11 +738,35 @@ pub trait Engine: Debug + Report + Send + Sync {
         pools: Option<&HashSet<PoolUuid>>,
     ) -> HashMap<FilesystemUuid, StratFilesystemDiff>;
 
+    /// Run the snapshot schedules of the filesystems in all pools.
+    async fn run_snapshot_schedules(&self) -> HashMap<PoolUuid, SnapshotScheduleRun>;
+
+    /// Recompute the space exclusively mapped by each filesystem in all pools
+    /// and the space that it shares with other filesystems. This is expensive
+    /// and is therefore done periodically rather than on request. Nothing is
+    /// computed if thin_ls is not installed.
+    async fn refresh_space_usage(&self) -> HashMap<FilesystemUuid, StratFilesystemDiff>;
+
+    /// Continue the online encryption or reencryption of every pool in which
+    /// one is in progress for a bounded time and return the changes to the
+    /// pools, which include the progress of the reencryption. A pool is
+    /// locked only while its progress is recorded, not while its data is
+    /// processed.
+    async fn continue_reencryption(&self) -> HashMap<PoolUuid, PoolDiff>;
+
+    /// Unlock the encrypted devices of the pools that are queued to be
+    /// unlocked automatically with every unlock method that they are
+    /// configured with. A pool is set up when the udev events for its
+    /// unlocked devices are handled, unless it was stopped.
+    async fn unlock_pending_pools(&self);
+
     /// Get the handler for kernel keyring operations.
     async fn get_key_handler(&self) -> Arc<dyn KeyActions>;
 
     /// Start and set up a pool, creating all necessary devicemapper devices to
     /// perform IO operations and start monitoring for events.
+    /// A pool with redundancy is started degraded if some of its data devices
+    /// are missing but one complete copy of the data is present.
     async fn start_pool(
         &self,
         pool_id: PoolIdentifier<PoolUuid>,
@@ -449,6 +781,20 @@ pub trait Engine: Debug + Report + Send + Sync {
         has_partially_constructed: bool,
     ) -> StratisResult<StopAction<PoolUuid>>;
 
+    /// Restore the LUKS2 header of the device with UUID dev_uuid in the pool
+    /// with UUID pool_uuid from an archive written by
+    /// Pool::back_up_luks_headers to the device at physical_path.
+    /// The Stratis token in the archived header must identify the given pool
+    /// and device, and the device at physical_path must be that device and
+    /// must not be in use. Returns an error if the pool is started.
+    async fn restore_luks_header(
+        &self,
+        pool_uuid: PoolUuid,
+        dev_uuid: DevUuid,
+        physical_path: &Path,
+        archive_path: &Path,
+    ) -> StratisResult<()>;
+
     /// Refresh the state of all pools and liminal devices.
     async fn refresh_state(&self) -> StratisResult<()>;
 
@@ -3,7 +3,7 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 pub use self::{
-    engine::{BlockDev, Engine, Filesystem, KeyActions, Pool, Report},
+    engine::{BlockDev, Engine, Filesystem, KeyActions, Pool, Report, MAX_STRATIS_PASS_SIZE},
     shared::{total_allocated, total_used},
     sim_engine::SimEngine,
     strat_engine::{
@@ -13,14 +13,16 @@ pub use self::{
     },
     structures::{AllLockReadGuard, ExclusiveGuard, SharedGuard, Table},
     types::{
-        ActionAvailability, BlockDevTier, ClevisInfo, CreateAction, DeleteAction, DevUuid, Diff,
-        EncryptionInfo, EngineAction, FilesystemUuid, GrowAction, KeyDescription, Lockable,
-        LockedPoolInfo, LockedPoolsInfo, MappingCreateAction, MappingDeleteAction,
-        MaybeInconsistent, Name, PoolDiff, PoolEncryptionInfo, PoolIdentifier, PoolUuid,
-        PropChangeAction, RenameAction, ReportType, SetCreateAction, SetDeleteAction,
-        SetUnlockAction, StartAction, StopAction, StoppedPoolInfo, StoppedPoolsInfo,
-        StratBlockDevDiff, StratFilesystemDiff, StratPoolDiff, StratisUuid, ThinPoolDiff,
-        ToDisplay, UdevEngineEvent, UnlockMethod,
+        ActionAvailability, AllocationPolicy, BlockDevTier, CacheMode, CacheSettings, CacheStats,
+        ClevisInfo, CreateAction, CryptParams, DataShrink, DeleteAction, DevUuid, Diff,
+        EncryptionInfo, EngineAction, EraseMode, Erasure, FilesystemSpaceUsage, FilesystemUuid,
+        GrowAction, IntegrityHash, JobProgress, KeyDescription, Lockable, LockedPoolInfo,
+        LockedPoolsInfo, MappingCreateAction, MappingDeleteAction, MaybeInconsistent, Name, Pbkdf,
+        PoolDiff, PoolEncryptionInfo, PoolIdentifier, PoolUuid, PropChangeAction, Redundancy,
+        RenameAction, ReportType, RevertAction, SetCreateAction, SetDeleteAction, SetUnlockAction,
+        SnapshotSchedule, SnapshotScheduleRun, SnapshotScheduleStatus, StartAction, StopAction,
+        StoppedPoolInfo, StoppedPoolsInfo, StratBlockDevDiff, StratFilesystemDiff, StratPoolDiff,
+        StratisUuid, ThinPoolDiff, ToDisplay, UdevEngineEvent, UnlockMethod,
     },
 };
 
@@ -3,6 +3,7 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::{
+    cmp::min,
     collections::{hash_map::RandomState, HashMap, HashSet},
     fs::File,
     io::Read,
@@ -10,7 +11,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use chrono::{DateTime, LocalResult, TimeZone, Utc};
+use chrono::{DateTime, LocalResult, NaiveDateTime, TimeZone, Utc};
 use nix::poll::{poll, PollFd, PollFlags};
 use regex::Regex;
 
@@ -18,10 +19,11 @@ use devicemapper::{Bytes, Sectors, IEC, SECTOR_SIZE};
 
 use crate::{
     engine::{
-        engine::{Pool, MAX_STRATIS_PASS_SIZE},
+        engine::{Filesystem, Pool, MAX_STRATIS_PASS_SIZE},
         types::{
-            BlockDevTier, CreateAction, DevUuid, Diff, EncryptionInfo, MaybeInconsistent, Name,
-            PoolEncryptionInfo, PoolUuid, SetCreateAction,
+            BlockDevTier, CreateAction, CryptParams, DevUuid, Diff, EncryptionInfo, EngineAction,
+            EraseMode, FilesystemUuid, IntegrityHash, MaybeInconsistent, Name, PoolEncryptionInfo,
+            PoolUuid, Redundancy, SetCreateAction, SnapshotSchedule, SnapshotScheduleRun,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -36,6 +38,9 @@ pub const DEFAULT_THIN_DEV_SIZE: Sectors = Sectors(2 * IEC::Gi); // 1 TiB
 // https://www.spinics.net/lists/linux-xfs/msg59453.html
 const MIN_THIN_DEV_SIZE: Sectors = Sectors(IEC::Mi); // 512 MiB
 
+// Linux has a maximum filename length of 255 bytes
+const MAX_NAME_LEN: usize = 255;
+
 /// Called when the name of a requested pool coincides with the name of an
 /// existing pool. Returns an error if the specifications of the requested
 /// pool differ from the specifications of the existing pool, otherwise
@@ -44,10 +49,25 @@ pub fn create_pool_idempotent_or_err<P>(
     pool: &P,
     pool_name: &Name,
     blockdev_paths: &[&Path],
+    redundancy: Redundancy,
+    integrity: Option<IntegrityHash>,
 ) -> StratisResult<CreateAction<PoolUuid>>
 where
     P: Pool,
 {
+    if pool.redundancy() != redundancy {
+        return Err(StratisError::Msg(format!(
+            "A pool named {pool_name} already exists with redundancy {} instead of {redundancy}",
+            pool.redundancy()
+        )));
+    }
+
+    if pool.integrity() != integrity {
+        return Err(StratisError::Msg(format!(
+            "A pool named {pool_name} already exists with a different integrity setting"
+        )));
+    }
+
     let input_devices: HashSet<PathBuf, RandomState> =
         blockdev_paths.iter().map(|p| p.to_path_buf()).collect();
 
@@ -117,10 +137,18 @@ pub fn set_key_shared(key_fd: RawFd, memory: &mut [u8]) -> StratisResult<usize>
 
     let bytes_read = key_file.read(memory)?;
 
+    // A key of exactly the maximum length fills the buffer; it is too long
+    // only if there is more to read. A pipe whose write end is closed
+    // reports POLLHUP and a regular file is always readable, so a ready file
+    // descriptor is not enough; check that another byte can be read.
     if bytes_read == MAX_STRATIS_PASS_SIZE {
         let mut pollers = [PollFd::new(key_file.as_raw_fd(), PollFlags::POLLIN)];
-        let num_events = poll(&mut pollers, 0)?;
-        if num_events > 0 {
+        poll(&mut pollers, 0)?;
+        let readable = pollers[0]
+            .revents()
+            .map(|revents| revents.contains(PollFlags::POLLIN))
+            .unwrap_or(false);
+        if readable && key_file.read(&mut [0u8; 1])? > 0 {
             return Err(StratisError::Msg(format!(
                 "Provided key exceeded maximum allow length of {}",
                 Bytes::from(MAX_STRATIS_PASS_SIZE)
@@ -141,10 +169,9 @@ pub fn validate_name(name: &str) -> StratisResult<()> {
     if name == "." || name == ".." {
         return Err(StratisError::Msg(format!("Name is . or .. : {name}")));
     }
-    // Linux has a maximum filename length of 255 bytes
-    if name.len() > 255 {
+    if name.len() > MAX_NAME_LEN {
         return Err(StratisError::Msg(format!(
-            "Name has more than 255 bytes: {name}"
+            "Name has more than {MAX_NAME_LEN} bytes: {name}"
         )));
     }
     if name.len() != name.trim().len() {
@@ -181,6 +208,28 @@ pub fn validate_name(name: &str) -> StratisResult<()> {
     Ok(())
 }
 
+/// Validate the specs of a group snapshot, which pair the UUID of each origin
+/// with the name of its snapshot. Every snapshot name must be a valid name,
+/// and neither an origin nor a snapshot name may be specified twice.
+pub fn validate_snapshot_specs(specs: &[(FilesystemUuid, &str)]) -> StratisResult<()> {
+    let mut origins = HashSet::new();
+    let mut names = HashSet::new();
+    for (origin_uuid, snapshot_name) in specs {
+        validate_name(snapshot_name)?;
+        if !origins.insert(origin_uuid) {
+            return Err(StratisError::Msg(format!(
+                "Filesystem with UUID {origin_uuid} is specified more than once"
+            )));
+        }
+        if !names.insert(snapshot_name) {
+            return Err(StratisError::Msg(format!(
+                "Snapshot name {snapshot_name} is specified more than once"
+            )));
+        }
+    }
+    Ok(())
+}
+
 /// Verify that all paths are absolute.
 pub fn validate_paths(paths: &[&Path]) -> StratisResult<()> {
     let non_absolute_paths: Vec<&Path> = paths
@@ -202,6 +251,47 @@ pub fn validate_paths(paths: &[&Path]) -> StratisResult<()> {
     }
 }
 
+/// Verify that crypt parameters are valid and that they are specified only
+/// for an encrypted pool.
+pub fn validate_crypt_params(
+    encryption_info: Option<&EncryptionInfo>,
+    crypt_params: Option<&CryptParams>,
+) -> StratisResult<()> {
+    match (encryption_info, crypt_params) {
+        (None, Some(_)) => Err(StratisError::Msg(
+            "Crypt parameters can only be specified for an encrypted pool".to_string(),
+        )),
+        (_, Some(params)) => params.validate(),
+        (_, None) => Ok(()),
+    }
+}
+
+/// Verify that a pool with the given redundancy is not to be encrypted;
+/// encryption is supported only for a data tier without redundancy.
+pub fn validate_redundancy(redundancy: Redundancy, encrypted: bool) -> StratisResult<()> {
+    if encrypted && redundancy != Redundancy::None {
+        return Err(StratisError::Msg(format!(
+            "Encryption is not supported for a pool with redundancy {redundancy}; only a pool without redundancy can be encrypted"
+        )));
+    }
+    Ok(())
+}
+
+/// Verify that an erase mode is applicable to a pool: keyslots can only be
+/// erased on an encrypted pool, while the data of an encrypted pool is not
+/// erased by discarding or overwriting it.
+pub fn validate_erase_mode(erase_mode: EraseMode, encrypted: bool) -> StratisResult<()> {
+    match (erase_mode, encrypted) {
+        (EraseMode::Keyslots, false) => Err(StratisError::Msg(
+            "Keyslots can only be erased on an encrypted pool".to_string(),
+        )),
+        (EraseMode::Discard | EraseMode::Zero, true) => Err(StratisError::Msg(format!(
+            "Erase mode {erase_mode} can only be used on an unencrypted pool; erase the keyslots of an encrypted pool instead"
+        ))),
+        _ => Ok(()),
+    }
+}
+
 pub fn validate_filesystem_size(
     name: &str,
     size_opt: Option<Bytes>,
@@ -348,8 +438,205 @@ pub fn now_to_timestamp() -> DateTime<Utc> {
     Utc.timestamp_opt(Utc::now().timestamp(), 0).unwrap()
 }
 
+/// The format of the time at which a scheduled snapshot was taken, which
+/// is appended to the name of the snapshot.
+const SCHEDULED_SNAPSHOT_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// The separator between the name of the origin of a scheduled snapshot and
+/// the time at which it was taken.
+const SCHEDULED_SNAPSHOT_SEPARATOR: &str = "-snapshot-";
+
+/// The name of a snapshot of the filesystem fs_name taken at the given time
+/// according to its snapshot schedule. The name of the origin is truncated
+/// so that the name of the snapshot does not exceed the maximum length of a
+/// name.
+pub fn scheduled_snapshot_name(fs_name: &str, time: DateTime<Utc>) -> String {
+    let suffix = format!(
+        "{SCHEDULED_SNAPSHOT_SEPARATOR}{}",
+        time.format(SCHEDULED_SNAPSHOT_TIME_FORMAT)
+    );
+    let mut end = min(fs_name.len(), MAX_NAME_LEN - suffix.len());
+    while !fs_name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{suffix}", &fs_name[..end])
+}
+
+/// Validate that the snapshots taken according to the snapshot schedule of
+/// the filesystem fs_name will have valid names.
+pub fn validate_scheduled_snapshot_name(fs_name: &str) -> StratisResult<()> {
+    validate_name(&scheduled_snapshot_name(fs_name, Utc::now()))
+}
+
+/// The time at which a scheduled snapshot was taken, or None if the name is
+/// not the name of a scheduled snapshot. The prefix of the name is not
+/// checked, so that snapshots continue to be recognized after their origin
+/// is renamed.
+fn scheduled_snapshot_time(snapshot_name: &str) -> Option<DateTime<Utc>> {
+    snapshot_name
+        .rsplit_once(SCHEDULED_SNAPSHOT_SEPARATOR)
+        .and_then(|(_, time)| {
+            NaiveDateTime::parse_from_str(time, SCHEDULED_SNAPSHOT_TIME_FORMAT).ok()
+        })
+        .map(|time| Utc.from_utc_datetime(&time))
+}
+
+/// Determine which of the given scheduled snapshots are no longer retained
+/// according to the schedule. A snapshot with a UUID of None stands for a
+/// snapshot that is about to be taken; it is never returned.
+fn expired_snapshots(
+    schedule: &SnapshotSchedule,
+    mut snapshots: Vec<(DateTime<Utc>, Option<FilesystemUuid>)>,
+) -> Vec<FilesystemUuid> {
+    let periods: [(u32, fn(&DateTime<Utc>) -> i64); 3] = [
+        (schedule.keep_hourly, |t| t.timestamp().div_euclid(3600)),
+        (schedule.keep_daily, |t| t.timestamp().div_euclid(86_400)),
+        // Weeks start on Monday; the Unix epoch was a Thursday.
+        (schedule.keep_weekly, |t| {
+            (t.timestamp().div_euclid(86_400) + 3).div_euclid(7)
+        }),
+    ];
+    let mut retained_periods: [Vec<i64>; 3] = Default::default();
+
+    // Visit the snapshots from newest to oldest so that the newest snapshot
+    // in each period is retained.
+    snapshots.sort_unstable_by(|(t1, _), (t2, _)| t2.cmp(t1));
+    snapshots
+        .into_iter()
+        .filter_map(|(time, uuid)| {
+            let mut retained = false;
+            for ((keep, period_of), seen) in periods.iter().zip(retained_periods.iter_mut()) {
+                let period = period_of(&time);
+                if !seen.contains(&period) && seen.len() < *keep as usize {
+                    seen.push(period);
+                    retained = true;
+                }
+            }
+            if retained {
+                None
+            } else {
+                uuid
+            }
+        })
+        .collect()
+}
+
+/// The work that is due according to the snapshot schedule of a filesystem.
+pub struct ScheduledSnapshotPlan {
+    pub origin: FilesystemUuid,
+    /// The name of the snapshot to take, if one is due
+    pub snapshot_name: Option<String>,
+    /// The scheduled snapshots of the origin that are no longer retained
+    pub expired: Vec<FilesystemUuid>,
+}
+
+/// Determine the work that is due at time now according to the snapshot
+/// schedules of the given filesystems of a pool. Filesystems for which no
+/// work is due are omitted.
+///
+/// The scheduled snapshots of a filesystem are the snapshots that have the
+/// filesystem as their origin and whose names end in the time at which they
+/// were taken. A snapshot is due if the newest of them was taken at least
+/// the schedule interval ago.
+pub fn scheduled_snapshot_plans(
+    filesystems: &[(Name, FilesystemUuid, &dyn Filesystem)],
+    now: DateTime<Utc>,
+) -> Vec<ScheduledSnapshotPlan> {
+    filesystems
+        .iter()
+        .filter_map(|(name, uuid, fs)| {
+            let schedule = fs.snapshot_schedule()?;
+            let mut snapshots = filesystems
+                .iter()
+                .filter(|(_, _, snapshot)| snapshot.origin() == Some(*uuid))
+                .filter_map(|(snapshot_name, snapshot_uuid, _)| {
+                    scheduled_snapshot_time(snapshot_name).map(|time| (time, Some(*snapshot_uuid)))
+                })
+                .collect::<Vec<_>>();
+
+            let due = snapshots
+                .iter()
+                .map(|(time, _)| *time)
+                .max()
+                .map(|newest| {
+                    u64::try_from((now - newest).num_seconds())
+                        .map(|elapsed| elapsed >= schedule.interval)
+                        .unwrap_or(false)
+                })
+                .unwrap_or(true);
+            let snapshot_name = if due {
+                snapshots.push((now, None));
+                Some(scheduled_snapshot_name(name, now))
+            } else {
+                None
+            };
+
+            let expired = expired_snapshots(&schedule, snapshots);
+            if snapshot_name.is_none() && expired.is_empty() {
+                None
+            } else {
+                Some(ScheduledSnapshotPlan {
+                    origin: *uuid,
+                    snapshot_name,
+                    expired,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Whether any work is due at time now according to the snapshot schedules
+/// of the filesystems of the given pool.
+pub fn snapshot_schedules_due<P>(pool: &P, now: DateTime<Utc>) -> bool
+where
+    P: Pool,
+{
+    !scheduled_snapshot_plans(&pool.filesystems(), now).is_empty()
+}
+
+/// Carry out the work that is due according to the snapshot schedule of a
+/// filesystem, recording the filesystems that were created and destroyed in
+/// run. No expired snapshots are destroyed if the new snapshot could not be
+/// taken, as they may still be needed to retain a snapshot for the current
+/// period.
+pub fn run_scheduled_snapshot_plan<P>(
+    pool: &mut P,
+    pool_name: &str,
+    pool_uuid: PoolUuid,
+    plan: &ScheduledSnapshotPlan,
+    run: &mut SnapshotScheduleRun,
+) -> StratisResult<()>
+where
+    P: Pool,
+{
+    if let Some(ref snapshot_name) = plan.snapshot_name {
+        if let CreateAction::Created((uuid, _)) =
+            pool.snapshot_filesystem(pool_name, pool_uuid, plan.origin, snapshot_name, false)?
+        {
+            run.created.push(uuid);
+        }
+    }
+    if !plan.expired.is_empty() {
+        if let Some(destroyed) = pool
+            .destroy_filesystems(pool_name, &plan.expired)?
+            .changed()
+        {
+            run.destroyed.extend(destroyed);
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{
+        io::{Seek, SeekFrom, Write},
+        os::unix::io::IntoRawFd,
+    };
+
+    use nix::unistd::{close, pipe, write};
+    use tempfile::tempfile;
+
     use super::*;
 
     #[test]
@@ -388,4 +675,124 @@ mod tests {
         assert_matches!(validate_name("ユニコード"), Ok(_));
         assert_matches!(validate_name("ユニコード?"), Err(_));
     }
+
+    #[test]
+    /// Verify that group snapshot specs with repeated origins or snapshot
+    /// names are rejected.
+    fn test_validate_snapshot_specs() {
+        let (a, b) = (FilesystemUuid::new_v4(), FilesystemUuid::new_v4());
+        assert_matches!(validate_snapshot_specs(&[]), Ok(_));
+        assert_matches!(
+            validate_snapshot_specs(&[(a, "a_snap"), (b, "b_snap")]),
+            Ok(_)
+        );
+        assert_matches!(
+            validate_snapshot_specs(&[(a, "a_snap"), (a, "b_snap")]),
+            Err(_)
+        );
+        assert_matches!(validate_snapshot_specs(&[(a, "snap"), (b, "snap")]), Err(_));
+        assert_matches!(validate_snapshot_specs(&[(a, "bad/name")]), Err(_));
+    }
+
+    #[test]
+    /// Verify that the names of scheduled snapshots can be parsed back to
+    /// the time at which they were taken.
+    fn test_scheduled_snapshot_name() {
+        let time = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let name = scheduled_snapshot_name("fs-snapshot-1", time);
+        assert_eq!(name, "fs-snapshot-1-snapshot-20231114T221320Z");
+        assert_eq!(scheduled_snapshot_time(&name), Some(time));
+        assert_eq!(scheduled_snapshot_time("fs-snapshot-1"), None);
+        assert_eq!(scheduled_snapshot_time("fs"), None);
+    }
+
+    #[test]
+    /// Verify that the name of the origin is truncated so that the name of a
+    /// scheduled snapshot of a filesystem with a long name is a valid name.
+    fn test_scheduled_snapshot_name_long() {
+        let time = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let fs_name = "\u{e9}".repeat(127);
+        assert!(validate_name(&fs_name).is_ok());
+        let name = scheduled_snapshot_name(&fs_name, time);
+        assert!(name.len() <= MAX_NAME_LEN);
+        assert!(validate_name(&name).is_ok());
+        assert!(name.ends_with("-snapshot-20231114T221320Z"));
+        assert_eq!(scheduled_snapshot_time(&name), Some(time));
+    }
+
+    #[test]
+    /// Verify that the newest snapshot of each retained period is kept and
+    /// that a snapshot that is about to be taken counts toward retention.
+    fn test_expired_snapshots() {
+        let schedule = SnapshotSchedule::new(600, 2, 2, 0).unwrap();
+        // 2023-11-15T00:00:00Z
+        let midnight = 1_700_006_400;
+        let at = |secs: i64| Utc.timestamp_opt(midnight + secs, 0).unwrap();
+        let uuids = (0..7).map(|_| FilesystemUuid::new_v4()).collect::<Vec<_>>();
+
+        // One snapshot on the previous day, then two snapshots in each of
+        // the first three hours of the day
+        let snapshots = [-86_400, 0, 600, 3600, 4200, 7200, 7800]
+            .iter()
+            .zip(uuids.iter())
+            .map(|(secs, uuid)| (at(*secs), Some(*uuid)))
+            .collect::<Vec<_>>();
+
+        // The newest snapshots of the two most recent hours and of the two
+        // most recent days are retained.
+        assert_eq!(
+            expired_snapshots(&schedule, snapshots.clone())
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            HashSet::from([uuids[1], uuids[2], uuids[3], uuids[5]])
+        );
+
+        // A new snapshot supersedes the newest one in the same hour.
+        let mut with_new = snapshots;
+        with_new.push((at(8400), None));
+        assert_eq!(
+            expired_snapshots(&schedule, with_new)
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            HashSet::from([uuids[1], uuids[2], uuids[3], uuids[5], uuids[6]])
+        );
+
+        assert!(SnapshotSchedule::new(30, 1, 0, 0).is_err());
+        assert!(SnapshotSchedule::new(600, 0, 0, 0).is_err());
+    }
+
+    #[test]
+    /// Verify that a key of exactly the maximum length is accepted, both
+    /// from a pipe and from a regular file, and that a longer key is
+    /// rejected.
+    fn test_set_key_shared_max_size() {
+        for (len, accepted) in [
+            (MAX_STRATIS_PASS_SIZE, true),
+            (MAX_STRATIS_PASS_SIZE + 1, false),
+        ] {
+            let key = vec![b'a'; len];
+
+            let (read_end, write_end) = pipe().unwrap();
+            write(write_end, &key).unwrap();
+            close(write_end).unwrap();
+            let mut memory = vec![0u8; MAX_STRATIS_PASS_SIZE];
+            let res = set_key_shared(read_end, &mut memory);
+            if accepted {
+                assert_eq!(res.unwrap(), len);
+            } else {
+                assert_matches!(res, Err(_));
+            }
+
+            let mut file = tempfile().unwrap();
+            file.write_all(&key).unwrap();
+            file.seek(SeekFrom::Start(0)).unwrap();
+            let mut memory = vec![0u8; MAX_STRATIS_PASS_SIZE];
+            let res = set_key_shared(file.into_raw_fd(), &mut memory);
+            if accepted {
+                assert_eq!(res.unwrap(), len);
+            } else {
+                assert_matches!(res, Err(_));
+            }
+        }
+    }
 }
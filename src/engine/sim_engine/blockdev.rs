@@ -12,7 +12,7 @@ use devicemapper::{Bytes, Sectors, IEC};
 use crate::engine::{
     engine::BlockDev,
     shared::now_to_timestamp,
-    types::{DevUuid, EncryptionInfo, KeyDescription},
+    types::{DevUuid, EncryptionInfo, IntegrityHash, KeyDescription},
 };
 
 #[derive(Debug)]
@@ -23,6 +23,7 @@ pub struct SimDev {
     hardware_info: Option<String>,
     initialization_time: DateTime<Utc>,
     encryption_info: Option<EncryptionInfo>,
+    integrity: Option<IntegrityHash>,
 }
 
 impl SimDev {
@@ -64,11 +65,23 @@ impl BlockDev for SimDev {
     fn new_size(&self) -> Option<Sectors> {
         None
     }
+
+    fn is_shrunk(&self) -> bool {
+        false
+    }
+
+    fn integrity_errors(&self) -> Option<u64> {
+        self.integrity.map(|_| 0)
+    }
 }
 
 impl SimDev {
     /// Generates a new device from any devnode.
-    pub fn new(devnode: &Path, encryption_info: Option<&EncryptionInfo>) -> (DevUuid, SimDev) {
+    pub fn new(
+        devnode: &Path,
+        encryption_info: Option<&EncryptionInfo>,
+        integrity: Option<IntegrityHash>,
+    ) -> (DevUuid, SimDev) {
         (
             DevUuid::new_v4(),
             SimDev {
@@ -77,6 +90,7 @@ impl SimDev {
                 hardware_info: None,
                 initialization_time: now_to_timestamp(),
                 encryption_info: encryption_info.cloned(),
+                integrity,
             },
         )
     }
@@ -88,6 +102,11 @@ impl SimDev {
         set_blockdev_user_info!(self; user_info)
     }
 
+    /// Set the encryption info for a block device that was not encrypted.
+    pub fn set_encryption_info(&mut self, encryption_info: &EncryptionInfo) {
+        self.encryption_info = Some(encryption_info.clone());
+    }
+
     /// Set the clevis info for a block device.
     pub fn set_clevis_info(&mut self, pin: &str, config: &Value) {
         self.encryption_info = self
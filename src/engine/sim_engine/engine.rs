@@ -4,11 +4,13 @@
 
 use std::{
     collections::{hash_map::RandomState, HashMap, HashSet},
+    fs,
     path::Path,
     sync::Arc,
 };
 
 use async_trait::async_trait;
+use chrono::Utc;
 use futures::executor::block_on;
 use serde_json::{json, Value};
 use tokio::sync::RwLock;
@@ -16,17 +18,21 @@ use tokio::sync::RwLock;
 use crate::{
     engine::{
         engine::{Engine, HandleEvents, KeyActions, Pool, Report},
-        shared::{create_pool_idempotent_or_err, validate_name, validate_paths},
+        shared::{
+            create_pool_idempotent_or_err, snapshot_schedules_due, validate_crypt_params,
+            validate_erase_mode, validate_name, validate_paths, validate_redundancy,
+        },
         sim_engine::{keys::SimKeyActions, pool::SimPool},
         structures::{
             AllLockReadGuard, AllLockWriteGuard, AllOrSomeLock, Lockable, SomeLockReadGuard,
             SomeLockWriteGuard, Table,
         },
         types::{
-            CreateAction, DeleteAction, DevUuid, EncryptionInfo, FilesystemUuid, LockedPoolsInfo,
-            Name, PoolDevice, PoolDiff, PoolIdentifier, PoolUuid, RenameAction, ReportType,
-            SetUnlockAction, StartAction, StopAction, StoppedPoolInfo, StoppedPoolsInfo,
-            StratFilesystemDiff, UdevEngineEvent, UnlockMethod,
+            CreateAction, CryptParams, DeleteAction, DevUuid, EncryptionInfo, EraseMode, Erasure,
+            FilesystemUuid, IntegrityHash, LockedPoolsInfo, Name, PoolDevice, PoolDiff,
+            PoolIdentifier, PoolUuid, Redundancy, RenameAction, ReportType, SetDeleteAction,
+            SetUnlockAction, SnapshotScheduleRun, StartAction, StopAction, StoppedPoolInfo,
+            StoppedPoolsInfo, StratFilesystemDiff, UdevEngineEvent, UnlockMethod,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -126,12 +132,17 @@ impl Engine for SimEngine {
         &self,
         name: &str,
         blockdev_paths: &[&Path],
+        redundancy: Redundancy,
         encryption_info: Option<&EncryptionInfo>,
+        crypt_params: Option<&CryptParams>,
+        integrity: Option<IntegrityHash>,
     ) -> StratisResult<CreateAction<PoolUuid>> {
         validate_name(name)?;
         let name = Name::new(name.to_owned());
 
         validate_paths(blockdev_paths)?;
+        validate_crypt_params(encryption_info, crypt_params)?;
+        validate_redundancy(redundancy, encryption_info.is_some())?;
 
         if let Some(key_desc) = encryption_info.and_then(|ei| ei.key_description()) {
             if !self.key_handler.contains_key(key_desc) {
@@ -144,7 +155,9 @@ impl Engine for SimEngine {
 
         let guard = self.pools.read(PoolIdentifier::Name(name.clone())).await;
         match guard.as_ref().map(|g| g.as_tuple()) {
-            Some((_, _, pool)) => create_pool_idempotent_or_err(pool, &name, blockdev_paths),
+            Some((_, _, pool)) => {
+                create_pool_idempotent_or_err(pool, &name, blockdev_paths, redundancy, integrity)
+            }
             None => {
                 if blockdev_paths.is_empty() {
                     Err(StratisError::Msg(
@@ -154,7 +167,15 @@ impl Engine for SimEngine {
                     let device_set: HashSet<_, RandomState> = HashSet::from_iter(blockdev_paths);
                     let devices = device_set.into_iter().cloned().collect::<Vec<_>>();
 
-                    let (pool_uuid, pool) = SimPool::new(&devices, encryption_info);
+                    if devices.len() < redundancy.copies() {
+                        return Err(StratisError::Msg(format!(
+                            "Redundancy {redundancy} requires at least {} devices in the data tier",
+                            redundancy.copies()
+                        )));
+                    }
+
+                    let (pool_uuid, pool) =
+                        SimPool::new(&devices, redundancy, encryption_info, integrity);
 
                     self.pools.modify_all().await.insert(
                         Name::new(name.to_owned()),
@@ -172,11 +193,18 @@ impl Engine for SimEngine {
         (Vec::new(), HashMap::new())
     }
 
-    async fn destroy_pool(&self, uuid: PoolUuid) -> StratisResult<DeleteAction<PoolUuid>> {
+    async fn destroy_pool(
+        &self,
+        uuid: PoolUuid,
+        erase_mode: Option<EraseMode>,
+    ) -> StratisResult<(DeleteAction<PoolUuid>, Option<Erasure>)> {
         if let Some(pool) = self.pools.read(PoolIdentifier::Uuid(uuid)).await {
             if pool.has_filesystems() {
                 return Err(StratisError::Msg("filesystems remaining on pool".into()));
             }
+            if let Some(erase_mode) = erase_mode {
+                validate_erase_mode(erase_mode, pool.is_encrypted())?;
+            }
             drop(pool);
             self.pools
                 .modify_all()
@@ -185,12 +213,26 @@ impl Engine for SimEngine {
                 .expect("Must succeed since self.pool.get_by_uuid() returned a value")
                 .1
                 .destroy()?;
-            Ok(DeleteAction::Deleted(uuid))
+            let erasure = match erase_mode {
+                Some(EraseMode::Discard | EraseMode::Zero) => Some(Erasure::new(|progress| {
+                    progress.set(100);
+                    Ok(String::new())
+                })),
+                Some(EraseMode::Keyslots) | None => None,
+            };
+            Ok((DeleteAction::Deleted(uuid), erasure))
         } else {
-            Ok(DeleteAction::Identity)
+            Ok((DeleteAction::Identity, None))
         }
     }
 
+    async fn remove_cache(&self, uuid: PoolUuid) -> StratisResult<SetDeleteAction<DevUuid>> {
+        let mut guard = get_mut_pool!(self; PoolIdentifier::Uuid(uuid))
+            .ok_or_else(|| StratisError::Msg(format!("No pool with UUID {uuid} found")))?;
+        let (name, _, pool) = guard.as_mut_tuple();
+        pool.remove_cache(uuid, &name)
+    }
+
     async fn rename_pool(
         &self,
         uuid: PoolUuid,
@@ -283,6 +325,44 @@ impl Engine for SimEngine {
         HashMap::default()
     }
 
+    async fn run_snapshot_schedules(&self) -> HashMap<PoolUuid, SnapshotScheduleRun> {
+        let now = Utc::now();
+        let uuids = self
+            .pools
+            .read_all()
+            .await
+            .iter()
+            .filter(|(_, _, pool)| snapshot_schedules_due(*pool, now))
+            .map(|(_, uuid, _)| *uuid)
+            .collect::<Vec<_>>();
+
+        let mut runs = HashMap::new();
+        for uuid in uuids {
+            if let Some(mut guard) = self.pools.write(PoolIdentifier::Uuid(uuid)).await {
+                let (name, uuid, pool) = guard.as_mut_tuple();
+                match pool.run_snapshot_schedules(&name, uuid, now) {
+                    Ok(run) => {
+                        runs.insert(uuid, run);
+                    }
+                    Err(e) => {
+                        warn!("Running snapshot schedules failed with error: {}", e);
+                    }
+                }
+            }
+        }
+        runs
+    }
+
+    async fn refresh_space_usage(&self) -> HashMap<FilesystemUuid, StratFilesystemDiff> {
+        HashMap::default()
+    }
+
+    async fn continue_reencryption(&self) -> HashMap<PoolUuid, PoolDiff> {
+        HashMap::default()
+    }
+
+    async fn unlock_pending_pools(&self) {}
+
     async fn get_key_handler(&self) -> Arc<dyn KeyActions> {
         Arc::clone(&self.key_handler) as Arc<dyn KeyActions>
     }
@@ -375,6 +455,44 @@ impl Engine for SimEngine {
         }
     }
 
+    async fn restore_luks_header(
+        &self,
+        pool_uuid: PoolUuid,
+        dev_uuid: DevUuid,
+        _: &Path,
+        archive_path: &Path,
+    ) -> StratisResult<()> {
+        if self
+            .pools
+            .read(PoolIdentifier::Uuid(pool_uuid))
+            .await
+            .is_some()
+        {
+            return Err(StratisError::Msg(format!(
+                "Pool {pool_uuid} must be stopped before a LUKS2 header of one of its devices can be restored"
+            )));
+        }
+
+        let archive = serde_json::from_slice::<Value>(&fs::read(archive_path)?)?;
+        if archive.get("pool_uuid").and_then(|u| u.as_str()) != Some(&pool_uuid.to_string()) {
+            return Err(StratisError::Msg(format!(
+                "Header archive {} does not belong to pool {pool_uuid}",
+                archive_path.display()
+            )));
+        }
+        if archive
+            .get("headers")
+            .and_then(|h| h.get(dev_uuid.to_string()))
+            .is_none()
+        {
+            return Err(StratisError::Msg(format!(
+                "Header archive {} contains no header for device {dev_uuid}",
+                archive_path.display()
+            )));
+        }
+        Ok(())
+    }
+
     async fn refresh_state(&self) -> StratisResult<()> {
         Ok(())
     }
@@ -386,9 +504,11 @@ impl Engine for SimEngine {
 
 #[cfg(test)]
 mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
     use crate::engine::{
         engine::Engine,
-        types::{EngineAction, RenameAction},
+        types::{EngineAction, JobProgress, KeyDescription, Pbkdf, RenameAction},
     };
 
     use super::*;
@@ -405,7 +525,7 @@ mod tests {
     #[test]
     /// When an engine has no pools, destroying any pool must succeed
     fn destroy_pool_empty() {
-        assert!(test_async!(SimEngine::default().destroy_pool(PoolUuid::new_v4())).is_ok());
+        assert!(test_async!(SimEngine::default().destroy_pool(PoolUuid::new_v4(), None)).is_ok());
     }
 
     #[test]
@@ -415,23 +535,60 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             "name",
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
         .changed()
         .unwrap();
-        assert!(test_async!(engine.destroy_pool(uuid)).is_ok());
+        assert!(test_async!(engine.destroy_pool(uuid, None)).is_ok());
     }
 
     #[test]
     /// Destroying a pool with devices should succeed
     fn destroy_pool_w_devices() {
         let engine = SimEngine::default();
-        let uuid = test_async!(engine.create_pool("name", strs_to_paths!(["/s/d"]), None))
-            .unwrap()
-            .changed()
-            .unwrap();
-        assert!(test_async!(engine.destroy_pool(uuid)).is_ok());
+        let uuid = test_async!(engine.create_pool(
+            "name",
+            strs_to_paths!(["/s/d"]),
+            Redundancy::None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        assert!(test_async!(engine.destroy_pool(uuid, None)).is_ok());
+    }
+
+    #[test]
+    /// Destroying a pool with an erase mode that does not apply to it should
+    /// fail and leave the pool in place; a valid erase mode should complete.
+    fn destroy_pool_erase() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "name",
+            strs_to_paths!(["/s/d"]),
+            Redundancy::None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        assert!(test_async!(engine.destroy_pool(uuid, Some(EraseMode::Keyslots))).is_err());
+        assert!(test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid))).is_some());
+        let erasure = match test_async!(engine.destroy_pool(uuid, Some(EraseMode::Zero))) {
+            Ok((DeleteAction::Deleted(_), Some(erasure))) => erasure,
+            res => panic!("Unexpected result of destroying the pool: {res:?}"),
+        };
+        let progress = JobProgress::default();
+        erasure.run(&progress).unwrap();
+        assert_eq!(progress.get(), 100);
     }
 
     #[test]
@@ -439,16 +596,23 @@ mod tests {
     fn destroy_pool_w_filesystem() {
         let engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = test_async!(engine.create_pool(pool_name, strs_to_paths!(["/s/d"]), None))
-            .unwrap()
-            .changed()
-            .unwrap();
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/s/d"]),
+            Redundancy::None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
         {
             let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
             pool.create_filesystems(pool_name, uuid, &[("test", None, None)])
                 .unwrap();
         }
-        assert!(test_async!(engine.destroy_pool(uuid)).is_err());
+        assert!(test_async!(engine.destroy_pool(uuid, None)).is_err());
     }
 
     #[test]
@@ -458,9 +622,9 @@ mod tests {
         let name = "name";
         let engine = SimEngine::default();
         let devices = strs_to_paths!(["/s/d"]);
-        test_async!(engine.create_pool(name, devices, None)).unwrap();
+        test_async!(engine.create_pool(name, devices, Redundancy::None, None, None, None)).unwrap();
         assert_matches!(
-            test_async!(engine.create_pool(name, devices, None)),
+            test_async!(engine.create_pool(name, devices, Redundancy::None, None, None, None)),
             Ok(CreateAction::Identity)
         );
     }
@@ -470,13 +634,130 @@ mod tests {
     fn create_pool_name_collision_different_args() {
         let name = "name";
         let engine = SimEngine::default();
-        test_async!(engine.create_pool(name, strs_to_paths!(["/s/d"]), None)).unwrap();
+        test_async!(engine.create_pool(
+            name,
+            strs_to_paths!(["/s/d"]),
+            Redundancy::None,
+            None,
+            None,
+            None
+        ))
+        .unwrap();
         assert!(test_async!(engine.create_pool(
             name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
+            None,
+        ))
+        .is_err());
+    }
+
+    #[test]
+    /// Creating a new pool with the same name and devices but a different
+    /// redundancy should fail
+    fn create_pool_name_collision_different_redundancy() {
+        let name = "name";
+        let engine = SimEngine::default();
+        let devices = strs_to_paths!(["/dev/one", "/dev/two"]);
+        test_async!(engine.create_pool(name, devices, Redundancy::None, None, None, None)).unwrap();
+        assert!(test_async!(engine.create_pool(
+            name,
+            devices,
+            Redundancy::Raid1,
+            None,
+            None,
+            None
+        ))
+        .is_err());
+    }
+
+    #[test]
+    /// Crypt parameters can only be specified for an encrypted pool and are
+    /// validated before the pool is created
+    fn create_pool_crypt_params() {
+        let engine = SimEngine::default();
+        let crypt_params = CryptParams {
+            cipher: Some("xchacha20,aes-adiantum-plain64".to_string()),
+            pbkdf: Some(Pbkdf::Argon2id),
+            pbkdf_memory: Some(65536),
+            ..CryptParams::default()
+        };
+        assert!(test_async!(engine.create_pool(
+            "name",
+            strs_to_paths!(["/s/d"]),
+            Redundancy::None,
+            None,
+            Some(&crypt_params),
             None,
         ))
         .is_err());
+
+        let encryption_info =
+            EncryptionInfo::KeyDesc(KeyDescription::try_from("key".to_string()).unwrap());
+        assert_matches!(
+            test_async!(engine.create_pool(
+                "name",
+                strs_to_paths!(["/s/d"]),
+                Redundancy::None,
+                Some(&encryption_info),
+                Some(&CryptParams {
+                    sector_size: Some(1000),
+                    ..crypt_params
+                }),
+                None,
+            )),
+            Err(StratisError::Msg(msg)) if msg.contains("sector size")
+        );
+    }
+
+    #[test]
+    /// Creating a pool with redundancy requires a distinct device for each
+    /// copy of the data
+    fn create_pool_raid1_too_few_devices() {
+        let engine = SimEngine::default();
+        assert!(test_async!(engine.create_pool(
+            "name",
+            strs_to_paths!(["/s/d", "/s/d"]),
+            Redundancy::Raid1,
+            None,
+            None,
+            None,
+        ))
+        .is_err());
+        let uuid = test_async!(engine.create_pool(
+            "name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            Redundancy::Raid1,
+            None,
+            None,
+            None,
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let pool = test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        assert_eq!(pool.redundancy(), Redundancy::Raid1);
+    }
+
+    #[test]
+    /// Creating an encrypted pool with redundancy should fail
+    fn create_pool_raid1_encrypted() {
+        let engine = SimEngine::default();
+        assert_matches!(
+            test_async!(engine.create_pool(
+                "name",
+                strs_to_paths!(["/dev/one", "/dev/two"]),
+                Redundancy::Raid1,
+                Some(&EncryptionInfo::KeyDesc(
+                    KeyDescription::try_from("key".to_string()).unwrap()
+                )),
+                None,
+                None,
+            )),
+            Err(StratisError::Msg(msg)) if msg.contains("redundancy")
+        );
     }
 
     #[test]
@@ -485,15 +766,22 @@ mod tests {
         let path = "/s/d";
         let engine = SimEngine::default();
         assert_matches!(
-            test_async!(engine.create_pool("name", strs_to_paths!([path, path]), None))
-                .unwrap()
-                .changed()
-                .map(
-                    |uuid| test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid)))
-                        .unwrap()
-                        .blockdevs()
-                        .len()
-                ),
+            test_async!(engine.create_pool(
+                "name",
+                strs_to_paths!([path, path]),
+                Redundancy::None,
+                None,
+                None,
+                None,
+            ))
+            .unwrap()
+            .changed()
+            .map(
+                |uuid| test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid)))
+                    .unwrap()
+                    .blockdevs()
+                    .len()
+            ),
             Some(1)
         );
     }
@@ -516,6 +804,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -534,6 +825,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             "old_name",
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -553,6 +847,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             "old_name",
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -561,6 +858,9 @@ mod tests {
         test_async!(engine.create_pool(
             new_name,
             strs_to_paths!(["/dev/four", "/dev/five", "/dev/six"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap();
@@ -575,6 +875,9 @@ mod tests {
         test_async!(engine.create_pool(
             new_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap();
@@ -583,4 +886,61 @@ mod tests {
             Ok(RenameAction::NoSource)
         );
     }
+
+    #[test]
+    /// A LUKS2 header can only be restored from an archive of the headers
+    /// of the pool that the device belongs to, and only while the pool is
+    /// stopped.
+    fn back_up_and_restore_luks_headers() {
+        let engine = SimEngine::default();
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let archive = tmp_dir.path().join("headers");
+        let uuid = test_async!(engine.create_pool(
+            "name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            Redundancy::None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+
+        let dev_uuid = {
+            let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+            let (name, _, pool) = guard.as_mut_tuple();
+            assert!(pool.back_up_luks_headers(uuid, &archive).is_err());
+            pool.encrypt_pool(
+                uuid,
+                &name,
+                &EncryptionInfo::KeyDesc(KeyDescription::try_from("key".to_string()).unwrap()),
+                None,
+            )
+            .unwrap();
+            pool.back_up_luks_headers(uuid, &archive).unwrap();
+            assert_eq!(
+                fs::metadata(&archive).unwrap().permissions().mode() & 0o777,
+                0o600
+            );
+            assert!(pool.back_up_luks_headers(uuid, &archive).is_err());
+            pool.blockdevs()[0].0
+        };
+
+        let path = Path::new("/dev/one");
+        assert!(test_async!(engine.restore_luks_header(uuid, dev_uuid, path, &archive)).is_err());
+        test_async!(engine.stop_pool(PoolIdentifier::Uuid(uuid), false)).unwrap();
+        test_async!(engine.restore_luks_header(uuid, dev_uuid, path, &archive)).unwrap();
+        assert!(
+            test_async!(engine.restore_luks_header(uuid, DevUuid::new_v4(), path, &archive))
+                .is_err()
+        );
+        assert!(test_async!(engine.restore_luks_header(
+            PoolUuid::new_v4(),
+            dev_uuid,
+            path,
+            &archive
+        ))
+        .is_err());
+    }
 }
@@ -2,7 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::path::PathBuf;
+use std::{mem::swap, path::PathBuf};
 
 use chrono::{DateTime, Utc};
 use serde_json::{Map, Value};
@@ -10,7 +10,9 @@ use serde_json::{Map, Value};
 use devicemapper::{Bytes, Sectors};
 
 use crate::{
-    engine::Filesystem,
+    engine::{
+        Filesystem, FilesystemSpaceUsage, FilesystemUuid, SnapshotSchedule, SnapshotScheduleStatus,
+    },
     stratis::{StratisError, StratisResult},
 };
 
@@ -20,10 +22,18 @@ pub struct SimFilesystem {
     created: DateTime<Utc>,
     size: Sectors,
     size_limit: Option<Sectors>,
+    origin: Option<FilesystemUuid>,
+    snapshot_schedule: Option<SnapshotSchedule>,
+    snapshot_schedule_status: Option<SnapshotScheduleStatus>,
+    read_only: bool,
 }
 
 impl SimFilesystem {
-    pub fn new(size: Sectors, size_limit: Option<Sectors>) -> StratisResult<SimFilesystem> {
+    pub fn new(
+        size: Sectors,
+        size_limit: Option<Sectors>,
+        origin: Option<FilesystemUuid>,
+    ) -> StratisResult<SimFilesystem> {
         if let Some(limit) = size_limit {
             if limit < size {
                 return Err(StratisError::Msg(format!(
@@ -36,6 +46,10 @@ impl SimFilesystem {
             created: Utc::now(),
             size,
             size_limit,
+            origin,
+            snapshot_schedule: None,
+            snapshot_schedule_status: None,
+            read_only: false,
         })
     }
 
@@ -61,6 +75,48 @@ impl SimFilesystem {
             }
         }
     }
+
+    /// Set or remove the snapshot schedule. Return true if the schedule was
+    /// changed.
+    pub fn set_snapshot_schedule(&mut self, schedule: Option<SnapshotSchedule>) -> bool {
+        if self.snapshot_schedule == schedule {
+            false
+        } else {
+            self.snapshot_schedule = schedule;
+            true
+        }
+    }
+
+    /// Make the filesystem read-only or writable. Return true if the setting
+    /// was changed.
+    pub fn set_read_only(&mut self, read_only: bool) -> bool {
+        if self.read_only == read_only {
+            false
+        } else {
+            self.read_only = read_only;
+            true
+        }
+    }
+
+    /// Record the outcome of a run of the snapshot schedule.
+    pub fn set_snapshot_schedule_status(&mut self, status: SnapshotScheduleStatus) {
+        self.snapshot_schedule_status = Some(status);
+    }
+
+    /// Exchange the contents of this filesystem and other.
+    pub fn swap_contents(&mut self, other: &mut SimFilesystem) -> StratisResult<()> {
+        for (fs, new_size) in [(&*self, other.size), (&*other, self.size)] {
+            if let Some(limit) = fs.size_limit {
+                if new_size > limit {
+                    return Err(StratisError::Msg(format!(
+                        "Size {new_size} of the exchanged contents exceeds the filesystem size limit {limit}"
+                    )));
+                }
+            }
+        }
+        swap(&mut self.size, &mut other.size);
+        Ok(())
+    }
 }
 
 impl Filesystem for SimFilesystem {
@@ -89,6 +145,29 @@ impl Filesystem for SimFilesystem {
     fn size_limit(&self) -> Option<Sectors> {
         self.size_limit
     }
+
+    fn origin(&self) -> Option<FilesystemUuid> {
+        self.origin
+    }
+
+    fn snapshot_schedule(&self) -> Option<SnapshotSchedule> {
+        self.snapshot_schedule
+    }
+
+    fn snapshot_schedule_status(&self) -> Option<SnapshotScheduleStatus> {
+        self.snapshot_schedule_status.clone()
+    }
+
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn space_usage(&self) -> Option<FilesystemSpaceUsage> {
+        Some(FilesystemSpaceUsage {
+            exclusive: (self.size / 2u64).bytes(),
+            shared: Bytes(0),
+        })
+    }
 }
 
 impl<'a> Into<Value> for &'a SimFilesystem {
@@ -112,6 +191,42 @@ impl<'a> Into<Value> for &'a SimFilesystem {
                     .unwrap_or_else(|| "Not set".to_string()),
             ),
         );
+        json.insert(
+            "origin".to_string(),
+            Value::from(
+                self.origin
+                    .map(|u| u.to_string())
+                    .unwrap_or_else(|| "Not set".to_string()),
+            ),
+        );
+        json.insert("read_only".to_string(), Value::from(self.read_only));
+        if let Some(space_usage) = self.space_usage() {
+            json.insert(
+                "exclusive".to_string(),
+                Value::from(space_usage.exclusive.to_string()),
+            );
+            json.insert(
+                "shared".to_string(),
+                Value::from(space_usage.shared.to_string()),
+            );
+        }
+        json.insert(
+            "snapshot_schedule".to_string(),
+            Value::from(
+                self.snapshot_schedule
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| "Not set".to_string()),
+            ),
+        );
+        json.insert(
+            "snapshot_schedule_status".to_string(),
+            Value::from(
+                self.snapshot_schedule_status
+                    .as_ref()
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| "Not run".to_string()),
+            ),
+        );
         Value::from(json)
     }
 }
@@ -4,11 +4,15 @@
 
 use std::{
     collections::{hash_map::RandomState, HashMap, HashSet},
+    fs::OpenOptions,
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
     path::Path,
     vec::Vec,
 };
 
-use serde_json::{Map, Value};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
 
 use devicemapper::{Bytes, Sectors, IEC};
 
@@ -16,16 +20,20 @@ use crate::{
     engine::{
         engine::{BlockDev, Filesystem, Pool},
         shared::{
-            gather_encryption_info, init_cache_idempotent_or_err, validate_filesystem_size,
-            validate_filesystem_size_specs, validate_name, validate_paths,
+            gather_encryption_info, init_cache_idempotent_or_err, run_scheduled_snapshot_plan,
+            scheduled_snapshot_plans, validate_crypt_params, validate_filesystem_size,
+            validate_filesystem_size_specs, validate_name, validate_paths, validate_redundancy,
+            validate_scheduled_snapshot_name, validate_snapshot_specs,
         },
         sim_engine::{blockdev::SimDev, filesystem::SimFilesystem},
         structures::Table,
         types::{
-            ActionAvailability, BlockDevTier, Clevis, CreateAction, DeleteAction, DevUuid,
-            EncryptionInfo, FilesystemUuid, GrowAction, Key, KeyDescription, Name, PoolDiff,
-            PoolEncryptionInfo, PoolUuid, RegenAction, RenameAction, SetCreateAction,
-            SetDeleteAction,
+            ActionAvailability, AllocationPolicy, BlockDevTier, CacheSettings, CacheStats, Clevis,
+            CreateAction, CryptParams, DataShrink, DeleteAction, DevUuid, EncryptedDevice,
+            EncryptionInfo, FilesystemUuid, GrowAction, IntegrityHash, Key, KeyDescription, Name,
+            PoolDiff, PoolEncryptionInfo, PoolUuid, Redundancy, Reencryption, RegenAction,
+            RenameAction, RevertAction, SetCreateAction, SetDeleteAction, SnapshotSchedule,
+            SnapshotScheduleRun, SnapshotScheduleStatus, StartAction, StratFilesystemDiff,
         },
         PropChangeAction,
     },
@@ -36,23 +44,38 @@ use crate::{
 pub struct SimPool {
     block_devs: HashMap<DevUuid, SimDev>,
     cache_devs: HashMap<DevUuid, SimDev>,
+    cache_settings: CacheSettings,
     filesystems: Table<FilesystemUuid, SimFilesystem>,
     fs_limit: u64,
     enable_overprov: bool,
+    redundancy: Redundancy,
+    integrity: Option<IntegrityHash>,
+    allocation_policy: AllocationPolicy,
+    keyring_bindings: Vec<KeyDescription>,
 }
 
 impl SimPool {
-    pub fn new(paths: &[&Path], enc_info: Option<&EncryptionInfo>) -> (PoolUuid, SimPool) {
+    pub fn new(
+        paths: &[&Path],
+        redundancy: Redundancy,
+        enc_info: Option<&EncryptionInfo>,
+        integrity: Option<IntegrityHash>,
+    ) -> (PoolUuid, SimPool) {
         let devices: HashSet<_, RandomState> = HashSet::from_iter(paths);
-        let device_pairs = devices.iter().map(|p| SimDev::new(p, enc_info));
+        let device_pairs = devices.iter().map(|p| SimDev::new(p, enc_info, integrity));
         (
             PoolUuid::new_v4(),
             SimPool {
                 block_devs: device_pairs.collect(),
                 cache_devs: HashMap::new(),
+                cache_settings: CacheSettings::default(),
                 filesystems: Table::default(),
                 fs_limit: 10,
                 enable_overprov: true,
+                redundancy,
+                integrity,
+                allocation_policy: AllocationPolicy::default(),
+                keyring_bindings: Vec::new(),
             },
         )
     }
@@ -84,6 +107,13 @@ impl SimPool {
             self.block_devs.values().map(|bd| bd.encryption_info()),
         )
         .expect("sim engine cannot create pools with encrypted and unencrypted devices together")
+        .map(|info| {
+            info.set_keyring_bindings(
+                self.block_devs
+                    .values()
+                    .map(|_| self.keyring_bindings.clone()),
+            )
+        })
     }
 
     fn add_clevis_info(&mut self, pin: &str, config: &Value) {
@@ -183,6 +213,7 @@ impl Pool for SimPool {
         _pool_name: &str,
         blockdevs: &[&Path],
         supports_encrypted: bool,
+        settings: CacheSettings,
     ) -> StratisResult<SetCreateAction<DevUuid>> {
         validate_paths(blockdevs)?;
 
@@ -197,9 +228,13 @@ impl Pool for SimPool {
                     "At least one blockdev path is required to initialize a cache.".to_string(),
                 ));
             }
-            let blockdev_pairs: Vec<_> = blockdevs.iter().map(|p| SimDev::new(p, None)).collect();
+            let blockdev_pairs: Vec<_> = blockdevs
+                .iter()
+                .map(|p| SimDev::new(p, None, None))
+                .collect();
             let blockdev_uuids: Vec<_> = blockdev_pairs.iter().map(|(uuid, _)| *uuid).collect();
             self.cache_devs.extend(blockdev_pairs);
+            self.cache_settings = settings;
             Ok(SetCreateAction::new(blockdev_uuids))
         } else {
             init_cache_idempotent_or_err(
@@ -209,6 +244,17 @@ impl Pool for SimPool {
         }
     }
 
+    fn remove_cache(
+        &mut self,
+        _pool_uuid: PoolUuid,
+        _pool_name: &str,
+    ) -> StratisResult<SetDeleteAction<DevUuid>> {
+        self.cache_settings = CacheSettings::default();
+        Ok(SetDeleteAction::new(
+            self.cache_devs.drain().map(|(uuid, _)| uuid).collect(),
+        ))
+    }
+
     fn create_filesystems<'b>(
         &mut self,
         _pool_name: &str,
@@ -243,7 +289,7 @@ impl Pool for SimPool {
         for (name, (size, size_limit)) in spec_map {
             if !self.filesystems.contains_name(name) {
                 let uuid = FilesystemUuid::new_v4();
-                let new_filesystem = SimFilesystem::new(size, size_limit)?;
+                let new_filesystem = SimFilesystem::new(size, size_limit, None)?;
                 self.filesystems
                     .insert(Name::new((name).to_owned()), uuid, new_filesystem);
                 result.push((name, uuid, size));
@@ -285,14 +331,9 @@ impl Pool for SimPool {
 
         let filtered_device_pairs: Vec<_> = devices
             .iter()
-            .map(|p| {
-                SimDev::new(
-                    p,
-                    match tier {
-                        BlockDevTier::Data => encryption_info.as_ref(),
-                        BlockDevTier::Cache => None,
-                    },
-                )
+            .map(|p| match tier {
+                BlockDevTier::Data => SimDev::new(p, encryption_info.as_ref(), self.integrity),
+                BlockDevTier::Cache => SimDev::new(p, None, None),
             })
             .filter(|(_, sd)| !filter.contains(&sd.devnode()))
             .collect();
@@ -311,6 +352,151 @@ impl Pool for SimPool {
         Ok((SetCreateAction::new(ret_uuids), None))
     }
 
+    fn remove_blockdevs(
+        &mut self,
+        _pool_uuid: PoolUuid,
+        _pool_name: &str,
+        uuids: &[DevUuid],
+    ) -> StratisResult<(SetDeleteAction<DevUuid>, Option<PoolDiff>)> {
+        if let Some(uuid) = uuids.iter().find(|u| self.cache_devs.contains_key(*u)) {
+            return Err(StratisError::Msg(format!(
+                "Blockdev with UUID {uuid} belongs to the cache tier; only blockdevs in the data tier can be removed"
+            )));
+        }
+
+        let to_remove = uuids
+            .iter()
+            .filter(|u| self.block_devs.contains_key(*u))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        if !to_remove.is_empty() && to_remove.len() == self.block_devs.len() {
+            return Err(StratisError::Msg(
+                "At least one blockdev must remain in the data tier".to_string(),
+            ));
+        }
+
+        let mut removed = Vec::new();
+        for uuid in to_remove {
+            if self.block_devs.remove(&uuid).is_some() {
+                removed.push(uuid);
+            }
+        }
+        Ok((SetDeleteAction::new(removed), None))
+    }
+
+    fn replace_blockdev(
+        &mut self,
+        pool_uuid: PoolUuid,
+        _pool_name: &str,
+        old: DevUuid,
+        new: &Path,
+    ) -> StratisResult<(CreateAction<DevUuid>, Option<PoolDiff>)> {
+        if self.cache_devs.contains_key(&old) {
+            return Err(StratisError::Msg(format!(
+                "Blockdev with UUID {old} belongs to the cache tier; only blockdevs in the data tier can be replaced"
+            )));
+        }
+        if !self.block_devs.contains_key(&old) {
+            return Err(StratisError::Msg(format!(
+                "Pool with UUID {pool_uuid} has no blockdev with UUID {old}"
+            )));
+        }
+
+        validate_paths(&[new])?;
+
+        if self
+            .block_devs
+            .values()
+            .chain(self.cache_devs.values())
+            .any(|d| d.devnode() == new)
+        {
+            return Err(StratisError::Msg(format!(
+                "Device {} already belongs to pool with UUID {pool_uuid}",
+                new.display()
+            )));
+        }
+
+        // The simulator has no data to copy, so the replacement completes
+        // immediately.
+        let encryption_info = pool_enc_to_enc!(self.encryption_info());
+        let (uuid, dev) = SimDev::new(new, encryption_info.as_ref(), self.integrity);
+        self.block_devs.remove(&old);
+        self.block_devs.insert(uuid, dev);
+        Ok((CreateAction::Created(uuid), None))
+    }
+
+    fn encrypt_pool(
+        &mut self,
+        pool_uuid: PoolUuid,
+        _pool_name: &str,
+        encryption_info: &EncryptionInfo,
+        crypt_params: Option<&CryptParams>,
+    ) -> StratisResult<(CreateAction<EncryptedDevice>, Option<PoolDiff>)> {
+        validate_crypt_params(Some(encryption_info), crypt_params)?;
+        validate_redundancy(self.redundancy, true)?;
+        if let Some(current) = pool_enc_to_enc!(self.encryption_info()) {
+            return if &current == encryption_info {
+                Ok((CreateAction::Identity, None))
+            } else {
+                Err(StratisError::Msg(format!(
+                    "Pool with UUID {pool_uuid} is already encrypted with different encryption info"
+                )))
+            };
+        }
+        if self.has_cache() {
+            return Err(StratisError::Msg(
+                "A pool with a cache can not be encrypted; remove the cache first".to_string(),
+            ));
+        }
+        // The simulator has no data to encrypt, so the encryption completes
+        // immediately.
+        self.block_devs
+            .iter_mut()
+            .for_each(|(_, bd)| bd.set_encryption_info(encryption_info));
+        Ok((CreateAction::Created(EncryptedDevice), None))
+    }
+
+    fn reencrypt_pool(
+        &mut self,
+        _pool_name: &str,
+        crypt_params: Option<&CryptParams>,
+    ) -> StratisResult<(StartAction<Reencryption>, Option<PoolDiff>)> {
+        if !self.is_encrypted() {
+            return Err(StratisError::Msg("The pool is not encrypted".to_string()));
+        }
+        crypt_params.map(CryptParams::validate).transpose()?;
+
+        // The simulator has no data to reencrypt, so the reencryption
+        // completes immediately.
+        Ok((StartAction::Started(Reencryption), None))
+    }
+
+    fn back_up_luks_headers(&self, pool_uuid: PoolUuid, archive_path: &Path) -> StratisResult<()> {
+        if !self.is_encrypted() {
+            return Err(StratisError::Msg("The pool is not encrypted".to_string()));
+        }
+
+        // The simulator has no LUKS2 headers, so the archive only records
+        // the devices that it would contain headers for.
+        let archive = json!({
+            "pool_uuid": pool_uuid.to_string(),
+            "headers": self
+                .block_devs
+                .keys()
+                .chain(self.cache_devs.keys())
+                .map(|uuid| (uuid.to_string(), Value::from("")))
+                .collect::<Map<_, _>>(),
+        });
+        let mut f = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(archive_path)?;
+        f.write_all(archive.to_string().as_bytes())?;
+        Ok(())
+    }
+
     fn bind_clevis(
         &mut self,
         pin: &str,
@@ -451,6 +637,60 @@ impl Pool for SimPool {
         })
     }
 
+    fn add_keyring_binding(
+        &mut self,
+        key_description: &KeyDescription,
+    ) -> StratisResult<CreateAction<Key>> {
+        let encryption_info = match pool_enc_to_enc!(self.encryption_info()) {
+            Some(ei) => ei,
+            None => {
+                return Err(StratisError::Msg(
+                    "Requested pool does not appear to be encrypted".to_string(),
+                ))
+            }
+        };
+
+        if self.keyring_bindings.contains(key_description) {
+            Ok(CreateAction::Identity)
+        } else if encryption_info.key_description() == Some(key_description) {
+            Err(StratisError::Msg(format!(
+                "Key description {} is already the primary keyring binding of the pool",
+                key_description.as_application_str(),
+            )))
+        } else {
+            self.keyring_bindings.push(key_description.to_owned());
+            Ok(CreateAction::Created(Key))
+        }
+    }
+
+    fn remove_keyring_binding(
+        &mut self,
+        key_description: &KeyDescription,
+    ) -> StratisResult<DeleteAction<Key>> {
+        if pool_enc_to_enc!(self.encryption_info()).is_none() {
+            return Err(StratisError::Msg(
+                "Requested pool does not appear to be encrypted".to_string(),
+            ));
+        }
+
+        let len = self.keyring_bindings.len();
+        self.keyring_bindings.retain(|kd| kd != key_description);
+        Ok(if self.keyring_bindings.len() < len {
+            DeleteAction::Deleted(Key)
+        } else {
+            DeleteAction::Identity
+        })
+    }
+
+    fn keyring_bindings(&self) -> StratisResult<Vec<KeyDescription>> {
+        match self.encryption_info() {
+            Some(ei) => ei.keyring_bindings().map(|kds| kds.to_vec()),
+            None => Err(StratisError::Msg(
+                "Requested pool does not appear to be encrypted".to_string(),
+            )),
+        }
+    }
+
     // The sim engine does not store token info so this method will always return
     // RenameAction::Identity.
     fn rebind_clevis(&mut self) -> StratisResult<RegenAction> {
@@ -513,6 +753,7 @@ impl Pool for SimPool {
         _pool_uuid: PoolUuid,
         origin_uuid: FilesystemUuid,
         snapshot_name: &str,
+        read_only: bool,
     ) -> StratisResult<CreateAction<(FilesystemUuid, &mut dyn Filesystem)>> {
         self.check_fs_limit(1)?;
 
@@ -533,7 +774,13 @@ impl Pool for SimPool {
                         return Ok(CreateAction::Identity);
                     }
                 }
-                SimFilesystem::new(filesystem.size(), filesystem.size_limit())?
+                let mut snapshot = SimFilesystem::new(
+                    filesystem.size(),
+                    filesystem.size_limit(),
+                    Some(origin_uuid),
+                )?;
+                snapshot.set_read_only(read_only);
+                snapshot
             }
             None => {
                 return Err(StratisError::Msg(origin_uuid.to_string()));
@@ -552,6 +799,76 @@ impl Pool for SimPool {
         )))
     }
 
+    fn snapshot_filesystems<'b>(
+        &mut self,
+        _pool_name: &str,
+        _pool_uuid: PoolUuid,
+        specs: &[(FilesystemUuid, &'b str)],
+    ) -> StratisResult<SetCreateAction<(&'b str, FilesystemUuid)>> {
+        validate_snapshot_specs(specs)?;
+        self.check_fs_limit(specs.len())?;
+
+        let mut snapshots = Vec::new();
+        for (origin_uuid, snapshot_name) in specs {
+            if self.filesystems.get_by_name(snapshot_name).is_some() {
+                return Err(StratisError::Msg(format!(
+                    "A filesystem named {snapshot_name} already exists"
+                )));
+            }
+            let (_, origin) = self
+                .filesystems
+                .get_by_uuid(*origin_uuid)
+                .ok_or_else(|| StratisError::Msg(origin_uuid.to_string()))?;
+            snapshots.push(SimFilesystem::new(
+                origin.size(),
+                origin.size_limit(),
+                Some(*origin_uuid),
+            )?);
+        }
+
+        let mut created = Vec::new();
+        for ((_, snapshot_name), snapshot) in specs.iter().zip(snapshots) {
+            let uuid = FilesystemUuid::new_v4();
+            self.filesystems
+                .insert(Name::new((*snapshot_name).to_owned()), uuid, snapshot);
+            created.push((*snapshot_name, uuid));
+        }
+        Ok(SetCreateAction::new(created))
+    }
+
+    fn revert_filesystem(
+        &mut self,
+        _pool_name: &str,
+        origin_uuid: FilesystemUuid,
+        snapshot_uuid: FilesystemUuid,
+    ) -> StratisResult<(RevertAction, HashMap<FilesystemUuid, StratFilesystemDiff>)> {
+        if origin_uuid == snapshot_uuid {
+            return Err(StratisError::Msg(
+                "A filesystem can not be reverted to itself".to_string(),
+            ));
+        }
+
+        let (snapshot_name, mut snapshot) = self
+            .filesystems
+            .remove_by_uuid(snapshot_uuid)
+            .ok_or_else(|| {
+                StratisError::Msg(format!("No filesystem with UUID {snapshot_uuid} found"))
+            })?;
+        let res = match self.filesystems.get_mut_by_uuid(origin_uuid) {
+            Some(_) if snapshot.origin() != Some(origin_uuid) => Err(StratisError::Msg(format!(
+                "Filesystem {snapshot_name} is not a snapshot of the filesystem with UUID {origin_uuid}"
+            ))),
+            Some((_, origin)) => origin.swap_contents(&mut snapshot),
+            None => Err(StratisError::Msg(format!(
+                "No filesystem with UUID {origin_uuid} found"
+            ))),
+        };
+        self.filesystems
+            .insert(snapshot_name, snapshot_uuid, snapshot);
+
+        res.map(|_| (RevertAction, HashMap::new()))
+    }
+
     fn total_physical_size(&self) -> Sectors {
         // We choose to make our pools very big, and we can change that
         // if it is inconvenient.
@@ -644,6 +961,32 @@ impl Pool for SimPool {
         !self.cache_devs.is_empty()
     }
 
+    fn cache_settings(&self) -> Option<&CacheSettings> {
+        if self.has_cache() {
+            Some(&self.cache_settings)
+        } else {
+            None
+        }
+    }
+
+    fn set_cache_settings(
+        &mut self,
+        _pool_name: &str,
+        settings: CacheSettings,
+    ) -> StratisResult<PropChangeAction<CacheSettings>> {
+        if !self.has_cache() {
+            return Err(StratisError::Msg(
+                "The pool has no cache; cache settings can not be changed".to_string(),
+            ));
+        }
+        if self.cache_settings == settings {
+            Ok(PropChangeAction::Identity)
+        } else {
+            self.cache_settings = settings.clone();
+            Ok(PropChangeAction::NewValue(settings))
+        }
+    }
+
     fn is_encrypted(&self) -> bool {
         self.encryption_info().is_some()
     }
@@ -684,6 +1027,65 @@ impl Pool for SimPool {
         false
     }
 
+    fn replacement_progress(&self) -> Option<u8> {
+        None
+    }
+
+    fn encryption_progress(&self) -> Option<u8> {
+        None
+    }
+
+    fn reencryption_progress(&self) -> Option<u8> {
+        None
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        if self.has_cache() {
+            Some(CacheStats::default())
+        } else {
+            None
+        }
+    }
+
+    fn redundancy(&self) -> Redundancy {
+        self.redundancy
+    }
+
+    fn integrity(&self) -> Option<IntegrityHash> {
+        self.integrity
+    }
+
+    fn is_degraded(&self) -> bool {
+        false
+    }
+
+    fn data_shrink(&self) -> Option<DataShrink> {
+        None
+    }
+
+    fn allocation_policy(&self) -> AllocationPolicy {
+        self.allocation_policy
+    }
+
+    fn set_allocation_policy(
+        &mut self,
+        _pool_name: &str,
+        policy: AllocationPolicy,
+    ) -> StratisResult<PropChangeAction<AllocationPolicy>> {
+        if policy != AllocationPolicy::Linear && self.redundancy != Redundancy::None {
+            return Err(StratisError::Msg(format!(
+                "Allocation policy {policy} is not supported for a data tier with redundancy {}",
+                self.redundancy
+            )));
+        }
+        if self.allocation_policy == policy {
+            Ok(PropChangeAction::Identity)
+        } else {
+            self.allocation_policy = policy;
+            Ok(PropChangeAction::NewValue(policy))
+        }
+    }
+
     fn grow_physical(
         &mut self,
         _: &Name,
@@ -709,6 +1111,59 @@ impl Pool for SimPool {
             Ok(PropChangeAction::Identity)
         }
     }
+
+    fn set_fs_read_only(
+        &mut self,
+        fs_uuid: FilesystemUuid,
+        read_only: bool,
+    ) -> StratisResult<PropChangeAction<bool>> {
+        let (_, fs) = self.filesystems.get_mut_by_uuid(fs_uuid).ok_or_else(|| {
+            StratisError::Msg(format!("Filesystem with UUID {fs_uuid} not found"))
+        })?;
+        if fs.set_read_only(read_only) {
+            Ok(PropChangeAction::NewValue(read_only))
+        } else {
+            Ok(PropChangeAction::Identity)
+        }
+    }
+
+    fn set_fs_snapshot_schedule(
+        &mut self,
+        fs_uuid: FilesystemUuid,
+        schedule: Option<SnapshotSchedule>,
+    ) -> StratisResult<PropChangeAction<Option<SnapshotSchedule>>> {
+        let (name, fs) = self.filesystems.get_mut_by_uuid(fs_uuid).ok_or_else(|| {
+            StratisError::Msg(format!("Filesystem with UUID {fs_uuid} not found"))
+        })?;
+        if schedule.is_some() {
+            validate_scheduled_snapshot_name(&name)?;
+        }
+        if fs.set_snapshot_schedule(schedule) {
+            Ok(PropChangeAction::NewValue(schedule))
+        } else {
+            Ok(PropChangeAction::Identity)
+        }
+    }
+
+    fn run_snapshot_schedules(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        now: DateTime<Utc>,
+    ) -> StratisResult<SnapshotScheduleRun> {
+        let plans = scheduled_snapshot_plans(&self.filesystems(), now);
+        let mut run = SnapshotScheduleRun::default();
+        for plan in plans {
+            let res = run_scheduled_snapshot_plan(self, pool_name, pool_uuid, &plan, &mut run);
+            if let Some((_, fs)) = self.filesystems.get_mut_by_uuid(plan.origin) {
+                fs.set_snapshot_schedule_status(SnapshotScheduleStatus {
+                    time: now,
+                    error: res.err().map(|e| e.to_string()),
+                });
+            }
+        }
+        Ok(run)
+    }
 }
 
 #[cfg(test)]
@@ -716,6 +1171,8 @@ mod tests {
 
     use std::path::Path;
 
+    use chrono::{Duration, TimeZone};
+
     use crate::engine::{
         sim_engine::SimEngine,
         types::{EngineAction, PoolIdentifier},
@@ -732,6 +1189,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -752,6 +1212,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -770,6 +1233,219 @@ mod tests {
         );
     }
 
+    #[test]
+    /// A snapshot records its origin, and a filesystem can only be reverted
+    /// to one of its own snapshots.
+    fn snapshot_revert() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let infos = pool
+            .create_filesystems(
+                pool_name,
+                uuid,
+                &[("origin", None, None), ("other", None, None)],
+            )
+            .unwrap()
+            .changed()
+            .unwrap();
+        let (origin_uuid, other_uuid) = if infos[0].0 == "origin" {
+            (infos[0].1, infos[1].1)
+        } else {
+            (infos[1].1, infos[0].1)
+        };
+        let snapshot_uuid = match pool
+            .snapshot_filesystem(pool_name, uuid, origin_uuid, "snapshot", true)
+            .unwrap()
+        {
+            CreateAction::Created((snapshot_uuid, snapshot)) => {
+                assert_eq!(snapshot.origin(), Some(origin_uuid));
+                assert!(snapshot.read_only());
+                snapshot_uuid
+            }
+            CreateAction::Identity => panic!("snapshot should have been created"),
+        };
+        assert_eq!(pool.get_filesystem(origin_uuid).unwrap().1.origin(), None);
+        assert!(!pool.get_filesystem(origin_uuid).unwrap().1.read_only());
+        assert!(matches!(
+            pool.set_fs_read_only(snapshot_uuid, true),
+            Ok(PropChangeAction::Identity)
+        ));
+        assert!(matches!(
+            pool.set_fs_read_only(snapshot_uuid, false),
+            Ok(PropChangeAction::NewValue(false))
+        ));
+
+        assert!(pool
+            .revert_filesystem(pool_name, origin_uuid, origin_uuid)
+            .is_err());
+        assert!(pool
+            .revert_filesystem(pool_name, snapshot_uuid, origin_uuid)
+            .is_err());
+        assert!(pool
+            .revert_filesystem(pool_name, other_uuid, snapshot_uuid)
+            .is_err());
+        assert!(pool
+            .revert_filesystem(pool_name, origin_uuid, snapshot_uuid)
+            .is_ok());
+        assert_eq!(
+            pool.get_filesystem_by_name(&Name::new("origin".to_string()))
+                .map(|(u, _)| u),
+            Some(origin_uuid)
+        );
+    }
+
+    #[test]
+    /// A group snapshot creates a snapshot of every origin, or none at all
+    /// if any of the snapshot names is in use.
+    fn snapshot_group() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let infos = pool
+            .create_filesystems(
+                pool_name,
+                uuid,
+                &[("data", None, None), ("wal", None, None)],
+            )
+            .unwrap()
+            .changed()
+            .unwrap();
+        let (data_uuid, wal_uuid) = if infos[0].0 == "data" {
+            (infos[0].1, infos[1].1)
+        } else {
+            (infos[1].1, infos[0].1)
+        };
+
+        assert!(pool
+            .snapshot_filesystems(
+                pool_name,
+                uuid,
+                &[(data_uuid, "data_snap"), (wal_uuid, "data")]
+            )
+            .is_err());
+        assert_eq!(pool.filesystems().len(), 2);
+
+        let created = pool
+            .snapshot_filesystems(
+                pool_name,
+                uuid,
+                &[(data_uuid, "data_snap"), (wal_uuid, "wal_snap")],
+            )
+            .unwrap()
+            .changed()
+            .unwrap();
+        assert_eq!(created.len(), 2);
+        for ((name, snapshot_uuid), origin_uuid) in created.into_iter().zip([data_uuid, wal_uuid]) {
+            let (snapshot_name, snapshot) = pool.get_filesystem(snapshot_uuid).unwrap();
+            assert_eq!(&*snapshot_name, name);
+            assert_eq!(snapshot.origin(), Some(origin_uuid));
+        }
+        assert_eq!(pool.filesystems().len(), 4);
+    }
+
+    #[test]
+    /// Scheduled snapshots are taken when they are due and destroyed when
+    /// they are no longer retained.
+    fn snapshot_schedule() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let fs_uuid = pool
+            .create_filesystems(pool_name, uuid, &[("origin", None, None)])
+            .unwrap()
+            .changed()
+            .unwrap()[0]
+            .1;
+
+        let schedule = SnapshotSchedule::new(1800, 1, 0, 0).unwrap();
+        assert!(pool
+            .set_fs_snapshot_schedule(fs_uuid, Some(schedule))
+            .unwrap()
+            .is_changed());
+        assert!(!pool
+            .set_fs_snapshot_schedule(fs_uuid, Some(schedule))
+            .unwrap()
+            .is_changed());
+
+        // 2023-11-15T00:00:00Z
+        let start = Utc.timestamp_opt(1_700_006_400, 0).unwrap();
+        let run = pool.run_snapshot_schedules(pool_name, uuid, start).unwrap();
+        assert_eq!(run.created.len(), 1);
+        assert!(run.destroyed.is_empty());
+        let first = run.created[0];
+        let (first_name, first_fs) = pool.get_filesystem(first).unwrap();
+        assert_eq!(&*first_name, "origin-snapshot-20231115T000000Z");
+        assert_eq!(first_fs.origin(), Some(fs_uuid));
+        assert_eq!(first_fs.snapshot_schedule(), None);
+
+        assert_eq!(
+            pool.run_snapshot_schedules(pool_name, uuid, start + Duration::seconds(600))
+                .unwrap(),
+            SnapshotScheduleRun::default()
+        );
+
+        // Only the newest snapshot in the hour is retained.
+        let now = start + Duration::seconds(1800);
+        let run = pool.run_snapshot_schedules(pool_name, uuid, now).unwrap();
+        assert_eq!(run.created.len(), 1);
+        assert_eq!(run.destroyed, vec![first]);
+        assert!(pool.get_filesystem(first).is_none());
+        assert_eq!(
+            pool.get_filesystem(fs_uuid)
+                .unwrap()
+                .1
+                .snapshot_schedule_status(),
+            Some(SnapshotScheduleStatus {
+                time: now,
+                error: None
+            })
+        );
+
+        assert!(pool
+            .set_fs_snapshot_schedule(fs_uuid, None)
+            .unwrap()
+            .is_changed());
+        assert_eq!(
+            pool.run_snapshot_schedules(pool_name, uuid, now + Duration::days(1))
+                .unwrap(),
+            SnapshotScheduleRun::default()
+        );
+    }
+
     #[test]
     /// Renaming a filesystem to another filesystem should fail if new name taken
     fn rename_fails() {
@@ -780,6 +1456,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -811,6 +1490,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -831,6 +1513,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -851,6 +1536,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -871,6 +1559,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -897,6 +1588,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -915,6 +1609,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -940,6 +1637,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -963,6 +1663,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -990,6 +1693,9 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             "pool_name",
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
             None,
         ))
         .unwrap()
@@ -1007,4 +1713,164 @@ mod tests {
             _ => false,
         });
     }
+
+    #[test]
+    /// Encrypting an unencrypted pool makes it encrypted; encrypting it again
+    /// with the same encryption info is an identity.
+    fn encrypt_pool() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            Redundancy::None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+        let encryption_info =
+            EncryptionInfo::KeyDesc(KeyDescription::try_from("key".to_string()).unwrap());
+
+        assert!(!pool.is_encrypted());
+        assert!(pool
+            .encrypt_pool(uuid, &pool_name, &encryption_info, None)
+            .unwrap()
+            .0
+            .is_changed());
+        assert!(pool.is_encrypted());
+        assert!(!pool
+            .encrypt_pool(uuid, &pool_name, &encryption_info, None)
+            .unwrap()
+            .0
+            .is_changed());
+    }
+
+    #[test]
+    /// Reencrypting a pool succeeds only if the pool is encrypted.
+    fn reencrypt_pool() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            Redundancy::None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+
+        assert!(pool.reencrypt_pool(&pool_name, None).is_err());
+        pool.encrypt_pool(
+            uuid,
+            &pool_name,
+            &EncryptionInfo::KeyDesc(KeyDescription::try_from("key".to_string()).unwrap()),
+            None,
+        )
+        .unwrap();
+        assert!(pool
+            .reencrypt_pool(&pool_name, None)
+            .unwrap()
+            .0
+            .is_changed());
+        assert_eq!(pool.reencryption_progress(), None);
+    }
+
+    #[test]
+    /// Additional keyring bindings can be added and removed individually and
+    /// are reported in the encryption info of the pool; the primary binding
+    /// can not also be added as an additional binding.
+    fn keyring_bindings() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            Redundancy::None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+        let primary = KeyDescription::try_from("key".to_string()).unwrap();
+        let first = KeyDescription::try_from("first".to_string()).unwrap();
+        let second = KeyDescription::try_from("second".to_string()).unwrap();
+
+        assert!(pool.add_keyring_binding(&first).is_err());
+        pool.encrypt_pool(
+            uuid,
+            &pool_name,
+            &EncryptionInfo::KeyDesc(primary.clone()),
+            None,
+        )
+        .unwrap();
+
+        assert!(pool.add_keyring_binding(&primary).is_err());
+        assert!(pool.add_keyring_binding(&second).unwrap().is_changed());
+        assert!(pool.add_keyring_binding(&first).unwrap().is_changed());
+        assert!(!pool.add_keyring_binding(&first).unwrap().is_changed());
+        assert_eq!(
+            pool.keyring_bindings().unwrap(),
+            vec![first.clone(), second.clone()]
+        );
+
+        assert!(pool.remove_keyring_binding(&second).unwrap().is_changed());
+        assert!(!pool.remove_keyring_binding(&second).unwrap().is_changed());
+        let encryption_info = pool.encryption_info().unwrap();
+        assert_eq!(encryption_info.keyring_bindings().unwrap(), &[first]);
+        assert_eq!(encryption_info.key_description().unwrap(), Some(&primary));
+    }
+
+    #[test]
+    /// Every blockdev added to the data tier of a pool with integrity
+    /// protection is protected; a pool with integrity protection keeps it
+    /// when it is encrypted.
+    fn integrity_pool() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            Redundancy::None,
+            None,
+            None,
+            Some(IntegrityHash::Crc32c),
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+
+        assert_eq!(pool.integrity(), Some(IntegrityHash::Crc32c));
+        pool.add_blockdevs(
+            uuid,
+            &pool_name,
+            &[Path::new("/dev/three")],
+            BlockDevTier::Data,
+        )
+        .unwrap();
+        assert!(pool
+            .blockdevs()
+            .iter()
+            .all(|(_, _, bd)| bd.integrity_errors() == Some(0)));
+        pool.encrypt_pool(
+            uuid,
+            &pool_name,
+            &EncryptionInfo::KeyDesc(KeyDescription::try_from("key".to_string()).unwrap()),
+            None,
+        )
+        .unwrap();
+        assert!(pool.is_encrypted());
+        assert_eq!(pool.integrity(), Some(IntegrityHash::Crc32c));
+    }
 }
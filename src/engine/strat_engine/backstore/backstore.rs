@@ -4,13 +4,23 @@
 
 // Code to handle the backing store of a pool.
 
-use std::{cmp, collections::HashMap, fs, path::PathBuf};
+use std::{
+    cmp,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::{Duration, Instant},
+};
 
 use chrono::{DateTime, Utc};
 use serde_json::Value;
 use tempfile::TempDir;
 
-use devicemapper::{CacheDev, Device, DmDevice, LinearDev, Sectors};
+use devicemapper::{
+    CacheDevStatus, Device, DmDevice, DmOptions, LinearDev, LinearDevTargetParams,
+    LinearTargetParams, Sectors, TargetLine,
+};
 
 use crate::{
     engine::{
@@ -19,13 +29,18 @@ use crate::{
             backstore::{
                 blockdev::StratBlockDev,
                 blockdevmgr::BlockDevMgr,
+                cache::CacheDev,
                 cache_tier::CacheTier,
                 crypt::{
-                    back_up_luks_header, interpret_clevis_config, restore_luks_header, CryptHandle,
+                    back_up_luks_header, back_up_luks_headers, interpret_clevis_config,
+                    restore_luks_header, CryptHandle,
                 },
                 data_tier::DataTier,
-                devices::UnownedDevices,
-                shared::BlockSizeSummary,
+                devices::{wipe_blockdevs, UnownedDevices},
+                mirror::{MirrorDev, MirrorSegment},
+                raid::RaidDev,
+                shared::{AllocatedAbove, BlkDevSegment, BlockSizeSummary, Segment},
+                stripe::{stripe_table, StripeDev},
                 transaction::RequestTransaction,
             },
             dm::{get_dm, list_of_backstore_devices, remove_optional_devices},
@@ -37,16 +52,41 @@ use crate::{
             writing::wipe_sectors,
         },
         types::{
-            ActionAvailability, BlockDevTier, DevUuid, EncryptionInfo, KeyDescription, Name,
-            PoolEncryptionInfo, PoolUuid,
+            ActionAvailability, AllocationPolicy, BlockDevTier, CacheMode, CacheSettings,
+            CacheStats, CryptParams, DevUuid, EncryptionInfo, IntegrityHash, KeyDescription, Name,
+            PoolEncryptionInfo, PoolUuid, Redundancy,
         },
     },
     stratis::{StratisError, StratisResult},
 };
 
-/// Use a cache block size that the kernel docs indicate is the largest
-/// typical size.
-const CACHE_BLOCK_SIZE: Sectors = Sectors(2048); // 1024 KiB
+/// The cache policy that writes back all dirty blocks and does not
+/// promote any blocks to the cache.
+const CLEANER_CACHE_POLICY: &str = "cleaner";
+
+/// The interval at which the number of dirty blocks is checked while the
+/// cache is being flushed.
+const CACHE_FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The settings of a cache whose dirty blocks are being written back in
+/// preparation for its removal.
+fn cleaner_cache_settings() -> CacheSettings {
+    CacheSettings {
+        mode: CacheMode::Writeback,
+        policy: CLEANER_CACHE_POLICY.to_string(),
+        policy_args: Vec::new(),
+    }
+}
+
+/// The mode, policy, and policy arguments with which the cache device of the
+/// cache tier is loaded.
+fn live_cache_settings(cache_tier: &CacheTier) -> CacheSettings {
+    if cache_tier.flushing {
+        cleaner_cache_settings()
+    } else {
+        cache_tier.settings.clone()
+    }
+}
 
 /// Make a DM cache device. If the cache device is being made new,
 /// take extra steps to make it clean.
@@ -81,21 +121,79 @@ fn make_cache(
         cache_tier.cache_segments.map_to_dm(),
     )?;
 
-    let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::Cache);
-    Ok(CacheDev::setup(
-        get_dm(),
-        &dm_name,
-        Some(&dm_uuid),
+    CacheDev::setup(
+        pool_uuid,
         meta,
         cache,
         origin,
-        CACHE_BLOCK_SIZE,
-    )?)
+        live_cache_settings(cache_tier),
+    )
+}
+
+/// The segments currently allocated in the data tier; if datadevs are
+/// being removed or replaced, the segments allocated from them are replaced
+/// by the ranges of the mirror device that mirror them.
+fn data_segments(data_tier: &DataTier, mirror: Option<&MirrorDev>) -> AllocatedAbove {
+    let (old, mirror) = match (data_tier.replacement.as_ref(), mirror) {
+        (Some(replacement), Some(mirror)) => (&replacement.old, mirror.device()),
+        _ => {
+            return AllocatedAbove {
+                inner: data_tier.segments.inner.clone(),
+            }
+        }
+    };
+
+    let mut mirror_offset = Sectors(0);
+    AllocatedAbove {
+        inner: data_tier
+            .segments
+            .inner
+            .iter()
+            .map(|bseg| {
+                if old.contains(&bseg.uuid) {
+                    let seg = BlkDevSegment::new(
+                        bseg.uuid,
+                        Segment::new(mirror, mirror_offset, bseg.segment.length),
+                    );
+                    mirror_offset += bseg.segment.length;
+                    seg
+                } else {
+                    bseg.clone()
+                }
+            })
+            .collect(),
+    }
+}
+
+/// The table of the cap device or of the origin of the cache device. Map
+/// all segments currently allocated in the data tier; see data_segments().
+/// If the data tier has redundancy, map the raid device that holds all the
+/// copies of the data; if some extents of the data tier are striped, map
+/// the stripe device that holds them.
+fn cap_table(
+    data_tier: &DataTier,
+    mirror: Option<&MirrorDev>,
+    raid: Option<&RaidDev>,
+    stripe: Option<&StripeDev>,
+) -> Vec<TargetLine<LinearDevTargetParams>> {
+    let lower = raid
+        .map(|raid| (raid.device(), raid.size()))
+        .or_else(|| stripe.map(|stripe| (stripe.device(), stripe.size())));
+    if let Some((device, size)) = lower {
+        return vec![TargetLine::new(
+            Sectors(0),
+            size,
+            LinearDevTargetParams::Linear(LinearTargetParams::new(device, Sectors(0))),
+        )];
+    }
+
+    data_segments(data_tier, mirror).map_to_dm()
 }
 
-/// This structure can allocate additional space to the upper layer, but it
-/// cannot accept returned space. When it is extended to be able to accept
-/// returned space the allocation algorithm will have to be revised.
+/// This structure can allocate additional space to the upper layer. It can
+/// accept returned space only at the end of the cap device, since the upper
+/// layer addresses all other space by its offset in the cap device; returned
+/// space is given back to the blockdevs of the data tier.
 #[derive(Debug)]
 pub struct Backstore {
     /// A cache DM Device.
@@ -107,8 +205,28 @@ pub struct Backstore {
     data_tier: DataTier,
     /// A linear DM device.
     linear: Option<LinearDev>,
+    /// A temporary mirror DM device, which exists only while a datadev is
+    /// being replaced.
+    mirror: Option<MirrorDev>,
+    /// A raid DM device beneath the cap device, which exists only if the
+    /// data tier has redundancy and some space has been allocated from it.
+    raid: Option<RaidDev>,
+    /// A DM device beneath the cap device, which exists only if some
+    /// extents of the data tier are striped.
+    stripe: Option<StripeDev>,
     /// Index for managing allocation of cap device
     next: Sectors,
+    /// The blockdevs that remain to be reencrypted with new volume keys by
+    /// the reencryption of the pool, beginning with the one that is being
+    /// reencrypted. Empty if no reencryption of the pool is in progress.
+    reencryption: Vec<DevUuid>,
+    /// The crypt parameters with which the blockdevs that remain to be
+    /// reencrypted are reencrypted.
+    reencryption_params: Option<CryptParams>,
+    /// The key derivation parameters with which the keyslots of the
+    /// blockdevs of an encrypted pool are created, if specified, as the
+    /// only parameters of crypt parameters.
+    pbkdf_params: Option<CryptParams>,
 }
 
 impl Backstore {
@@ -125,11 +243,10 @@ impl Backstore {
     /// a pool exists, data has been allocated to the cap device.
     ///
     /// Precondition:
-    ///   * key_description.is_some() -> every StratBlockDev in datadevs has a
-    ///   key description and that key description == key_description
-    ///   * key_description.is_none() -> no StratBlockDev in datadevs has a
-    ///   key description.
-    ///   * no StratBlockDev in cachedevs has a key description
+    ///   * the pool is encrypted -> every StratBlockDev in datadevs and
+    ///   cachedevs is encrypted with the same encryption info
+    ///   * the pool is unencrypted -> no StratBlockDev in datadevs or
+    ///   cachedevs is encrypted
     ///
     /// Postcondition:
     /// self.linear.is_some() XOR self.cache.is_some()
@@ -142,13 +259,56 @@ impl Backstore {
         last_update_time: DateTime<Utc>,
     ) -> BDARecordResult<Backstore> {
         let block_mgr = BlockDevMgr::new(datadevs, Some(last_update_time));
-        let data_tier = DataTier::setup(block_mgr, &backstore_save.data_tier)?;
+        let data_tier = DataTier::setup(
+            block_mgr,
+            &backstore_save.data_tier,
+            backstore_save.redundancy.unwrap_or_default(),
+            backstore_save.allocation_policy.unwrap_or_default(),
+        )?;
+        let raid = if data_tier.redundancy == Redundancy::None {
+            None
+        } else {
+            match RaidDev::setup(pool_uuid, data_tier.leg_tables()) {
+                Ok(raid) => Some(raid),
+                Err(e) => {
+                    return Err((
+                        e,
+                        data_tier
+                            .block_mgr
+                            .into_bdas()
+                            .into_iter()
+                            .chain(bds_to_bdas(cachedevs))
+                            .collect::<HashMap<_, _>>(),
+                    ));
+                }
+            }
+        };
+        let stripe = if data_tier.stripes.is_empty() {
+            None
+        } else {
+            match stripe_table(&data_segments(&data_tier, None), &data_tier.stripes)
+                .and_then(|table| StripeDev::setup(pool_uuid, table))
+            {
+                Ok(stripe) => Some(stripe),
+                Err(e) => {
+                    return Err((
+                        e,
+                        data_tier
+                            .block_mgr
+                            .into_bdas()
+                            .into_iter()
+                            .chain(bds_to_bdas(cachedevs))
+                            .collect::<HashMap<_, _>>(),
+                    ));
+                }
+            }
+        };
         let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::OriginSub);
         let origin = match LinearDev::setup(
             get_dm(),
             &dm_name,
             Some(&dm_uuid),
-            data_tier.segments.map_to_dm(),
+            cap_table(&data_tier, None, raid.as_ref(), stripe.as_ref()),
         ) {
             Ok(origin) => origin,
             Err(e) => {
@@ -209,13 +369,25 @@ impl Backstore {
             (None, None, Some(origin))
         };
 
-        Ok(Backstore {
+        let mut backstore = Backstore {
             data_tier,
             cache_tier,
             linear: origin,
             cache,
+            mirror: None,
+            raid,
+            stripe,
             next: backstore_save.cap.allocs[0].1,
-        })
+            reencryption: backstore_save.reencryption.clone(),
+            reencryption_params: backstore_save.reencryption_params.clone(),
+            pbkdf_params: backstore_save.pbkdf_params.clone(),
+        };
+
+        if let Some(ref replacement) = backstore_save.data_tier.replacement {
+            backstore.resume_replacement(pool_uuid, &replacement.old, replacement.new);
+        }
+
+        Ok(backstore)
     }
 
     /// Initialize a Backstore object, by initializing the specified devs.
@@ -225,6 +397,12 @@ impl Backstore {
     ///
     /// When the backstore is initialized it may be unencrypted, or it may
     /// be encrypted only with a kernel keyring and without Clevis information.
+    /// If encrypted, the devices are formatted with the given crypt
+    /// parameters. If an integrity hash is given, every device in the data
+    /// tier is given integrity protection with that hash.
+    ///
+    /// Return an error if there are fewer devices than the redundancy
+    /// requires copies of the data.
     ///
     /// WARNING: metadata changing event
     pub fn initialize(
@@ -233,26 +411,50 @@ impl Backstore {
         devices: UnownedDevices,
         mda_data_size: MDADataSize,
         encryption_info: Option<&EncryptionInfo>,
+        crypt_params: Option<&CryptParams>,
+        integrity: Option<IntegrityHash>,
+        redundancy: Redundancy,
     ) -> StratisResult<Backstore> {
-        let data_tier = DataTier::new(BlockDevMgr::initialize(
-            pool_name,
-            pool_uuid,
-            devices,
-            mda_data_size,
-            encryption_info,
-            None,
-        )?);
+        if devices.len() < redundancy.copies() {
+            return Err(StratisError::Msg(format!(
+                "Redundancy {redundancy} requires at least {} devices in the data tier",
+                redundancy.copies()
+            )));
+        }
+
+        let data_tier = DataTier::new(
+            BlockDevMgr::initialize(
+                pool_name,
+                pool_uuid,
+                devices,
+                mda_data_size,
+                encryption_info,
+                crypt_params,
+                integrity,
+            )?,
+            redundancy,
+        );
 
         Ok(Backstore {
             data_tier,
             cache_tier: None,
             linear: None,
             cache: None,
+            mirror: None,
+            raid: None,
+            stripe: None,
             next: Sectors(0),
+            reencryption: Vec::new(),
+            reencryption_params: None,
+            pbkdf_params: encryption_info
+                .and(crypt_params)
+                .and_then(CryptParams::pbkdf_params),
         })
     }
 
-    /// Initialize the cache tier and add cachedevs to the backstore.
+    /// Initialize the cache tier and add cachedevs to the backstore. If the
+    /// pool is encrypted, the cachedevs are encrypted with the encryption
+    /// info and the crypt parameters of the datadevs.
     ///
     /// Returns all `DevUuid`s of devices that were added to the cache on initialization.
     ///
@@ -266,6 +468,7 @@ impl Backstore {
         pool_uuid: PoolUuid,
         devices: UnownedDevices,
         sector_size: Option<u32>,
+        settings: CacheSettings,
     ) -> StratisResult<Vec<DevUuid>> {
         match self.cache_tier {
             Some(_) => unreachable!("self.cache.is_none()"),
@@ -284,10 +487,18 @@ impl Backstore {
                         .map(EncryptionInfo::try_from)
                         .transpose()?
                         .as_ref(),
-                    sector_size,
+                    self.data_tier
+                        .block_mgr
+                        .crypt_params()?
+                        .map(|params| CryptParams {
+                            sector_size: sector_size.or(params.sector_size),
+                            ..params
+                        })
+                        .as_ref(),
+                    None,
                 )?;
 
-                let cache_tier = CacheTier::new(bdm)?;
+                let cache_tier = CacheTier::new(bdm, settings)?;
 
                 let linear = self.linear
                     .take()
@@ -320,8 +531,6 @@ impl Backstore {
     ///
     /// Precondition: Must be invoked only after some space has been allocated
     /// from the backstore. This ensures that there is certainly a cap device.
-    // Precondition: self.linear.is_none() && self.cache.is_some()
-    // Precondition: self.cache_key_desc has the desired key description
     // Precondition: self.cache.is_some() && self.linear.is_none()
     pub fn add_cachedevs(
         &mut self,
@@ -341,8 +550,7 @@ impl Backstore {
 
                 if cache_change {
                     let table = cache_tier.cache_segments.map_to_dm();
-                    cache_device.set_cache_table(get_dm(), table)?;
-                    cache_device.resume(get_dm())?;
+                    cache_device.set_cache_table(table)?;
                 }
 
                 // NOTE: currently CacheTier::add() does not ever update the
@@ -350,8 +558,7 @@ impl Backstore {
                 // when CacheTier::add() is fixed, this code will become live.
                 if meta_change {
                     let table = cache_tier.meta_segments.map_to_dm();
-                    cache_device.set_meta_table(get_dm(), table)?;
-                    cache_device.resume(get_dm())?;
+                    cache_device.set_meta_table(table)?;
                 }
 
                 Ok(uuids)
@@ -360,189 +567,1234 @@ impl Backstore {
         }
     }
 
-    /// Add datadevs to the backstore. The data tier always exists if the
-    /// backstore exists at all, so there is no need to create it.
-    pub fn add_datadevs(
-        &mut self,
-        pool_name: Name,
-        pool_uuid: PoolUuid,
-        devices: UnownedDevices,
-        sector_size: Option<u32>,
-    ) -> StratisResult<Vec<DevUuid>> {
-        self.data_tier
-            .add(pool_name, pool_uuid, devices, sector_size)
-    }
-
-    /// Extend the cap device whether it is a cache or not. Create the DM
-    /// device if it does not already exist. Return an error if DM
-    /// operations fail. Use all segments currently allocated in the data tier.
-    fn extend_cap_device(&mut self, pool_uuid: PoolUuid) -> StratisResult<()> {
-        let create = match (self.cache.as_mut(), self.linear.as_mut()) {
-            (None, None) => true,
-            (Some(cache), None) => {
-                let table = self.data_tier.segments.map_to_dm();
-                cache.set_origin_table(get_dm(), table)?;
-                cache.resume(get_dm())?;
-                false
-            }
-            (None, Some(linear)) => {
-                let table = self.data_tier.segments.map_to_dm();
-                linear.set_table(get_dm(), table)?;
-                linear.resume(get_dm())?;
-                false
-            }
-            _ => panic!("NOT (self.cache().is_some() AND self.linear.is_some())"),
+    /// The statistics reported by the cache device, if there is a cache.
+    pub fn cache_stats(&self) -> StratisResult<Option<CacheStats>> {
+        let cache = match self.cache.as_ref() {
+            Some(cache) => cache,
+            None => return Ok(None),
         };
 
-        if create {
-            let table = self.data_tier.segments.map_to_dm();
-            let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::OriginSub);
-            let origin = LinearDev::setup(get_dm(), &dm_name, Some(&dm_uuid), table)?;
-            self.linear = Some(origin);
+        match cache.status()? {
+            CacheDevStatus::Working(status) => {
+                let perf = &status.performance;
+                Ok(Some(CacheStats {
+                    read_hits: perf.read_hits,
+                    read_misses: perf.read_misses,
+                    write_hits: perf.write_hits,
+                    write_misses: perf.write_misses,
+                    dirty_blocks: perf.dirty,
+                    promotions: perf.promotions,
+                    demotions: perf.demotions,
+                }))
+            }
+            CacheDevStatus::Error => Err(StratisError::Msg(
+                "Cache device reported an error while its status was being read".to_string(),
+            )),
+            CacheDevStatus::Fail => Err(StratisError::Msg(
+                "Cache device is in a failed state".to_string(),
+            )),
         }
+    }
 
-        Ok(())
+    /// The mode, policy, and policy arguments of the cache, if there is a
+    /// cache.
+    pub fn cache_settings(&self) -> Option<&CacheSettings> {
+        self.cache_tier.as_ref().map(|ct| &ct.settings)
     }
 
-    /// Satisfy a request for multiple segments. This request must
-    /// always be satisfied exactly, None is returned if this can not
-    /// be done.
-    ///
-    /// Precondition: self.next <= self.size()
-    /// Postcondition: self.next <= self.size()
+    /// Change the mode, policy, and policy arguments of the cache. Return
+    /// true if the settings were changed. Return an error if there is no
+    /// cache or if the kernel rejects the settings, e.g., if the mode is
+    /// passthrough while the cache holds dirty blocks.
     ///
-    /// Postcondition: forall i, sizes_i == result_i.1. The second value
-    /// in each pair in the returned vector is therefore redundant, but is
-    /// retained as a convenience to the caller.
-    /// Postcondition:
-    /// forall i, result_i.0 = result_(i - 1).0 + result_(i - 1).1
-    pub fn request_alloc(
-        &mut self,
-        sizes: &[Sectors],
-    ) -> StratisResult<Option<RequestTransaction>> {
-        let mut transaction = match self.data_tier.alloc_request(sizes)? {
-            Some(t) => t,
-            None => return Ok(None),
+    /// WARNING: metadata changing event
+    pub fn set_cache_settings(&mut self, settings: CacheSettings) -> StratisResult<bool> {
+        let (cache_tier, cache) = match (self.cache_tier.as_mut(), self.cache.as_mut()) {
+            (Some(cache_tier), Some(cache)) => (cache_tier, cache),
+            _ => {
+                return Err(StratisError::Msg(
+                    "The pool has no cache; cache settings can not be changed".to_string(),
+                ))
+            }
         };
 
-        let mut next = self.next;
-        for size in sizes {
-            transaction.add_seg_req((next, *size));
-            next += *size
+        if cache_tier.flushing {
+            return Err(StratisError::Msg(
+                "The cache is being removed; cache settings can not be changed".to_string(),
+            ));
         }
 
-        // Assert that the postcondition holds.
-        assert_eq!(
-            sizes,
-            transaction
-                .get_backstore()
-                .iter()
-                .map(|x| x.1)
-                .collect::<Vec<Sectors>>()
-                .as_slice()
-        );
+        if cache_tier.settings == settings {
+            return Ok(false);
+        }
 
-        Ok(Some(transaction))
+        cache.set_settings(settings.clone())?;
+        cache_tier.settings = settings;
+        Ok(true)
     }
 
-    /// Commit space requested by request_alloc() to metadata.
+    /// Switch the cache device to the cleaner policy, which writes back all
+    /// dirty blocks to the origin device and never promotes blocks to the
+    /// cache. The settings recorded in the cache tier are not changed, so
+    /// that abort_cache_flush() can restore them.
     ///
-    /// This method commits the newly allocated data segments and then extends the cap device
-    /// to be the same size as the allocated data size.
-    pub fn commit_alloc(
-        &mut self,
-        pool_uuid: PoolUuid,
-        transaction: RequestTransaction,
-    ) -> StratisResult<()> {
-        let segs = transaction.get_backstore();
-        self.data_tier.alloc_commit(transaction)?;
-        // This must occur after the segments have been updated in the data tier
-        self.extend_cap_device(pool_uuid)?;
+    /// Precondition: self.cache.is_some()
+    pub fn start_cache_flush(&mut self) -> StratisResult<()> {
+        let cache = self.cache.as_mut().expect("self.cache.is_some()");
+        let cache_tier = self.cache_tier.as_mut().expect("self.cache.is_some()");
 
-        assert!(self.next <= self.size());
+        cache.set_settings(cleaner_cache_settings())?;
+        cache_tier.flushing = true;
+        Ok(())
+    }
 
-        self.next += segs
-            .into_iter()
-            .fold(Sectors(0), |mut size, (_, next_size)| {
-                size += next_size;
-                size
-            });
+    /// True if the dirty blocks of the cache are being written back by
+    /// start_cache_flush().
+    pub fn cache_flushing(&self) -> bool {
+        self.cache_tier
+            .as_ref()
+            .map(|cache_tier| cache_tier.flushing)
+            .unwrap_or(false)
+    }
+
+    /// Restore the settings recorded in the cache tier after a flush of the
+    /// cache has been abandoned.
+    ///
+    /// Precondition: self.cache.is_some()
+    pub fn abort_cache_flush(&mut self) -> StratisResult<()> {
+        let cache = self.cache.as_mut().expect("self.cache.is_some()");
+        let cache_tier = self.cache_tier.as_mut().expect("self.cache.is_some()");
 
+        cache.set_settings(cache_tier.settings.clone())?;
+        cache_tier.flushing = false;
         Ok(())
     }
 
-    /// Get only the datadevs in the pool.
-    pub fn datadevs(&self) -> Vec<(DevUuid, &StratBlockDev)> {
-        self.data_tier.blockdevs()
+    /// The number of dirty blocks in the cache, which have not yet been
+    /// written back to the origin device.
+    ///
+    /// Precondition: self.cache.is_some()
+    pub fn cache_dirty_blocks(&self) -> StratisResult<u64> {
+        let cache = self.cache.as_ref().expect("self.cache.is_some()");
+
+        match cache.status()? {
+            CacheDevStatus::Working(status) => Ok(status.performance.dirty),
+            CacheDevStatus::Error => Err(StratisError::Msg(
+                "Cache device reported an error while its dirty blocks were being written back"
+                    .to_string(),
+            )),
+            CacheDevStatus::Fail => Err(StratisError::Msg(
+                "Cache device failed while its dirty blocks were being written back".to_string(),
+            )),
+        }
     }
 
-    /// Get only the cachdevs in the pool.
-    pub fn cachedevs(&self) -> Vec<(DevUuid, &StratBlockDev)> {
-        match self.cache_tier {
-            Some(ref cache) => cache.blockdevs(),
-            None => Vec::new(),
+    /// Write back all dirty blocks in the cache to the origin device.
+    /// The cache device is switched to the cleaner policy and this method
+    /// returns once the cache holds no dirty blocks. If the cache still
+    /// holds dirty blocks once timeout has elapsed, or if the cache device
+    /// fails, the settings of the cache are restored and an error is
+    /// returned.
+    ///
+    /// The cleaner policy remains in effect after this method returns
+    /// successfully; it is expected that the cache is removed subsequently.
+    ///
+    /// Precondition: self.cache.is_some()
+    pub fn flush_cache(&mut self, timeout: Duration) -> StratisResult<()> {
+        self.start_cache_flush()?;
+
+        let deadline = Instant::now() + timeout;
+        let res = loop {
+            let dirty = match self.cache_dirty_blocks() {
+                Ok(dirty) => dirty,
+                Err(err) => break Err(err),
+            };
+            if dirty == 0 {
+                break Ok(());
+            }
+            if Instant::now() >= deadline {
+                break Err(StratisError::Msg(format!(
+                    "{dirty} dirty cache blocks were not written back within {} seconds",
+                    timeout.as_secs()
+                )));
+            }
+            debug!(
+                "Waiting for {} dirty cache blocks to be written back",
+                dirty
+            );
+            sleep(CACHE_FLUSH_POLL_INTERVAL);
+        };
+
+        if let Err(err) = res {
+            return match self.abort_cache_flush() {
+                Ok(_) => Err(err),
+                Err(e) => Err(StratisError::RollbackError {
+                    causal_error: Box::new(err),
+                    rollback_error: Box::new(e),
+                    level: ActionAvailability::NoRequests,
+                }),
+            };
         }
-    }
 
-    /// Return a reference to all the blockdevs that this pool has ownership
-    /// of. The blockdevs may be returned in any order. It is unsafe to assume
-    /// that they are grouped by tier or any other organization.
-    pub fn blockdevs(&self) -> Vec<(DevUuid, BlockDevTier, &StratBlockDev)> {
-        self.datadevs()
-            .into_iter()
-            .map(|(uuid, dev)| (uuid, BlockDevTier::Data, dev))
-            .chain(
-                self.cachedevs()
-                    .into_iter()
-                    .map(|(uuid, dev)| (uuid, BlockDevTier::Cache, dev)),
-            )
-            .collect()
+        Ok(())
     }
 
-    pub fn blockdevs_mut(&mut self) -> Vec<(DevUuid, BlockDevTier, &mut StratBlockDev)> {
-        match self.cache_tier {
-            Some(ref mut cache) => cache
-                .blockdevs_mut()
-                .into_iter()
-                .map(|(uuid, dev)| (uuid, BlockDevTier::Cache, dev))
-                .chain(
-                    self.data_tier
-                        .blockdevs_mut()
-                        .into_iter()
-                        .map(|(uuid, dev)| (uuid, BlockDevTier::Data, dev)),
-                )
-                .collect(),
-            None => self
-                .data_tier
-                .blockdevs_mut()
+    /// Remove the cache from the backstore. The DM cache device and its
+    /// cache and meta sub-devices are removed and the origin sub-device
+    /// becomes the cap device. Return the cachedevs, which have not been
+    /// wiped, so that the caller can first save pool metadata that no
+    /// longer refers to them.
+    ///
+    /// Precondition: flush_cache() has been invoked and no I/O has been
+    /// issued to the cache device since.
+    /// Precondition: no DM device above the backstore references the cache
+    /// device; the caller must already have switched it to the origin
+    /// sub-device returned by cache_origin().
+    // Precondition: self.cache.is_some() && self.linear.is_none()
+    // Postcondition: self.cache.is_none() && self.linear.is_some()
+    pub fn remove_cache(&mut self, pool_uuid: PoolUuid) -> StratisResult<Vec<StratBlockDev>> {
+        assert!(self.cache.is_some());
+        assert!(self.linear.is_none());
+
+        remove_optional_devices(
+            [CacheRole::Cache, CacheRole::CacheSub, CacheRole::MetaSub]
                 .into_iter()
-                .map(|(uuid, dev)| (uuid, BlockDevTier::Data, dev))
+                .map(|role| format_backstore_ids(pool_uuid, role).0)
                 .collect(),
-        }
-    }
+        )?;
+        self.cache = None;
+
+        // The origin sub-device still exists with the same table, so setting
+        // it up again just creates a handle for the existing device.
+        let table = cap_table(
+            &self.data_tier,
+            self.mirror.as_ref(),
+            self.raid.as_ref(),
+            self.stripe.as_ref(),
+        );
+        let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::OriginSub);
+        self.linear = Some(LinearDev::setup(get_dm(), &dm_name, Some(&dm_uuid), table)?);
 
-    /// The current size of all the blockdevs in the data tier.
-    pub fn datatier_size(&self) -> Sectors {
-        self.data_tier.size()
+        Ok(self
+            .cache_tier
+            .take()
+            .map(|mut cache_tier| cache_tier.block_mgr.drain_bds())
+            .unwrap_or_default())
     }
 
-    /// The current size of allocated space on the blockdevs in the data tier.
-    pub fn datatier_allocated_size(&self) -> Sectors {
-        self.data_tier.allocated()
+    /// The device number of the origin sub-device of the cache, if there is
+    /// a cache.
+    pub fn cache_origin(&self) -> Option<Device> {
+        self.cache.as_ref().map(|cache| cache.origin())
     }
 
-    /// The current usable size of all the blockdevs in the data tier.
-    pub fn datatier_usable_size(&self) -> Sectors {
-        self.data_tier.usable_size()
+    /// Add datadevs to the backstore. The data tier always exists if the
+    /// backstore exists at all, so there is no need to create it.
+    pub fn add_datadevs(
+        &mut self,
+        pool_name: Name,
+        pool_uuid: PoolUuid,
+        devices: UnownedDevices,
+        sector_size: Option<u32>,
+    ) -> StratisResult<Vec<DevUuid>> {
+        self.data_tier
+            .add(pool_name, pool_uuid, devices, sector_size)
     }
 
-    /// The size of the cap device.
+    /// Remove datadevs from the backstore while the pool remains in use.
+    /// Space for all data allocated from the specified datadevs is reserved
+    /// on the remaining datadevs and a mirror device is placed beneath the
+    /// cap device, which copies the data to it in the background. The
+    /// removal is completed by check_replacement() once all data has been
+    /// copied; until then, the pool metadata continues to refer to the data
+    /// on the datadevs and records the removal, which is restarted if the
+    /// pool is stopped before it is complete.
     ///
-    /// The size of the cap device is obtained from the size of the component
-    /// DM devices. But the devicemapper library stores the data from which
-    /// the size of each DM device is calculated; the result is computed and
-    /// no ioctl is required.
-    fn size(&self) -> Sectors {
+    /// Return an error if the remaining datadevs do not have sufficient free
+    /// space to accommodate the data.
+    ///
+    /// If the data tier has redundancy, the copy of the data that is held
+    /// in part by the specified datadevs is rebuilt on the remaining
+    /// datadevs instead and the datadevs are removed at once; see
+    /// rebuild_datadevs(). Return the removed datadevs; their metadata has
+    /// not been erased, so that the caller can first save pool metadata that
+    /// no longer refers to them.
+    ///
+    /// WARNING: metadata changing event
+    pub fn remove_datadevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        uuids: &[DevUuid],
+    ) -> StratisResult<Vec<StratBlockDev>> {
+        if self.data_tier.redundancy != Redundancy::None {
+            return self.rebuild_datadevs(uuids, None);
+        }
+
+        if let Err(err) = self.setup_replacement(pool_uuid, uuids, None) {
+            return match self.data_tier.cancel_replacement() {
+                Ok(_) => Err(err),
+                Err(e) => Err(StratisError::RollbackError {
+                    causal_error: Box::new(err),
+                    rollback_error: Box::new(e),
+                    level: ActionAvailability::NoRequests,
+                }),
+            };
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// Remove datadevs from a data tier with redundancy. Space on other
+    /// datadevs, or only on new if specified, is allocated for the copy of
+    /// the data that is held in part by the datadevs to be removed, the leg
+    /// of the raid device that holds that copy is remapped to it, and the
+    /// kernel rebuilds the copy from the other legs while the pool remains
+    /// in use. The datadevs to be removed may be absent. Return the removed
+    /// datadevs that were present; their metadata has not been erased.
+    ///
+    /// Return an error if the other legs of the raid device are not in sync.
+    fn rebuild_datadevs(
+        &mut self,
+        uuids: &[DevUuid],
+        new: Option<DevUuid>,
+    ) -> StratisResult<Vec<StratBlockDev>> {
+        let rebuild = self.data_tier.rebuild_request(uuids, new)?;
+
+        if let (Some((leg, _)), Some(raid)) = (rebuild.as_ref(), self.raid.as_ref()) {
+            if !raid.in_sync(Some(*leg))? {
+                return Err(StratisError::Msg(
+                    "The other copies of the data are not yet in sync; no copy can be rebuilt from them until they are".to_string(),
+                ));
+            }
+        }
+
+        if let (Some((leg, transaction)), Some(raid)) = (rebuild.as_ref(), self.raid.as_mut()) {
+            raid.rebuild_leg(
+                *leg,
+                self.data_tier.rebuild_tables(*leg, uuids, transaction),
+            )?;
+        }
+
+        self.data_tier.rebuild_commit(uuids, rebuild)
+    }
+
+    /// Replace the datadev old of a data tier with redundancy with the
+    /// device specified by devices, while the pool remains in use. The new
+    /// device is added to the data tier and the copy of the data held in part
+    /// by old is rebuilt on it by the kernel; see rebuild_datadevs(). old may
+    /// be absent. Return the UUID of the new blockdev and old, if it was
+    /// present; its metadata has not been erased.
+    ///
+    /// Precondition: devices contains exactly one device.
+    ///
+    /// WARNING: metadata changing event
+    pub fn rebuild_replacement(
+        &mut self,
+        pool_name: Name,
+        pool_uuid: PoolUuid,
+        old: DevUuid,
+        devices: UnownedDevices,
+        sector_size: Option<u32>,
+    ) -> StratisResult<(DevUuid, Option<StratBlockDev>)> {
+        let new = *self
+            .data_tier
+            .add(pool_name, pool_uuid, devices, sector_size)?
+            .first()
+            .expect("devices contains exactly one device");
+
+        match self.rebuild_datadevs(&[old], Some(new)) {
+            Ok(mut removed) => Ok((new, removed.pop())),
+            Err(err) => {
+                let rollback_res = self
+                    .data_tier
+                    .block_mgr
+                    .take_blockdevs(&[new])
+                    .and_then(|mut bds| wipe_blockdevs(&mut bds));
+                match rollback_res {
+                    Ok(_) => Err(err),
+                    Err(e) => Err(StratisError::RollbackError {
+                        causal_error: Box::new(err),
+                        rollback_error: Box::new(e),
+                        level: ActionAvailability::NoRequests,
+                    }),
+                }
+            }
+        }
+    }
+
+    /// Replace the datadev old with the device specified by devices, while
+    /// the pool remains in use. The new device is added to the data tier and
+    /// a mirror device is placed beneath the cap device, which copies the
+    /// data allocated from old to the new device in the background. Return
+    /// the UUID of the new blockdev.
+    ///
+    /// The replacement is completed by check_replacement() once all data
+    /// has been copied. Until then, the pool metadata continues to refer to
+    /// the data on old and records the replacement; if the pool is stopped
+    /// before the replacement is complete, the copy is restarted when the
+    /// pool is next set up.
+    ///
+    /// Precondition: devices contains exactly one device.
+    ///
+    /// WARNING: metadata changing event
+    pub fn start_replacement(
+        &mut self,
+        pool_name: Name,
+        pool_uuid: PoolUuid,
+        old: DevUuid,
+        devices: UnownedDevices,
+        sector_size: Option<u32>,
+    ) -> StratisResult<DevUuid> {
+        let new = *self
+            .data_tier
+            .add(pool_name, pool_uuid, devices, sector_size)?
+            .first()
+            .expect("devices contains exactly one device");
+
+        if let Err(err) = self.setup_replacement(pool_uuid, &[old], Some(new)) {
+            let rollback_res = self.data_tier.cancel_replacement().and_then(|_| {
+                self.data_tier
+                    .block_mgr
+                    .take_blockdevs(&[new])
+                    .and_then(|mut bds| wipe_blockdevs(&mut bds))
+            });
+            return match rollback_res {
+                Ok(_) => Err(err),
+                Err(e) => Err(StratisError::RollbackError {
+                    causal_error: Box::new(err),
+                    rollback_error: Box::new(e),
+                    level: ActionAvailability::NoRequests,
+                }),
+            };
+        }
+
+        Ok(new)
+    }
+
+    /// Restart a replacement or removal recorded in the pool metadata, which
+    /// was interrupted when the pool was stopped. The space reserved for the
+    /// data was not recorded, so all the data allocated from old is copied
+    /// again. If the replacement can not be restarted, it is abandoned; new,
+    /// if specified, remains in the data tier, unused, and the cap device
+    /// maps old as before.
+    fn resume_replacement(&mut self, pool_uuid: PoolUuid, old: &[DevUuid], new: Option<DevUuid>) {
+        if let Err(err) = self.setup_replacement(pool_uuid, old, new) {
+            if let Err(e) = self.data_tier.cancel_replacement() {
+                warn!("Failed to release the space reserved for the data of blockdevs being removed or replaced in pool with UUID {pool_uuid}: {e}");
+            }
+            warn!(
+                "Failed to resume moving the data off blockdevs {} in pool with UUID {pool_uuid}, abandoning it: {err}",
+                old.iter()
+                    .map(|uuid| uuid.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+
+    /// Reserve space for the data allocated from old, on new if specified,
+    /// otherwise on the remaining datadevs, set up the mirror device and map
+    /// it into the cap device.
+    fn setup_replacement(
+        &mut self,
+        pool_uuid: PoolUuid,
+        old: &[DevUuid],
+        new: Option<DevUuid>,
+    ) -> StratisResult<()> {
+        let pairs = self.data_tier.start_replacement(old, new)?;
+
+        if pairs.is_empty() || self.device().is_none() {
+            return Ok(());
+        }
+
+        let segments = pairs
+            .into_iter()
+            .map(|(old_seg, new_seg)| MirrorSegment {
+                src: old_seg.segment.device,
+                src_offset: old_seg.segment.start,
+                dst: new_seg.segment.device,
+                dst_offset: new_seg.segment.start,
+                length: old_seg.segment.length,
+            })
+            .collect::<Vec<_>>();
+        self.mirror = Some(MirrorDev::setup(pool_uuid, &segments)?);
+
+        if let Err(err) = self.extend_cap_device(pool_uuid) {
+            // The stripe device may already map the mirror, so it and the
+            // cap device are remapped to the old blockdevs before the mirror
+            // is removed.
+            let mirror = self.mirror.take().expect("set above");
+            return match self
+                .extend_cap_device(pool_uuid)
+                .and_then(|_| mirror.teardown())
+            {
+                Ok(_) => Err(err),
+                Err(e) => Err(StratisError::RollbackError {
+                    causal_error: Box::new(err),
+                    rollback_error: Box::new(e),
+                    level: ActionAvailability::NoRequests,
+                }),
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Check the progress of the replacement or removal of datadevs, if one
+    /// is in progress. If all data has been copied, remap the cap device to
+    /// the space reserved for the data, remove the mirror device, and return
+    /// the replaced or removed blockdevs. Their metadata has not been erased,
+    /// so that the caller can first save pool metadata that no longer refers
+    /// to them.
+    ///
+    /// WARNING: metadata changing event
+    pub fn check_replacement(&mut self, pool_uuid: PoolUuid) -> StratisResult<Vec<StratBlockDev>> {
+        let replacement = match self.data_tier.replacement.as_mut() {
+            Some(r) => r,
+            None => return Ok(Vec::new()),
+        };
+
+        let (in_sync, total) = match self.mirror {
+            Some(ref mirror) => mirror.sync_progress()?,
+            None => (0, 0),
+        };
+        replacement.progress = if total == 0 {
+            100
+        } else {
+            convert_int!(in_sync * 100 / total, u64, u8)?
+        };
+
+        if in_sync < total {
+            return Ok(Vec::new());
+        }
+
+        let old = self.data_tier.finish_replacement()?;
+        if let Some(mirror) = self.mirror.take() {
+            // This must occur after the segments have been updated in the
+            // data tier.
+            self.extend_cap_device(pool_uuid)?;
+            mirror.teardown()?;
+        }
+
+        Ok(old)
+    }
+
+    /// The percentage of the data copied by the replacement or removal of
+    /// datadevs, if one is in progress.
+    pub fn replacement_progress(&self) -> Option<u8> {
+        self.data_tier.replacement_progress()
+    }
+
+    /// Encrypt the datadevs of an unencrypted pool while the pool remains in
+    /// use. Each datadev is formatted with a LUKS2 header and the cap device
+    /// is remapped to the activated encrypted devices; the data is encrypted
+    /// in the background; see next_reencryption(). The LUKS2 header takes up
+    /// the last crypt_metadata_size() of each datadev, which must not be
+    /// allocated.
+    ///
+    /// If any datadev can not be converted, the datadevs that have already
+    /// been converted are restored and the cap device is remapped to them.
+    ///
+    /// Precondition: crypt_params specifies the sector size.
+    ///
+    /// WARNING: metadata changing event
+    pub fn encrypt(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &Name,
+        encryption_info: &EncryptionInfo,
+        crypt_params: &CryptParams,
+    ) -> StratisResult<()> {
+        if self.is_encrypted() {
+            return Err(StratisError::Msg(
+                "The pool is already encrypted".to_string(),
+            ));
+        }
+        if self.cache_tier.is_some() {
+            return Err(StratisError::Msg(
+                "A pool with a cache can not be encrypted; remove the cache first".to_string(),
+            ));
+        }
+        if self.data_tier.redundancy != Redundancy::None {
+            return Err(StratisError::Msg(
+                "A pool with redundancy can not be encrypted".to_string(),
+            ));
+        }
+        if self.data_tier.replacement.is_some() {
+            return Err(StratisError::Msg(
+                "A pool can not be encrypted while a datadev is being replaced".to_string(),
+            ));
+        }
+
+        let tmp_dir = TempDir::new()?;
+
+        // The physical devices must not be in use while their heads are
+        // replaced by the LUKS2 headers.
+        if let Some(ref mut linear) = self.linear {
+            linear.suspend(get_dm(), DmOptions::default())?;
+        }
+        if let Some(ref mut stripe) = self.stripe {
+            if let Err(err) = stripe.suspend() {
+                return match self.extend_cap_device(pool_uuid) {
+                    Ok(_) => Err(err),
+                    Err(e) => Err(StratisError::RollbackError {
+                        causal_error: Box::new(err),
+                        rollback_error: Box::new(e),
+                        level: ActionAvailability::NoRequests,
+                    }),
+                };
+            }
+        }
+
+        let mut encrypted = Vec::new();
+        let mut result = Ok(());
+        for (uuid, bd) in self.data_tier.blockdevs_mut() {
+            let head = tmp_dir.path().join(uuid.to_string());
+            match bd.encrypt(pool_name.clone(), encryption_info, crypt_params, &head) {
+                Ok(_) => encrypted.push(uuid),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        if let Err(err) = result {
+            let mut rollback_res = Ok(());
+            for (uuid, bd) in self.data_tier.blockdevs_mut() {
+                if encrypted.contains(&uuid) {
+                    rollback_res = rollback_res.and_then(|_| {
+                        bd.rollback_encryption(&tmp_dir.path().join(uuid.to_string()))
+                    });
+                }
+            }
+            let rollback_res = rollback_res.and_then(|_| {
+                self.data_tier.remap_devices();
+                self.extend_cap_device(pool_uuid)
+            });
+            return match rollback_res {
+                Ok(_) => Err(err),
+                Err(e) => Err(StratisError::RollbackError {
+                    causal_error: Box::new(err),
+                    rollback_error: Box::new(e),
+                    level: ActionAvailability::NoRequests,
+                }),
+            };
+        }
+
+        self.pbkdf_params = crypt_params.pbkdf_params();
+
+        // This must occur after the segments have been updated in the data
+        // tier.
+        self.data_tier.remap_devices();
+        self.extend_cap_device(pool_uuid)
+    }
+
+    /// Reencrypt the datadevs and the cachedevs with new volume keys while
+    /// the pool remains in use. The blockdevs are reencrypted one at a time;
+    /// the reencryption of the first is started here, that of each of the
+    /// others by next_reencryption() once the one before it has been
+    /// completed. Each is reencrypted with the cipher and the key size in
+    /// crypt_params, if specified; see CryptHandle::start_reencryption().
+    /// The keyslots for the new volume keys are created with the key
+    /// derivation parameters in crypt_params, which are recorded for the
+    /// keyslots that are added subsequently, or else with those recorded.
+    ///
+    /// The blockdevs that remain to be reencrypted and the crypt parameters
+    /// are recorded in the pool metadata and the progress of the
+    /// reencryption of each blockdev in its LUKS2 header, so that an
+    /// interrupted reencryption of the pool is resumed where it left off.
+    pub fn reencrypt(&mut self, crypt_params: Option<&CryptParams>) -> StratisResult<()> {
+        if !self.is_encrypted() {
+            return Err(StratisError::Msg("The pool is not encrypted".to_string()));
+        }
+        if !self.reencryption.is_empty() || self.encryption_progress().is_some() {
+            return Err(StratisError::Msg(
+                "The pool is already being encrypted or reencrypted".to_string(),
+            ));
+        }
+
+        let mut crypt_params = crypt_params.cloned().unwrap_or_default();
+        if !crypt_params.has_pbkdf() {
+            if let Some(pbkdf_params) = self.pbkdf_params.clone() {
+                crypt_params = CryptParams {
+                    cipher: crypt_params.cipher,
+                    key_size: crypt_params.key_size,
+                    sector_size: crypt_params.sector_size,
+                    ..pbkdf_params
+                };
+            }
+        }
+
+        let reencryption = self
+            .blockdevs()
+            .into_iter()
+            .map(|(uuid, _, _)| uuid)
+            .collect::<Vec<_>>();
+        if let Some(&first) = reencryption.first() {
+            let (_, bd) = self
+                .get_mut_blockdev_by_uuid(first)
+                .expect("listed among the blockdevs");
+            bd.start_reencryption(&crypt_params)?;
+        }
+
+        self.reencryption = reencryption;
+        self.pbkdf_params = crypt_params.pbkdf_params();
+        self.reencryption_params = Some(crypt_params).filter(|p| *p != CryptParams::default());
+        Ok(())
+    }
+
+    /// Prepare to continue the online encryption or reencryption of the
+    /// blockdevs, if one is in progress. The blockdevs are processed one at a
+    /// time. If the reencryption of the pool is in progress and no blockdev
+    /// is being reencrypted, the reencryption of the next blockdev is
+    /// started.
+    ///
+    /// Returns whether the blockdevs that remain to be reencrypted have
+    /// changed, in which case the pool metadata must be written, and the
+    /// UUID and a handle of the blockdev whose data is to be processed, if
+    /// any; see StratBlockDev::reencryption_handle().
+    pub fn next_reencryption(&mut self) -> StratisResult<(bool, Option<(DevUuid, CryptHandle)>)> {
+        if let Some(next) = self
+            .blockdevs()
+            .into_iter()
+            .find_map(|(uuid, _, bd)| bd.reencryption_handle().map(|handle| (uuid, handle)))
+        {
+            return Ok((false, Some(next)));
+        }
+
+        let crypt_params = self.reencryption_params.clone().unwrap_or_default();
+        match self.reencryption.first() {
+            Some(&next) => match self.get_mut_blockdev_by_uuid(next) {
+                Some((_, bd)) => {
+                    bd.start_reencryption(&crypt_params)?;
+                    Ok((false, bd.reencryption_handle().map(|handle| (next, handle))))
+                }
+                // The blockdev has been removed from the pool.
+                None => {
+                    self.reencryption.remove(0);
+                    if self.reencryption.is_empty() {
+                        self.reencryption_params = None;
+                    }
+                    Ok((true, None))
+                }
+            },
+            None => Ok((false, None)),
+        }
+    }
+
+    /// Record the progress of the online encryption or reencryption of the
+    /// blockdev, as returned by CryptHandle::continue_reencryption().
+    ///
+    /// Returns true if the blockdevs that remain to be reencrypted have
+    /// changed, in which case the pool metadata must be written.
+    pub fn record_reencryption(&mut self, uuid: DevUuid, progress: Option<u8>) -> bool {
+        // The blockdev may have been removed from the pool while its data
+        // was processed.
+        if let Some((_, bd)) = self.get_mut_blockdev_by_uuid(uuid) {
+            if !bd.record_reencryption(progress) {
+                return false;
+            }
+        }
+
+        let remaining = self.reencryption.len();
+        self.reencryption.retain(|u| *u != uuid);
+        if self.reencryption.is_empty() {
+            self.reencryption_params = None;
+        }
+        self.reencryption.len() != remaining
+    }
+
+    /// The percentage of the blockdevs that has been reencrypted with new
+    /// volume keys, if a reencryption of the pool is in progress.
+    pub fn reencryption_progress(&self) -> Option<u8> {
+        if self.reencryption.is_empty() {
+            return None;
+        }
+
+        let blockdevs = self.blockdevs();
+        let completed = blockdevs.len().saturating_sub(self.reencryption.len());
+        let current = self
+            .reencryption
+            .first()
+            .and_then(|uuid| self.get_blockdev_by_uuid(*uuid))
+            .and_then(|(_, bd)| bd.reencryption_progress())
+            .unwrap_or(0);
+        let total = convert_int!(completed, usize, u64).ok()? * 100 + u64::from(current);
+        convert_int!(
+            total / convert_int!(blockdevs.len(), usize, u64).ok()?,
+            u64,
+            u8
+        )
+        .ok()
+    }
+
+    /// The percentage of the datadevs that has been encrypted, if an online
+    /// encryption of the pool is in progress.
+    pub fn encryption_progress(&self) -> Option<u8> {
+        let datadevs = self.datadevs();
+        if !self.reencryption.is_empty()
+            || datadevs
+                .iter()
+                .all(|(_, bd)| bd.reencryption_progress().is_none())
+        {
+            return None;
+        }
+
+        let total = datadevs
+            .iter()
+            .map(|(_, bd)| u64::from(bd.reencryption_progress().unwrap_or(100)))
+            .sum::<u64>();
+        convert_int!(
+            total / convert_int!(datadevs.len(), usize, u64).ok()?,
+            u64,
+            u8
+        )
+        .ok()
+    }
+
+    /// Back up the LUKS2 headers of all blockdevs of this encrypted pool to a
+    /// single archive file.
+    pub fn back_up_luks_headers(&self, pool_uuid: PoolUuid, archive: &Path) -> StratisResult<()> {
+        if !self.is_encrypted() {
+            return Err(StratisError::Msg("The pool is not encrypted".to_string()));
+        }
+        if !self.reencryption.is_empty() || self.encryption_progress().is_some() {
+            return Err(StratisError::Msg(
+                "The LUKS2 headers can not be backed up while the pool is being encrypted or reencrypted"
+                    .to_string(),
+            ));
+        }
+
+        let blockdevs = self.blockdevs();
+        let devices = blockdevs
+            .iter()
+            .map(|(uuid, _, bd)| (*uuid, bd.physical_path()))
+            .collect::<Vec<_>>();
+        back_up_luks_headers(pool_uuid, &devices, archive)
+    }
+
+    /// Destroy every keyslot of every block device of an encrypted pool.
+    pub fn erase_keyslots(&self) -> StratisResult<()> {
+        if !self.is_encrypted() {
+            return Err(StratisError::Msg("The pool is not encrypted".to_string()));
+        }
+        self.blockdevs()
+            .into_iter()
+            .try_for_each(|(_, _, bd)| bd.erase_keyslots())
+    }
+
+    /// The ranges of each block device that are allocated according to the
+    /// allocs recorded in the metadata, as (start, length) pairs, together with
+    /// the path of the physical device. The allocs are offsets in the device
+    /// stacked on the physical device, if any, which can not in general be
+    /// translated to offsets in the physical device; an integrity device
+    /// interleaves its metadata with the data. None is returned in place of
+    /// the ranges for such a block device, so that the whole physical device
+    /// is erased.
+    pub fn allocated_ranges(&self) -> Vec<(PathBuf, Option<Vec<(Sectors, Sectors)>>)> {
+        let record = self.record();
+        let mut ranges: HashMap<DevUuid, Vec<(Sectors, Sectors)>> = HashMap::new();
+        for seg in record
+            .data_tier
+            .blockdev
+            .allocs
+            .iter()
+            .chain(
+                record
+                    .cache_tier
+                    .iter()
+                    .flat_map(|ct| ct.blockdev.allocs.iter()),
+            )
+            .flatten()
+        {
+            ranges
+                .entry(seg.parent)
+                .or_default()
+                .push((seg.start, seg.length));
+        }
+
+        self.blockdevs()
+            .into_iter()
+            .filter_map(|(uuid, _, bd)| {
+                ranges.remove(&uuid).map(|ranges| {
+                    if bd.data_path() == bd.physical_path() {
+                        (bd.physical_path().to_owned(), Some(ranges))
+                    } else {
+                        (bd.physical_path().to_owned(), None)
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Extend the raid device so that each of its legs maps all the segments
+    /// allocated for it in the data tier. Create the DM device if it does
+    /// not already exist. Do nothing if the data tier has no redundancy.
+    fn extend_raid_device(&mut self, pool_uuid: PoolUuid) -> StratisResult<()> {
+        if self.data_tier.redundancy == Redundancy::None {
+            return Ok(());
+        }
+
+        let tables = self.data_tier.leg_tables();
+        match self.raid {
+            Some(ref mut raid) => raid.extend(tables)?,
+            None => self.raid = Some(RaidDev::setup(pool_uuid, tables)?),
+        }
+
+        Ok(())
+    }
+
+    /// Extend the stripe device so that it maps all the segments allocated
+    /// in the data tier. Create the DM device if it does not already exist.
+    /// Do nothing if no extents of the data tier are striped.
+    fn extend_stripe_device(&mut self, pool_uuid: PoolUuid) -> StratisResult<()> {
+        if self.data_tier.stripes.is_empty() {
+            return Ok(());
+        }
+
+        let table = stripe_table(
+            &data_segments(&self.data_tier, self.mirror.as_ref()),
+            &self.data_tier.stripes,
+        )?;
+        match self.stripe {
+            Some(ref mut stripe) => stripe.set_table(table)?,
+            None => self.stripe = Some(StripeDev::setup(pool_uuid, table)?),
+        }
+
+        Ok(())
+    }
+
+    /// Extend the cap device whether it is a cache or not. Create the DM
+    /// device if it does not already exist. Return an error if DM
+    /// operations fail. Use all segments currently allocated in the data tier.
+    fn extend_cap_device(&mut self, pool_uuid: PoolUuid) -> StratisResult<()> {
+        // This must occur before the cap device is extended, since the cap
+        // device maps the whole raid or stripe device.
+        self.extend_raid_device(pool_uuid)?;
+        self.extend_stripe_device(pool_uuid)?;
+
+        let create = match (self.cache.as_mut(), self.linear.as_mut()) {
+            (None, None) => true,
+            (Some(cache), None) => {
+                let table = cap_table(
+                    &self.data_tier,
+                    self.mirror.as_ref(),
+                    self.raid.as_ref(),
+                    self.stripe.as_ref(),
+                );
+                cache.set_origin_table(table)?;
+                false
+            }
+            (None, Some(linear)) => {
+                let table = cap_table(
+                    &self.data_tier,
+                    self.mirror.as_ref(),
+                    self.raid.as_ref(),
+                    self.stripe.as_ref(),
+                );
+                linear.set_table(get_dm(), table)?;
+                linear.resume(get_dm())?;
+                false
+            }
+            _ => panic!("NOT (self.cache().is_some() AND self.linear.is_some())"),
+        };
+
+        if create {
+            let table = cap_table(
+                &self.data_tier,
+                self.mirror.as_ref(),
+                self.raid.as_ref(),
+                self.stripe.as_ref(),
+            );
+            let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::OriginSub);
+            let origin = LinearDev::setup(get_dm(), &dm_name, Some(&dm_uuid), table)?;
+            self.linear = Some(origin);
+        }
+
+        Ok(())
+    }
+
+    /// Satisfy a request for multiple segments. This request must
+    /// always be satisfied exactly, None is returned if this can not
+    /// be done.
+    ///
+    /// Precondition: self.next <= self.size()
+    /// Postcondition: self.next <= self.size()
+    ///
+    /// Postcondition: forall i, sizes_i == result_i.1. The second value
+    /// in each pair in the returned vector is therefore redundant, but is
+    /// retained as a convenience to the caller.
+    /// Postcondition:
+    /// forall i, result_i.0 = result_(i - 1).0 + result_(i - 1).1
+    pub fn request_alloc(
+        &mut self,
+        sizes: &[Sectors],
+    ) -> StratisResult<Option<RequestTransaction>> {
+        let mut transaction = match self.data_tier.alloc_request(sizes)? {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+
+        let mut next = self.next;
+        for size in sizes {
+            transaction.add_seg_req((next, *size));
+            next += *size
+        }
+
+        // Assert that the postcondition holds.
+        assert_eq!(
+            sizes,
+            transaction
+                .get_backstore()
+                .iter()
+                .map(|x| x.1)
+                .collect::<Vec<Sectors>>()
+                .as_slice()
+        );
+
+        Ok(Some(transaction))
+    }
+
+    /// Commit space requested by request_alloc() to metadata.
+    ///
+    /// This method commits the newly allocated data segments and then extends the cap device
+    /// to be the same size as the allocated data size.
+    pub fn commit_alloc(
+        &mut self,
+        pool_uuid: PoolUuid,
+        transaction: RequestTransaction,
+    ) -> StratisResult<()> {
+        let segs = transaction.get_backstore();
+        self.data_tier.alloc_commit(transaction)?;
+        // This must occur after the segments have been updated in the data tier
+        self.extend_cap_device(pool_uuid)?;
+
+        assert!(self.next <= self.size());
+
+        self.next += segs
+            .into_iter()
+            .fold(Sectors(0), |mut size, (_, next_size)| {
+                size += next_size;
+                size
+            });
+
+        Ok(())
+    }
+
+    /// The number of sectors of the cap device that have been allocated to
+    /// the upper layer.
+    pub fn allocated_in_cap(&self) -> Sectors {
+        self.next
+    }
+
+    /// Return an error if the specified (start, length) range of the cap
+    /// device can not be released by release_alloc().
+    pub fn check_release(&self, range: (Sectors, Sectors)) -> StratisResult<()> {
+        let (start, length) = range;
+        if start + length != self.next {
+            return Err(StratisError::Msg(format!(
+                "Only a range at the end of the space allocated from the cap device, which ends at {}, can be released",
+                self.next
+            )));
+        }
+
+        if self.cache.is_some() {
+            return Err(StratisError::Msg(
+                "Space can not be released from a backstore with a cache".to_string(),
+            ));
+        }
+
+        self.data_tier.check_release(length)
+    }
+
+    /// Release the specified (start, length) range of the cap device, which
+    /// was allocated by commit_alloc(). The cap device is shrunk and the
+    /// space is returned to the blockdevs in the data tier, so that it can
+    /// be allocated again.
+    ///
+    /// Return an error if the range is not at the end of the allocated space,
+    /// if there is a cache, or if the data tier can not release the space.
+    ///
+    /// Precondition: no DM device above the backstore maps any sector of the
+    /// range.
+    ///
+    /// WARNING: metadata changing event
+    pub fn release_alloc(&mut self, range: (Sectors, Sectors)) -> StratisResult<()> {
+        self.check_release(range)?;
+
+        self.data_tier.release_tail(range.1)?;
+
+        // The stripe device must be shrunk before the cap device, since the
+        // cap device maps the whole stripe device.
+        if let Some(ref mut stripe) = self.stripe {
+            stripe.set_table(stripe_table(
+                &data_segments(&self.data_tier, self.mirror.as_ref()),
+                &self.data_tier.stripes,
+            )?)?;
+        }
+
+        let table = cap_table(
+            &self.data_tier,
+            self.mirror.as_ref(),
+            self.raid.as_ref(),
+            self.stripe.as_ref(),
+        );
+        let linear = self
+            .linear
+            .as_mut()
+            .expect("space was allocated and there is no cache, so the cap device is linear");
+        linear.set_table(get_dm(), table)?;
+        linear.resume(get_dm())?;
+
+        self.next -= range.1;
+
+        Ok(())
+    }
+
+    /// Get only the datadevs in the pool.
+    pub fn datadevs(&self) -> Vec<(DevUuid, &StratBlockDev)> {
+        self.data_tier.blockdevs()
+    }
+
+    /// Get only the cachdevs in the pool.
+    pub fn cachedevs(&self) -> Vec<(DevUuid, &StratBlockDev)> {
+        match self.cache_tier {
+            Some(ref cache) => cache.blockdevs(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Return a reference to all the blockdevs that this pool has ownership
+    /// of. The blockdevs may be returned in any order. It is unsafe to assume
+    /// that they are grouped by tier or any other organization.
+    pub fn blockdevs(&self) -> Vec<(DevUuid, BlockDevTier, &StratBlockDev)> {
+        self.datadevs()
+            .into_iter()
+            .map(|(uuid, dev)| (uuid, BlockDevTier::Data, dev))
+            .chain(
+                self.cachedevs()
+                    .into_iter()
+                    .map(|(uuid, dev)| (uuid, BlockDevTier::Cache, dev)),
+            )
+            .collect()
+    }
+
+    pub fn blockdevs_mut(&mut self) -> Vec<(DevUuid, BlockDevTier, &mut StratBlockDev)> {
+        match self.cache_tier {
+            Some(ref mut cache) => cache
+                .blockdevs_mut()
+                .into_iter()
+                .map(|(uuid, dev)| (uuid, BlockDevTier::Cache, dev))
+                .chain(
+                    self.data_tier
+                        .blockdevs_mut()
+                        .into_iter()
+                        .map(|(uuid, dev)| (uuid, BlockDevTier::Data, dev)),
+                )
+                .collect(),
+            None => self
+                .data_tier
+                .blockdevs_mut()
+                .into_iter()
+                .map(|(uuid, dev)| (uuid, BlockDevTier::Data, dev))
+                .collect(),
+        }
+    }
+
+    /// The current size of all the blockdevs in the data tier.
+    pub fn datatier_size(&self) -> Sectors {
+        self.data_tier.size()
+    }
+
+    /// The current size of allocated space on the blockdevs in the data tier.
+    pub fn datatier_allocated_size(&self) -> Sectors {
+        self.data_tier.allocated()
+    }
+
+    /// The current usable size of all the blockdevs in the data tier.
+    pub fn datatier_usable_size(&self) -> Sectors {
+        self.data_tier.usable_size()
+    }
+
+    /// The redundancy of the data tier.
+    pub fn redundancy(&self) -> Redundancy {
+        self.data_tier.redundancy
+    }
+
+    /// The hash with which the data on the blockdevs in the data tier is
+    /// checked, if they have integrity protection.
+    pub fn integrity(&self) -> Option<IntegrityHash> {
+        self.data_tier.block_mgr.integrity_hash()
+    }
+
+    /// Refresh the number of integrity errors of every blockdev in the data
+    /// tier. Return the UUIDs of the blockdevs the number of which has
+    /// changed.
+    pub fn check_integrity(&mut self) -> Vec<DevUuid> {
+        self.data_tier
+            .blockdevs_mut()
+            .into_iter()
+            .filter_map(|(uuid, bd)| match bd.check_integrity() {
+                Ok(changed) => changed.then_some(uuid),
+                Err(e) => {
+                    warn!(
+                        "Failed to read the integrity errors of device {}: {}",
+                        bd.devnode().display(),
+                        e
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The policy by which new extents of the cap device are allocated from
+    /// the data tier.
+    pub fn allocation_policy(&self) -> AllocationPolicy {
+        self.data_tier.allocation_policy
+    }
+
+    /// Set the policy by which new extents of the cap device are allocated
+    /// from the data tier. Return true if the policy was changed.
+    ///
+    /// WARNING: metadata changing event
+    pub fn set_allocation_policy(&mut self, policy: AllocationPolicy) -> StratisResult<bool> {
+        self.data_tier.set_allocation_policy(policy)
+    }
+
+    /// Whether the datadev belongs to the pool but was absent when the pool
+    /// was set up.
+    pub fn is_absent_datadev(&self, uuid: DevUuid) -> bool {
+        self.data_tier.is_absent(uuid)
+    }
+
+    /// Record that the datadev has disappeared from the system. Return true
+    /// if the datadev was not already known to be missing.
+    pub fn mark_datadev_missing(&mut self, uuid: DevUuid) -> bool {
+        self.data_tier.mark_missing(uuid)
+    }
+
+    /// Whether the data tier is degraded: some datadev has disappeared from
+    /// the system or has shrunk, or the kernel has failed a leg of the raid
+    /// device.
+    pub fn is_degraded(&self) -> StratisResult<bool> {
+        if self.data_tier.has_missing() || self.data_tier.has_shrunk() {
+            return Ok(true);
+        }
+        match self.raid {
+            Some(ref raid) => raid.has_failed_leg(),
+            None => Ok(false),
+        }
+    }
+
+    /// The size of the cap device.
+    ///
+    /// The size of the cap device is obtained from the size of the component
+    /// DM devices. But the devicemapper library stores the data from which
+    /// the size of each DM device is calculated; the result is computed and
+    /// no ioctl is required.
+    fn size(&self) -> Sectors {
         self.linear
             .as_ref()
             .map(|d| d.size())
@@ -700,6 +1952,9 @@ impl Backstore {
             blockdevs.iter().map(|(_, _, bd)| bd.encryption_info()),
         )
         .expect("All devices must be either encrypted or unencrypted for the pool to be set up")
+        .map(|info| {
+            info.set_keyring_bindings(blockdevs.iter().map(|(_, _, bd)| bd.keyring_bindings()))
+        })
     }
 
     /// Bind all devices in the given backstore using the given clevis
@@ -835,9 +2090,10 @@ impl Backstore {
                 )))
             }
         } else {
+            let pbkdf_params = self.pbkdf_params.clone();
             operation_loop(
                 self.blockdevs_mut().into_iter().map(|(_, _, bd)| bd),
-                |blockdev| blockdev.bind_keyring(key_desc),
+                |blockdev| blockdev.bind_keyring(key_desc, pbkdf_params.as_ref()),
             )?;
             Ok(true)
         }
@@ -895,9 +2151,10 @@ impl Backstore {
             Ok(Some(false))
         } else if encryption_info.key_description().is_some() {
             // Keys are not the same but key description is present
+            let pbkdf_params = self.pbkdf_params.clone();
             operation_loop(
                 self.blockdevs_mut().into_iter().map(|(_, _, bd)| bd),
-                |blockdev| blockdev.rebind_keyring(key_desc),
+                |blockdev| blockdev.rebind_keyring(key_desc, pbkdf_params.as_ref()),
             )?;
             Ok(Some(true))
         } else {
@@ -905,6 +2162,70 @@ impl Backstore {
         }
     }
 
+    /// Bind all devices in the given backstore to an additional passphrase
+    /// in the kernel keyring.
+    ///
+    /// * Returns Ok(true) if the binding was performed.
+    /// * Returns Ok(false) if the pool was already bound to the key description
+    /// and nothing was changed.
+    /// * Returns Err(_) if an inconsistency was found in the metadata across pools
+    /// or binding failed.
+    pub fn add_keyring_binding(&mut self, key_desc: &KeyDescription) -> StratisResult<bool> {
+        let encryption_info = match self.encryption_info() {
+            Some(ei) => ei,
+            None => {
+                return Err(StratisError::Msg(
+                    "Requested pool does not appear to be encrypted".to_string(),
+                ));
+            }
+        };
+
+        if encryption_info.keyring_bindings()?.contains(key_desc) {
+            Ok(false)
+        } else if encryption_info.key_description()? == Some(key_desc) {
+            Err(StratisError::Msg(format!(
+                "Key description {} is already the primary keyring binding of the pool",
+                key_desc.as_application_str(),
+            )))
+        } else {
+            let pbkdf_params = self.pbkdf_params.clone();
+            operation_loop(
+                self.blockdevs_mut().into_iter().map(|(_, _, bd)| bd),
+                |blockdev| blockdev.add_keyring_binding(key_desc, pbkdf_params.as_ref()),
+            )?;
+            Ok(true)
+        }
+    }
+
+    /// Remove an additional keyring binding from all devices in the given
+    /// backstore.
+    ///
+    /// * Returns Ok(true) if the binding was removed.
+    /// * Returns Ok(false) if the pool was not bound to the key description
+    /// and nothing was changed.
+    /// * Returns Err(_) if an inconsistency was found in the metadata across pools
+    /// or removing the binding failed.
+    pub fn remove_keyring_binding(&mut self, key_desc: &KeyDescription) -> StratisResult<bool> {
+        let encryption_info = match self.encryption_info() {
+            Some(ei) => ei,
+            None => {
+                return Err(StratisError::Msg(
+                    "Requested pool does not appear to be encrypted".to_string(),
+                ));
+            }
+        };
+
+        if encryption_info.keyring_bindings()?.contains(key_desc) {
+            operation_loop(
+                self.blockdevs_mut().into_iter().map(|(_, _, bd)| bd),
+                |blockdev| blockdev.remove_keyring_binding(key_desc),
+            )?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
     /// Regenerate the Clevis bindings with the block devices in this pool using
     /// the same configuration.
     ///
@@ -943,6 +2264,12 @@ impl Backstore {
         self.data_tier.grow(dev)
     }
 
+    /// Shrink the datadev to the size of its underlying physical device.
+    /// Return an error if the region that was lost holds any allocations.
+    pub fn shrink(&mut self, dev: DevUuid) -> StratisResult<bool> {
+        self.data_tier.shrink(dev)
+    }
+
     /// Rename pool name in LUKS2 token if pool is encrypted.
     pub fn rename_pool(&mut self, new_name: &Name) -> StratisResult<()> {
         if self.encryption_info().is_some() {
@@ -954,6 +2281,19 @@ impl Backstore {
         Ok(())
     }
 
+    /// Record in the LUKS2 metadata of every encrypted blockdev whether the
+    /// pool was stopped on purpose, so that this can be told before the
+    /// blockdevs are unlocked.
+    pub fn set_stopped(&mut self, stopped: bool) -> StratisResult<()> {
+        if self.encryption_info().is_some() {
+            operation_loop(
+                self.blockdevs_mut().into_iter().map(|(_, _, bd)| bd),
+                |blockdev| blockdev.set_stopped(stopped),
+            )?;
+        }
+        Ok(())
+    }
+
     /// A summary of block sizes
     pub fn block_size_summary(&self, tier: BlockDevTier) -> Option<BlockSizeSummary> {
         match tier {
@@ -1017,6 +2357,11 @@ impl Recordable<BackstoreSave> for Backstore {
                 allocs: vec![(Sectors(0), self.next)],
             },
             data_tier: self.data_tier.record(),
+            redundancy: Some(self.data_tier.redundancy),
+            allocation_policy: Some(self.data_tier.allocation_policy),
+            reencryption: self.reencryption.clone(),
+            reencryption_params: self.reencryption_params.clone(),
+            pbkdf_params: self.pbkdf_params.clone(),
         }
     }
 }
@@ -1089,12 +2434,16 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::{env, fs::OpenOptions, path::Path};
+    use std::{env, fs::OpenOptions, os::unix::fs::FileExt, path::Path, thread::sleep};
 
-    use devicemapper::{CacheDevStatus, DataBlocks, DmOptions, IEC};
+    use devicemapper::{CacheDevStatus, DataBlocks, DevId, DmFlags, DmOptions, IEC};
 
     use crate::engine::strat_engine::{
-        backstore::devices::{ProcessedPathInfos, UnownedDevices},
+        backstore::{
+            cache::CACHE_BLOCK_SIZE,
+            devices::{ProcessedPathInfos, UnownedDevices},
+            raid::RAID_META_SIZE,
+        },
         cmd,
         metadata::device_identifiers,
         ns::{unshare_mount_namespace, MemoryFilesystem},
@@ -1108,7 +2457,9 @@ mod tests {
     /// Assert some invariants of the backstore
     /// * backstore.cache_tier.is_some() <=> backstore.cache.is_some() &&
     ///   backstore.cache_tier.is_some() => backstore.linear.is_none()
-    /// * backstore's data tier allocated is equal to the size of the cap device
+    /// * backstore's data tier allocated is equal to the size of the cap
+    ///   device, or, if the data tier has redundancy, the size of each copy
+    ///   of the data other than the raid metadata is
     /// * backstore's next index is always less than the size of the cap
     ///   device
     fn invariant(backstore: &Backstore) {
@@ -1118,8 +2469,13 @@ mod tests {
                     && backstore.cache.is_some()
                     && backstore.linear.is_none())
         );
+        let mapped = match backstore.data_tier.redundancy {
+            Redundancy::None => backstore.data_tier.allocated(),
+            _ if backstore.data_tier.segments.size() == Sectors(0) => Sectors(0),
+            _ => backstore.data_tier.segments.size() - RAID_META_SIZE,
+        };
         assert_eq!(
-            backstore.data_tier.allocated(),
+            mapped,
             match (&backstore.linear, &backstore.cache) {
                 (None, None) => Sectors(0),
                 (&None, Some(cache)) => cache.size(),
@@ -1173,6 +2529,9 @@ mod tests {
             initdatadevs,
             MDADataSize::default(),
             None,
+            None,
+            None,
+            Redundancy::None,
         )
         .unwrap();
 
@@ -1186,7 +2545,13 @@ mod tests {
         backstore.commit_alloc(pool_uuid, transaction).unwrap();
 
         let cache_uuids = backstore
-            .init_cache(pool_name.clone(), pool_uuid, initcachedevs, None)
+            .init_cache(
+                pool_name.clone(),
+                pool_uuid,
+                initcachedevs,
+                None,
+                CacheSettings::default(),
+            )
             .unwrap();
 
         invariant(&backstore);
@@ -1197,65 +2562,174 @@ mod tests {
         let cache_status = backstore
             .cache
             .as_ref()
-            .map(|c| c.status(get_dm(), DmOptions::default()).unwrap())
+            .map(|c| c.status().unwrap())
+            .unwrap();
+
+        match cache_status {
+            CacheDevStatus::Working(status) => {
+                let usage = &status.usage;
+                assert_eq!(usage.used_cache, DataBlocks(0));
+                assert_eq!(usage.total_meta, meta_size.metablocks());
+                assert!(usage.total_cache > DataBlocks(0));
+            }
+            CacheDevStatus::Error => panic!("cache status could not be obtained"),
+            CacheDevStatus::Fail => panic!("cache is in a failed state"),
+        }
+
+        let data_uuids = backstore
+            .add_datadevs(pool_name.clone(), pool_uuid, datadevs, None)
+            .unwrap();
+        invariant(&backstore);
+        assert_eq!(data_uuids.len(), datadevpaths.len());
+
+        let cache_uuids = backstore
+            .add_cachedevs(pool_name, pool_uuid, cachedevs, None)
+            .unwrap();
+        invariant(&backstore);
+        assert_eq!(cache_uuids.len(), cachedevpaths.len());
+
+        let cache_status = backstore
+            .cache
+            .as_ref()
+            .map(|c| c.status().unwrap())
+            .unwrap();
+
+        match cache_status {
+            CacheDevStatus::Working(status) => {
+                let usage = &status.usage;
+                assert_eq!(usage.used_cache, DataBlocks(0));
+                assert_eq!(usage.total_meta, meta_size.metablocks());
+                assert!(usage.total_cache > DataBlocks(0));
+            }
+            CacheDevStatus::Error => panic!("cache status could not be obtained"),
+            CacheDevStatus::Fail => panic!("cache is in a failed state"),
+        }
+
+        assert_eq!(
+            backstore
+                .cache_stats()
+                .unwrap()
+                .map(|stats| stats.dirty_blocks),
+            Some(0)
+        );
+
+        backstore.destroy(pool_uuid).unwrap();
+    }
+
+    #[test]
+    fn loop_test_add_cache_devs() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(4, 5, None),
+            test_add_cache_devs,
+        );
+    }
+
+    #[test]
+    fn real_test_add_cache_devs() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(4, None, None),
+            test_add_cache_devs,
+        );
+    }
+
+    /// Test that the mode and policy of a cache are kept when the cap device
+    /// is extended and when cachedevs are added, both of which load a new
+    /// table into the cache device.
+    fn test_cache_settings_kept(paths: &[&Path]) {
+        assert!(paths.len() > 2);
+
+        let (initcachepaths, paths) = paths.split_at(1);
+        let (cachedevpaths, initdatapaths) = paths.split_at(1);
+
+        let pool_uuid = PoolUuid::new_v4();
+        let pool_name = Name::new("pool_name".to_string());
+
+        let mut backstore = Backstore::initialize(
+            pool_name.clone(),
+            pool_uuid,
+            get_devices(initdatapaths).unwrap(),
+            MDADataSize::default(),
+            None,
+            None,
+            None,
+            Redundancy::None,
+        )
+        .unwrap();
+
+        let transaction = backstore
+            .request_alloc(&[INITIAL_BACKSTORE_ALLOCATION])
+            .unwrap()
+            .unwrap();
+        backstore.commit_alloc(pool_uuid, transaction).unwrap();
+
+        let settings = CacheSettings::new(
+            CacheMode::Writeback,
+            "smq".to_string(),
+            vec![("migration_threshold".to_string(), "4096".to_string())],
+        )
+        .unwrap();
+        backstore
+            .init_cache(
+                pool_name.clone(),
+                pool_uuid,
+                get_devices(initcachepaths).unwrap(),
+                None,
+                settings.clone(),
+            )
             .unwrap();
 
-        match cache_status {
-            CacheDevStatus::Working(status) => {
-                let usage = &status.usage;
-                assert_eq!(usage.used_cache, DataBlocks(0));
-                assert_eq!(usage.total_meta, meta_size.metablocks());
-                assert!(usage.total_cache > DataBlocks(0));
-            }
-            CacheDevStatus::Error => panic!("cache status could not be obtained"),
-            CacheDevStatus::Fail => panic!("cache is in a failed state"),
-        }
+        let check_live_table = |backstore: &Backstore| {
+            let cache = backstore.cache.as_ref().unwrap();
+            let (_, table) = get_dm()
+                .table_status(
+                    &DevId::Name(cache.name()),
+                    DmOptions::default().set_flags(DmFlags::DM_STATUS_TABLE),
+                )
+                .unwrap();
+            let params = &table[0].3;
+            assert!(params.contains(" 1 writeback smq 2 migration_threshold 4096"));
+            assert_eq!(table[0].1, *cache.size());
+        };
+        check_live_table(&backstore);
 
-        let data_uuids = backstore
-            .add_datadevs(pool_name.clone(), pool_uuid, datadevs, None)
+        let size = backstore.datatier_usable_size() - backstore.datatier_allocated_size();
+        let transaction = backstore
+            .request_alloc(&[Sectors(*size / 2)])
+            .unwrap()
             .unwrap();
+        backstore.commit_alloc(pool_uuid, transaction).unwrap();
         invariant(&backstore);
-        assert_eq!(data_uuids.len(), datadevpaths.len());
+        check_live_table(&backstore);
 
-        let cache_uuids = backstore
-            .add_cachedevs(pool_name, pool_uuid, cachedevs, None)
+        backstore
+            .add_cachedevs(
+                pool_name,
+                pool_uuid,
+                get_devices(cachedevpaths).unwrap(),
+                None,
+            )
             .unwrap();
         invariant(&backstore);
-        assert_eq!(cache_uuids.len(), cachedevpaths.len());
-
-        let cache_status = backstore
-            .cache
-            .as_ref()
-            .map(|c| c.status(get_dm(), DmOptions::default()).unwrap())
-            .unwrap();
+        check_live_table(&backstore);
 
-        match cache_status {
-            CacheDevStatus::Working(status) => {
-                let usage = &status.usage;
-                assert_eq!(usage.used_cache, DataBlocks(0));
-                assert_eq!(usage.total_meta, meta_size.metablocks());
-                assert!(usage.total_cache > DataBlocks(0));
-            }
-            CacheDevStatus::Error => panic!("cache status could not be obtained"),
-            CacheDevStatus::Fail => panic!("cache is in a failed state"),
-        }
+        assert_eq!(backstore.cache_settings(), Some(&settings));
 
         backstore.destroy(pool_uuid).unwrap();
     }
 
     #[test]
-    fn loop_test_add_cache_devs() {
+    fn loop_test_cache_settings_kept() {
         loopbacked::test_with_spec(
-            &loopbacked::DeviceLimits::Range(4, 5, None),
-            test_add_cache_devs,
+            &loopbacked::DeviceLimits::Exactly(3, None),
+            test_cache_settings_kept,
         );
     }
 
     #[test]
-    fn real_test_add_cache_devs() {
+    fn real_test_cache_settings_kept() {
         real::test_with_spec(
-            &real::DeviceLimits::AtLeast(4, None, None),
-            test_add_cache_devs,
+            &real::DeviceLimits::Exactly(3, None, None),
+            test_cache_settings_kept,
         );
     }
 
@@ -1279,6 +2753,9 @@ mod tests {
             devices1,
             MDADataSize::default(),
             None,
+            None,
+            None,
+            Redundancy::None,
         )
         .unwrap();
 
@@ -1304,7 +2781,13 @@ mod tests {
         let old_device = backstore.device();
 
         backstore
-            .init_cache(pool_name, pool_uuid, devices2, None)
+            .init_cache(
+                pool_name,
+                pool_uuid,
+                devices2,
+                None,
+                CacheSettings::default(),
+            )
             .unwrap();
 
         for path in paths2 {
@@ -1334,6 +2817,385 @@ mod tests {
         real::test_with_spec(&real::DeviceLimits::AtLeast(2, None, None), test_setup);
     }
 
+    /// Create a backstore and set a striped allocation policy.
+    /// Verify that space allocated to the cap device is striped across the
+    /// datadevs and that the allocation policy and the striped extents are
+    /// recorded.
+    fn test_striped_allocation(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let pool_uuid = PoolUuid::new_v4();
+        let pool_name = Name::new("pool_name".to_string());
+
+        let devices = get_devices(paths).unwrap();
+
+        let mut backstore = Backstore::initialize(
+            pool_name,
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            None,
+            None,
+            None,
+            Redundancy::None,
+        )
+        .unwrap();
+
+        let policy = AllocationPolicy::striped(Sectors(128)).unwrap();
+        assert!(backstore.set_allocation_policy(policy).unwrap());
+        assert!(!backstore.set_allocation_policy(policy).unwrap());
+
+        let request = INITIAL_BACKSTORE_ALLOCATION * paths.len();
+        let transaction = backstore.request_alloc(&[request]).unwrap().unwrap();
+        backstore.commit_alloc(pool_uuid, transaction).unwrap();
+
+        invariant(&backstore);
+
+        assert!(backstore.stripe.is_some());
+        assert_eq!(backstore.data_tier.stripes.len(), 1);
+        assert!(backstore.data_tier.stripes[0].stripes > 1);
+
+        let save = backstore.record();
+        assert_eq!(save.allocation_policy, Some(policy));
+        assert_eq!(save.data_tier.stripes.len(), 1);
+
+        backstore.destroy(pool_uuid).unwrap();
+    }
+
+    #[test]
+    fn loop_test_striped_allocation() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_striped_allocation,
+        );
+    }
+
+    #[test]
+    fn real_test_striped_allocation() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_striped_allocation,
+        );
+    }
+
+    /// Create a backstore with a striped extent, write some data, and remove
+    /// a datadev that holds a piece of the extent. Verify that the piece is
+    /// moved to a single range on the remaining datadevs, so that the extent
+    /// remains striped, and that the data can be read back.
+    fn test_striped_removal(paths: &[&Path]) {
+        assert!(paths.len() > 2);
+
+        let (paths1, paths2) = paths.split_at(2);
+
+        let pool_uuid = PoolUuid::new_v4();
+        let pool_name = Name::new("pool_name".to_string());
+
+        let mut backstore = Backstore::initialize(
+            pool_name.clone(),
+            pool_uuid,
+            get_devices(paths1).unwrap(),
+            MDADataSize::default(),
+            None,
+            None,
+            None,
+            Redundancy::None,
+        )
+        .unwrap();
+
+        backstore
+            .set_allocation_policy(AllocationPolicy::striped(Sectors(128)).unwrap())
+            .unwrap();
+        let transaction = backstore
+            .request_alloc(&[INITIAL_BACKSTORE_ALLOCATION * 2usize])
+            .unwrap()
+            .unwrap();
+        backstore.commit_alloc(pool_uuid, transaction).unwrap();
+        assert_eq!(backstore.data_tier.stripes.len(), 1);
+
+        let bytes = vec![0xa5u8; 4096];
+        {
+            let f = OpenOptions::new()
+                .write(true)
+                .open(backstore.linear.as_ref().unwrap().devnode())
+                .unwrap();
+            f.write_all_at(&bytes, 0).unwrap();
+            f.sync_all().unwrap();
+        }
+
+        backstore
+            .add_datadevs(pool_name, pool_uuid, get_devices(paths2).unwrap(), None)
+            .unwrap();
+        invariant(&backstore);
+
+        let first = backstore.data_tier.segments.inner[0].uuid;
+        assert!(backstore
+            .remove_datadevs(pool_uuid, &[first])
+            .unwrap()
+            .is_empty());
+        invariant(&backstore);
+
+        let mut removed = Vec::new();
+        while backstore.replacement_progress().is_some() {
+            sleep(Duration::from_millis(100));
+            removed.extend(backstore.check_replacement(pool_uuid).unwrap());
+        }
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].uuid(), first);
+        wipe_blockdevs(&mut removed).unwrap();
+        invariant(&backstore);
+
+        assert!(!backstore.data_tier.segments.uuids().contains(&first));
+        assert_eq!(backstore.data_tier.stripes.len(), 1);
+        assert!(stripe_table(&backstore.data_tier.segments, &backstore.data_tier.stripes).is_ok());
+
+        let mut buf = vec![0u8; 4096];
+        {
+            let f = OpenOptions::new()
+                .read(true)
+                .open(backstore.linear.as_ref().unwrap().devnode())
+                .unwrap();
+            f.read_exact_at(&mut buf, 0).unwrap();
+        }
+        assert_eq!(buf, bytes);
+
+        backstore.destroy(pool_uuid).unwrap();
+    }
+
+    #[test]
+    fn loop_test_striped_removal() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(3, 4, None),
+            test_striped_removal,
+        );
+    }
+
+    #[test]
+    fn real_test_striped_removal() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(3, None, None),
+            test_striped_removal,
+        );
+    }
+
+    /// Wait until every leg of the raid device of the backstore is in sync.
+    fn wait_for_raid_sync(backstore: &Backstore) {
+        let raid = backstore.raid.as_ref().unwrap();
+        for _ in 0..600 {
+            if raid.in_sync(None).unwrap() {
+                return;
+            }
+            sleep(Duration::from_millis(100));
+        }
+        panic!("raid device did not synchronize within one minute");
+    }
+
+    /// Make a backstore with redundancy raid1 on two blockdevs, write some
+    /// data, and remove the blockdev that holds the second copy, so that
+    /// the copy is rebuilt on a third blockdev. Then set the backstore up
+    /// again without the blockdev that holds the first copy and verify that
+    /// it is degraded, that no space can be allocated, and that the data can
+    /// be read from the rebuilt copy. Finally, replace the absent blockdev.
+    fn test_raid1_rebuild(paths: &[&Path]) {
+        assert!(paths.len() > 2);
+
+        let (paths1, paths2) = paths.split_at(2);
+
+        let pool_uuid = PoolUuid::new_v4();
+        let pool_name = Name::new("pool_name".to_string());
+
+        let mut backstore = Backstore::initialize(
+            pool_name.clone(),
+            pool_uuid,
+            get_devices(paths1).unwrap(),
+            MDADataSize::default(),
+            None,
+            None,
+            None,
+            Redundancy::Raid1,
+        )
+        .unwrap();
+
+        let transaction = backstore
+            .request_alloc(&[INITIAL_BACKSTORE_ALLOCATION])
+            .unwrap()
+            .unwrap();
+        backstore.commit_alloc(pool_uuid, transaction).unwrap();
+        invariant(&backstore);
+
+        let bytes = vec![0xa5u8; 4096];
+        {
+            let f = OpenOptions::new()
+                .write(true)
+                .open(backstore.linear.as_ref().unwrap().devnode())
+                .unwrap();
+            f.write_all_at(&bytes, 0).unwrap();
+            f.sync_all().unwrap();
+        }
+
+        backstore
+            .add_datadevs(
+                pool_name.clone(),
+                pool_uuid,
+                get_devices(paths2).unwrap(),
+                None,
+            )
+            .unwrap();
+        invariant(&backstore);
+
+        let first = *backstore.data_tier.segments.uuids().iter().next().unwrap();
+        let second = *backstore.data_tier.other_legs[0]
+            .uuids()
+            .iter()
+            .next()
+            .unwrap();
+
+        wait_for_raid_sync(&backstore);
+        let mut removed = backstore.remove_datadevs(pool_uuid, &[second]).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].uuid(), second);
+        wipe_blockdevs(&mut removed).unwrap();
+        invariant(&backstore);
+        assert!(!backstore.data_tier.other_legs[0].uuids().contains(&second));
+        wait_for_raid_sync(&backstore);
+
+        let save = backstore.record();
+        backstore.teardown(pool_uuid).unwrap();
+        let (mut absent, datadevs): (Vec<_>, Vec<_>) = backstore
+            .drain_bds()
+            .into_iter()
+            .partition(|bd| bd.uuid() == first);
+        let absent_path = absent[0].devnode().to_path_buf();
+
+        let mut backstore =
+            Backstore::setup(pool_uuid, &save, datadevs, vec![], Utc::now()).unwrap();
+        invariant(&backstore);
+        assert!(backstore.is_absent_datadev(first));
+        assert!(backstore.is_degraded().unwrap());
+        assert!(backstore
+            .request_alloc(&[INITIAL_BACKSTORE_ALLOCATION])
+            .is_err());
+
+        let mut read = vec![0u8; bytes.len()];
+        cap_read(&backstore, &mut read);
+        assert_eq!(read, bytes);
+
+        wipe_blockdevs(&mut absent).unwrap();
+        let (new, replaced) = backstore
+            .rebuild_replacement(
+                pool_name,
+                pool_uuid,
+                first,
+                get_devices(&[absent_path.as_path()]).unwrap(),
+                None,
+            )
+            .unwrap();
+        assert!(replaced.is_none());
+        invariant(&backstore);
+        assert!(!backstore.is_absent_datadev(first));
+        assert!(backstore.data_tier.segments.uuids().contains(&new));
+        wait_for_raid_sync(&backstore);
+        assert!(!backstore.is_degraded().unwrap());
+
+        backstore.destroy(pool_uuid).unwrap();
+    }
+
+    /// Read from the start of the cap device of the backstore.
+    fn cap_read(backstore: &Backstore, buf: &mut [u8]) {
+        OpenOptions::new()
+            .read(true)
+            .open(backstore.linear.as_ref().unwrap().devnode())
+            .unwrap()
+            .read_exact_at(buf, 0)
+            .unwrap();
+    }
+
+    #[test]
+    fn loop_test_raid1_rebuild() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(3, None),
+            test_raid1_rebuild,
+        );
+    }
+
+    #[test]
+    fn real_test_raid1_rebuild() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(3, None, None),
+            test_raid1_rebuild,
+        );
+    }
+
+    /// Allocate two ranges from the backstore and verify that only the
+    /// second can be released, that releasing it returns the space to the
+    /// data tier, and that the space can then be allocated again.
+    fn test_release_alloc(paths: &[&Path]) {
+        let pool_uuid = PoolUuid::new_v4();
+        let pool_name = Name::new("pool_name".to_string());
+
+        let mut backstore = Backstore::initialize(
+            pool_name,
+            pool_uuid,
+            get_devices(paths).unwrap(),
+            MDADataSize::default(),
+            None,
+            None,
+            None,
+            Redundancy::None,
+        )
+        .unwrap();
+
+        let transaction = backstore
+            .request_alloc(&[INITIAL_BACKSTORE_ALLOCATION, INITIAL_BACKSTORE_ALLOCATION])
+            .unwrap()
+            .unwrap();
+        let ranges = transaction.get_backstore();
+        backstore.commit_alloc(pool_uuid, transaction).unwrap();
+
+        let available = backstore.available_in_backstore();
+
+        assert_matches!(backstore.release_alloc(ranges[0]), Err(_));
+        backstore.release_alloc(ranges[1]).unwrap();
+
+        invariant(&backstore);
+
+        assert_eq!(backstore.allocated_in_cap(), INITIAL_BACKSTORE_ALLOCATION);
+        assert_eq!(
+            backstore.available_in_backstore(),
+            available + INITIAL_BACKSTORE_ALLOCATION
+        );
+        assert_eq!(
+            backstore.datatier_allocated_size(),
+            INITIAL_BACKSTORE_ALLOCATION
+        );
+
+        let transaction = backstore
+            .request_alloc(&[INITIAL_BACKSTORE_ALLOCATION])
+            .unwrap()
+            .unwrap();
+        assert_eq!(transaction.get_backstore(), vec![ranges[1]]);
+        backstore.commit_alloc(pool_uuid, transaction).unwrap();
+
+        invariant(&backstore);
+
+        backstore.destroy(pool_uuid).unwrap();
+    }
+
+    #[test]
+    fn loop_test_release_alloc() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_release_alloc,
+        );
+    }
+
+    #[test]
+    fn real_test_release_alloc() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_release_alloc,
+        );
+    }
+
     fn test_clevis_initialize(paths: &[&Path]) {
         unshare_mount_namespace().unwrap();
 
@@ -1349,7 +3211,8 @@ mod tests {
                 "tang".to_string(),
                 json!({"url": env::var("TANG_URL").expect("TANG_URL env var required"), "stratis:tang:trust_url": true}),
             ))),
-        )
+            None, None,
+            Redundancy::None,)
         .unwrap();
         cmd::udev_settle().unwrap();
 
@@ -1417,7 +3280,8 @@ mod tests {
                         json!({"url": env::var("TANG_URL").expect("TANG_URL env var required"), "stratis:tang:trust_url": true}),
                     ),
                 )),
-            ).unwrap();
+                None, None,
+                Redundancy::None,).unwrap();
             cmd::udev_settle().unwrap();
 
             if backstore.bind_clevis(
@@ -8,7 +8,7 @@ use std::{
     cmp::Ordering,
     fmt,
     fs::{File, OpenOptions},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use chrono::{DateTime, Utc};
@@ -23,7 +23,8 @@ use crate::{
         strat_engine::{
             backstore::{
                 crypt::CryptHandle,
-                devices::BlockSizes,
+                devices::{get_devno_from_path, BlockSizes},
+                integrity::{integrity_meta_size, IntegrityDev},
                 range_alloc::{PerDevSegments, RangeAllocator},
                 transaction::RequestTransaction,
             },
@@ -32,12 +33,13 @@ use crate::{
                 disown_device, static_header, BDAExtendedSize, BlockdevSize, MDADataSize,
                 MetadataLocation, StaticHeader, BDA,
             },
-            serde_structs::{BaseBlockDevSave, Recordable},
+            serde_structs::{BaseBlockDevSave, IntegritySave, Recordable},
             types::BDAResult,
+            writing::copy_sectors,
         },
         types::{
-            Compare, DevUuid, DevicePath, Diff, EncryptionInfo, KeyDescription, Name, PoolUuid,
-            StateDiff, StratBlockDevDiff,
+            ActionAvailability, Compare, CryptParams, DevUuid, DevicePath, Diff, EncryptionInfo,
+            IntegrityHash, KeyDescription, Name, PoolUuid, StateDiff, StratBlockDevDiff,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -109,6 +111,9 @@ pub struct StratBlockDev {
     underlying_device: UnderlyingDevice,
     new_size: Option<Sectors>,
     blksizes: StratSectorSizes,
+    reencryption_progress: Option<u8>,
+    integrity: Option<IntegrityDev>,
+    integrity_errors: Option<u64>,
 }
 
 impl StratBlockDev {
@@ -183,6 +188,24 @@ impl StratBlockDev {
             },
         };
 
+        // An online encryption or reencryption that was interrupted by a
+        // restart is resumed by CryptHandle::continue_reencryption().
+        let reencryption_progress = match underlying_device.crypt_handle() {
+            Some(handle) => match handle.is_reencrypting() {
+                Ok(true) => Some(0),
+                Ok(false) => None,
+                Err(e) => {
+                    warn!(
+                        "Failed to determine whether device {} is being reencrypted; assuming that it is: {}",
+                        handle.luks2_device_path().display(),
+                        e,
+                    );
+                    Some(0)
+                }
+            },
+            None => None,
+        };
+
         Ok(StratBlockDev {
             dev,
             bda,
@@ -192,6 +215,9 @@ impl StratBlockDev {
             underlying_device,
             new_size: None,
             blksizes,
+            reencryption_progress,
+            integrity: None,
+            integrity_errors: None,
         })
     }
 
@@ -205,6 +231,32 @@ impl StratBlockDev {
         &self.dev
     }
 
+    /// Returns the Device from which the segments allocated on the blockdev
+    /// are mapped. This is the integrity device if the blockdev has
+    /// integrity protection and is not encrypted, otherwise it is the same
+    /// as device(); the integrity device of an encrypted blockdev lies
+    /// beneath the encryption.
+    pub fn data_device(&self) -> Device {
+        match self.integrity {
+            Some(ref integrity) if self.underlying_device.crypt_handle().is_none() => {
+                integrity.device()
+            }
+            _ => self.dev,
+        }
+    }
+
+    /// Returns the path of the device returned by data_device(). Data must
+    /// only be copied to or from the segments allocated on the blockdev
+    /// through this path.
+    pub fn data_path(&self) -> PathBuf {
+        match self.integrity {
+            Some(ref integrity) if self.underlying_device.crypt_handle().is_none() => {
+                integrity.devnode()
+            }
+            _ => self.metadata_path().to_owned(),
+        }
+    }
+
     /// Returns the LUKS2 device's Device if encrypted
     pub fn luks_device(&self) -> Option<&Device> {
         self.underlying_device.crypt_handle().map(|ch| ch.device())
@@ -237,8 +289,12 @@ impl StratBlockDev {
     ///               aes-xts-plain64 encryption.
     pub fn disown(&mut self) -> StratisResult<()> {
         if let Some(ref mut handle) = self.underlying_device.crypt_handle_mut() {
+            // Wiping the LUKS2 header also removes the integrity device
+            // beneath the encryption, if any.
             handle.wipe()?;
+            self.integrity = None;
         } else {
+            self.teardown_integrity()?;
             disown_device(
                 &mut OpenOptions::new()
                     .write(true)
@@ -275,6 +331,16 @@ impl StratBlockDev {
         self.used.request(self.uuid(), size, transaction)
     }
 
+    /// Find a single sector range of the specified size that could be
+    /// allocated. Return None if there is no such range.
+    pub fn request_contiguous_space(
+        &self,
+        size: Sectors,
+        transaction: &RequestTransaction,
+    ) -> StratisResult<Option<Sectors>> {
+        self.used.request_contiguous(self.uuid(), size, transaction)
+    }
+
     /// Commit allocation requested by request_space().
     ///
     /// This method will record the requested allocations in the metadata.
@@ -282,6 +348,12 @@ impl StratBlockDev {
         self.used.commit(segs);
     }
 
+    /// Return a range previously allocated by commit_space(), so that it
+    /// may be allocated again.
+    pub fn release_space(&mut self, range: &(Sectors, Sectors)) -> StratisResult<()> {
+        self.used.release(range)
+    }
+
     // ALL SIZE METHODS (except size(), which is in BlockDev impl.)
     /// The number of Sectors on this device used by Stratis for metadata
     pub fn metadata_size(&self) -> BDAExtendedSize {
@@ -330,6 +402,15 @@ impl StratBlockDev {
             .map(|ch| ch.encryption_info())
     }
 
+    /// Get the crypt parameters read from the LUKS2 header of the given
+    /// encrypted blockdev.
+    pub fn crypt_params(&self) -> StratisResult<Option<CryptParams>> {
+        self.underlying_device
+            .crypt_handle()
+            .map(|ch| ch.crypt_params())
+            .transpose()
+    }
+
     /// Get the pool name for the given block device.
     ///
     /// Returns:
@@ -345,6 +426,15 @@ impl StratBlockDev {
             .map(|ch| ch.pool_name())
     }
 
+    /// True if the blockdev is encrypted and its LUKS2 metadata records that
+    /// the pool was stopped on purpose.
+    pub fn stopped(&self) -> bool {
+        self.underlying_device
+            .crypt_handle()
+            .map(|ch| ch.stopped())
+            .unwrap_or(false)
+    }
+
     /// Block size information
     pub fn blksizes(&self) -> StratSectorSizes {
         self.blksizes
@@ -368,11 +458,15 @@ impl StratBlockDev {
 
     /// Bind a block device to a passphrase represented by a key description
     /// in the kernel keyring.
-    pub fn bind_keyring(&mut self, key_desc: &KeyDescription) -> StratisResult<()> {
+    pub fn bind_keyring(
+        &mut self,
+        key_desc: &KeyDescription,
+        pbkdf_params: Option<&CryptParams>,
+    ) -> StratisResult<()> {
         let crypt_handle = self.underlying_device.crypt_handle_mut().ok_or_else(|| {
             StratisError::Msg("This device does not appear to be encrypted".to_string())
         })?;
-        crypt_handle.bind_keyring(key_desc)
+        crypt_handle.bind_keyring(key_desc, pbkdf_params)
     }
 
     /// Unbind a block device from a passphrase represented by a key description
@@ -386,11 +480,54 @@ impl StratBlockDev {
 
     /// Change the passphrase for a block device to a passphrase represented by a
     /// key description in the kernel keyring.
-    pub fn rebind_keyring(&mut self, key_desc: &KeyDescription) -> StratisResult<()> {
+    pub fn rebind_keyring(
+        &mut self,
+        key_desc: &KeyDescription,
+        pbkdf_params: Option<&CryptParams>,
+    ) -> StratisResult<()> {
+        let crypt_handle = self.underlying_device.crypt_handle_mut().ok_or_else(|| {
+            StratisError::Msg("This device does not appear to be encrypted".to_string())
+        })?;
+        crypt_handle.rebind_keyring(key_desc, pbkdf_params)
+    }
+
+    /// Get the key descriptions of the keyring bindings of the block device
+    /// in addition to the one in its encryption info. Empty if the block
+    /// device is not encrypted.
+    pub fn keyring_bindings(&self) -> Vec<KeyDescription> {
+        self.underlying_device
+            .crypt_handle()
+            .map(|ch| ch.keyring_bindings())
+            .unwrap_or_default()
+    }
+
+    /// Bind a block device to an additional passphrase represented by a key
+    /// description in the kernel keyring.
+    pub fn add_keyring_binding(
+        &mut self,
+        key_desc: &KeyDescription,
+        pbkdf_params: Option<&CryptParams>,
+    ) -> StratisResult<()> {
+        let crypt_handle = self.underlying_device.crypt_handle_mut().ok_or_else(|| {
+            StratisError::Msg("This device does not appear to be encrypted".to_string())
+        })?;
+        crypt_handle.add_keyring_binding(key_desc, pbkdf_params)
+    }
+
+    /// Remove an additional keyring binding from a block device.
+    pub fn remove_keyring_binding(&mut self, key_desc: &KeyDescription) -> StratisResult<()> {
         let crypt_handle = self.underlying_device.crypt_handle_mut().ok_or_else(|| {
             StratisError::Msg("This device does not appear to be encrypted".to_string())
         })?;
-        crypt_handle.rebind_keyring(key_desc)
+        crypt_handle.remove_keyring_binding(key_desc)
+    }
+
+    /// Destroy every keyslot of an encrypted block device.
+    pub fn erase_keyslots(&self) -> StratisResult<()> {
+        let crypt_handle = self.underlying_device.crypt_handle().ok_or_else(|| {
+            StratisError::Msg("This device does not appear to be encrypted".to_string())
+        })?;
+        crypt_handle.erase_keyslots()
     }
 
     /// Regenerate the Clevis bindings for a block device.
@@ -406,21 +543,36 @@ impl StratBlockDev {
     /// Returns:
     /// * `None` if the size hasn't changed or is equal to the current size recorded
     /// in the metadata.
-    /// * Otherwise, `Some(_)`
+    /// * Otherwise, `Some(_)`, which may be less than the size recorded in
+    /// the metadata if the device has shrunk.
     pub fn calc_new_size(&self) -> StratisResult<Option<Sectors>> {
-        let s = Self::scan_blkdev_size(
-            self.physical_path(),
-            self.underlying_device.crypt_handle().is_some(),
-        )?;
+        let s = self.scan_size()?;
         if Some(s) == self.new_size
             || (self.new_size.is_none() && s == self.bda.dev_size().sectors())
         {
             Ok(None)
         } else {
+            if s < self.bda.dev_size().sectors() {
+                warn!(
+                    "The device with path: {}, UUID: {} has shrunk from {} to {}",
+                    self.devnode().display(),
+                    self.bda.dev_uuid(),
+                    self.bda.dev_size().sectors(),
+                    s,
+                );
+            }
             Ok(Some(s))
         }
     }
 
+    /// Whether the newly detected size of the block device is less than the
+    /// size recorded in the metadata.
+    pub fn is_shrunk(&self) -> bool {
+        self.new_size
+            .map(|s| s < self.bda.dev_size().sectors())
+            .unwrap_or(false)
+    }
+
     /// Scan the block device specified by physical_path for its size.
     pub fn scan_blkdev_size(physical_path: &Path, is_encrypted: bool) -> StratisResult<Sectors> {
         Ok(blkdev_size(&File::open(physical_path)?)?.sectors()
@@ -431,6 +583,27 @@ impl StratBlockDev {
             })
     }
 
+    /// Scan the physical device for the size that the block device would have
+    /// on it. On an encrypted block device with integrity protection beneath
+    /// the encryption, the integrity tags at the end of the physical device
+    /// are not part of the block device.
+    fn scan_size(&self) -> StratisResult<Sectors> {
+        match self
+            .underlying_device
+            .crypt_handle()
+            .and_then(|handle| handle.integrity())
+        {
+            Some(integrity) => Ok(Sectors(
+                Self::scan_blkdev_size(self.physical_path(), true)?
+                    .saturating_sub(*integrity.meta_length),
+            )),
+            None => Self::scan_blkdev_size(
+                self.physical_path(),
+                self.underlying_device.crypt_handle().is_some(),
+            ),
+        }
+    }
+
     /// Set the newly detected size of a block device.
     pub fn set_new_size(&mut self, new_size: Sectors) {
         match self.bda.dev_size().cmp(&BlockdevSize::new(new_size)) {
@@ -498,11 +671,11 @@ impl StratBlockDev {
             }
         }
 
-        let size = BlockdevSize::new(Self::scan_blkdev_size(
-            self.physical_path(),
-            self.underlying_device.crypt_handle().is_some(),
-        )?);
+        let size = BlockdevSize::new(self.scan_size()?);
         let metadata_size = self.bda.dev_size();
+        if size > metadata_size {
+            self.check_no_integrity("grown")?;
+        }
         match size.cmp(&metadata_size) {
             Ordering::Less => Err(StratisError::Msg(
                 "The underlying device appears to have shrunk; you may experience data loss"
@@ -532,6 +705,445 @@ impl StratBlockDev {
         }
     }
 
+    /// Record the given size of the block device in the static header on
+    /// the device at the metadata path.
+    fn write_blkdev_size(&self, size: BlockdevSize) -> StratisResult<StaticHeader> {
+        let mut f = OpenOptions::new()
+            .write(true)
+            .read(true)
+            .open(self.metadata_path())?;
+        let mut h = static_header(&mut f)?.ok_or_else(|| {
+            StratisError::Msg(format!(
+                "No static header found on device {}",
+                self.metadata_path().display()
+            ))
+        })?;
+
+        h.blkdev_size = size;
+        StaticHeader::write_header(&mut f, h, MetadataLocation::Both)
+    }
+
+    /// Shrink the block device to the newly detected size of the underlying
+    /// physical device. Return an error and leave the size as is if any
+    /// sectors allocated on the device lie past the new size.
+    /// Do nothing if the device has not shrunk.
+    pub fn shrink(&mut self) -> StratisResult<bool> {
+        /// Precondition: size < h.blkdev_size
+        fn write_size(bd: &StratBlockDev, size: BlockdevSize) -> StratisResult<StaticHeader> {
+            if let Some(h) = bd.underlying_device.crypt_handle() {
+                h.resize(Some(size.sectors()))?;
+            }
+
+            bd.write_blkdev_size(size)
+        }
+
+        let size = match self.new_size {
+            Some(s) if self.is_shrunk() => BlockdevSize::new(s),
+            _ => return Ok(false),
+        };
+        self.check_no_integrity("shrunk")?;
+        let metadata_size = self.bda.dev_size();
+
+        self.used.decrease_size(size.sectors())?;
+
+        match write_size(self, size) {
+            Ok(h) => {
+                self.bda.header = h;
+                self.new_size = None;
+                Ok(true)
+            }
+            Err(e) => {
+                self.used.increase_size(metadata_size.sectors());
+                Err(e)
+            }
+        }
+    }
+
+    /// Encrypt the block device online. The device is formatted with a LUKS2
+    /// header and activated; the activated device presents the data, including
+    /// the Stratis BDA, at the offsets at which it was found on the
+    /// unencrypted device, so that it takes the place of the physical device
+    /// in the cap device. The data is encrypted in the background; see
+    /// reencryption_handle().
+    /// while the device is in use.
+    ///
+    /// The LUKS2 header takes up crypt_metadata_size() of the device, so the
+    /// size of the block device is reduced accordingly; an error is returned
+    /// if any sectors allocated on the device lie past the reduced size. The
+    /// first crypt_metadata_size() bytes of the physical device, which are
+    /// overwritten by the LUKS2 header, are saved to the file head, so that
+    /// the operation can be rolled back by rollback_encryption().
+    ///
+    /// If the block device has integrity protection, the integrity device
+    /// becomes the layer beneath the encryption, and the region holding the
+    /// integrity tags is no longer part of the block device.
+    ///
+    /// Precondition: the physical device is not in use, i.e. the cap device
+    /// is suspended, and crypt_params specifies the sector size.
+    pub fn encrypt(
+        &mut self,
+        pool_name: Name,
+        encryption_info: &EncryptionInfo,
+        crypt_params: &CryptParams,
+        head: &Path,
+    ) -> StratisResult<()> {
+        let physical_path = match self.underlying_device {
+            UnderlyingDevice::Encrypted(_) => {
+                return Err(StratisError::Msg(format!(
+                    "Device {} is already encrypted",
+                    self.devnode().display()
+                )));
+            }
+            UnderlyingDevice::Unencrypted(ref path) => path.clone(),
+        };
+
+        // The data of a block device with integrity protection is encrypted
+        // through its integrity device, which ends where the integrity tags
+        // begin; the tags then lie beneath the encryption and are no longer
+        // allocated from the block device.
+        let integrity = self.integrity.as_ref().map(|integrity| integrity.record());
+        let data_path = self.data_path();
+        let metadata_size = self.bda.dev_size();
+        let data_size = integrity
+            .as_ref()
+            .map(|save| save.meta_start)
+            .unwrap_or_else(|| metadata_size.sectors());
+        let size = BlockdevSize::new(data_size - crypt_metadata_size().sectors());
+        if let Some(ref save) = integrity {
+            self.used.release(&(save.meta_start, save.meta_length))?;
+        }
+        if let Err(e) = self.used.decrease_size(size.sectors()) {
+            self.allocate_integrity_tags()?;
+            return Err(StratisError::Chained(
+                format!(
+                    "The last {} of the data on device {} must be unallocated to make room for the LUKS2 header",
+                    crypt_metadata_size(),
+                    physical_path.display()
+                ),
+                Box::new(e),
+            ));
+        }
+
+        File::create(head)?;
+        let head_length = crypt_metadata_size().sectors();
+        if let Err(e) = copy_sectors(&*physical_path, Sectors(0), head, Sectors(0), head_length) {
+            self.used.increase_size(metadata_size.sectors());
+            self.allocate_integrity_tags()?;
+            return Err(e);
+        }
+
+        let handle = match CryptHandle::initialize_online(
+            &physical_path,
+            self.pool_uuid(),
+            self.uuid(),
+            pool_name,
+            encryption_info,
+            crypt_params,
+            integrity.as_ref(),
+        ) {
+            Ok(handle) => handle,
+            Err(e) => {
+                if let Err(rollback_err) =
+                    copy_sectors(head, Sectors(0), &data_path, Sectors(0), head_length)
+                {
+                    return Err(StratisError::RollbackError {
+                        causal_error: Box::new(e),
+                        rollback_error: Box::new(rollback_err),
+                        level: ActionAvailability::NoRequests,
+                    });
+                }
+                self.used.increase_size(metadata_size.sectors());
+                self.allocate_integrity_tags()?;
+                return Err(e);
+            }
+        };
+
+        self.underlying_device = UnderlyingDevice::Encrypted(handle);
+        let result = File::open(self.metadata_path())
+            .map_err(StratisError::from)
+            .and_then(|f| BlockSizes::read(&f))
+            .and_then(|crypt_blksizes| {
+                let devno = get_devno_from_path(self.metadata_path())?;
+                let header = self.write_blkdev_size(size)?;
+                Ok((crypt_blksizes, devno, header))
+            });
+        match result {
+            Ok((crypt_blksizes, devno, header)) => {
+                self.bda.header = header;
+                self.dev = devno;
+                self.blksizes.crypt = Some(crypt_blksizes);
+                self.reencryption_progress = Some(0);
+                Ok(())
+            }
+            Err(e) => match self.rollback_encryption(head) {
+                Ok(()) => Err(e),
+                Err(rollback_err) => Err(StratisError::RollbackError {
+                    causal_error: Box::new(e),
+                    rollback_error: Box::new(rollback_err),
+                    level: ActionAvailability::NoRequests,
+                }),
+            },
+        }
+    }
+
+    /// Roll back an online encryption started by encrypt() by deactivating
+    /// the encrypted device and restoring the head of the physical device
+    /// from the file head. This is only possible while no data has been
+    /// encrypted beyond the first chunk, i.e. before any data has been
+    /// processed with reencryption_handle().
+    ///
+    /// Precondition: the activated device is not in use, i.e. the cap device
+    /// is suspended.
+    pub fn rollback_encryption(&mut self, head: &Path) -> StratisResult<()> {
+        let physical_path = self.devnode().to_owned();
+        if let Some(handle) = self.underlying_device.crypt_handle() {
+            handle.deactivate_encryption()?;
+        }
+
+        // The head of a device with integrity protection is restored through
+        // the integrity device, so that the data in it can be read again.
+        let head_path = self
+            .integrity
+            .as_ref()
+            .map(|integrity| integrity.devnode())
+            .unwrap_or_else(|| physical_path.clone());
+        copy_sectors(
+            head,
+            Sectors(0),
+            &head_path,
+            Sectors(0),
+            crypt_metadata_size().sectors(),
+        )?;
+
+        let mut f = OpenOptions::new().read(true).open(&physical_path)?;
+        let header = static_header(&mut f)?.ok_or_else(|| {
+            StratisError::Msg(format!(
+                "No static header found on device {} after restoring it",
+                physical_path.display()
+            ))
+        })?;
+
+        self.dev = get_devno_from_path(&physical_path)?;
+        self.used.increase_size(header.blkdev_size.sectors());
+        self.allocate_integrity_tags()?;
+        self.bda.header = header;
+        self.underlying_device = UnderlyingDevice::Unencrypted(DevicePath::new(&physical_path)?);
+        self.blksizes.crypt = None;
+        self.reencryption_progress = None;
+        Ok(())
+    }
+
+    /// Start the online reencryption of the block device with a new volume
+    /// key, used with the cipher and the key size in the crypt parameters, if
+    /// specified. The data is reencrypted in the background while the device
+    /// is in use; see reencryption_handle().
+    pub fn start_reencryption(&mut self, crypt_params: &CryptParams) -> StratisResult<()> {
+        if self.reencryption_progress.is_some() {
+            return Err(StratisError::Msg(format!(
+                "Device {} is already being reencrypted",
+                self.devnode().display()
+            )));
+        }
+        let handle = self.underlying_device.crypt_handle().ok_or_else(|| {
+            StratisError::Msg("This device does not appear to be encrypted".to_string())
+        })?;
+
+        handle.start_reencryption(crypt_params)?;
+        self.reencryption_progress = Some(0);
+        Ok(())
+    }
+
+    /// A handle of the encrypted device with which the online encryption or
+    /// reencryption of the block device is continued, if one is in progress.
+    /// The data is processed with CryptHandle::continue_reencryption(), which
+    /// does not require access to the block device, so that it can be done
+    /// without holding the lock on the pool; the progress is then recorded
+    /// by record_reencryption().
+    pub fn reencryption_handle(&self) -> Option<CryptHandle> {
+        self.reencryption_progress
+            .and(self.underlying_device.crypt_handle().cloned())
+    }
+
+    /// Record the progress of the online encryption or reencryption of the
+    /// block device, as returned by CryptHandle::continue_reencryption().
+    ///
+    /// Returns true if the reencryption has been completed.
+    pub fn record_reencryption(&mut self, progress: Option<u8>) -> bool {
+        if self.reencryption_progress.is_none() {
+            return false;
+        }
+
+        match progress {
+            Some(progress) if progress < 100 => {
+                self.reencryption_progress = Some(progress);
+                false
+            }
+            _ => {
+                info!(
+                    "Completed the reencryption of device {}",
+                    self.devnode().display()
+                );
+                self.reencryption_progress = None;
+                true
+            }
+        }
+    }
+
+    /// The percentage of the block device that has been processed, if an
+    /// online encryption or reencryption of the block device is in progress.
+    pub fn reencryption_progress(&self) -> Option<u8> {
+        self.reencryption_progress
+    }
+
+    /// Add integrity protection with the given hash to a newly initialized
+    /// block device. The integrity tags are kept at the end of the device,
+    /// the space for them is allocated from the device, and the tags of
+    /// the data already on the device are calculated in the background.
+    ///
+    /// The integrity device of an encrypted block device is set up beneath
+    /// the encryption when the encrypted device is initialized; it is only
+    /// taken over here.
+    ///
+    /// Returns an error if any space other than that for the Stratis
+    /// metadata has already been allocated from the device.
+    pub fn init_integrity(&mut self, hash: IntegrityHash) -> StratisResult<()> {
+        if self.in_use() || self.integrity.is_some() {
+            return Err(StratisError::Msg(format!(
+                "Integrity protection can only be added to device {} before it is used",
+                self.devnode().display()
+            )));
+        }
+
+        if let Some(handle) = self.underlying_device.crypt_handle() {
+            return match handle.integrity().cloned() {
+                Some(save) if save.hash == hash => self.setup_integrity(&save),
+                _ => Err(StratisError::Msg(format!(
+                    "Integrity protection with hash {} was not set up beneath the encryption of device {}",
+                    hash,
+                    self.devnode().display()
+                ))),
+            };
+        }
+
+        let size = self.total_size().sectors();
+        let meta_length = integrity_meta_size(size, hash);
+        if meta_length >= self.available() {
+            return Err(StratisError::Msg(format!(
+                "Device {} is too small to hold the {} of integrity tags for its data",
+                self.devnode().display(),
+                meta_length
+            )));
+        }
+        let meta_start = size - meta_length;
+
+        let integrity =
+            IntegrityDev::setup(self.uuid(), self.dev, hash, meta_start, meta_length, true)?;
+        let mut segs = PerDevSegments::new(size);
+        segs.insert(&(meta_start, meta_length))?;
+        self.used.commit(segs);
+
+        self.integrity = Some(integrity);
+        self.integrity_errors = Some(0);
+        Ok(())
+    }
+
+    /// Set up the integrity protection of the block device recorded in the
+    /// metadata, or, if the block device is encrypted, in the Stratis LUKS2
+    /// token. The integrity device beneath the encryption has already been
+    /// set up in order to unlock the device and is taken over.
+    ///
+    /// Precondition: the region holding the integrity tags of an unencrypted
+    /// block device was allocated when the block device was constructed.
+    pub fn setup_integrity(&mut self, save: &IntegritySave) -> StratisResult<()> {
+        let base = self.luks_device().copied().unwrap_or(self.dev);
+        let integrity = IntegrityDev::setup(
+            self.uuid(),
+            base,
+            save.hash,
+            save.meta_start,
+            save.meta_length,
+            false,
+        )?;
+        self.integrity = Some(integrity);
+        self.integrity_errors = Some(0);
+        Ok(())
+    }
+
+    /// The hash with which the data on the block device is checked, if the
+    /// block device has integrity protection.
+    pub fn integrity_hash(&self) -> Option<IntegrityHash> {
+        self.integrity.as_ref().map(|integrity| integrity.hash())
+    }
+
+    /// The number of reads from the block device that have failed because
+    /// the data did not match its integrity tag since the pool was started,
+    /// as of the last call to check_integrity(). None if the block device
+    /// has no integrity protection.
+    pub fn integrity_errors(&self) -> Option<u64> {
+        self.integrity_errors
+    }
+
+    /// Refresh the number of integrity errors from the kernel. Returns
+    /// true if it has changed.
+    pub fn check_integrity(&mut self) -> StratisResult<bool> {
+        let errors = match self.integrity {
+            Some(ref integrity) => Some(integrity.mismatches()?),
+            None => None,
+        };
+        if errors != self.integrity_errors {
+            if let Some(errors) = errors {
+                warn!(
+                    "The data read from device {} has failed its integrity check {} times",
+                    self.devnode().display(),
+                    errors
+                );
+            }
+            self.integrity_errors = errors;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Refuse an operation that changes the location of the data on the
+    /// block device if the block device has integrity protection.
+    fn check_no_integrity(&self, operation: &str) -> StratisResult<()> {
+        if self.integrity.is_some() {
+            Err(StratisError::Msg(format!(
+                "Device {} has integrity protection; it can not be {}",
+                self.devnode().display(),
+                operation
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Allocate the region holding the integrity tags of an unencrypted block
+    /// device with integrity protection, if any, again after its online
+    /// encryption has failed or has been rolled back.
+    fn allocate_integrity_tags(&mut self) -> StratisResult<()> {
+        if let Some(save) = self.integrity.as_ref().map(|integrity| integrity.record()) {
+            let mut segs = PerDevSegments::new(self.used.size().sectors());
+            segs.insert(&(save.meta_start, save.meta_length))?;
+            self.used.commit(segs);
+        }
+        Ok(())
+    }
+
+    /// Tear down the integrity device of the block device, if any.
+    fn teardown_integrity(&mut self) -> StratisResult<()> {
+        if let Some(ref mut integrity) = self.integrity {
+            debug!(
+                "Removing integrity device of device with UUID {}",
+                self.bda.dev_uuid()
+            );
+            integrity.teardown()?;
+            self.integrity = None;
+        }
+        Ok(())
+    }
+
     /// Rename pool in metadata if it is encrypted.
     pub fn rename_pool(&mut self, pool_name: Name) -> StratisResult<()> {
         match self.underlying_device.crypt_handle_mut() {
@@ -540,22 +1152,35 @@ impl StratBlockDev {
         }
     }
 
+    /// Record in the metadata whether the pool was stopped on purpose, if it
+    /// is encrypted.
+    pub fn set_stopped(&mut self, stopped: bool) -> StratisResult<()> {
+        match self.underlying_device.crypt_handle_mut() {
+            Some(handle) => handle.set_stopped_in_metadata(stopped),
+            None => Ok(()),
+        }
+    }
+
     #[cfg(test)]
     pub fn invariant(&self) {
         assert!(self.total_size() == self.used.size());
     }
 
-    /// If a pool is encrypted, tear down the cryptsetup devicemapper devices on the
-    /// physical device.
+    /// Tear down the integrity device, if any, and, if a pool is encrypted, the
+    /// cryptsetup devicemapper devices on the physical device. The integrity
+    /// device of an encrypted pool lies beneath the encryption and is torn
+    /// down with it.
     pub fn teardown(&mut self) -> StratisResult<()> {
         if let Some(ch) = self.underlying_device.crypt_handle() {
             debug!(
                 "Deactivating unlocked encrypted device with UUID {}",
                 self.bda.dev_uuid()
             );
-            ch.deactivate()
-        } else {
+            ch.deactivate()?;
+            self.integrity = None;
             Ok(())
+        } else {
+            self.teardown_integrity()
         }
     }
 }
@@ -579,6 +1204,23 @@ impl<'a> Into<Value> for &'a StratBlockDev {
                 unreachable!("EncryptionInfo conversion returns a JSON object");
             };
         }
+        match self.crypt_params() {
+            Ok(Some(crypt_params)) => {
+                if let Value::Object(params_map) =
+                    <&CryptParams as Into<Value>>::into(&crypt_params)
+                {
+                    map.extend(params_map);
+                } else {
+                    unreachable!("CryptParams conversion returns a JSON object");
+                };
+            }
+            Ok(None) => (),
+            Err(e) => warn!(
+                "Failed to read the crypt parameters of device {}: {}",
+                self.devnode().display(),
+                e
+            ),
+        }
         map.insert("size".to_string(), Value::from(self.size().to_string()));
         if let Some(new_size) = self.new_size {
             map.insert("new_size".to_string(), Value::from(new_size.to_string()));
@@ -588,6 +1230,12 @@ impl<'a> Into<Value> for &'a StratBlockDev {
             Value::from(self.blksizes.to_string()),
         );
         map.insert("in_use".to_string(), Value::from(self.in_use()));
+        if let Some(hash) = self.integrity_hash() {
+            map.insert("integrity".to_string(), Value::from(hash.to_string()));
+        }
+        if let Some(errors) = self.integrity_errors {
+            map.insert("integrity_errors".to_string(), Value::from(errors));
+        }
         json
     }
 }
@@ -624,6 +1272,14 @@ impl BlockDev for StratBlockDev {
     fn new_size(&self) -> Option<Sectors> {
         self.new_size
     }
+
+    fn is_shrunk(&self) -> bool {
+        self.is_shrunk()
+    }
+
+    fn integrity_errors(&self) -> Option<u64> {
+        self.integrity_errors()
+    }
 }
 
 impl Recordable<BaseBlockDevSave> for StratBlockDev {
@@ -632,12 +1288,19 @@ impl Recordable<BaseBlockDevSave> for StratBlockDev {
             uuid: self.uuid(),
             user_info: self.user_info.clone(),
             hardware_info: self.hardware_info.clone(),
+            integrity: self
+                .integrity
+                .as_ref()
+                .filter(|_| self.underlying_device.crypt_handle().is_none())
+                .map(|integrity| integrity.record()),
         }
     }
 }
 
 pub struct StratBlockDevState {
     new_size: Option<Sectors>,
+    shrunk: bool,
+    integrity_errors: Option<u64>,
 }
 
 impl StateDiff for StratBlockDevState {
@@ -646,12 +1309,16 @@ impl StateDiff for StratBlockDevState {
     fn diff(&self, new_state: &Self) -> Self::Diff {
         StratBlockDevDiff {
             size: self.new_size.compare(&new_state.new_size),
+            shrunk: self.shrunk.compare(&new_state.shrunk),
+            integrity_errors: self.integrity_errors.compare(&new_state.integrity_errors),
         }
     }
 
     fn unchanged(&self) -> Self::Diff {
         StratBlockDevDiff {
             size: Diff::Unchanged(self.new_size),
+            shrunk: Diff::Unchanged(self.shrunk),
+            integrity_errors: Diff::Unchanged(self.integrity_errors),
         }
     }
 }
@@ -663,6 +1330,8 @@ impl<'a> DumpState<'a> for StratBlockDev {
     fn cached(&self) -> Self::State {
         StratBlockDevState {
             new_size: self.new_size,
+            shrunk: self.is_shrunk(),
+            integrity_errors: self.integrity_errors,
         }
     }
 
@@ -670,6 +1339,8 @@ impl<'a> DumpState<'a> for StratBlockDev {
         self.set_new_size(input);
         StratBlockDevState {
             new_size: self.new_size,
+            shrunk: self.is_shrunk(),
+            integrity_errors: self.integrity_errors,
         }
     }
 }
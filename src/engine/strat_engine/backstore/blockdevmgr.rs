@@ -4,9 +4,7 @@
 
 // Code to handle a collection of block devices.
 
-use std::collections::HashMap;
-#[cfg(test)]
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use chrono::{DateTime, Duration, Utc};
 use rand::{seq::IteratorRandom, thread_rng};
@@ -29,7 +27,10 @@ use crate::{
             serde_structs::{BaseBlockDevSave, Recordable},
             shared::bds_to_bdas,
         },
-        types::{DevUuid, EncryptionInfo, Name, PoolEncryptionInfo, PoolUuid},
+        types::{
+            CryptParams, DevUuid, EncryptionInfo, IntegrityHash, Name, PoolEncryptionInfo,
+            PoolUuid, Redundancy,
+        },
     },
     stratis::{StratisError, StratisResult},
 };
@@ -58,25 +59,28 @@ impl BlockDevMgr {
     }
 
     /// Initialize a new StratBlockDevMgr with specified pool and devices.
+    /// If encrypted, the devices are formatted with the given crypt
+    /// parameters. If an integrity hash is given, every device is given
+    /// integrity protection with that hash.
     pub fn initialize(
         pool_name: Name,
         pool_uuid: PoolUuid,
         devices: UnownedDevices,
         mda_data_size: MDADataSize,
         encryption_info: Option<&EncryptionInfo>,
-        sector_size: Option<u32>,
+        crypt_params: Option<&CryptParams>,
+        integrity: Option<IntegrityHash>,
     ) -> StratisResult<BlockDevMgr> {
-        Ok(BlockDevMgr::new(
-            initialize_devices(
-                devices,
-                pool_name,
-                pool_uuid,
-                mda_data_size,
-                encryption_info,
-                sector_size,
-            )?,
-            None,
-        ))
+        let bds = initialize_devices(
+            devices,
+            pool_name,
+            pool_uuid,
+            mda_data_size,
+            encryption_info,
+            crypt_params,
+            integrity,
+        )?;
+        Ok(BlockDevMgr::new(bds, None))
     }
 
     /// Convert the BlockDevMgr into a collection of BDAs.
@@ -89,17 +93,23 @@ impl BlockDevMgr {
         self.block_devs.drain(..).collect::<Vec<_>>()
     }
 
-    /// Get a hashmap that maps UUIDs to Devices.
+    /// Get a hashmap that maps UUIDs to the Devices from which segments are
+    /// mapped.
     pub fn uuid_to_devno(&self) -> HashMap<DevUuid, Device> {
         self.block_devs
             .iter()
-            .map(|bd| (bd.uuid(), *bd.device()))
+            .map(|bd| (bd.uuid(), bd.data_device()))
             .collect()
     }
 
     /// Add paths to self.
     /// Return the uuids of all blockdevs corresponding to paths that were
     /// added.
+    ///
+    /// If encrypted, the devices are formatted with the crypt parameters of
+    /// the existing devices and the given sector size. If the existing
+    /// devices have integrity protection, the devices are given integrity
+    /// protection with the same hash.
     pub fn add(
         &mut self,
         pool_name: Name,
@@ -132,6 +142,11 @@ impl BlockDevMgr {
             }
         }
 
+        let crypt_params = self.crypt_params()?.map(|params| CryptParams {
+            sector_size: sector_size.or(params.sector_size),
+            ..params
+        });
+
         // FIXME: This is a bug. If new devices are added to a pool, and the
         // variable length metadata requires more than the minimum allocated,
         // then the necessary amount must be provided or the data can not be
@@ -142,7 +157,8 @@ impl BlockDevMgr {
             pool_uuid,
             MDADataSize::default(),
             encryption_info.as_ref(),
-            sector_size,
+            crypt_params.as_ref(),
+            self.integrity_hash(),
         )?;
         let bdev_uuids = bds.iter().map(|bd| bd.uuid()).collect();
         self.block_devs.extend(bds);
@@ -160,33 +176,46 @@ impl BlockDevMgr {
     /// from them for upper layers.
     ///
     /// If a specified blockdev is not found, returns an error and does nothing.
+    pub(super) fn remove_blockdevs(&mut self, uuids: &[DevUuid]) -> StratisResult<()> {
+        let mut removed = self.take_blockdevs(uuids)?;
+        wipe_blockdevs(&mut removed)?;
+        Ok(())
+    }
+
+    /// Remove the specified block devs from self without erasing their
+    /// metadata and return them.
+    ///
+    /// Precondition: It is the responsibility of the caller to ensure that
+    /// none of the blockdevs are in use, that is, have had any space allocated
+    /// from them for upper layers.
+    ///
+    /// If a specified blockdev is not found, returns an error and does nothing.
     ///
     /// NOTE: This method traverses the block_devs Vec from the rear to the
     /// front, looking for blockdevs to remove. This is algorithmically
     /// inefficient, unless it is assumed that the blockdevs specified are very
     /// near the end of the Vec, which is expected to be the case. In that case,
     /// the algorithm is O(n).
-    pub(super) fn remove_blockdevs(&mut self, uuids: &[DevUuid]) -> StratisResult<()> {
+    pub(super) fn take_blockdevs(
+        &mut self,
+        uuids: &[DevUuid],
+    ) -> StratisResult<Vec<StratBlockDev>> {
+        if let Some(uuid) = uuids
+            .iter()
+            .find(|uuid| !self.block_devs.iter().any(|bd| bd.uuid() == **uuid))
+        {
+            return Err(StratisError::Msg(format!(
+                "Blockdev corresponding to UUID: {uuid} not found."
+            )));
+        }
+
         let mut removed = Vec::new();
         for uuid in uuids {
-            let mut found = false;
-            let blockdevs_last_index = self.block_devs.len() - 1;
-            for i in 0..blockdevs_last_index {
-                let index = blockdevs_last_index - i;
-                if self.block_devs[index].uuid() == *uuid {
-                    removed.push(self.block_devs.swap_remove(index));
-                    found = true;
-                    break;
-                }
-            }
-            if !found {
-                return Err(StratisError::Msg(format!(
-                    "Blockdev corresponding to UUID: {uuid} not found."
-                )));
+            if let Some(index) = self.block_devs.iter().rposition(|bd| bd.uuid() == *uuid) {
+                removed.push(self.block_devs.swap_remove(index));
             }
         }
-        wipe_blockdevs(&mut removed)?;
-        Ok(())
+        Ok(removed)
     }
 
     /// Allocate space according to sizes vector request.
@@ -194,7 +223,121 @@ impl BlockDevMgr {
     /// not possible to satisfy the request.
     /// This method is atomic, it either allocates all requested or allocates
     /// nothing.
-    pub fn request_space(&self, sizes: &[Sectors]) -> StratisResult<Option<RequestTransaction>> {
+    ///
+    /// If the redundancy requires more than one copy of the data, every
+    /// request is satisfied once for each copy and the segments for copy c
+    /// of request i are recorded under index i * copies + c. legs contains,
+    /// for each copy, the blockdevs that already hold some of that copy; a
+    /// copy is never allocated from a blockdev that holds, or is picked in
+    /// this request to hold, any other copy.
+    pub fn request_space(
+        &self,
+        sizes: &[Sectors],
+        redundancy: Redundancy,
+        legs: &[HashSet<DevUuid>],
+    ) -> StratisResult<Option<RequestTransaction>> {
+        self.request_space_excluding(sizes, redundancy, legs, &[])
+    }
+
+    /// Allocate space according to sizes vector request, as request_space()
+    /// does, but never allocate from the blockdevs specified in exclude.
+    pub fn request_space_excluding(
+        &self,
+        sizes: &[Sectors],
+        redundancy: Redundancy,
+        legs: &[HashSet<DevUuid>],
+        exclude: &[DevUuid],
+    ) -> StratisResult<Option<RequestTransaction>> {
+        let mut transaction = RequestTransaction::default();
+
+        let block_devs = self
+            .block_devs
+            .iter()
+            .filter(|bd| !exclude.contains(&bd.uuid()))
+            .collect::<Vec<_>>();
+
+        let copies = redundancy.copies();
+        let total_needed: Sectors = sizes.iter().cloned().sum::<Sectors>() * copies;
+        let total_avail: Sectors = block_devs.iter().map(|bd| bd.available()).sum();
+        if total_avail < total_needed {
+            return Ok(None);
+        }
+
+        let mut legs = (0..copies)
+            .map(|copy| legs.get(copy).cloned().unwrap_or_default())
+            .collect::<Vec<_>>();
+
+        for (idx, &needed) in sizes.iter().enumerate() {
+            for copy in 0..copies {
+                let mut alloc = Sectors(0);
+                // TODO: Consider greater efficiency for allocation generally.
+                // Over time, the blockdevs at the start will be exhausted. It
+                // might be a good idea to keep an auxiliary structure, so that
+                // only blockdevs with some space left to allocate are accessed.
+                // In the context of this major inefficiency that ensues over time
+                // the obvious but more minor inefficiency of this inner loop is
+                // not worth worrying about.
+                for bd in &block_devs {
+                    if alloc == needed {
+                        break;
+                    }
+
+                    if legs
+                        .iter()
+                        .enumerate()
+                        .any(|(leg, uuids)| leg != copy && uuids.contains(&bd.uuid()))
+                    {
+                        continue;
+                    }
+
+                    let r_segs = bd.request_space(needed - alloc, &transaction)?;
+                    if r_segs.sum() == Sectors(0) {
+                        continue;
+                    }
+                    for (&start, &length) in r_segs.iter() {
+                        transaction.add_bd_seg_req(
+                            idx * copies + copy,
+                            BlkDevSegment::new(
+                                bd.uuid(),
+                                Segment::new(bd.data_device(), start, length),
+                            ),
+                        );
+                    }
+                    legs[copy].insert(bd.uuid());
+                    alloc += r_segs.sum();
+                }
+
+                // With a single copy the check on the total available space
+                // guarantees success; with more than one, the blockdevs that
+                // may hold a copy may not have enough space between them.
+                if alloc != needed {
+                    assert!(copies > 1);
+                    return Ok(None);
+                }
+                if copies > 1 {
+                    transaction.add_leg_req(idx * copies + copy, copy);
+                }
+            }
+        }
+
+        Ok(Some(transaction))
+    }
+
+    /// Allocate space according to sizes vector request, as request_space()
+    /// does for data without redundancy, but stripe each request across as
+    /// many blockdevs as possible.
+    ///
+    /// The largest part of a request that can be split into equal pieces,
+    /// each a multiple of stripe_size and each in a single free range of a
+    /// different blockdev, is striped across those blockdevs and recorded
+    /// with add_stripe_req(). The rest of the request, or all of it if it
+    /// can not be striped across at least two blockdevs, is allocated as by
+    /// request_space().
+    pub fn request_striped_space(
+        &self,
+        sizes: &[Sectors],
+        stripe_size: Sectors,
+    ) -> StratisResult<Option<RequestTransaction>> {
         let mut transaction = RequestTransaction::default();
 
         let total_needed: Sectors = sizes.iter().cloned().sum();
@@ -204,13 +347,36 @@ impl BlockDevMgr {
 
         for (idx, &needed) in sizes.iter().enumerate() {
             let mut alloc = Sectors(0);
-            // TODO: Consider greater efficiency for allocation generally.
-            // Over time, the blockdevs at the start will be exhausted. It
-            // might be a good idea to keep an auxiliary structure, so that
-            // only blockdevs with some space left to allocate are accessed.
-            // In the context of this major inefficiency that ensues over time
-            // the obvious but more minor inefficiency of this inner loop is
-            // not worth worrying about.
+
+            for stripes in (2..=self.block_devs.len()).rev() {
+                let width = Sectors(*needed / stripes as u64 / *stripe_size * *stripe_size);
+                if width == Sectors(0) {
+                    continue;
+                }
+
+                let mut pieces = Vec::with_capacity(stripes);
+                for bd in &self.block_devs {
+                    if pieces.len() == stripes {
+                        break;
+                    }
+                    if let Some(start) = bd.request_contiguous_space(width, &transaction)? {
+                        pieces.push(BlkDevSegment::new(
+                            bd.uuid(),
+                            Segment::new(bd.data_device(), start, width),
+                        ));
+                    }
+                }
+
+                if pieces.len() == stripes {
+                    for piece in pieces {
+                        transaction.add_bd_seg_req(idx, piece);
+                    }
+                    transaction.add_stripe_req(idx, stripes, stripe_size);
+                    alloc = width * stripes;
+                    break;
+                }
+            }
+
             for bd in &self.block_devs {
                 if alloc == needed {
                     break;
@@ -220,7 +386,10 @@ impl BlockDevMgr {
                 for (&start, &length) in r_segs.iter() {
                     transaction.add_bd_seg_req(
                         idx,
-                        BlkDevSegment::new(bd.uuid(), Segment::new(*bd.device(), start, length)),
+                        BlkDevSegment::new(
+                            bd.uuid(),
+                            Segment::new(bd.data_device(), start, length),
+                        ),
                     );
                 }
                 alloc += r_segs.sum();
@@ -231,6 +400,86 @@ impl BlockDevMgr {
         Ok(Some(transaction))
     }
 
+    /// Allocate space according to sizes vector request, as
+    /// request_space_excluding() does for data without redundancy, but
+    /// allocate every request for which contiguous is Some(_) from a single
+    /// free range of a single blockdev. Such a request is allocated from a
+    /// blockdev that is not among the blockdevs it specifies, if possible.
+    /// Return None if a contiguous request can not be satisfied.
+    pub fn request_space_contiguous(
+        &self,
+        requests: &[(Sectors, Option<Vec<DevUuid>>)],
+        exclude: &[DevUuid],
+    ) -> StratisResult<Option<RequestTransaction>> {
+        let mut transaction = RequestTransaction::default();
+
+        let block_devs = self
+            .block_devs
+            .iter()
+            .filter(|bd| !exclude.contains(&bd.uuid()))
+            .collect::<Vec<_>>();
+
+        let total_needed: Sectors = requests.iter().map(|(size, _)| *size).sum();
+        let total_avail: Sectors = block_devs.iter().map(|bd| bd.available()).sum();
+        if total_avail < total_needed {
+            return Ok(None);
+        }
+
+        for (idx, (needed, contiguous)) in requests.iter().enumerate() {
+            let needed = *needed;
+            match contiguous {
+                Some(avoid) => {
+                    let mut found = None;
+                    for bd in block_devs
+                        .iter()
+                        .filter(|bd| !avoid.contains(&bd.uuid()))
+                        .chain(block_devs.iter().filter(|bd| avoid.contains(&bd.uuid())))
+                    {
+                        if let Some(start) = bd.request_contiguous_space(needed, &transaction)? {
+                            found = Some(BlkDevSegment::new(
+                                bd.uuid(),
+                                Segment::new(bd.data_device(), start, needed),
+                            ));
+                            break;
+                        }
+                    }
+                    match found {
+                        Some(seg) => transaction.add_bd_seg_req(idx, seg),
+                        None => return Ok(None),
+                    }
+                }
+                None => {
+                    let mut alloc = Sectors(0);
+                    for bd in &block_devs {
+                        if alloc == needed {
+                            break;
+                        }
+
+                        let r_segs = bd.request_space(needed - alloc, &transaction)?;
+                        for (&start, &length) in r_segs.iter() {
+                            transaction.add_bd_seg_req(
+                                idx,
+                                BlkDevSegment::new(
+                                    bd.uuid(),
+                                    Segment::new(bd.data_device(), start, length),
+                                ),
+                            );
+                        }
+                        alloc += r_segs.sum();
+                    }
+
+                    // Contiguous requests allocated earlier may have split
+                    // the free space so that it can not hold this request.
+                    if alloc != needed {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+
+        Ok(Some(transaction))
+    }
+
     /// Commit the allocations calculated by the request_space() method.
     ///
     /// This method converts the block device segments into the necessary data
@@ -270,6 +519,21 @@ impl BlockDevMgr {
         Ok(())
     }
 
+    /// Return the given segments, which must have been allocated by
+    /// commit_space(), to the blockdevs they were allocated from so that they
+    /// may be allocated again.
+    pub fn release_space(&mut self, segs: &[BlkDevSegment]) -> StratisResult<()> {
+        for seg in segs {
+            self.get_mut_blockdev_by_uuid(seg.uuid)
+                .ok_or_else(|| {
+                    StratisError::Msg(format!("Block device with UUID {} not found", seg.uuid))
+                })?
+                .release_space(&(seg.segment.start, seg.segment.length))?;
+        }
+
+        Ok(())
+    }
+
     /// Write the given data to all blockdevs marking with current time.
     /// Return an error if data was not written to any blockdev.
     /// Omit blockdevs which do not have sufficient space in BDA to accommodate
@@ -365,12 +629,32 @@ impl BlockDevMgr {
             self.block_devs.iter().map(|bd| bd.encryption_info()),
         )
         .expect("Cannot create a pool out of both encrypted and unencrypted devices")
+        .map(|info| {
+            info.set_keyring_bindings(self.block_devs.iter().map(|bd| bd.keyring_bindings()))
+        })
     }
 
     pub fn is_encrypted(&self) -> bool {
         self.encryption_info().is_some()
     }
 
+    /// Get the crypt parameters with which the devices were formatted, read
+    /// from the LUKS2 header of the first device. Return None if the devices
+    /// are not encrypted.
+    pub fn crypt_params(&self) -> StratisResult<Option<CryptParams>> {
+        match self.block_devs.first() {
+            Some(bd) => bd.crypt_params(),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the hash with which the data on the devices is checked, taken
+    /// from the first device. Return None if the devices have no integrity
+    /// protection.
+    pub fn integrity_hash(&self) -> Option<IntegrityHash> {
+        self.block_devs.first().and_then(|bd| bd.integrity_hash())
+    }
+
     #[cfg(test)]
     fn invariant(&self) {
         let pool_uuids = self
@@ -408,6 +692,15 @@ impl BlockDevMgr {
         bd.grow()
     }
 
+    pub fn shrink(&mut self, dev: DevUuid) -> StratisResult<bool> {
+        let bd = self
+            .block_devs
+            .iter_mut()
+            .find(|bd| bd.uuid() == dev)
+            .ok_or_else(|| StratisError::Msg(format!("Block device with UUID {dev} not found")))?;
+        bd.shrink()
+    }
+
     /// Tear down devicemapper devices for the block devices in this BlockDevMgr.
     pub fn teardown(&mut self) -> StratisResult<()> {
         let errs = self.block_devs.iter_mut().fold(Vec::new(), |mut errs, bd| {
@@ -467,12 +760,16 @@ mod tests {
             MDADataSize::default(),
             None,
             None,
+            None,
         )
         .unwrap();
         assert_eq!(mgr.avail_space() + mgr.metadata_size(), mgr.size());
 
         let allocated = Sectors(2);
-        let transaction = mgr.request_space(&[allocated]).unwrap().unwrap();
+        let transaction = mgr
+            .request_space(&[allocated], Redundancy::None, &[])
+            .unwrap()
+            .unwrap();
         mgr.commit_space(transaction).unwrap();
         assert_eq!(
             mgr.avail_space() + allocated + mgr.metadata_size(),
@@ -513,6 +810,7 @@ mod tests {
                 MDADataSize::default(),
                 Some(&EncryptionInfo::KeyDesc(key_desc.clone())),
                 None,
+                None,
             )
             .unwrap();
 
@@ -558,6 +856,7 @@ mod tests {
                 MDADataSize::default(),
                 Some(&EncryptionInfo::KeyDesc(key_desc.clone())),
                 None,
+                None,
             )
             .unwrap();
 
@@ -608,6 +907,7 @@ mod tests {
             MDADataSize::default(),
             None,
             None,
+            None,
         )
         .unwrap();
         cmd::udev_settle().unwrap();
@@ -637,6 +937,7 @@ mod tests {
             MDADataSize::default(),
             None,
             None,
+            None,
         )
         .unwrap();
 
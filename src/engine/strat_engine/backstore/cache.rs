@@ -0,0 +1,223 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to handle the DM cache device that caches the origin sub-device of
+// the backstore.
+
+use devicemapper::{
+    CacheDevStatus, DevId, Device, DmDevice, DmFlags, DmName, DmNameBuf, DmOptions, LinearDev,
+    LinearDevTargetParams, Sectors, TargetLine,
+};
+
+use crate::{
+    engine::{
+        strat_engine::{
+            dm::get_dm,
+            names::{format_backstore_ids, CacheRole},
+        },
+        types::{CacheSettings, PoolUuid},
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// Use a cache block size that the kernel docs indicate is the largest
+/// typical size.
+pub const CACHE_BLOCK_SIZE: Sectors = Sectors(2048); // 1024 KiB
+
+/// A cache table with the given devices, length, and block size, and with
+/// the given mode, policy, and policy arguments.
+fn cache_table(
+    meta: Device,
+    cache: Device,
+    origin: Device,
+    length: Sectors,
+    cache_block_size: Sectors,
+    settings: &CacheSettings,
+) -> Vec<(u64, u64, String, String)> {
+    // Each policy argument is a key and a value.
+    let mut params = format!(
+        "{} {} {} {} 1 {} {} {}",
+        meta,
+        cache,
+        origin,
+        *cache_block_size,
+        settings.mode,
+        settings.policy,
+        2 * settings.policy_args.len()
+    );
+    for (key, value) in settings.policy_args.iter() {
+        params.push_str(&format!(" {key} {value}"));
+    }
+
+    vec![(0, *length, "cache".to_string(), params)]
+}
+
+/// A DM cache device, made up of a meta, a cache, and an origin sub-device.
+/// Unlike the CacheDev of the devicemapper library, which always loads a
+/// table with the default mode and policy, every table of this device is
+/// loaded with the mode, policy, and policy arguments it was given.
+#[derive(Debug)]
+pub struct CacheDev {
+    name: DmNameBuf,
+    device: Device,
+    meta: LinearDev,
+    cache: LinearDev,
+    origin: LinearDev,
+    settings: CacheSettings,
+}
+
+impl CacheDev {
+    /// Set up the cache device for the given pool on the given sub-devices
+    /// with the given settings. If the cache device already exists, e.g.,
+    /// because stratisd was restarted while the pool was running, its table
+    /// is replaced by a table with the given settings.
+    pub fn setup(
+        pool_uuid: PoolUuid,
+        meta: LinearDev,
+        cache: LinearDev,
+        origin: LinearDev,
+        settings: CacheSettings,
+    ) -> StratisResult<CacheDev> {
+        let (name, uuid) = format_backstore_ids(pool_uuid, CacheRole::Cache);
+        let dm = get_dm();
+        let existing = dm
+            .list_devices()?
+            .into_iter()
+            .find(|(n, _, _)| *n == name)
+            .map(|(_, device, _)| device);
+        let exists = existing.is_some();
+        let device = match existing {
+            Some(device) => device,
+            None => dm
+                .device_create(&name, Some(&uuid), DmOptions::default())?
+                .device(),
+        };
+
+        let cache_dev = CacheDev {
+            name,
+            device,
+            meta,
+            cache,
+            origin,
+            settings,
+        };
+        if let Err(err) = cache_dev.load_table(&cache_dev.settings) {
+            if !exists {
+                if let Err(e) =
+                    dm.device_remove(&DevId::Name(&cache_dev.name), DmOptions::default())
+                {
+                    warn!("Failed to remove partially constructed cache device: {e}");
+                }
+            }
+            return Err(err);
+        }
+
+        Ok(cache_dev)
+    }
+
+    /// Load a table with the given settings that maps the current
+    /// sub-devices and make it the active table.
+    fn load_table(&self, settings: &CacheSettings) -> StratisResult<()> {
+        let dm = get_dm();
+        let id = DevId::Name(&self.name);
+        dm.table_load(
+            &id,
+            &cache_table(
+                self.meta.device(),
+                self.cache.device(),
+                self.origin.device(),
+                self.origin.size(),
+                CACHE_BLOCK_SIZE,
+                settings,
+            ),
+            DmOptions::default(),
+        )?;
+        dm.device_suspend(&id, DmOptions::default())?;
+        Ok(())
+    }
+
+    /// Suspend the cache device so that the table of one of its sub-devices
+    /// can be changed.
+    fn suspend(&self) -> StratisResult<()> {
+        get_dm().device_suspend(
+            &DevId::Name(&self.name),
+            DmOptions::default().set_flags(DmFlags::DM_SUSPEND),
+        )?;
+        Ok(())
+    }
+
+    /// Change the mode, policy, and policy arguments of the cache device.
+    /// The settings are unchanged if the kernel rejects the new settings.
+    pub fn set_settings(&mut self, settings: CacheSettings) -> StratisResult<()> {
+        self.load_table(&settings)?;
+        self.settings = settings;
+        Ok(())
+    }
+
+    /// Change the table of the cache sub-device.
+    pub fn set_cache_table(
+        &mut self,
+        table: Vec<TargetLine<LinearDevTargetParams>>,
+    ) -> StratisResult<()> {
+        self.suspend()?;
+        self.cache.set_table(get_dm(), table)?;
+        self.cache.resume(get_dm())?;
+        self.load_table(&self.settings)
+    }
+
+    /// Change the table of the meta sub-device.
+    pub fn set_meta_table(
+        &mut self,
+        table: Vec<TargetLine<LinearDevTargetParams>>,
+    ) -> StratisResult<()> {
+        self.suspend()?;
+        self.meta.set_table(get_dm(), table)?;
+        self.meta.resume(get_dm())?;
+        self.load_table(&self.settings)
+    }
+
+    /// Change the table of the origin sub-device; the cache device maps all
+    /// the sectors of the new origin sub-device.
+    pub fn set_origin_table(
+        &mut self,
+        table: Vec<TargetLine<LinearDevTargetParams>>,
+    ) -> StratisResult<()> {
+        self.suspend()?;
+        self.origin.set_table(get_dm(), table)?;
+        self.origin.resume(get_dm())?;
+        self.load_table(&self.settings)
+    }
+
+    /// The status of the cache device as reported by the kernel.
+    pub fn status(&self) -> StratisResult<CacheDevStatus> {
+        let (_, status) = get_dm().table_status(&DevId::Name(&self.name), DmOptions::default())?;
+        match status.as_slice() {
+            [(_, _, _, params)] => Ok(params.parse::<CacheDevStatus>()?),
+            _ => Err(StratisError::Msg(format!(
+                "Expected a single line in the status of the cache device, found {}",
+                status.len()
+            ))),
+        }
+    }
+
+    /// The name of the cache device.
+    pub fn name(&self) -> &DmName {
+        &self.name
+    }
+
+    /// The device number of the cache device.
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// The device number of the origin sub-device.
+    pub fn origin(&self) -> Device {
+        self.origin.device()
+    }
+
+    /// The number of sectors mapped by the cache device.
+    pub fn size(&self) -> Sectors {
+        self.origin.size()
+    }
+}
@@ -21,7 +21,7 @@ use crate::{
             serde_structs::{BaseDevSave, BlockDevSave, CacheTierSave, Recordable},
             types::BDARecordResult,
         },
-        types::{BlockDevTier, DevUuid, Name, PoolUuid},
+        types::{BlockDevTier, CacheSettings, DevUuid, Name, PoolUuid, Redundancy},
     },
     stratis::{StratisError, StratisResult},
 };
@@ -45,6 +45,12 @@ pub struct CacheTier {
     /// The list of segments granted by block_mgr and used by the metadata
     /// device.
     pub(super) meta_segments: AllocatedAbove,
+    /// The mode, policy, and policy arguments of the cache device.
+    pub(super) settings: CacheSettings,
+    /// True while the dirty blocks of the cache are written back in
+    /// preparation for its removal; the cache device then uses the cleaner
+    /// policy in place of settings.
+    pub(super) flushing: bool,
 }
 
 impl CacheTier {
@@ -89,6 +95,8 @@ impl CacheTier {
             block_mgr,
             cache_segments,
             meta_segments,
+            settings: cache_tier_save.settings.clone().unwrap_or_default(),
+            flushing: false,
         })
     }
 
@@ -130,7 +138,7 @@ impl CacheTier {
 
         let trans = self
             .block_mgr
-            .request_space(&[avail_space])?
+            .request_space(&[avail_space], Redundancy::None, &[])?
             .expect("asked for exactly the space available, must get");
         let segments = trans.get_blockdevmgr();
         if let Err(e) = self.block_mgr.commit_space(trans) {
@@ -150,7 +158,7 @@ impl CacheTier {
     /// sub-device too big.
     ///
     /// WARNING: metadata changing event
-    pub fn new(mut block_mgr: BlockDevMgr) -> StratisResult<CacheTier> {
+    pub fn new(mut block_mgr: BlockDevMgr, settings: CacheSettings) -> StratisResult<CacheTier> {
         let avail_space = block_mgr.avail_space();
 
         // FIXME: Come up with a better way to choose metadata device size
@@ -171,7 +179,11 @@ impl CacheTier {
         }
 
         let trans = block_mgr
-            .request_space(&[meta_space, avail_space - meta_space])?
+            .request_space(
+                &[meta_space, avail_space - meta_space],
+                Redundancy::None,
+                &[],
+            )?
             .expect("asked for exactly the space available, must get");
         let meta_segments = AllocatedAbove {
             inner: trans.get_segs_for_req(0).expect("segments.len() == 2"),
@@ -190,6 +202,8 @@ impl CacheTier {
             block_mgr,
             cache_segments,
             meta_segments,
+            settings,
+            flushing: false,
         })
     }
 
@@ -267,6 +281,7 @@ impl Recordable<CacheTierSave> for CacheTier {
                 allocs: vec![self.cache_segments.record(), self.meta_segments.record()],
                 devs: self.block_mgr.record(),
             },
+            settings: Some(self.settings.clone()),
         }
     }
 }
@@ -314,10 +329,11 @@ mod tests {
             MDADataSize::default(),
             None,
             None,
+            None,
         )
         .unwrap();
 
-        let mut cache_tier = CacheTier::new(mgr).unwrap();
+        let mut cache_tier = CacheTier::new(mgr, CacheSettings::default()).unwrap();
         cache_tier.invariant();
 
         // A cache tier w/ some devices and everything promptly allocated to
@@ -13,11 +13,19 @@ pub const STRATIS_TOKEN_DEVNAME_KEY: &str = "activation_name";
 pub const STRATIS_TOKEN_POOL_UUID_KEY: &str = "pool_uuid";
 pub const STRATIS_TOKEN_DEV_UUID_KEY: &str = "device_uuid";
 pub const STRATIS_TOKEN_POOLNAME_KEY: &str = "pool_name";
+pub const STRATIS_TOKEN_INTEGRITY_KEY: &str = "integrity";
+pub const STRATIS_TOKEN_STOPPED_KEY: &str = "stopped";
 
 pub const STRATIS_TOKEN_ID: c_uint = 0;
 pub const LUKS2_TOKEN_ID: c_uint = 1;
 pub const CLEVIS_LUKS_TOKEN_ID: c_uint = 2;
 
+/// The number of keyslots in the LUKS2 format.
+pub const LUKS2_MAX_KEYSLOTS: c_uint = 32;
+
+/// The number of token slots in the LUKS2 format.
+pub const LUKS2_MAX_TOKENS: c_uint = 32;
+
 pub const LUKS2_TOKEN_TYPE: &str = "luks2-keyring";
 pub const STRATIS_TOKEN_TYPE: &str = "stratis";
 
@@ -4,21 +4,28 @@
 
 use std::{
     fmt::Debug,
+    fs::File,
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 use either::Either;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use serde_json::{to_value, Value};
+use tempfile::TempDir;
 
 use devicemapper::{Device, DmName, DmNameBuf, Sectors};
 use libcryptsetup_rs::{
-    c_uint,
+    c_progress_callback, c_uint,
     consts::{
-        flags::{CryptActivate, CryptVolumeKey},
-        vals::{EncryptionFormat, KeyslotsSize, MetadataSize},
+        flags::{CryptActivate, CryptPbkdf, CryptReencrypt, CryptVolumeKey},
+        vals::{
+            CryptReencryptDirectionInfo, CryptReencryptInfo, CryptReencryptModeInfo,
+            EncryptionFormat, KdfType, KeyslotsSize, MetadataSize,
+        },
     },
-    CryptDevice, CryptInit, CryptParamsLuks2, CryptParamsLuks2Ref, SafeMemHandle, TokenInput,
+    CryptDevice, CryptInit, CryptParamsLuks2, CryptParamsLuks2Ref, CryptParamsReencrypt,
+    CryptPbkdfType, CryptSettingsHandle, SafeMemHandle, TokenInput,
 };
 
 use crate::{
@@ -33,38 +40,182 @@ use crate::{
                         STRATIS_TOKEN_ID,
                     },
                     shared::{
-                        acquire_crypt_device, activate, add_keyring_keyslot, check_luks2_token,
-                        clevis_info_from_metadata, ensure_inactive, ensure_wiped,
-                        get_keyslot_number, interpret_clevis_config, key_desc_from_metadata,
-                        load_crypt_metadata, replace_pool_name, setup_crypt_device,
-                        setup_crypt_handle, wipe_fallback, StratisLuks2Token,
+                        acquire_crypt_device, acquire_crypt_device_with_data, activate,
+                        activate_with_integrity, add_keyring_binding, add_keyring_keyslot,
+                        check_luks2_token, clevis_info_from_metadata, crypt_metadata_size,
+                        ensure_inactive, ensure_wiped, erase_keyslots, get_keyslot_number,
+                        interpret_clevis_config, key_desc_from_metadata, key_desc_to_passphrase,
+                        load_crypt_metadata, remove_keyring_binding, replace_pool_name,
+                        replace_stopped, setup_crypt_device, setup_crypt_handle, wipe_fallback,
+                        StratisLuks2Token,
                     },
                 },
                 devices::get_devno_from_path,
+                integrity::{integrity_devnode, integrity_layout, remove_integrity_devices},
             },
             cmd::{clevis_decrypt, clevis_luks_bind, clevis_luks_regen, clevis_luks_unbind},
+            device::blkdev_size,
             dm::DEVICEMAPPER_PATH,
             metadata::StratisIdentifiers,
             names::format_crypt_name,
+            serde_structs::IntegritySave,
         },
         types::{
-            DevUuid, DevicePath, EncryptionInfo, KeyDescription, Name, PoolUuid, SizedKeyMemory,
-            UnlockMethod,
+            CryptParams, DevUuid, DevicePath, EncryptionInfo, IntegrityHash, KeyDescription, Name,
+            Pbkdf, PoolUuid, SizedKeyMemory, UnlockMethod,
         },
         ClevisInfo,
     },
     stratis::{StratisError, StratisResult},
 };
 
+/// The state of a slice of an online reencryption, which is interrupted once
+/// its deadline has passed.
+struct ReencryptionSlice {
+    deadline: Instant,
+    progress: u8,
+}
+
+/// Parameters for the LUKS2 format with the given sector size.
+fn luks2_params(sector_size: u32) -> CryptParamsLuks2 {
+    CryptParamsLuks2 {
+        pbkdf: None,
+        integrity: None,
+        integrity_params: None,
+        data_alignment: 0,
+        data_device: None,
+        sector_size,
+        label: None,
+        subsystem: None,
+    }
+}
+
+/// Parameters for the LUKS2 format with the given crypt parameters, or None
+/// if libcryptsetup's defaults are to be used. An unspecified sector size is
+/// chosen by libcryptsetup.
+fn format_params(crypt_params: &CryptParams) -> StratisResult<Option<CryptParamsLuks2>> {
+    if crypt_params.sector_size.is_none() && !crypt_params.has_pbkdf() {
+        return Ok(None);
+    }
+
+    let mut params = luks2_params(crypt_params.sector_size.unwrap_or(0));
+    params.pbkdf = pbkdf_type(crypt_params)?;
+    Ok(Some(params))
+}
+
+/// The key derivation parameters of the crypt parameters, or None if
+/// libcryptsetup's defaults are to be used.
+fn pbkdf_type(crypt_params: &CryptParams) -> StratisResult<Option<CryptPbkdfType>> {
+    if !crypt_params.has_pbkdf() {
+        return Ok(None);
+    }
+
+    let mut pbkdf = CryptSettingsHandle::get_pbkdf_default(EncryptionFormat::Luks2)?;
+    if let Some(kdf) = crypt_params.pbkdf {
+        pbkdf.type_ = match kdf {
+            Pbkdf::Pbkdf2 => KdfType::Pbkdf2,
+            Pbkdf::Argon2i => KdfType::Argon2I,
+            Pbkdf::Argon2id => KdfType::Argon2Id,
+        };
+    }
+    if pbkdf.type_ == KdfType::Pbkdf2 {
+        pbkdf.max_memory_kb = 0;
+        pbkdf.parallel_threads = 0;
+    }
+    if let Some(memory) = crypt_params.pbkdf_memory {
+        pbkdf.max_memory_kb = memory;
+    }
+    if let Some(parallel) = crypt_params.pbkdf_parallel {
+        pbkdf.parallel_threads = parallel;
+    }
+    if let Some(iterations) = crypt_params.pbkdf_iterations {
+        pbkdf.iterations = iterations;
+        pbkdf.flags |= CryptPbkdf::NO_BENCHMARK;
+    }
+    Ok(Some(pbkdf))
+}
+
+/// Create the keyslots that are subsequently added to the device with the
+/// key derivation parameters of the crypt parameters, if any.
+fn set_keyslot_pbkdf(
+    device: &mut CryptDevice,
+    crypt_params: Option<&CryptParams>,
+) -> StratisResult<()> {
+    if let Some(pbkdf) = crypt_params.map(pbkdf_type).transpose()?.flatten() {
+        device.settings_handle().set_pbkdf_type(&pbkdf)?;
+    }
+    Ok(())
+}
+
+/// The cipher and mode in the crypt parameters, or aes-xts-plain64 if they
+/// are not specified.
+fn cipher_and_mode(crypt_params: &CryptParams) -> StratisResult<(&str, &str)> {
+    Ok(crypt_params
+        .cipher_and_mode()?
+        .unwrap_or(("aes", "xts-plain64")))
+}
+
+/// Parameters for the online encryption of a device that holds unencrypted
+/// data. The data is shifted towards the end of the device by data_shift to
+/// make room for the LUKS2 header, beginning with the last segment.
+fn encryption_params(
+    data_shift: Sectors,
+    sector_size: u32,
+    flags: CryptReencrypt,
+) -> CryptParamsReencrypt {
+    CryptParamsReencrypt {
+        mode: CryptReencryptModeInfo::Encrypt,
+        direction: CryptReencryptDirectionInfo::Backward,
+        resilience: "datashift".to_string(),
+        hash: "sha256".to_string(),
+        data_shift: *data_shift,
+        max_hotzone_size: 0,
+        device_size: 0,
+        luks2: luks2_params(sector_size),
+        flags,
+    }
+}
+
+/// Parameters for the reencryption of a device with a new volume key in
+/// place, beginning with the first segment.
+fn reencryption_params(sector_size: u32, flags: CryptReencrypt) -> CryptParamsReencrypt {
+    CryptParamsReencrypt {
+        mode: CryptReencryptModeInfo::Reencrypt,
+        direction: CryptReencryptDirectionInfo::Forward,
+        resilience: "checksum".to_string(),
+        hash: "sha256".to_string(),
+        data_shift: 0,
+        max_hotzone_size: 0,
+        device_size: 0,
+        luks2: luks2_params(sector_size),
+        flags,
+    }
+}
+
+/// Parameters for resuming or querying a reencryption. All parameters other
+/// than the flags are read from the reencryption metadata in the LUKS2
+/// header.
+fn resume_params(flags: CryptReencrypt) -> CryptParamsReencrypt {
+    reencryption_params(0, flags)
+}
+
 #[derive(Debug, Clone)]
 pub struct CryptMetadata {
     pub physical_path: DevicePath,
     pub identifiers: StratisIdentifiers,
     pub encryption_info: EncryptionInfo,
+    /// Keyring bindings in addition to the one in the encryption info, each
+    /// with the ID of the LUKS2 token that records it.
+    pub keyring_bindings: Vec<(c_uint, KeyDescription)>,
     pub activation_name: DmNameBuf,
     pub activated_path: PathBuf,
     pub pool_name: Option<Name>,
     pub device: Device,
+    /// The dm-integrity layer beneath the encryption, if any. The integrity
+    /// device is the data device of the LUKS2 header on the physical device.
+    pub integrity: Option<IntegritySave>,
+    /// True if the pool was stopped on purpose.
+    pub stopped: bool,
 }
 
 /// Handle for performing all operations on an encrypted device.
@@ -77,13 +228,16 @@ pub struct CryptHandle {
 }
 
 impl CryptHandle {
+    #[allow(clippy::too_many_arguments)]
     pub(super) fn new(
         physical_path: DevicePath,
         pool_uuid: PoolUuid,
         dev_uuid: DevUuid,
         encryption_info: EncryptionInfo,
+        keyring_bindings: Vec<(c_uint, KeyDescription)>,
         pool_name: Option<Name>,
         devno: Device,
+        integrity: Option<IntegritySave>,
     ) -> CryptHandle {
         let activation_name = format_crypt_name(&dev_uuid);
         let path = vec![DEVICEMAPPER_PATH, &activation_name.to_string()]
@@ -98,10 +252,13 @@ impl CryptHandle {
                     device_uuid: dev_uuid,
                 },
                 encryption_info,
+                keyring_bindings,
                 activation_name,
                 pool_name,
                 device: devno,
                 activated_path,
+                integrity,
+                stopped: false,
             },
         }
     }
@@ -154,27 +311,32 @@ impl CryptHandle {
             .unwrap_or(false)
     }
 
-    /// Initialize a device with the provided key description and Clevis info.
+    /// Initialize a device with the provided key description and Clevis info,
+    /// formatting it with the given crypt parameters.
+    ///
+    /// If an integrity hash is given, a dm-integrity device that keeps its
+    /// tags at the end of the physical device is set up beneath the
+    /// encryption, and the encrypted device is activated on top of it.
     pub fn initialize(
         physical_path: &Path,
         pool_uuid: PoolUuid,
         dev_uuid: DevUuid,
         pool_name: Name,
         encryption_info: &EncryptionInfo,
-        sector_size: Option<u32>,
+        crypt_params: &CryptParams,
+        integrity: Option<IntegrityHash>,
     ) -> StratisResult<Self> {
         let activation_name = format_crypt_name(&dev_uuid);
 
-        let luks2_params = sector_size.map(|sector_size| CryptParamsLuks2 {
-            pbkdf: None,
-            integrity: None,
-            integrity_params: None,
-            data_alignment: 0,
-            data_device: None,
-            sector_size,
-            label: None,
-            subsystem: None,
-        });
+        let luks2_params = format_params(crypt_params)?;
+        let integrity = match integrity {
+            Some(hash) => Some(integrity_layout(
+                blkdev_size(&File::open(physical_path)?)?.sectors(),
+                hash,
+                crypt_metadata_size().sectors(),
+            )?),
+            None => None,
+        };
 
         let mut device = log_on_failure!(
             CryptInit::init(physical_path),
@@ -186,7 +348,7 @@ impl CryptHandle {
             MetadataSize::try_from(convert_int!(*DEFAULT_CRYPT_METADATA_SIZE, u128, u64)?)?,
             KeyslotsSize::try_from(convert_int!(*DEFAULT_CRYPT_KEYSLOTS_SIZE, u128, u64)?)?,
         )?;
-        Self::initialize_with_err(&mut device, physical_path, pool_uuid, dev_uuid, &pool_name, encryption_info, luks2_params.as_ref())
+        Self::initialize_with_err(&mut device, physical_path, pool_uuid, dev_uuid, &pool_name, encryption_info, crypt_params, luks2_params.as_ref(), integrity.as_ref())
             .and_then(|path| clevis_info_from_metadata(&mut device).map(|ci| (path, ci)))
             .and_then(|(_, clevis_info)| {
                 let encryption_info =
@@ -206,13 +368,22 @@ impl CryptHandle {
                     pool_uuid,
                     dev_uuid,
                     encryption_info,
+                    Vec::new(),
                     Some(pool_name),
                     devno,
+                    integrity.clone(),
                 ))
             })
             .map_err(|e| {
                 if let Err(err) =
                     Self::rollback(&mut device, physical_path, &activation_name)
+                        .and_then(|_| {
+                            if integrity.is_some() {
+                                remove_integrity_devices(dev_uuid)
+                            } else {
+                                Ok(())
+                            }
+                        })
                 {
                     warn!(
                         "Failed to roll back crypt device initialization; you may need to manually wipe this device: {}",
@@ -223,6 +394,111 @@ impl CryptHandle {
             })
     }
 
+    /// Initialize the online encryption of a device that holds unencrypted
+    /// data and activate it. The last crypt_metadata_size() bytes of the
+    /// device must be unused; the data is shifted towards the end of the
+    /// device to make room for the LUKS2 header, so that the activated device
+    /// presents the data at the offsets at which it was found on the
+    /// unencrypted device. Only the first chunk of data is encrypted before
+    /// this method returns; the remainder is encrypted by
+    /// continue_reencryption() while the activated device is in use.
+    ///
+    /// The device is formatted and encrypted with the crypt parameters, as
+    /// a device of a new pool is.
+    ///
+    /// The LUKS2 header overwrites the first crypt_metadata_size() bytes of
+    /// the device, which the caller must preserve in order to be able to
+    /// roll back the operation.
+    ///
+    /// If the device has integrity protection, the data is encrypted
+    /// through its integrity device, which is set up and remains the data
+    /// device of the LUKS2 header, and the integrity layer is recorded in
+    /// the Stratis token; the LUKS2 header itself is written to the physical
+    /// device, so that the data at the start of the integrity device must be
+    /// preserved through the integrity device.
+    ///
+    /// Precondition: crypt_params specifies the sector size.
+    pub fn initialize_online(
+        physical_path: &Path,
+        pool_uuid: PoolUuid,
+        dev_uuid: DevUuid,
+        pool_name: Name,
+        encryption_info: &EncryptionInfo,
+        crypt_params: &CryptParams,
+        integrity: Option<&IntegritySave>,
+    ) -> StratisResult<Self> {
+        // The header is built in a detached header file and written to the
+        // device only once the data at its location has been moved.
+        let tmp_dir = TempDir::new()?;
+        let header_path = tmp_dir.path().join("header");
+        File::create(&header_path)?.set_len(convert_int!(*crypt_metadata_size(), u128, u64)?)?;
+
+        let data_path = match integrity {
+            Some(_) => integrity_devnode(dev_uuid),
+            None => physical_path.to_owned(),
+        };
+        let mut device = log_on_failure!(
+            CryptInit::init_with_data_device(&header_path, &data_path),
+            "Failed to acquire context for device {} while initializing online encryption; \
+            nothing to clean up",
+            physical_path.display()
+        );
+        device.settings_handle().set_metadata_size(
+            MetadataSize::try_from(convert_int!(*DEFAULT_CRYPT_METADATA_SIZE, u128, u64)?)?,
+            KeyslotsSize::try_from(convert_int!(*DEFAULT_CRYPT_KEYSLOTS_SIZE, u128, u64)?)?,
+        )?;
+        Self::initialize_online_with_err(
+            &mut device,
+            &header_path,
+            physical_path,
+            (pool_uuid, dev_uuid, &pool_name),
+            encryption_info,
+            crypt_params,
+            integrity,
+        )?;
+
+        log_on_failure!(
+            CryptInit::init(physical_path).and_then(|mut device| device
+                .backup_handle()
+                .header_restore(Some(EncryptionFormat::Luks2), &header_path)),
+            "Failed to write the LUKS2 header to device {}",
+            physical_path.display()
+        );
+
+        let mut device = match integrity {
+            Some(_) => acquire_crypt_device_with_data(physical_path, &data_path)?,
+            None => acquire_crypt_device(physical_path)?,
+        };
+        activate(
+            &mut device,
+            encryption_info.key_description(),
+            Self::initial_unlock_method(encryption_info),
+            &format_crypt_name(&dev_uuid),
+        )?;
+
+        let encryption_info = EncryptionInfo::from_options((
+            encryption_info.key_description().cloned(),
+            clevis_info_from_metadata(&mut device)?,
+        ))
+        .ok_or_else(|| {
+            StratisError::Msg(format!(
+                "No valid encryption method that can be used to unlock device {} found after initialization",
+                physical_path.display()
+            ))
+        })?;
+
+        Ok(CryptHandle::new(
+            DevicePath::new(physical_path)?,
+            pool_uuid,
+            dev_uuid,
+            encryption_info,
+            Vec::new(),
+            Some(pool_name),
+            get_devno_from_path(physical_path)?,
+            integrity.cloned(),
+        ))
+    }
+
     /// Initialize with a passphrase in the kernel keyring only.
     fn initialize_with_keyring(
         device: &mut CryptDevice,
@@ -311,6 +587,7 @@ impl CryptHandle {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn initialize_with_err(
         device: &mut CryptDevice,
         physical_path: &Path,
@@ -318,23 +595,128 @@ impl CryptHandle {
         dev_uuid: DevUuid,
         pool_name: &Name,
         encryption_info: &EncryptionInfo,
+        crypt_params: &CryptParams,
+        luks2_params: Option<&CryptParamsLuks2>,
+        integrity: Option<&IntegritySave>,
+    ) -> StratisResult<()> {
+        Self::format(device, physical_path, crypt_params, luks2_params)?;
+        Self::initialize_keyslots(device, physical_path, encryption_info)?;
+        Self::initialize_stratis_token(device, pool_uuid, dev_uuid, pool_name, integrity)?;
+
+        match integrity {
+            Some(integrity) => activate_with_integrity(
+                physical_path,
+                dev_uuid,
+                integrity,
+                true,
+                encryption_info.key_description(),
+                Self::initial_unlock_method(encryption_info),
+                &format_crypt_name(&dev_uuid),
+            ),
+            None => activate(
+                device,
+                encryption_info.key_description(),
+                Self::initial_unlock_method(encryption_info),
+                &format_crypt_name(&dev_uuid),
+            ),
+        }
+    }
+
+    /// Set up the LUKS2 header in the detached header file header_path for
+    /// the online encryption of physical_path and initialize the
+    /// reencryption, which moves the first chunk of data.
+    fn initialize_online_with_err(
+        device: &mut CryptDevice,
+        header_path: &Path,
+        physical_path: &Path,
+        (pool_uuid, dev_uuid, pool_name): (PoolUuid, DevUuid, &Name),
+        encryption_info: &EncryptionInfo,
+        crypt_params: &CryptParams,
+        integrity: Option<&IntegritySave>,
+    ) -> StratisResult<()> {
+        let sector_size = crypt_params
+            .sector_size
+            .expect("the sector size is specified for online encryption");
+        let mut format_luks2_params =
+            format_params(crypt_params)?.unwrap_or_else(|| luks2_params(sector_size));
+        format_luks2_params.sector_size = sector_size;
+
+        let data_shift = crypt_metadata_size().sectors();
+        log_on_failure!(
+            device.set_data_offset(*data_shift),
+            "Failed to set the data offset for the online encryption of device {}",
+            physical_path.display()
+        );
+
+        Self::format(
+            device,
+            physical_path,
+            crypt_params,
+            Some(&format_luks2_params),
+        )?;
+        Self::initialize_keyslots(device, header_path, encryption_info)?;
+        Self::initialize_stratis_token(device, pool_uuid, dev_uuid, pool_name, integrity)?;
+
+        let passphrase = Self::passphrase(device, encryption_info.key_description())?;
+        log_on_failure!(
+            device.reencrypt_handle().reencrypt_init_by_passphrase(
+                None,
+                passphrase.as_ref(),
+                None,
+                None,
+                Some(cipher_and_mode(crypt_params)?),
+                encryption_params(
+                    data_shift,
+                    sector_size,
+                    CryptReencrypt::INITIALIZE_ONLY | CryptReencrypt::MOVE_FIRST_SEGMENT
+                ),
+            ),
+            "Failed to initialize the online encryption of device {}",
+            physical_path.display()
+        );
+
+        Ok(())
+    }
+
+    /// Format the device with a LUKS2 header using the cipher and key size
+    /// in the crypt parameters, or aes-xts-plain64 with a STRATIS_MEK_SIZE
+    /// key if they are not specified.
+    fn format(
+        device: &mut CryptDevice,
+        physical_path: &Path,
+        crypt_params: &CryptParams,
         luks2_params: Option<&CryptParamsLuks2>,
     ) -> StratisResult<()> {
         let mut luks2_params_ref: Option<CryptParamsLuks2Ref<'_>> =
             luks2_params.map(|lp| lp.try_into()).transpose()?;
+        let cipher = cipher_and_mode(crypt_params)?;
+        let key_size = match crypt_params.key_size {
+            Some(bits) => convert_int!(bits / 8, u32, usize)?,
+            None => STRATIS_MEK_SIZE,
+        };
 
         log_on_failure!(
             device.context_handle().format::<CryptParamsLuks2Ref<'_>>(
                 EncryptionFormat::Luks2,
-                ("aes", "xts-plain64"),
+                cipher,
                 None,
-                libcryptsetup_rs::Either::Right(STRATIS_MEK_SIZE),
+                libcryptsetup_rs::Either::Right(key_size),
                 luks2_params_ref.as_mut()
             ),
             "Failed to format device {} with LUKS2 header",
             physical_path.display()
         );
 
+        Ok(())
+    }
+
+    /// Add the keyslots that unlock a freshly formatted device with the
+    /// methods in the encryption info.
+    fn initialize_keyslots(
+        device: &mut CryptDevice,
+        physical_path: &Path,
+        encryption_info: &EncryptionInfo,
+    ) -> StratisResult<()> {
         match encryption_info {
             EncryptionInfo::Both(kd, (pin, config)) => {
                 let mut parsed_config = config.clone();
@@ -349,8 +731,19 @@ impl CryptHandle {
             }
         };
 
+        Ok(())
+    }
+
+    /// Initialize the Stratis token, which records the dm-integrity layer
+    /// beneath the encryption, if any.
+    fn initialize_stratis_token(
+        device: &mut CryptDevice,
+        pool_uuid: PoolUuid,
+        dev_uuid: DevUuid,
+        pool_name: &Name,
+        integrity: Option<&IntegritySave>,
+    ) -> StratisResult<()> {
         let activation_name = format_crypt_name(&dev_uuid);
-        // Initialize stratis token
         log_on_failure!(
             device.token_handle().json_set(TokenInput::ReplaceToken(
                 STRATIS_TOKEN_ID,
@@ -361,24 +754,26 @@ impl CryptHandle {
                         device_uuid: dev_uuid
                     },
                     pool_name: Some(pool_name.clone()),
+                    integrity: integrity.cloned(),
+                    stopped: false,
                 })?,
             )),
             "Failed to create the Stratis token"
         );
 
-        activate(
-            device,
-            encryption_info.key_description(),
-            if matches!(
-                encryption_info,
-                EncryptionInfo::Both(_, _) | EncryptionInfo::KeyDesc(_)
-            ) {
-                UnlockMethod::Keyring
-            } else {
-                UnlockMethod::Clevis
-            },
-            &activation_name,
-        )
+        Ok(())
+    }
+
+    /// The method with which a freshly initialized device is activated.
+    fn initial_unlock_method(encryption_info: &EncryptionInfo) -> UnlockMethod {
+        if matches!(
+            encryption_info,
+            EncryptionInfo::Both(_, _) | EncryptionInfo::KeyDesc(_)
+        ) {
+            UnlockMethod::Keyring
+        } else {
+            UnlockMethod::Clevis
+        }
     }
 
     pub fn rollback(
@@ -390,8 +785,19 @@ impl CryptHandle {
     }
 
     /// Acquire the crypt device handle for the physical path in this `CryptHandle`.
+    /// If the device has integrity protection and its integrity device is set
+    /// up, the data is accessed through the integrity device.
     pub(super) fn acquire_crypt_device(&self) -> StratisResult<CryptDevice> {
-        acquire_crypt_device(self.luks2_device_path())
+        let data_path = self
+            .metadata
+            .integrity
+            .as_ref()
+            .map(|_| integrity_devnode(self.metadata.identifiers.device_uuid))
+            .filter(|path| path.exists());
+        match data_path {
+            Some(data_path) => acquire_crypt_device_with_data(self.luks2_device_path(), &data_path),
+            None => acquire_crypt_device(self.luks2_device_path()),
+        }
     }
 
     /// Query the device metadata to reconstruct a handle for performing operations
@@ -427,6 +833,11 @@ impl CryptHandle {
         }
     }
 
+    /// The dm-integrity layer beneath the encryption, if any.
+    pub fn integrity(&self) -> Option<&IntegritySave> {
+        self.metadata.integrity.as_ref()
+    }
+
     /// Get the encryption info for this encrypted device.
     pub fn encryption_info(&self) -> &EncryptionInfo {
         &self.metadata.encryption_info
@@ -453,6 +864,12 @@ impl CryptHandle {
         self.metadata.pool_name.as_ref()
     }
 
+    /// Return true if the LUKS2 metadata records that the pool was stopped
+    /// on purpose.
+    pub fn stopped(&self) -> bool {
+        self.metadata.stopped
+    }
+
     /// Device number for the LUKS2 encrypted device.
     pub fn device(&self) -> &Device {
         &self.metadata.device
@@ -468,6 +885,47 @@ impl CryptHandle {
         get_keyslot_number(&mut self.acquire_crypt_device()?, token_id)
     }
 
+    /// Get the crypt parameters with which the device was formatted from its
+    /// LUKS2 header. The parameters of the key derivation function are those
+    /// of the keyslot for the kernel keyring, if there is one; the keyslots
+    /// for Clevis are added by Clevis with its own parameters.
+    pub fn crypt_params(&self) -> StratisResult<CryptParams> {
+        let mut device = self.acquire_crypt_device()?;
+
+        let cipher = format!(
+            "{}-{}",
+            device.status_handle().get_cipher()?,
+            device.status_handle().get_cipher_mode()?
+        );
+        let key_size = convert_int!(device.status_handle().get_volume_key_size(), i32, u32)? * 8;
+        let sector_size = convert_int!(device.status_handle().get_sector_size(), i32, u32)?;
+
+        let pbkdf = match get_keyslot_number(&mut device, LUKS2_TOKEN_ID)?
+            .and_then(|keyslots| keyslots.first().copied())
+        {
+            Some(keyslot) => Some(device.keyslot_handle().get_pbkdf(keyslot)?),
+            None => None,
+        };
+
+        Ok(CryptParams {
+            cipher: Some(cipher),
+            key_size: Some(key_size),
+            sector_size: Some(sector_size),
+            pbkdf: pbkdf.as_ref().map(|p| match p.type_ {
+                KdfType::Pbkdf2 => Pbkdf::Pbkdf2,
+                KdfType::Argon2I => Pbkdf::Argon2i,
+                KdfType::Argon2Id => Pbkdf::Argon2id,
+            }),
+            pbkdf_memory: pbkdf
+                .as_ref()
+                .and_then(|p| Some(p.max_memory_kb).filter(|m| *m != 0)),
+            pbkdf_iterations: pbkdf.as_ref().map(|p| p.iterations),
+            pbkdf_parallel: pbkdf
+                .as_ref()
+                .and_then(|p| Some(p.parallel_threads).filter(|t| *t != 0)),
+        })
+    }
+
     /// Get info for the clevis binding.
     pub fn clevis_info(&self) -> StratisResult<Option<ClevisInfo>> {
         clevis_info_from_metadata(&mut self.acquire_crypt_device()?)
@@ -570,9 +1028,15 @@ impl CryptHandle {
         Ok(())
     }
 
-    /// Add a keyring binding to the underlying LUKS2 volume.
-    pub fn bind_keyring(&mut self, key_desc: &KeyDescription) -> StratisResult<()> {
+    /// Add a keyring binding to the underlying LUKS2 volume. Its keyslot is
+    /// created with the key derivation parameters in pbkdf_params, if any.
+    pub fn bind_keyring(
+        &mut self,
+        key_desc: &KeyDescription,
+        pbkdf_params: Option<&CryptParams>,
+    ) -> StratisResult<()> {
         let mut device = self.acquire_crypt_device()?;
+        set_keyslot_pbkdf(&mut device, pbkdf_params)?;
         let key = Self::clevis_decrypt(&mut device)?.ok_or_else(|| {
             StratisError::Msg(
                 "The Clevis token appears to have been wiped outside of \
@@ -621,9 +1085,16 @@ impl CryptHandle {
         Ok(())
     }
 
-    /// Change the key description and passphrase that a device is bound to
-    pub fn rebind_keyring(&mut self, new_key_desc: &KeyDescription) -> StratisResult<()> {
+    /// Change the key description and passphrase that a device is bound to.
+    /// The keyslot is recreated with the key derivation parameters in
+    /// pbkdf_params, if any.
+    pub fn rebind_keyring(
+        &mut self,
+        new_key_desc: &KeyDescription,
+        pbkdf_params: Option<&CryptParams>,
+    ) -> StratisResult<()> {
         let mut device = self.acquire_crypt_device()?;
+        set_keyslot_pbkdf(&mut device, pbkdf_params)?;
 
         let old_key_description = self.metadata.encryption_info
             .key_description()
@@ -643,12 +1114,85 @@ impl CryptHandle {
         Ok(())
     }
 
+    /// Get the key descriptions of the keyring bindings in addition to the
+    /// one in the encryption info.
+    pub fn keyring_bindings(&self) -> Vec<KeyDescription> {
+        self.metadata
+            .keyring_bindings
+            .iter()
+            .map(|(_, kd)| kd.clone())
+            .collect()
+    }
+
+    /// Bind the device to an additional passphrase in the kernel keyring,
+    /// which can be removed independently of the other unlock methods. Its
+    /// keyslot is created with the key derivation parameters in
+    /// pbkdf_params, if any.
+    pub fn add_keyring_binding(
+        &mut self,
+        key_desc: &KeyDescription,
+        pbkdf_params: Option<&CryptParams>,
+    ) -> StratisResult<()> {
+        if self.encryption_info().key_description() == Some(key_desc)
+            || self
+                .metadata
+                .keyring_bindings
+                .iter()
+                .any(|(_, kd)| kd == key_desc)
+        {
+            return Err(StratisError::Msg(format!(
+                "Device is already bound to key description {}",
+                key_desc.as_application_str()
+            )));
+        }
+
+        let mut device = self.acquire_crypt_device()?;
+        set_keyslot_pbkdf(&mut device, pbkdf_params)?;
+        let passphrase = Self::passphrase(&mut device, self.encryption_info().key_description())?;
+        let token_id = add_keyring_binding(&mut device, key_desc, &passphrase)?;
+        self.metadata
+            .keyring_bindings
+            .push((token_id, key_desc.clone()));
+        Ok(())
+    }
+
+    /// Remove an additional keyring binding from the device.
+    pub fn remove_keyring_binding(&mut self, key_desc: &KeyDescription) -> StratisResult<()> {
+        let index = self
+            .metadata
+            .keyring_bindings
+            .iter()
+            .position(|(_, kd)| kd == key_desc)
+            .ok_or_else(|| {
+                StratisError::Msg(format!(
+                    "Device has no additional keyring binding with key description {}",
+                    key_desc.as_application_str()
+                ))
+            })?;
+
+        let mut device = self.acquire_crypt_device()?;
+        remove_keyring_binding(&mut device, self.metadata.keyring_bindings[index].0)?;
+        self.metadata.keyring_bindings.remove(index);
+        Ok(())
+    }
+
     /// Rename the pool in the LUKS2 token.
     pub fn rename_pool_in_metadata(&mut self, pool_name: Name) -> StratisResult<()> {
         let mut device = self.acquire_crypt_device()?;
         replace_pool_name(&mut device, pool_name)
     }
 
+    /// Record in the LUKS2 token whether the pool was stopped on purpose.
+    pub fn set_stopped_in_metadata(&mut self, stopped: bool) -> StratisResult<()> {
+        if self.metadata.stopped == stopped {
+            return Ok(());
+        }
+        let mut device = self.acquire_crypt_device()?;
+        replace_stopped(&mut device, stopped)?;
+        self.metadata.stopped = stopped;
+        Ok(())
+    }
+
     /// Decrypt a Clevis passphrase and return it securely.
     fn clevis_decrypt(device: &mut CryptDevice) -> StratisResult<Option<SizedKeyMemory>> {
         let mut token = match device.token_handle().json_get(CLEVIS_LUKS_TOKEN_ID).ok() {
@@ -667,18 +1211,269 @@ impl CryptHandle {
         clevis_decrypt(&jwe).map(Some)
     }
 
-    /// Deactivate the device referenced by the current device handle.
+    /// Get a passphrase that unlocks the device: the passphrase in the kernel
+    /// keyring if the device is bound to one, otherwise the passphrase
+    /// decrypted by Clevis.
+    fn passphrase(
+        device: &mut CryptDevice,
+        key_description: Option<&KeyDescription>,
+    ) -> StratisResult<SizedKeyMemory> {
+        match key_description {
+            Some(kd) => key_desc_to_passphrase(kd),
+            None => Self::clevis_decrypt(device)?.ok_or_else(|| {
+                StratisError::Msg(
+                    "No kernel keyring or Clevis binding was found with which to unlock the device"
+                        .to_string(),
+                )
+            }),
+        }
+    }
+
+    /// Start an online reencryption of the device with a new volume key. A
+    /// keyslot for the new volume key is added for each unlock method to
+    /// which the device is bound, and the token of the unlock method is
+    /// assigned to it; libcryptsetup removes the keyslots for the old volume
+    /// key once the reencryption is complete. Only the reencryption metadata
+    /// is written here; the data is reencrypted by continue_reencryption().
+    ///
+    /// The new volume key is used with the cipher and the key size in the
+    /// crypt parameters, or else with those of the current volume key. The
+    /// new keyslots are created with the key derivation parameters in the
+    /// crypt parameters, if any. The encryption sector size of an active
+    /// device can not be changed.
+    pub fn start_reencryption(&self, crypt_params: &CryptParams) -> StratisResult<()> {
+        let mut device = self.acquire_crypt_device()?;
+
+        let sector_size = convert_int!(device.status_handle().get_sector_size(), i32, u32)?;
+        if let Some(requested) = crypt_params.sector_size.filter(|s| *s != sector_size) {
+            return Err(StratisError::Msg(format!(
+                "The encryption sector size of device {} is {} bytes and can not be changed to {} bytes",
+                self.luks2_device_path().display(),
+                sector_size,
+                requested
+            )));
+        }
+
+        let mut passphrases = Vec::new();
+        if let Some(kd) = self.encryption_info().key_description() {
+            passphrases.push((LUKS2_TOKEN_ID, key_desc_to_passphrase(kd)?));
+        }
+        if let Some(passphrase) = Self::clevis_decrypt(&mut device)? {
+            passphrases.push((CLEVIS_LUKS_TOKEN_ID, passphrase));
+        }
+        for (token_id, kd) in self.metadata.keyring_bindings.iter() {
+            passphrases.push((*token_id, key_desc_to_passphrase(kd)?));
+        }
+
+        let (cipher, cipher_mode) = match crypt_params.cipher_and_mode()? {
+            Some((cipher, mode)) => (cipher.to_string(), mode.to_string()),
+            None => (
+                device.status_handle().get_cipher()?,
+                device.status_handle().get_cipher_mode()?,
+            ),
+        };
+        let key_size = match crypt_params.key_size {
+            Some(bits) => convert_int!(bits / 8, u32, usize)?,
+            None => convert_int!(device.status_handle().get_volume_key_size(), i32, usize)?,
+        };
+        set_keyslot_pbkdf(&mut device, Some(crypt_params))?;
+
+        let mut volume_key = SafeMemHandle::alloc(key_size)?;
+        thread_rng().fill(volume_key.as_mut());
+
+        let mut keyslots = Vec::new();
+        let result = passphrases
+            .iter()
+            .try_for_each(|(token_id, passphrase)| -> StratisResult<()> {
+                let keyslot = device.keyslot_handle().add_by_key(
+                    None,
+                    Some(volume_key.as_ref()),
+                    passphrase.as_ref(),
+                    CryptVolumeKey::NO_SEGMENT,
+                )?;
+                keyslots.push(keyslot);
+                device
+                    .token_handle()
+                    .assign_keyslot(*token_id, Some(keyslot))?;
+                Ok(())
+            })
+            .and_then(|_| {
+                let (_, passphrase) = passphrases.first().ok_or_else(|| {
+                    StratisError::Msg(
+                        "No kernel keyring or Clevis binding was found with which to unlock the device"
+                            .to_string(),
+                    )
+                })?;
+                device.reencrypt_handle().reencrypt_init_by_passphrase(
+                    None,
+                    passphrase.as_ref(),
+                    None,
+                    keyslots.first().copied(),
+                    Some((cipher.as_str(), cipher_mode.as_str())),
+                    reencryption_params(sector_size, CryptReencrypt::INITIALIZE_ONLY),
+                )?;
+                Ok(())
+            });
+
+        if let Err(e) = result {
+            for keyslot in keyslots {
+                if let Err(destroy_err) = device.keyslot_handle().destroy(keyslot) {
+                    warn!(
+                        "Failed to remove keyslot {} for the new volume key of device {}: {}",
+                        keyslot,
+                        self.luks2_device_path().display(),
+                        destroy_err
+                    );
+                }
+            }
+            return Err(StratisError::Chained(
+                format!(
+                    "Failed to start the reencryption of device {}",
+                    self.luks2_device_path().display()
+                ),
+                Box::new(e),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Whether an online reencryption of the device is in progress.
+    pub fn is_reencrypting(&self) -> StratisResult<bool> {
+        Ok(!matches!(
+            self.acquire_crypt_device()?
+                .reencrypt_handle()
+                .status(resume_params(CryptReencrypt::empty()))?,
+            CryptReencryptInfo::None
+        ))
+    }
+
+    /// Continue the online reencryption of the device, if one is in progress,
+    /// for about the given duration. The reencryption is interrupted after
+    /// the first chunk that completes once the duration has elapsed; its
+    /// progress is recorded in the LUKS2 header, so that it can be continued
+    /// later, even after a restart.
+    ///
+    /// Returns the percentage of the device that has been processed, or None
+    /// if no reencryption is in progress.
+    pub fn continue_reencryption(&self, duration: Duration) -> StratisResult<Option<u8>> {
+        fn record_progress(size: u64, offset: u64, slice: Option<&mut ReencryptionSlice>) -> bool {
+            match slice {
+                Some(slice) => {
+                    if size != 0 {
+                        slice.progress =
+                            u8::try_from(offset.saturating_mul(100) / size).unwrap_or(100);
+                    }
+                    Instant::now() >= slice.deadline
+                }
+                None => false,
+            }
+        }
+
+        c_progress_callback!(c_record_progress, ReencryptionSlice, record_progress);
+
+        let mut device = self.acquire_crypt_device()?;
+        match device
+            .reencrypt_handle()
+            .status(resume_params(CryptReencrypt::empty()))?
+        {
+            CryptReencryptInfo::None => return Ok(None),
+            CryptReencryptInfo::Clean => (),
+            CryptReencryptInfo::Crash => {
+                let passphrase =
+                    Self::passphrase(&mut device, self.encryption_info().key_description())?;
+                log_on_failure!(
+                    device.reencrypt_handle().reencrypt_init_by_passphrase(
+                        None,
+                        passphrase.as_ref(),
+                        None,
+                        None,
+                        None,
+                        resume_params(CryptReencrypt::RECOVERY),
+                    ),
+                    "Failed to recover the interrupted reencryption of device {}",
+                    self.luks2_device_path().display()
+                );
+            }
+            CryptReencryptInfo::Invalid => {
+                return Err(StratisError::Msg(format!(
+                    "The reencryption metadata on device {} is invalid",
+                    self.luks2_device_path().display()
+                )));
+            }
+        }
+
+        let passphrase = Self::passphrase(&mut device, self.encryption_info().key_description())?;
+        log_on_failure!(
+            device.reencrypt_handle().reencrypt_init_by_passphrase(
+                Some(&self.activation_name().to_string()),
+                passphrase.as_ref(),
+                None,
+                None,
+                None,
+                resume_params(CryptReencrypt::RESUME_ONLY),
+            ),
+            "Failed to resume the reencryption of device {}",
+            self.luks2_device_path().display()
+        );
+
+        let mut slice = ReencryptionSlice {
+            deadline: Instant::now() + duration,
+            progress: 0,
+        };
+        log_on_failure!(
+            device
+                .reencrypt_handle()
+                .reencrypt2::<ReencryptionSlice>(Some(c_record_progress), Some(&mut slice)),
+            "Failed to reencrypt device {}",
+            self.luks2_device_path().display()
+        );
+
+        match device
+            .reencrypt_handle()
+            .status(resume_params(CryptReencrypt::empty()))?
+        {
+            CryptReencryptInfo::None => Ok(Some(100)),
+            _ => Ok(Some(slice.progress)),
+        }
+    }
+
+    /// Deactivate the device referenced by the current device handle and
+    /// remove the integrity device beneath it, if any.
     pub fn deactivate(&self) -> StratisResult<()> {
+        self.deactivate_encryption()?;
+        self.remove_integrity()
+    }
+
+    /// Deactivate the device referenced by the current device handle, but
+    /// leave the integrity device beneath it, if any, set up.
+    pub fn deactivate_encryption(&self) -> StratisResult<()> {
         ensure_inactive(&mut self.acquire_crypt_device()?, self.activation_name())
     }
 
-    /// Wipe all LUKS2 metadata on the device safely using libcryptsetup.
+    /// Destroy every keyslot of the encrypted device.
+    pub fn erase_keyslots(&self) -> StratisResult<()> {
+        erase_keyslots(&mut self.acquire_crypt_device()?, self.luks2_device_path())
+    }
+
+    /// Wipe all LUKS2 metadata on the device safely using libcryptsetup and
+    /// remove the integrity device beneath it, if any.
     pub fn wipe(&self) -> StratisResult<()> {
         ensure_wiped(
             &mut self.acquire_crypt_device()?,
             self.luks2_device_path(),
             self.activation_name(),
-        )
+        )?;
+        self.remove_integrity()
+    }
+
+    /// Remove the integrity device beneath the encryption, if any. The
+    /// encrypted device must already have been deactivated.
+    fn remove_integrity(&self) -> StratisResult<()> {
+        match self.metadata.integrity {
+            Some(_) => remove_integrity_devices(self.metadata.identifiers.device_uuid),
+            None => Ok(()),
+        }
     }
 
     /// Get the size of the logical device built on the underlying encrypted physical
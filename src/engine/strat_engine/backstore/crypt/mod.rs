@@ -13,8 +13,9 @@ pub use self::{
     consts::CLEVIS_TANG_TRUST_URL,
     handle::CryptHandle,
     shared::{
-        back_up_luks_header, crypt_metadata_size, interpret_clevis_config, register_clevis_token,
-        restore_luks_header, set_up_crypt_logging,
+        back_up_luks_header, back_up_luks_headers, crypt_metadata_size, interpret_clevis_config,
+        register_clevis_token, restore_luks_header, restore_luks_header_from_archive,
+        set_up_crypt_logging,
     },
 };
 
@@ -48,7 +49,10 @@ mod tests {
             ns::{unshare_mount_namespace, MemoryFilesystem},
             tests::{crypt, loopbacked, real},
         },
-        types::{DevUuid, EncryptionInfo, KeyDescription, Name, PoolUuid, UnlockMethod},
+        types::{
+            CryptParams, DevUuid, EncryptionInfo, KeyDescription, Name, Pbkdf, PoolUuid,
+            UnlockMethod,
+        },
     };
 
     use super::*;
@@ -73,6 +77,7 @@ mod tests {
             dev_uuid,
             pool_name,
             &EncryptionInfo::KeyDesc(key_description),
+            &CryptParams::default(),
             None,
         );
 
@@ -117,6 +122,7 @@ mod tests {
                     dev_uuid,
                     pool_name.clone(),
                     &EncryptionInfo::KeyDesc(key_desc.clone()),
+                    &CryptParams::default(),
                     None,
                 )
                 .unwrap();
@@ -198,6 +204,7 @@ mod tests {
                 dev_uuid,
                 pool_name,
                 &EncryptionInfo::KeyDesc(key_desc.clone()),
+                &CryptParams::default(),
                 None,
             )
             .unwrap();
@@ -342,7 +349,11 @@ mod tests {
                     dev_uuid,
                     pool_name,
                     &EncryptionInfo::KeyDesc(key_description.clone()),
-                    Some(4096u32),
+                    &CryptParams {
+                        sector_size: Some(4096u32),
+                        ..CryptParams::default()
+                    },
+                    None,
                 )
                 .unwrap();
             }
@@ -353,6 +364,91 @@ mod tests {
         loopbacked::test_with_spec(&loopbacked::DeviceLimits::Exactly(1, None), the_test);
     }
 
+    #[test]
+    // Test that whether the pool was stopped on purpose is recorded in the
+    // LUKS2 token and can be read back without unlocking the device.
+    fn loop_test_stopped_in_metadata() {
+        fn the_test(paths: &[&Path]) {
+            fn test_stopped(paths: &[&Path], key_description: &KeyDescription) {
+                let mut handle = CryptHandle::initialize(
+                    paths[0],
+                    PoolUuid::new_v4(),
+                    DevUuid::new_v4(),
+                    Name::new("pool_name".to_string()),
+                    &EncryptionInfo::KeyDesc(key_description.clone()),
+                    &CryptParams::default(),
+                    None,
+                )
+                .unwrap();
+                assert!(!handle.stopped());
+
+                handle.set_stopped_in_metadata(true).unwrap();
+                handle.deactivate().unwrap();
+                assert!(
+                    CryptHandle::load_metadata(paths[0])
+                        .unwrap()
+                        .unwrap()
+                        .stopped
+                );
+
+                let mut handle = CryptHandle::setup(paths[0], Some(UnlockMethod::Keyring))
+                    .unwrap()
+                    .unwrap();
+                assert!(handle.stopped());
+                handle.set_stopped_in_metadata(false).unwrap();
+                assert!(
+                    !CryptHandle::load_metadata(paths[0])
+                        .unwrap()
+                        .unwrap()
+                        .stopped
+                );
+                handle.wipe().unwrap();
+            }
+
+            crypt::insert_and_cleanup_key(paths, test_stopped);
+        }
+
+        loopbacked::test_with_spec(&loopbacked::DeviceLimits::Exactly(1, None), the_test);
+    }
+
+    #[test]
+    // Test that a device is formatted with the given crypt parameters and
+    // that they are read back from its LUKS2 header.
+    fn loop_test_crypt_params() {
+        fn the_test(paths: &[&Path]) {
+            fn test_crypt_params(paths: &[&Path], key_description: &KeyDescription) {
+                let crypt_params = CryptParams {
+                    cipher: Some("aes-xts-plain64".to_string()),
+                    key_size: Some(256),
+                    sector_size: Some(4096),
+                    pbkdf: Some(Pbkdf::Argon2id),
+                    pbkdf_memory: Some(32768),
+                    pbkdf_iterations: Some(4),
+                    pbkdf_parallel: Some(1),
+                };
+
+                let handle = CryptHandle::initialize(
+                    paths[0],
+                    PoolUuid::new_v4(),
+                    DevUuid::new_v4(),
+                    Name::new("pool_name".to_string()),
+                    &EncryptionInfo::KeyDesc(key_description.clone()),
+                    &crypt_params,
+                    None,
+                )
+                .unwrap();
+
+                assert_eq!(handle.crypt_params().unwrap(), crypt_params);
+
+                handle.wipe().unwrap();
+            }
+
+            crypt::insert_and_cleanup_key(paths, test_crypt_params);
+        }
+
+        loopbacked::test_with_spec(&loopbacked::DeviceLimits::Exactly(1, None), the_test);
+    }
+
     fn test_both_initialize(paths: &[&Path]) {
         fn both_initialize(paths: &[&Path], key_desc: &KeyDescription) {
             unshare_mount_namespace().unwrap();
@@ -371,6 +467,7 @@ mod tests {
                         json!({"url": env::var("TANG_URL").expect("TANG_URL env var required"), "stratis:tang:trust_url": true}),
                     ),
                 ),
+                &CryptParams::default(),
                 None,
             ).unwrap();
 
@@ -443,6 +540,7 @@ mod tests {
                 "tang".to_string(),
                 json!({"url": env::var("TANG_URL").expect("TANG_URL env var required"), "stratis:tang:trust_url": true}),
             )),
+            &CryptParams::default(),
             None,
         )
         .unwrap();
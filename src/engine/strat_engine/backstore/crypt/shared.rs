@@ -3,15 +3,20 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::{
+    collections::HashMap,
     fmt::{self, Formatter},
-    fs::OpenOptions,
+    fs::{self, File, OpenOptions},
     io::Write,
     mem::forget,
+    os::unix::{
+        fs::{OpenOptionsExt, PermissionsExt},
+        io::IntoRawFd,
+    },
     path::{Path, PathBuf},
     slice::from_raw_parts_mut,
 };
 
-use data_encoding::BASE64URL_NOPAD;
+use data_encoding::{BASE64, BASE64URL_NOPAD};
 use either::Either;
 use serde::{
     de::{Error, MapAccess, Visitor},
@@ -22,40 +27,47 @@ use serde_json::{from_value, to_value, Map, Value};
 use sha2::{Digest, Sha256};
 use tempfile::TempDir;
 
-use devicemapper::{Bytes, DevId, DmName, DmNameBuf, DmOptions};
+use devicemapper::{Bytes, DevId, DmName, DmNameBuf, DmOptions, Sectors};
 use libcryptsetup_rs::{
     c_uint,
     consts::{
         flags::{CryptActivate, CryptVolumeKey, CryptWipe},
         vals::{
             CryptDebugLevel, CryptLogLevel, CryptStatusInfo, CryptWipePattern, EncryptionFormat,
+            KeyslotInfo,
         },
     },
-    register, set_debug_level, set_log_callback, CryptDevice, CryptInit, TokenInput,
+    register, set_debug_level, set_log_callback, CryptDevice, CryptInit, SafeMemHandle, TokenInput,
 };
 
 use crate::{
     engine::{
+        engine::MAX_STRATIS_PASS_SIZE,
+        shared::set_key_shared,
         strat_engine::{
             backstore::{
                 crypt::{
                     consts::{
                         CLEVIS_LUKS_TOKEN_ID, CLEVIS_TANG_TRUST_URL, CLEVIS_TOKEN_NAME,
                         DEFAULT_CRYPT_KEYSLOTS_SIZE, DEFAULT_CRYPT_METADATA_SIZE,
-                        LUKS2_SECTOR_SIZE, LUKS2_TOKEN_ID, LUKS2_TOKEN_TYPE,
-                        STRATIS_TOKEN_DEVNAME_KEY, STRATIS_TOKEN_DEV_UUID_KEY, STRATIS_TOKEN_ID,
-                        STRATIS_TOKEN_POOLNAME_KEY, STRATIS_TOKEN_POOL_UUID_KEY,
-                        STRATIS_TOKEN_TYPE, TOKEN_KEYSLOTS_KEY, TOKEN_TYPE_KEY,
+                        LUKS2_MAX_KEYSLOTS, LUKS2_MAX_TOKENS, LUKS2_SECTOR_SIZE, LUKS2_TOKEN_ID,
+                        LUKS2_TOKEN_TYPE, STRATIS_TOKEN_DEVNAME_KEY, STRATIS_TOKEN_DEV_UUID_KEY,
+                        STRATIS_TOKEN_ID, STRATIS_TOKEN_INTEGRITY_KEY, STRATIS_TOKEN_POOLNAME_KEY,
+                        STRATIS_TOKEN_POOL_UUID_KEY, STRATIS_TOKEN_STOPPED_KEY, STRATIS_TOKEN_TYPE,
+                        TOKEN_KEYSLOTS_KEY, TOKEN_TYPE_KEY,
                     },
                     handle::{CryptHandle, CryptMetadata},
                 },
                 devices::get_devno_from_path,
+                integrity::IntegrityDev,
             },
             cmd::clevis_decrypt,
+            device::blkdev_size,
             dm::get_dm,
             dm::DEVICEMAPPER_PATH,
             keys,
-            metadata::StratisIdentifiers,
+            metadata::{device_identifiers, StratisIdentifiers},
+            serde_structs::IntegritySave,
         },
         types::{
             DevUuid, DevicePath, EncryptionInfo, KeyDescription, Name, PoolUuid, SizedKeyMemory,
@@ -87,6 +99,11 @@ pub struct StratisLuks2Token {
     pub devname: DmNameBuf,
     pub identifiers: StratisIdentifiers,
     pub pool_name: Option<Name>,
+    /// The dm-integrity layer beneath the encryption, if any.
+    pub integrity: Option<IntegritySave>,
+    /// True if the pool was stopped on purpose, so that it can be told
+    /// without unlocking the device; see StratPool::stop().
+    pub stopped: bool,
 }
 
 impl Serialize for StratisLuks2Token {
@@ -109,6 +126,12 @@ impl Serialize for StratisLuks2Token {
         if let Some(ref pn) = self.pool_name {
             map_serializer.serialize_entry(STRATIS_TOKEN_POOLNAME_KEY, pn)?;
         }
+        if let Some(ref integrity) = self.integrity {
+            map_serializer.serialize_entry(STRATIS_TOKEN_INTEGRITY_KEY, integrity)?;
+        }
+        if self.stopped {
+            map_serializer.serialize_entry(STRATIS_TOKEN_STOPPED_KEY, &true)?;
+        }
         map_serializer.end()
     }
 }
@@ -137,6 +160,8 @@ impl<'de> Deserialize<'de> for StratisLuks2Token {
                 let mut p_uuid = None;
                 let mut d_uuid = None;
                 let mut p_name = None;
+                let mut integrity = None;
+                let mut stopped = None;
 
                 while let Some((k, v)) = map.next_entry::<String, Value>()? {
                     match k.as_str() {
@@ -158,6 +183,12 @@ impl<'de> Deserialize<'de> for StratisLuks2Token {
                         STRATIS_TOKEN_POOLNAME_KEY => {
                             p_name = Some(v);
                         }
+                        STRATIS_TOKEN_INTEGRITY_KEY => {
+                            integrity = Some(v);
+                        }
+                        STRATIS_TOKEN_STOPPED_KEY => {
+                            stopped = Some(v);
+                        }
                         st => {
                             return Err(A::Error::custom(format!("Found unrecognized key {st}")));
                         }
@@ -246,6 +277,18 @@ impl<'de> Deserialize<'de> for StratisLuks2Token {
                             }
                             None => None,
                         };
+                        let integrity = integrity
+                            .map(|v| from_value::<IntegritySave>(v).map_err(A::Error::custom))
+                            .transpose()?;
+                        let stopped = match stopped {
+                            Some(Value::Bool(b)) => b,
+                            Some(_) => {
+                                return Err(A::Error::custom(format!(
+                                    "Unrecognized value type for {STRATIS_TOKEN_STOPPED_KEY}"
+                                )))
+                            }
+                            None => false,
+                        };
                         Ok(StratisLuks2Token {
                             devname,
                             identifiers: StratisIdentifiers {
@@ -253,6 +296,8 @@ impl<'de> Deserialize<'de> for StratisLuks2Token {
                                 device_uuid,
                             },
                             pool_name,
+                            integrity,
+                            stopped,
                         })
                     })
             }
@@ -274,8 +319,31 @@ pub fn acquire_crypt_device(physical_path: &Path) -> StratisResult<CryptDevice>
     })
 }
 
+/// Acquire a crypt device handle for the LUKS2 header on the physical device
+/// the data of which is accessed through a separate data device, the
+/// integrity device beneath the encryption, or return an error.
+pub fn acquire_crypt_device_with_data(
+    physical_path: &Path,
+    data_path: &Path,
+) -> StratisResult<CryptDevice> {
+    let mut device = log_on_failure!(
+        CryptInit::init_with_data_device(physical_path, data_path),
+        "Failed to acquire a context for device {} with data device {}",
+        physical_path.display(),
+        data_path.display()
+    );
+    log_on_failure!(
+        device
+            .context_handle()
+            .load::<()>(Some(EncryptionFormat::Luks2), None),
+        "Failed to load the LUKS2 header of device {}",
+        physical_path.display()
+    );
+    Ok(device)
+}
+
 /// Get the passphrase associated with a given key description.
-fn key_desc_to_passphrase(key_description: &KeyDescription) -> StratisResult<SizedKeyMemory> {
+pub fn key_desc_to_passphrase(key_description: &KeyDescription) -> StratisResult<SizedKeyMemory> {
     let key_option = log_on_failure!(
         read_key(key_description),
         "Failed to read key with key description {} from keyring",
@@ -402,6 +470,9 @@ pub fn load_crypt_metadata(
         None => None,
     };
     let clevis_info = clevis_info_from_metadata(device)?;
+    let keyring_bindings = keyring_bindings_from_metadata(device)?;
+    let integrity = integrity_from_metadata(device)?;
+    let stopped = stopped_from_metadata(device)?;
 
     let encryption_info =
         if let Some(info) = EncryptionInfo::from_options((key_description, clevis_info)) {
@@ -421,10 +492,13 @@ pub fn load_crypt_metadata(
         physical_path: physical,
         identifiers,
         encryption_info,
+        keyring_bindings,
         activation_name,
         pool_name,
         device: devno,
         activated_path,
+        integrity,
+        stopped,
     }))
 }
 
@@ -446,12 +520,23 @@ pub fn setup_crypt_handle(
         .exists()
     {
         if let Some(unlock) = unlock_method {
-            activate(
-                device,
-                metadata.encryption_info.key_description(),
-                unlock,
-                &metadata.activation_name,
-            )?;
+            match metadata.integrity {
+                Some(ref integrity) => activate_with_integrity(
+                    physical_path,
+                    metadata.identifiers.device_uuid,
+                    integrity,
+                    false,
+                    metadata.encryption_info.key_description(),
+                    unlock,
+                    &metadata.activation_name,
+                )?,
+                None => activate(
+                    device,
+                    metadata.encryption_info.key_description(),
+                    unlock,
+                    &metadata.activation_name,
+                )?,
+            }
         }
     }
 
@@ -460,8 +545,10 @@ pub fn setup_crypt_handle(
         metadata.identifiers.pool_uuid,
         metadata.identifiers.device_uuid,
         metadata.encryption_info,
+        metadata.keyring_bindings,
         metadata.pool_name,
         metadata.device,
+        metadata.integrity,
     )))
 }
 
@@ -756,34 +843,42 @@ pub fn activate(
     unlock_method: UnlockMethod,
     name: &DmName,
 ) -> StratisResult<()> {
-    if let (Some(kd), UnlockMethod::Keyring) = (key_desc, unlock_method) {
-        let key_description_missing = keys::search_key_persistent(kd)
-            .map_err(|_| {
+    if unlock_method == UnlockMethod::Any {
+        return activate_any(device, key_desc, name);
+    }
+
+    if let UnlockMethod::Keyfile(ref keyfile) = unlock_method {
+        let keyslot = get_keyslot_number(device, LUKS2_TOKEN_ID)?
+            .and_then(|keyslots| keyslots.first().copied())
+            .ok_or_else(|| {
                 StratisError::Msg(format!(
-                    "Searching the persistent keyring for the key description {} failed.",
-                    kd.as_application_str(),
+                    "Device {name} is not bound to a passphrase and can not be unlocked with a keyfile"
                 ))
-            })?
-            .is_none();
-        if key_description_missing {
-            warn!(
-                "Key description {} was not found in the keyring",
-                kd.as_application_str()
-            );
-            return Err(StratisError::Msg(format!(
-                "The key description \"{}\" is not currently set.",
-                kd.as_application_str(),
-            )));
-        }
+            })?;
+        let passphrase = read_keyfile(keyfile)?;
+        log_on_failure!(
+            device.activate_handle().activate_by_passphrase(
+                Some(&name.to_string()),
+                Some(keyslot),
+                passphrase.as_ref(),
+                CryptActivate::empty(),
+            ),
+            "Failed to activate device with name {} using keyfile {}",
+            name,
+            keyfile.display()
+        );
+
+        return device_is_active(Some(device), name);
+    }
+
+    if unlock_method == UnlockMethod::Keyring {
+        return activate_keyring(device, key_desc, name);
     }
+
     log_on_failure!(
         device.token_handle().activate_by_token::<()>(
             Some(&name.to_string()),
-            Some(if unlock_method == UnlockMethod::Keyring {
-                LUKS2_TOKEN_ID
-            } else {
-                CLEVIS_LUKS_TOKEN_ID
-            }),
+            Some(CLEVIS_LUKS_TOKEN_ID),
             None,
             CryptActivate::empty(),
         ),
@@ -797,6 +892,144 @@ pub fn activate(
     Ok(())
 }
 
+/// Activate encrypted Stratis device with the first of its keyring bindings
+/// whose key is set in the kernel keyring, starting with the binding to
+/// key_desc.
+fn activate_keyring(
+    device: &mut CryptDevice,
+    key_desc: Option<&KeyDescription>,
+    name: &DmName,
+) -> StratisResult<()> {
+    let bindings = key_desc
+        .map(|kd| (LUKS2_TOKEN_ID, kd.clone()))
+        .into_iter()
+        .chain(keyring_bindings_from_metadata(device)?)
+        .collect::<Vec<_>>();
+
+    let mut missing = Vec::new();
+    for (token_id, kd) in bindings {
+        let key_description_missing = keys::search_key_persistent(&kd)
+            .map_err(|_| {
+                StratisError::Msg(format!(
+                    "Searching the persistent keyring for the key description {} failed.",
+                    kd.as_application_str(),
+                ))
+            })?
+            .is_none();
+        if key_description_missing {
+            warn!(
+                "Key description {} was not found in the keyring",
+                kd.as_application_str()
+            );
+            missing.push(format!("\"{}\"", kd.as_application_str()));
+            continue;
+        }
+
+        log_on_failure!(
+            device.token_handle().activate_by_token::<()>(
+                Some(&name.to_string()),
+                Some(token_id),
+                None,
+                CryptActivate::empty(),
+            ),
+            "Failed to activate device with name {}",
+            name
+        );
+
+        // Check activation status.
+        return device_is_active(Some(device), name);
+    }
+
+    Err(StratisError::Msg(match missing.len() {
+        0 => format!("Device {name} is not bound to a passphrase in the kernel keyring"),
+        1 => format!("The key description {} is not currently set.", missing[0]),
+        _ => format!(
+            "None of the key descriptions {} are currently set.",
+            missing.join(", ")
+        ),
+    }))
+}
+
+/// Set up the integrity device beneath the encryption of the encrypted
+/// Stratis device on the physical device, formatting it if format is true,
+/// and activate the encrypted device on top of it. The integrity device is
+/// removed again if the activation fails.
+pub fn activate_with_integrity(
+    physical_path: &Path,
+    dev_uuid: DevUuid,
+    integrity: &IntegritySave,
+    format: bool,
+    key_desc: Option<&KeyDescription>,
+    unlock_method: UnlockMethod,
+    name: &DmName,
+) -> StratisResult<()> {
+    let mut integrity_dev = IntegrityDev::setup(
+        dev_uuid,
+        get_devno_from_path(physical_path)?,
+        integrity.hash,
+        integrity.meta_start,
+        integrity.meta_length,
+        format,
+    )?;
+    let result = acquire_crypt_device_with_data(physical_path, &integrity_dev.devnode())
+        .and_then(|mut device| activate(&mut device, key_desc, unlock_method, name));
+    if result.is_err() {
+        if let Err(e) = integrity_dev.teardown() {
+            warn!(
+                "Failed to remove the integrity device beneath encrypted device {}: {}",
+                name, e
+            );
+        }
+    }
+    result
+}
+
+/// Activate encrypted Stratis device by trying each unlock method that the
+/// device is configured with in turn: first the kernel keyring, then Clevis.
+fn activate_any(
+    device: &mut CryptDevice,
+    key_desc: Option<&KeyDescription>,
+    name: &DmName,
+) -> StratisResult<()> {
+    let mut methods = Vec::new();
+    if key_desc.is_some() || !keyring_bindings_from_metadata(device)?.is_empty() {
+        methods.push(UnlockMethod::Keyring);
+    }
+    if clevis_info_from_metadata(device)?.is_some() {
+        methods.push(UnlockMethod::Clevis);
+    }
+
+    for method in methods {
+        info!("Attempting to unlock device {} with {}", name, method);
+        match activate(device, key_desc, method.clone(), name) {
+            Ok(()) => {
+                info!("Unlocked device {} with {}", name, method);
+                return Ok(());
+            }
+            Err(e) => warn!("Failed to unlock device {} with {}: {}", name, method, e),
+        }
+    }
+
+    Err(StratisError::Msg(format!(
+        "Device {name} could not be unlocked with any of the unlock methods it is configured with"
+    )))
+}
+
+/// Read the passphrase from the keyfile at the given path. Return an error
+/// if the keyfile is accessible to users other than its owner.
+fn read_keyfile(keyfile: &Path) -> StratisResult<SizedKeyMemory> {
+    let file = File::open(keyfile)?;
+    if file.metadata()?.permissions().mode() & 0o077 != 0 {
+        return Err(StratisError::Msg(format!(
+            "Keyfile {} is accessible to users other than its owner; refusing to use it",
+            keyfile.display()
+        )));
+    }
+    let mut memory = SafeMemHandle::alloc(MAX_STRATIS_PASS_SIZE)?;
+    let bytes = set_key_shared(file.into_raw_fd(), memory.as_mut())?;
+    Ok(SizedKeyMemory::new(memory, bytes))
+}
+
 /// Get a list of all keyslots associated with the LUKS2 token.
 /// This is necessary because attempting to destroy an uninitialized
 /// keyslot will result in an error.
@@ -902,6 +1135,37 @@ pub fn wipe_fallback(path: &Path, causal_error: StratisError) -> StratisError {
 /// with devicemapper and cryptsetup. `physical_path` should be the path to
 /// the device node of the physical storage backing the encrypted volume.
 /// This method is idempotent and leaves the disk as wiped.
+/// Destroy every keyslot of an encrypted device, including keyslots that no
+/// token refers to. Without any keyslot the volume key, and with it the data
+/// on the device, can no longer be recovered.
+pub fn erase_keyslots(device: &mut CryptDevice, physical_path: &Path) -> StratisResult<()> {
+    for keyslot in 0..LUKS2_MAX_KEYSLOTS {
+        let status = log_on_failure!(
+            device
+                .keyslot_handle()
+                .status(convert_int!(keyslot, c_uint, libc::c_int)?),
+            "Failed to get the status of keyslot {} of device {}",
+            keyslot,
+            physical_path.display()
+        );
+        if matches!(status, KeyslotInfo::Inactive | KeyslotInfo::Invalid) {
+            continue;
+        }
+        log_on_failure!(
+            device.keyslot_handle().destroy(keyslot),
+            "Failed to destroy keyslot {} of device {}",
+            keyslot,
+            physical_path.display()
+        );
+        info!(
+            "Erased keyslot {} of device {}",
+            keyslot,
+            physical_path.display()
+        );
+    }
+    Ok(())
+}
+
 pub fn ensure_wiped(
     device: &mut CryptDevice,
     physical_path: &Path,
@@ -1042,6 +1306,93 @@ pub fn key_desc_from_metadata(device: &mut CryptDevice) -> Option<String> {
     device.token_handle().luks2_keyring_get(LUKS2_TOKEN_ID).ok()
 }
 
+/// Query the Stratis metadata for the keyring bindings of the device in
+/// addition to the one in the LUKS2 keyring token slot, each with the ID of
+/// the token that records it.
+pub fn keyring_bindings_from_metadata(
+    device: &mut CryptDevice,
+) -> StratisResult<Vec<(c_uint, KeyDescription)>> {
+    let mut bindings = Vec::new();
+    for token_id in 0..LUKS2_MAX_TOKENS {
+        if [STRATIS_TOKEN_ID, LUKS2_TOKEN_ID, CLEVIS_LUKS_TOKEN_ID].contains(&token_id) {
+            continue;
+        }
+        match device.token_handle().json_get(token_id) {
+            Ok(json) if luks2_token_type_is_valid(&json) => (),
+            _ => continue,
+        }
+        let key_desc = device.token_handle().luks2_keyring_get(token_id)?;
+        match KeyDescription::from_system_key_desc(&key_desc) {
+            Some(Ok(kd)) => bindings.push((token_id, kd)),
+            Some(Err(e)) => warn!(
+                "Key description {} in LUKS2 token {} is not a valid Stratis key description: {}; ignoring",
+                key_desc, token_id, e
+            ),
+            None => warn!(
+                "Key description {} in LUKS2 token {} does not appear to be a Stratis key description; ignoring",
+                key_desc, token_id
+            ),
+        }
+    }
+    Ok(bindings)
+}
+
+/// Bind the device to an additional passphrase in the kernel keyring with a
+/// keyslot and a LUKS2 keyring token of its own. pass must be an existing
+/// passphrase of the device.
+///
+/// Returns the ID of the new token.
+pub fn add_keyring_binding(
+    device: &mut CryptDevice,
+    key_description: &KeyDescription,
+    pass: &SizedKeyMemory,
+) -> StratisResult<c_uint> {
+    let key = key_desc_to_passphrase(key_description)?;
+    let keyslot = log_on_failure!(
+        device
+            .keyslot_handle()
+            .add_by_passphrase(None, pass.as_ref(), key.as_ref()),
+        "Failed to add a keyslot for key description {}",
+        key_description.as_application_str()
+    );
+    let token_id = log_on_failure!(
+        device
+            .token_handle()
+            .luks2_keyring_set(None, &key_description.to_system_string()),
+        "Failed to initialize a LUKS2 token for key description {}",
+        key_description.as_application_str()
+    );
+    log_on_failure!(
+        device
+            .token_handle()
+            .assign_keyslot(token_id, Some(keyslot)),
+        "Failed to assign the LUKS2 token for key description {} to its keyslot",
+        key_description.as_application_str()
+    );
+    Ok(token_id)
+}
+
+/// Remove the keyring binding recorded in the LUKS2 token with the given ID
+/// and destroy its keyslots.
+pub fn remove_keyring_binding(device: &mut CryptDevice, token_id: c_uint) -> StratisResult<()> {
+    let keyslots = get_keyslot_number(device, token_id)?.ok_or_else(|| {
+        StratisError::Msg(format!(
+            "No LUKS2 keyring token was found in slot {token_id}"
+        ))
+    })?;
+    for keyslot in keyslots {
+        log_on_failure!(
+            device.keyslot_handle().destroy(keyslot),
+            "Failed partway through the removal of a kernel keyring binding \
+            which cannot be rolled back; manual intervention may be required"
+        )
+    }
+    device
+        .token_handle()
+        .json_set(TokenInput::RemoveToken(token_id))?;
+    Ok(())
+}
+
 /// Query the Stratis metadata for the pool name.
 pub fn pool_name_from_metadata(device: &mut CryptDevice) -> StratisResult<Option<Name>> {
     Ok(
@@ -1050,6 +1401,32 @@ pub fn pool_name_from_metadata(device: &mut CryptDevice) -> StratisResult<Option
     )
 }
 
+/// Query the Stratis metadata for the dm-integrity layer beneath the
+/// encryption.
+pub fn integrity_from_metadata(device: &mut CryptDevice) -> StratisResult<Option<IntegritySave>> {
+    Ok(
+        from_value::<StratisLuks2Token>(device.token_handle().json_get(STRATIS_TOKEN_ID)?)?
+            .integrity,
+    )
+}
+
+/// Query the Stratis metadata for whether the pool was stopped on purpose.
+pub fn stopped_from_metadata(device: &mut CryptDevice) -> StratisResult<bool> {
+    Ok(from_value::<StratisLuks2Token>(device.token_handle().json_get(STRATIS_TOKEN_ID)?)?.stopped)
+}
+
+/// Record in the Stratis LUKS2 token whether the pool was stopped on purpose.
+pub fn replace_stopped(device: &mut CryptDevice, stopped: bool) -> StratisResult<()> {
+    let mut token =
+        from_value::<StratisLuks2Token>(device.token_handle().json_get(STRATIS_TOKEN_ID)?)?;
+    token.stopped = stopped;
+    device.token_handle().json_set(TokenInput::ReplaceToken(
+        STRATIS_TOKEN_ID,
+        &to_value(token)?,
+    ))?;
+    Ok(())
+}
+
 /// Replace the old pool name in the Stratis LUKS2 token.
 pub fn replace_pool_name(device: &mut CryptDevice, new_name: Name) -> StratisResult<()> {
     let mut token =
@@ -1095,6 +1472,179 @@ pub fn restore_luks_header(dev_path: &Path, backup_path: &Path) -> StratisResult
     Ok(())
 }
 
+/// The LUKS2 header of one encrypted device in a header archive file, base64
+/// encoded, with the size of the device it was backed up from.
+#[derive(Debug, Deserialize, Serialize)]
+struct ArchivedLuksHeader {
+    header: String,
+    size: Sectors,
+}
+
+/// The LUKS2 headers of the encrypted devices of a pool, as written to a
+/// header archive file.
+#[derive(Debug, Deserialize, Serialize)]
+struct LuksHeaderArchive {
+    pool_uuid: PoolUuid,
+    headers: HashMap<DevUuid, ArchivedLuksHeader>,
+}
+
+/// Back up the LUKS2 headers of all the given devices of the pool with the
+/// given UUID to a single archive file at archive_path. The archive is
+/// written only if every header could be backed up. As the headers contain
+/// the keyslots, the archive is readable by its owner only.
+pub fn back_up_luks_headers(
+    pool_uuid: PoolUuid,
+    devices: &[(DevUuid, &Path)],
+    archive_path: &Path,
+) -> StratisResult<()> {
+    let tmp_dir = TempDir::new()?;
+    let headers = devices
+        .iter()
+        .map(|(dev_uuid, physical_path)| {
+            let header_path = back_up_luks_header(physical_path, &tmp_dir)?;
+            Ok((
+                *dev_uuid,
+                ArchivedLuksHeader {
+                    header: BASE64.encode(&fs::read(header_path)?),
+                    size: blkdev_size(&File::open(physical_path)?)?.sectors(),
+                },
+            ))
+        })
+        .collect::<StratisResult<HashMap<_, _>>>()?;
+
+    let mut archive = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(archive_path)?;
+    archive
+        .write_all(serde_json::to_string(&LuksHeaderArchive { pool_uuid, headers })?.as_bytes())?;
+    archive.sync_all()?;
+    Ok(())
+}
+
+/// Restore the LUKS2 header of the device with the given UUID in the pool
+/// with the given UUID to the device at physical_path from the archive at
+/// archive_path. The Stratis token in the archived header is checked
+/// against the pool and device UUIDs and the device at physical_path is
+/// verified to be that device and to be unused before the header is written.
+pub fn restore_luks_header_from_archive(
+    pool_uuid: PoolUuid,
+    dev_uuid: DevUuid,
+    physical_path: &Path,
+    archive_path: &Path,
+) -> StratisResult<()> {
+    let archive = serde_json::from_slice::<LuksHeaderArchive>(&fs::read(archive_path)?)?;
+    if archive.pool_uuid != pool_uuid {
+        return Err(StratisError::Msg(format!(
+            "Header archive {} belongs to pool {}, not pool {pool_uuid}",
+            archive_path.display(),
+            archive.pool_uuid,
+        )));
+    }
+    let archived = archive.headers.get(&dev_uuid).ok_or_else(|| {
+        StratisError::Msg(format!(
+            "Header archive {} contains no header for device {dev_uuid}",
+            archive_path.display()
+        ))
+    })?;
+
+    let tmp_dir = TempDir::new()?;
+    let header_path = tmp_dir.path().join("header");
+    fs::write(&header_path, BASE64.decode(archived.header.as_bytes())?)?;
+
+    let identifiers = device_from_physical_path(&header_path)?
+        .ok_or_else(|| {
+            StratisError::Msg(format!(
+                "Archived header for device {dev_uuid} is not a LUKS2 header"
+            ))
+        })
+        .and_then(|mut device| identifiers_from_metadata(&mut device))?;
+    if identifiers != StratisIdentifiers::new(pool_uuid, dev_uuid) {
+        return Err(StratisError::Msg(format!(
+            "The Stratis token in the archived header identifies {identifiers}, not device {dev_uuid} in pool {pool_uuid}"
+        )));
+    }
+
+    verify_restore_target(
+        StratisIdentifiers::new(pool_uuid, dev_uuid),
+        physical_path,
+        archived.size,
+    )?;
+
+    let mut device = log_on_failure!(
+        CryptInit::init(physical_path),
+        "Failed to acquire a context for device {}",
+        physical_path.display()
+    );
+    device
+        .backup_handle()
+        .header_restore(Some(EncryptionFormat::Luks2), &header_path)?;
+    Ok(())
+}
+
+/// Verify that the device at physical_path is the device with the given
+/// identifiers and that it is not in use before a LUKS2 header is restored
+/// to it. If the device still has a LUKS2 header or a Stratis header, that
+/// header must identify the device. Otherwise, its size must be the size
+/// recorded when the header was backed up. The device must not have any
+/// holders and must not be open exclusively.
+fn verify_restore_target(
+    identifiers: StratisIdentifiers,
+    physical_path: &Path,
+    size: Sectors,
+) -> StratisResult<()> {
+    let devno = get_devno_from_path(physical_path)?;
+    let holders_path = [
+        "/sys/dev/block",
+        &format!("{}:{}", devno.major, devno.minor),
+        "holders",
+    ]
+    .iter()
+    .collect::<PathBuf>();
+    if fs::read_dir(holders_path)?.next().is_some() {
+        return Err(StratisError::Msg(format!(
+            "Device {} is in use by other devices; refusing to restore a LUKS2 header to it",
+            physical_path.display()
+        )));
+    }
+
+    let mut f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_EXCL)
+        .open(physical_path)
+        .map_err(|e| {
+            StratisError::Msg(format!(
+                "Failed to open device {} exclusively, it appears to be in use: {e}",
+                physical_path.display()
+            ))
+        })?;
+
+    let found = match device_from_physical_path(physical_path)? {
+        Some(mut device) => Some(identifiers_from_metadata(&mut device)?),
+        None => device_identifiers(&mut f)?,
+    };
+    match found {
+        Some(found) if found != identifiers => Err(StratisError::Msg(format!(
+            "Device {} belongs to {found}, not to {identifiers}; refusing to restore a LUKS2 header to it",
+            physical_path.display()
+        ))),
+        Some(_) => Ok(()),
+        None => {
+            let dev_size = blkdev_size(&f)?.sectors();
+            if dev_size != size {
+                Err(StratisError::Msg(format!(
+                    "Device {} has size {dev_size} but the device with {identifiers} had size {size} when its header was backed up; refusing to restore a LUKS2 header to it",
+                    physical_path.display()
+                )))
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
 fn open_safe(device: &mut CryptDevice, token: libc::c_int) -> StratisResult<SizedKeyMemory> {
     let token = device.token_handle().json_get(token as c_uint).ok();
     let jwe = token.as_ref().and_then(|t| t.get("jwe"));
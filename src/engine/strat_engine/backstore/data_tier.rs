@@ -4,10 +4,12 @@
 
 // Code to handle the backing store of a pool.
 
-#[cfg(test)]
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeMap, HashSet},
+    iter::once,
+};
 
-use devicemapper::Sectors;
+use devicemapper::{Device, Sectors};
 
 use crate::{
     engine::{
@@ -16,17 +18,104 @@ use crate::{
                 blockdev::StratBlockDev,
                 blockdevmgr::BlockDevMgr,
                 devices::UnownedDevices,
+                raid::{RaidLegTables, RAID_META_SIZE},
                 shared::{metadata_to_segment, AllocatedAbove, BlkDevSegment, BlockDevPartition},
+                stripe::StripedExtent,
                 transaction::RequestTransaction,
             },
-            serde_structs::{BaseDevSave, BlockDevSave, DataTierSave, Recordable},
+            serde_structs::{
+                BaseBlockDevSave, BaseDevSave, BlockDevSave, DataTierSave, Recordable,
+                ReplacementSave,
+            },
             types::BDARecordResult,
         },
-        types::{BlockDevTier, DevUuid, Name, PoolUuid},
+        types::{AllocationPolicy, BlockDevTier, DevUuid, Name, PoolUuid, Redundancy},
     },
-    stratis::StratisResult,
+    stratis::{StratisError, StratisResult},
 };
 
+/// A replacement of a blockdev or a removal of blockdevs in the data tier
+/// that is in progress.
+#[derive(Debug)]
+pub struct Replacement {
+    /// The blockdevs whose data is being moved off them
+    pub(super) old: Vec<DevUuid>,
+    /// The blockdev that replaces the single blockdev in old, or None if the
+    /// blockdevs in old are being removed
+    pub(super) new: Option<DevUuid>,
+    /// The segments reserved for each segment allocated from the blockdevs
+    /// in old, in order. The space is marked as used on the blockdevs, so
+    /// that it is not allocated for any other purpose, but is not mapped
+    /// until the data has been copied.
+    reserved: Vec<Vec<BlkDevSegment>>,
+    /// The percentage of the data that has been copied
+    pub(super) progress: u8,
+}
+
+/// Divide the segments of a copy of the data into the tables of the
+/// metadata and image devices of a leg of the raid device; the first
+/// RAID_META_SIZE sectors of the copy hold the raid metadata.
+fn raid_leg_tables(segments: &[BlkDevSegment]) -> RaidLegTables {
+    let mut meta = Vec::new();
+    let mut image = Vec::new();
+    let mut offset = Sectors(0);
+    for seg in segments {
+        if offset >= RAID_META_SIZE {
+            image.push(seg.clone());
+        } else if offset + seg.segment.length <= RAID_META_SIZE {
+            meta.push(seg.clone());
+        } else {
+            let split = RAID_META_SIZE - offset;
+            let mut head = seg.clone();
+            head.segment.length = split;
+            let mut tail = seg.clone();
+            tail.segment.start += split;
+            tail.segment.length = seg.segment.length - split;
+            meta.push(head);
+            image.push(tail);
+        }
+        offset += seg.segment.length;
+    }
+
+    RaidLegTables {
+        meta: AllocatedAbove { inner: meta }.map_to_dm(),
+        image: AllocatedAbove { inner: image }.map_to_dm(),
+    }
+}
+
+/// The segments of each request of a transaction, in request order.
+fn segments_by_request(transaction: &RequestTransaction) -> Vec<Vec<BlkDevSegment>> {
+    (0..)
+        .map_while(|idx| transaction.get_segs_for_req(idx))
+        .collect()
+}
+
+/// The segments of a copy of the data with every segment allocated from
+/// any of the specified blockdevs replaced by the segments reserved for it,
+/// which are given for each such segment, in order.
+fn relocate_segments(
+    segments: &AllocatedAbove,
+    uuids: &[DevUuid],
+    reserved: &[Vec<BlkDevSegment>],
+) -> Vec<BlkDevSegment> {
+    let mut reserved = reserved.iter();
+    let mut relocated = Vec::with_capacity(segments.inner.len());
+    for seg in segments.inner.iter() {
+        if uuids.contains(&seg.uuid) {
+            relocated.extend(
+                reserved
+                    .next()
+                    .expect("space was reserved for every segment to be moved")
+                    .iter()
+                    .cloned(),
+            );
+        } else {
+            relocated.push(seg.clone());
+        }
+    }
+    relocated
+}
+
 /// Handles the lowest level, base layer of this tier.
 #[derive(Debug)]
 pub struct DataTier {
@@ -34,31 +123,109 @@ pub struct DataTier {
     pub(super) block_mgr: BlockDevMgr,
     /// The list of segments granted by block_mgr and used by dm_device
     pub(super) segments: AllocatedAbove,
+    /// The replacement of a blockdev, if one is in progress
+    pub(super) replacement: Option<Replacement>,
+    /// The redundancy of the data stored in this tier
+    pub(super) redundancy: Redundancy,
+    /// The segments holding each copy of the data other than the first,
+    /// which is held by segments. No two copies are allocated from the same
+    /// blockdev. Empty if the tier has no redundancy.
+    pub(super) other_legs: Vec<AllocatedAbove>,
+    /// The blockdevs that have disappeared while the pool was set up or
+    /// were absent when it was set up
+    missing: HashSet<DevUuid>,
+    /// The recorded metadata of the blockdevs that were absent when the
+    /// tier was set up. Only a tier with redundancy can be set up without
+    /// all of its blockdevs.
+    absent: Vec<BaseBlockDevSave>,
+    /// The policy by which new extents are allocated
+    pub(super) allocation_policy: AllocationPolicy,
+    /// The extents of segments that are striped, in order
+    pub(super) stripes: Vec<StripedExtent>,
 }
 
 impl DataTier {
     /// Setup a previously existing data layer from the block_mgr and
     /// previously allocated segments.
+    ///
+    /// If the tier has redundancy, some of the blockdevs recorded in the
+    /// metadata may be absent from block_mgr. The copies of the data that
+    /// are allocated in part from an absent blockdev can not be mapped; see
+    /// leg_tables().
     pub fn setup(
         block_mgr: BlockDevMgr,
         data_tier_save: &DataTierSave,
+        redundancy: Redundancy,
+        allocation_policy: AllocationPolicy,
     ) -> BDARecordResult<DataTier> {
-        let uuid_to_devno = block_mgr.uuid_to_devno();
+        let mut uuid_to_devno = block_mgr.uuid_to_devno();
+        let absent = data_tier_save
+            .blockdev
+            .devs
+            .iter()
+            .filter(|dev| !uuid_to_devno.contains_key(&dev.uuid))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !absent.is_empty() && redundancy == Redundancy::None {
+            return Err((
+                StratisError::Msg(format!(
+                    "Blockdevs with UUIDs {} are absent but the data tier has no redundancy",
+                    absent
+                        .iter()
+                        .map(|dev| dev.uuid.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+                block_mgr.into_bdas(),
+            ));
+        }
+        // The segments allocated from an absent blockdev are never mapped,
+        // so any device number will do.
+        for dev in absent.iter() {
+            uuid_to_devno.insert(dev.uuid, Device { major: 0, minor: 0 });
+        }
         let mapper = |ld: &BaseDevSave| -> StratisResult<BlkDevSegment> {
             metadata_to_segment(&uuid_to_devno, ld)
         };
-        let segments = match data_tier_save.blockdev.allocs[0]
-            .iter()
-            .map(&mapper)
+        let allocs = &data_tier_save.blockdev.allocs;
+        let mut legs = match (0..redundancy.copies())
+            .map(|copy| {
+                allocs
+                    .get(copy)
+                    .ok_or_else(|| {
+                        StratisError::Msg(format!(
+                            "Data tier metadata has allocations for {} copies of the data but redundancy {redundancy} requires {}",
+                            allocs.len(),
+                            redundancy.copies()
+                        ))
+                    })?
+                    .iter()
+                    .map(&mapper)
+                    .collect::<StratisResult<Vec<_>>>()
+                    .map(|inner| AllocatedAbove { inner })
+            })
             .collect::<StratisResult<Vec<_>>>()
         {
-            Ok(s) => AllocatedAbove { inner: s },
+            Ok(legs) => legs,
             Err(e) => return Err((e, block_mgr.into_bdas())),
         };
+        let other_legs = legs.split_off(1);
+        let segments = legs.pop().expect("at least one copy of the data");
 
         Ok(DataTier {
             block_mgr,
             segments,
+            replacement: None,
+            redundancy,
+            other_legs,
+            missing: absent.iter().map(|dev| dev.uuid).collect(),
+            absent,
+            allocation_policy,
+            stripes: data_tier_save
+                .stripes
+                .iter()
+                .map(StripedExtent::from)
+                .collect(),
         })
     }
 
@@ -67,13 +234,54 @@ impl DataTier {
     /// Initially 0 segments are allocated.
     ///
     /// WARNING: metadata changing event
-    pub fn new(block_mgr: BlockDevMgr) -> DataTier {
+    pub fn new(block_mgr: BlockDevMgr, redundancy: Redundancy) -> DataTier {
         DataTier {
             block_mgr,
             segments: AllocatedAbove { inner: vec![] },
+            replacement: None,
+            redundancy,
+            other_legs: (1..redundancy.copies())
+                .map(|_| AllocatedAbove { inner: vec![] })
+                .collect(),
+            missing: HashSet::new(),
+            absent: Vec::new(),
+            allocation_policy: AllocationPolicy::default(),
+            stripes: Vec::new(),
         }
     }
 
+    /// The segments holding each copy of the data, in order.
+    pub fn legs(&self) -> impl Iterator<Item = &AllocatedAbove> {
+        once(&self.segments).chain(self.other_legs.iter())
+    }
+
+    /// The blockdevs holding each copy of the data, in order.
+    fn leg_devs(&self) -> Vec<HashSet<DevUuid>> {
+        self.legs().map(|leg| leg.uuids()).collect()
+    }
+
+    /// The tables of the metadata and image devices of each leg of the raid
+    /// device, which are obtained by dividing the segments of each copy of
+    /// the data after the raid metadata. None for a copy that is allocated in
+    /// part from an absent blockdev.
+    pub fn leg_tables(&self) -> Vec<Option<RaidLegTables>> {
+        self.legs()
+            .map(|leg| {
+                if leg.inner.iter().any(|seg| self.is_absent(seg.uuid)) {
+                    None
+                } else {
+                    Some(raid_leg_tables(&leg.inner))
+                }
+            })
+            .collect()
+    }
+
+    /// Whether the blockdev belongs to this tier but was absent when the
+    /// tier was set up.
+    pub fn is_absent(&self, uuid: DevUuid) -> bool {
+        self.absent.iter().any(|dev| dev.uuid == uuid)
+    }
+
     /// Add the given paths to self. Return UUIDs of the new blockdevs
     /// corresponding to the specified paths.
     /// WARNING: metadata changing event
@@ -91,24 +299,597 @@ impl DataTier {
     /// Allocate a region for all sector size requests from unallocated segments in
     /// block devices belonging to the data tier. Return Some(_) if requested
     /// amount or more was allocated, otherwise, None.
+    ///
+    /// While blockdevs are being removed, no space is allocated from them;
+    /// while a blockdev is being replaced, no space is allocated from either
+    /// the blockdev or its replacement.
+    ///
+    /// If the tier has redundancy, every request is allocated once for each
+    /// copy of the data; the first allocation is preceded by a request for
+    /// the raid metadata of each copy. Space can not be allocated while any
+    /// blockdev of a tier with redundancy is missing. Otherwise, if the
+    /// allocation policy is striped, every request is striped across as
+    /// many blockdevs as possible.
     pub fn alloc_request(&self, requests: &[Sectors]) -> StratisResult<Option<RequestTransaction>> {
-        self.block_mgr.request_space(requests)
+        let legs = self.leg_devs();
+        let with_meta;
+        let requests = if self.redundancy == Redundancy::None {
+            requests
+        } else {
+            if self.has_missing() {
+                return Err(StratisError::Msg(
+                    "Some blockdevs in the data tier are missing; no space can be allocated until they are replaced".to_string(),
+                ));
+            }
+            if self.segments.size() == Sectors(0) {
+                with_meta = once(RAID_META_SIZE)
+                    .chain(requests.iter().cloned())
+                    .collect::<Vec<_>>();
+                &with_meta
+            } else {
+                requests
+            }
+        };
+        match (self.replacement.as_ref(), self.allocation_policy) {
+            (Some(replacement), _) => self.block_mgr.request_space_excluding(
+                requests,
+                self.redundancy,
+                &legs,
+                &replacement
+                    .old
+                    .iter()
+                    .cloned()
+                    .chain(replacement.new)
+                    .collect::<Vec<_>>(),
+            ),
+            (None, AllocationPolicy::Striped { stripe_size }) => {
+                assert_eq!(self.redundancy, Redundancy::None);
+                self.block_mgr.request_striped_space(requests, stripe_size)
+            }
+            (None, AllocationPolicy::Linear) => {
+                self.block_mgr
+                    .request_space(requests, self.redundancy, &legs)
+            }
+        }
+    }
+
+    /// Set the policy by which new extents are allocated. Extents that are
+    /// already allocated are not changed. Return true if the policy was
+    /// changed.
+    ///
+    /// Return an error if the policy is striped and the tier has redundancy.
+    ///
+    /// WARNING: metadata changing event
+    pub fn set_allocation_policy(&mut self, policy: AllocationPolicy) -> StratisResult<bool> {
+        if policy == self.allocation_policy {
+            return Ok(false);
+        }
+
+        if policy != AllocationPolicy::Linear && self.redundancy != Redundancy::None {
+            return Err(StratisError::Msg(format!(
+                "Allocation policy {policy} is not supported for a data tier with redundancy {}",
+                self.redundancy
+            )));
+        }
+
+        self.allocation_policy = policy;
+        Ok(true)
     }
 
     /// Commit an allocation that was determined to be valid by alloc_request()
     /// to metadata.
     pub fn alloc_commit(&mut self, transaction: RequestTransaction) -> StratisResult<()> {
-        let segments = transaction.get_blockdevmgr();
+        let copies = self.redundancy.copies();
+        let mut leg_segments = vec![Vec::new(); copies];
+        let mut req_lengths = BTreeMap::new();
+        for (idx, seg) in transaction.get_blockdevmgr_by_req() {
+            *req_lengths.entry(idx).or_insert(Sectors(0)) += seg.segment.length;
+            let leg = if copies == 1 {
+                0
+            } else {
+                transaction
+                    .get_leg_for_req(idx)
+                    .expect("every request of a tier with redundancy is allocated for a copy")
+            };
+            leg_segments[leg].push(seg);
+        }
+
+        // Requests are striped only if the tier has no redundancy, in which
+        // case their segments are appended to segments in request order.
+        let mut start = self.segments.size();
+        for (idx, length) in req_lengths {
+            if let Some((stripes, stripe_size)) = transaction.get_stripes_for_req(idx) {
+                let width = transaction
+                    .get_segs_for_req(idx)
+                    .expect("a striped request has segments")[0]
+                    .segment
+                    .length;
+                self.stripes.push(StripedExtent {
+                    start,
+                    length: width * stripes,
+                    stripes,
+                    stripe_size,
+                });
+            }
+            start += length;
+        }
         self.block_mgr.commit_space(transaction)?;
-        self.segments.coalesce_blkdevsegs(&segments);
+        for (leg, segments) in once(&mut self.segments)
+            .chain(self.other_legs.iter_mut())
+            .zip(leg_segments)
+        {
+            leg.coalesce_blkdevsegs(&segments);
+        }
 
         Ok(())
     }
 
+    /// Return an error if the last amount sectors of the segments can not be
+    /// released by release_tail().
+    pub fn check_release(&self, amount: Sectors) -> StratisResult<()> {
+        if self.redundancy != Redundancy::None {
+            return Err(StratisError::Msg(format!(
+                "Space can not be released from a data tier with redundancy {}",
+                self.redundancy
+            )));
+        }
+
+        if self.replacement.is_some() {
+            return Err(StratisError::Msg(
+                "Blockdevs in the data tier are being removed or replaced; no space can be released until the data has been moved off them".to_string(),
+            ));
+        }
+
+        let size = self.segments.size();
+        if amount > size {
+            return Err(StratisError::Msg(format!(
+                "{amount} can not be released; only {size} are allocated in the data tier"
+            )));
+        }
+
+        if let Some(extent) = self
+            .stripes
+            .iter()
+            .find(|extent| extent.start + extent.length > size - amount)
+        {
+            return Err(StratisError::Msg(format!(
+                "{amount} can not be released; the extent at {} in the data tier is striped",
+                extent.start
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Remove the last amount sectors from the segments and return the space
+    /// they occupy to the blockdevs, so that it may be allocated again.
+    ///
+    /// Return an error if the tier has redundancy, if a blockdev is being
+    /// replaced, or if any of the sectors belongs to a striped extent.
+    ///
+    /// WARNING: metadata changing event
+    pub fn release_tail(&mut self, amount: Sectors) -> StratisResult<()> {
+        self.check_release(amount)?;
+
+        let removed = self.segments.truncate(amount);
+        self.block_mgr.release_space(&removed)
+    }
+
+    /// The segments allocated from any of the specified blockdevs, in the
+    /// order in which they are mapped to the upper device.
+    fn segments_on<'a>(
+        &'a self,
+        uuids: &'a [DevUuid],
+    ) -> impl Iterator<Item = &'a BlkDevSegment> + 'a {
+        self.segments
+            .inner
+            .iter()
+            .filter(move |seg| uuids.contains(&seg.uuid))
+    }
+
+    /// Request space for the copy of the data that is allocated in part from
+    /// the blockdevs that are to be removed, on blockdevs that hold no other
+    /// copy of the data; if new is specified, only on new, which must
+    /// already belong to this tier and have no space allocated. One request
+    /// is made for each segment of the copy allocated from the blockdevs to
+    /// be removed, in order. Return the index of the copy and the
+    /// transaction, or None if no data is allocated from the blockdevs.
+    ///
+    /// Return an error if any of the blockdevs does not belong to this tier,
+    /// if fewer blockdevs than copies of the data would remain, if the
+    /// blockdevs hold parts of more than one copy of the data, if any other
+    /// copy is allocated in part from a missing blockdev, or if there is not
+    /// enough free space to hold the copy.
+    ///
+    /// Precondition: the tier has redundancy.
+    pub fn rebuild_request(
+        &self,
+        uuids: &[DevUuid],
+        new: Option<DevUuid>,
+    ) -> StratisResult<Option<(usize, RequestTransaction)>> {
+        assert_ne!(self.redundancy, Redundancy::None);
+
+        if let Some(uuid) = uuids.iter().find(|uuid| {
+            self.block_mgr.get_blockdev_by_uuid(**uuid).is_none() && !self.is_absent(**uuid)
+        }) {
+            return Err(StratisError::Msg(format!(
+                "Blockdev with UUID {uuid} does not belong to the data tier"
+            )));
+        }
+
+        let remaining = self
+            .block_mgr
+            .blockdevs()
+            .iter()
+            .map(|(uuid, _)| *uuid)
+            .chain(self.absent.iter().map(|dev| dev.uuid))
+            .filter(|uuid| !uuids.contains(uuid))
+            .count();
+        if remaining < self.redundancy.copies() {
+            return Err(StratisError::Msg(format!(
+                "Redundancy {} requires at least {} blockdevs in the data tier",
+                self.redundancy,
+                self.redundancy.copies()
+            )));
+        }
+
+        let leg_devs = self.leg_devs();
+        let legs = leg_devs
+            .iter()
+            .enumerate()
+            .filter(|(_, devs)| uuids.iter().any(|uuid| devs.contains(uuid)))
+            .map(|(leg, _)| leg)
+            .collect::<Vec<_>>();
+        let leg = match legs.as_slice() {
+            [] => return Ok(None),
+            [leg] => *leg,
+            _ => {
+                return Err(StratisError::Msg(
+                    "The blockdevs hold parts of more than one copy of the data; only one copy can be rebuilt at a time".to_string(),
+                ));
+            }
+        };
+
+        if leg_devs
+            .iter()
+            .enumerate()
+            .any(|(idx, devs)| idx != leg && devs.iter().any(|uuid| self.missing.contains(uuid)))
+        {
+            return Err(StratisError::Msg(
+                "Another copy of the data is allocated in part from a missing blockdev; the copy held by the blockdevs can not be rebuilt from it".to_string(),
+            ));
+        }
+
+        let sizes = self
+            .legs()
+            .nth(leg)
+            .expect("leg is the index of a copy of the data")
+            .inner
+            .iter()
+            .filter(|seg| uuids.contains(&seg.uuid))
+            .map(|seg| seg.segment.length)
+            .collect::<Vec<_>>();
+
+        let excluded = self
+            .block_mgr
+            .blockdevs()
+            .iter()
+            .map(|(uuid, _)| *uuid)
+            .filter(|uuid| {
+                uuids.contains(uuid)
+                    || self.missing.contains(uuid)
+                    || new.map(|new| new != *uuid).unwrap_or(false)
+                    || leg_devs
+                        .iter()
+                        .enumerate()
+                        .any(|(idx, devs)| idx != leg && devs.contains(uuid))
+            })
+            .collect::<Vec<_>>();
+
+        match self
+            .block_mgr
+            .request_space_excluding(&sizes, Redundancy::None, &[], &excluded)?
+        {
+            Some(transaction) => Ok(Some((leg, transaction))),
+            None => {
+                let needed = sizes.iter().cloned().sum::<Sectors>();
+                Err(StratisError::Msg(format!(
+                    "{needed} of a copy of the data must be moved off the blockdevs but there is not enough free space on the blockdevs that hold no other copy"
+                )))
+            }
+        }
+    }
+
+    /// The tables of the leg of the raid device that holds the copy of the
+    /// data with the given index, once the segments of the copy allocated
+    /// from the blockdevs to be removed are replaced by the segments
+    /// reserved for them by rebuild_request().
+    pub fn rebuild_tables(
+        &self,
+        leg: usize,
+        uuids: &[DevUuid],
+        transaction: &RequestTransaction,
+    ) -> RaidLegTables {
+        raid_leg_tables(&relocate_segments(
+            self.legs()
+                .nth(leg)
+                .expect("leg is the index of a copy of the data"),
+            uuids,
+            &segments_by_request(transaction),
+        ))
+    }
+
+    /// Replace the segments of the copy of the data reserved for by
+    /// rebuild_request(), if any, and remove the blockdevs from this tier.
+    /// Return the removed blockdevs that were present; their metadata has
+    /// not been erased.
+    ///
+    /// WARNING: metadata changing event
+    pub fn rebuild_commit(
+        &mut self,
+        uuids: &[DevUuid],
+        rebuild: Option<(usize, RequestTransaction)>,
+    ) -> StratisResult<Vec<StratBlockDev>> {
+        if let Some((leg, transaction)) = rebuild {
+            let leg_segments = once(&mut self.segments)
+                .chain(self.other_legs.iter_mut())
+                .nth(leg)
+                .expect("leg is the index of a copy of the data");
+            let segments =
+                relocate_segments(leg_segments, uuids, &segments_by_request(&transaction));
+            self.block_mgr.commit_space(transaction)?;
+
+            let mut allocated = AllocatedAbove { inner: vec![] };
+            allocated.coalesce_blkdevsegs(&segments);
+            *leg_segments = allocated;
+        }
+
+        self.absent.retain(|dev| !uuids.contains(&dev.uuid));
+        self.missing.retain(|uuid| !uuids.contains(uuid));
+
+        let present = uuids
+            .iter()
+            .filter(|uuid| self.block_mgr.get_blockdev_by_uuid(**uuid).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        self.block_mgr.take_blockdevs(&present)
+    }
+
+    /// Begin moving the data allocated from the blockdevs in old off them:
+    /// if new is specified, to new, which must already belong to this tier
+    /// and have no space allocated, in order to replace the single blockdev
+    /// in old; otherwise to the remaining blockdevs, in order to remove the
+    /// blockdevs in old. Space is reserved for every segment allocated from
+    /// old and marked as used, but the segments are not changed until
+    /// finish_replacement() is called.
+    ///
+    /// Return pairs of segments, in the order returned by segments_on(); the
+    /// first segment of each pair is a piece of a segment allocated from old,
+    /// the second the equally long segment to which it must be copied.
+    ///
+    /// Every piece of a striped extent that is allocated from old is moved to
+    /// a single free range, preferably on a blockdev that holds no other
+    /// piece of the extent, so that the extent remains striped.
+    ///
+    /// Return an error if any of the blockdevs in old does not belong to this
+    /// tier, if no blockdevs would remain, or if there is not enough free
+    /// space to hold the data to be moved.
+    pub fn start_replacement(
+        &mut self,
+        old: &[DevUuid],
+        new: Option<DevUuid>,
+    ) -> StratisResult<Vec<(BlkDevSegment, BlkDevSegment)>> {
+        if self.redundancy != Redundancy::None {
+            return Err(StratisError::Msg(format!(
+                "Data can not be moved off blockdevs in a data tier with redundancy {}",
+                self.redundancy
+            )));
+        }
+
+        if self.replacement.is_some() {
+            return Err(StratisError::Msg(
+                "Blockdevs in the data tier are already being removed or replaced".to_string(),
+            ));
+        }
+
+        if let Some(uuid) = old.iter().find(|uuid| self.is_absent(**uuid)) {
+            return Err(StratisError::Msg(format!(
+                "Blockdev with UUID {uuid} is absent; the data allocated from it can not be copied"
+            )));
+        }
+
+        if let Some(uuid) = old
+            .iter()
+            .find(|uuid| self.block_mgr.get_blockdev_by_uuid(**uuid).is_none())
+        {
+            return Err(StratisError::Msg(format!(
+                "Blockdev with UUID {uuid} does not belong to the data tier"
+            )));
+        }
+
+        let blockdevs = self.block_mgr.blockdevs();
+        if blockdevs.iter().all(|(uuid, _)| old.contains(uuid)) {
+            return Err(StratisError::Msg(
+                "At least one blockdev must remain in the data tier".to_string(),
+            ));
+        }
+
+        let requests = self.relocation_requests(old);
+
+        let excluded = match new {
+            Some(new) => blockdevs
+                .iter()
+                .map(|(uuid, _)| *uuid)
+                .filter(|uuid| *uuid != new)
+                .collect::<Vec<_>>(),
+            None => old.to_vec(),
+        };
+
+        let transaction = match self.block_mgr.request_space_contiguous(
+            &requests
+                .iter()
+                .map(|(_, size, avoid)| (*size, avoid.clone()))
+                .collect::<Vec<_>>(),
+            &excluded,
+        )? {
+            Some(transaction) => transaction,
+            None => {
+                let needed = requests.iter().map(|(_, size, _)| *size).sum::<Sectors>();
+                let avail = blockdevs
+                    .iter()
+                    .filter(|(uuid, _)| !excluded.contains(uuid))
+                    .map(|(_, bd)| bd.available())
+                    .sum::<Sectors>();
+                return Err(StratisError::Msg(match new {
+                    Some(_) if avail < needed => format!(
+                        "{needed} are allocated from the blockdev to be replaced but its replacement is too small to hold them"
+                    ),
+                    None if avail < needed => format!(
+                        "{needed} must be moved off the blockdevs to be removed but only {avail} are free on the remaining blockdevs in the data tier"
+                    ),
+                    _ => "The free space on the blockdevs that the data would be moved to is too fragmented to hold every piece of the striped extents in a single range".to_string(),
+                }));
+            }
+        };
+
+        let mut reserved = vec![Vec::new(); self.segments_on(old).count()];
+        for (req_idx, (seg_idx, _, _)) in requests.iter().enumerate() {
+            reserved[*seg_idx].extend(
+                transaction
+                    .get_segs_for_req(req_idx)
+                    .expect("every request was satisfied"),
+            );
+        }
+        let mut pairs = Vec::new();
+        for (seg, new_segs) in self.segments_on(old).zip(reserved.iter()) {
+            let mut offset = Sectors(0);
+            for new_seg in new_segs {
+                let mut old_seg = seg.clone();
+                old_seg.segment.start += offset;
+                old_seg.segment.length = new_seg.segment.length;
+                offset += new_seg.segment.length;
+                pairs.push((old_seg, new_seg.clone()));
+            }
+        }
+
+        self.block_mgr.commit_space(transaction)?;
+        self.replacement = Some(Replacement {
+            old: old.to_vec(),
+            new,
+            reserved,
+            progress: 0,
+        });
+
+        Ok(pairs)
+    }
+
+    /// The requests for space for the data allocated from the blockdevs in
+    /// old, each with the index in segments_on(old) of the segment it is
+    /// for; the requests for each segment are in order and together as long
+    /// as the segment. Every piece of a striped extent is requested
+    /// separately, as contiguous space, together with the blockdevs that
+    /// hold the other pieces of the extent.
+    fn relocation_requests(&self, old: &[DevUuid]) -> Vec<(usize, Sectors, Option<Vec<DevUuid>>)> {
+        // The blockdev from which the sector at the offset in the
+        // concatenation of the segments is allocated.
+        let uuid_at = |offset: Sectors| {
+            let mut start = Sectors(0);
+            self.segments.inner.iter().find_map(|seg| {
+                start += seg.segment.length;
+                if offset < start {
+                    Some(seg.uuid)
+                } else {
+                    None
+                }
+            })
+        };
+
+        let mut requests = Vec::new();
+        let mut seg_start = Sectors(0);
+        let mut idx = 0;
+        for seg in self.segments.inner.iter() {
+            let seg_end = seg_start + seg.segment.length;
+            if old.contains(&seg.uuid) {
+                let mut next = seg_start;
+                for extent in self.stripes.iter() {
+                    let width = extent.width();
+                    for stripe in 0..extent.stripes {
+                        let piece_start = extent.start + width * stripe;
+                        if piece_start < seg_start || piece_start >= seg_end {
+                            continue;
+                        }
+                        // The stripe device can only be set up if every
+                        // piece lies within a single segment.
+                        assert!(piece_start + width <= seg_end);
+
+                        if piece_start > next {
+                            requests.push((idx, piece_start - next, None));
+                        }
+                        let avoid = (0..extent.stripes)
+                            .filter(|other| *other != stripe)
+                            .filter_map(|other| uuid_at(extent.start + width * other))
+                            .collect::<Vec<_>>();
+                        requests.push((idx, width, Some(avoid)));
+                        next = piece_start + width;
+                    }
+                }
+                if seg_end > next {
+                    requests.push((idx, seg_end - next, None));
+                }
+                idx += 1;
+            }
+            seg_start = seg_end;
+        }
+
+        requests
+    }
+
+    /// Complete the replacement or removal that is in progress, once all the
+    /// data has been copied. The segments allocated from the old blockdevs
+    /// are replaced by the segments reserved for them and the old blockdevs
+    /// are removed from this tier. Return the old blockdevs; their metadata
+    /// has not been erased.
+    ///
+    /// WARNING: metadata changing event
+    pub fn finish_replacement(&mut self) -> StratisResult<Vec<StratBlockDev>> {
+        let Replacement { old, reserved, .. } = self
+            .replacement
+            .take()
+            .expect("only called while a replacement is in progress");
+
+        let segments = relocate_segments(&self.segments, &old, &reserved);
+        let mut allocated = AllocatedAbove { inner: vec![] };
+        allocated.coalesce_blkdevsegs(&segments);
+        self.segments = allocated;
+
+        self.block_mgr.take_blockdevs(&old)
+    }
+
+    /// Abandon the replacement or removal that is in progress, if any, and
+    /// release the space reserved for it.
+    pub fn cancel_replacement(&mut self) -> StratisResult<()> {
+        match self.replacement.take() {
+            Some(replacement) => self.block_mgr.release_space(
+                &replacement
+                    .reserved
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// The percentage of the data copied by the replacement or removal that
+    /// is in progress, if any.
+    pub fn replacement_progress(&self) -> Option<u8> {
+        self.replacement.as_ref().map(|r| r.progress)
+    }
+
     /// The sum of the lengths of all the sectors that have been mapped to an
-    /// upper device.
+    /// upper device, including every copy of the data.
     pub fn allocated(&self) -> Sectors {
-        self.segments.size()
+        self.legs().map(|leg| leg.size()).sum()
     }
 
     /// The total size of all the blockdevs combined
@@ -121,9 +902,32 @@ impl DataTier {
         self.block_mgr.metadata_size()
     }
 
-    /// The total usable size of all the blockdevs combined
+    /// The total usable size of all the blockdevs combined, as seen by the
+    /// upper device. If the tier has redundancy, each sector of the upper
+    /// device takes up space on every leg, and each leg begins with the raid
+    /// metadata, so this is an upper bound; if the blockdevs can not be
+    /// divided evenly between the legs, less space can be allocated.
     pub fn usable_size(&self) -> Sectors {
-        self.size() - self.metadata_size()
+        let size = (self.size() - self.metadata_size()) / self.redundancy.copies();
+        if self.redundancy == Redundancy::None {
+            size
+        } else if size > RAID_META_SIZE {
+            size - RAID_META_SIZE
+        } else {
+            Sectors(0)
+        }
+    }
+
+    /// Record that the blockdev has disappeared from the system. Return true
+    /// if the blockdev belongs to this tier and was not already known to be
+    /// missing.
+    pub fn mark_missing(&mut self, uuid: DevUuid) -> bool {
+        self.block_mgr.get_blockdev_by_uuid(uuid).is_some() && self.missing.insert(uuid)
+    }
+
+    /// Whether any blockdev in this tier has disappeared from the system.
+    pub fn has_missing(&self) -> bool {
+        !self.missing.is_empty()
     }
 
     /// Destroy the store. Wipe its blockdevs.
@@ -167,6 +971,33 @@ impl DataTier {
         self.block_mgr.grow(dev)
     }
 
+    pub fn shrink(&mut self, dev: DevUuid) -> StratisResult<bool> {
+        self.block_mgr.shrink(dev)
+    }
+
+    /// Whether any blockdev in this tier is smaller than the size recorded
+    /// in its metadata.
+    pub fn has_shrunk(&self) -> bool {
+        self.block_mgr
+            .blockdevs()
+            .iter()
+            .any(|(_, bd)| bd.is_shrunk())
+    }
+
+    /// Update the device numbers of all segments from the blockdevs they are
+    /// allocated from. Necessary whenever the device number of a blockdev
+    /// changes, e.g. when it is encrypted.
+    pub fn remap_devices(&mut self) {
+        let uuid_to_devno = self.block_mgr.uuid_to_devno();
+        for leg in once(&mut self.segments).chain(self.other_legs.iter_mut()) {
+            for seg in leg.inner.iter_mut() {
+                if let Some(devno) = uuid_to_devno.get(&seg.uuid) {
+                    seg.segment.device = *devno;
+                }
+            }
+        }
+    }
+
     /// Return the partition of the block devs that are in use and those
     /// that are not.
     pub fn partition_by_use(&self) -> BlockDevPartition<'_> {
@@ -177,7 +1008,22 @@ impl DataTier {
 
     #[cfg(test)]
     pub fn invariant(&self) {
-        let allocated_uuids = self.segments.uuids();
+        let leg_devs = self.leg_devs();
+        for (idx, uuids) in leg_devs.iter().enumerate() {
+            assert!(leg_devs[idx + 1..]
+                .iter()
+                .all(|other| uuids.is_disjoint(other)));
+        }
+        let allocated_uuids = leg_devs
+            .into_iter()
+            .flatten()
+            .filter(|uuid| !self.is_absent(*uuid))
+            .chain(
+                self.replacement
+                    .iter()
+                    .flat_map(|r| r.reserved.iter().flatten().map(|seg| seg.uuid)),
+            )
+            .collect::<HashSet<_>>();
         let in_use_uuids = self
             .block_mgr
             .blockdevs()
@@ -186,6 +1032,14 @@ impl DataTier {
             .map(|(u, _)| *u)
             .collect::<HashSet<_>>();
         assert_eq!(allocated_uuids, in_use_uuids);
+
+        let mut next = Sectors(0);
+        for extent in self.stripes.iter() {
+            assert!(extent.start >= next);
+            assert!(extent.stripes > 1);
+            next = extent.start + extent.length;
+        }
+        assert!(next <= self.segments.size());
     }
 }
 
@@ -193,9 +1047,19 @@ impl Recordable<DataTierSave> for DataTier {
     fn record(&self) -> DataTierSave {
         DataTierSave {
             blockdev: BlockDevSave {
-                allocs: vec![self.segments.record()],
-                devs: self.block_mgr.record(),
+                allocs: self.legs().map(|leg| leg.record()).collect(),
+                devs: self
+                    .block_mgr
+                    .record()
+                    .into_iter()
+                    .chain(self.absent.iter().cloned())
+                    .collect(),
             },
+            stripes: self.stripes.iter().map(|extent| extent.record()).collect(),
+            replacement: self.replacement.as_ref().map(|r| ReplacementSave {
+                old: r.old.clone(),
+                new: r.new,
+            }),
         }
     }
 }
@@ -243,10 +1107,11 @@ mod tests {
             MDADataSize::default(),
             None,
             None,
+            None,
         )
         .unwrap();
 
-        let mut data_tier = DataTier::new(mgr);
+        let mut data_tier = DataTier::new(mgr, Redundancy::None);
         data_tier.invariant();
 
         // A data_tier w/ some devices but nothing allocated
@@ -292,6 +1157,69 @@ mod tests {
         data_tier.destroy().unwrap();
     }
 
+    /// Make a data tier that stores two copies of its data and verify that
+    /// every allocation is made once for each copy, from distinct blockdevs,
+    /// and that the first allocation includes the raid metadata of each copy.
+    fn test_raid1_alloc(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let pool_uuid = PoolUuid::new_v4();
+        let pool_name = Name::new("pool_name".to_string());
+
+        let mgr = BlockDevMgr::initialize(
+            pool_name,
+            pool_uuid,
+            get_devices(paths).unwrap(),
+            MDADataSize::default(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let mut data_tier = DataTier::new(mgr, Redundancy::Raid1);
+        data_tier.invariant();
+        assert_eq!(data_tier.legs().count(), 2);
+
+        let request_amount = data_tier.block_mgr.avail_space() / 8usize;
+        assert!(request_amount != Sectors(0));
+
+        for _ in 0..2 {
+            let transaction = data_tier.alloc_request(&[request_amount]).unwrap().unwrap();
+            data_tier.alloc_commit(transaction).unwrap();
+            data_tier.invariant();
+        }
+
+        for leg in data_tier.legs() {
+            assert_eq!(leg.size(), 2usize * request_amount + RAID_META_SIZE);
+        }
+        assert_eq!(
+            data_tier.allocated(),
+            4usize * request_amount + 2usize * RAID_META_SIZE
+        );
+
+        let record = data_tier.record();
+        assert_eq!(record.blockdev.allocs.len(), 2);
+
+        data_tier.destroy().unwrap();
+    }
+
+    #[test]
+    fn loop_test_raid1_alloc() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_raid1_alloc,
+        );
+    }
+
+    #[test]
+    fn real_test_raid1_alloc() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_raid1_alloc,
+        );
+    }
+
     #[test]
     fn loop_test_add_and_alloc() {
         loopbacked::test_with_spec(
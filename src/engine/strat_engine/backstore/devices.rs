@@ -36,7 +36,7 @@ use crate::{
                 STRATIS_FS_TYPE,
             },
         },
-        types::{DevUuid, DevicePath, EncryptionInfo, Name, PoolUuid},
+        types::{CryptParams, DevUuid, DevicePath, EncryptionInfo, IntegrityHash, Name, PoolUuid},
     },
     stratis::{StratisError, StratisResult},
 };
@@ -178,7 +178,10 @@ fn dev_info(devnode: &DevicePath) -> StratisResult<(DeviceInfo, Option<StratisId
     let (ownership, devnum, hw_id) = udev_info(devnode)?;
 
     match ownership {
-        UdevOwnership::Luks | UdevOwnership::MultipathMember | UdevOwnership::Theirs => {
+        UdevOwnership::IntegrityLayer
+        | UdevOwnership::Luks
+        | UdevOwnership::MultipathMember
+        | UdevOwnership::Theirs => {
             let err_str = format!(
                 "udev information indicates that device {} is a {}",
                 devnode.display(),
@@ -473,6 +476,10 @@ impl UnownedDevices {
         self.inner.is_empty()
     }
 
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
     pub fn unpack(self) -> Vec<DeviceInfo> {
         self.inner
     }
@@ -500,13 +507,18 @@ impl UnownedDevices {
 ///
 /// Precondition: Each device's DeviceInfo struct contains all necessary
 /// information about the device.
+///
+/// If an integrity hash is given, every device is given integrity
+/// protection with that hash; on encrypted devices, the integrity
+/// protection lies beneath the encryption.
 pub fn initialize_devices(
     devices: UnownedDevices,
     pool_name: Name,
     pool_uuid: PoolUuid,
     mda_data_size: MDADataSize,
     encryption_info: Option<&EncryptionInfo>,
-    sector_size: Option<u32>,
+    crypt_params: Option<&CryptParams>,
+    integrity: Option<IntegrityHash>,
 ) -> StratisResult<Vec<StratBlockDev>> {
     /// Initialize an encrypted device on the given physical device
     /// using the pool and device UUIDs of the new Stratis block device
@@ -521,7 +533,8 @@ pub fn initialize_devices(
         pool_uuid: PoolUuid,
         dev_uuid: DevUuid,
         encryption_info: &EncryptionInfo,
-        sector_size: Option<u32>,
+        crypt_params: Option<&CryptParams>,
+        integrity: Option<IntegrityHash>,
     ) -> StratisResult<(CryptHandle, Device, Sectors)> {
         let handle = CryptHandle::initialize(
             physical_path,
@@ -529,7 +542,8 @@ pub fn initialize_devices(
             dev_uuid,
             pool_name,
             encryption_info,
-            sector_size,
+            crypt_params.unwrap_or(&CryptParams::default()),
+            integrity,
         )?;
 
         let device_size = match handle.logical_device_size() {
@@ -590,6 +604,18 @@ pub fn initialize_devices(
         StratBlockDev::new(devno, bda, &[], None, hw_id, underlying_device).map_err(|(e, _)| e)
     }
 
+    /// Add integrity protection with the given hash, if any, to a newly
+    /// initialized block device.
+    fn initialize_integrity(
+        mut blockdev: StratBlockDev,
+        integrity: Option<IntegrityHash>,
+    ) -> StratisResult<StratBlockDev> {
+        if let Some(hash) = integrity {
+            blockdev.init_integrity(hash)?;
+        }
+        Ok(blockdev)
+    }
+
     /// Clean up an encrypted device after initialization failure.
     fn clean_up_encrypted(handle: &mut CryptHandle, causal_error: StratisError) -> StratisError {
         if let Err(e) = handle.wipe() {
@@ -652,7 +678,8 @@ pub fn initialize_devices(
         pool_uuid: PoolUuid,
         mda_data_size: MDADataSize,
         encryption_info: Option<&EncryptionInfo>,
-        sector_size: Option<u32>,
+        crypt_params: Option<&CryptParams>,
+        integrity: Option<IntegrityHash>,
     ) -> StratisResult<StratBlockDev> {
         let dev_uuid = DevUuid::new_v4();
         let (handle, devno, blockdev_size) = if let Some(ei) = encryption_info {
@@ -662,7 +689,8 @@ pub fn initialize_devices(
                 pool_uuid,
                 dev_uuid,
                 ei,
-                sector_size,
+                crypt_params,
+                integrity,
             )
             .map(|(handle, devno, devsize)| {
                 debug!(
@@ -696,7 +724,8 @@ pub fn initialize_devices(
                     dev_uuid,
                     (mda_data_size, BlockdevSize::new(blockdev_size)),
                     &dev_info.id_wwn,
-                );
+                )
+                .and_then(|bd| initialize_integrity(bd, integrity));
                 if let Err(err) = blockdev {
                     Err(clean_up_encrypted(&mut handle_clone, err))
                 } else {
@@ -711,7 +740,8 @@ pub fn initialize_devices(
                     dev_uuid,
                     (mda_data_size, BlockdevSize::new(blockdev_size)),
                     &dev_info.id_wwn,
-                );
+                )
+                .and_then(|bd| initialize_integrity(bd, integrity));
                 if let Err(err) = blockdev {
                     Err(clean_up_unencrypted(physical_path, err))
                 } else {
@@ -728,7 +758,8 @@ pub fn initialize_devices(
         pool_uuid: PoolUuid,
         mda_data_size: MDADataSize,
         encryption_info: Option<&EncryptionInfo>,
-        sector_size: Option<u32>,
+        crypt_params: Option<&CryptParams>,
+        integrity: Option<IntegrityHash>,
     ) -> StratisResult<Vec<StratBlockDev>> {
         let mut initialized_blockdevs: Vec<StratBlockDev> = Vec::new();
         for dev_info in devices.inner {
@@ -738,7 +769,8 @@ pub fn initialize_devices(
                 pool_uuid,
                 mda_data_size,
                 encryption_info,
-                sector_size,
+                crypt_params,
+                integrity,
             ) {
                 Ok(blockdev) => initialized_blockdevs.push(blockdev),
                 Err(err) => {
@@ -774,7 +806,8 @@ pub fn initialize_devices(
         pool_uuid,
         mda_data_size,
         encryption_info,
-        sector_size,
+        crypt_params,
+        integrity,
     );
 
     {
@@ -853,6 +886,7 @@ mod tests {
                 .map(|kd| EncryptionInfo::KeyDesc(kd.clone()))
                 .as_ref(),
             None,
+            None,
         )
         .unwrap();
 
@@ -1092,6 +1126,7 @@ mod tests {
                 .map(|kd| EncryptionInfo::KeyDesc(kd.clone()))
                 .as_ref(),
             None,
+            None,
         )
         .is_ok()
         {
@@ -0,0 +1,233 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Erasure of the allocated ranges of the block devices of a destroyed pool.
+
+use std::{
+    cmp::min,
+    fs::{File, OpenOptions},
+    os::unix::fs::{FileExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+use nix::errno::Errno;
+
+use devicemapper::{Bytes, Sectors, IEC};
+
+use crate::{
+    engine::{
+        strat_engine::device::{blkdev_discard, blkdev_size},
+        types::{EraseMode, JobProgress},
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// The size of the pieces in which ranges are erased, so that progress can
+/// be reported while a large range is erased.
+const ERASE_CHUNK_SIZE: Sectors = Sectors(2 * IEC::Mi); // 1 GiB
+
+/// The size of the buffer of zeros written to devices that do not support
+/// discard.
+const ZERO_BUFFER_SIZE: usize = IEC::Mi as usize; // 1 MiB
+
+/// A block device the ranges of which that were allocated to a destroyed
+/// pool are to be erased. Once the pool is destroyed, the device is also
+/// held open exclusively while it is erased, so that it can not be reused in
+/// the meantime.
+pub struct EraseTarget {
+    path: PathBuf,
+    file: File,
+    claim: Option<File>,
+    ranges: Vec<(Sectors, Sectors)>,
+}
+
+impl EraseTarget {
+    /// Open the device at path for erasing the given (start, length) ranges,
+    /// or the whole device if ranges is None, and verify that the ranges lie
+    /// within the device. This is done while the pool still exists, so the
+    /// device can not yet be opened exclusively.
+    pub fn open(
+        path: PathBuf,
+        ranges: Option<Vec<(Sectors, Sectors)>>,
+    ) -> StratisResult<EraseTarget> {
+        let file = OpenOptions::new().write(true).open(&path).map_err(|e| {
+            StratisError::Msg(format!(
+                "Failed to open device {} for erasing it: {e}",
+                path.display()
+            ))
+        })?;
+        let size = blkdev_size(&file)?.sectors();
+        let ranges = ranges.unwrap_or_else(|| vec![(Sectors(0), size)]);
+        if let Some((start, length)) = ranges
+            .iter()
+            .find(|(start, length)| *start + *length > size)
+        {
+            return Err(StratisError::Msg(format!(
+                "Range of {length} starting at {start} to be erased exceeds the size {size} of device {}",
+                path.display()
+            )));
+        }
+        Ok(EraseTarget {
+            path,
+            file,
+            claim: None,
+            ranges,
+        })
+    }
+
+    /// Open the device exclusively once the pool has been destroyed, so that
+    /// it can not be reused while it is erased. If this fails, the device is
+    /// erased regardless.
+    pub fn claim(&mut self) {
+        match OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_EXCL)
+            .open(&self.path)
+        {
+            Ok(f) => self.claim = Some(f),
+            Err(e) => warn!(
+                "Failed to open device {} exclusively while erasing it: {}",
+                self.path.display(),
+                e
+            ),
+        }
+    }
+}
+
+/// The means by which a range of a device is erased.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum EraseMethod {
+    SecureDiscard,
+    Discard,
+    Zero,
+}
+
+impl EraseMethod {
+    /// How the data on a device was erased with this method, if it was
+    /// requested with the given method.
+    fn describe(self, requested: EraseMethod) -> &'static str {
+        match self {
+            EraseMethod::SecureDiscard => "securely discarded",
+            EraseMethod::Discard if requested == EraseMethod::SecureDiscard => {
+                "discarded, but not securely, since the device does not support secure discard; the device may retain copies of the data"
+            }
+            EraseMethod::Discard => "discarded",
+            EraseMethod::Zero if requested != EraseMethod::Zero => {
+                "overwritten with zeros, since the device does not support discard"
+            }
+            EraseMethod::Zero => "overwritten with zeros",
+        }
+    }
+}
+
+/// Erase a chunk of a device with the given method. If the device does not
+/// support the method, fall back to the next weaker one. Return the method
+/// that succeeded so that it can be used for the rest of the device and
+/// reported once the device has been erased.
+fn erase_chunk(
+    path: &Path,
+    file: &File,
+    start: Sectors,
+    length: Sectors,
+    method: EraseMethod,
+) -> StratisResult<EraseMethod> {
+    match method {
+        EraseMethod::SecureDiscard | EraseMethod::Discard => {
+            let secure = method == EraseMethod::SecureDiscard;
+            match blkdev_discard(file, start.bytes(), length.bytes(), secure) {
+                Ok(()) => Ok(method),
+                Err(StratisError::Nix(Errno::EOPNOTSUPP)) => {
+                    let fallback = if secure {
+                        EraseMethod::Discard
+                    } else {
+                        EraseMethod::Zero
+                    };
+                    warn!(
+                        "Device {} does not support {}; falling back to {}",
+                        path.display(),
+                        if secure { "secure discard" } else { "discard" },
+                        if secure {
+                            "discard"
+                        } else {
+                            "overwriting with zeros"
+                        },
+                    );
+                    erase_chunk(path, file, start, length, fallback)
+                }
+                Err(e) => Err(e),
+            }
+        }
+        EraseMethod::Zero => {
+            let zeros = vec![0u8; ZERO_BUFFER_SIZE];
+            let end = (start + length).bytes();
+            let mut offset = start.bytes();
+            while offset < end {
+                let len = min(*(end - offset), ZERO_BUFFER_SIZE as u128);
+                file.write_all_at(
+                    &zeros[..convert_int!(len, u128, usize)?],
+                    convert_int!(*offset, u128, u64)?,
+                )?;
+                offset += Bytes(len);
+            }
+            Ok(method)
+        }
+    }
+}
+
+/// Erase the ranges of each target with the given erase mode and record the
+/// progress as the fraction of the total length of the ranges that has been
+/// erased. Return a report of how the data on each device was erased, which
+/// states if a device did not support the requested method and the data on
+/// it was erased by a weaker one.
+pub fn erase_ranges(
+    targets: Vec<EraseTarget>,
+    erase_mode: EraseMode,
+    progress: &JobProgress,
+) -> StratisResult<String> {
+    let initial_method = match erase_mode {
+        EraseMode::Discard => EraseMethod::SecureDiscard,
+        EraseMode::Zero => EraseMethod::Zero,
+        EraseMode::Keyslots => {
+            return Err(StratisError::Msg(
+                "Keyslots are not erased by erasing the ranges of a device".to_string(),
+            ))
+        }
+    };
+
+    let total = targets
+        .iter()
+        .flat_map(|target| target.ranges.iter().map(|(_, length)| *length))
+        .sum::<Sectors>();
+    let mut erased = Sectors(0);
+    progress.set(0);
+
+    let mut report = Vec::with_capacity(targets.len());
+    for target in targets {
+        let mut method = initial_method;
+        for (start, length) in target.ranges.iter() {
+            let end = *start + *length;
+            let mut offset = *start;
+            while offset < end {
+                let chunk = min(ERASE_CHUNK_SIZE, end - offset);
+                method = erase_chunk(&target.path, &target.file, offset, chunk, method)?;
+                offset += chunk;
+                erased += chunk;
+                progress.set(convert_int!(*erased * 100 / *total, u64, u8)?);
+            }
+        }
+        target.file.sync_all()?;
+        info!(
+            "Erased the ranges of device {} previously allocated to the pool",
+            target.path.display()
+        );
+        report.push(format!(
+            "{}: {}",
+            target.path.display(),
+            method.describe(initial_method)
+        ));
+    }
+
+    progress.set(100);
+    Ok(report.join("\n"))
+}
@@ -0,0 +1,306 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to handle the dm-integrity device that checks all data read from a
+// blockdev.
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use devicemapper::{
+    device_exists, DevId, Device, DmDevice, DmNameBuf, DmOptions, LinearDev, LinearDevTargetParams,
+    LinearTargetParams, Sectors, TargetLine, IEC, SECTOR_SIZE,
+};
+
+use crate::{
+    engine::{
+        strat_engine::{
+            dm::{get_dm, list_of_integrity_devices, remove_optional_devices, DEVICEMAPPER_PATH},
+            names::{format_integrity_ids, IntegrityRole},
+            serde_structs::{IntegritySave, Recordable},
+        },
+        types::{DevUuid, IntegrityHash},
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// Space reserved for the superblock and the journal or bitmap of the
+/// dm-integrity device. The kernel limits the journal to 64 MiB.
+const INTEGRITY_FIXED_META_SIZE: Sectors = Sectors(65 * IEC::Ki * 2); // 65 MiB
+
+/// The number of sectors at the end of a blockdev of the given size that
+/// are reserved to hold the integrity metadata. Every sector of the data
+/// region has a tag of hash.tag_size() bytes.
+pub fn integrity_meta_size(dev_size: Sectors, hash: IntegrityHash) -> Sectors {
+    let tags = *dev_size * hash.tag_size();
+    Sectors((tags + SECTOR_SIZE as u64 - 1) / SECTOR_SIZE as u64) + INTEGRITY_FIXED_META_SIZE
+}
+
+/// The layout of the integrity metadata on a device of the given size: the
+/// tags are kept at the end of the device. Returns an error if the device
+/// is too small to hold them and at least min_data_size sectors of data.
+pub fn integrity_layout(
+    dev_size: Sectors,
+    hash: IntegrityHash,
+    min_data_size: Sectors,
+) -> StratisResult<IntegritySave> {
+    let meta_length = integrity_meta_size(dev_size, hash);
+    if meta_length + min_data_size >= dev_size {
+        return Err(StratisError::Msg(format!(
+            "A device of {dev_size} is too small to hold the {meta_length} of integrity tags for its data"
+        )));
+    }
+    Ok(IntegritySave {
+        hash,
+        meta_start: dev_size - meta_length,
+        meta_length,
+    })
+}
+
+/// The path of the integrity device of the blockdev with the given UUID.
+pub fn integrity_devnode(dev_uuid: DevUuid) -> PathBuf {
+    let (name, _) = format_integrity_ids(dev_uuid, IntegrityRole::Integrity);
+    [DEVICEMAPPER_PATH, name.to_string().as_str()]
+        .iter()
+        .collect()
+}
+
+/// Remove the integrity device of the blockdev with the given UUID and the
+/// devices it is made up of, if they exist. The integrity device must no
+/// longer be referenced by any other DM device.
+pub fn remove_integrity_devices(dev_uuid: DevUuid) -> StratisResult<()> {
+    remove_optional_devices(list_of_integrity_devices(&[dev_uuid]))?;
+    Ok(())
+}
+
+/// A dm-integrity device that maps the data region of a blockdev, which
+/// begins at the start of the blockdev, so that the sectors of the
+/// integrity device are at the same offsets as the sectors of the blockdev
+/// that they map. The tags are kept in a separate region at the end of the
+/// blockdev. A read of a sector the tag of which does not match its
+/// contents fails with an I/O error and is counted by the kernel.
+///
+/// Data must only be written through the integrity device; the Stratis
+/// metadata at the start of the blockdev, which is never read through the
+/// integrity device, is the only exception.
+///
+/// On an encrypted blockdev, the integrity device is set up on the physical
+/// device beneath the encryption, and is the data device of the LUKS2
+/// header, which is kept at the start of the physical device. The LUKS2
+/// header, like the Stratis metadata on an unencrypted blockdev, is never
+/// read through the integrity device.
+#[derive(Debug)]
+pub struct IntegrityDev {
+    hash: IntegrityHash,
+    name: DmNameBuf,
+    device: Device,
+    data: LinearDev,
+    meta: LinearDev,
+}
+
+impl IntegrityDev {
+    /// Set up the integrity device for the blockdev with the given UUID on
+    /// the given device, on which the integrity metadata is kept in the
+    /// sectors [meta_start, meta_start + meta_length).
+    ///
+    /// If format is true, the integrity metadata is erased first, so that
+    /// the kernel formats the integrity device and calculates the tags of
+    /// all data already on the device in the background. Otherwise, an
+    /// integrity device that has already been set up, e.g. in order to
+    /// unlock an encrypted blockdev, is taken over.
+    pub fn setup(
+        dev_uuid: DevUuid,
+        base: Device,
+        hash: IntegrityHash,
+        meta_start: Sectors,
+        meta_length: Sectors,
+        format: bool,
+    ) -> StratisResult<IntegrityDev> {
+        let linear = |role, start, length| {
+            let (name, uuid) = format_integrity_ids(dev_uuid, role);
+            LinearDev::setup(
+                get_dm(),
+                &name,
+                Some(&uuid),
+                vec![TargetLine::new(
+                    Sectors(0),
+                    length,
+                    LinearDevTargetParams::Linear(LinearTargetParams::new(base, start)),
+                )],
+            )
+        };
+
+        let mut data = linear(IntegrityRole::Data, Sectors(0), meta_start)?;
+        let mut meta = match linear(IntegrityRole::Meta, meta_start, meta_length) {
+            Ok(meta) => meta,
+            Err(err) => {
+                teardown_linear(vec![data]);
+                return Err(StratisError::from(err));
+            }
+        };
+
+        if format {
+            if let Err(err) = erase_superblock(&meta.devnode()) {
+                teardown_linear(vec![data, meta]);
+                return Err(err);
+            }
+        }
+
+        let (name, uuid) = format_integrity_ids(dev_uuid, IntegrityRole::Integrity);
+        let dm = get_dm();
+        if !format && device_exists(dm, &name)? {
+            let device = dm.device_info(&DevId::Name(&name))?.device();
+            return Ok(IntegrityDev {
+                hash,
+                name,
+                device,
+                data,
+                meta,
+            });
+        }
+
+        let device = match dm.device_create(&name, Some(&uuid), DmOptions::default()) {
+            Ok(info) => info.device(),
+            Err(err) => {
+                teardown_linear(vec![data, meta]);
+                return Err(StratisError::from(err));
+            }
+        };
+
+        let params = format!(
+            "{} 0 - B 3 meta_device:{} internal_hash:{} recalculate",
+            data.device(),
+            meta.device(),
+            hash.algorithm()
+        );
+        let table = vec![(0, *data.size(), "integrity".to_string(), params)];
+        let id = DevId::Name(&name);
+        if let Err(err) = dm
+            .table_load(&id, &table, DmOptions::default())
+            .and_then(|_| dm.device_suspend(&id, DmOptions::default()))
+        {
+            if let Err(e) = dm.device_remove(&id, DmOptions::default()) {
+                warn!("Failed to remove partially constructed integrity device: {e}");
+            }
+            if let Err(e) = data.teardown(dm).and_then(|_| meta.teardown(dm)) {
+                warn!("Failed to remove partially constructed integrity device: {e}");
+            }
+            return Err(StratisError::from(err));
+        }
+
+        Ok(IntegrityDev {
+            hash,
+            name,
+            device,
+            data,
+            meta,
+        })
+    }
+
+    /// The device number of the integrity device.
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// The path of the integrity device.
+    pub fn devnode(&self) -> PathBuf {
+        [DEVICEMAPPER_PATH, self.name.to_string().as_str()]
+            .iter()
+            .collect()
+    }
+
+    /// The hash with which the integrity tags are calculated.
+    pub fn hash(&self) -> IntegrityHash {
+        self.hash
+    }
+
+    /// The number of reads that the kernel has failed because the data
+    /// read did not match its tag since the integrity device was set up.
+    pub fn mismatches(&self) -> StratisResult<u64> {
+        let (_, status) = get_dm().table_status(&DevId::Name(&self.name), DmOptions::default())?;
+        status.iter().try_fold(0, |acc, (_, _, _, params)| {
+            Ok(acc + parse_mismatches(params)?)
+        })
+    }
+
+    /// Remove the integrity device and the devices it is made up of. The
+    /// integrity device must no longer be referenced by any other DM device.
+    pub fn teardown(&mut self) -> StratisResult<()> {
+        get_dm().device_remove(&DevId::Name(&self.name), DmOptions::default())?;
+        self.data.teardown(get_dm())?;
+        self.meta.teardown(get_dm())?;
+        Ok(())
+    }
+}
+
+impl Recordable<IntegritySave> for IntegrityDev {
+    fn record(&self) -> IntegritySave {
+        IntegritySave {
+            hash: self.hash,
+            meta_start: self.data.size(),
+            meta_length: self.meta.size(),
+        }
+    }
+}
+
+/// Erase the superblock at the start of the integrity metadata, so that the
+/// kernel formats the integrity device when it is next set up.
+fn erase_superblock(meta_path: &Path) -> StratisResult<()> {
+    let mut f = OpenOptions::new().write(true).open(meta_path)?;
+    f.write_all(&[0u8; 4096])?;
+    f.sync_all()?;
+    Ok(())
+}
+
+/// Remove the linear devices of an integrity device that could not be set
+/// up.
+fn teardown_linear(devs: Vec<LinearDev>) {
+    for mut dev in devs {
+        if let Err(e) = dev.teardown(get_dm()) {
+            warn!("Failed to remove partially constructed integrity device: {e}");
+        }
+    }
+}
+
+/// Parse the number of mismatches from the status of an integrity target,
+/// which has the form
+/// "<mismatches> <provided_data_sectors> <recalculated_sector or ->".
+fn parse_mismatches(params: &str) -> StratisResult<u64> {
+    params
+        .split_whitespace()
+        .next()
+        .and_then(|n| n.parse::<u64>().ok())
+        .ok_or_else(|| StratisError::Msg(format!("Unexpected integrity target status: {params}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mismatches() {
+        assert_eq!(parse_mismatches("0 2097152 -").unwrap(), 0);
+        assert_eq!(parse_mismatches("17 2097152 1024").unwrap(), 17);
+        assert!(parse_mismatches("").is_err());
+        assert!(parse_mismatches("- 2097152 -").is_err());
+    }
+
+    #[test]
+    /// Verify that the space reserved for the tags grows with the size of
+    /// the tag.
+    fn test_integrity_meta_size() {
+        let size = Sectors(IEC::Gi * 2); // 1 GiB
+        assert_eq!(
+            integrity_meta_size(size, IntegrityHash::Crc32c),
+            Sectors(IEC::Ki * 16) + INTEGRITY_FIXED_META_SIZE
+        );
+        assert_eq!(
+            integrity_meta_size(size, IntegrityHash::Sha256),
+            Sectors(IEC::Ki * 128) + INTEGRITY_FIXED_META_SIZE
+        );
+    }
+}
@@ -0,0 +1,147 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to handle the temporary mirror device used to replace a blockdev.
+
+use devicemapper::{DevId, Device, DmNameBuf, DmOptions, Sectors};
+
+use crate::{
+    engine::{
+        strat_engine::{
+            dm::get_dm,
+            names::{format_backstore_ids, CacheRole},
+        },
+        types::PoolUuid,
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// The size of a region tracked by the in-core dirty log of the mirror.
+const MIRROR_REGION_SIZE: Sectors = Sectors(1024); // 512 KiB
+
+/// A single piece of data to be mirrored: length sectors starting at
+/// src_offset on src are mirrored to length sectors starting at dst_offset
+/// on dst.
+#[derive(Debug)]
+pub struct MirrorSegment {
+    pub src: Device,
+    pub src_offset: Sectors,
+    pub dst: Device,
+    pub dst_offset: Sectors,
+    pub length: Sectors,
+}
+
+/// A DM mirror device with two legs for every segment. The first leg is the
+/// source of the data, the second its destination; the kernel copies the
+/// data from the first leg to the second, while writes go to both.
+#[derive(Debug)]
+pub struct MirrorDev {
+    name: DmNameBuf,
+    device: Device,
+}
+
+impl MirrorDev {
+    /// Create the mirror device for the given pool. The segments are mapped
+    /// consecutively, in the order given.
+    pub fn setup(pool_uuid: PoolUuid, segments: &[MirrorSegment]) -> StratisResult<MirrorDev> {
+        let (name, uuid) = format_backstore_ids(pool_uuid, CacheRole::Mirror);
+
+        let mut table = Vec::with_capacity(segments.len());
+        let mut offset = Sectors(0);
+        for seg in segments {
+            table.push((
+                *offset,
+                *seg.length,
+                "mirror".to_string(),
+                format!(
+                    "core 1 {} 2 {} {} {} {} 1 handle_errors",
+                    *MIRROR_REGION_SIZE, seg.src, *seg.src_offset, seg.dst, *seg.dst_offset
+                ),
+            ));
+            offset += seg.length;
+        }
+
+        let dm = get_dm();
+        let device = dm
+            .device_create(&name, Some(&uuid), DmOptions::default())?
+            .device();
+        let id = DevId::Name(&name);
+        if let Err(err) = dm
+            .table_load(&id, &table, DmOptions::default())
+            .and_then(|_| dm.device_suspend(&id, DmOptions::default()))
+        {
+            if let Err(e) = dm.device_remove(&id, DmOptions::default()) {
+                warn!("Failed to remove partially constructed mirror device: {e}");
+            }
+            return Err(StratisError::from(err));
+        }
+
+        Ok(MirrorDev { name, device })
+    }
+
+    /// The device number of the mirror device.
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// The number of regions that have been copied and the total number of
+    /// regions, summed over all the segments of the mirror.
+    pub fn sync_progress(&self) -> StratisResult<(u64, u64)> {
+        let (_, status) = get_dm().table_status(&DevId::Name(&self.name), DmOptions::default())?;
+        status
+            .iter()
+            .try_fold((0, 0), |(in_sync, total), (_, _, _, params)| {
+                let (seg_in_sync, seg_total) = parse_sync_ratio(params)?;
+                Ok((in_sync + seg_in_sync, total + seg_total))
+            })
+    }
+
+    /// Remove the mirror device. The device must no longer be referenced by
+    /// any other DM device.
+    pub fn teardown(self) -> StratisResult<()> {
+        get_dm().device_remove(&DevId::Name(&self.name), DmOptions::default())?;
+        Ok(())
+    }
+}
+
+/// Parse the "<in_sync>/<total>" region count from the status of a mirror
+/// target, which has the form
+/// "<#legs> <leg>... <in_sync>/<total> <#health> <health> ...".
+fn parse_sync_ratio(params: &str) -> StratisResult<(u64, u64)> {
+    let err = || StratisError::Msg(format!("Unexpected mirror target status: {params}"));
+
+    let mut fields = params.split_whitespace();
+    let num_legs = fields
+        .next()
+        .and_then(|n| n.parse::<usize>().ok())
+        .ok_or_else(err)?;
+    let (in_sync, total) = fields
+        .nth(num_legs)
+        .and_then(|ratio| ratio.split_once('/'))
+        .ok_or_else(err)?;
+
+    Ok((
+        in_sync.parse::<u64>().map_err(|_| err())?,
+        total.parse::<u64>().map_err(|_| err())?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sync_ratio() {
+        assert_eq!(
+            parse_sync_ratio("2 253:1 253:2 12/40 1 AA 1 core").unwrap(),
+            (12, 40)
+        );
+        assert_eq!(
+            parse_sync_ratio("2 253:1 253:2 40/40 1 AD 1 core").unwrap(),
+            (40, 40)
+        );
+        assert!(parse_sync_ratio("2 253:1 253:2").is_err());
+        assert!(parse_sync_ratio("").is_err());
+    }
+}
@@ -6,20 +6,30 @@
 mod backstore;
 mod blockdev;
 mod blockdevmgr;
+mod cache;
 mod cache_tier;
 mod crypt;
 mod data_tier;
 mod devices;
+mod erase;
+mod integrity;
+mod mirror;
+mod raid;
 mod range_alloc;
 mod shared;
+mod stripe;
 mod transaction;
 
 pub use self::{
     backstore::Backstore,
     blockdev::{StratBlockDev, StratSectorSizes, UnderlyingDevice},
     crypt::{
-        crypt_metadata_size, register_clevis_token, set_up_crypt_logging, CryptHandle,
-        CLEVIS_TANG_TRUST_URL,
+        crypt_metadata_size, register_clevis_token, restore_luks_header_from_archive,
+        set_up_crypt_logging, CryptHandle, CLEVIS_TANG_TRUST_URL,
     },
-    devices::{find_stratis_devs_by_uuid, initialize_devices, ProcessedPathInfos, UnownedDevices},
+    devices::{
+        find_stratis_devs_by_uuid, get_devno_from_path, initialize_devices, wipe_blockdevs,
+        ProcessedPathInfos, UnownedDevices,
+    },
+    erase::{erase_ranges, EraseTarget},
 };
@@ -0,0 +1,340 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to handle the raid device that holds the data of a data tier with
+// redundancy.
+
+use devicemapper::{
+    DevId, Device, DmDevice, DmNameBuf, DmOptions, LinearDev, LinearDevTargetParams, Sectors,
+    TargetLine,
+};
+
+use crate::{
+    engine::{
+        strat_engine::{
+            dm::get_dm,
+            names::{format_backstore_ids, CacheRole},
+            writing::wipe_sectors,
+        },
+        types::PoolUuid,
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// The size of a region of the raid device for the purpose of
+/// resynchronization.
+const RAID_REGION_SIZE: Sectors = Sectors(1024); // 512 KiB
+
+/// The number of sectors at the start of each leg that hold the metadata of
+/// the raid device for that leg: a superblock and a bitmap of the regions
+/// that are in sync.
+pub const RAID_META_SIZE: Sectors = Sectors(8192); // 4 MiB
+
+/// The tables of the DM devices that make up one leg of the raid device.
+pub struct RaidLegTables {
+    /// The table of the device that holds the raid metadata for the leg
+    pub meta: Vec<TargetLine<LinearDevTargetParams>>,
+    /// The table of the device that holds the copy of the data
+    pub image: Vec<TargetLine<LinearDevTargetParams>>,
+}
+
+/// The DM devices that make up one leg of the raid device.
+#[derive(Debug)]
+struct RaidLeg {
+    meta: LinearDev,
+    image: LinearDev,
+}
+
+impl RaidLeg {
+    /// Set up the devices of the leg with the given index.
+    fn setup(pool_uuid: PoolUuid, leg: usize, tables: RaidLegTables) -> StratisResult<RaidLeg> {
+        let (name, uuid) = format_backstore_ids(pool_uuid, CacheRole::RaidMeta(leg));
+        let mut meta = LinearDev::setup(get_dm(), &name, Some(&uuid), tables.meta)?;
+        let (name, uuid) = format_backstore_ids(pool_uuid, CacheRole::RaidLeg(leg));
+        match LinearDev::setup(get_dm(), &name, Some(&uuid), tables.image) {
+            Ok(image) => Ok(RaidLeg { meta, image }),
+            Err(err) => {
+                if let Err(e) = meta.teardown(get_dm()) {
+                    warn!(
+                        "Failed to remove metadata device of partially constructed raid leg: {e}"
+                    );
+                }
+                Err(StratisError::from(err))
+            }
+        }
+    }
+
+    fn teardown(mut self) -> StratisResult<()> {
+        self.image.teardown(get_dm())?;
+        self.meta.teardown(get_dm())?;
+        Ok(())
+    }
+}
+
+/// A DM raid1 device. Each leg is made up of a linear device that maps one
+/// copy of the data allocated in the data tier and a linear device that
+/// maps the metadata in which the kernel records which regions of the leg
+/// are in sync, so that only regions that were written while the legs were
+/// out of sync are resynchronized when the device is set up again.
+///
+/// A leg that is absent, because some blockdev from which it is allocated
+/// has not been found, is omitted from the table; the device then runs
+/// degraded on the remaining legs.
+#[derive(Debug)]
+pub struct RaidDev {
+    pool_uuid: PoolUuid,
+    name: DmNameBuf,
+    device: Device,
+    size: Sectors,
+    legs: Vec<Option<RaidLeg>>,
+}
+
+impl RaidDev {
+    /// Create the raid device for the given pool, with one leg for each
+    /// entry in leg_tables; None for a leg that is absent. The images of all
+    /// legs must map the same number of sectors.
+    ///
+    /// Return an error if all the legs are absent.
+    pub fn setup(
+        pool_uuid: PoolUuid,
+        leg_tables: Vec<Option<RaidLegTables>>,
+    ) -> StratisResult<RaidDev> {
+        if leg_tables.iter().all(|tables| tables.is_none()) {
+            return Err(StratisError::Msg(
+                "No copy of the data in the data tier is complete; the raid device can not be set up".to_string(),
+            ));
+        }
+
+        let mut legs = Vec::with_capacity(leg_tables.len());
+        for (leg, tables) in leg_tables.into_iter().enumerate() {
+            match tables.map(|tables| RaidLeg::setup(pool_uuid, leg, tables)) {
+                Some(Ok(raid_leg)) => legs.push(Some(raid_leg)),
+                Some(Err(err)) => {
+                    teardown_legs(legs);
+                    return Err(err);
+                }
+                None => legs.push(None),
+            }
+        }
+
+        let size = leg_size(&legs);
+        let (name, uuid) = format_backstore_ids(pool_uuid, CacheRole::Raid);
+        let dm = get_dm();
+        let device = match dm.device_create(&name, Some(&uuid), DmOptions::default()) {
+            Ok(info) => info.device(),
+            Err(err) => {
+                teardown_legs(legs);
+                return Err(StratisError::from(err));
+            }
+        };
+
+        let raid = RaidDev {
+            pool_uuid,
+            name,
+            device,
+            size,
+            legs,
+        };
+        if let Err(err) = raid.load_table(None) {
+            if let Err(e) = dm.device_remove(&DevId::Name(&raid.name), DmOptions::default()) {
+                warn!("Failed to remove partially constructed raid device: {e}");
+            }
+            teardown_legs(raid.legs);
+            return Err(err);
+        }
+
+        Ok(raid)
+    }
+
+    /// Load a table that maps all the sectors of the legs and make it the
+    /// active table. If rebuild is specified, the kernel copies all the
+    /// data to the leg with that index from the other legs.
+    fn load_table(&self, rebuild: Option<usize>) -> StratisResult<()> {
+        let rebuild_params = rebuild
+            .map(|leg| format!(" rebuild {leg}"))
+            .unwrap_or_default();
+        let params = format!(
+            "raid1 {} 0 region_size {}{} {} {}",
+            if rebuild.is_some() { 5 } else { 3 },
+            *RAID_REGION_SIZE,
+            rebuild_params,
+            self.legs.len(),
+            self.legs
+                .iter()
+                .map(|leg| match leg {
+                    Some(leg) => format!("{} {}", leg.meta.device(), leg.image.device()),
+                    None => "- -".to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        let table = vec![(0, *self.size, "raid".to_string(), params)];
+
+        let dm = get_dm();
+        let id = DevId::Name(&self.name);
+        dm.table_load(&id, &table, DmOptions::default())?;
+        dm.device_suspend(&id, DmOptions::default())?;
+        Ok(())
+    }
+
+    /// Extend the images of the legs to map the given tables and the raid
+    /// device to map all the sectors of its legs. The metadata devices of
+    /// the legs are not changed. Only the regions that are added are
+    /// synchronized.
+    ///
+    /// Return an error if any leg is absent.
+    pub fn extend(&mut self, leg_tables: Vec<Option<RaidLegTables>>) -> StratisResult<()> {
+        assert_eq!(self.legs.len(), leg_tables.len());
+
+        if self.legs.iter().any(|leg| leg.is_none()) || leg_tables.iter().any(|t| t.is_none()) {
+            return Err(StratisError::Msg(
+                "The raid device can not be extended while a copy of the data is absent"
+                    .to_string(),
+            ));
+        }
+
+        for (leg, tables) in self
+            .legs
+            .iter_mut()
+            .flatten()
+            .zip(leg_tables.into_iter().flatten())
+        {
+            leg.image.set_table(get_dm(), tables.image)?;
+            leg.image.resume(get_dm())?;
+        }
+
+        self.size = leg_size(&self.legs);
+        self.load_table(None)
+    }
+
+    /// Replace the leg with the given index by a leg that maps the given
+    /// tables, and have the kernel copy all the data to it from the other
+    /// legs. The raid device remains in use; until the copy is complete the
+    /// data is held only by the other legs.
+    pub fn rebuild_leg(&mut self, leg: usize, tables: RaidLegTables) -> StratisResult<()> {
+        // Remove the leg from the table before its devices are removed.
+        if let Some(old) = self.legs[leg].take() {
+            self.load_table(None)?;
+            old.teardown()?;
+        }
+
+        let raid_leg = RaidLeg::setup(self.pool_uuid, leg, tables)?;
+        // Stale metadata on the new leg would tell the kernel that some of
+        // its regions are in sync.
+        if let Err(err) = wipe_sectors(raid_leg.meta.devnode(), Sectors(0), raid_leg.meta.size()) {
+            if let Err(e) = raid_leg.teardown() {
+                warn!("Failed to remove raid leg that could not be initialized: {e}");
+            }
+            return Err(err);
+        }
+        self.legs[leg] = Some(raid_leg);
+
+        self.load_table(Some(leg))
+    }
+
+    /// The device number of the raid device.
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// The number of sectors mapped by the raid device.
+    pub fn size(&self) -> Sectors {
+        self.size
+    }
+
+    /// Whether any leg of the raid device is absent or has been failed by
+    /// the kernel.
+    pub fn has_failed_leg(&self) -> StratisResult<bool> {
+        if self.legs.iter().any(|leg| leg.is_none()) {
+            return Ok(true);
+        }
+
+        let (_, status) = get_dm().table_status(&DevId::Name(&self.name), DmOptions::default())?;
+        status.iter().try_fold(false, |failed, (_, _, _, params)| {
+            Ok(failed || parse_health(params)?.contains('D'))
+        })
+    }
+
+    /// Whether every leg of the raid device, other than the leg with the
+    /// index except, if specified, is present and in sync.
+    pub fn in_sync(&self, except: Option<usize>) -> StratisResult<bool> {
+        let (_, status) = get_dm().table_status(&DevId::Name(&self.name), DmOptions::default())?;
+        status.iter().try_fold(true, |in_sync, (_, _, _, params)| {
+            Ok(in_sync
+                && parse_health(params)?
+                    .chars()
+                    .enumerate()
+                    .all(|(leg, health)| Some(leg) == except || health == 'A'))
+        })
+    }
+
+    /// Remove the raid device and its legs. The raid device must no longer
+    /// be referenced by any other DM device.
+    pub fn teardown(self) -> StratisResult<()> {
+        get_dm().device_remove(&DevId::Name(&self.name), DmOptions::default())?;
+        for leg in self.legs.into_iter().flatten() {
+            leg.teardown()?;
+        }
+        Ok(())
+    }
+}
+
+/// The number of sectors mapped by the image of every leg that is present.
+fn leg_size(legs: &[Option<RaidLeg>]) -> Sectors {
+    legs.iter()
+        .flatten()
+        .map(|leg| leg.image.size())
+        .min()
+        .unwrap_or(Sectors(0))
+}
+
+/// Remove the legs of a raid device that could not be set up.
+fn teardown_legs(legs: Vec<Option<RaidLeg>>) {
+    for leg in legs.into_iter().flatten() {
+        if let Err(e) = leg.teardown() {
+            warn!("Failed to remove leg of partially constructed raid device: {e}");
+        }
+    }
+}
+
+/// Parse the health characters, one for each leg, from the status of a raid
+/// target, which has the form
+/// "<raid_type> <#devices> <health_chars> <sync_ratio> <sync_action> ...".
+/// A leg is "A" if it is alive and in sync, "a" if it is alive but not in
+/// sync, and "D" if it has failed.
+fn parse_health(params: &str) -> StratisResult<&str> {
+    let err = || StratisError::Msg(format!("Unexpected raid target status: {params}"));
+
+    let mut fields = params.split_whitespace();
+    let num_legs = fields
+        .nth(1)
+        .and_then(|n| n.parse::<usize>().ok())
+        .ok_or_else(err)?;
+    let health = fields.next().ok_or_else(err)?;
+    if health.len() != num_legs {
+        return Err(err());
+    }
+
+    Ok(health)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_health() {
+        assert_eq!(
+            parse_health("raid1 2 AA 2048/2048 idle 0 0 -").unwrap(),
+            "AA"
+        );
+        assert_eq!(
+            parse_health("raid1 2 aD 1024/2048 recover 0 0 -").unwrap(),
+            "aD"
+        );
+        assert!(parse_health("raid1 2 A 2048/2048 idle 0 0 -").is_err());
+        assert!(parse_health("raid1").is_err());
+        assert!(parse_health("").is_err());
+    }
+}
@@ -225,6 +225,40 @@ impl PerDevSegments {
         Ok(())
     }
 
+    /// Remove specified range from self. Return an error if the range is not
+    /// wholly contained in a single existing range. If the range is in the
+    /// middle of an existing range, split the existing range in two.
+    /// Removing a 0 length range has no effect.
+    pub fn remove(&mut self, range: &(Sectors, Sectors)) -> StratisResult<()> {
+        let &(start, len) = range;
+
+        if len == Sectors(0) {
+            return Ok(());
+        }
+
+        let (used_start, used_len) = match self.used.range(..=start).next_back() {
+            Some((&used_start, &used_len)) if start + len <= used_start + used_len => {
+                (used_start, used_len)
+            }
+            _ => {
+                return Err(StratisError::Msg(format!(
+                    "range ({start}, {len}) is not contained in any used range"
+                )));
+            }
+        };
+
+        self.used.remove(&used_start);
+        if start > used_start {
+            self.used.insert(used_start, start - used_start);
+        }
+        if start + len < used_start + used_len {
+            self.used
+                .insert(start + len, used_start + used_len - (start + len));
+        }
+
+        Ok(())
+    }
+
     /// Take the union of two PerDevSegments. Require that both PerDevSegments
     /// objects have the same limit, for simplicity.
     pub fn union(&self, other: &PerDevSegments) -> StratisResult<PerDevSegments> {
@@ -350,14 +384,11 @@ impl RangeAllocator {
         self.segments.sum()
     }
 
-    /// Attempt to allocate.
-    /// Returns a PerDevSegments object containing the allocated ranges.
-    /// The device UUID is used to filter out all segment requests on devices
-    /// other than the specified device.
-    pub fn request(
+    /// The ranges that are neither allocated nor reserved on the device with
+    /// the specified UUID by the transaction.
+    fn unused(
         &self,
         uuid: DevUuid,
-        amount: Sectors,
         transaction: &RequestTransaction,
     ) -> StratisResult<PerDevSegments> {
         let trans_used = transaction
@@ -378,12 +409,23 @@ impl RangeAllocator {
                 },
             )?;
 
-        let total_segments = self.segments.union(&trans_used)?;
+        Ok(self.segments.union(&trans_used)?.complement())
+    }
 
+    /// Attempt to allocate.
+    /// Returns a PerDevSegments object containing the allocated ranges.
+    /// The device UUID is used to filter out all segment requests on devices
+    /// other than the specified device.
+    pub fn request(
+        &self,
+        uuid: DevUuid,
+        amount: Sectors,
+        transaction: &RequestTransaction,
+    ) -> StratisResult<PerDevSegments> {
         let mut segs = PerDevSegments::new(self.segments.limit());
         let mut needed = amount;
 
-        for (&start, &len) in total_segments.complement().iter() {
+        for (&start, &len) in self.unused(uuid, transaction)?.iter() {
             if needed == Sectors(0) {
                 break;
             }
@@ -396,6 +438,23 @@ impl RangeAllocator {
         Ok(segs)
     }
 
+    /// Attempt to allocate a single range of the specified length.
+    /// Returns the start of the first unused range that is long enough, or
+    /// None if there is no such range. The device UUID is used as in
+    /// request().
+    pub fn request_contiguous(
+        &self,
+        uuid: DevUuid,
+        amount: Sectors,
+        transaction: &RequestTransaction,
+    ) -> StratisResult<Option<Sectors>> {
+        Ok(self
+            .unused(uuid, transaction)?
+            .iter()
+            .find(|(_, &len)| len >= amount)
+            .map(|(&start, _)| start))
+    }
+
     /// Commit an allocation determined to be valid.
     ///
     /// This method does not actually modify metadata but is required for bookkeeping
@@ -407,6 +466,13 @@ impl RangeAllocator {
             .expect("all segments verified to be in available ranges");
     }
 
+    /// Return a range that was previously committed, so that it may be
+    /// allocated again. Return an error if the range is not wholly contained
+    /// in an allocated range.
+    pub fn release(&mut self, range: &(Sectors, Sectors)) -> StratisResult<()> {
+        self.segments.remove(range)
+    }
+
     /// Increase the available size of the RangeAlloc data structure.
     ///
     /// Precondition: new_size > self.limit
@@ -414,12 +480,84 @@ impl RangeAllocator {
         assert!(new_size > self.segments.limit);
         self.segments.limit = new_size;
     }
+
+    /// Decrease the available size of the RangeAlloc data structure.
+    /// Return an error and leave the size as is if any allocated range
+    /// extends past the new size.
+    ///
+    /// Precondition: new_size < self.limit
+    pub fn decrease_size(&mut self, new_size: Sectors) -> StratisResult<()> {
+        assert!(new_size < self.segments.limit);
+        if let Some((&start, &len)) = self.segments.used.iter().next_back() {
+            if start + len > new_size {
+                return Err(StratisError::Msg(format!(
+                    "Sectors up to {} are allocated; the allocator can not be shrunk to {}",
+                    start + len,
+                    new_size
+                )));
+            }
+        }
+        self.segments.limit = new_size;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use devicemapper::Device;
+
+    use crate::engine::strat_engine::backstore::shared::{BlkDevSegment, Segment};
+
     use super::*;
 
+    #[test]
+    /// Verify that an allocator can be shrunk only if no allocated range
+    /// extends past the new size.
+    fn test_allocator_decrease_size() {
+        let mut allocator = RangeAllocator::new(
+            BlockdevSize::new(Sectors(128)),
+            &[(Sectors(10), Sectors(50))],
+        )
+        .unwrap();
+
+        assert_matches!(allocator.decrease_size(Sectors(59)), Err(_));
+        assert_eq!(allocator.size(), BlockdevSize::new(Sectors(128)));
+
+        allocator.decrease_size(Sectors(60)).unwrap();
+        assert_eq!(allocator.size(), BlockdevSize::new(Sectors(60)));
+        assert_eq!(allocator.available(), Sectors(10));
+        allocator.segments.invariant();
+    }
+
+    #[test]
+    /// Verify that released ranges become available again and that only
+    /// allocated ranges can be released.
+    fn test_allocator_release() {
+        let mut allocator = RangeAllocator::new(
+            BlockdevSize::new(Sectors(128)),
+            &[(Sectors(10), Sectors(50)), (Sectors(70), Sectors(20))],
+        )
+        .unwrap();
+
+        assert_matches!(allocator.release(&(Sectors(50), Sectors(20))), Err(_));
+        assert_matches!(allocator.release(&(Sectors(0), Sectors(20))), Err(_));
+        assert_eq!(allocator.used(), Sectors(70));
+
+        allocator.release(&(Sectors(20), Sectors(10))).unwrap();
+        allocator.segments.invariant();
+        assert_eq!(allocator.segments.len(), 3);
+        assert_eq!(allocator.used(), Sectors(60));
+
+        allocator.release(&(Sectors(70), Sectors(20))).unwrap();
+        allocator.release(&(Sectors(40), Sectors(20))).unwrap();
+        allocator.segments.invariant();
+        assert_eq!(
+            allocator.segments.iter().collect::<Vec<_>>(),
+            vec![(&Sectors(10), &Sectors(10)), (&Sectors(30), &Sectors(10))]
+        );
+        assert_eq!(allocator.available(), Sectors(108));
+    }
+
     #[test]
     /// Test proper operation of RangeAllocator.
     /// 1. Instantiate a RangeAllocator.
@@ -653,4 +791,58 @@ mod tests {
 
         allocator.invariant();
     }
+
+    #[test]
+    /// Verify that request_contiguous() finds the first unused range that is
+    /// long enough, taking into account the ranges reserved by the
+    /// transaction, and finds nothing if there is no such range.
+    fn test_allocator_request_contiguous() {
+        let allocator = RangeAllocator::new(
+            BlockdevSize::new(Sectors(128)),
+            &[(Sectors(10), Sectors(100))],
+        )
+        .unwrap();
+        let uuid = DevUuid::new_v4();
+
+        let transaction = RequestTransaction::default();
+        assert_eq!(
+            allocator
+                .request_contiguous(uuid, Sectors(10), &transaction)
+                .unwrap(),
+            Some(Sectors(0))
+        );
+        assert_eq!(
+            allocator
+                .request_contiguous(uuid, Sectors(11), &transaction)
+                .unwrap(),
+            Some(Sectors(110))
+        );
+        assert_eq!(
+            allocator
+                .request_contiguous(uuid, Sectors(19), &transaction)
+                .unwrap(),
+            None
+        );
+
+        let mut transaction = RequestTransaction::default();
+        transaction.add_bd_seg_req(
+            0,
+            BlkDevSegment::new(
+                uuid,
+                Segment::new(Device { major: 0, minor: 0 }, Sectors(110), Sectors(1)),
+            ),
+        );
+        assert_eq!(
+            allocator
+                .request_contiguous(uuid, Sectors(18), &transaction)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            allocator
+                .request_contiguous(uuid, Sectors(17), &transaction)
+                .unwrap(),
+            Some(Sectors(111))
+        );
+    }
 }
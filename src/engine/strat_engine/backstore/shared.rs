@@ -138,8 +138,43 @@ impl AllocatedAbove {
         );
     }
 
+    /// Remove the last amount sectors from the segments. Return the pieces
+    /// of the segments that were removed, in the order in which they were
+    /// mapped.
+    ///
+    /// Precondition: amount <= self.size()
+    pub fn truncate(&mut self, amount: Sectors) -> Vec<BlkDevSegment> {
+        assert!(amount <= self.size());
+
+        let mut removed = Vec::new();
+        let mut remaining = amount;
+        while remaining > Sectors(0) {
+            let last = self
+                .inner
+                .last_mut()
+                .expect("amount <= self.size(), so some segment remains");
+            if last.segment.length <= remaining {
+                remaining -= last.segment.length;
+                removed.push(self.inner.pop().expect("last segment exists"));
+            } else {
+                last.segment.length -= remaining;
+                removed.push(BlkDevSegment::new(
+                    last.uuid,
+                    Segment::new(
+                        last.segment.device,
+                        last.segment.start + last.segment.length,
+                        remaining,
+                    ),
+                ));
+                remaining = Sectors(0);
+            }
+        }
+        removed.reverse();
+
+        removed
+    }
+
     /// A set of UUIDs of every device that is allocated from.
-    #[cfg(test)]
     pub fn uuids(&self) -> HashSet<DevUuid> {
         self.inner
             .iter()
@@ -0,0 +1,285 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to handle the device that maps a data tier some extents of which are
+// striped across blockdevs.
+
+use std::cmp::{max, min};
+
+use devicemapper::{DevId, Device, DmFlags, DmNameBuf, DmOptions, Sectors};
+
+use crate::{
+    engine::{
+        strat_engine::{
+            backstore::shared::{AllocatedAbove, BlkDevSegment},
+            dm::get_dm,
+            names::{format_backstore_ids, CacheRole},
+            serde_structs::{Recordable, StripeSave},
+        },
+        types::PoolUuid,
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// A DM table in the form accepted by table_load().
+type Table = Vec<(u64, u64, String, String)>;
+
+/// An extent of the data tier that is striped across blockdevs. The extent
+/// begins at start in the concatenation of the segments allocated to the
+/// cap device; the next length sectors of the concatenation are made up of
+/// stripes contiguous pieces of equal size, each on a different blockdev.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StripedExtent {
+    pub start: Sectors,
+    pub length: Sectors,
+    pub stripes: usize,
+    pub stripe_size: Sectors,
+}
+
+impl StripedExtent {
+    /// The length of each piece of the extent.
+    pub fn width(&self) -> Sectors {
+        self.length / self.stripes
+    }
+}
+
+impl<'a> From<&'a StripeSave> for StripedExtent {
+    fn from(save: &StripeSave) -> StripedExtent {
+        StripedExtent {
+            start: save.start,
+            length: save.length,
+            stripes: save.stripes,
+            stripe_size: save.stripe_size,
+        }
+    }
+}
+
+impl Recordable<StripeSave> for StripedExtent {
+    fn record(&self) -> StripeSave {
+        StripeSave {
+            start: self.start,
+            length: self.length,
+            stripes: self.stripes,
+            stripe_size: self.stripe_size,
+        }
+    }
+}
+
+/// The pieces of the segments, as (device, offset, length) triples, that
+/// make up the sectors [start, start + length) of their concatenation.
+fn pieces(
+    segments: &[BlkDevSegment],
+    start: Sectors,
+    length: Sectors,
+) -> Vec<(Device, Sectors, Sectors)> {
+    let end = start + length;
+    let mut pieces = Vec::new();
+    let mut seg_start = Sectors(0);
+    for seg in segments {
+        if seg_start >= end {
+            break;
+        }
+        let seg_end = seg_start + seg.segment.length;
+        if seg_end > start {
+            let from = max(start, seg_start);
+            let to = min(end, seg_end);
+            pieces.push((
+                seg.segment.device,
+                seg.segment.start + (from - seg_start),
+                to - from,
+            ));
+        }
+        seg_start = seg_end;
+    }
+    pieces
+}
+
+/// Append linear lines to the table for the sectors [start, start + length)
+/// of the concatenation of the segments.
+fn push_linear(table: &mut Table, segments: &[BlkDevSegment], start: Sectors, length: Sectors) {
+    let mut logical = start;
+    for (device, offset, length) in pieces(segments, start, length) {
+        table.push((
+            *logical,
+            *length,
+            "linear".to_string(),
+            format!("{device} {}", *offset),
+        ));
+        logical += length;
+    }
+}
+
+/// Build the table of the stripe device, which maps the concatenation of
+/// the segments. The sectors in each extent are striped across the pieces
+/// of the extent, all others are mapped linearly. The extents must be
+/// ordered and may not overlap.
+///
+/// Return an error if a piece of an extent is not contiguous on a single
+/// device, since such an extent can not be mapped by a striped target.
+pub fn stripe_table(segments: &AllocatedAbove, extents: &[StripedExtent]) -> StratisResult<Table> {
+    let mut table = Vec::new();
+    let mut next = Sectors(0);
+    for extent in extents {
+        push_linear(&mut table, &segments.inner, next, extent.start - next);
+
+        let width = extent.width();
+        let mut params = vec![
+            extent.stripes.to_string(),
+            (*extent.stripe_size).to_string(),
+        ];
+        for stripe in 0..extent.stripes {
+            match pieces(&segments.inner, extent.start + width * stripe, width).as_slice() {
+                [(device, offset, _)] => params.push(format!("{device} {}", **offset)),
+                _ => {
+                    return Err(StratisError::Msg(format!(
+                        "Stripe {stripe} of the extent at {} in the data tier is not contiguous",
+                        extent.start
+                    )));
+                }
+            }
+        }
+        table.push((
+            *extent.start,
+            *extent.length,
+            "striped".to_string(),
+            params.join(" "),
+        ));
+
+        next = extent.start + extent.length;
+    }
+    push_linear(&mut table, &segments.inner, next, segments.size() - next);
+
+    Ok(table)
+}
+
+/// A DM device that maps all the segments allocated in the data tier, some
+/// extents of which are striped.
+#[derive(Debug)]
+pub struct StripeDev {
+    name: DmNameBuf,
+    device: Device,
+    size: Sectors,
+}
+
+impl StripeDev {
+    /// Create the stripe device for the given pool with the given table.
+    pub fn setup(pool_uuid: PoolUuid, table: Table) -> StratisResult<StripeDev> {
+        let (name, uuid) = format_backstore_ids(pool_uuid, CacheRole::Stripe);
+        let dm = get_dm();
+        let device = dm
+            .device_create(&name, Some(&uuid), DmOptions::default())?
+            .device();
+
+        let mut stripe = StripeDev {
+            name,
+            device,
+            size: Sectors(0),
+        };
+        if let Err(err) = stripe.set_table(table) {
+            if let Err(e) = dm.device_remove(&DevId::Name(&stripe.name), DmOptions::default()) {
+                warn!("Failed to remove partially constructed stripe device: {e}");
+            }
+            return Err(err);
+        }
+
+        Ok(stripe)
+    }
+
+    /// Load the table and make it the active table.
+    pub fn set_table(&mut self, table: Table) -> StratisResult<()> {
+        let size = table
+            .iter()
+            .map(|(_, length, _, _)| Sectors(*length))
+            .sum::<Sectors>();
+
+        let dm = get_dm();
+        let id = DevId::Name(&self.name);
+        dm.table_load(&id, &table, DmOptions::default())?;
+        dm.device_suspend(&id, DmOptions::default())?;
+
+        self.size = size;
+        Ok(())
+    }
+
+    /// Suspend the stripe device; it is resumed when its table is next set.
+    pub fn suspend(&mut self) -> StratisResult<()> {
+        get_dm().device_suspend(
+            &DevId::Name(&self.name),
+            DmOptions::default().set_flags(DmFlags::DM_SUSPEND),
+        )?;
+        Ok(())
+    }
+
+    /// The device number of the stripe device.
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// The number of sectors mapped by the stripe device.
+    pub fn size(&self) -> Sectors {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{strat_engine::backstore::shared::Segment, types::DevUuid};
+
+    use super::*;
+
+    fn seg(uuid: DevUuid, minor: u32, start: u64, length: u64) -> BlkDevSegment {
+        BlkDevSegment::new(
+            uuid,
+            Segment::new(Device { major: 8, minor }, Sectors(start), Sectors(length)),
+        )
+    }
+
+    #[test]
+    /// Verify that the sectors of an extent are striped and that all other
+    /// sectors are mapped linearly.
+    fn test_stripe_table() {
+        let (a, b) = (DevUuid::new_v4(), DevUuid::new_v4());
+        let segments = AllocatedAbove {
+            inner: vec![seg(a, 0, 0, 80), seg(b, 16, 0, 72)],
+        };
+        let extents = [StripedExtent {
+            start: Sectors(16),
+            length: Sectors(128),
+            stripes: 2,
+            stripe_size: Sectors(8),
+        }];
+
+        assert_eq!(
+            stripe_table(&segments, &extents).unwrap(),
+            vec![
+                (0, 16, "linear".to_string(), "8:0 0".to_string()),
+                (
+                    16,
+                    128,
+                    "striped".to_string(),
+                    "2 8 8:0 16 8:16 0".to_string()
+                ),
+                (144, 8, "linear".to_string(), "8:16 64".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    /// Verify that an extent a piece of which is not contiguous can not be
+    /// mapped.
+    fn test_stripe_table_not_contiguous() {
+        let (a, b) = (DevUuid::new_v4(), DevUuid::new_v4());
+        let segments = AllocatedAbove {
+            inner: vec![seg(a, 0, 0, 8), seg(b, 16, 0, 8), seg(a, 0, 100, 16)],
+        };
+        let extents = [StripedExtent {
+            start: Sectors(0),
+            length: Sectors(32),
+            stripes: 2,
+            stripe_size: Sectors(8),
+        }];
+
+        assert!(stripe_table(&segments, &extents).is_err());
+    }
+}
@@ -16,7 +16,7 @@ use crate::engine::strat_engine::backstore::shared::BlkDevSegment;
 /// segment with one or more block device segments that make it up. Because the
 /// request and allocated space are both vectors in the same order, the API
 /// for this data structure relies heavily on indices.
-#[derive(Default)]
+#[derive(Debug, Default)]
 pub struct RequestTransaction {
     /// Block device segments
     blockdevmgr: Vec<BlkDevSegment>,
@@ -24,6 +24,12 @@ pub struct RequestTransaction {
     backstore: Vec<(Sectors, Sectors)>,
     /// Map between a cap device segment and its corresponding block device segments
     map: HashMap<usize, HashSet<usize>>,
+    /// Map between a striped cap device segment and its number of stripes and
+    /// stripe size
+    stripes: HashMap<usize, (usize, Sectors)>,
+    /// Map between a cap device segment and the copy of the data it is
+    /// allocated for, if the data tier has redundancy
+    legs: HashMap<usize, usize>,
 }
 
 impl RequestTransaction {
@@ -49,6 +55,32 @@ impl RequestTransaction {
         }
     }
 
+    /// Record that the cap device segment request at index seg_req_idx is
+    /// striped. The first block device segments added for it, one for each
+    /// stripe, are of equal length and are striped in chunks of stripe_size;
+    /// any segments added after them are mapped linearly.
+    pub fn add_stripe_req(&mut self, seg_req_idx: usize, stripes: usize, stripe_size: Sectors) {
+        self.stripes.insert(seg_req_idx, (stripes, stripe_size));
+    }
+
+    /// Get the number of stripes and the stripe size of the cap device
+    /// request located at index idx, if it is striped.
+    pub fn get_stripes_for_req(&self, idx: usize) -> Option<(usize, Sectors)> {
+        self.stripes.get(&idx).cloned()
+    }
+
+    /// Record that the cap device segment request at index seg_req_idx is
+    /// allocated for the copy of the data with index leg.
+    pub fn add_leg_req(&mut self, seg_req_idx: usize, leg: usize) {
+        self.legs.insert(seg_req_idx, leg);
+    }
+
+    /// Get the index of the copy of the data that the cap device request
+    /// located at index idx is allocated for, if it is allocated for one.
+    pub fn get_leg_for_req(&self, idx: usize) -> Option<usize> {
+        self.legs.get(&idx).cloned()
+    }
+
     /// Drain the block device segments from this transaction data structure and
     /// make them available as an iterator.
     pub fn drain_blockdevmgr(&mut self) -> impl Iterator<Item = BlkDevSegment> + '_ {
@@ -60,6 +92,32 @@ impl RequestTransaction {
         self.blockdevmgr.clone()
     }
 
+    /// Get all block device segments for this transaction, each paired with
+    /// the index of the cap device request it was added for, in the order in
+    /// which they were added.
+    pub fn get_blockdevmgr_by_req(&self) -> Vec<(usize, BlkDevSegment)> {
+        let req_idxs = self
+            .map
+            .iter()
+            .flat_map(|(req_idx, seg_idxs)| {
+                seg_idxs.iter().map(move |seg_idx| (*seg_idx, *req_idx))
+            })
+            .collect::<HashMap<_, _>>();
+        self.blockdevmgr
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(seg_idx, seg)| {
+                (
+                    *req_idxs
+                        .get(&seg_idx)
+                        .expect("every block device segment belongs to a request"),
+                    seg,
+                )
+            })
+            .collect::<Vec<_>>()
+    }
+
     /// Get a single cap device segment for this transaction by the index associated
     /// with the request.
     pub fn get_backstore_elem(&mut self, idx: usize) -> Option<(Sectors, Sectors)> {
@@ -100,6 +158,8 @@ impl RequestTransaction {
     /// device request at index idx.
     pub fn remove_request(&mut self, idx: usize) {
         self.backstore.remove(idx);
+        self.stripes.remove(&idx);
+        self.legs.remove(&idx);
         let removal_is = self
             .map
             .get(&idx)
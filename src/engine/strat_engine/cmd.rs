@@ -27,7 +27,7 @@ use libc::c_uint;
 use libcryptsetup_rs::SafeMemHandle;
 use serde_json::Value;
 
-use devicemapper::{MetaBlocks, Sectors};
+use devicemapper::{DataBlocks, MetaBlocks, Sectors, ThinDevId};
 
 use crate::{
     engine::{
@@ -90,6 +90,12 @@ const TPM2_UNSEAL: &str = "tpm2_unseal";
 const TPM2_LOAD: &str = "tpm2_load";
 const MKTEMP: &str = "mktemp";
 
+// These are external executables that stratisd uses if they are present.
+// They are looked up each time they are needed, so they are not in
+// EXECUTABLES.
+const THIN_SHRINK: &str = "thin_shrink";
+const THIN_LS: &str = "thin_ls";
+
 // This list of executables required for Clevis to function properly is based
 // off of the Clevis dracut module and the Stratis dracut module for supporting
 // Clevis in the initramfs. This list is the complete list of executables required
@@ -203,6 +209,22 @@ fn get_executable(name: &str) -> &Path {
         .expect("verify_executables() was previously called and returned no error")
 }
 
+/// Get an absolute path for an executable that stratisd uses only if it is
+/// present, or return an error if it is not.
+fn get_optional_executable(name: &str) -> StratisResult<PathBuf> {
+    find_executable(name).ok_or_else(|| {
+        StratisError::Msg(format!(
+            "Unable to find executable \"{}\" in any of {}",
+            name,
+            EXECUTABLES_PATHS
+                .iter()
+                .map(|p| format!("\"{}\"", p.display()))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ))
+    })
+}
+
 /// Get an absolute path for a Clevis-related executable or return an error if Clevis
 /// support is disabled.
 fn get_clevis_executable(name: &str) -> StratisResult<PathBuf> {
@@ -295,6 +317,83 @@ pub fn thin_repair(meta_dev: &Path, new_meta_dev: &Path) -> StratisResult<()> {
     )
 }
 
+/// Whether thin_shrink is installed.
+pub fn has_thin_shrink() -> bool {
+    find_executable(THIN_SHRINK).is_some()
+}
+
+/// Call thin_shrink on a thinpool that is not set up, writing metadata for
+/// a data device of nr_blocks data blocks to new_meta_dev. Any mapped data
+/// blocks beyond nr_blocks are first copied to unmapped blocks below it.
+/// Return an error if thin_shrink is not installed.
+pub fn thin_shrink(
+    meta_dev: &Path,
+    new_meta_dev: &Path,
+    data_dev: &Path,
+    nr_blocks: DataBlocks,
+) -> StratisResult<()> {
+    execute_cmd(
+        Command::new(get_optional_executable(THIN_SHRINK)?.as_os_str())
+            .arg("--input")
+            .arg(meta_dev)
+            .arg("--output")
+            .arg(new_meta_dev)
+            .arg("--data")
+            .arg(data_dev)
+            .arg("--nr-blocks")
+            .arg((*nr_blocks).to_string()),
+    )
+}
+
+/// Whether thin_ls is installed.
+pub fn has_thin_ls() -> bool {
+    find_executable(THIN_LS).is_some()
+}
+
+/// Call thin_ls on the reserved metadata snapshot of a thin pool and return,
+/// for every thin device, the number of data blocks mapped by no other thin
+/// device and the number of data blocks shared with other thin devices.
+/// Precondition: a metadata snapshot has been reserved for the thin pool.
+pub fn thin_ls(meta_dev: &Path) -> StratisResult<HashMap<ThinDevId, (DataBlocks, DataBlocks)>> {
+    let mut command = Command::new(get_optional_executable(THIN_LS)?.as_os_str());
+    command
+        .arg("--metadata-snap")
+        .arg("--no-headers")
+        .arg("--format")
+        .arg("DEV,EXCLUSIVE_BLOCKS,SHARED_BLOCKS")
+        .arg(meta_dev);
+    let output = command.output().map_err(|err| {
+        StratisError::Msg(format!(
+            "Failed to execute command {command:?}, err: {err:?}"
+        ))
+    })?;
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    handle_output(&mut command, output)?;
+
+    stdout
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let fields = line
+                .split_whitespace()
+                .map(|field| field.parse::<u64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| {
+                    StratisError::Msg(format!("Failed to parse thin_ls output \"{line}\": {e}"))
+                })?;
+            match fields.as_slice() {
+                [dev, exclusive, shared] => Ok((
+                    ThinDevId::new_u64(*dev)?,
+                    (DataBlocks(*exclusive), DataBlocks(*shared)),
+                )),
+                _ => Err(StratisError::Msg(format!(
+                    "Unexpected number of fields in thin_ls output \"{line}\""
+                ))),
+            }
+        })
+        .collect()
+}
+
 /// Call udevadm settle
 #[cfg(test)]
 pub fn udev_settle() -> StratisResult<()> {
@@ -24,6 +24,11 @@ use crate::stratis::{StratisError, StratisResult};
 ioctl_read_bad!(blksszget, 0x1268, c_int);
 ioctl_read_bad!(blkpbszget, 0x127b, c_int);
 
+// BLKDISCARD and BLKSECDISCARD take a pointer to the offset and length of the
+// range to discard but are defined using _IO as well.
+ioctl_write_ptr_bad!(blkdiscard, request_code_none!(0x12, 119), [u64; 2]);
+ioctl_write_ptr_bad!(blksecdiscard, request_code_none!(0x12, 125), [u64; 2]);
+
 const BLK: Group = Group::new(0x12);
 
 const BLKGETSIZE64: Ioctl<Read, &u64> = unsafe { BLK.read(114) };
@@ -54,3 +59,26 @@ pub fn blkdev_physical_sector_size(file: &File) -> StratisResult<Bytes> {
     })?;
     Ok(Bytes::from(convert_int!(val, c_int, u16)?))
 }
+
+/// Discard the range of length bytes starting at offset on the device. If
+/// secure is true, the device must also erase any copies of the data that it
+/// may have made, e.g., when remapping blocks (BLKSECDISCARD).
+/// Returns an error with errno EOPNOTSUPP if the device does not support the
+/// requested kind of discard.
+pub fn blkdev_discard(
+    file: &File,
+    offset: Bytes,
+    length: Bytes,
+    secure: bool,
+) -> StratisResult<()> {
+    let range = [
+        convert_int!(*offset, u128, u64)?,
+        convert_int!(*length, u128, u64)?,
+    ];
+    if secure {
+        unsafe { blksecdiscard(file.as_raw_fd(), &range) }?;
+    } else {
+        unsafe { blkdiscard(file.as_raw_fd(), &range) }?;
+    }
+    Ok(())
+}
@@ -11,10 +11,11 @@ use devicemapper::{DevId, DmNameBuf, DmOptions, DmResult, DM};
 use crate::{
     engine::{
         strat_engine::names::{
-            format_backstore_ids, format_crypt_name, format_flex_ids, format_thin_ids,
-            format_thinpool_ids, CacheRole, FlexRole, ThinPoolRole, ThinRole,
+            format_backstore_ids, format_crypt_name, format_flex_ids, format_integrity_ids,
+            format_thin_ids, format_thinpool_ids, CacheRole, FlexRole, IntegrityRole, ThinPoolRole,
+            ThinRole,
         },
-        types::{DevUuid, FilesystemUuid, PoolUuid},
+        types::{DevUuid, FilesystemUuid, PoolUuid, Redundancy},
     },
     stratis::{StratisError, StratisResult},
 };
@@ -105,6 +106,37 @@ pub fn list_of_backstore_devices(pool_uuid: PoolUuid) -> Vec<DmNameBuf> {
     devs.push(cache_meta);
     let (origin, _) = format_backstore_ids(pool_uuid, CacheRole::OriginSub);
     devs.push(origin);
+    let (mirror, _) = format_backstore_ids(pool_uuid, CacheRole::Mirror);
+    devs.push(mirror);
+    let (raid, _) = format_backstore_ids(pool_uuid, CacheRole::Raid);
+    devs.push(raid);
+    for leg in 0..Redundancy::Raid1.copies() {
+        let (raid_leg, _) = format_backstore_ids(pool_uuid, CacheRole::RaidLeg(leg));
+        devs.push(raid_leg);
+        let (raid_meta, _) = format_backstore_ids(pool_uuid, CacheRole::RaidMeta(leg));
+        devs.push(raid_meta);
+    }
+    let (stripe, _) = format_backstore_ids(pool_uuid, CacheRole::Stripe);
+    devs.push(stripe);
+
+    devs
+}
+
+/// The integrity devices of the given blockdevs, each listed before the
+/// devices it is made up of.
+pub fn list_of_integrity_devices(dev_uuids: &[DevUuid]) -> Vec<DmNameBuf> {
+    let mut devs = Vec::new();
+
+    for dev_uuid in dev_uuids.iter() {
+        for role in [
+            IntegrityRole::Integrity,
+            IntegrityRole::Data,
+            IntegrityRole::Meta,
+        ] {
+            let (integrity, _) = format_integrity_ids(*dev_uuid, role);
+            devs.push(integrity);
+        }
+    }
 
     devs
 }
@@ -134,6 +166,8 @@ fn list_of_partial_pool_devices(pool_uuid: PoolUuid, dev_uuids: &[DevUuid]) -> V
 
     devs.extend(list_of_crypt_devices(dev_uuids));
 
+    devs.extend(list_of_integrity_devices(dev_uuids));
+
     devs
 }
 
@@ -6,14 +6,17 @@ use std::{
     collections::{HashMap, HashSet},
     path::Path,
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use futures::{executor::block_on, future::join_all};
 use serde_json::Value;
 use tokio::{
     sync::RwLock,
     task::{spawn_blocking, JoinHandle},
+    time::sleep,
 };
 
 use devicemapper::DmNameBuf;
@@ -21,13 +24,18 @@ use devicemapper::DmNameBuf;
 use crate::{
     engine::{
         engine::{HandleEvents, KeyActions},
-        shared::{create_pool_idempotent_or_err, validate_name, validate_paths},
+        shared::{
+            create_pool_idempotent_or_err, snapshot_schedules_due, validate_crypt_params,
+            validate_erase_mode, validate_name, validate_paths, validate_redundancy,
+        },
         strat_engine::{
-            backstore::ProcessedPathInfos,
-            cmd::verify_executables,
+            backstore::{
+                erase_ranges, restore_luks_header_from_archive, EraseTarget, ProcessedPathInfos,
+            },
+            cmd::{has_thin_ls, thin_ls, verify_executables},
             dm::get_dm,
             keys::StratKeyActions,
-            liminal::{find_all, DeviceSet, LiminalDevices},
+            liminal::{auto_unlock_pool, find_all, DeviceSet, LiminalDevices},
             ns::MemoryFilesystem,
             pool::StratPool,
         },
@@ -36,15 +44,32 @@ use crate::{
             SomeLockWriteGuard, Table,
         },
         types::{
-            CreateAction, DeleteAction, DevUuid, EncryptionInfo, FilesystemUuid, LockedPoolsInfo,
-            PoolDiff, PoolIdentifier, RenameAction, ReportType, SetUnlockAction, StartAction,
-            StopAction, StoppedPoolsInfo, StratFilesystemDiff, UdevEngineEvent, UnlockMethod,
+            CreateAction, CryptParams, DeleteAction, DevUuid, DevicePath, EncryptionInfo,
+            EraseMode, Erasure, FilesystemUuid, IntegrityHash, LockedPoolsInfo, PoolDiff,
+            PoolIdentifier, Redundancy, RenameAction, ReportType, SetDeleteAction, SetUnlockAction,
+            SnapshotScheduleRun, StartAction, StopAction, StoppedPoolsInfo, StratFilesystemDiff,
+            UdevEngineEvent, UnlockMethod,
         },
         Engine, Name, Pool, PoolUuid, Report,
     },
     stratis::{StratisError, StratisResult},
 };
 
+/// The time for which the online encryption or reencryption of a pool is
+/// continued before its progress is recorded. The pool is not locked while
+/// the data is processed, so this bounds only how often the progress is
+/// reported.
+const REENCRYPTION_SLICE: Duration = Duration::from_secs(10);
+
+/// The interval at which the number of dirty blocks is checked while the
+/// cache of a pool is written back in preparation for its removal.
+const CACHE_FLUSH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The longest time for which remove_cache() waits for the dirty blocks of
+/// a cache to be written back. It is shorter than the default D-Bus method
+/// call timeout, so that a caller learns why the cache was not removed.
+const CACHE_FLUSH_TIMEOUT: Duration = Duration::from_secs(20);
+
 type EventNumbers = HashMap<PoolUuid, HashMap<DmNameBuf, u32>>;
 type PoolJoinHandles = Vec<JoinHandle<StratisResult<(PoolUuid, PoolDiff)>>>;
 
@@ -187,6 +212,68 @@ impl StratEngine {
             })
     }
 
+    /// Continue the online encryption or reencryption of the pool with the
+    /// given UUID, if one is in progress, for about REENCRYPTION_SLICE. The
+    /// pool is locked only while the datadev to be processed is determined and
+    /// while its progress is recorded, so that other requests for the pool are
+    /// served while its data is processed.
+    async fn continue_pool_reencryption(&self, uuid: PoolUuid) -> StratisResult<Option<PoolDiff>> {
+        let pool_id = PoolIdentifier::Uuid(uuid);
+        let not_found = || StratisError::Msg(format!("No pool with UUID {uuid} found"));
+
+        let mut guard = self
+            .pools
+            .write(pool_id.clone())
+            .await
+            .ok_or_else(not_found)?;
+        let (dev_uuid, handle) = match spawn_blocking!({
+            let (name, _, pool) = guard.as_mut_tuple();
+            pool.next_reencryption(&name)
+        })?? {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+
+        let progress = spawn_blocking!(handle.continue_reencryption(REENCRYPTION_SLICE))??;
+
+        let mut guard = self.pools.write(pool_id).await.ok_or_else(not_found)?;
+        spawn_blocking!({
+            let (name, _, pool) = guard.as_mut_tuple();
+            pool.record_reencryption(&name, dev_uuid, progress)
+        })?
+        .map(Some)
+    }
+
+    /// Recompute the space usage of the filesystems in the pool with the given
+    /// UUID. The pool is locked only while a metadata snapshot is reserved and
+    /// while it is released and the result recorded, so that other requests
+    /// for the pool are served while thin_ls reads the snapshot.
+    async fn refresh_pool_space_usage(
+        &self,
+        uuid: PoolUuid,
+    ) -> StratisResult<HashMap<FilesystemUuid, StratFilesystemDiff>> {
+        let pool_id = PoolIdentifier::Uuid(uuid);
+        let not_found = || StratisError::Msg(format!("No pool with UUID {uuid} found"));
+
+        let mut guard = self
+            .pools
+            .write(pool_id.clone())
+            .await
+            .ok_or_else(not_found)?;
+        let meta_dev = spawn_blocking!({
+            let (_, _, pool) = guard.as_mut_tuple();
+            pool.reserve_metadata_snap()
+        })??;
+
+        let blocks = spawn_blocking!(thin_ls(&meta_dev)).and_then(|res| res);
+
+        let mut guard = self.pools.write(pool_id).await.ok_or_else(not_found)?;
+        spawn_blocking!({
+            let (_, _, pool) = guard.as_mut_tuple();
+            pool.record_space_usage(blocks)
+        })?
+    }
+
     /// The implementation for pool_evented when caused by a devicemapper event.
     async fn pool_evented_dm(&self, pools: &HashSet<PoolUuid>) -> HashMap<PoolUuid, PoolDiff> {
         let mut joins = Vec::new();
@@ -248,6 +335,17 @@ impl StratEngine {
         Self::join_all_fs_checks(joins).await
     }
 
+    fn spawn_snapshot_schedule_handling(
+        joins: &mut Vec<JoinHandle<StratisResult<(PoolUuid, SnapshotScheduleRun)>>>,
+        mut guard: SomeLockWriteGuard<PoolUuid, StratPool>,
+        now: DateTime<Utc>,
+    ) {
+        joins.push(spawn_blocking(move || {
+            let (name, uuid, pool) = guard.as_mut_tuple();
+            Ok((uuid, pool.run_snapshot_schedules(&name, uuid, now)?))
+        }));
+    }
+
     /// Recursively remove all devicemapper devices in all pools.
     /// Do not remove the dm-crypt devices that comprise the backstore.
     #[cfg(test)]
@@ -345,6 +443,7 @@ impl Engine for StratEngine {
                     } else {
                         None
                     };
+                    LiminalDevices::block_evaluate_removal(&mut pools_write_all, event);
                     match LiminalDevices::block_evaluate_size(&mut pools_write_all, event) {
                         Ok(Some((dev_uuid, diff))) => (uuid, Some((dev_uuid, diff))),
                         Ok(None) => (uuid, None),
@@ -387,12 +486,17 @@ impl Engine for StratEngine {
         &self,
         name: &str,
         blockdev_paths: &[&Path],
+        redundancy: Redundancy,
         encryption_info: Option<&EncryptionInfo>,
+        crypt_params: Option<&CryptParams>,
+        integrity: Option<IntegrityHash>,
     ) -> StratisResult<CreateAction<PoolUuid>> {
         validate_name(name)?;
         let name = Name::new(name.to_owned());
 
         validate_paths(blockdev_paths)?;
+        validate_crypt_params(encryption_info, crypt_params)?;
+        validate_redundancy(redundancy, encryption_info.is_some())?;
 
         let cloned_paths = blockdev_paths
             .iter()
@@ -426,6 +530,8 @@ impl Engine for StratEngine {
                             .map(|info| info.devnode.as_path()),
                     )
                     .collect::<Vec<_>>(),
+                redundancy,
+                integrity,
             )
         } else {
             stratis_devices.error_on_not_empty()?;
@@ -444,11 +550,19 @@ impl Engine for StratEngine {
 
             let cloned_name = name.clone();
             let cloned_enc_info = encryption_info.cloned();
+            let cloned_crypt_params = crypt_params.cloned();
 
             let pool_uuid = {
                 let mut pools = self.pools.modify_all().await;
                 let (pool_uuid, pool) = spawn_blocking!({
-                    StratPool::initialize(&cloned_name, unowned_devices, cloned_enc_info.as_ref())
+                    StratPool::initialize(
+                        &cloned_name,
+                        unowned_devices,
+                        cloned_enc_info.as_ref(),
+                        cloned_crypt_params.as_ref(),
+                        integrity,
+                        redundancy,
+                    )
                 })??;
                 pools.insert(Name::new(name.to_string()), pool_uuid, pool);
                 pool_uuid
@@ -458,38 +572,128 @@ impl Engine for StratEngine {
         }
     }
 
-    async fn destroy_pool(&self, uuid: PoolUuid) -> StratisResult<DeleteAction<PoolUuid>> {
+    async fn destroy_pool(
+        &self,
+        uuid: PoolUuid,
+        erase_mode: Option<EraseMode>,
+    ) -> StratisResult<(DeleteAction<PoolUuid>, Option<Erasure>)> {
         if let Some(pool) = self.pools.read(PoolIdentifier::Uuid(uuid)).await {
             if pool.has_filesystems() {
                 return Err(StratisError::Msg("filesystems remaining on pool".into()));
             };
+            if let Some(erase_mode) = erase_mode {
+                validate_erase_mode(erase_mode, pool.is_encrypted())?;
+            }
         } else {
-            return Ok(DeleteAction::Identity);
+            return Ok((DeleteAction::Identity, None));
         }
 
         let mut guard = self.pools.modify_all().await;
-        let (pool_name, mut pool) = guard
+        let (pool_name, pool) = guard
             .remove_by_uuid(uuid)
             .expect("Must succeed since self.pools.get_by_uuid() returned a value");
 
-        let (res, mut pool) = spawn_blocking!((pool.destroy(uuid), pool))?;
-        if let Err((err, true)) = res {
-            guard.insert(pool_name, uuid, pool);
-            Err(err)
-        } else if let Err((err, false)) = res {
-            // We use blkid to scan for existing devices with this pool UUID and device UUIDs
-            // because some of the block devices could have been destroyed above. Using the
-            // cached data structures alone could result in phantom devices that have already
-            // been destroyed but are still recorded in the stopped pool.
-            let device_set = DeviceSet::from(pool.drain_bds());
-            self.liminal_devices
-                .write()
+        // The devices to be erased are opened while the pool still exists so
+        // that a device that can not be erased leaves the pool in place.
+        let (targets, mut pool) = spawn_blocking!({
+            let targets = match erase_mode {
+                Some(EraseMode::Discard | EraseMode::Zero) => pool
+                    .allocated_ranges()
+                    .into_iter()
+                    .map(|(path, ranges)| EraseTarget::open(path, ranges))
+                    .collect::<StratisResult<Vec<_>>>(),
+                Some(EraseMode::Keyslots) | None => Ok(Vec::new()),
+            };
+            (targets, pool)
+        })?;
+        let mut targets = match targets {
+            Ok(targets) => targets,
+            Err(err) => {
+                guard.insert(pool_name, uuid, pool);
+                return Err(err);
+            }
+        };
+
+        let (res, mut pool) = spawn_blocking!((pool.destroy(uuid, erase_mode), pool))?;
+        match res {
+            Err((err, true)) => {
+                guard.insert(pool_name, uuid, pool);
+                Err(err)
+            }
+            Err((err, false)) => {
+                // We use blkid to scan for existing devices with this pool UUID and device UUIDs
+                // because some of the block devices could have been destroyed above. Using the
+                // cached data structures alone could result in phantom devices that have already
+                // been destroyed but are still recorded in the stopped pool.
+                let device_set = DeviceSet::from(pool.drain_bds());
+                self.liminal_devices
+                    .write()
+                    .await
+                    .handle_stopped_pool(uuid, device_set);
+                Err(err)
+            }
+            Ok(()) => {
+                // Claim the devices before other pool operations are allowed
+                // again so that they can not be reused while they are being
+                // erased.
+                targets.iter_mut().for_each(|target| target.claim());
+                let erasure = match erase_mode {
+                    Some(erase_mode @ (EraseMode::Discard | EraseMode::Zero)) => {
+                        Some(Erasure::new(move |progress| {
+                            erase_ranges(targets, erase_mode, progress)
+                        }))
+                    }
+                    Some(EraseMode::Keyslots) | None => None,
+                };
+                Ok((DeleteAction::Deleted(uuid), erasure))
+            }
+        }
+    }
+
+    async fn remove_cache(&self, uuid: PoolUuid) -> StratisResult<SetDeleteAction<DevUuid>> {
+        let pool_id = PoolIdentifier::Uuid(uuid);
+        let not_found = || StratisError::Msg(format!("No pool with UUID {uuid} found"));
+
+        let mut guard = self
+            .get_mut_pool(pool_id.clone())
+            .await
+            .ok_or_else(not_found)?;
+        if !spawn_blocking!(guard.start_cache_flush())?? {
+            return Ok(SetDeleteAction::new(vec![]));
+        }
+
+        // The pool is locked only while the number of dirty blocks is read,
+        // so that other requests for the pool are served meanwhile.
+        let deadline = Instant::now() + CACHE_FLUSH_TIMEOUT;
+        let flush_res = loop {
+            let pool = self
+                .pools
+                .read(pool_id.clone())
                 .await
-                .handle_stopped_pool(uuid, device_set);
-            Err(err)
-        } else {
-            Ok(DeleteAction::Deleted(uuid))
+                .ok_or_else(not_found)?;
+            match spawn_blocking!(pool.cache_dirty_blocks())? {
+                Ok(Some(0)) | Ok(None) => break Ok(()),
+                Ok(Some(dirty)) if Instant::now() >= deadline => {
+                    break Err(StratisError::Msg(format!(
+                        "{dirty} dirty cache blocks were not written back within {} seconds; the cache was not removed, retry once fewer blocks are dirty",
+                        CACHE_FLUSH_TIMEOUT.as_secs()
+                    )))
+                }
+                Ok(Some(_)) => (),
+                Err(err) => break Err(err),
+            }
+            sleep(CACHE_FLUSH_POLL_INTERVAL).await;
+        };
+
+        let mut guard = self.get_mut_pool(pool_id).await.ok_or_else(not_found)?;
+        if let Err(err) = flush_res {
+            spawn_blocking!(guard.abandon_cache_flush(err))??;
+            unreachable!("abandon_cache_flush() always returns an error");
         }
+        spawn_blocking!({
+            let (name, _, pool) = guard.as_mut_tuple();
+            pool.remove_cache(uuid, &name)
+        })?
     }
 
     async fn rename_pool(
@@ -642,6 +846,126 @@ impl Engine for StratEngine {
         }
     }
 
+    async fn run_snapshot_schedules(&self) -> HashMap<PoolUuid, SnapshotScheduleRun> {
+        let now = Utc::now();
+        // Only the pools for which work is due are locked for writing, so
+        // that the other pools remain available while snapshots are taken.
+        let uuids = self
+            .pools
+            .read_all()
+            .await
+            .iter()
+            .filter(|(_, _, pool)| snapshot_schedules_due(*pool, now))
+            .map(|(_, uuid, _)| *uuid)
+            .collect::<Vec<_>>();
+
+        let mut joins = Vec::new();
+        for uuid in uuids {
+            if let Some(guard) = self.pools.write(PoolIdentifier::Uuid(uuid)).await {
+                Self::spawn_snapshot_schedule_handling(&mut joins, guard, now);
+            }
+        }
+
+        join_all(joins)
+            .await
+            .into_iter()
+            .filter_map(|res| match res {
+                Ok(Ok(tup)) => Some(tup),
+                Ok(Err(StratisError::ActionDisabled(_))) => None,
+                Ok(Err(e)) => {
+                    warn!("Running snapshot schedules failed with error: {}", e);
+                    None
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to get status for thread running snapshot schedules: {}",
+                        e
+                    );
+                    None
+                }
+            })
+            .collect::<HashMap<_, _>>()
+    }
+
+    async fn refresh_space_usage(&self) -> HashMap<FilesystemUuid, StratFilesystemDiff> {
+        if !has_thin_ls() {
+            return HashMap::new();
+        }
+
+        let uuids = self
+            .pools
+            .read_all()
+            .await
+            .iter()
+            .map(|(_, uuid, _)| *uuid)
+            .collect::<Vec<_>>();
+
+        join_all(uuids.into_iter().map(|uuid| async move {
+            match self.refresh_pool_space_usage(uuid).await {
+                Ok(diffs) => diffs,
+                Err(StratisError::ActionDisabled(_)) => HashMap::new(),
+                Err(e) => {
+                    warn!(
+                        "Failed to compute the space usage of the filesystems in pool with UUID {}: {}",
+                        uuid, e
+                    );
+                    HashMap::new()
+                }
+            }
+        }))
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    async fn continue_reencryption(&self) -> HashMap<PoolUuid, PoolDiff> {
+        let uuids = self
+            .pools
+            .read_all()
+            .await
+            .iter()
+            .filter(|(_, _, pool)| {
+                pool.encryption_progress().is_some() || pool.reencryption_progress().is_some()
+            })
+            .map(|(_, uuid, _)| *uuid)
+            .collect::<Vec<_>>();
+
+        join_all(uuids.into_iter().map(|uuid| async move {
+            match self.continue_pool_reencryption(uuid).await {
+                Ok(diff) => diff.map(|diff| (uuid, diff)),
+                Err(StratisError::ActionDisabled(_)) => None,
+                Err(e) => {
+                    warn!(
+                        "Failed to continue the reencryption of pool with UUID {}: {}",
+                        uuid, e
+                    );
+                    None
+                }
+            }
+        }))
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    async fn unlock_pending_pools(&self) {
+        let pending = self.liminal_devices.write().await.take_pending_unlocks();
+
+        join_all(pending.into_iter().map(|(pool_uuid, devnodes)| async move {
+            if let Err(e) =
+                spawn_blocking!(auto_unlock_pool(pool_uuid, &devnodes)).and_then(|res| res)
+            {
+                info!(
+                    "Failed to automatically unlock pool with UUID {}: {}",
+                    pool_uuid, e
+                );
+            }
+        }))
+        .await;
+    }
+
     async fn get_key_handler(&self) -> Arc<dyn KeyActions> {
         Arc::clone(&self.key_handler) as Arc<dyn KeyActions>
     }
@@ -729,6 +1053,63 @@ impl Engine for StratEngine {
         }
     }
 
+    async fn restore_luks_header(
+        &self,
+        pool_uuid: PoolUuid,
+        dev_uuid: DevUuid,
+        physical_path: &Path,
+        archive_path: &Path,
+    ) -> StratisResult<()> {
+        if self
+            .pools
+            .read(PoolIdentifier::Uuid(pool_uuid))
+            .await
+            .is_some()
+        {
+            return Err(StratisError::Msg(format!(
+                "Pool {pool_uuid} must be stopped before a LUKS2 header of one of its devices can be restored"
+            )));
+        }
+
+        let physical_path = DevicePath::new(physical_path)?;
+        // The liminal devices are locked until the header has been restored
+        // so that the pool can not be started in the meantime.
+        let lim = self.liminal_devices.read().await;
+        let stopped_pools = lim.stopped_pools();
+        if let Some(info) = stopped_pools
+            .stopped
+            .get(&pool_uuid)
+            .or_else(|| stopped_pools.partially_constructed.get(&pool_uuid))
+        {
+            for device in info.devices.iter() {
+                if device.uuid == dev_uuid && device.devnode != *physical_path {
+                    return Err(StratisError::Msg(format!(
+                        "Device {dev_uuid} of pool {pool_uuid} is {}, not {}",
+                        device.devnode.display(),
+                        physical_path.display()
+                    )));
+                }
+                if device.uuid != dev_uuid && device.devnode == *physical_path {
+                    return Err(StratisError::Msg(format!(
+                        "Device {} is device {}, not device {dev_uuid}, of pool {pool_uuid}",
+                        physical_path.display(),
+                        device.uuid
+                    )));
+                }
+            }
+        }
+
+        let archive_path = archive_path.to_path_buf();
+        let res = spawn_blocking!(restore_luks_header_from_archive(
+            pool_uuid,
+            dev_uuid,
+            &physical_path,
+            &archive_path,
+        ))?;
+        drop(lim);
+        res
+    }
+
     async fn refresh_state(&self) -> StratisResult<()> {
         let mut pools = self.pools.modify_all().await;
         *pools = Table::default();
@@ -750,21 +1131,31 @@ impl Engine for StratEngine {
 mod test {
     use std::{
         env,
+        fs::{set_permissions, File, OpenOptions, Permissions},
+        io::Write,
+        os::unix::fs::{FileExt, OpenOptionsExt, PermissionsExt},
         panic::{catch_unwind, UnwindSafe},
         path::Path,
+        thread::sleep,
+        time::Duration,
     };
 
     use devicemapper::Sectors;
+    use libcryptsetup_rs::SafeMemHandle;
+    use tempfile::TempDir;
 
     use crate::engine::{
-        engine::Pool,
+        engine::{BlockDev, Pool},
         strat_engine::{
             backstore::crypt_metadata_size,
             cmd,
             ns::unshare_mount_namespace,
             tests::{crypt, loopbacked, real, FailDevice},
         },
-        types::{ActionAvailability, EngineAction, KeyDescription},
+        types::{
+            ActionAvailability, BlockDevTier, CacheMode, CacheSettings, EngineAction, JobProgress,
+            KeyDescription, SizedKeyMemory,
+        },
     };
 
     use super::*;
@@ -775,10 +1166,11 @@ mod test {
         let engine = StratEngine::initialize().unwrap();
 
         let name1 = "name1";
-        let uuid1 = test_async!(engine.create_pool(name1, paths, None))
-            .unwrap()
-            .changed()
-            .unwrap();
+        let uuid1 =
+            test_async!(engine.create_pool(name1, paths, Redundancy::None, None, None, None))
+                .unwrap()
+                .changed()
+                .unwrap();
 
         let events = generate_events!();
         test_async!(engine.handle_events(events));
@@ -882,16 +1274,18 @@ mod test {
         let engine = StratEngine::initialize().unwrap();
 
         let name1 = "name1";
-        let uuid1 = test_async!(engine.create_pool(name1, paths1, None))
-            .unwrap()
-            .changed()
-            .unwrap();
+        let uuid1 =
+            test_async!(engine.create_pool(name1, paths1, Redundancy::None, None, None, None))
+                .unwrap()
+                .changed()
+                .unwrap();
 
         let name2 = "name2";
-        let uuid2 = test_async!(engine.create_pool(name2, paths2, None))
-            .unwrap()
-            .changed()
-            .unwrap();
+        let uuid2 =
+            test_async!(engine.create_pool(name2, paths2, Redundancy::None, None, None, None))
+                .unwrap()
+                .changed()
+                .unwrap();
 
         let events = generate_events!();
         test_async!(engine.handle_events(events));
@@ -939,15 +1333,23 @@ mod test {
     {
         unshare_mount_namespace().unwrap();
         let engine = StratEngine::initialize().unwrap();
-        let uuid = test_async!(engine.create_pool(name, data_paths, Some(encryption_info)))
-            .unwrap()
-            .changed()
-            .expect("Pool should be newly created");
+        let uuid = test_async!(engine.create_pool(
+            name,
+            data_paths,
+            Redundancy::None,
+            Some(encryption_info),
+            None,
+            None,
+        ))
+        .unwrap()
+        .changed()
+        .expect("Pool should be newly created");
         let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid)))
             .expect("Pool must be present");
 
         if let Some(cds) = cache_paths {
-            pool.init_cache(uuid, name, cds, true).unwrap();
+            pool.init_cache(uuid, name, cds, true, CacheSettings::default())
+                .unwrap();
         }
 
         fail_device
@@ -968,7 +1370,7 @@ mod test {
         test_async!(engine.stop_pool(PoolIdentifier::Uuid(uuid), true)).unwrap();
 
         test_async!(engine.start_pool(PoolIdentifier::Uuid(uuid), Some(unlock_method))).unwrap();
-        test_async!(engine.destroy_pool(uuid)).unwrap();
+        test_async!(engine.destroy_pool(uuid, None)).unwrap();
         cmd::udev_settle().unwrap();
         engine.teardown().unwrap();
     }
@@ -1347,7 +1749,7 @@ mod test {
     fn test_start_stop(paths: &[&Path]) {
         let engine = StratEngine::initialize().unwrap();
         let name = "pool_name";
-        let uuid = test_async!(engine.create_pool(name, paths, None))
+        let uuid = test_async!(engine.create_pool(name, paths, Redundancy::None, None, None, None))
             .unwrap()
             .changed()
             .unwrap();
@@ -1386,4 +1788,384 @@ mod test {
     fn real_test_start_stop() {
         real::test_with_spec(&real::DeviceLimits::AtLeast(2, None, None), test_start_stop);
     }
+
+    /// Test that an encrypted pool can be started with a keyfile that contains
+    /// its passphrase, but not with a keyfile that is accessible to users
+    /// other than its owner.
+    fn test_start_keyfile(paths: &[&Path]) {
+        fn test(paths: &[&Path], key_desc: &KeyDescription) {
+            let passphrase = b"stratis-test-keyfile-passphrase";
+            let mut mem = SafeMemHandle::alloc(passphrase.len()).unwrap();
+            mem.as_mut().copy_from_slice(passphrase);
+            StratKeyActions::set_no_fd(key_desc, SizedKeyMemory::new(mem, passphrase.len()))
+                .unwrap();
+
+            let tmp_dir = TempDir::new().unwrap();
+            let keyfile = tmp_dir.path().join("keyfile");
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&keyfile)
+                .unwrap()
+                .write_all(passphrase)
+                .unwrap();
+
+            unshare_mount_namespace().unwrap();
+            let engine = StratEngine::initialize().unwrap();
+            let uuid = test_async!(engine.create_pool(
+                "pool_name",
+                paths,
+                Redundancy::None,
+                Some(&EncryptionInfo::KeyDesc(key_desc.clone())),
+                None,
+                None,
+            ))
+            .unwrap()
+            .changed()
+            .unwrap();
+            test_async!(engine.stop_pool(PoolIdentifier::Uuid(uuid), true)).unwrap();
+
+            set_permissions(&keyfile, Permissions::from_mode(0o644)).unwrap();
+            assert!(test_async!(engine.start_pool(
+                PoolIdentifier::Uuid(uuid),
+                Some(UnlockMethod::Keyfile(keyfile.clone()))
+            ))
+            .is_err());
+            assert_eq!(test_async!(engine.stopped_pools()).stopped.len(), 1);
+
+            set_permissions(&keyfile, Permissions::from_mode(0o600)).unwrap();
+            assert!(test_async!(engine.start_pool(
+                PoolIdentifier::Uuid(uuid),
+                Some(UnlockMethod::Keyfile(keyfile))
+            ))
+            .unwrap()
+            .is_changed());
+            assert_eq!(test_async!(engine.pools()).len(), 1);
+
+            test_async!(engine.destroy_pool(uuid, None)).unwrap();
+            cmd::udev_settle().unwrap();
+            engine.teardown().unwrap();
+        }
+
+        crypt::insert_and_cleanup_key(paths, test)
+    }
+
+    #[test]
+    fn loop_test_start_keyfile() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_start_keyfile,
+        );
+    }
+
+    #[test]
+    fn real_test_start_keyfile() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_start_keyfile,
+        );
+    }
+
+    /// Test that destroying an encrypted pool while erasing its keyslots
+    /// erases them as part of destroying the pool and leaves no erasure to
+    /// be run afterwards.
+    fn test_destroy_erase_keyslots(paths: &[&Path]) {
+        fn test(paths: &[&Path], key_desc: &KeyDescription) {
+            unshare_mount_namespace().unwrap();
+            let engine = StratEngine::initialize().unwrap();
+            let uuid = test_async!(engine.create_pool(
+                "pool_name",
+                paths,
+                Redundancy::None,
+                Some(&EncryptionInfo::KeyDesc(key_desc.clone())),
+                None,
+                None,
+            ))
+            .unwrap()
+            .changed()
+            .unwrap();
+
+            assert_matches!(
+                test_async!(engine.destroy_pool(uuid, Some(EraseMode::Keyslots))),
+                Ok((DeleteAction::Deleted(_), None))
+            );
+            assert!(test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid))).is_none());
+            cmd::udev_settle().unwrap();
+            engine.teardown().unwrap();
+        }
+
+        crypt::insert_and_cleanup_key(paths, test)
+    }
+
+    #[test]
+    fn loop_test_destroy_erase_keyslots() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_destroy_erase_keyslots,
+        );
+    }
+
+    #[test]
+    fn real_test_destroy_erase_keyslots() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_destroy_erase_keyslots,
+        );
+    }
+
+    /// Test that destroying a pool while discarding or zeroing its data
+    /// returns an erasure that completes once the pool is gone, and that
+    /// zeroing leaves only zeros in the ranges that were allocated to it.
+    fn test_destroy_erase_ranges(paths: &[&Path]) {
+        unshare_mount_namespace().unwrap();
+        let engine = StratEngine::initialize().unwrap();
+
+        for erase_mode in [EraseMode::Discard, EraseMode::Zero] {
+            let uuid = test_async!(engine.create_pool(
+                "pool_name",
+                paths,
+                Redundancy::None,
+                None,
+                None,
+                None
+            ))
+            .unwrap()
+            .changed()
+            .unwrap();
+            let ranges = test_async!(engine.pools.read(PoolIdentifier::Uuid(uuid)))
+                .unwrap()
+                .allocated_ranges();
+
+            let erasure = match test_async!(engine.destroy_pool(uuid, Some(erase_mode))) {
+                Ok((DeleteAction::Deleted(_), Some(erasure))) => erasure,
+                res => panic!("Unexpected result of destroying the pool: {res:?}"),
+            };
+            assert!(test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid))).is_none());
+            let progress = JobProgress::default();
+            let report = erasure.run(&progress).unwrap();
+            assert_eq!(progress.get(), 100);
+            assert_eq!(report.lines().count(), ranges.len());
+
+            if erase_mode == EraseMode::Zero {
+                assert!(report
+                    .lines()
+                    .all(|line| line.ends_with(": overwritten with zeros")));
+                for (path, ranges) in ranges {
+                    let ranges = ranges.expect("the pool has no integrity devices");
+                    let file = File::open(&path).unwrap();
+                    for (start, length) in ranges {
+                        let mut buf = vec![0xffu8; 4096];
+                        for offset in [start, start + length - Sectors(8)] {
+                            file.read_exact_at(&mut buf, *offset.bytes() as u64)
+                                .unwrap();
+                            assert!(buf.iter().all(|b| *b == 0));
+                        }
+                    }
+                }
+            }
+            cmd::udev_settle().unwrap();
+        }
+        engine.teardown().unwrap();
+    }
+
+    #[test]
+    fn loop_test_destroy_erase_ranges() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_destroy_erase_ranges,
+        );
+    }
+
+    #[test]
+    fn real_test_destroy_erase_ranges() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_destroy_erase_ranges,
+        );
+    }
+
+    /// Test that a replacement of a datadev that is interrupted by stopping
+    /// the pool is recorded in the pool metadata and is resumed, and can be
+    /// completed, when the pool is started again.
+    fn test_replace_start_stop(paths: &[&Path]) {
+        let (paths1, paths2) = paths.split_at(1);
+
+        let engine = StratEngine::initialize().unwrap();
+        let name = "pool_name";
+        let uuid =
+            test_async!(engine.create_pool(name, paths1, Redundancy::None, None, None, None))
+                .unwrap()
+                .changed()
+                .unwrap();
+
+        let (old, new) = {
+            let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+            let old = pool.blockdevs()[0].0;
+            let (action, _) = pool.replace_blockdev(uuid, name, old, paths2[0]).unwrap();
+            (old, action.changed().unwrap())
+        };
+
+        test_async!(engine.stop_pool(PoolIdentifier::Uuid(uuid), true)).unwrap();
+        test_async!(engine.start_pool(PoolIdentifier::Uuid(uuid), None)).unwrap();
+
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        assert!(pool.replacement_progress().is_some());
+        let replacement = pool.record(name).backstore.data_tier.replacement.unwrap();
+        assert_eq!((replacement.old, replacement.new), (vec![old], Some(new)));
+
+        let pool_name = Name::new(name.to_string());
+        while pool.replacement_progress().is_some() {
+            sleep(Duration::from_millis(100));
+            pool.event_on(uuid, &pool_name).unwrap();
+        }
+
+        assert_eq!(
+            pool.blockdevs()
+                .into_iter()
+                .map(|(uuid, _, _)| uuid)
+                .collect::<Vec<_>>(),
+            vec![new]
+        );
+        assert!(pool.record(name).backstore.data_tier.replacement.is_none());
+    }
+
+    #[test]
+    fn loop_test_replace_start_stop() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(2, None),
+            test_replace_start_stop,
+        );
+    }
+
+    #[test]
+    fn real_test_replace_start_stop() {
+        real::test_with_spec(
+            &real::DeviceLimits::Exactly(2, None, None),
+            test_replace_start_stop,
+        );
+    }
+
+    /// Test that the cache of a pool in writeback mode is removed by the
+    /// engine, which writes back its dirty blocks without holding the lock on
+    /// the pool throughout, and that removing it again does nothing.
+    fn test_engine_remove_cache(paths: &[&Path]) {
+        let (paths1, paths2) = paths.split_at(1);
+
+        let engine = StratEngine::initialize().unwrap();
+        let name = "pool_name";
+        let uuid =
+            test_async!(engine.create_pool(name, paths1, Redundancy::None, None, None, None))
+                .unwrap()
+                .changed()
+                .unwrap();
+
+        let settings =
+            CacheSettings::new(CacheMode::Writeback, "smq".to_string(), Vec::new()).unwrap();
+        let cache_uuids = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid)))
+            .unwrap()
+            .init_cache(uuid, name, paths2, true, settings)
+            .unwrap()
+            .changed()
+            .unwrap();
+
+        assert_eq!(
+            test_async!(engine.remove_cache(uuid))
+                .unwrap()
+                .changed()
+                .unwrap(),
+            cache_uuids
+        );
+        assert!(!test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid)))
+            .unwrap()
+            .has_cache());
+        assert!(!test_async!(engine.remove_cache(uuid)).unwrap().is_changed());
+    }
+
+    #[test]
+    fn loop_test_engine_remove_cache() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(2, None),
+            test_engine_remove_cache,
+        );
+    }
+
+    #[test]
+    fn real_test_engine_remove_cache() {
+        real::test_with_spec(
+            &real::DeviceLimits::Exactly(2, None, None),
+            test_engine_remove_cache,
+        );
+    }
+
+    /// Test that the cachedevs of an encrypted pool are encrypted and that
+    /// they are unlocked together with the datadevs when the pool is started.
+    fn test_start_stop_encrypted_cache(paths: &[&Path]) {
+        fn test(paths: &[&Path], key_desc: &KeyDescription) {
+            unshare_mount_namespace().unwrap();
+            let engine = StratEngine::initialize().unwrap();
+            let name = "pool_name";
+            let (data, cache) = paths.split_at(1);
+            let uuid = test_async!(engine.create_pool(
+                name,
+                data,
+                Redundancy::None,
+                Some(&EncryptionInfo::KeyDesc(key_desc.clone())),
+                None,
+                None,
+            ))
+            .unwrap()
+            .changed()
+            .unwrap();
+
+            {
+                let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid)))
+                    .expect("Pool must be present");
+                pool.init_cache(uuid, name, cache, true, CacheSettings::default())
+                    .unwrap();
+                assert!(pool.blockdevs().iter().all(|(_, _, bd)| bd.is_encrypted()));
+            }
+
+            test_async!(engine.stop_pool(PoolIdentifier::Uuid(uuid), true)).unwrap();
+            assert!(test_async!(
+                engine.start_pool(PoolIdentifier::Uuid(uuid), Some(UnlockMethod::Keyring))
+            )
+            .unwrap()
+            .is_changed());
+
+            let pool = test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid)))
+                .expect("Pool must be present");
+            assert!(pool.has_cache());
+            assert_eq!(
+                pool.blockdevs()
+                    .iter()
+                    .filter(|(_, tier, _)| *tier == BlockDevTier::Cache)
+                    .count(),
+                cache.len()
+            );
+            drop(pool);
+
+            test_async!(engine.destroy_pool(uuid, None)).unwrap();
+            cmd::udev_settle().unwrap();
+            engine.teardown().unwrap();
+        }
+
+        crypt::insert_and_cleanup_key(paths, test)
+    }
+
+    #[test]
+    fn loop_test_start_stop_encrypted_cache() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_start_stop_encrypted_cache,
+        );
+    }
+
+    #[test]
+    fn real_test_start_stop_encrypted_cache() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_start_stop_encrypted_cache,
+        );
+    }
 }
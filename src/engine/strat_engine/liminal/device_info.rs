@@ -40,6 +40,7 @@ pub struct LLuksInfo {
     pub identifiers: StratisIdentifiers,
     pub encryption_info: EncryptionInfo,
     pub pool_name: Option<Name>,
+    pub stopped: bool,
 }
 
 impl fmt::Display for LLuksInfo {
@@ -64,6 +65,7 @@ impl From<LuksInfo> for LLuksInfo {
             identifiers: info.identifiers,
             encryption_info: info.encryption_info,
             pool_name: info.pool_name,
+            stopped: info.stopped,
         }
     }
 }
@@ -97,6 +99,9 @@ impl<'a> Into<Value> for &'a LLuksInfo {
         if let Some(ref n) = self.pool_name {
             map.insert("pool_name".to_string(), Value::from(n.to_string()));
         }
+        if self.stopped {
+            map.insert("stopped".to_string(), Value::from(true));
+        }
         json
     }
 }
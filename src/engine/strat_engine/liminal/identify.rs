@@ -103,6 +103,8 @@ pub struct LuksInfo {
     pub encryption_info: EncryptionInfo,
     /// Name of the pool stored in LUKS2 Stratis token
     pub pool_name: Option<Name>,
+    /// Whether the LUKS2 Stratis token records that the pool was stopped
+    pub stopped: bool,
 }
 
 impl fmt::Display for LuksInfo {
@@ -188,6 +190,7 @@ impl From<StratBlockDev> for Vec<DeviceInfo> {
                             device_uuid: bd.uuid(),
                         },
                         pool_name: pname.cloned(),
+                        stopped: bd.stopped(),
                     }));
                     if bd.metadata_path().exists() {
                         device_infos.push(DeviceInfo::Stratis(StratisInfo {
@@ -282,6 +285,7 @@ fn process_luks_device(dev: &UdevEngineDevice) -> Option<LuksInfo> {
                 identifiers: metadata.identifiers,
                 encryption_info: metadata.encryption_info,
                 pool_name: metadata.pool_name,
+                stopped: metadata.stopped,
             }),
         },
         None => {
@@ -378,7 +382,7 @@ fn identify_luks_device(dev: &UdevEngineDevice) -> Option<LuksInfo> {
         }
         Ok(ownership) => match ownership {
             UdevOwnership::Luks => process_luks_device(dev),
-            UdevOwnership::MultipathMember => None,
+            UdevOwnership::MultipathMember | UdevOwnership::IntegrityLayer => None,
             _ => {
                 warn!("udev enumeration identified this device as a LUKS block device but on further examination udev identifies it as a {}",
                       ownership);
@@ -412,7 +416,7 @@ fn identify_stratis_device(dev: &UdevEngineDevice) -> Option<StratisInfo> {
         }
         Ok(ownership) => match ownership {
             UdevOwnership::Stratis => process_stratis_device(dev),
-            UdevOwnership::MultipathMember => None,
+            UdevOwnership::MultipathMember | UdevOwnership::IntegrityLayer => None,
             _ => {
                 warn!("udev enumeration identified this device as a Stratis block device but on further examination udev identifies it as a {}",
                       ownership);
@@ -533,6 +537,7 @@ mod tests {
                 MDADataSize::default(),
                 Some(&EncryptionInfo::KeyDesc(key_description.clone())),
                 None,
+                None,
             )
             .unwrap();
 
@@ -634,6 +639,7 @@ mod tests {
             MDADataSize::default(),
             None,
             None,
+            None,
         )
         .unwrap();
 
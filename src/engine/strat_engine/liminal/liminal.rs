@@ -17,7 +17,7 @@ use crate::{
     engine::{
         engine::{DumpState, Pool, StateDiff},
         strat_engine::{
-            backstore::{find_stratis_devs_by_uuid, CryptHandle, StratBlockDev},
+            backstore::{find_stratis_devs_by_uuid, get_devno_from_path, CryptHandle},
             dm::{has_leftover_devices, stop_partially_constructed_pool},
             liminal::{
                 device_info::{
@@ -61,6 +61,9 @@ pub struct LiminalDevices {
     partially_constructed_pools: HashMap<PoolUuid, DeviceSet>,
     /// Lookup data structure for name to UUID mapping for starting pools by name.
     name_to_uuid: HashMap<Name, UuidOrConflict>,
+    /// Pools for which new encrypted devices were discovered and which are
+    /// to be unlocked automatically, outside of udev event handling.
+    pending_unlocks: HashSet<PoolUuid>,
 }
 
 impl LiminalDevices {
@@ -128,10 +131,12 @@ impl LiminalDevices {
                 for (dev_uuid, info) in map.iter() {
                     match info {
                         LInfo::Stratis(_) => (),
-                        LInfo::Luks(ref luks_info) => match handle_luks(luks_info, unlock_method) {
-                            Ok(()) => unlocked.push(*dev_uuid),
-                            Err(e) => return Err(e),
-                        },
+                        LInfo::Luks(ref luks_info) => {
+                            match handle_luks(luks_info, unlock_method.clone()) {
+                                Ok(()) => unlocked.push(*dev_uuid),
+                                Err(e) => return Err(e),
+                            }
+                        }
                     }
                 }
                 unlocked
@@ -157,6 +162,40 @@ impl LiminalDevices {
         Ok(unlocked)
     }
 
+    /// Take the pools that are queued to be unlocked automatically, with the
+    /// paths of their encrypted devices that are still locked. Pools that
+    /// have since been started or that are partially constructed are
+    /// skipped, as are pools that the LUKS2 metadata of any of their devices
+    /// records were stopped on purpose.
+    pub fn take_pending_unlocks(&mut self) -> Vec<(PoolUuid, Vec<PathBuf>)> {
+        let stopped_pools = &self.stopped_pools;
+        self.pending_unlocks
+            .drain()
+            .filter_map(|pool_uuid| {
+                let device_set = stopped_pools.get(&pool_uuid)?;
+                if device_set.iter().any(|(_, info)| match info {
+                    LInfo::Luks(luks_info) => luks_info.stopped,
+                    LInfo::Stratis(_) => false,
+                }) {
+                    info!("Pool with UUID {pool_uuid} was stopped; not unlocking it automatically");
+                    return None;
+                }
+                let devnodes = device_set
+                    .iter()
+                    .filter_map(|(_, info)| match info {
+                        LInfo::Luks(luks_info) => Some(luks_info.dev_info.devnode.clone()),
+                        LInfo::Stratis(_) => None,
+                    })
+                    .collect::<Vec<_>>();
+                if devnodes.is_empty() {
+                    None
+                } else {
+                    Some((pool_uuid, devnodes))
+                }
+            })
+            .collect()
+    }
+
     /// Start a pool, create the devicemapper devices, and return the fully constructed
     /// pool.
     pub fn start_pool(
@@ -398,29 +437,45 @@ impl LiminalDevices {
         }
     }
 
-    /// Calculate whether block device size has changed.
+    /// Calculate whether block device size has changed. If a datadev has
+    /// shrunk, let the pool attempt to shrink it to its new size.
     fn handle_size_change(
-        tier: BlockDevTier,
+        pool_name: &Name,
+        pool: &mut StratPool,
         dev_uuid: DevUuid,
-        dev: &mut StratBlockDev,
-    ) -> Option<(DevUuid, StratBlockDevDiff)> {
-        if tier == BlockDevTier::Data {
-            let orig = dev.cached();
-            match dev.calc_new_size() {
-                Ok(Some(s)) => Some((dev_uuid, orig.diff(&dev.dump(s)))),
-                Err(e) => {
-                    warn!(
-                        "Failed to determine device size for {}: {}",
-                        dev.devnode().display(),
-                        e
-                    );
-                    None
+    ) -> StratisResult<Option<(DevUuid, StratBlockDevDiff)>> {
+        let (orig, shrunk) = match pool.get_mut_strat_blockdev(dev_uuid)? {
+            Some((BlockDevTier::Data, dev)) => {
+                let orig = dev.cached();
+                match dev.calc_new_size() {
+                    Ok(Some(s)) => {
+                        dev.dump(s);
+                        (orig, dev.is_shrunk())
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to determine device size for {}: {}",
+                            dev.devnode().display(),
+                            e
+                        );
+                        return Ok(None);
+                    }
+                    _ => return Ok(None),
                 }
-                _ => None,
             }
-        } else {
-            None
+            _ => return Ok(None),
+        };
+
+        if shrunk && pool.handle_shrunk_datadev(pool_name, dev_uuid) {
+            info!(
+                "Datadev with UUID {} in pool {} was shrunk to the size of its underlying device",
+                dev_uuid, pool_name
+            );
         }
+
+        Ok(pool
+            .get_strat_blockdev(dev_uuid)
+            .map(|(_, dev)| (dev_uuid, orig.diff(&dev.cached()))))
     }
 
     /// Take maps of pool UUIDs to sets of devices and return a list of
@@ -551,8 +606,10 @@ impl LiminalDevices {
                 Err(e) => return Err((e, bdas)),
             };
 
+            // The pool is started explicitly, so it is set up even if it
+            // is degraded.
             setup_pool(
-                pools, pool_uuid, luks_info, infos, bdas, timestamp, metadata,
+                pools, pool_uuid, luks_info, infos, bdas, timestamp, metadata, true,
             )
         }
 
@@ -633,8 +690,10 @@ impl LiminalDevices {
                 Err(e) => return Err((e, bdas)),
             };
             if let Some(true) | None = metadata.started {
+                // Some devices may yet appear, so the pool is not set up
+                // degraded.
                 setup_pool(
-                    pools, pool_uuid, luks_info, infos, bdas, timestamp, metadata,
+                    pools, pool_uuid, luks_info, infos, bdas, timestamp, metadata, false,
                 )
                 .map(Either::Left)
             } else {
@@ -731,10 +790,8 @@ impl LiminalDevices {
             if let Some(di) = device_info {
                 let pool_uuid = di.stratis_identifiers().pool_uuid;
                 let dev_uuid = di.stratis_identifiers().device_uuid;
-                if let Some((_, pool)) = pools.get_mut_by_uuid(pool_uuid) {
-                    if let Some((tier, dev)) = pool.get_mut_strat_blockdev(dev_uuid)? {
-                        ret = Self::handle_size_change(tier, dev_uuid, dev);
-                    }
+                if let Some((pool_name, pool)) = pools.get_mut_by_uuid(pool_uuid) {
+                    ret = Self::handle_size_change(&pool_name, pool, dev_uuid)?;
                 }
             }
         }
@@ -742,12 +799,46 @@ impl LiminalDevices {
         Ok(ret)
     }
 
+    /// If the device that was removed is a datadev of a pool that is set up,
+    /// record that the datadev is missing, so that the pool is reported as
+    /// degraded.
+    pub fn block_evaluate_removal(pools: &mut Table<PoolUuid, StratPool>, event: &UdevEngineEvent) {
+        if event.event_type() != libudev::EventType::Remove {
+            return;
+        }
+        let device_path = match event.device().devnode() {
+            Some(d) => d,
+            None => return,
+        };
+
+        for (name, pool_uuid, pool) in pools.iter_mut() {
+            let dev_uuid = pool
+                .blockdevs()
+                .into_iter()
+                .find(|(_, tier, bd)| *tier == BlockDevTier::Data && bd.devnode() == device_path)
+                .map(|(dev_uuid, _, _)| dev_uuid);
+            if let Some(dev_uuid) = dev_uuid {
+                if pool.mark_datadev_missing(dev_uuid) {
+                    warn!(
+                        "Device {} with UUID {} belonging to pool {} with UUID {} has disappeared; the pool is degraded",
+                        device_path.display(),
+                        dev_uuid,
+                        name,
+                        pool_uuid
+                    );
+                }
+                return;
+            }
+        }
+    }
+
     /// Given some information gathered about a single Stratis device, determine
     /// whether or not a pool can be constructed, and if it can, construct the
     /// pool and return the newly constructed pool. If the device appears to
     /// belong to a pool that has already been set up assume that no further
     /// processing is required and return None. If there is an error
-    /// constructing the pool, retain the set of devices.
+    /// constructing the pool, retain the set of devices. The pools of newly
+    /// added encrypted devices are queued to be unlocked automatically.
     pub fn block_evaluate(
         &mut self,
         pools: &Table<PoolUuid, StratPool>,
@@ -795,6 +886,10 @@ impl LiminalDevices {
                     self.uuid_lookup
                         .insert(device_path.to_path_buf(), (pool_uuid, device_uuid));
 
+                    if let (libudev::EventType::Add, DeviceInfo::Luks(_)) = (event_type, &info) {
+                        self.pending_unlocks.insert(pool_uuid);
+                    }
+
                     devices.process_info_add(info);
                     match devices.pool_name() {
                         Ok(MaybeInconsistent::No(Some(name))) => {
@@ -943,6 +1038,102 @@ impl<'a> Into<Value> for &'a LiminalDevices {
     }
 }
 
+/// Unlock the encrypted devices of a stopped pool at the given paths with
+/// every unlock method that they are configured with. The pool is set up when
+/// the udev events for the unlocked devices are handled. If any of the
+/// devices can not be unlocked, the devices are locked again.
+///
+/// Pools that were stopped on purpose are not passed to this function; see
+/// LiminalDevices::take_pending_unlocks(). A pool stopped by a version of
+/// stratisd that did not record this in the LUKS2 metadata can only be
+/// recognized from the pool metadata, so if that records that the pool was
+/// stopped, the devices are locked again.
+///
+/// The passphrase is never asked for: automatic unlocking runs unattended,
+/// so a pool whose devices can only be unlocked with a passphrase that is
+/// not in the kernel keyring remains stopped until it is started explicitly,
+/// e.g., by "stratis-min pool start --unlock-method=any", which asks for the
+/// passphrase as a last resort.
+pub fn auto_unlock_pool(pool_uuid: PoolUuid, devnodes: &[PathBuf]) -> StratisResult<()> {
+    fn lock(handles: &[CryptHandle]) {
+        for handle in handles {
+            if let Err(e) = handle.deactivate() {
+                warn!(
+                    "Failed to lock encrypted device {} again: {}",
+                    handle.luks2_device_path().display(),
+                    e
+                );
+            }
+        }
+    }
+
+    fn is_stopped(pool_uuid: PoolUuid, handles: &[CryptHandle]) -> StratisResult<bool> {
+        let infos = handles
+            .iter()
+            .map(|handle| {
+                let devnode = handle.activated_device_path().to_owned();
+                let bda = bda_wrapper(&devnode)
+                    .map_err(StratisError::Msg)?
+                    .map_err(StratisError::Msg)?
+                    .ok_or_else(|| {
+                        StratisError::Msg(format!(
+                            "No Stratis metadata found on unlocked device {}",
+                            devnode.display()
+                        ))
+                    })?;
+                Ok((
+                    handle.device_identifiers().device_uuid,
+                    LStratisInfo {
+                        dev_info: StratisDevInfo {
+                            device_number: get_devno_from_path(&devnode)?,
+                            devnode,
+                        },
+                        bda,
+                        luks: None,
+                    },
+                ))
+            })
+            .collect::<StratisResult<HashMap<_, _>>>()?;
+        let (_, metadata) = load_stratis_metadata(pool_uuid, stratis_infos_ref(&infos))?;
+        Ok(metadata.started == Some(false))
+    }
+
+    info!("Attempting to automatically unlock the devices of pool with UUID {pool_uuid}");
+    let mut handles = Vec::new();
+    for devnode in devnodes {
+        match CryptHandle::setup(devnode, Some(UnlockMethod::Any)) {
+            Ok(Some(handle)) => handles.push(handle),
+            Ok(None) => warn!(
+                "Device {} does not appear to be formatted with the proper Stratis LUKS2 metadata",
+                devnode.display()
+            ),
+            Err(e) => {
+                lock(&handles);
+                return Err(StratisError::Chained(
+                    format!(
+                        "Failed to automatically unlock encrypted device {}; the pool must be started explicitly",
+                        devnode.display()
+                    ),
+                    Box::new(e),
+                ));
+            }
+        }
+    }
+
+    match is_stopped(pool_uuid, &handles) {
+        Ok(false) => Ok(()),
+        Ok(true) => {
+            info!("Pool with UUID {pool_uuid} was stopped; locking its devices again");
+            lock(&handles);
+            Ok(())
+        }
+        Err(e) => {
+            lock(&handles);
+            Err(e)
+        }
+    }
+}
+
 /// Read the BDA and MDA information for a set of devices that has been
 /// determined to be a part of the same pool.
 fn load_stratis_metadata(
@@ -981,8 +1172,13 @@ fn load_stratis_metadata(
 /// the pool information to the stopped pools data structure.
 /// Do not attempt setup if the pool contains any unopened devices.
 ///
+/// If allow_degraded is true, a pool whose data tier has redundancy is set
+/// up even if some of its data devices are absent, so long as one complete
+/// copy of the data is present.
+///
 /// If there is a name conflict between the set of devices in devices
 /// and some existing pool, return an error.
+#[allow(clippy::too_many_arguments)]
 fn setup_pool(
     pools: &Table<PoolUuid, StratPool>,
     pool_uuid: PoolUuid,
@@ -991,6 +1187,7 @@ fn setup_pool(
     bdas: HashMap<DevUuid, BDA>,
     timestamp: DateTime<Utc>,
     metadata: PoolSave,
+    allow_degraded: bool,
 ) -> BDARecordResult<(Name, StratPool)> {
     if let Some((uuid, _)) = pools.get_by_name(&metadata.name) {
         return Err((
@@ -1002,7 +1199,7 @@ fn setup_pool(
             )), bdas));
     }
 
-    let (datadevs, cachedevs) = match get_blockdevs(&metadata.backstore, infos, bdas) {
+    let (datadevs, cachedevs) = match get_blockdevs(&metadata.backstore, infos, bdas, allow_degraded) {
         Err((err, bdas)) => return Err(
             (StratisError::Chained(
                 format!(
@@ -11,5 +11,5 @@ mod setup;
 pub use self::{
     device_info::{DeviceSet, LInfo},
     identify::{find_all, DeviceInfo},
-    liminal::LiminalDevices,
+    liminal::{auto_unlock_pool, LiminalDevices},
 };
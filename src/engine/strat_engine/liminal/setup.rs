@@ -26,7 +26,7 @@ use crate::{
             shared::{bds_to_bdas, tiers_to_bdas},
             types::{BDARecordResult, BDAResult},
         },
-        types::{BlockDevTier, DevUuid, DevicePath, Name},
+        types::{BlockDevTier, DevUuid, DevicePath, Name, Redundancy},
     },
     stratis::{StratisError, StratisResult},
 };
@@ -128,6 +128,9 @@ pub fn get_name(infos: HashMap<DevUuid, &LStratisInfo>) -> StratisResult<Option<
 /// the given devices. Sort the blockdevs in the order in which they were
 /// recorded in the metadata.
 /// Returns an error if the blockdevs obtained do not match the metadata.
+/// If allow_degraded is true and the data tier has redundancy, some of the
+/// data devs recorded in the metadata may be absent; whether the data tier
+/// can be set up without them is determined when it is set up.
 /// Returns a tuple, of which the first are the data devs, and the second
 /// are the devs that support the cache tier.
 /// Precondition: Every device in infos has already been determined to
@@ -137,6 +140,7 @@ pub fn get_blockdevs(
     backstore_save: &BackstoreSave,
     infos: &HashMap<DevUuid, LStratisDevInfo>,
     mut bdas: HashMap<DevUuid, BDA>,
+    allow_degraded: bool,
 ) -> BDARecordResult<(Vec<StratBlockDev>, Vec<StratBlockDev>)> {
     let recorded_data_map: HashMap<DevUuid, (usize, &BaseBlockDevSave)> = backstore_save
         .data_tier
@@ -160,7 +164,8 @@ pub fn get_blockdevs(
         };
 
     let mut segment_table: HashMap<DevUuid, Vec<(Sectors, Sectors)>> = HashMap::new();
-    for seg in &backstore_save.data_tier.blockdev.allocs[0] {
+    // Every copy of the data is allocated from the data tier.
+    for seg in backstore_save.data_tier.blockdev.allocs.iter().flatten() {
         segment_table
             .entry(seg.parent)
             .or_default()
@@ -241,7 +246,12 @@ pub fn get_blockdevs(
         // least the recorded size, so all segments should be
         // available to be allocated. If this fails, the most likely
         // conclusion is metadata corruption.
-        let segments = segment_table.get(&dev_uuid);
+        // The region holding the integrity tags is allocated like the
+        // segments of the upper layers.
+        let mut segments = segment_table.get(&dev_uuid).cloned().unwrap_or_default();
+        if let Some(ref integrity) = bd_save.integrity {
+            segments.push((integrity.meta_start, integrity.meta_length));
+        }
 
         let physical_path = match &info.luks {
             Some(luks) => &luks.dev_info.devnode,
@@ -258,17 +268,27 @@ pub fn get_blockdevs(
                 Err(e) => return Err((e, bda)),
             }),
         };
-        Ok((
-            tier,
-            StratBlockDev::new(
-                info.dev_info.device_number,
-                bda,
-                segments.unwrap_or(&vec![]),
-                bd_save.user_info.clone(),
-                bd_save.hardware_info.clone(),
-                underlying_device,
-            )?,
-        ))
+        // The integrity layer of an encrypted device lies beneath the
+        // encryption and is recorded in the Stratis LUKS2 token.
+        let integrity = bd_save.integrity.clone().or_else(|| {
+            underlying_device
+                .crypt_handle()
+                .and_then(|handle| handle.integrity().cloned())
+        });
+        let mut blockdev = StratBlockDev::new(
+            info.dev_info.device_number,
+            bda,
+            &segments,
+            bd_save.user_info.clone(),
+            bd_save.hardware_info.clone(),
+            underlying_device,
+        )?;
+        if let Some(ref integrity) = integrity {
+            if let Err(e) = blockdev.setup_integrity(integrity) {
+                return Err((e, blockdev.bda));
+            }
+        }
+        Ok((tier, blockdev))
     }
 
     let (mut datadevs, mut cachedevs): (Vec<StratBlockDev>, Vec<StratBlockDev>) = (vec![], vec![]);
@@ -296,6 +316,7 @@ pub fn get_blockdevs(
     fn check_and_sort_devs(
         mut devs: Vec<StratBlockDev>,
         dev_map: &HashMap<DevUuid, (usize, &BaseBlockDevSave)>,
+        allow_absent: bool,
     ) -> BDARecordResult<Vec<StratBlockDev>> {
         let mut uuids = HashSet::new();
         let mut duplicate_uuids = Vec::new();
@@ -315,7 +336,12 @@ pub fn get_blockdevs(
         }
 
         let recorded_uuids: HashSet<_> = dev_map.keys().cloned().collect();
-        if uuids != recorded_uuids {
+        let consistent = if allow_absent {
+            uuids.is_subset(&recorded_uuids)
+        } else {
+            uuids == recorded_uuids
+        };
+        if !consistent {
             let err_msg = format!(
                 "UUIDs of devices found ({}) did not correspond with UUIDs specified in the metadata for this group of devices ({})",
                 uuids.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(", "),
@@ -332,7 +358,9 @@ pub fn get_blockdevs(
         Ok(devs)
     }
 
-    let datadevs = match check_and_sort_devs(datadevs, &recorded_data_map) {
+    let allow_absent =
+        allow_degraded && backstore_save.redundancy.unwrap_or_default() != Redundancy::None;
+    let datadevs = match check_and_sort_devs(datadevs, &recorded_data_map, allow_absent) {
         Ok(dd) => dd,
         Err((err, mut bdas)) => {
             bdas.extend(bds_to_bdas(cachedevs));
@@ -345,7 +373,7 @@ pub fn get_blockdevs(
         }
     };
 
-    let cachedevs = match check_and_sort_devs(cachedevs, &recorded_cache_map) {
+    let cachedevs = match check_and_sort_devs(cachedevs, &recorded_cache_map, false) {
         Ok(cd) => cd,
         Err((err, mut bdas)) => {
             bdas.extend(bds_to_bdas(datadevs));
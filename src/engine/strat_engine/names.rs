@@ -126,6 +126,20 @@ pub enum CacheRole {
     MetaSub,
     /// The origin sub-device of the DM cache device, holds the actual data.
     OriginSub,
+    /// A temporary mirror device, mapped into the origin while the data on
+    /// a blockdev is copied to its replacement.
+    Mirror,
+    /// The raid device that holds the data of a data tier with redundancy,
+    /// mapped into the origin.
+    Raid,
+    /// A leg of the raid device, holds one copy of the data.
+    RaidLeg(usize),
+    /// The metadata of a leg of the raid device, records which regions of
+    /// the leg are in sync.
+    RaidMeta(usize),
+    /// The device that maps the extents of the data tier, some of which are
+    /// striped across blockdevs, mapped into the origin.
+    Stripe,
 }
 
 impl Display for CacheRole {
@@ -135,6 +149,34 @@ impl Display for CacheRole {
             CacheRole::CacheSub => write!(f, "cachesub"),
             CacheRole::MetaSub => write!(f, "metasub"),
             CacheRole::OriginSub => write!(f, "originsub"),
+            CacheRole::Mirror => write!(f, "mirror"),
+            CacheRole::Raid => write!(f, "raid"),
+            CacheRole::RaidLeg(leg) => write!(f, "raidleg{leg}"),
+            CacheRole::RaidMeta(leg) => write!(f, "raidmeta{leg}"),
+            CacheRole::Stripe => write!(f, "stripe"),
+        }
+    }
+}
+
+/// The roles taken on by the DM devices that add integrity protection to a
+/// blockdev.
+#[derive(Clone, Copy)]
+pub enum IntegrityRole {
+    /// The dm-integrity device, checks all data read from the blockdev.
+    Integrity,
+    /// The linear device that maps the data region of the blockdev.
+    Data,
+    /// The linear device that maps the region of the blockdev that holds
+    /// the integrity tags.
+    Meta,
+}
+
+impl Display for IntegrityRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            IntegrityRole::Integrity => write!(f, "integrity"),
+            IntegrityRole::Data => write!(f, "integritydata"),
+            IntegrityRole::Meta => write!(f, "integritymeta"),
         }
     }
 }
@@ -238,6 +280,47 @@ pub fn format_backstore_ids(pool_uuid: PoolUuid, role: CacheRole) -> (DmNameBuf,
     )
 }
 
+/// Format a name & uuid for the dm devices that add integrity protection to
+/// a blockdev.
+///
+/// Prerequisite: len(format!("{}", FORMAT_VERSION)
+///             + len("stratis")                         7
+///             + len("private")                         7
+///             + num_dashes                             4
+///             + len(dev uuid)                          32
+///             + max(len(IntegrityRole))                13
+///             < 128 (129 for UUID)
+///
+/// which is equivalent to len(format!("{}", FORMAT_VERSION) < 65 (66 for UUID)
+pub fn format_integrity_ids(dev_uuid: DevUuid, role: IntegrityRole) -> (DmNameBuf, DmUuidBuf) {
+    let value = format!(
+        "stratis-{}-private-{}-{}",
+        FORMAT_VERSION,
+        uuid_to_string!(dev_uuid),
+        role
+    );
+    (
+        DmNameBuf::new(value.clone()).expect("FORMAT_VERSION display_length < 65"),
+        DmUuidBuf::new(value).expect("FORMAT_VERSION display_length < 66"),
+    )
+}
+
+/// Return true if the device mapper name is the name of one of the dm devices
+/// that add integrity protection to a blockdev.
+pub fn is_integrity_name(name: &str) -> bool {
+    name.strip_prefix(&format!("stratis-{FORMAT_VERSION}-private-"))
+        .map(|rest| {
+            [
+                IntegrityRole::Integrity,
+                IntegrityRole::Data,
+                IntegrityRole::Meta,
+            ]
+            .iter()
+            .any(|role| rest.ends_with(&format!("-{role}")))
+        })
+        .unwrap_or(false)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2,23 +2,34 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::{cmp::max, collections::HashMap, path::Path, vec::Vec};
+use std::{
+    cmp::max,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+    vec::Vec,
+};
 
 use chrono::{DateTime, Utc};
 use serde_json::{Map, Value};
 
-use devicemapper::{Bytes, DmNameBuf, Sectors};
+use devicemapper::{Bytes, DataBlocks, DmNameBuf, Sectors, ThinDevId};
 use stratisd_proc_macros::strat_pool_impl_gen;
 
 use crate::{
     engine::{
         engine::{BlockDev, DumpState, Filesystem, Pool, StateDiff},
         shared::{
-            init_cache_idempotent_or_err, validate_filesystem_size, validate_filesystem_size_specs,
-            validate_name, validate_paths,
+            init_cache_idempotent_or_err, run_scheduled_snapshot_plan, scheduled_snapshot_plans,
+            validate_crypt_params, validate_filesystem_size, validate_filesystem_size_specs,
+            validate_name, validate_paths, validate_redundancy, validate_scheduled_snapshot_name,
+            validate_snapshot_specs,
         },
         strat_engine::{
-            backstore::{Backstore, ProcessedPathInfos, StratBlockDev, UnownedDevices},
+            backstore::{
+                wipe_blockdevs, Backstore, CryptHandle, ProcessedPathInfos, StratBlockDev,
+                UnownedDevices,
+            },
             liminal::DeviceSet,
             metadata::{MDADataSize, BDA},
             serde_structs::{FlexDevsSave, PoolSave, Recordable},
@@ -27,16 +38,38 @@ use crate::{
             types::BDARecordResult,
         },
         types::{
-            ActionAvailability, BlockDevTier, Clevis, Compare, CreateAction, DeleteAction, DevUuid,
-            Diff, EncryptionInfo, FilesystemUuid, GrowAction, Key, KeyDescription, Name, PoolDiff,
-            PoolEncryptionInfo, PoolUuid, RegenAction, RenameAction, SetCreateAction,
-            SetDeleteAction, StratFilesystemDiff, StratPoolDiff,
+            ActionAvailability, AllocationPolicy, BlockDevTier, CacheSettings, CacheStats, Clevis,
+            Compare, CreateAction, CryptParams, DataShrink, DeleteAction, DevUuid, Diff,
+            EncryptedDevice, EncryptionInfo, EraseMode, FilesystemUuid, GrowAction, IntegrityHash,
+            Key, KeyDescription, Name, PoolDiff, PoolEncryptionInfo, PoolUuid, Redundancy,
+            Reencryption, RegenAction, RenameAction, RevertAction, SetCreateAction,
+            SetDeleteAction, SnapshotSchedule, SnapshotScheduleRun, SnapshotScheduleStatus,
+            StartAction, StratBlockDevDiff, StratFilesystemDiff, StratPoolDiff,
         },
         PropChangeAction,
     },
     stratis::{StratisError, StratisResult},
 };
 
+/// The longest time for which the dirty blocks of a cache are written back
+/// while the pool is suspended. Only the blocks dirtied since the cache was
+/// last found clean remain to be written back.
+const CACHE_FLUSH_SUSPENDED_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Restore the settings of the cache of backstore after writing back its
+/// dirty blocks in preparation for its removal has failed with err. Return
+/// err, or a rollback error if the settings could not be restored.
+fn abort_cache_flush(backstore: &mut Backstore, err: StratisError) -> StratisError {
+    match backstore.abort_cache_flush() {
+        Ok(_) => err,
+        Err(e) => StratisError::RollbackError {
+            causal_error: Box::new(err),
+            rollback_error: Box::new(e),
+            level: ActionAvailability::NoRequests,
+        },
+    }
+}
+
 /// Get the index which indicates the start of unallocated space in the cap
 /// device.
 /// NOTE: Since segments are always allocated to each flex dev in order, the
@@ -151,12 +184,35 @@ fn get_pool_state(info: Option<PoolEncryptionInfo>, backstore: &Backstore) -> Ac
     max(avail, backstore.action_availability())
 }
 
+/// Read the statistics of the cache of the pool, if it has a cache. If the
+/// statistics can not be obtained, log the error and report no statistics.
+fn read_cache_stats(backstore: &Backstore) -> Option<CacheStats> {
+    backstore.cache_stats().unwrap_or_else(|e| {
+        warn!("Failed to read the statistics of the cache: {}", e);
+        None
+    })
+}
+
+/// Determine whether the data tier of the pool is degraded. If this can not
+/// be determined, log the error and report the pool as degraded.
+fn read_degraded(backstore: &Backstore) -> bool {
+    backstore.is_degraded().unwrap_or_else(|e| {
+        warn!(
+            "Failed to determine whether the data tier is degraded: {}",
+            e
+        );
+        true
+    })
+}
+
 #[derive(Debug)]
 pub struct StratPool {
     backstore: Backstore,
     thin_pool: ThinPool,
     action_avail: ActionAvailability,
     metadata_size: Sectors,
+    cache_stats: Option<CacheStats>,
+    degraded: bool,
 }
 
 #[strat_pool_impl_gen]
@@ -169,6 +225,9 @@ impl StratPool {
         name: &str,
         devices: UnownedDevices,
         encryption_info: Option<&EncryptionInfo>,
+        crypt_params: Option<&CryptParams>,
+        integrity: Option<IntegrityHash>,
+        redundancy: Redundancy,
     ) -> StratisResult<(PoolUuid, StratPool)> {
         let pool_uuid = PoolUuid::new_v4();
 
@@ -181,6 +240,9 @@ impl StratPool {
             devices,
             MDADataSize::default(),
             encryption_info,
+            crypt_params,
+            integrity,
+            redundancy,
         )?;
 
         let thinpool = ThinPool::new(
@@ -222,6 +284,8 @@ impl StratPool {
             thin_pool: thinpool,
             action_avail: ActionAvailability::Full,
             metadata_size,
+            cache_stats: None,
+            degraded: false,
         };
 
         pool.write_metadata(&Name::new(name.to_owned()))?;
@@ -252,7 +316,7 @@ impl StratPool {
             return Err((e, tiers_to_bdas(datadevs, cachedevs, None)));
         }
 
-        let backstore =
+        let mut backstore =
             Backstore::setup(uuid, &metadata.backstore, datadevs, cachedevs, timestamp)?;
         let action_avail = get_pool_state(encryption_info, &backstore);
 
@@ -270,7 +334,7 @@ impl StratPool {
             uuid,
             &metadata.thinpool_dev,
             &metadata.flex_devs,
-            &backstore,
+            &mut backstore,
         ) {
             Ok(tp) => tp,
             Err(e) => return Err((e, backstore.into_bdas())),
@@ -281,17 +345,36 @@ impl StratPool {
             || metadata.thinpool_dev.feature_args.is_none();
 
         let metadata_size = backstore.datatier_metadata_size();
+        let cache_stats = read_cache_stats(&backstore);
+        let degraded = read_degraded(&backstore);
         let mut pool = StratPool {
             backstore,
             thin_pool: thinpool,
             action_avail,
             metadata_size,
+            cache_stats,
+            degraded,
         };
 
         // The value of the started field in the pool metadata needs to be
         // updated unless the value is already present in the metadata and has
         // value true.
         needs_save |= !metadata.started.unwrap_or(false);
+        if let Err(e) = pool.backstore.set_stopped(false) {
+            warn!(
+                "Failed to record in the LUKS2 metadata that pool with UUID {} was started: {}",
+                uuid, e
+            );
+        }
+
+        // The data device was shrunk, or could not be shrunk, on setup; in
+        // either case the pending shrink is no longer recorded.
+        needs_save |= metadata.thinpool_dev.data_shrink.is_some();
+
+        // A replacement recorded in the metadata may have been abandoned on
+        // setup.
+        needs_save |= metadata.backstore.data_tier.replacement.is_some()
+            && pool.backstore.replacement_progress().is_none();
 
         if needs_save {
             if let Err(err) = pool.write_metadata(pool_name) {
@@ -340,6 +423,38 @@ impl StratPool {
         self.thin_pool.has_filesystems()
     }
 
+    /// Begin writing back the dirty blocks of the cache, if the pool has a
+    /// cache, so that remove_cache() has few left to write back while the
+    /// pool is locked. Return true if the pool has a cache.
+    #[pool_mutating_action("NoRequests")]
+    pub fn start_cache_flush(&mut self) -> StratisResult<bool> {
+        if !self.has_cache() {
+            return Ok(false);
+        }
+        self.backstore.start_cache_flush()?;
+        Ok(true)
+    }
+
+    /// The number of dirty blocks in the cache, if the pool has a cache.
+    pub fn cache_dirty_blocks(&self) -> StratisResult<Option<u64>> {
+        if !self.has_cache() {
+            return Ok(None);
+        }
+        self.backstore.cache_dirty_blocks().map(Some)
+    }
+
+    /// Abandon writing back the dirty blocks of the cache begun by
+    /// start_cache_flush(), which failed with err, and restore the settings
+    /// of the cache. Return err, or a rollback error if the settings could
+    /// not be restored.
+    #[pool_rollback]
+    pub fn abandon_cache_flush(&mut self, err: StratisError) -> StratisResult<()> {
+        if !self.has_cache() {
+            return Err(err);
+        }
+        Err(abort_cache_flush(&mut self.backstore, err))
+    }
+
     /// The names of DM devices belonging to this pool that may generate events
     pub fn get_eventing_dev_names(&self, pool_uuid: PoolUuid) -> Vec<DmNameBuf> {
         self.thin_pool.get_eventing_dev_names(pool_uuid)
@@ -351,11 +466,95 @@ impl StratPool {
     pub fn event_on(&mut self, pool_uuid: PoolUuid, pool_name: &Name) -> StratisResult<PoolDiff> {
         let cached = self.cached();
         let (changed, thin_pool) = self.thin_pool.check(pool_uuid, &mut self.backstore)?;
+        if changed {
+            self.write_metadata(pool_name)?;
+        }
+        self.finish_replacement(pool_uuid, pool_name)?;
+        let blockdevs = self.check_integrity();
         let pool = cached.diff(&self.dump(()));
+        Ok(PoolDiff {
+            thin_pool,
+            pool,
+            blockdevs,
+        })
+    }
+
+    /// Prepare to continue the online encryption or reencryption of the
+    /// datadevs, if one is in progress, and return the UUID and a handle of
+    /// the datadev whose data is to be processed, if any. The data is
+    /// processed without holding the lock on the pool; see
+    /// Backstore::next_reencryption().
+    #[pool_mutating_action("NoPoolChanges")]
+    pub fn next_reencryption(
+        &mut self,
+        pool_name: &Name,
+    ) -> StratisResult<Option<(DevUuid, CryptHandle)>> {
+        let (changed, next) = self.backstore.next_reencryption()?;
         if changed {
             self.write_metadata(pool_name)?;
         }
-        Ok(PoolDiff { thin_pool, pool })
+        Ok(next)
+    }
+
+    /// Record the progress of the online encryption or reencryption of the
+    /// datadev, as returned by CryptHandle::continue_reencryption(), and
+    /// return the changes.
+    #[pool_mutating_action("NoPoolChanges")]
+    pub fn record_reencryption(
+        &mut self,
+        pool_name: &Name,
+        uuid: DevUuid,
+        progress: Option<u8>,
+    ) -> StratisResult<PoolDiff> {
+        let cached = self.cached();
+        if self.backstore.record_reencryption(uuid, progress) {
+            self.write_metadata(pool_name)?;
+        }
+        Ok(PoolDiff {
+            thin_pool: self.thin_pool.cached().unchanged(),
+            pool: cached.diff(&self.dump(())),
+            blockdevs: HashMap::new(),
+        })
+    }
+
+    /// Refresh the number of integrity errors of the datadevs and return
+    /// the changes.
+    fn check_integrity(&mut self) -> HashMap<DevUuid, StratBlockDevDiff> {
+        let cached = self
+            .backstore
+            .datadevs()
+            .into_iter()
+            .map(|(uuid, bd)| (uuid, bd.cached()))
+            .collect::<HashMap<_, _>>();
+        self.backstore
+            .check_integrity()
+            .into_iter()
+            .filter_map(|uuid| {
+                let orig = cached.get(&uuid)?;
+                self.backstore
+                    .get_blockdev_by_uuid(uuid)
+                    .map(|(_, bd)| (uuid, orig.diff(&bd.cached())))
+            })
+            .collect()
+    }
+
+    /// Check the progress of the replacement or removal of blockdevs, if one
+    /// is in progress, and complete it if all data has been copied.
+    fn finish_replacement(&mut self, pool_uuid: PoolUuid, pool_name: &str) -> StratisResult<()> {
+        let mut old = self.backstore.check_replacement(pool_uuid)?;
+        if !old.is_empty() {
+            self.write_metadata(pool_name)?;
+
+            // The pool metadata no longer refers to the replaced or removed
+            // blockdevs, so failing to wipe them does not affect the pool.
+            if let Err(e) = wipe_blockdevs(&mut old) {
+                warn!(
+                    "Failed to wipe Stratis metadata from blockdevs replaced in or removed from pool with UUID {}: {}",
+                    pool_uuid, e
+                );
+            }
+        }
+        Ok(())
     }
 
     /// Called when a DM device in this pool has generated an event. This method
@@ -368,6 +567,28 @@ impl StratPool {
         self.thin_pool.check_fs(pool_uuid, &self.backstore)
     }
 
+    /// Reserve a metadata snapshot of the thin pool, from which the space
+    /// mapped exclusively by each filesystem in this pool and the space that
+    /// it shares with other filesystems can be computed while the pool is not
+    /// locked. Returns the path of the thin pool metadata device.
+    #[pool_mutating_action("NoPoolChanges")]
+    pub fn reserve_metadata_snap(&mut self) -> StratisResult<PathBuf> {
+        self.thin_pool.reserve_metadata_snap()
+    }
+
+    /// Release the metadata snapshot reserved by reserve_metadata_snap and
+    /// record the space usage of the filesystems computed from it.
+    #[pool_mutating_action("NoPoolChanges")]
+    pub fn record_space_usage(
+        &mut self,
+        blocks: StratisResult<HashMap<ThinDevId, (DataBlocks, DataBlocks)>>,
+    ) -> StratisResult<HashMap<FilesystemUuid, StratFilesystemDiff>> {
+        let released = self.thin_pool.release_metadata_snap();
+        let diffs = self.thin_pool.set_space_usage(&blocks?);
+        released?;
+        Ok(diffs)
+    }
+
     pub fn record(&self, name: &str) -> PoolSave {
         PoolSave {
             name: name.to_owned(),
@@ -394,6 +615,37 @@ impl StratPool {
         self.backstore.blockdevs()
     }
 
+    /// Record that the datadev has disappeared from the system. The pool is
+    /// reported as degraded from the next time its state is refreshed until
+    /// it is stopped. Return true if the datadev was not already known to be
+    /// missing.
+    pub fn mark_datadev_missing(&mut self, uuid: DevUuid) -> bool {
+        self.backstore.mark_datadev_missing(uuid)
+    }
+
+    /// Handle a datadev the underlying device of which has shrunk. If the
+    /// region that was lost holds no allocations, shrink the datadev to the
+    /// new size and return true. Otherwise, the datadev remains shrunk, so
+    /// that the pool is reported as degraded, and the pool no longer accepts
+    /// requests, since the DM tables of the pool map sectors past the end of
+    /// the device.
+    pub fn handle_shrunk_datadev(&mut self, pool_name: &Name, uuid: DevUuid) -> bool {
+        match self.backstore.shrink(uuid) {
+            Ok(changed) => changed,
+            Err(e) => {
+                warn!(
+                    "Datadev with UUID {} in pool {} has shrunk and can not be shrunk in the metadata: {}; putting pool in {} state",
+                    uuid,
+                    pool_name,
+                    e,
+                    ActionAvailability::NoRequests,
+                );
+                self.action_avail = max(self.action_avail.clone(), ActionAvailability::NoRequests);
+                false
+            }
+        }
+    }
+
     #[pool_mutating_action("NoPoolChanges")]
     pub fn blockdevs_mut(
         &mut self,
@@ -407,12 +659,28 @@ impl StratPool {
     ///
     /// This method is not a mutating action as the pool should be allowed
     /// to be destroyed even if the metadata is inconsistent.
-    pub fn destroy(&mut self, pool_uuid: PoolUuid) -> Result<(), (StratisError, bool)> {
+    pub fn destroy(
+        &mut self,
+        pool_uuid: PoolUuid,
+        erase_mode: Option<EraseMode>,
+    ) -> Result<(), (StratisError, bool)> {
         self.thin_pool.teardown(pool_uuid)?;
+        // Keyslots must be erased before the LUKS2 metadata is wiped.
+        if erase_mode == Some(EraseMode::Keyslots) {
+            self.backstore.erase_keyslots().map_err(|e| (e, false))?;
+        }
         self.backstore.destroy(pool_uuid).map_err(|e| (e, false))?;
         Ok(())
     }
 
+    /// The ranges of each block device of the pool that are allocated, as
+    /// (start, length) pairs, together with the path of the block device;
+    /// None if the whole block device must be erased. See
+    /// Backstore::allocated_ranges().
+    pub fn allocated_ranges(&self) -> Vec<(PathBuf, Option<Vec<(Sectors, Sectors)>>)> {
+        self.backstore.allocated_ranges()
+    }
+
     /// Check the limit of filesystems on a pool and return an error if it has been passed.
     fn check_fs_limit(&self, new_fs: usize) -> StratisResult<()> {
         let fs_limit = self.fs_limit();
@@ -437,6 +705,16 @@ impl StratPool {
         self.backstore
             .save_state(json.as_bytes())
             .map_err(|e| (e, false))?;
+        // The pool metadata is not readable until the blockdevs of an
+        // encrypted pool are unlocked, so the LUKS2 metadata records that
+        // the pool was stopped as well, so that the pool is not unlocked
+        // automatically.
+        if let Err(e) = self.backstore.set_stopped(true) {
+            warn!(
+                "Failed to record in the LUKS2 metadata that pool with UUID {} was stopped; its devices may be unlocked automatically: {}",
+                pool_uuid, e
+            );
+        }
         self.backstore.teardown(pool_uuid).map_err(|e| (e, false))?;
         let bds = self.backstore.drain_bds();
         Ok(DeviceSet::from(bds))
@@ -534,6 +812,7 @@ impl Pool for StratPool {
         pool_name: &str,
         blockdevs: &[&Path],
         supports_encrypted: bool,
+        settings: CacheSettings,
     ) -> StratisResult<SetCreateAction<DevUuid>> {
         validate_paths(blockdevs)?;
 
@@ -642,6 +921,7 @@ impl Pool for StratPool {
                     pool_uuid,
                     unowned_devices,
                     sector_size,
+                    settings,
                 )
                 .and_then(|bdi| {
                     self.thin_pool
@@ -653,6 +933,7 @@ impl Pool for StratPool {
                 });
             self.thin_pool.resume()?;
             let devices = devices_result?;
+            self.cache_stats = read_cache_stats(&self.backstore);
             self.write_metadata(pool_name)?;
             Ok(SetCreateAction::new(devices))
         } else {
@@ -681,6 +962,72 @@ impl Pool for StratPool {
         }
     }
 
+    #[pool_mutating_action("NoRequests")]
+    #[pool_rollback]
+    fn remove_cache(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+    ) -> StratisResult<SetDeleteAction<DevUuid>> {
+        if !self.has_cache() {
+            return Ok(SetDeleteAction::new(vec![]));
+        }
+
+        // The dirty blocks must already have been written back while the
+        // pool was in use, as Engine::remove_cache() does; only those dirtied
+        // since are written back while the pool is suspended.
+        if !self.backstore.cache_flushing() {
+            self.backstore.start_cache_flush()?;
+        }
+        match self.backstore.cache_dirty_blocks() {
+            Ok(0) => (),
+            Ok(dirty) => {
+                return Err(abort_cache_flush(
+                    &mut self.backstore,
+                    StratisError::Msg(format!(
+                        "The cache still holds {dirty} dirty blocks; they must be written back before the cache can be removed"
+                    )),
+                ))
+            }
+            Err(err) => return Err(abort_cache_flush(&mut self.backstore, err)),
+        }
+
+        if let Err(err) = self.thin_pool.suspend() {
+            return Err(abort_cache_flush(&mut self.backstore, err));
+        }
+        let detach_res = self
+            .backstore
+            .flush_cache(CACHE_FLUSH_SUSPENDED_TIMEOUT)
+            .and_then(|_| {
+                let origin = self
+                    .backstore
+                    .cache_origin()
+                    .expect("self.has_cache() was checked above");
+                self.thin_pool
+                    .set_device(origin)
+                    .map_err(|err| abort_cache_flush(&mut self.backstore, err))
+            });
+        self.thin_pool.resume()?;
+        detach_res?;
+
+        let mut removed = self.backstore.remove_cache(pool_uuid)?;
+        self.cache_stats = None;
+        self.write_metadata(pool_name)?;
+
+        let uuids = removed.iter().map(|bd| bd.uuid()).collect::<Vec<_>>();
+
+        // The pool metadata no longer refers to the cache, so failing to
+        // wipe the cachedevs does not affect the pool.
+        if let Err(e) = wipe_blockdevs(&mut removed) {
+            warn!(
+                "Failed to wipe Stratis metadata from cachedevs removed from pool with UUID {}: {}",
+                pool_uuid, e
+            );
+        }
+
+        Ok(SetDeleteAction::new(uuids))
+    }
+
     #[pool_mutating_action("NoRequests")]
     #[pool_rollback]
     fn bind_clevis(
@@ -707,6 +1054,94 @@ impl Pool for StratPool {
         }
     }
 
+    #[pool_mutating_action("NoRequests")]
+    #[pool_rollback]
+    fn encrypt_pool(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        encryption_info: &EncryptionInfo,
+        crypt_params: Option<&CryptParams>,
+    ) -> StratisResult<(CreateAction<EncryptedDevice>, Option<PoolDiff>)> {
+        validate_crypt_params(Some(encryption_info), crypt_params)?;
+        validate_redundancy(self.backstore.redundancy(), true)?;
+        if let Some(current) = pool_enc_to_enc!(self.encryption_info()) {
+            return if &current == encryption_info {
+                Ok((CreateAction::Identity, None))
+            } else {
+                Err(StratisError::Msg(format!(
+                    "Pool with UUID {pool_uuid} is already encrypted with different encryption info"
+                )))
+            };
+        }
+
+        let mut crypt_params = crypt_params.cloned().unwrap_or_default();
+        if crypt_params.sector_size.is_none() {
+            crypt_params.sector_size = Some(convert_int!(
+                *self
+                    .backstore
+                    .block_size_summary(BlockDevTier::Data)
+                    .expect("always exists")
+                    .validate()
+                    .expect("All operations prevented if validate() function on data tier block size summary returns an error")
+                    .base
+                    .logical_sector_size,
+                u128,
+                u32
+            )?);
+        }
+
+        let cached = self.cached();
+
+        self.backstore.encrypt(
+            pool_uuid,
+            &Name::new(pool_name.to_string()),
+            encryption_info,
+            &crypt_params,
+        )?;
+        self.write_metadata(pool_name)?;
+
+        Ok((
+            CreateAction::Created(EncryptedDevice),
+            Some(PoolDiff {
+                thin_pool: self.thin_pool.cached().unchanged(),
+                pool: cached.diff(&self.dump(())),
+                blockdevs: HashMap::new(),
+            }),
+        ))
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    #[pool_rollback]
+    fn reencrypt_pool(
+        &mut self,
+        pool_name: &str,
+        crypt_params: Option<&CryptParams>,
+    ) -> StratisResult<(StartAction<Reencryption>, Option<PoolDiff>)> {
+        if self.backstore.reencryption_progress().is_some() {
+            return Ok((StartAction::Identity, None));
+        }
+        crypt_params.map(CryptParams::validate).transpose()?;
+
+        let cached = self.cached();
+
+        self.backstore.reencrypt(crypt_params)?;
+        self.write_metadata(pool_name)?;
+
+        Ok((
+            StartAction::Started(Reencryption),
+            Some(PoolDiff {
+                thin_pool: self.thin_pool.cached().unchanged(),
+                pool: cached.diff(&self.dump(())),
+                blockdevs: HashMap::new(),
+            }),
+        ))
+    }
+
+    fn back_up_luks_headers(&self, pool_uuid: PoolUuid, archive_path: &Path) -> StratisResult<()> {
+        self.backstore.back_up_luks_headers(pool_uuid, archive_path)
+    }
+
     #[pool_mutating_action("NoRequests")]
     #[pool_rollback]
     fn bind_keyring(
@@ -745,6 +1180,43 @@ impl Pool for StratPool {
         }
     }
 
+    #[pool_mutating_action("NoRequests")]
+    #[pool_rollback]
+    fn add_keyring_binding(
+        &mut self,
+        key_description: &KeyDescription,
+    ) -> StratisResult<CreateAction<Key>> {
+        let changed = self.backstore.add_keyring_binding(key_description)?;
+        if changed {
+            Ok(CreateAction::Created(Key))
+        } else {
+            Ok(CreateAction::Identity)
+        }
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    #[pool_rollback]
+    fn remove_keyring_binding(
+        &mut self,
+        key_description: &KeyDescription,
+    ) -> StratisResult<DeleteAction<Key>> {
+        let changed = self.backstore.remove_keyring_binding(key_description)?;
+        if changed {
+            Ok(DeleteAction::Deleted(Key))
+        } else {
+            Ok(DeleteAction::Identity)
+        }
+    }
+
+    fn keyring_bindings(&self) -> StratisResult<Vec<KeyDescription>> {
+        match self.backstore.encryption_info() {
+            Some(ei) => ei.keyring_bindings().map(|kds| kds.to_vec()),
+            None => Err(StratisError::Msg(
+                "Requested pool does not appear to be encrypted".to_string(),
+            )),
+        }
+    }
+
     #[pool_mutating_action("NoRequests")]
     #[pool_rollback]
     fn rebind_clevis(&mut self) -> StratisResult<RegenAction> {
@@ -990,6 +1462,7 @@ impl Pool for StratPool {
                     Some(PoolDiff {
                         thin_pool: self.thin_pool.cached().unchanged(),
                         pool: cached.diff(&self.dump(())),
+                        blockdevs: HashMap::new(),
                     }),
                 ))
             }
@@ -998,6 +1471,186 @@ impl Pool for StratPool {
         bdev_info
     }
 
+    #[pool_mutating_action("NoRequests")]
+    #[pool_rollback]
+    fn remove_blockdevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        uuids: &[DevUuid],
+    ) -> StratisResult<(SetDeleteAction<DevUuid>, Option<PoolDiff>)> {
+        let mut to_remove = Vec::new();
+        for &uuid in uuids {
+            match self.backstore.get_blockdev_by_uuid(uuid) {
+                Some((BlockDevTier::Data, _)) => {
+                    if !to_remove.contains(&uuid) {
+                        to_remove.push(uuid);
+                    }
+                }
+                Some((BlockDevTier::Cache, _)) => {
+                    return Err(StratisError::Msg(format!(
+                        "Blockdev with UUID {uuid} belongs to the cache tier; only blockdevs in the data tier can be removed"
+                    )));
+                }
+                None if self.backstore.is_absent_datadev(uuid) => {
+                    if !to_remove.contains(&uuid) {
+                        to_remove.push(uuid);
+                    }
+                }
+                None => (),
+            }
+        }
+
+        if to_remove.is_empty() {
+            return Ok((SetDeleteAction::new(vec![]), None));
+        }
+
+        let cached = self.cached();
+
+        let mut removed = self.backstore.remove_datadevs(pool_uuid, &to_remove)?;
+        self.write_metadata(pool_name)?;
+
+        // The pool metadata no longer refers to the removed blockdevs, so
+        // failing to wipe them does not affect the pool.
+        if let Err(e) = wipe_blockdevs(&mut removed) {
+            warn!(
+                "Failed to wipe Stratis metadata from blockdevs removed from pool with UUID {}: {}",
+                pool_uuid, e
+            );
+        }
+
+        // If the removed blockdevs held no data, the removal can be completed
+        // at once.
+        self.finish_replacement(pool_uuid, pool_name)?;
+
+        Ok((
+            SetDeleteAction::new(to_remove),
+            Some(PoolDiff {
+                thin_pool: self.thin_pool.cached().unchanged(),
+                pool: cached.diff(&self.dump(())),
+                blockdevs: HashMap::new(),
+            }),
+        ))
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    #[pool_rollback]
+    fn replace_blockdev(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        old: DevUuid,
+        new: &Path,
+    ) -> StratisResult<(CreateAction<DevUuid>, Option<PoolDiff>)> {
+        match self.backstore.get_blockdev_by_uuid(old) {
+            Some((BlockDevTier::Data, _)) => (),
+            Some((BlockDevTier::Cache, _)) => {
+                return Err(StratisError::Msg(format!(
+                    "Blockdev with UUID {old} belongs to the cache tier; only blockdevs in the data tier can be replaced"
+                )));
+            }
+            None if self.backstore.is_absent_datadev(old) => (),
+            None => {
+                return Err(StratisError::Msg(format!(
+                    "Pool with UUID {pool_uuid} has no blockdev with UUID {old}"
+                )));
+            }
+        }
+
+        let paths = [new];
+        validate_paths(&paths)?;
+
+        let devices = ProcessedPathInfos::try_from(paths.as_slice())?;
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        let (this_pool, other_pools) = stratis_devices.partition(pool_uuid);
+        other_pools.error_on_not_empty()?;
+        if !this_pool.is_empty() {
+            return Err(StratisError::Msg(format!(
+                "Device {} already belongs to pool with UUID {pool_uuid}",
+                new.display()
+            )));
+        }
+
+        let block_size_summary = unowned_devices.blocksizes();
+        let added_sector_sizes = block_size_summary
+            .keys()
+            .next()
+            .expect("one unowned device was specified");
+
+        let current_sector_sizes = self
+            .backstore
+            .block_size_summary(BlockDevTier::Data)
+            .expect("always exists")
+            .validate()
+            .expect("All operations prevented if validate() function on data tier block size summary returns an error");
+
+        if !(&current_sector_sizes.base == added_sector_sizes) {
+            let err_str = format!("The sector sizes of the device proposed as a replacement, {added_sector_sizes}, do not match the effective sector sizes of the existing data devices, {0}", current_sector_sizes.base);
+            return Err(StratisError::Msg(err_str));
+        }
+
+        let sector_size = if self.is_encrypted() {
+            Some(convert_int!(
+                *current_sector_sizes
+                    .crypt
+                    .expect("pool is encrypted")
+                    .logical_sector_size,
+                u128,
+                u32
+            )?)
+        } else {
+            None
+        };
+
+        let cached = self.cached();
+
+        let new_uuid = if self.backstore.redundancy() == Redundancy::None {
+            let new_uuid = self.backstore.start_replacement(
+                Name::new(pool_name.to_string()),
+                pool_uuid,
+                old,
+                unowned_devices,
+                sector_size,
+            )?;
+            self.write_metadata(pool_name)?;
+
+            // If no data was allocated from the old blockdev, the replacement
+            // is already complete.
+            self.finish_replacement(pool_uuid, pool_name)?;
+            new_uuid
+        } else {
+            let (new_uuid, replaced) = self.backstore.rebuild_replacement(
+                Name::new(pool_name.to_string()),
+                pool_uuid,
+                old,
+                unowned_devices,
+                sector_size,
+            )?;
+            self.write_metadata(pool_name)?;
+
+            // The pool metadata no longer refers to the replaced blockdev, so
+            // failing to wipe it does not affect the pool.
+            if let Some(replaced) = replaced {
+                if let Err(e) = wipe_blockdevs(&mut [replaced]) {
+                    warn!(
+                        "Failed to wipe Stratis metadata from blockdev replaced in pool with UUID {}: {}",
+                        pool_uuid, e
+                    );
+                }
+            }
+            new_uuid
+        };
+
+        Ok((
+            CreateAction::Created(new_uuid),
+            Some(PoolDiff {
+                thin_pool: self.thin_pool.cached().unchanged(),
+                pool: cached.diff(&self.dump(())),
+                blockdevs: HashMap::new(),
+            }),
+        ))
+    }
+
     #[pool_mutating_action("NoRequests")]
     fn destroy_filesystems(
         &mut self,
@@ -1039,6 +1692,7 @@ impl Pool for StratPool {
         pool_uuid: PoolUuid,
         origin_uuid: FilesystemUuid,
         snapshot_name: &str,
+        read_only: bool,
     ) -> StratisResult<CreateAction<(FilesystemUuid, &'a mut dyn Filesystem)>> {
         self.check_fs_limit(1)?;
 
@@ -1064,14 +1718,75 @@ impl Pool for StratPool {
         }
 
         self.thin_pool
-            .snapshot_filesystem(pool_name, pool_uuid, origin_uuid, snapshot_name)
+            .snapshot_filesystem(pool_name, pool_uuid, origin_uuid, snapshot_name, read_only)
             .map(|(uuid, fs)| CreateAction::Created((uuid, fs as &mut dyn Filesystem)))
     }
 
-    fn total_physical_size(&self) -> Sectors {
-        self.backstore.datatier_size()
-    }
-
+    #[pool_mutating_action("NoRequests")]
+    fn snapshot_filesystems<'b>(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        specs: &[(FilesystemUuid, &'b str)],
+    ) -> StratisResult<SetCreateAction<(&'b str, FilesystemUuid)>> {
+        validate_snapshot_specs(specs)?;
+        if specs.is_empty() {
+            return Ok(SetCreateAction::empty());
+        }
+        self.check_fs_limit(specs.len())?;
+
+        let mut increase = Sectors(0);
+        for (origin_uuid, snapshot_name) in specs {
+            if self
+                .thin_pool
+                .get_filesystem_by_name(snapshot_name)
+                .is_some()
+            {
+                return Err(StratisError::Msg(format!(
+                    "A filesystem named {snapshot_name} already exists"
+                )));
+            }
+            increase += self
+                .thin_pool
+                .get_filesystem_by_uuid(*origin_uuid)
+                .ok_or_else(|| {
+                    StratisError::Msg(format!(
+                        "Filesystem with UUID {origin_uuid} could not be found"
+                    ))
+                })?
+                .1
+                .thindev_size();
+        }
+        self.check_overprov(increase)?;
+
+        let uuids = self
+            .thin_pool
+            .snapshot_filesystems(pool_name, pool_uuid, specs)?;
+        Ok(SetCreateAction::new(
+            specs
+                .iter()
+                .zip(uuids)
+                .map(|((_, snapshot_name), uuid)| (*snapshot_name, uuid))
+                .collect(),
+        ))
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn revert_filesystem(
+        &mut self,
+        pool_name: &str,
+        origin_uuid: FilesystemUuid,
+        snapshot_uuid: FilesystemUuid,
+    ) -> StratisResult<(RevertAction, HashMap<FilesystemUuid, StratFilesystemDiff>)> {
+        self.thin_pool
+            .revert_filesystem(pool_name, origin_uuid, snapshot_uuid)
+            .map(|diffs| (RevertAction, diffs))
+    }
+
+    fn total_physical_size(&self) -> Sectors {
+        self.backstore.datatier_size()
+    }
+
     fn total_allocated_size(&self) -> Sectors {
         self.backstore.datatier_allocated_size() + self.metadata_size
     }
@@ -1150,6 +1865,24 @@ impl Pool for StratPool {
         self.backstore.has_cache()
     }
 
+    fn cache_settings(&self) -> Option<&CacheSettings> {
+        self.backstore.cache_settings()
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn set_cache_settings(
+        &mut self,
+        pool_name: &str,
+        settings: CacheSettings,
+    ) -> StratisResult<PropChangeAction<CacheSettings>> {
+        if self.backstore.set_cache_settings(settings.clone())? {
+            self.write_metadata(pool_name)?;
+            Ok(PropChangeAction::NewValue(settings))
+        } else {
+            Ok(PropChangeAction::Identity)
+        }
+    }
+
     fn is_encrypted(&self) -> bool {
         self.backstore.is_encrypted()
     }
@@ -1199,6 +1932,56 @@ impl Pool for StratPool {
         self.thin_pool.out_of_alloc_space()
     }
 
+    fn replacement_progress(&self) -> Option<u8> {
+        self.backstore.replacement_progress()
+    }
+
+    fn encryption_progress(&self) -> Option<u8> {
+        self.backstore.encryption_progress()
+    }
+
+    fn reencryption_progress(&self) -> Option<u8> {
+        self.backstore.reencryption_progress()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache_stats
+    }
+
+    fn redundancy(&self) -> Redundancy {
+        self.backstore.redundancy()
+    }
+
+    fn integrity(&self) -> Option<IntegrityHash> {
+        self.backstore.integrity()
+    }
+
+    fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    fn data_shrink(&self) -> Option<DataShrink> {
+        self.thin_pool.data_shrink()
+    }
+
+    fn allocation_policy(&self) -> AllocationPolicy {
+        self.backstore.allocation_policy()
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn set_allocation_policy(
+        &mut self,
+        pool_name: &str,
+        policy: AllocationPolicy,
+    ) -> StratisResult<PropChangeAction<AllocationPolicy>> {
+        if self.backstore.set_allocation_policy(policy)? {
+            self.write_metadata(pool_name)?;
+            Ok(PropChangeAction::NewValue(policy))
+        } else {
+            Ok(PropChangeAction::Identity)
+        }
+    }
+
     #[pool_mutating_action("NoRequests")]
     fn grow_physical(
         &mut self,
@@ -1218,6 +2001,7 @@ impl Pool for StratPool {
                 Some(PoolDiff {
                     thin_pool: self.thin_pool.cached().unchanged(),
                     pool: cached.diff(&self.dump(())),
+                    blockdevs: HashMap::new(),
                 }),
             ))
         } else {
@@ -1241,11 +2025,75 @@ impl Pool for StratPool {
             Ok(PropChangeAction::Identity)
         }
     }
+
+    #[pool_mutating_action("NoRequests")]
+    fn set_fs_read_only(
+        &mut self,
+        fs_uuid: FilesystemUuid,
+        read_only: bool,
+    ) -> StratisResult<PropChangeAction<bool>> {
+        if self.thin_pool.set_fs_read_only(fs_uuid, read_only)? {
+            Ok(PropChangeAction::NewValue(read_only))
+        } else {
+            Ok(PropChangeAction::Identity)
+        }
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn set_fs_snapshot_schedule(
+        &mut self,
+        fs_uuid: FilesystemUuid,
+        schedule: Option<SnapshotSchedule>,
+    ) -> StratisResult<PropChangeAction<Option<SnapshotSchedule>>> {
+        if schedule.is_some() {
+            if let Some((name, _)) = self.thin_pool.get_filesystem_by_uuid(fs_uuid) {
+                validate_scheduled_snapshot_name(&name)?;
+            }
+        }
+        if self.thin_pool.set_fs_snapshot_schedule(fs_uuid, schedule)? {
+            Ok(PropChangeAction::NewValue(schedule))
+        } else {
+            Ok(PropChangeAction::Identity)
+        }
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn run_snapshot_schedules(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        now: DateTime<Utc>,
+    ) -> StratisResult<SnapshotScheduleRun> {
+        let plans = scheduled_snapshot_plans(&Pool::filesystems(self), now);
+        let mut run = SnapshotScheduleRun::default();
+        for plan in plans {
+            let res = run_scheduled_snapshot_plan(self, pool_name, pool_uuid, &plan, &mut run);
+            if let Err(ref e) = res {
+                warn!(
+                    "Failed to run the snapshot schedule of filesystem with UUID {}: {}",
+                    plan.origin, e
+                );
+            }
+            self.thin_pool.set_fs_snapshot_schedule_status(
+                plan.origin,
+                SnapshotScheduleStatus {
+                    time: now,
+                    error: res.err().map(|e| e.to_string()),
+                },
+            );
+        }
+        Ok(run)
+    }
 }
 
 pub struct StratPoolState {
     metadata_size: Bytes,
     out_of_alloc_space: bool,
+    replacement_progress: Option<u8>,
+    encryption_progress: Option<u8>,
+    reencryption_progress: Option<u8>,
+    cache_stats: Option<CacheStats>,
+    degraded: bool,
 }
 
 impl StateDiff for StratPoolState {
@@ -1255,6 +2103,15 @@ impl StateDiff for StratPoolState {
         StratPoolDiff {
             metadata_size: self.metadata_size.compare(&other.metadata_size),
             out_of_alloc_space: self.out_of_alloc_space.compare(&other.out_of_alloc_space),
+            replacement_progress: self
+                .replacement_progress
+                .compare(&other.replacement_progress),
+            encryption_progress: self.encryption_progress.compare(&other.encryption_progress),
+            reencryption_progress: self
+                .reencryption_progress
+                .compare(&other.reencryption_progress),
+            cache_stats: self.cache_stats.compare(&other.cache_stats),
+            degraded: self.degraded.compare(&other.degraded),
         }
     }
 
@@ -1262,6 +2119,11 @@ impl StateDiff for StratPoolState {
         StratPoolDiff {
             metadata_size: Diff::Unchanged(self.metadata_size),
             out_of_alloc_space: Diff::Unchanged(self.out_of_alloc_space),
+            replacement_progress: Diff::Unchanged(self.replacement_progress),
+            encryption_progress: Diff::Unchanged(self.encryption_progress),
+            reencryption_progress: Diff::Unchanged(self.reencryption_progress),
+            cache_stats: Diff::Unchanged(self.cache_stats),
+            degraded: Diff::Unchanged(self.degraded),
         }
     }
 }
@@ -1274,14 +2136,26 @@ impl<'a> DumpState<'a> for StratPool {
         StratPoolState {
             metadata_size: self.metadata_size.bytes(),
             out_of_alloc_space: self.thin_pool.out_of_alloc_space(),
+            replacement_progress: self.backstore.replacement_progress(),
+            encryption_progress: self.backstore.encryption_progress(),
+            reencryption_progress: self.backstore.reencryption_progress(),
+            cache_stats: self.cache_stats,
+            degraded: self.degraded,
         }
     }
 
     fn dump(&mut self, _: Self::DumpInput) -> Self::State {
         self.metadata_size = self.backstore.datatier_metadata_size();
+        self.cache_stats = read_cache_stats(&self.backstore);
+        self.degraded = read_degraded(&self.backstore);
         StratPoolState {
             metadata_size: self.metadata_size.bytes(),
             out_of_alloc_space: self.thin_pool.out_of_alloc_space(),
+            replacement_progress: self.backstore.replacement_progress(),
+            encryption_progress: self.backstore.encryption_progress(),
+            reencryption_progress: self.backstore.reencryption_progress(),
+            cache_stats: self.cache_stats,
+            degraded: self.degraded,
         }
     }
 }
@@ -1289,8 +2163,11 @@ impl<'a> DumpState<'a> for StratPool {
 #[cfg(test)]
 mod tests {
     use std::{
+        collections::HashSet,
         fs::OpenOptions,
         io::{BufWriter, Read, Write},
+        thread::sleep,
+        time::Duration,
     };
 
     use nix::mount::{mount, umount, MsFlags};
@@ -1303,7 +2180,7 @@ mod tests {
             tests::{loopbacked, real},
             thinpool::ThinPoolStatusDigest,
         },
-        types::{EngineAction, PoolIdentifier},
+        types::{CacheMode, EngineAction, PoolIdentifier},
         Engine, StratEngine,
     };
 
@@ -1311,7 +2188,11 @@ mod tests {
 
     fn invariant(pool: &StratPool, pool_name: &str) {
         check_metadata(&pool.record(&Name::new(pool_name.into()))).unwrap();
-        assert!(!(pool.is_encrypted() && pool.backstore.has_cache()));
+        assert!(pool
+            .backstore
+            .blockdevs()
+            .iter()
+            .all(|(_, _, bd)| bd.encryption_info().is_some() == pool.is_encrypted()));
         if pool.avail_actions() == ActionAvailability::NoRequests {
             assert!(
                 pool.encryption_info().is_some()
@@ -1346,7 +2227,9 @@ mod tests {
         stratis_devices.error_on_not_empty().unwrap();
 
         let name = "stratis-test-pool";
-        let (uuid, mut pool) = StratPool::initialize(name, unowned_devices2, None).unwrap();
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices2, None, None, None, Redundancy::None)
+                .unwrap();
         invariant(&pool, name);
 
         let metadata1 = pool.record(name);
@@ -1385,7 +2268,8 @@ mod tests {
                 .unwrap();
         }
 
-        pool.init_cache(uuid, name, paths1, true).unwrap();
+        pool.init_cache(uuid, name, paths1, true, CacheSettings::default())
+            .unwrap();
         invariant(&pool, name);
 
         let metadata2 = pool.record(name);
@@ -1433,10 +2317,13 @@ mod tests {
         stratis_devices.error_on_not_empty().unwrap();
 
         let name = "stratis-test-pool";
-        let (uuid, mut pool) = StratPool::initialize(name, unowned_devices, None).unwrap();
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, None, None, None, Redundancy::None)
+                .unwrap();
         invariant(&pool, name);
 
-        pool.init_cache(uuid, name, cache_path, true).unwrap();
+        pool.init_cache(uuid, name, cache_path, true, CacheSettings::default())
+            .unwrap();
         invariant(&pool, name);
 
         pool.add_blockdevs(uuid, name, data_paths, BlockDevTier::Data)
@@ -1461,6 +2348,200 @@ mod tests {
         );
     }
 
+    /// Verify that the cache settings given when initializing the cache are
+    /// applied and recorded, and that they can be changed afterwards.
+    fn test_cache_settings(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let (cache_paths, data_paths) = paths.split_at(paths.len() / 2);
+
+        let devices = ProcessedPathInfos::try_from(data_paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let name = "stratis-test-pool";
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, None, None, None, Redundancy::None)
+                .unwrap();
+        invariant(&pool, name);
+
+        assert!(pool
+            .set_cache_settings(name, CacheSettings::default())
+            .is_err());
+
+        pool.create_filesystems(name, uuid, &[("stratis-filesystem", None, None)])
+            .unwrap();
+
+        let writeback = CacheSettings::new(
+            CacheMode::Writeback,
+            "smq".to_string(),
+            vec![("migration_threshold".to_string(), "4096".to_string())],
+        )
+        .unwrap();
+        pool.init_cache(uuid, name, cache_paths, true, writeback.clone())
+            .unwrap();
+        invariant(&pool, name);
+
+        assert_eq!(pool.cache_settings(), Some(&writeback));
+        assert_eq!(
+            pool.record(name)
+                .backstore
+                .cache_tier
+                .and_then(|ct| ct.settings),
+            Some(writeback.clone())
+        );
+
+        assert_matches!(
+            pool.set_cache_settings(name, writeback),
+            Ok(PropChangeAction::Identity)
+        );
+
+        let writethrough = CacheSettings::default();
+        assert_matches!(
+            pool.set_cache_settings(name, writethrough.clone()),
+            Ok(PropChangeAction::NewValue(_))
+        );
+        invariant(&pool, name);
+
+        assert_eq!(pool.cache_settings(), Some(&writethrough));
+        assert_eq!(
+            pool.record(name)
+                .backstore
+                .cache_tier
+                .and_then(|ct| ct.settings),
+            Some(writethrough)
+        );
+
+        pool.teardown(uuid).unwrap();
+    }
+
+    #[test]
+    fn loop_test_cache_settings() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_cache_settings,
+        );
+    }
+
+    #[test]
+    fn real_test_cache_settings() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_cache_settings,
+        );
+    }
+
+    /// Verify that removing the cache leaves data written through the cache
+    /// intact, removes the cache tier from the metadata, and releases the
+    /// cachedevs.
+    fn test_remove_cache(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let (cache_paths, data_paths) = paths.split_at(paths.len() / 2);
+
+        let devices = ProcessedPathInfos::try_from(data_paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let name = "stratis-test-pool";
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, None, None, None, Redundancy::None)
+                .unwrap();
+        invariant(&pool, name);
+
+        assert!(!pool.remove_cache(uuid, name).unwrap().is_changed());
+
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(name, uuid, &[("stratis-filesystem", None, None)])
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .unwrap();
+        invariant(&pool, name);
+
+        let cache_uuids = pool
+            .init_cache(uuid, name, cache_paths, true, CacheSettings::default())
+            .unwrap()
+            .changed()
+            .unwrap();
+        invariant(&pool, name);
+        assert!(pool.cache_stats().is_some());
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let new_file = tmp_dir.path().join("stratis_test.txt");
+        let bytestring = b"some bytes";
+        {
+            let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
+            mount(
+                Some(&fs.devnode()),
+                tmp_dir.path(),
+                Some("xfs"),
+                MsFlags::empty(),
+                None as Option<&str>,
+            )
+            .unwrap();
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(&new_file)
+                .unwrap()
+                .write_all(bytestring)
+                .unwrap();
+        }
+
+        assert!(pool.start_cache_flush().unwrap());
+        while pool.cache_dirty_blocks().unwrap() != Some(0) {
+            sleep(Duration::from_millis(100));
+        }
+        let removed = pool.remove_cache(uuid, name).unwrap().changed().unwrap();
+        invariant(&pool, name);
+        assert_eq!(pool.cache_stats(), None);
+
+        assert_eq!(
+            removed.iter().collect::<HashSet<_>>(),
+            cache_uuids.iter().collect::<HashSet<_>>()
+        );
+        assert!(!pool.has_cache());
+        assert_matches!(pool.record(name).backstore.cache_tier, None);
+
+        let mut buf = [0u8; 10];
+        {
+            OpenOptions::new()
+                .read(true)
+                .open(&new_file)
+                .unwrap()
+                .read_exact(&mut buf)
+                .unwrap();
+        }
+        assert_eq!(&buf, bytestring);
+        umount(tmp_dir.path()).unwrap();
+
+        let devices = ProcessedPathInfos::try_from(cache_paths).unwrap();
+        let (stratis_devices, _) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        pool.teardown(uuid).unwrap();
+    }
+
+    #[test]
+    fn loop_test_remove_cache() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_remove_cache,
+        );
+    }
+
+    #[test]
+    fn real_test_remove_cache() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_remove_cache,
+        );
+    }
+
     /// Verify that adding additional blockdevs will cause a pool that is
     /// out of space to be extended.
     fn test_add_datadevs(paths: &[&Path]) {
@@ -1473,7 +2554,9 @@ mod tests {
         stratis_devices.error_on_not_empty().unwrap();
 
         let name = "stratis-test-pool";
-        let (pool_uuid, mut pool) = StratPool::initialize(name, unowned_devices1, None).unwrap();
+        let (pool_uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices1, None, None, None, Redundancy::None)
+                .unwrap();
         invariant(&pool, name);
 
         let fs_name = "stratis_test_filesystem";
@@ -1546,6 +2629,293 @@ mod tests {
         );
     }
 
+    /// Verify that removing a data blockdev from a pool with a mounted
+    /// filesystem moves the data allocated from the blockdev to the
+    /// remaining blockdevs in the background and wipes the removed blockdev
+    /// once the data has been copied.
+    fn test_remove_datadevs(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let (paths1, paths2) = paths.split_at(1);
+
+        let devices1 = ProcessedPathInfos::try_from(paths1).unwrap();
+        let (stratis_devices, unowned_devices1) = devices1.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let name = "stratis-test-pool";
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices1, None, None, None, Redundancy::None)
+                .unwrap();
+        invariant(&pool, name);
+
+        let to_remove = pool
+            .backstore
+            .datadevs()
+            .iter()
+            .map(|(uuid, _)| *uuid)
+            .collect::<Vec<_>>();
+
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(name, uuid, &[("stratis-filesystem", None, None)])
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .unwrap();
+        invariant(&pool, name);
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let new_file = tmp_dir.path().join("stratis_test.txt");
+        let bytestring = b"some bytes";
+        {
+            let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
+            mount(
+                Some(&fs.devnode()),
+                tmp_dir.path(),
+                Some("xfs"),
+                MsFlags::empty(),
+                None as Option<&str>,
+            )
+            .unwrap();
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(&new_file)
+                .unwrap()
+                .write_all(bytestring)
+                .unwrap();
+        }
+
+        pool.add_blockdevs(uuid, name, paths2, BlockDevTier::Data)
+            .unwrap();
+        invariant(&pool, name);
+
+        let (removed, _) = pool.remove_blockdevs(uuid, name, &to_remove).unwrap();
+        assert_eq!(removed.changed(), Some(to_remove.clone()));
+        invariant(&pool, name);
+
+        let pool_name = Name::new(name.to_string());
+        while pool.replacement_progress().is_some() {
+            sleep(Duration::from_millis(100));
+            pool.event_on(uuid, &pool_name).unwrap();
+        }
+        invariant(&pool, name);
+
+        assert!(pool
+            .backstore
+            .datadevs()
+            .iter()
+            .all(|(uuid, _)| !to_remove.contains(uuid)));
+        assert!(pool.record(name).backstore.data_tier.blockdev.allocs[0]
+            .iter()
+            .all(|seg| !to_remove.contains(&seg.parent)));
+
+        let mut buf = [0u8; 10];
+        {
+            OpenOptions::new()
+                .read(true)
+                .open(&new_file)
+                .unwrap()
+                .read_exact(&mut buf)
+                .unwrap();
+        }
+        assert_eq!(&buf, bytestring);
+        umount(tmp_dir.path()).unwrap();
+
+        udev_settle().unwrap();
+        let (stratis_devices, _) = ProcessedPathInfos::try_from(paths1).unwrap().unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let remaining = pool
+            .backstore
+            .datadevs()
+            .iter()
+            .map(|(uuid, _)| *uuid)
+            .collect::<Vec<_>>();
+        assert_matches!(pool.remove_blockdevs(uuid, name, &remaining), Err(_));
+
+        pool.teardown(uuid).unwrap();
+    }
+
+    #[test]
+    fn loop_test_remove_datadevs() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_remove_datadevs,
+        );
+    }
+
+    #[test]
+    fn real_test_remove_datadevs() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_remove_datadevs,
+        );
+    }
+
+    /// Verify that a data blockdev can be replaced while a filesystem on the
+    /// pool is mounted, that the data is intact after the replacement, and
+    /// that the replaced blockdev is no longer owned by Stratis.
+    fn test_replace_datadev(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let (paths1, paths2) = paths.split_at(1);
+
+        let devices1 = ProcessedPathInfos::try_from(paths1).unwrap();
+        let (stratis_devices, unowned_devices1) = devices1.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let name = "stratis-test-pool";
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices1, None, None, None, Redundancy::None)
+                .unwrap();
+        invariant(&pool, name);
+
+        let old = pool.backstore.datadevs()[0].0;
+
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(name, uuid, &[("stratis-filesystem", None, None)])
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .unwrap();
+        invariant(&pool, name);
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let new_file = tmp_dir.path().join("stratis_test.txt");
+        let bytestring = b"some bytes";
+        {
+            let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
+            mount(
+                Some(&fs.devnode()),
+                tmp_dir.path(),
+                Some("xfs"),
+                MsFlags::empty(),
+                None as Option<&str>,
+            )
+            .unwrap();
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(&new_file)
+                .unwrap()
+                .write_all(bytestring)
+                .unwrap();
+        }
+
+        let (action, _) = pool.replace_blockdev(uuid, name, old, paths2[0]).unwrap();
+        let new = action.changed().unwrap();
+        assert_matches!(pool.replace_blockdev(uuid, name, new, paths2[0]), Err(_));
+
+        let pool_name = Name::new(name.to_string());
+        while pool.replacement_progress().is_some() {
+            sleep(Duration::from_millis(100));
+            pool.event_on(uuid, &pool_name).unwrap();
+        }
+        invariant(&pool, name);
+
+        assert_eq!(
+            pool.backstore
+                .datadevs()
+                .iter()
+                .map(|(uuid, _)| *uuid)
+                .collect::<Vec<_>>(),
+            vec![new]
+        );
+        assert!(pool.record(name).backstore.data_tier.blockdev.allocs[0]
+            .iter()
+            .all(|seg| seg.parent == new));
+
+        let mut buf = [0u8; 10];
+        {
+            OpenOptions::new()
+                .read(true)
+                .open(&new_file)
+                .unwrap()
+                .read_exact(&mut buf)
+                .unwrap();
+        }
+        assert_eq!(&buf, bytestring);
+        umount(tmp_dir.path()).unwrap();
+
+        udev_settle().unwrap();
+        let (stratis_devices, _) = ProcessedPathInfos::try_from(paths1).unwrap().unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        pool.teardown(uuid).unwrap();
+    }
+
+    #[test]
+    fn loop_test_replace_datadev() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(2, None),
+            test_replace_datadev,
+        );
+    }
+
+    #[test]
+    fn real_test_replace_datadev() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_replace_datadev,
+        );
+    }
+
+    /// Test that a pool with redundancy raid1 keeps a copy of its data on
+    /// distinct datadevs, records its redundancy, and is reported degraded
+    /// once a datadev disappears.
+    fn test_raid1(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let name = "stratis-test-pool";
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, None, None, None, Redundancy::Raid1)
+                .unwrap();
+        invariant(&pool, name);
+
+        assert_eq!(pool.redundancy(), Redundancy::Raid1);
+        assert_eq!(
+            pool.record(name).backstore.redundancy,
+            Some(Redundancy::Raid1)
+        );
+
+        pool.create_filesystems(name, uuid, &[("stratis_test_filesystem", None, None)])
+            .unwrap();
+        invariant(&pool, name);
+
+        pool.dump(());
+        assert!(!pool.is_degraded());
+
+        let (dev_uuid, _) = pool.backstore.datadevs()[0];
+        assert!(pool.mark_datadev_missing(dev_uuid));
+        assert!(!pool.mark_datadev_missing(dev_uuid));
+        let cached = pool.cached();
+        assert!(cached.diff(&pool.dump(())).degraded.is_changed());
+        assert!(pool.is_degraded());
+
+        pool.destroy(uuid, None).unwrap();
+    }
+
+    #[test]
+    fn loop_test_raid1() {
+        loopbacked::test_with_spec(&loopbacked::DeviceLimits::Range(2, 3, None), test_raid1);
+    }
+
+    #[test]
+    fn real_test_raid1() {
+        real::test_with_spec(&real::DeviceLimits::AtLeast(2, None, None), test_raid1);
+    }
+
     /// Test that rollback errors are properly detected an maintenance mode
     /// is set accordingly.
     fn test_maintenance_mode(paths: &[&Path]) {
@@ -1557,14 +2927,16 @@ mod tests {
         let (stratis_devices, unowned_devices) = devices.unpack();
         stratis_devices.error_on_not_empty().unwrap();
 
-        let (uuid, mut pool) = StratPool::initialize(name, unowned_devices, None).unwrap();
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, None, None, None, Redundancy::None)
+                .unwrap();
         invariant(&pool, name);
 
         assert_eq!(pool.action_avail, ActionAvailability::Full);
         assert!(pool.return_rollback_failure().is_err());
         assert_eq!(pool.action_avail, ActionAvailability::NoRequests);
 
-        pool.destroy(uuid).unwrap();
+        pool.destroy(uuid, None).unwrap();
         udev_settle().unwrap();
 
         let name = "stratis-test-pool";
@@ -1573,7 +2945,9 @@ mod tests {
         let (stratis_devices, unowned_devices) = devices.unpack();
         stratis_devices.error_on_not_empty().unwrap();
 
-        let (_, mut pool) = StratPool::initialize(name, unowned_devices, None).unwrap();
+        let (_, mut pool) =
+            StratPool::initialize(name, unowned_devices, None, None, None, Redundancy::None)
+                .unwrap();
         invariant(&pool, name);
 
         assert_eq!(pool.action_avail, ActionAvailability::Full);
@@ -1612,8 +2986,15 @@ mod tests {
         let (stratis_devices, unowned_devices) = devices.unpack();
         stratis_devices.error_on_not_empty().unwrap();
 
-        let (pool_uuid, mut pool) =
-            StratPool::initialize(pool_name, unowned_devices, None).unwrap();
+        let (pool_uuid, mut pool) = StratPool::initialize(
+            pool_name,
+            unowned_devices,
+            None,
+            None,
+            None,
+            Redundancy::None,
+        )
+        .unwrap();
 
         let (_, fs_uuid, _) = pool
             .create_filesystems(
@@ -1721,10 +3102,11 @@ mod tests {
     fn test_grow_physical_pre_grow(paths: &[&Path]) {
         let pool_name = Name::new("pool".to_string());
         let engine = StratEngine::initialize().unwrap();
-        let pool_uuid = test_async!(engine.create_pool(&pool_name, paths, None))
-            .unwrap()
-            .changed()
-            .unwrap();
+        let pool_uuid =
+            test_async!(engine.create_pool(&pool_name, paths, Redundancy::None, None, None, None))
+                .unwrap()
+                .changed()
+                .unwrap();
         let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(pool_uuid))).unwrap();
         let (_, _, pool) = guard.as_mut_tuple();
 
@@ -16,7 +16,10 @@ use serde::Serialize;
 
 use devicemapper::{Sectors, ThinDevId};
 
-use crate::engine::types::{DevUuid, FilesystemUuid};
+use crate::engine::types::{
+    AllocationPolicy, CacheSettings, CryptParams, DevUuid, FilesystemUuid, IntegrityHash,
+    Redundancy, SnapshotSchedule,
+};
 
 /// Implements saving struct data to a serializable form. The form should be
 /// sufficient, in conjunction with the environment, to reconstruct the
@@ -46,11 +49,56 @@ pub struct BackstoreSave {
     pub cap: CapSave,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cache_tier: Option<CacheTierSave>,
+    // TODO: This data type should no longer be optional in Stratis 4.0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redundancy: Option<Redundancy>,
+    // TODO: This data type should no longer be optional in Stratis 4.0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allocation_policy: Option<AllocationPolicy>,
+    /// The blockdevs that remain to be reencrypted with new volume keys,
+    /// beginning with the one that is being reencrypted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reencryption: Vec<DevUuid>,
+    /// The crypt parameters with which the blockdevs that remain to be
+    /// reencrypted are reencrypted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reencryption_params: Option<CryptParams>,
+    /// The key derivation parameters with which the keyslots of the
+    /// blockdevs of an encrypted pool are created, if specified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pbkdf_params: Option<CryptParams>,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct DataTierSave {
     pub blockdev: BlockDevSave,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stripes: Vec<StripeSave>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<ReplacementSave>,
+}
+
+/// A replacement or removal of blockdevs in the data tier that is in
+/// progress. The space reserved for the data is not recorded; the data
+/// allocated from old is copied again, to new if specified, otherwise to the
+/// remaining blockdevs, when the pool is next set up.
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ReplacementSave {
+    pub old: Vec<DevUuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<DevUuid>,
+}
+
+/// An extent of the data tier that is striped across blockdevs. The extent
+/// begins at start in the concatenation of the segments allocated to the
+/// cap device; the next length sectors of that concatenation are made up
+/// of stripes contiguous pieces of equal size, one on each blockdev.
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct StripeSave {
+    pub start: Sectors,
+    pub length: Sectors,
+    pub stripes: usize,
+    pub stripe_size: Sectors,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -66,13 +114,28 @@ pub struct BaseDevSave {
     pub length: Sectors,
 }
 
-#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct BaseBlockDevSave {
     pub uuid: DevUuid,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user_info: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hardware_info: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<IntegritySave>,
+}
+
+/// The dm-integrity layer of a blockdev. The tags are kept in the sectors
+/// [meta_start, meta_start + meta_length) of the blockdev; all sectors
+/// before meta_start are checked. On an encrypted blockdev, the layer lies
+/// beneath the encryption, the sectors are those of the physical device,
+/// and the layer is recorded in the Stratis LUKS2 token rather than in the
+/// pool-level metadata.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct IntegritySave {
+    pub hash: IntegrityHash,
+    pub meta_start: Sectors,
+    pub meta_length: Sectors,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -83,6 +146,9 @@ pub struct CapSave {
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct CacheTierSave {
     pub blockdev: BlockDevSave,
+    // TODO: This data type should no longer be optional in Stratis 4.0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings: Option<CacheSettings>,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -105,6 +171,10 @@ pub struct ThinPoolDevSave {
     // TODO: This data type should no longer be optional in Stratis 4.0
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enable_overprov: Option<bool>,
+    /// The size to which the thin data device is to be shrunk when the pool
+    /// is next set up
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_shrink: Option<Sectors>,
 }
 
 // Struct representing filesystem metadata. This metadata is not held in the
@@ -119,4 +189,13 @@ pub struct FilesystemSave {
     pub created: u64, // Unix timestamp
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fs_size_limit: Option<Sectors>,
+    /// The filesystem of which this filesystem was created as a snapshot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<FilesystemUuid>,
+    /// The schedule by which snapshots of this filesystem are taken
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_schedule: Option<SnapshotSchedule>,
+    /// Whether the thin device of the filesystem is activated read-only
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
 }
@@ -6,17 +6,20 @@ use std::{
     cmp::min,
     fs::{File, OpenOptions},
     io::{Read, Write},
+    mem::swap,
+    os::unix::io::AsRawFd,
     path::{Path, PathBuf},
 };
 
 use chrono::{DateTime, Utc};
 use data_encoding::BASE32_NOPAD;
+use libc::c_int;
 use retry::{delay::Fixed, retry_with_index};
 use serde_json::{Map, Value};
 
 use devicemapper::{
-    Bytes, DevId, DmDevice, DmName, DmOptions, DmUuid, Sectors, ThinDev, ThinDevId, ThinPoolDev,
-    ThinStatus,
+    Bytes, DevId, DmDevice, DmFlags, DmName, DmOptions, DmUuid, Sectors, TargetTable, ThinDev,
+    ThinDevId, ThinPoolDev, ThinStatus,
 };
 
 use nix::{
@@ -36,8 +39,8 @@ use crate::{
             serde_structs::FilesystemSave,
         },
         types::{
-            ActionAvailability, Compare, Diff, FilesystemUuid, Name, PoolUuid, StratFilesystemDiff,
-            StratisUuid,
+            ActionAvailability, Compare, Diff, FilesystemSpaceUsage, FilesystemUuid, Name,
+            PoolUuid, SnapshotSchedule, SnapshotScheduleStatus, StratFilesystemDiff, StratisUuid,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -45,12 +48,21 @@ use crate::{
 
 const TEMP_MNT_POINT_PREFIX: &str = "stratis_mp_";
 
+// The argument of FIFREEZE and FITHAW is ignored by the kernel.
+ioctl_readwrite!(fifreeze, b'X', 119, c_int);
+ioctl_readwrite!(fithaw, b'X', 120, c_int);
+
 #[derive(Debug)]
 pub struct StratFilesystem {
     thin_dev: ThinDev,
     created: DateTime<Utc>,
     used: Option<Bytes>,
     size_limit: Option<Sectors>,
+    origin: Option<FilesystemUuid>,
+    snapshot_schedule: Option<SnapshotSchedule>,
+    snapshot_schedule_status: Option<SnapshotScheduleStatus>,
+    read_only: bool,
+    space_usage: Option<FilesystemSpaceUsage>,
 }
 
 fn init_used(thin_dev: &ThinDev) -> Option<Bytes> {
@@ -114,6 +126,11 @@ impl StratFilesystem {
                 thin_dev,
                 created: Utc::now(),
                 size_limit,
+                origin: None,
+                snapshot_schedule: None,
+                snapshot_schedule_status: None,
+                read_only: false,
+                space_usage: None,
             },
         ))
     }
@@ -135,11 +152,19 @@ impl StratFilesystem {
             thinpool_dev,
             fssave.thin_id,
         )?;
+        if fssave.read_only {
+            load_thin_table(&thin_dev, true)?;
+        }
         Ok(StratFilesystem {
             used: init_used(&thin_dev),
             thin_dev,
             created,
             size_limit: fssave.fs_size_limit,
+            origin: fssave.origin,
+            snapshot_schedule: fssave.snapshot_schedule,
+            snapshot_schedule_status: None,
+            read_only: fssave.read_only,
+            space_usage: None,
         })
     }
 
@@ -190,16 +215,20 @@ impl StratFilesystem {
     ///
     /// As of the introduction of filesystem size limits, snapshots inherit the origin size limit
     /// but the limit can be changed or removed through the API.
+    ///
+    /// The snapshot records origin_uuid, the UUID of this filesystem, as its origin.
     #[allow(clippy::too_many_arguments)]
     pub fn snapshot(
         &self,
         thin_pool: &ThinPoolDev,
+        origin_uuid: FilesystemUuid,
         snapshot_name: &str,
         snapshot_dm_name: &DmName,
         snapshot_dm_uuid: Option<&DmUuid>,
         snapshot_fs_name: &Name,
         snapshot_fs_uuid: FilesystemUuid,
         snapshot_thin_id: ThinDevId,
+        read_only: bool,
     ) -> StratisResult<StratFilesystem> {
         match self.thin_dev.snapshot(
             get_dm(),
@@ -208,44 +237,24 @@ impl StratFilesystem {
             thin_pool,
             snapshot_thin_id,
         ) {
-            Ok(thin_dev) => {
-                // If the source is mounted, XFS puts a dummy record in the
-                // log to enforce replay of the snapshot to deal with any
-                // orphaned inodes. The dummy record put the log in a dirty
-                // state. xfs_admin won't allow a filesystem UUID
-                // to be updated when the log is dirty.  To clear the log
-                // we mount/unmount the filesystem before updating the UUID.
-                //
-                // If the source is unmounted the XFS log will be clean so
-                // we can skip the mount/unmount.
-                if !self.mount_points()?.is_empty() {
-                    let tmp_dir = tempfile::Builder::new()
-                        .prefix(TEMP_MNT_POINT_PREFIX)
-                        .tempdir()?;
-                    // Mount the snapshot with the "nouuid" option. mount
-                    // will fail due to duplicate UUID otherwise.
-                    mount(
-                        Some(&thin_dev.devnode()),
-                        tmp_dir.path(),
-                        Some("xfs"),
-                        MsFlags::empty(),
-                        Some("nouuid"),
-                    )?;
-                    if let Err(e) = retry_with_index(Fixed::from_millis(100).take(2), |i| {
-                        trace!("Unmount temporary snapshot mount attempt {}", i);
-                        umount(tmp_dir.path())
-                    }) {
-                        warn!("Unmounting temporary snapshot mount failed: {}", e);
+            Ok(mut thin_dev) => {
+                self.prepare_snapshot(&thin_dev, snapshot_fs_uuid)?;
+                // The UUID of the snapshot must be set before its thin
+                // device is made read-only.
+                if read_only {
+                    if let Err(err) = load_thin_table(&thin_dev, true) {
+                        // Nothing refers to the new thin device yet, so it is
+                        // deleted from the thin pool as well.
+                        if let Err(e) = thin_dev.destroy(get_dm(), thin_pool) {
+                            warn!(
+                                "Failed to tear down snapshot thin device {}: {}",
+                                snapshot_dm_name, e
+                            );
+                        }
+                        return Err(err);
                     }
                 }
-
-                set_uuid(&thin_dev.devnode(), snapshot_fs_uuid)?;
-                Ok(StratFilesystem {
-                    used: init_used(&thin_dev),
-                    thin_dev,
-                    created: Utc::now(),
-                    size_limit: self.size_limit,
-                })
+                Ok(self.snapshot_of(origin_uuid, thin_dev, read_only))
             }
             Err(e) => Err(StratisError::Msg(format!(
                 "failed to create {snapshot_name} snapshot for {snapshot_fs_name} - {e}"
@@ -253,6 +262,143 @@ impl StratFilesystem {
         }
     }
 
+    /// Set up the thin device with id snapshot_thin_id, which was created in
+    /// the thin pool as a snapshot of this filesystem, and return the
+    /// snapshot filesystem. If the snapshot can not be prepared, the thin
+    /// device is torn down again, but the thin id is not deleted.
+    #[allow(clippy::too_many_arguments)]
+    pub fn setup_snapshot(
+        &self,
+        thin_pool: &ThinPoolDev,
+        origin_uuid: FilesystemUuid,
+        snapshot_dm_name: &DmName,
+        snapshot_dm_uuid: Option<&DmUuid>,
+        snapshot_fs_uuid: FilesystemUuid,
+        snapshot_thin_id: ThinDevId,
+    ) -> StratisResult<StratFilesystem> {
+        let mut thin_dev = ThinDev::setup(
+            get_dm(),
+            snapshot_dm_name,
+            snapshot_dm_uuid,
+            self.thin_dev.size(),
+            thin_pool,
+            snapshot_thin_id,
+        )?;
+        if let Err(err) = self.prepare_snapshot(&thin_dev, snapshot_fs_uuid) {
+            if let Err(e) = thin_dev.teardown(get_dm()) {
+                warn!(
+                    "Failed to tear down snapshot thin device {}: {}",
+                    snapshot_dm_name, e
+                );
+            }
+            return Err(err);
+        }
+        Ok(self.snapshot_of(origin_uuid, thin_dev, false))
+    }
+
+    /// Give the XFS filesystem on thin_dev, a new snapshot of this
+    /// filesystem, the UUID snapshot_fs_uuid.
+    fn prepare_snapshot(
+        &self,
+        thin_dev: &ThinDev,
+        snapshot_fs_uuid: FilesystemUuid,
+    ) -> StratisResult<()> {
+        // If the source is mounted, XFS puts a dummy record in the
+        // log to enforce replay of the snapshot to deal with any
+        // orphaned inodes. The dummy record put the log in a dirty
+        // state. xfs_admin won't allow a filesystem UUID
+        // to be updated when the log is dirty.  To clear the log
+        // we mount/unmount the filesystem before updating the UUID.
+        //
+        // If the source is unmounted the XFS log will be clean so
+        // we can skip the mount/unmount.
+        if !self.mount_points()?.is_empty() {
+            let tmp_dir = tempfile::Builder::new()
+                .prefix(TEMP_MNT_POINT_PREFIX)
+                .tempdir()?;
+            // Mount the snapshot with the "nouuid" option. mount
+            // will fail due to duplicate UUID otherwise.
+            mount(
+                Some(&thin_dev.devnode()),
+                tmp_dir.path(),
+                Some("xfs"),
+                MsFlags::empty(),
+                Some("nouuid"),
+            )?;
+            if let Err(e) = retry_with_index(Fixed::from_millis(100).take(2), |i| {
+                trace!("Unmount temporary snapshot mount attempt {}", i);
+                umount(tmp_dir.path())
+            }) {
+                warn!("Unmounting temporary snapshot mount failed: {}", e);
+            }
+        }
+
+        set_uuid(&thin_dev.devnode(), snapshot_fs_uuid)?;
+        Ok(())
+    }
+
+    /// The snapshot filesystem of this filesystem on thin_dev.
+    fn snapshot_of(
+        &self,
+        origin_uuid: FilesystemUuid,
+        thin_dev: ThinDev,
+        read_only: bool,
+    ) -> StratFilesystem {
+        StratFilesystem {
+            used: init_used(&thin_dev),
+            thin_dev,
+            created: Utc::now(),
+            size_limit: self.size_limit,
+            origin: Some(origin_uuid),
+            snapshot_schedule: None,
+            snapshot_schedule_status: None,
+            read_only,
+            space_usage: None,
+        }
+    }
+
+    /// Freeze the filesystem if it is mounted and suspend its thin device,
+    /// so that the contents of the thin device do not change until resume()
+    /// is called. Returns the mount point at which the filesystem was
+    /// frozen, if any.
+    pub fn suspend(&mut self) -> StratisResult<Option<PathBuf>> {
+        let frozen = match self.mount_points()?.into_iter().next() {
+            Some(mount_point) => {
+                fs_freeze(&mount_point)?;
+                Some(mount_point)
+            }
+            None => None,
+        };
+        // The filesystem is already frozen, so device-mapper must not
+        // attempt to freeze it again.
+        if let Err(err) = self.thin_dev.suspend(
+            get_dm(),
+            DmOptions::default().set_flags(DmFlags::DM_SKIP_LOCKFS),
+        ) {
+            if let Some(ref mount_point) = frozen {
+                if let Err(e) = fs_thaw(mount_point) {
+                    warn!(
+                        "Failed to thaw filesystem mounted at {}: {}",
+                        mount_point.display(),
+                        e
+                    );
+                }
+            }
+            return Err(StratisError::from(err));
+        }
+        Ok(frozen)
+    }
+
+    /// Resume the thin device of the filesystem and thaw the filesystem if it
+    /// was frozen at frozen by suspend().
+    pub fn resume(&mut self, frozen: Option<&Path>) -> StratisResult<()> {
+        let res = self.thin_dev.resume(get_dm()).map_err(StratisError::from);
+        match frozen {
+            Some(mount_point) => res.and(fs_thaw(mount_point)),
+            None => res,
+        }
+    }
+
     /// Check the filesystem usage and determine whether it should extend.
     ///
     /// Returns:
@@ -281,6 +427,11 @@ impl StratFilesystem {
             }
         }
 
+        // A read-only filesystem can not be grown.
+        if self.read_only {
+            return None;
+        }
+
         match should_extend_fail(self) {
             Ok(mt_pt) => mt_pt,
             Err(e) => {
@@ -366,9 +517,111 @@ impl StratFilesystem {
             size: self.thin_dev.size(),
             created: self.created.timestamp() as u64,
             fs_size_limit: self.size_limit,
+            origin: self.origin,
+            snapshot_schedule: self.snapshot_schedule,
+            read_only: self.read_only,
         }
     }
 
+    /// Exchange the thin devices of this filesystem and other, so that each
+    /// filesystem presents the contents that the other one presented before.
+    /// The XFS UUID of each thin device is then set to the UUID of the
+    /// filesystem that now owns it, so that each filesystem keeps its UUID.
+    /// The DM devices, and therefore the devlinks, stay with the filesystems.
+    ///
+    /// Precondition: neither filesystem is mounted.
+    pub fn swap_thin_devs(
+        &mut self,
+        uuid: FilesystemUuid,
+        other: &mut StratFilesystem,
+        other_uuid: FilesystemUuid,
+    ) -> StratisResult<()> {
+        for fs in [&*self, &*other] {
+            if !fs.mount_points()?.is_empty() {
+                return Err(StratisError::Msg(format!(
+                    "Filesystem with device {} is mounted; both filesystems must be unmounted",
+                    fs.thin_dev.devnode().display()
+                )));
+            }
+        }
+        for (fs, new_size) in [
+            (&*self, other.thindev_size()),
+            (&*other, self.thindev_size()),
+        ] {
+            if let Some(limit) = fs.size_limit {
+                if new_size > limit {
+                    return Err(StratisError::Msg(format!(
+                        "Size {new_size} of the exchanged thin device exceeds the filesystem size limit {limit}"
+                    )));
+                }
+            }
+        }
+
+        let table = self.thin_dev.table().table.clone();
+        let other_table = other.thin_dev.table().table.clone();
+
+        let mut new_table = table.clone();
+        new_table.length = other_table.length;
+        new_table.params.thin_id = other_table.params.thin_id;
+        let mut new_other_table = other_table.clone();
+        new_other_table.length = table.length;
+        new_other_table.params.thin_id = table.params.thin_id;
+
+        self.thin_dev.set_table(get_dm(), new_table)?;
+        if let Err(causal) = other.thin_dev.set_table(get_dm(), new_other_table) {
+            if let Err(rollback) = self.thin_dev.set_table(get_dm(), table) {
+                return Err(StratisError::RollbackError {
+                    causal_error: Box::new(StratisError::from(causal)),
+                    rollback_error: Box::new(StratisError::from(rollback)),
+                    level: ActionAvailability::NoPoolChanges,
+                });
+            } else {
+                return Err(StratisError::from(causal));
+            }
+        }
+        swap(&mut self.space_usage, &mut other.space_usage);
+
+        // If the UUID of only the first filesystem could be set, it must be
+        // reset to its previous value before the tables are exchanged back.
+        let uuid_res = match set_uuid(&self.thin_dev.devnode(), uuid) {
+            Ok(()) => set_uuid(&other.thin_dev.devnode(), other_uuid).map_err(|e| (e, true)),
+            Err(e) => Err((e, false)),
+        };
+        if let Err((causal, uuid_set)) = uuid_res {
+            swap(&mut self.space_usage, &mut other.space_usage);
+            let rollback = if uuid_set {
+                set_uuid(&self.thin_dev.devnode(), other_uuid)
+            } else {
+                Ok(())
+            }
+            .and_then(|_| Ok(self.thin_dev.set_table(get_dm(), table)?))
+            .and_then(|_| Ok(other.thin_dev.set_table(get_dm(), other_table)?))
+            .and_then(|_| {
+                [&*self, &*other]
+                    .into_iter()
+                    .filter(|fs| fs.read_only)
+                    .try_for_each(|fs| load_thin_table(&fs.thin_dev, true))
+            });
+            return match rollback {
+                Ok(()) => Err(causal),
+                Err(rollback) => Err(StratisError::RollbackError {
+                    causal_error: Box::new(causal),
+                    rollback_error: Box::new(rollback),
+                    level: ActionAvailability::NoPoolChanges,
+                }),
+            };
+        }
+
+        // Loading the exchanged tables made both thin devices writable.
+        for fs in [&*self, &*other] {
+            if fs.read_only {
+                load_thin_table(&fs.thin_dev, true)?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Find places where this filesystem is mounted.
     fn mount_points(&self) -> StratisResult<Vec<PathBuf>> {
         // Use major:minor values to find mounts for this filesystem
@@ -417,9 +670,53 @@ impl StratFilesystem {
         }
     }
 
+    /// Set or remove the snapshot schedule of the filesystem. Return true
+    /// if the schedule was changed.
+    pub fn set_snapshot_schedule(&mut self, schedule: Option<SnapshotSchedule>) -> bool {
+        if self.snapshot_schedule == schedule {
+            false
+        } else {
+            self.snapshot_schedule = schedule;
+            true
+        }
+    }
+
+    /// Record the outcome of a run of the snapshot schedule.
+    pub fn set_snapshot_schedule_status(&mut self, status: SnapshotScheduleStatus) {
+        self.snapshot_schedule_status = Some(status);
+    }
+
     pub fn thindev_size(&self) -> Sectors {
         self.thin_dev.size()
     }
+
+    /// Make the thin device of the filesystem read-only or writable. Return
+    /// true if the setting was changed. A mounted filesystem can not be made
+    /// read-only.
+    pub fn set_read_only(&mut self, read_only: bool) -> StratisResult<bool> {
+        if self.read_only == read_only {
+            return Ok(false);
+        }
+        if read_only && !self.mount_points()?.is_empty() {
+            return Err(StratisError::Msg(format!(
+                "Filesystem with device {} is mounted; it must be unmounted to make it read-only",
+                self.thin_dev.devnode().display()
+            )));
+        }
+        load_thin_table(&self.thin_dev, read_only)?;
+        self.read_only = read_only;
+        Ok(true)
+    }
+
+    pub fn thin_id(&self) -> ThinDevId {
+        self.thin_dev.id()
+    }
+
+    /// Record the space usage of the thin device computed from a metadata
+    /// snapshot of the thin pool.
+    pub fn set_space_usage(&mut self, space_usage: Option<FilesystemSpaceUsage>) {
+        self.space_usage = space_usage;
+    }
 }
 
 impl Filesystem for StratFilesystem {
@@ -459,12 +756,33 @@ impl Filesystem for StratFilesystem {
     fn size_limit(&self) -> Option<Sectors> {
         self.size_limit
     }
+
+    fn origin(&self) -> Option<FilesystemUuid> {
+        self.origin
+    }
+
+    fn snapshot_schedule(&self) -> Option<SnapshotSchedule> {
+        self.snapshot_schedule
+    }
+
+    fn snapshot_schedule_status(&self) -> Option<SnapshotScheduleStatus> {
+        self.snapshot_schedule_status.clone()
+    }
+
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn space_usage(&self) -> Option<FilesystemSpaceUsage> {
+        self.space_usage
+    }
 }
 
 /// Represents the state of the Stratis filesystem at a given moment in time.
 pub struct StratFilesystemState {
     size: Bytes,
     used: Option<Bytes>,
+    space_usage: Option<FilesystemSpaceUsage>,
 }
 
 impl StateDiff for StratFilesystemState {
@@ -474,6 +792,14 @@ impl StateDiff for StratFilesystemState {
         StratFilesystemDiff {
             size: self.size.compare(&new_state.size),
             used: self.used.compare(&new_state.used),
+            exclusive: self
+                .space_usage
+                .map(|u| u.exclusive)
+                .compare(&new_state.space_usage.map(|u| u.exclusive)),
+            shared: self
+                .space_usage
+                .map(|u| u.shared)
+                .compare(&new_state.space_usage.map(|u| u.shared)),
         }
     }
 
@@ -481,6 +807,8 @@ impl StateDiff for StratFilesystemState {
         StratFilesystemDiff {
             size: Diff::Unchanged(self.size),
             used: Diff::Unchanged(self.used),
+            exclusive: Diff::Unchanged(self.space_usage.map(|u| u.exclusive)),
+            shared: Diff::Unchanged(self.space_usage.map(|u| u.shared)),
         }
     }
 }
@@ -492,6 +820,7 @@ impl<'a> DumpState<'a> for StratFilesystem {
         StratFilesystemState {
             size: self.size(),
             used: self.used,
+            space_usage: self.space_usage,
         }
     }
 
@@ -500,6 +829,7 @@ impl<'a> DumpState<'a> for StratFilesystem {
         StratFilesystemState {
             used: self.used,
             size: self.size(),
+            space_usage: self.space_usage,
         }
     }
 }
@@ -521,6 +851,47 @@ pub fn fs_usage(mount_point: &Path) -> StratisResult<(Bytes, Bytes)> {
     ))
 }
 
+/// Reload the table of thin_dev, activating it read-only or writable, and
+/// make the reloaded table live.
+fn load_thin_table(thin_dev: &ThinDev, read_only: bool) -> StratisResult<()> {
+    let id = DevId::Name(thin_dev.name());
+    let options = if read_only {
+        DmOptions::default().set_flags(DmFlags::DM_READONLY)
+    } else {
+        DmOptions::default()
+    };
+    get_dm().table_load(&id, &thin_dev.table().to_raw_table(), options)?;
+    get_dm().device_suspend(&id, DmOptions::default().set_flags(DmFlags::DM_SUSPEND))?;
+    get_dm().device_suspend(&id, DmOptions::default())?;
+    Ok(())
+}
+
+/// Freeze the filesystem mounted at mount_point (FIFREEZE).
+fn fs_freeze(mount_point: &Path) -> StratisResult<()> {
+    let file = File::open(mount_point)?;
+    let mut arg: c_int = 0;
+    unsafe { fifreeze(file.as_raw_fd(), &mut arg) }.map_err(|e| {
+        StratisError::Msg(format!(
+            "Failed to freeze filesystem mounted at {}: {e}",
+            mount_point.display()
+        ))
+    })?;
+    Ok(())
+}
+
+/// Thaw the filesystem mounted at mount_point (FITHAW).
+fn fs_thaw(mount_point: &Path) -> StratisResult<()> {
+    let file = File::open(mount_point)?;
+    let mut arg: c_int = 0;
+    unsafe { fithaw(file.as_raw_fd(), &mut arg) }.map_err(|e| {
+        StratisError::Msg(format!(
+            "Failed to thaw filesystem mounted at {}: {e}",
+            mount_point.display()
+        ))
+    })?;
+    Ok(())
+}
+
 impl<'a> Into<Value> for &'a StratFilesystem {
     fn into(self) -> Value {
         let mut json = Map::new();
@@ -545,6 +916,48 @@ impl<'a> Into<Value> for &'a StratFilesystem {
                     .unwrap_or_else(|| "Not set".to_string()),
             ),
         );
+        json.insert(
+            "origin".to_string(),
+            Value::from(
+                self.origin
+                    .map(|u| u.to_string())
+                    .unwrap_or_else(|| "Not set".to_string()),
+            ),
+        );
+        json.insert("read_only".to_string(), Value::from(self.read_only));
+        json.insert(
+            "exclusive".to_string(),
+            Value::from(
+                self.space_usage
+                    .map(|u| u.exclusive.to_string())
+                    .unwrap_or_else(|| "Unavailable".to_string()),
+            ),
+        );
+        json.insert(
+            "shared".to_string(),
+            Value::from(
+                self.space_usage
+                    .map(|u| u.shared.to_string())
+                    .unwrap_or_else(|| "Unavailable".to_string()),
+            ),
+        );
+        json.insert(
+            "snapshot_schedule".to_string(),
+            Value::from(
+                self.snapshot_schedule
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| "Not set".to_string()),
+            ),
+        );
+        json.insert(
+            "snapshot_schedule_status".to_string(),
+            Value::from(
+                self.snapshot_schedule_status
+                    .as_ref()
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| "Not run".to_string()),
+            ),
+        );
         Value::from(json)
     }
 }
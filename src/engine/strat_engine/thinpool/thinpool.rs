@@ -8,6 +8,8 @@ use std::{
     cmp::{max, min, Ordering},
     collections::{HashMap, HashSet},
     fmt,
+    mem::swap,
+    path::PathBuf,
     thread::scope,
 };
 
@@ -16,7 +18,7 @@ use retry::{delay::Fixed, retry_with_index};
 use serde_json::{Map, Value};
 
 use devicemapper::{
-    device_exists, Bytes, DataBlocks, Device, DmDevice, DmName, DmNameBuf, DmOptions,
+    device_exists, message, Bytes, DataBlocks, Device, DmDevice, DmName, DmNameBuf, DmOptions,
     FlakeyTargetParams, LinearDev, LinearDevTargetParams, LinearTargetParams, MetaBlocks, Sectors,
     TargetLine, ThinDevId, ThinPoolDev, ThinPoolStatus, ThinPoolStatusSummary, ThinPoolUsage, IEC,
 };
@@ -26,7 +28,10 @@ use crate::{
         engine::{DumpState, Filesystem, StateDiff},
         strat_engine::{
             backstore::Backstore,
-            cmd::{thin_check, thin_metadata_size, thin_repair},
+            cmd::{
+                has_thin_ls, has_thin_shrink, thin_check, thin_ls, thin_metadata_size, thin_repair,
+                thin_shrink,
+            },
             dm::{get_dm, list_of_thin_pool_devices, remove_optional_devices},
             names::{
                 format_flex_ids, format_thin_ids, format_thinpool_ids, FlexRole, ThinPoolRole,
@@ -37,7 +42,11 @@ use crate::{
             writing::wipe_sectors,
         },
         structures::Table,
-        types::{Compare, Diff, FilesystemUuid, Name, PoolUuid, StratFilesystemDiff, ThinPoolDiff},
+        types::{
+            ActionAvailability, Compare, DataShrink, Diff, FilesystemSpaceUsage, FilesystemUuid,
+            Name, PoolUuid, Redundancy, SnapshotSchedule, SnapshotScheduleStatus,
+            StratFilesystemDiff, ThinPoolDiff,
+        },
     },
     stratis::{StratisError, StratisResult},
 };
@@ -305,6 +314,15 @@ pub struct ThinPool {
     fs_limit: u64,
     enable_overprov: bool,
     out_of_meta_space: bool,
+    /// The size to which the data device is to be shrunk when the pool is
+    /// next set up, since the kernel does not allow the data device of a
+    /// thin pool that is set up to be shrunk.
+    data_shrink: Option<Sectors>,
+    /// Why the data device could not be shrunk when the pool was set up,
+    /// if it could not be.
+    data_shrink_failure: Option<String>,
+    /// Whether the data device is to be shrunk, as reported to the user.
+    data_shrink_status: Option<DataShrink>,
 }
 
 impl ThinPool {
@@ -425,6 +443,9 @@ impl ThinPool {
             fs_limit: DEFAULT_FS_LIMIT,
             enable_overprov: true,
             out_of_meta_space: false,
+            data_shrink: None,
+            data_shrink_failure: None,
+            data_shrink_status: None,
         })
     }
 
@@ -440,17 +461,25 @@ impl ThinPool {
         pool_uuid: PoolUuid,
         thin_pool_save: &ThinPoolDevSave,
         flex_devs: &FlexDevsSave,
-        backstore: &Backstore,
+        backstore: &mut Backstore,
     ) -> StratisResult<ThinPool> {
         let mdv_segments = flex_devs.meta_dev.to_vec();
         let meta_segments = flex_devs.thin_meta_dev.to_vec();
-        let data_segments = flex_devs.thin_data_dev.to_vec();
+        let mut data_segments = flex_devs.thin_data_dev.to_vec();
         let spare_segments = flex_devs.thin_meta_dev_spare.to_vec();
 
         let backstore_device = backstore.device().expect("When stratisd was running previously, space was allocated from the backstore, so backstore must have a cap device");
 
         let (thinpool_name, thinpool_uuid) = format_thinpool_ids(pool_uuid, ThinPoolRole::Pool);
-        let (meta_dev, meta_segments, spare_segments) = setup_metadev(
+        // The metadata of a thin pool that is already set up, e.g., because
+        // stratisd was restarted, must not be rewritten.
+        let data_shrink = if device_exists(get_dm(), &thinpool_name)? {
+            None
+        } else {
+            thin_pool_save.data_shrink
+        };
+
+        let (mut meta_dev, mut meta_segments, mut spare_segments) = setup_metadev(
             pool_uuid,
             &thinpool_name,
             backstore_device,
@@ -459,13 +488,55 @@ impl ThinPool {
         )?;
 
         let (dm_name, dm_uuid) = format_flex_ids(pool_uuid, FlexRole::ThinData);
-        let data_dev = LinearDev::setup(
+        let mut data_dev = LinearDev::setup(
             get_dm(),
             &dm_name,
             Some(&dm_uuid),
             segs_to_table(backstore_device, &data_segments),
         )?;
 
+        let mut data_shrink_failure = None;
+        match data_shrink {
+            // check() reports that thin_shrink is not installed.
+            Some(new_size) if !has_thin_shrink() => {
+                info!(
+                    "thin_shrink is not installed; not shrinking thinpool data sub-device belonging to pool with uuid {pool_uuid} to {new_size}"
+                );
+            }
+            Some(new_size) => match attempt_thin_shrink(
+                pool_uuid,
+                &meta_dev,
+                &mut data_dev,
+                backstore,
+                &mut data_segments,
+                &spare_segments,
+                new_size,
+            ) {
+                Ok((mut new_meta_dev, range)) => {
+                    let name = meta_dev.name().to_owned();
+                    meta_dev.teardown(get_dm())?;
+                    new_meta_dev.set_name(get_dm(), &name)?;
+                    meta_dev = new_meta_dev;
+                    swap(&mut meta_segments, &mut spare_segments);
+
+                    backstore.release_alloc(range)?;
+                    info!(
+                        "Shrunk thinpool data sub-device belonging to pool with uuid {} by {}",
+                        pool_uuid, range.1
+                    );
+                }
+                // The shrink is scheduled again by check(), so it is
+                // attempted again when the pool is next started.
+                Err(err) => {
+                    warn!(
+                        "Failed to shrink thinpool data sub-device belonging to pool with uuid {pool_uuid} to {new_size}: {err}"
+                    );
+                    data_shrink_failure = Some(err.to_string());
+                }
+            },
+            None => (),
+        }
+
         // TODO: Remove in stratisd 4.0.
         let mut migrate = false;
 
@@ -540,6 +611,7 @@ impl ThinPool {
         }
 
         let thin_ids: Vec<ThinDevId> = filesystem_metadatas.iter().map(|x| x.thin_id).collect();
+        delete_unreferenced_thin_ids(&thinpool_dev, &thin_ids);
         let thin_pool_status = thinpool_dev.status(get_dm(), DmOptions::default()).ok();
         let segments = Segments {
             meta_segments,
@@ -564,6 +636,9 @@ impl ThinPool {
             fs_limit,
             enable_overprov: thin_pool_save.enable_overprov.unwrap_or(true),
             out_of_meta_space: false,
+            data_shrink: None,
+            data_shrink_failure,
+            data_shrink_status: None,
         })
     }
 
@@ -646,6 +721,31 @@ impl ThinPool {
             }
         }
 
+        let (data_shrink, data_shrink_status) = match self.data_shrink_target(backstore) {
+            Ok(None) => (None, None),
+            Ok(Some(new_size)) => (
+                Some(new_size),
+                Some(match self.data_shrink_failure {
+                    Some(ref err) => DataShrink::Failed(new_size, err.clone()),
+                    None => DataShrink::Deferred(new_size),
+                }),
+            ),
+            Err(reason) => (None, Some(DataShrink::Skipped(reason))),
+        };
+        if data_shrink_status != self.data_shrink_status {
+            if let Some(ref status) = data_shrink_status {
+                info!(
+                    "Thinpool data sub-device belonging to pool with uuid {}: {}",
+                    pool_uuid, status
+                );
+            }
+            self.data_shrink_status = data_shrink_status;
+        }
+        if data_shrink != self.data_shrink {
+            self.data_shrink = data_shrink;
+            should_save = true;
+        }
+
         let new_state = self.dump(backstore);
 
         Ok((should_save, old_state.diff(&new_state)))
@@ -774,6 +874,51 @@ impl ThinPool {
         Ok(updated)
     }
 
+    /// Reserve a metadata snapshot of the thin pool, so that thin_ls can read
+    /// the thin pool metadata consistently while the pool is in use.
+    /// Any snapshot left over from an earlier, interrupted computation is
+    /// released first, since the kernel allows only one at a time.
+    /// Returns the path of the thin pool metadata device.
+    pub fn reserve_metadata_snap(&self) -> StratisResult<PathBuf> {
+        if let Err(err) = message(get_dm(), &self.thin_pool, "release_metadata_snap") {
+            debug!(
+                "No stale metadata snapshot released for thinpool with \"{}\": {}",
+                thin_pool_identifiers(&self.thin_pool),
+                err
+            );
+        }
+        message(get_dm(), &self.thin_pool, "reserve_metadata_snap")?;
+        Ok(self.thin_pool.meta_dev().devnode())
+    }
+
+    /// Release the metadata snapshot reserved by reserve_metadata_snap.
+    pub fn release_metadata_snap(&self) -> StratisResult<()> {
+        message(get_dm(), &self.thin_pool, "release_metadata_snap")?;
+        Ok(())
+    }
+
+    /// Set the space mapped exclusively by the thin device of each filesystem
+    /// and the space that it shares with other thin devices from the output of
+    /// thin_ls. Returns the changes to the properties of the filesystems.
+    pub fn set_space_usage(
+        &mut self,
+        blocks: &HashMap<ThinDevId, (DataBlocks, DataBlocks)>,
+    ) -> HashMap<FilesystemUuid, StratFilesystemDiff> {
+        self.filesystems
+            .iter_mut()
+            .map(|(_, uuid, fs)| {
+                let old_state = fs.cached();
+                fs.set_space_usage(blocks.get(&fs.thin_id()).map(|(exclusive, shared)| {
+                    FilesystemSpaceUsage {
+                        exclusive: datablocks_to_sectors(*exclusive).bytes(),
+                        shared: datablocks_to_sectors(*shared).bytes(),
+                    }
+                }));
+                (*uuid, old_state.diff(&fs.cached()))
+            })
+            .collect()
+    }
+
     /// Set the current status of the thin_pool device to thin_pool_status.
     /// If there has been a change, log that change at the info or warn level
     /// as appropriate.
@@ -892,6 +1037,77 @@ impl ThinPool {
             .contains("error_if_no_space")
     }
 
+    /// The size to which the data device can be shrunk, if the thin pool
+    /// status shows that more than twice DATA_ALLOC_SIZE of it is unused.
+    /// Only space in the last data segment that lies at the end of the space
+    /// allocated from the backstore can be released, since the backstore can
+    /// accept returned space only there. At least DATA_ALLOC_SIZE is left
+    /// unused, rounded up to a multiple of DATA_ALLOC_SIZE, so that the data
+    /// device is not extended again immediately and the size changes only
+    /// when the usage changes substantially.
+    ///
+    /// Returns Ok(None) if not enough of the data device is unused to be
+    /// worth shrinking it, and an error with the reason if enough is unused
+    /// but it can not be returned.
+    fn data_shrink_target(&self, backstore: &Backstore) -> Result<Option<Sectors>, String> {
+        let used = match status_to_usage(self.thin_pool_status.as_ref()) {
+            Some(usage) => usage.used_data,
+            None => return Ok(None),
+        };
+        let data_size = self.thin_pool.data_dev().size();
+        let data_blocks = sectors_to_datablocks(data_size);
+        if data_blocks - used <= DataBlocks(2 * *DATA_ALLOC_SIZE) {
+            return Ok(None);
+        }
+
+        if backstore.redundancy() != Redundancy::None {
+            return Err(format!(
+                "space can not be returned to the backstore of a pool with redundancy {}",
+                backstore.redundancy()
+            ));
+        }
+
+        if !has_thin_shrink() {
+            return Err("thin_shrink is not installed".to_string());
+        }
+
+        let &(start, length) = match self.segments.data_segments.last() {
+            Some(segment) => segment,
+            None => return Ok(None),
+        };
+        if start + length != backstore.allocated_in_cap() {
+            return Err(
+                "space allocated from the backstore after the data device is in use, and space can only be returned at the end of the space allocated from the backstore"
+                    .to_string(),
+            );
+        }
+
+        let needed = DataBlocks((*used / *DATA_ALLOC_SIZE + 1) * *DATA_ALLOC_SIZE);
+        let new_size =
+            datablocks_to_sectors(max(data_blocks - sectors_to_datablocks(length), needed));
+        if new_size + datablocks_to_sectors(DATA_ALLOC_SIZE) > data_size
+            || data_size - new_size > length
+        {
+            return Err(format!(
+                "only the unused space in the last segment of the data device, of size {length}, can be returned, and it is too small"
+            ));
+        }
+
+        backstore
+            .check_release((
+                start + length - (data_size - new_size),
+                data_size - new_size,
+            ))
+            .map(|_| Some(new_size))
+            .map_err(|err| err.to_string())
+    }
+
+    /// Whether unused space in the data device is to be returned to the
+    /// backstore, as last determined by check().
+    pub fn data_shrink(&self) -> Option<DataShrink> {
+        self.data_shrink_status.clone()
+    }
+
     /// Extend thinpool's data dev.
     ///
     /// This method returns the extension size as Ok(data_extension).
@@ -1239,13 +1455,15 @@ impl ThinPool {
     }
 
     /// Create a filesystem snapshot of the origin.  Given origin_uuid
-    /// must exist.  Returns the Uuid of the new filesystem.
+    /// must exist.  If read_only is true, the thin device of the snapshot
+    /// is activated read-only.  Returns the Uuid of the new filesystem.
     pub fn snapshot_filesystem(
         &mut self,
         pool_name: &str,
         pool_uuid: PoolUuid,
         origin_uuid: FilesystemUuid,
         snapshot_name: &str,
+        read_only: bool,
     ) -> StratisResult<(FilesystemUuid, &mut StratFilesystem)> {
         let snapshot_fs_uuid = FilesystemUuid::new_v4();
         let (snapshot_dm_name, snapshot_dm_uuid) =
@@ -1254,12 +1472,14 @@ impl ThinPool {
         let new_filesystem = match self.get_filesystem_by_uuid(origin_uuid) {
             Some((fs_name, filesystem)) => filesystem.snapshot(
                 &self.thin_pool,
+                origin_uuid,
                 snapshot_name,
                 &snapshot_dm_name,
                 Some(&snapshot_dm_uuid),
                 &fs_name,
                 snapshot_fs_uuid,
                 snapshot_id,
+                read_only,
             )?,
             None => {
                 return Err(StratisError::Msg(
@@ -1286,6 +1506,315 @@ impl ThinPool {
         ))
     }
 
+    /// Snapshot several filesystems at the same point in time, so that
+    /// together the snapshots form a crash-consistent image of all the
+    /// origins. specs pairs the UUID of each origin with the name of its
+    /// snapshot. Either all the snapshots are created or none are; the
+    /// snapshots are not created in one thin pool metadata transaction, so
+    /// a failure is rolled back by deleting those already created.
+    ///
+    /// Returns the UUIDs of the snapshots in the order of specs.
+    pub fn snapshot_filesystems(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        specs: &[(FilesystemUuid, &str)],
+    ) -> StratisResult<Vec<FilesystemUuid>> {
+        let mut snapshots = Vec::new();
+        for (origin_uuid, snapshot_name) in specs {
+            if self.filesystems.get_by_uuid(*origin_uuid).is_none() {
+                return Err(StratisError::Msg(format!(
+                    "Filesystem with UUID {origin_uuid} could not be found"
+                )));
+            }
+            snapshots.push((
+                *origin_uuid,
+                Name::new((*snapshot_name).to_owned()),
+                FilesystemUuid::new_v4(),
+                self.id_gen.new_id()?,
+            ));
+        }
+
+        self.create_group_snapshots(
+            &snapshots
+                .iter()
+                .map(|(origin_uuid, _, _, thin_id)| (*origin_uuid, *thin_id))
+                .collect::<Vec<_>>(),
+        )?;
+
+        let mut new_filesystems = Vec::new();
+        let mut res = Ok(());
+        for (origin_uuid, snapshot_name, snapshot_fs_uuid, thin_id) in snapshots.iter() {
+            let (_, origin) = self
+                .filesystems
+                .get_by_uuid(*origin_uuid)
+                .expect("checked above");
+            let (dm_name, dm_uuid) =
+                format_thin_ids(pool_uuid, ThinRole::Filesystem(*snapshot_fs_uuid));
+            let fs = match origin.setup_snapshot(
+                &self.thin_pool,
+                *origin_uuid,
+                &dm_name,
+                Some(&dm_uuid),
+                *snapshot_fs_uuid,
+                *thin_id,
+            ) {
+                Ok(fs) => fs,
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            };
+            let saved = self.mdv.save_fs(snapshot_name, *snapshot_fs_uuid, &fs);
+            new_filesystems.push((*snapshot_fs_uuid, fs));
+            if let Err(e) = saved {
+                res = Err(e);
+                break;
+            }
+        }
+
+        if let Err(causal) = res {
+            let mut rollback = Ok(());
+            let mut deleted = HashSet::new();
+            for (snapshot_fs_uuid, mut fs) in new_filesystems {
+                if let Err(e) = self.mdv.rm_fs(snapshot_fs_uuid) {
+                    warn!(
+                        "Failed to remove metadata of snapshot with UUID {}: {}",
+                        snapshot_fs_uuid, e
+                    );
+                }
+                deleted.insert(fs.thin_id());
+                rollback = rollback.and(fs.destroy(&self.thin_pool));
+            }
+            for (_, _, _, thin_id) in snapshots.iter() {
+                if !deleted.contains(thin_id) {
+                    rollback = rollback.and(self.delete_thin_id(*thin_id));
+                }
+            }
+            return Err(match rollback {
+                Ok(_) => causal,
+                Err(rollback_error) => StratisError::RollbackError {
+                    causal_error: Box::new(causal),
+                    rollback_error: Box::new(rollback_error),
+                    level: ActionAvailability::NoPoolChanges,
+                },
+            });
+        }
+
+        let mut uuids = Vec::new();
+        for ((snapshot_fs_uuid, fs), (_, snapshot_name, _, _)) in
+            new_filesystems.into_iter().zip(snapshots)
+        {
+            fs.udev_fs_change(pool_name, snapshot_fs_uuid, &snapshot_name);
+            self.filesystems.insert(snapshot_name, snapshot_fs_uuid, fs);
+            uuids.push(snapshot_fs_uuid);
+        }
+        info!(
+            "Created a group snapshot of {} filesystems in pool {}",
+            uuids.len(),
+            pool_name
+        );
+        Ok(uuids)
+    }
+
+    /// Create a thin snapshot with the given thin id of each origin
+    /// filesystem. While the snapshots are created, every origin that is
+    /// mounted is frozen and the thin devices of all the origins are
+    /// suspended, so that no origin changes until all snapshots exist.
+    ///
+    /// The snapshots are not created in a single thin pool metadata
+    /// transaction: each is created by a separate message to the thin pool,
+    /// which may commit its metadata after any one of them. So if any
+    /// snapshot can not be created or any origin can not be resumed, every
+    /// snapshot that was already created is deleted again, in the reverse
+    /// order of creation. A snapshot that can not be deleted is logged and
+    /// reported in the rollback error. A snapshot left behind because
+    /// stratisd stopped meanwhile is not recorded in the MDV, and is deleted
+    /// when the pool is next set up; see delete_unreferenced_thin_ids().
+    fn create_group_snapshots(
+        &mut self,
+        snapshots: &[(FilesystemUuid, ThinDevId)],
+    ) -> StratisResult<()> {
+        let mut res = Ok(());
+
+        let mut suspended = Vec::new();
+        for (origin_uuid, _) in snapshots {
+            let (_, origin) = self
+                .filesystems
+                .get_mut_by_uuid(*origin_uuid)
+                .expect("checked by caller");
+            match origin.suspend() {
+                Ok(frozen) => suspended.push((*origin_uuid, frozen)),
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+
+        let mut created = Vec::new();
+        if res.is_ok() {
+            for (origin_uuid, thin_id) in snapshots {
+                let origin_id = self
+                    .filesystems
+                    .get_by_uuid(*origin_uuid)
+                    .expect("checked by caller")
+                    .1
+                    .thin_id();
+                match message(
+                    get_dm(),
+                    &self.thin_pool,
+                    &format!("create_snap {thin_id} {origin_id}"),
+                ) {
+                    Ok(_) => created.push(*thin_id),
+                    Err(e) => {
+                        res = Err(StratisError::from(e));
+                        break;
+                    }
+                }
+            }
+        }
+
+        for (origin_uuid, frozen) in suspended.into_iter().rev() {
+            let (origin_name, origin) = self
+                .filesystems
+                .get_mut_by_uuid(origin_uuid)
+                .expect("checked by caller");
+            if let Err(e) = origin.resume(frozen.as_deref()) {
+                error!(
+                    "Failed to resume filesystem {} after creating group snapshot: {}",
+                    origin_name, e
+                );
+                res = res.and(Err(e));
+            }
+        }
+
+        if let Err(causal) = res {
+            let rollback = created
+                .into_iter()
+                .rev()
+                .fold(Ok(()), |acc, thin_id| {
+                    let res = self.delete_thin_id(thin_id);
+                    if let Err(ref e) = res {
+                        warn!(
+                            "Failed to delete snapshot with thin id {} after a failed group snapshot: {}",
+                            thin_id, e
+                        );
+                    }
+                    acc.and(res)
+                });
+            return Err(match rollback {
+                Ok(_) => causal,
+                Err(rollback_error) => StratisError::RollbackError {
+                    causal_error: Box::new(causal),
+                    rollback_error: Box::new(rollback_error),
+                    level: ActionAvailability::NoPoolChanges,
+                },
+            });
+        }
+        Ok(())
+    }
+
+    /// Delete a thin device that has no device-mapper device from the thin
+    /// pool.
+    fn delete_thin_id(&self, thin_id: ThinDevId) -> StratisResult<()> {
+        message(get_dm(), &self.thin_pool, &format!("delete {thin_id}"))?;
+        Ok(())
+    }
+
+    /// Revert the origin filesystem to the contents of the given snapshot of
+    /// it by exchanging the thin ids of the two filesystems. The origin keeps
+    /// its name, UUID and devlinks; the snapshot is left with the contents
+    /// that the origin had before. Both filesystems must be unmounted.
+    ///
+    /// Returns the changes to the properties of both filesystems.
+    pub fn revert_filesystem(
+        &mut self,
+        pool_name: &str,
+        origin_uuid: FilesystemUuid,
+        snapshot_uuid: FilesystemUuid,
+    ) -> StratisResult<HashMap<FilesystemUuid, StratFilesystemDiff>> {
+        if origin_uuid == snapshot_uuid {
+            return Err(StratisError::Msg(
+                "A filesystem can not be reverted to itself".to_string(),
+            ));
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        fn revert(
+            mdv: &MetadataVol,
+            origin_name: &Name,
+            origin_uuid: FilesystemUuid,
+            origin: &mut StratFilesystem,
+            snapshot_name: &Name,
+            snapshot_uuid: FilesystemUuid,
+            snapshot: &mut StratFilesystem,
+        ) -> StratisResult<HashMap<FilesystemUuid, StratFilesystemDiff>> {
+            let origin_state = origin.cached();
+            let snapshot_state = snapshot.cached();
+
+            origin.swap_thin_devs(origin_uuid, snapshot, snapshot_uuid)?;
+            if let Err(causal) = mdv
+                .save_fs(origin_name, origin_uuid, origin)
+                .and_then(|_| mdv.save_fs(snapshot_name, snapshot_uuid, snapshot))
+            {
+                return Err(
+                    match origin
+                        .swap_thin_devs(origin_uuid, snapshot, snapshot_uuid)
+                        .and_then(|_| mdv.save_fs(origin_name, origin_uuid, origin))
+                        .and_then(|_| mdv.save_fs(snapshot_name, snapshot_uuid, snapshot))
+                    {
+                        Ok(_) => causal,
+                        Err(rollback) => StratisError::RollbackError {
+                            causal_error: Box::new(causal),
+                            rollback_error: Box::new(rollback),
+                            level: ActionAvailability::NoPoolChanges,
+                        },
+                    },
+                );
+            }
+
+            let mut diffs = HashMap::new();
+            diffs.insert(origin_uuid, origin_state.diff(&origin.dump(())));
+            diffs.insert(snapshot_uuid, snapshot_state.diff(&snapshot.dump(())));
+            Ok(diffs)
+        }
+
+        let (snapshot_name, mut snapshot) = self
+            .filesystems
+            .remove_by_uuid(snapshot_uuid)
+            .ok_or_else(|| {
+                StratisError::Msg(format!("No filesystem with UUID {snapshot_uuid} found"))
+            })?;
+        let res = match self.filesystems.get_mut_by_uuid(origin_uuid) {
+            Some(_) if snapshot.origin() != Some(origin_uuid) => Err(StratisError::Msg(format!(
+                "Filesystem {snapshot_name} is not a snapshot of the filesystem with UUID {origin_uuid}"
+            ))),
+            Some((origin_name, origin)) => revert(
+                &self.mdv,
+                &origin_name,
+                origin_uuid,
+                origin,
+                &snapshot_name,
+                snapshot_uuid,
+                &mut snapshot,
+            ),
+            None => Err(StratisError::Msg(format!(
+                "No filesystem with UUID {origin_uuid} found"
+            ))),
+        };
+        self.filesystems
+            .insert(snapshot_name, snapshot_uuid, snapshot);
+
+        if res.is_ok() {
+            info!(
+                "Reverted filesystem with UUID {} in pool {} to snapshot with UUID {}",
+                origin_uuid, pool_name, snapshot_uuid
+            );
+        }
+        res
+    }
+
     /// Destroy a filesystem within the thin pool. Destroy metadata associated
     /// with the thinpool. If there is a failure to destroy the filesystem,
     /// retain it, and return an error.
@@ -1583,6 +2112,65 @@ impl ThinPool {
         }
         Ok(changed)
     }
+
+    /// Set the snapshot schedule for filesystem with given UUID.
+    pub fn set_fs_snapshot_schedule(
+        &mut self,
+        fs_uuid: FilesystemUuid,
+        schedule: Option<SnapshotSchedule>,
+    ) -> StratisResult<bool> {
+        let (name, fs) = self
+            .filesystems
+            .get_mut_by_uuid(fs_uuid)
+            .ok_or_else(|| StratisError::Msg(format!("No filesystem with UUID {fs_uuid} found")))?;
+        let old_schedule = fs.snapshot_schedule();
+        if !fs.set_snapshot_schedule(schedule) {
+            return Ok(false);
+        }
+        if let Err(e) = self.mdv.save_fs(&name, fs_uuid, fs) {
+            fs.set_snapshot_schedule(old_schedule);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    /// Make the filesystem with given UUID read-only or writable.
+    pub fn set_fs_read_only(
+        &mut self,
+        fs_uuid: FilesystemUuid,
+        read_only: bool,
+    ) -> StratisResult<bool> {
+        let (name, fs) = self
+            .filesystems
+            .get_mut_by_uuid(fs_uuid)
+            .ok_or_else(|| StratisError::Msg(format!("No filesystem with UUID {fs_uuid} found")))?;
+        if !fs.set_read_only(read_only)? {
+            return Ok(false);
+        }
+        if let Err(causal) = self.mdv.save_fs(&name, fs_uuid, fs) {
+            return Err(match fs.set_read_only(!read_only) {
+                Ok(_) => causal,
+                Err(rollback) => StratisError::RollbackError {
+                    causal_error: Box::new(causal),
+                    rollback_error: Box::new(rollback),
+                    level: ActionAvailability::NoPoolChanges,
+                },
+            });
+        }
+        Ok(true)
+    }
+
+    /// Record the outcome of a run of the snapshot schedule of the filesystem
+    /// with given UUID.
+    pub fn set_fs_snapshot_schedule_status(
+        &mut self,
+        fs_uuid: FilesystemUuid,
+        status: SnapshotScheduleStatus,
+    ) {
+        if let Some((_, fs)) = self.get_mut_filesystem_by_uuid(fs_uuid) {
+            fs.set_snapshot_schedule_status(status);
+        }
+    }
 }
 
 impl<'a> Into<Value> for &'a ThinPool {
@@ -1611,6 +2199,7 @@ impl<'a> Into<Value> for &'a ThinPool {
 pub struct ThinPoolState {
     allocated_size: Bytes,
     used: Option<Bytes>,
+    data_shrink: Option<DataShrink>,
 }
 
 impl StateDiff for ThinPoolState {
@@ -1620,6 +2209,7 @@ impl StateDiff for ThinPoolState {
         ThinPoolDiff {
             allocated_size: self.allocated_size.compare(&new_state.allocated_size),
             used: self.used.compare(&new_state.used),
+            data_shrink: self.data_shrink.compare(&new_state.data_shrink),
         }
     }
 
@@ -1627,6 +2217,7 @@ impl StateDiff for ThinPoolState {
         ThinPoolDiff {
             allocated_size: Diff::Unchanged(self.allocated_size),
             used: Diff::Unchanged(self.used),
+            data_shrink: Diff::Unchanged(self.data_shrink.clone()),
         }
     }
 }
@@ -1639,6 +2230,7 @@ impl<'a> DumpState<'a> for ThinPool {
         ThinPoolState {
             allocated_size: self.allocated_size.bytes(),
             used: self.total_physical_used().map(|u| u.bytes()),
+            data_shrink: self.data_shrink_status.clone(),
         }
     }
 
@@ -1649,6 +2241,7 @@ impl<'a> DumpState<'a> for ThinPool {
         ThinPoolState {
             allocated_size: self.allocated_size.bytes(),
             used: self.total_physical_used().map(|u| u.bytes()),
+            data_shrink: self.data_shrink_status.clone(),
         }
     }
 }
@@ -1687,6 +2280,62 @@ impl Recordable<ThinPoolDevSave> for ThinPool {
             ),
             fs_limit: Some(self.fs_limit),
             enable_overprov: Some(self.enable_overprov),
+            data_shrink: self.data_shrink,
+        }
+    }
+}
+
+/// Delete every thin device in the thin pool that is not referenced by the
+/// metadata of any filesystem in the MDV. Such a thin device is left behind
+/// if stratisd stops while snapshots are created, e.g., by
+/// ThinPool::snapshot_filesystems(), before the metadata of the snapshots
+/// is saved or after it is removed again. The thin devices in the thin pool
+/// can only be listed by thin_ls; if it is not installed or the thin pool
+/// metadata can not be read, no thin device is deleted.
+fn delete_unreferenced_thin_ids(thinpool_dev: &ThinPoolDev, referenced: &[ThinDevId]) {
+    if !has_thin_ls() {
+        info!(
+            "thin_ls is not installed; not checking thinpool with \"{}\" for thin devices not referenced by any filesystem",
+            thin_pool_identifiers(thinpool_dev)
+        );
+        return;
+    }
+
+    let thin_ids = match message(get_dm(), thinpool_dev, "reserve_metadata_snap")
+        .map_err(StratisError::from)
+        .and_then(|_| {
+            let thin_ids = thin_ls(&thinpool_dev.meta_dev().devnode());
+            message(get_dm(), thinpool_dev, "release_metadata_snap")?;
+            thin_ids
+        }) {
+        Ok(thin_ids) => thin_ids,
+        Err(err) => {
+            warn!(
+                "Failed to list the thin devices of thinpool with \"{}\"; thin devices not referenced by any filesystem are not deleted: {}",
+                thin_pool_identifiers(thinpool_dev),
+                err
+            );
+            return;
+        }
+    };
+
+    for thin_id in thin_ids
+        .keys()
+        .filter(|id| !referenced.contains(id))
+        .sorted()
+    {
+        match message(get_dm(), thinpool_dev, &format!("delete {thin_id}")) {
+            Ok(_) => info!(
+                "Deleted thin device with thin id {} not referenced by any filesystem from thinpool with \"{}\"",
+                thin_id,
+                thin_pool_identifiers(thinpool_dev)
+            ),
+            Err(err) => warn!(
+                "Failed to delete thin device with thin id {} not referenced by any filesystem from thinpool with \"{}\": {}",
+                thin_id,
+                thin_pool_identifiers(thinpool_dev),
+                err
+            ),
         }
     }
 }
@@ -1753,6 +2402,81 @@ fn attempt_thin_repair(
     Ok(new_meta_dev)
 }
 
+/// Attempt to shrink the data device of a thin pool that is not yet set up
+/// to new_size. thin_shrink copies any data mapped beyond new_size to unused
+/// blocks below it and writes the metadata for the smaller data device to
+/// the spare segments. If the operation succeeds, reload the data device to
+/// map only the remaining data segments and return the new meta device and
+/// the range of the backstore that is no longer mapped by the data device.
+///
+/// The metadata on the old meta device remains valid for the larger data
+/// device, since thin_shrink copies data only to blocks that it does not map.
+fn attempt_thin_shrink(
+    pool_uuid: PoolUuid,
+    meta_dev: &LinearDev,
+    data_dev: &mut LinearDev,
+    backstore: &Backstore,
+    data_segments: &mut Vec<(Sectors, Sectors)>,
+    spare_segments: &[(Sectors, Sectors)],
+    new_size: Sectors,
+) -> StratisResult<(LinearDev, (Sectors, Sectors))> {
+    let data_size = data_dev.size();
+    let range = match data_segments.last() {
+        Some(&(start, length)) if new_size < data_size && data_size - new_size <= length => (
+            start + length - (data_size - new_size),
+            data_size - new_size,
+        ),
+        _ => {
+            return Err(StratisError::Msg(format!(
+                "The data sub-device of size {data_size} can not be shrunk to {new_size} within its last segment"
+            )));
+        }
+    };
+    backstore.check_release(range)?;
+
+    let device = backstore
+        .device()
+        .expect("space was allocated from the backstore, so backstore must have a cap device");
+    let (dm_name, dm_uuid) = format_flex_ids(pool_uuid, FlexRole::ThinMetaSpare);
+    let mut new_meta_dev = LinearDev::setup(
+        get_dm(),
+        &dm_name,
+        Some(&dm_uuid),
+        segs_to_table(device, spare_segments),
+    )?;
+
+    if let Err(err) = thin_shrink(
+        &meta_dev.devnode(),
+        &new_meta_dev.devnode(),
+        &data_dev.devnode(),
+        sectors_to_datablocks(new_size),
+    ) {
+        if let Err(e) = new_meta_dev.teardown(get_dm()) {
+            warn!("Failed to remove spare meta device: {e}");
+        }
+        return Err(err);
+    }
+
+    let mut segments = data_segments.clone();
+    if let Some(last) = segments.last_mut() {
+        last.1 -= range.1;
+    }
+    segments.retain(|&(_, length)| length != Sectors(0));
+
+    if let Err(err) = data_dev
+        .set_table(get_dm(), segs_to_table(device, &segments))
+        .and_then(|_| data_dev.resume(get_dm()))
+    {
+        if let Err(e) = new_meta_dev.teardown(get_dm()) {
+            warn!("Failed to remove spare meta device: {e}");
+        }
+        return Err(StratisError::from(err));
+    }
+    *data_segments = segments;
+
+    Ok((new_meta_dev, range))
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
@@ -1761,7 +2485,7 @@ mod tests {
         path::Path,
     };
 
-    use nix::mount::{mount, MsFlags};
+    use nix::mount::{mount, umount, MsFlags};
 
     use devicemapper::{Bytes, SECTOR_SIZE};
 
@@ -1775,6 +2499,7 @@ mod tests {
             tests::{loopbacked, real},
             writing::SyncAll,
         },
+        types::{CacheSettings, Redundancy},
     };
 
     use super::*;
@@ -1808,9 +2533,16 @@ mod tests {
 
         let devices = get_devices(paths).unwrap();
 
-        let mut backstore =
-            Backstore::initialize(pool_name, pool_uuid, devices, MDADataSize::default(), None)
-                .unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_name,
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            None,
+            Redundancy::None,
+            None,
+        )
+        .unwrap();
         let size = ThinPoolSizeParams::new(backstore.datatier_usable_size()).unwrap();
         let mut pool = ThinPool::new(pool_uuid, &size, DATA_BLOCK_SIZE, &mut backstore).unwrap();
 
@@ -1906,6 +2638,8 @@ mod tests {
             first_devices,
             MDADataSize::default(),
             None,
+            Redundancy::None,
+            None,
         )
         .unwrap();
         let mut pool = ThinPool::new(
@@ -2046,6 +2780,8 @@ mod tests {
             devices,
             MDADataSize::default(),
             None,
+            Redundancy::None,
+            None,
         )
         .unwrap();
         let mut pool = ThinPool::new(
@@ -2106,7 +2842,7 @@ mod tests {
 
         let snapshot_name = "test_snapshot";
         let (_, snapshot_filesystem) = pool
-            .snapshot_filesystem(pool_name, pool_uuid, fs_uuid, snapshot_name)
+            .snapshot_filesystem(pool_name, pool_uuid, fs_uuid, snapshot_name, false)
             .unwrap();
 
         cmd::udev_settle().unwrap();
@@ -2154,22 +2890,444 @@ mod tests {
         );
     }
 
-    /// Verify that a filesystem rename causes the filesystem metadata to be
-    /// updated.
-    fn test_filesystem_rename(paths: &[&Path]) {
-        let pool_name = Name::new("pool_name".to_string());
-        let name1 = "name1";
-        let name2 = "name2";
+    /// Verify that a read-only snapshot rejects writes and is still
+    /// read-only after the pool is torn down and set up again.
+    fn test_read_only_snapshot(paths: &[&Path]) {
+        fn write_fails(fs: &StratFilesystem) -> bool {
+            OpenOptions::new()
+                .write(true)
+                .open(fs.devnode())
+                .and_then(|mut f| {
+                    f.write_all(&[1u8; SECTOR_SIZE])?;
+                    f.sync_all()
+                })
+                .is_err()
+        }
 
+        let pool_name = "pool";
         let pool_uuid = PoolUuid::new_v4();
 
         let devices = get_devices(paths).unwrap();
 
-        let mut backstore =
-            Backstore::initialize(pool_name, pool_uuid, devices, MDADataSize::default(), None)
-                .unwrap();
-        let mut pool = ThinPool::new(
-            pool_uuid,
+        let mut backstore = Backstore::initialize(
+            Name::new(pool_name.to_string()),
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            None,
+            Redundancy::None,
+            None,
+        )
+        .unwrap();
+        let mut pool = ThinPool::new(
+            pool_uuid,
+            &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+            DATA_BLOCK_SIZE,
+            &mut backstore,
+        )
+        .unwrap();
+
+        let fs_uuid = pool
+            .create_filesystem(
+                pool_name,
+                pool_uuid,
+                "stratis_test_filesystem",
+                DEFAULT_THIN_DEV_SIZE,
+                None,
+            )
+            .unwrap();
+
+        let (snapshot_uuid, snapshot) = pool
+            .snapshot_filesystem(pool_name, pool_uuid, fs_uuid, "test_snapshot", true)
+            .unwrap();
+        assert!(snapshot.read_only());
+        assert!(write_fails(snapshot));
+
+        cmd::udev_settle().unwrap();
+
+        let flexdevs: FlexDevsSave = pool.record();
+        let thinpoolsave: ThinPoolDevSave = pool.record();
+
+        retry_operation!(pool.teardown(pool_uuid));
+
+        let pool = ThinPool::setup(
+            pool_name,
+            pool_uuid,
+            &thinpoolsave,
+            &flexdevs,
+            &mut backstore,
+        )
+        .unwrap();
+
+        let (_, snapshot) = pool.get_filesystem_by_uuid(snapshot_uuid).unwrap();
+        assert!(snapshot.read_only());
+        assert!(write_fails(snapshot));
+    }
+
+    #[test]
+    fn loop_test_read_only_snapshot() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_read_only_snapshot,
+        );
+    }
+
+    #[test]
+    fn real_test_read_only_snapshot() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_read_only_snapshot,
+        );
+    }
+
+    /// Verify that a snapshot records its origin and that reverting the
+    /// origin to the snapshot exchanges the contents of the two filesystems
+    /// while the origin keeps its name and devlink.
+    fn test_filesystem_revert(paths: &[&Path]) {
+        let pool_name = "pool";
+        let pool_uuid = PoolUuid::new_v4();
+
+        let devices = get_devices(paths).unwrap();
+
+        let mut backstore = Backstore::initialize(
+            Name::new(pool_name.to_string()),
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            None,
+            Redundancy::None,
+            None,
+        )
+        .unwrap();
+        let mut pool = ThinPool::new(
+            pool_uuid,
+            &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+            DATA_BLOCK_SIZE,
+            &mut backstore,
+        )
+        .unwrap();
+
+        let filesystem_name = "stratis_test_filesystem";
+        let fs_uuid = pool
+            .create_filesystem(
+                pool_name,
+                pool_uuid,
+                filesystem_name,
+                DEFAULT_THIN_DEV_SIZE,
+                None,
+            )
+            .unwrap();
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let write_file = |pool: &ThinPool, file_name: &str| {
+            let (_, filesystem) = pool.get_filesystem_by_uuid(fs_uuid).unwrap();
+            mount(
+                Some(&filesystem.devnode()),
+                tmp_dir.path(),
+                Some("xfs"),
+                MsFlags::empty(),
+                None as Option<&str>,
+            )
+            .unwrap();
+            let mut f = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(tmp_dir.path().join(file_name))
+                .unwrap();
+            f.write_all(&[8u8; SECTOR_SIZE]).unwrap();
+            f.sync_all().unwrap();
+            umount(tmp_dir.path()).unwrap();
+        };
+
+        write_file(&pool, "before_snapshot");
+        let snapshot_name = "test_snapshot";
+        let (snapshot_uuid, snapshot) = pool
+            .snapshot_filesystem(pool_name, pool_uuid, fs_uuid, snapshot_name, false)
+            .unwrap();
+        assert_eq!(snapshot.origin(), Some(fs_uuid));
+        write_file(&pool, "after_snapshot");
+
+        let origin_thin_id = pool
+            .get_filesystem_by_uuid(fs_uuid)
+            .unwrap()
+            .1
+            .record(&Name::new(filesystem_name.to_string()), fs_uuid)
+            .thin_id;
+        assert!(pool
+            .revert_filesystem(pool_name, snapshot_uuid, fs_uuid)
+            .is_err());
+        pool.revert_filesystem(pool_name, fs_uuid, snapshot_uuid)
+            .unwrap();
+
+        let saved = pool.mdv.filesystems().unwrap();
+        let origin_save = saved.iter().find(|fssave| fssave.uuid == fs_uuid).unwrap();
+        let snapshot_save = saved
+            .iter()
+            .find(|fssave| fssave.uuid == snapshot_uuid)
+            .unwrap();
+        assert_eq!(origin_save.name, filesystem_name);
+        assert_eq!(snapshot_save.thin_id, origin_thin_id);
+        assert_eq!(snapshot_save.origin, Some(fs_uuid));
+
+        cmd::udev_settle().unwrap();
+        assert!(Path::new(&format!("/dev/stratis/{pool_name}/{filesystem_name}")).exists());
+
+        let (_, filesystem) = pool.get_filesystem_by_uuid(fs_uuid).unwrap();
+        mount(
+            Some(&filesystem.devnode()),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None as Option<&str>,
+        )
+        .unwrap();
+        assert!(tmp_dir.path().join("before_snapshot").exists());
+        assert!(!tmp_dir.path().join("after_snapshot").exists());
+        umount(tmp_dir.path()).unwrap();
+    }
+
+    #[test]
+    fn loop_test_filesystem_revert() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_filesystem_revert,
+        );
+    }
+
+    #[test]
+    fn real_test_filesystem_revert() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_filesystem_revert,
+        );
+    }
+
+    /// Compute the space usage of the filesystems in the same way that the
+    /// engine does, holding the metadata snapshot for the whole computation.
+    fn refresh_space_usage(pool: &mut ThinPool) -> HashMap<FilesystemUuid, StratFilesystemDiff> {
+        let meta_dev = pool.reserve_metadata_snap().unwrap();
+        let blocks = cmd::thin_ls(&meta_dev);
+        pool.release_metadata_snap().unwrap();
+        pool.set_space_usage(&blocks.unwrap())
+    }
+
+    /// Verify that the space mapped by a filesystem is shared with its
+    /// snapshot and becomes exclusive to it when the snapshot is destroyed.
+    /// Verify also that a metadata snapshot left reserved by an interrupted
+    /// computation does not prevent a later one.
+    fn test_space_usage(paths: &[&Path]) {
+        if !cmd::has_thin_ls() {
+            return;
+        }
+
+        let pool_name = "pool";
+        let pool_uuid = PoolUuid::new_v4();
+
+        let devices = get_devices(paths).unwrap();
+
+        let mut backstore = Backstore::initialize(
+            Name::new(pool_name.to_string()),
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            None,
+            Redundancy::None,
+            None,
+        )
+        .unwrap();
+        let mut pool = ThinPool::new(
+            pool_uuid,
+            &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+            DATA_BLOCK_SIZE,
+            &mut backstore,
+        )
+        .unwrap();
+
+        let fs_uuid = pool
+            .create_filesystem(
+                pool_name,
+                pool_uuid,
+                "stratis_test_filesystem",
+                DEFAULT_THIN_DEV_SIZE,
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            pool.get_filesystem_by_uuid(fs_uuid)
+                .unwrap()
+                .1
+                .space_usage(),
+            None
+        );
+
+        let (snapshot_uuid, _) = pool
+            .snapshot_filesystem(pool_name, pool_uuid, fs_uuid, "test_snapshot", true)
+            .unwrap();
+
+        pool.reserve_metadata_snap().unwrap();
+
+        let diffs = refresh_space_usage(&mut pool);
+        assert!(diffs[&fs_uuid].shared.is_changed());
+        let origin_usage = pool
+            .get_filesystem_by_uuid(fs_uuid)
+            .unwrap()
+            .1
+            .space_usage()
+            .unwrap();
+        let snapshot_usage = pool
+            .get_filesystem_by_uuid(snapshot_uuid)
+            .unwrap()
+            .1
+            .space_usage()
+            .unwrap();
+        assert!(origin_usage.shared > Bytes(0));
+        assert_eq!(origin_usage.shared, snapshot_usage.shared);
+
+        pool.destroy_filesystem(pool_name, snapshot_uuid).unwrap();
+        refresh_space_usage(&mut pool);
+        let origin_usage = pool
+            .get_filesystem_by_uuid(fs_uuid)
+            .unwrap()
+            .1
+            .space_usage()
+            .unwrap();
+        assert_eq!(origin_usage.shared, Bytes(0));
+        assert!(origin_usage.exclusive > Bytes(0));
+    }
+
+    #[test]
+    fn loop_test_space_usage() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_space_usage,
+        );
+    }
+
+    #[test]
+    fn real_test_space_usage() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_space_usage,
+        );
+    }
+
+    /// Verify that a thin device that is not referenced by any filesystem,
+    /// as is left behind if stratisd stops while a group snapshot is
+    /// created, is deleted when the pool is set up, and that the thin
+    /// devices of the filesystems are kept.
+    fn test_delete_unreferenced_thin_ids(paths: &[&Path]) {
+        if !cmd::has_thin_ls() {
+            return;
+        }
+
+        let pool_name = "pool";
+        let pool_uuid = PoolUuid::new_v4();
+
+        let devices = get_devices(paths).unwrap();
+
+        let mut backstore = Backstore::initialize(
+            Name::new(pool_name.to_string()),
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            None,
+            Redundancy::None,
+            None,
+        )
+        .unwrap();
+        let mut pool = ThinPool::new(
+            pool_uuid,
+            &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+            DATA_BLOCK_SIZE,
+            &mut backstore,
+        )
+        .unwrap();
+
+        let fs_uuid = pool
+            .create_filesystem(
+                pool_name,
+                pool_uuid,
+                "stratis_test_filesystem",
+                DEFAULT_THIN_DEV_SIZE,
+                None,
+            )
+            .unwrap();
+        let fs_thin_id = pool.get_filesystem_by_uuid(fs_uuid).unwrap().1.thin_id();
+        let orphan_thin_id = pool.id_gen.new_id().unwrap();
+        message(
+            get_dm(),
+            &pool.thin_pool,
+            &format!("create_snap {orphan_thin_id} {fs_thin_id}"),
+        )
+        .unwrap();
+
+        let thin_ids = |pool: &ThinPool| {
+            let meta_dev = pool.reserve_metadata_snap().unwrap();
+            let blocks = cmd::thin_ls(&meta_dev);
+            pool.release_metadata_snap().unwrap();
+            blocks.unwrap().into_keys().collect::<HashSet<_>>()
+        };
+        assert!(thin_ids(&pool).contains(&orphan_thin_id));
+
+        let flexdevs: FlexDevsSave = pool.record();
+        let thinpoolsave: ThinPoolDevSave = pool.record();
+        retry_operation!(pool.teardown(pool_uuid));
+
+        let pool = ThinPool::setup(
+            pool_name,
+            pool_uuid,
+            &thinpoolsave,
+            &flexdevs,
+            &mut backstore,
+        )
+        .unwrap();
+
+        assert_eq!(thin_ids(&pool), HashSet::from([fs_thin_id]));
+        assert!(pool.get_filesystem_by_uuid(fs_uuid).is_some());
+    }
+
+    #[test]
+    fn loop_test_delete_unreferenced_thin_ids() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_delete_unreferenced_thin_ids,
+        );
+    }
+
+    #[test]
+    fn real_test_delete_unreferenced_thin_ids() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_delete_unreferenced_thin_ids,
+        );
+    }
+
+    /// Verify that a filesystem rename causes the filesystem metadata to be
+    /// updated.
+    fn test_filesystem_rename(paths: &[&Path]) {
+        let pool_name = Name::new("pool_name".to_string());
+        let name1 = "name1";
+        let name2 = "name2";
+
+        let pool_uuid = PoolUuid::new_v4();
+
+        let devices = get_devices(paths).unwrap();
+
+        let mut backstore = Backstore::initialize(
+            pool_name,
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            None,
+            Redundancy::None,
+            None,
+        )
+        .unwrap();
+        let mut pool = ThinPool::new(
+            pool_uuid,
             &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
             DATA_BLOCK_SIZE,
             &mut backstore,
@@ -2199,8 +3357,14 @@ mod tests {
 
         retry_operation!(pool.teardown(pool_uuid));
 
-        let pool =
-            ThinPool::setup(pool_name, pool_uuid, &thinpoolsave, &flexdevs, &backstore).unwrap();
+        let pool = ThinPool::setup(
+            pool_name,
+            pool_uuid,
+            &thinpoolsave,
+            &flexdevs,
+            &mut backstore,
+        )
+        .unwrap();
 
         assert_eq!(&*pool.get_filesystem_by_uuid(fs_uuid).unwrap().0, name2);
     }
@@ -2236,6 +3400,8 @@ mod tests {
             devices,
             MDADataSize::default(),
             None,
+            Redundancy::None,
+            None,
         )
         .unwrap();
         let mut pool = ThinPool::new(
@@ -2282,7 +3448,7 @@ mod tests {
             pool_uuid,
             &thinpooldevsave,
             &pool.record(),
-            &backstore,
+            &mut backstore,
         )
         .unwrap();
 
@@ -2301,6 +3467,102 @@ mod tests {
     fn real_test_pool_setup() {
         real::test_with_spec(&real::DeviceLimits::AtLeast(1, None, None), test_pool_setup);
     }
+
+    /// Extend the data device of a new pool twice, so that most of it is
+    /// unused, and verify that check() schedules a shrink of the data device
+    /// by the extensions, which lie at the end of the cap device, and reports
+    /// it as deferred. Then set the pool up again and verify that the data
+    /// device was shrunk and the space returned to the backstore. If
+    /// thin_shrink is not installed, verify instead that the shrink is
+    /// reported as skipped and the data device was left as it was.
+    fn test_data_shrink(paths: &[&Path]) {
+        let pool_name = "pool";
+        let pool_uuid = PoolUuid::new_v4();
+
+        let devices = get_devices(paths).unwrap();
+
+        let mut backstore = Backstore::initialize(
+            Name::new(pool_name.to_string()),
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            None,
+            Redundancy::None,
+            None,
+        )
+        .unwrap();
+        let mut pool = ThinPool::new(
+            pool_uuid,
+            &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+            DATA_BLOCK_SIZE,
+            &mut backstore,
+        )
+        .unwrap();
+
+        let init_data_size = pool.thin_pool.data_dev().size();
+        for _ in 0..2 {
+            let (_, res) = pool.extend_thin_data_device(pool_uuid, &mut backstore);
+            assert_eq!(res.unwrap(), datablocks_to_sectors(DATA_ALLOC_SIZE));
+        }
+        let allocated = backstore.allocated_in_cap();
+
+        let (_, diff) = pool.check(pool_uuid, &mut backstore).unwrap();
+        if has_thin_shrink() {
+            assert_eq!(pool.data_shrink, Some(init_data_size));
+            assert_eq!(
+                pool.data_shrink(),
+                Some(DataShrink::Deferred(init_data_size))
+            );
+        } else {
+            assert_eq!(pool.data_shrink, None);
+            assert_matches!(pool.data_shrink(), Some(DataShrink::Skipped(_)));
+        }
+        assert!(diff.data_shrink.is_changed());
+
+        let flexdevs: FlexDevsSave = pool.record();
+        let thinpooldevsave: ThinPoolDevSave = pool.record();
+        retry_operation!(pool.teardown(pool_uuid));
+
+        let pool = ThinPool::setup(
+            pool_name,
+            pool_uuid,
+            &thinpooldevsave,
+            &flexdevs,
+            &mut backstore,
+        )
+        .unwrap();
+
+        assert_eq!(pool.data_shrink, None);
+        if has_thin_shrink() {
+            assert_eq!(pool.thin_pool.data_dev().size(), init_data_size);
+            assert_eq!(
+                backstore.allocated_in_cap(),
+                allocated - datablocks_to_sectors(DATA_ALLOC_SIZE) * 2u64
+            );
+        } else {
+            assert_eq!(
+                pool.thin_pool.data_dev().size(),
+                init_data_size + datablocks_to_sectors(DATA_ALLOC_SIZE) * 2u64
+            );
+            assert_eq!(backstore.allocated_in_cap(), allocated);
+        }
+    }
+
+    #[test]
+    fn loop_test_data_shrink() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, Some(Sectors(20 * IEC::Mi))),
+            test_data_shrink,
+        );
+    }
+
+    #[test]
+    fn real_test_data_shrink() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, Some(Sectors(20 * IEC::Mi)), None),
+            test_data_shrink,
+        );
+    }
     /// Verify that destroy_filesystems actually deallocates the space
     /// from the thinpool, by attempting to reinstantiate it using the
     /// same thin id and verifying that it fails.
@@ -2310,9 +3572,16 @@ mod tests {
 
         let devices = get_devices(paths).unwrap();
 
-        let mut backstore =
-            Backstore::initialize(pool_name, pool_uuid, devices, MDADataSize::default(), None)
-                .unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_name,
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            None,
+            Redundancy::None,
+            None,
+        )
+        .unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
             &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
@@ -2339,7 +3608,7 @@ mod tests {
             pool_uuid,
             &thinpooldevsave,
             &flexdevs,
-            &backstore,
+            &mut backstore,
         )
         .unwrap();
 
@@ -2378,6 +3647,8 @@ mod tests {
             devices,
             MDADataSize::default(),
             None,
+            Redundancy::None,
+            None,
         )
         .unwrap();
         let mut pool = ThinPool::new(
@@ -2440,6 +3711,8 @@ mod tests {
             devices,
             MDADataSize::default(),
             None,
+            Redundancy::None,
+            None,
         )
         .unwrap();
         let mut pool = ThinPool::new(
@@ -2499,7 +3772,13 @@ mod tests {
             .device()
             .expect("Space already allocated from backstore, backstore must have device");
         backstore
-            .init_cache(Name::new(pool_name.to_string()), pool_uuid, devices1, None)
+            .init_cache(
+                Name::new(pool_name.to_string()),
+                pool_uuid,
+                devices1,
+                None,
+                CacheSettings::default(),
+            )
             .unwrap();
         let new_device = backstore
             .device()
@@ -2561,6 +3840,8 @@ mod tests {
             devices,
             MDADataSize::default(),
             None,
+            Redundancy::None,
+            None,
         )
         .unwrap();
         let mut pool = ThinPool::new(
@@ -2642,7 +3923,7 @@ mod tests {
 
         {
             let (_, fs) = pool
-                .snapshot_filesystem(pool_name, pool_uuid, fs_uuid, "snapshot")
+                .snapshot_filesystem(pool_name, pool_uuid, fs_uuid, "snapshot", false)
                 .unwrap();
             assert_eq!(fs.size_limit(), Some(Sectors(1600 * IEC::Ki)));
         }
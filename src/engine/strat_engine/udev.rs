@@ -6,7 +6,10 @@
 use std::{ffi::OsStr, fmt};
 
 use crate::{
-    engine::types::{DevicePath, UdevEngineDevice},
+    engine::{
+        strat_engine::names::is_integrity_name,
+        types::{DevicePath, UdevEngineDevice},
+    },
     stratis::{StratisError, StratisResult},
 };
 
@@ -52,6 +55,18 @@ fn is_multipath_member(device: &UdevEngineDevice) -> StratisResult<bool> {
     }
 }
 
+/// Returns true if udev indicates that the device is one of the dm devices
+/// that Stratis sets up to add integrity protection to a blockdev. These
+/// devices expose the same data as the blockdev beneath them, so they must
+/// not be identified as Stratis or LUKS devices in their own right.
+fn is_integrity_layer(device: &UdevEngineDevice) -> StratisResult<bool> {
+    match get_udev_property(device, "DM_NAME") {
+        None => Ok(false),
+        Some(Ok(value)) => Ok(is_integrity_name(&value)),
+        Some(Err(err)) => Err(err),
+    }
+}
+
 /// If the expression is true, then it seems that no other system is
 /// known to udev to claim this device.
 fn is_unclaimed(device: &UdevEngineDevice) -> bool {
@@ -83,6 +98,7 @@ fn is_luks(device: &UdevEngineDevice) -> StratisResult<bool> {
 /// An enum to encode udev classification of a device
 #[derive(Debug, Eq, PartialEq)]
 pub enum UdevOwnership {
+    IntegrityLayer,
     Luks,
     MultipathMember,
     Stratis,
@@ -93,6 +109,9 @@ pub enum UdevOwnership {
 impl fmt::Display for UdevOwnership {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            UdevOwnership::IntegrityLayer => {
+                write!(f, "Stratis integrity layer of a block device")
+            }
             UdevOwnership::Luks => write!(f, "LUKS encrypted block device"),
             UdevOwnership::MultipathMember => write!(f, "member of a multipath block device"),
             UdevOwnership::Stratis => write!(f, "Stratis block device"),
@@ -121,6 +140,12 @@ pub fn decide_ownership(device: &UdevEngineDevice) -> StratisResult<UdevOwnershi
             return Ok(UdevOwnership::MultipathMember);
         }
 
+        // An integrity layer shows the Stratis or LUKS metadata of the device
+        // beneath it; the device beneath it is the one that is identified.
+        if is_integrity_layer(device)? {
+            return Ok(UdevOwnership::IntegrityLayer);
+        }
+
         // We believe that the following designations are mutually exclusive, i.e.
         // it is not possible to be a Stratis device and also to appear unowned.
         Ok(if is_stratis(device)? {
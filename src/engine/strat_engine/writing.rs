@@ -9,13 +9,13 @@ use std::io::Cursor;
 use std::{
     cmp::min,
     fs::{File, OpenOptions},
-    io::{self, BufWriter, Seek, SeekFrom, Write},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
     path::Path,
 };
 
 use devicemapper::{Sectors, IEC, SECTOR_SIZE};
 
-use crate::stratis::StratisResult;
+use crate::stratis::{StratisError, StratisResult};
 
 /// The SyncAll trait unifies the File type with other types that do
 /// not implement sync_all().
@@ -83,3 +83,47 @@ pub fn wipe_sectors<P: AsRef<Path>>(
 ) -> StratisResult<()> {
     write_sectors(path, offset, length, &[0u8; SECTOR_SIZE])
 }
+
+/// Copy length sectors from src, starting at src_offset, to dst, starting at
+/// dst_offset.
+/// Note that this method buffers the data written and syncs only when all
+/// is written.
+pub fn copy_sectors<P, Q>(
+    src: P,
+    src_offset: Sectors,
+    dst: Q,
+    dst_offset: Sectors,
+    length: Sectors,
+) -> StratisResult<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let mut src_f = File::open(src)?;
+    src_f.seek(SeekFrom::Start(convert_int!(
+        *src_offset.bytes(),
+        u128,
+        u64
+    )?))?;
+
+    let mut dst_f = BufWriter::with_capacity(
+        convert_const!(min(u128::from(IEC::Mi), *(length.bytes())), u128, usize),
+        OpenOptions::new().write(true).open(dst)?,
+    );
+    dst_f.seek(SeekFrom::Start(convert_int!(
+        *dst_offset.bytes(),
+        u128,
+        u64
+    )?))?;
+
+    let to_copy = convert_int!(*length.bytes(), u128, u64)?;
+    let copied = io::copy(&mut src_f.take(to_copy), &mut dst_f)?;
+    if copied != to_copy {
+        return Err(StratisError::Msg(format!(
+            "Copied only {copied} of {to_copy} bytes requested"
+        )));
+    }
+
+    dst_f.sync_all()?;
+    Ok(())
+}
@@ -23,6 +23,12 @@ pub struct Key;
 /// Return value indicating clevis operation
 pub struct Clevis;
 
+/// Return value indicating an encryption operation
+pub struct EncryptedDevice;
+
+/// Return value indicating a reencryption operation
+pub struct Reencryption;
+
 /// A trait for a generic kind of action. Defines the type of the thing to
 /// be changed, and also a method to indicate what changed.
 pub trait EngineAction {
@@ -75,6 +81,22 @@ impl Display for CreateAction<PoolUuid> {
     }
 }
 
+impl Display for CreateAction<DevUuid> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CreateAction::Created(uuid) => {
+                write!(
+                    f,
+                    "Blockdev with UUID {uuid} was added to replace an existing blockdev"
+                )
+            }
+            CreateAction::Identity => {
+                write!(f, "No blockdev was replaced; no action taken")
+            }
+        }
+    }
+}
+
 impl Display for CreateAction<Clevis> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -94,6 +116,25 @@ impl Display for CreateAction<Clevis> {
     }
 }
 
+impl Display for CreateAction<EncryptedDevice> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CreateAction::Created(EncryptedDevice) => {
+                write!(
+                    f,
+                    "Encryption of the pool was started successfully; the data is encrypted in the background"
+                )
+            }
+            CreateAction::Identity => {
+                write!(
+                    f,
+                    "The pool requested for encryption is already encrypted; no action taken"
+                )
+            }
+        }
+    }
+}
+
 impl Display for CreateAction<Key> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -627,6 +668,27 @@ impl Display for SetDeleteAction<FilesystemUuid> {
     }
 }
 
+impl Display for SetDeleteAction<DevUuid> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changed.is_empty() {
+            write!(
+                f,
+                "The requested blockdevs are already absent; no action taken"
+            )
+        } else {
+            write!(
+                f,
+                "Blockdevs with UUIDs {} were successfully removed",
+                self.changed
+                    .iter()
+                    .map(|u| u.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    }
+}
+
 /// Action indicating a Clevis binding regeneration
 pub struct RegenAction;
 
@@ -639,6 +701,18 @@ impl Display for RegenAction {
     }
 }
 
+/// Action indicating that a filesystem was reverted to one of its snapshots
+pub struct RevertAction;
+
+impl Display for RevertAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The filesystem was successfully reverted to the contents of its snapshot"
+        )
+    }
+}
+
 /// Action indicating an operation for starting a resource
 pub enum StartAction<T> {
     Identity,
@@ -659,6 +733,21 @@ impl Display for StartAction<PoolUuid> {
     }
 }
 
+impl Display for StartAction<Reencryption> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartAction::Identity => write!(
+                f,
+                "The requested pool is already being reencrypted; no action was taken"
+            ),
+            StartAction::Started(Reencryption) => write!(
+                f,
+                "Reencryption of the pool with new volume keys was started successfully; the data is reencrypted in the background"
+            ),
+        }
+    }
+}
+
 impl<T> EngineAction for StartAction<T> {
     type Return = T;
 
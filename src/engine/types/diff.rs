@@ -2,10 +2,15 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::ops::{Deref, DerefMut};
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+};
 
 use devicemapper::{Bytes, Sectors};
 
+use crate::engine::types::{CacheStats, DataShrink, DevUuid};
+
 /// This interface defines a generic way to compare whether two values of
 /// the same type have changed or remained the same.
 pub trait Compare {
@@ -79,6 +84,7 @@ impl<T> DerefMut for Diff<T> {
 pub struct ThinPoolDiff {
     pub allocated_size: Diff<Bytes>,
     pub used: Diff<Option<Bytes>>,
+    pub data_shrink: Diff<Option<DataShrink>>,
 }
 
 /// Change in attributes of a Stratis pool that may need to be reported to the
@@ -87,6 +93,11 @@ pub struct ThinPoolDiff {
 pub struct StratPoolDiff {
     pub metadata_size: Diff<Bytes>,
     pub out_of_alloc_space: Diff<bool>,
+    pub replacement_progress: Diff<Option<u8>>,
+    pub encryption_progress: Diff<Option<u8>>,
+    pub reencryption_progress: Diff<Option<u8>>,
+    pub cache_stats: Diff<Option<CacheStats>>,
+    pub degraded: Diff<bool>,
 }
 
 /// Represents the difference between two dumped states for a filesystem.
@@ -94,16 +105,23 @@ pub struct StratPoolDiff {
 pub struct StratFilesystemDiff {
     pub size: Diff<Bytes>,
     pub used: Diff<Option<Bytes>>,
+    pub exclusive: Diff<Option<Bytes>>,
+    pub shared: Diff<Option<Bytes>>,
 }
 
 /// Represents the difference between two dumped states for a pool.
 pub struct PoolDiff {
     pub thin_pool: ThinPoolDiff,
     pub pool: StratPoolDiff,
+    /// The changes to the blockdevs of the pool that were found while
+    /// checking the pool.
+    pub blockdevs: HashMap<DevUuid, StratBlockDevDiff>,
 }
 
 /// Represents the difference between two dumped states for a block device.
 #[derive(Debug)]
 pub struct StratBlockDevDiff {
     pub size: Diff<Option<Sectors>>,
+    pub shrunk: Diff<bool>,
+    pub integrity_errors: Diff<Option<u64>>,
 }
@@ -170,14 +170,14 @@ impl TryFrom<PoolEncryptionInfo> for EncryptionInfo {
     type Error = StratisError;
 
     fn try_from(pei: PoolEncryptionInfo) -> StratisResult<Self> {
-        match pei {
-            PoolEncryptionInfo::KeyDesc(MaybeInconsistent::No(kd)) => {
+        match (pei.key_description, pei.clevis_info) {
+            (Some(MaybeInconsistent::No(kd)), None) => {
                 Ok(EncryptionInfo::KeyDesc(kd))
             },
-            PoolEncryptionInfo::ClevisInfo(MaybeInconsistent::No(ci)) => {
+            (None, Some(MaybeInconsistent::No(ci))) => {
                 Ok(EncryptionInfo::ClevisInfo(ci))
             },
-            PoolEncryptionInfo::Both(MaybeInconsistent::No(kd), MaybeInconsistent::No(ci)) => {
+            (Some(MaybeInconsistent::No(kd)), Some(MaybeInconsistent::No(ci))) => {
                 Ok(EncryptionInfo::Both(kd, ci))
             },
             _ => {
@@ -187,139 +187,134 @@ impl TryFrom<PoolEncryptionInfo> for EncryptionInfo {
     }
 }
 
+/// The encryption information of a pool, reconciled across all of its
+/// devices.
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
-pub enum PoolEncryptionInfo {
-    KeyDesc(MaybeInconsistent<KeyDescription>),
-    ClevisInfo(MaybeInconsistent<ClevisInfo>),
-    Both(
-        MaybeInconsistent<KeyDescription>,
-        MaybeInconsistent<ClevisInfo>,
-    ),
+pub struct PoolEncryptionInfo {
+    key_description: Option<MaybeInconsistent<KeyDescription>>,
+    clevis_info: Option<MaybeInconsistent<ClevisInfo>>,
+    /// Keyring bindings in addition to the one with key_description, sorted
+    /// by key description.
+    keyring_bindings: MaybeInconsistent<Vec<KeyDescription>>,
 }
 
 impl PoolEncryptionInfo {
-    /// Reconcile two PoolEncryptionInfo records.
+    /// Reconcile two records of the same piece of encryption information.
+    fn reconcile<T>(
+        first: MaybeInconsistent<T>,
+        second: MaybeInconsistent<T>,
+    ) -> MaybeInconsistent<T>
+    where
+        T: PartialEq,
+    {
+        if first != second {
+            MaybeInconsistent::Yes
+        } else {
+            first
+        }
+    }
+
+    /// Reconcile two records of an optional piece of encryption information.
     ///
     /// Because rollback failure can result in some devices having a key description
     /// or Clevis info and some not having that information, any reconciliation
     /// for Some(_) and None will result in Some(MaybeInconsistent::Yes).
+    fn reconcile_option<T>(
+        first: Option<MaybeInconsistent<T>>,
+        second: Option<MaybeInconsistent<T>>,
+    ) -> Option<MaybeInconsistent<T>>
+    where
+        T: PartialEq,
+    {
+        match (first, second) {
+            (None, None) => None,
+            (Some(first), Some(second)) => Some(Self::reconcile(first, second)),
+            _ => Some(MaybeInconsistent::Yes),
+        }
+    }
+
+    /// Reconcile two PoolEncryptionInfo records.
     fn add_enc_info<I>(self, info: I) -> Self
     where
         PoolEncryptionInfo: From<I>,
     {
         let pei = PoolEncryptionInfo::from(info);
-        match (self, pei) {
-            (PoolEncryptionInfo::KeyDesc(kd1), PoolEncryptionInfo::KeyDesc(kd2)) => {
-                if kd1 != kd2 {
-                    PoolEncryptionInfo::KeyDesc(MaybeInconsistent::Yes)
-                } else {
-                    PoolEncryptionInfo::KeyDesc(kd1)
-                }
-            }
-            (PoolEncryptionInfo::ClevisInfo(ci1), PoolEncryptionInfo::ClevisInfo(ci2)) => {
-                if ci1 != ci2 {
-                    PoolEncryptionInfo::ClevisInfo(MaybeInconsistent::Yes)
-                } else {
-                    PoolEncryptionInfo::ClevisInfo(ci1)
-                }
-            }
-            (PoolEncryptionInfo::Both(kd1, ci1), PoolEncryptionInfo::Both(kd2, ci2)) => {
-                PoolEncryptionInfo::Both(
-                    if kd1 != kd2 {
-                        MaybeInconsistent::Yes
-                    } else {
-                        kd1
-                    },
-                    if ci1 != ci2 {
-                        MaybeInconsistent::Yes
-                    } else {
-                        ci1
-                    },
-                )
-            }
-            (PoolEncryptionInfo::KeyDesc(_), PoolEncryptionInfo::ClevisInfo(_))
-            | (PoolEncryptionInfo::ClevisInfo(_), PoolEncryptionInfo::KeyDesc(_)) => {
-                PoolEncryptionInfo::Both(MaybeInconsistent::Yes, MaybeInconsistent::Yes)
-            }
-            (PoolEncryptionInfo::KeyDesc(kd1), PoolEncryptionInfo::Both(kd2, _))
-            | (PoolEncryptionInfo::Both(kd1, _), PoolEncryptionInfo::KeyDesc(kd2)) => {
-                PoolEncryptionInfo::Both(
-                    if kd1 != kd2 {
-                        MaybeInconsistent::Yes
-                    } else {
-                        kd1
-                    },
-                    MaybeInconsistent::Yes,
-                )
-            }
-            (PoolEncryptionInfo::ClevisInfo(ci1), PoolEncryptionInfo::Both(_, ci2))
-            | (PoolEncryptionInfo::Both(_, ci1), PoolEncryptionInfo::ClevisInfo(ci2)) => {
-                PoolEncryptionInfo::Both(
-                    MaybeInconsistent::Yes,
-                    if ci1 != ci2 {
-                        MaybeInconsistent::Yes
-                    } else {
-                        ci1
-                    },
-                )
-            }
+        PoolEncryptionInfo {
+            key_description: Self::reconcile_option(self.key_description, pei.key_description),
+            clevis_info: Self::reconcile_option(self.clevis_info, pei.clevis_info),
+            keyring_bindings: Self::reconcile(self.keyring_bindings, pei.keyring_bindings),
+        }
+    }
+
+    /// Set the additional keyring bindings, given the bindings of each
+    /// device in the pool. The bindings are marked as inconsistent if they
+    /// differ between devices.
+    pub fn set_keyring_bindings<I>(self, bindings: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<KeyDescription>>,
+    {
+        let keyring_bindings = bindings
+            .into_iter()
+            .map(|mut kds| {
+                kds.sort_by(|kd1, kd2| kd1.as_application_str().cmp(kd2.as_application_str()));
+                MaybeInconsistent::No(kds)
+            })
+            .reduce(Self::reconcile)
+            .unwrap_or(MaybeInconsistent::No(Vec::new()));
+        PoolEncryptionInfo {
+            keyring_bindings,
+            ..self
         }
     }
 
     pub fn is_inconsistent(&self) -> bool {
-        matches!(
-            self,
-            PoolEncryptionInfo::KeyDesc(MaybeInconsistent::Yes)
-                | PoolEncryptionInfo::ClevisInfo(MaybeInconsistent::Yes)
-                | PoolEncryptionInfo::Both(MaybeInconsistent::Yes, _)
-                | PoolEncryptionInfo::Both(_, MaybeInconsistent::Yes)
-        )
+        matches!(self.key_description, Some(MaybeInconsistent::Yes))
+            || matches!(self.clevis_info, Some(MaybeInconsistent::Yes))
+            || matches!(self.keyring_bindings, MaybeInconsistent::Yes)
     }
 
     pub fn key_description(&self) -> StratisResult<Option<&KeyDescription>> {
-        match self {
-            PoolEncryptionInfo::KeyDesc(kd) | PoolEncryptionInfo::Both(kd, _) => {
-                if let MaybeInconsistent::No(ref key_description) = kd {
-                    Ok(Some(key_description))
-                } else {
-                    Err(StratisError::Msg(
-                        "Key description is inconsistent across devices".to_string(),
-                    ))
-                }
-            }
-            _ => Ok(None),
+        match self.key_description {
+            Some(MaybeInconsistent::No(ref key_description)) => Ok(Some(key_description)),
+            Some(MaybeInconsistent::Yes) => Err(StratisError::Msg(
+                "Key description is inconsistent across devices".to_string(),
+            )),
+            None => Ok(None),
         }
     }
 
     pub fn clevis_info(&self) -> StratisResult<Option<&ClevisInfo>> {
-        match self {
-            PoolEncryptionInfo::ClevisInfo(ci) | PoolEncryptionInfo::Both(_, ci) => {
-                if let MaybeInconsistent::No(ref clevis_info) = ci {
-                    Ok(Some(clevis_info))
-                } else {
-                    Err(StratisError::Msg(
-                        "Clevis information is inconsistent across devices".to_string(),
-                    ))
-                }
-            }
-            _ => Ok(None),
+        match self.clevis_info {
+            Some(MaybeInconsistent::No(ref clevis_info)) => Ok(Some(clevis_info)),
+            Some(MaybeInconsistent::Yes) => Err(StratisError::Msg(
+                "Clevis information is inconsistent across devices".to_string(),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// The key descriptions of the keyring bindings of the pool in addition
+    /// to the one returned by key_description().
+    pub fn keyring_bindings(&self) -> StratisResult<&[KeyDescription]> {
+        match self.keyring_bindings {
+            MaybeInconsistent::No(ref key_descriptions) => Ok(key_descriptions),
+            MaybeInconsistent::Yes => Err(StratisError::Msg(
+                "Keyring bindings are inconsistent across devices".to_string(),
+            )),
         }
     }
 }
 
 impl From<&EncryptionInfo> for PoolEncryptionInfo {
     fn from(enc_info: &EncryptionInfo) -> Self {
-        match enc_info {
-            EncryptionInfo::KeyDesc(ref kd) => {
-                PoolEncryptionInfo::KeyDesc(MaybeInconsistent::No(kd.to_owned()))
-            }
-            EncryptionInfo::ClevisInfo(ref ci) => {
-                PoolEncryptionInfo::ClevisInfo(MaybeInconsistent::No(ci.to_owned()))
-            }
-            EncryptionInfo::Both(ref kd, ref ci) => PoolEncryptionInfo::Both(
-                MaybeInconsistent::No(kd.to_owned()),
-                MaybeInconsistent::No(ci.to_owned()),
-            ),
+        PoolEncryptionInfo {
+            key_description: enc_info
+                .key_description()
+                .map(|kd| MaybeInconsistent::No(kd.to_owned())),
+            clevis_info: enc_info
+                .clevis_info()
+                .map(|ci| MaybeInconsistent::No(ci.to_owned())),
+            keyring_bindings: MaybeInconsistent::No(Vec::new()),
         }
     }
 }
@@ -340,6 +335,170 @@ where
     }
 }
 
+/// The password-based key derivation function used for the keyslots of an
+/// encrypted device.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pbkdf {
+    Pbkdf2,
+    Argon2i,
+    Argon2id,
+}
+
+impl<'a> TryFrom<&'a str> for Pbkdf {
+    type Error = StratisError;
+
+    fn try_from(s: &str) -> StratisResult<Pbkdf> {
+        match s {
+            "pbkdf2" => Ok(Pbkdf::Pbkdf2),
+            "argon2i" => Ok(Pbkdf::Argon2i),
+            "argon2id" => Ok(Pbkdf::Argon2id),
+            _ => Err(StratisError::Msg(format!(
+                "{s} is an invalid key derivation function"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Pbkdf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pbkdf::Pbkdf2 => write!(f, "pbkdf2"),
+            Pbkdf::Argon2i => write!(f, "argon2i"),
+            Pbkdf::Argon2id => write!(f, "argon2id"),
+        }
+    }
+}
+
+/// Parameters for the LUKS2 format of the devices of an encrypted pool.
+/// Any parameter that is not specified is chosen by libcryptsetup, except
+/// for the cipher and the key size, which default to aes-xts-plain64 with a
+/// 512 bit key.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct CryptParams {
+    /// The cipher and its mode, e.g. "aes-xts-plain64" or
+    /// "xchacha20,aes-adiantum-plain64"
+    pub cipher: Option<String>,
+    /// The size of the volume key in bits
+    pub key_size: Option<u32>,
+    /// The encryption sector size in bytes
+    pub sector_size: Option<u32>,
+    pub pbkdf: Option<Pbkdf>,
+    /// The memory cost of an Argon2 key derivation function in KiB
+    pub pbkdf_memory: Option<u32>,
+    /// The number of iterations of the key derivation function; if not
+    /// specified, it is benchmarked by libcryptsetup
+    pub pbkdf_iterations: Option<u32>,
+    /// The number of parallel threads of an Argon2 key derivation function
+    pub pbkdf_parallel: Option<u32>,
+}
+
+impl CryptParams {
+    /// Check that the parameters can be used to format a LUKS2 device.
+    pub fn validate(&self) -> StratisResult<()> {
+        self.cipher_and_mode()?;
+        if let Some(key_size) = self.key_size {
+            if key_size == 0 || key_size % 8 != 0 {
+                return Err(StratisError::Msg(format!(
+                    "The key size must be a positive multiple of 8 bits, not {key_size}"
+                )));
+            }
+        }
+        if let Some(sector_size) = self.sector_size {
+            if !(512..=4096).contains(&sector_size) || !sector_size.is_power_of_two() {
+                return Err(StratisError::Msg(format!(
+                    "The encryption sector size must be a power of 2 between 512 and 4096 bytes, not {sector_size}"
+                )));
+            }
+        }
+        if self.pbkdf == Some(Pbkdf::Pbkdf2)
+            && (self.pbkdf_memory.is_some() || self.pbkdf_parallel.is_some())
+        {
+            return Err(StratisError::Msg(
+                "A memory cost and parallel threads can only be specified for an Argon2 key derivation function".to_string(),
+            ));
+        }
+        if [
+            self.pbkdf_memory,
+            self.pbkdf_iterations,
+            self.pbkdf_parallel,
+        ]
+        .contains(&Some(0))
+        {
+            return Err(StratisError::Msg(
+                "The parameters of the key derivation function must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Split the cipher into the cipher name and the mode in the form
+    /// expected by libcryptsetup; the mode begins after the first '-'.
+    pub fn cipher_and_mode(&self) -> StratisResult<Option<(&str, &str)>> {
+        self.cipher
+            .as_ref()
+            .map(|cipher| match cipher.split_once('-') {
+                Some((name, mode)) if !name.is_empty() && !mode.is_empty() => Ok((name, mode)),
+                _ => Err(StratisError::Msg(format!(
+                    "The cipher must be specified as <cipher>-<mode>, not \"{cipher}\""
+                ))),
+            })
+            .transpose()
+    }
+
+    /// Whether any parameter of the key derivation function is specified.
+    pub fn has_pbkdf(&self) -> bool {
+        self.pbkdf.is_some()
+            || self.pbkdf_memory.is_some()
+            || self.pbkdf_iterations.is_some()
+            || self.pbkdf_parallel.is_some()
+    }
+
+    /// Only the parameters of the key derivation function, or None if none
+    /// is specified.
+    pub fn pbkdf_params(&self) -> Option<CryptParams> {
+        if self.has_pbkdf() {
+            Some(CryptParams {
+                pbkdf: self.pbkdf,
+                pbkdf_memory: self.pbkdf_memory,
+                pbkdf_iterations: self.pbkdf_iterations,
+                pbkdf_parallel: self.pbkdf_parallel,
+                ..CryptParams::default()
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> Into<Value> for &'a CryptParams {
+    fn into(self) -> Value {
+        let mut json = Map::new();
+        if let Some(ref cipher) = self.cipher {
+            json.insert("cipher".to_string(), Value::from(cipher.to_owned()));
+        }
+        if let Some(key_size) = self.key_size {
+            json.insert("key_size".to_string(), Value::from(key_size));
+        }
+        if let Some(sector_size) = self.sector_size {
+            json.insert("sector_size".to_string(), Value::from(sector_size));
+        }
+        if let Some(pbkdf) = self.pbkdf {
+            json.insert("pbkdf".to_string(), Value::from(pbkdf.to_string()));
+        }
+        if let Some(memory) = self.pbkdf_memory {
+            json.insert("pbkdf_memory".to_string(), Value::from(memory));
+        }
+        if let Some(iterations) = self.pbkdf_iterations {
+            json.insert("pbkdf_iterations".to_string(), Value::from(iterations));
+        }
+        if let Some(parallel) = self.pbkdf_parallel {
+            json.insert("pbkdf_parallel".to_string(), Value::from(parallel));
+        }
+        Value::from(json)
+    }
+}
+
 /// A data type representing a key description for the kernel keyring
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct KeyDescription(String);
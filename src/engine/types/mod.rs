@@ -11,8 +11,14 @@ use std::{
     iter::once,
     ops::Deref,
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
 };
 
+use chrono::{DateTime, SecondsFormat, Utc};
+use devicemapper::{Bytes, Sectors};
 use libudev::EventType;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -23,15 +29,18 @@ pub use crate::engine::{
     structures::Lockable,
     types::{
         actions::{
-            Clevis, CreateAction, DeleteAction, EngineAction, GrowAction, Key, MappingCreateAction,
-            MappingDeleteAction, PropChangeAction, RegenAction, RenameAction, SetCreateAction,
-            SetDeleteAction, SetUnlockAction, StartAction, StopAction, ToDisplay,
+            Clevis, CreateAction, DeleteAction, EncryptedDevice, EngineAction, GrowAction, Key,
+            MappingCreateAction, MappingDeleteAction, PropChangeAction, Reencryption, RegenAction,
+            RenameAction, RevertAction, SetCreateAction, SetDeleteAction, SetUnlockAction,
+            StartAction, StopAction, ToDisplay,
         },
         diff::{
             Compare, Diff, PoolDiff, StratBlockDevDiff, StratFilesystemDiff, StratPoolDiff,
             ThinPoolDiff,
         },
-        keys::{EncryptionInfo, KeyDescription, PoolEncryptionInfo, SizedKeyMemory},
+        keys::{
+            CryptParams, EncryptionInfo, KeyDescription, Pbkdf, PoolEncryptionInfo, SizedKeyMemory,
+        },
     },
 };
 use crate::stratis::{StratisError, StratisResult};
@@ -129,11 +138,20 @@ impl Display for StratisUuid {
     }
 }
 
-/// Use Clevis or keyring to unlock LUKS volume.
-#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+/// Use Clevis, keyring, or a keyfile to unlock LUKS volume.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
 pub enum UnlockMethod {
     Clevis,
     Keyring,
+    /// Unlock with the passphrase contained in the file at the given path,
+    /// which must be the passphrase that the pool is bound to in the keyring.
+    /// Given as "keyfile:<absolute path>" where an unlock method is parsed
+    /// from a string.
+    Keyfile(PathBuf),
+    /// Try every unlock mechanism the device is configured with in order:
+    /// first the kernel keyring, then Clevis. stratis-min asks for the
+    /// passphrase if neither succeeds; the engine itself never does.
+    Any,
 }
 
 impl<'a> TryFrom<&'a str> for UnlockMethod {
@@ -143,13 +161,112 @@ impl<'a> TryFrom<&'a str> for UnlockMethod {
         match s {
             "keyring" => Ok(UnlockMethod::Keyring),
             "clevis" => Ok(UnlockMethod::Clevis),
-            _ => Err(StratisError::Msg(format!(
-                "{s} is an invalid unlock method"
-            ))),
+            "any" => Ok(UnlockMethod::Any),
+            _ => match s.strip_prefix("keyfile:").map(Path::new) {
+                Some(path) if path.is_absolute() => Ok(UnlockMethod::Keyfile(path.to_owned())),
+                Some(_) => Err(StratisError::Msg(format!(
+                    "{s} is an invalid unlock method; the path to the keyfile must be absolute"
+                ))),
+                None => Err(StratisError::Msg(format!(
+                    "{s} is an invalid unlock method"
+                ))),
+            },
+        }
+    }
+}
+
+impl Display for UnlockMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnlockMethod::Clevis => write!(f, "clevis"),
+            UnlockMethod::Keyring => write!(f, "keyring"),
+            UnlockMethod::Keyfile(path) => write!(f, "keyfile:{}", path.display()),
+            UnlockMethod::Any => write!(f, "any"),
+        }
+    }
+}
+
+/// How to erase the data on the block devices of a pool that is destroyed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EraseMode {
+    /// Destroy every LUKS2 keyslot of the devices of an encrypted pool so
+    /// that the data can no longer be decrypted.
+    Keyslots,
+    /// Discard the allocated ranges of the devices of an unencrypted pool,
+    /// securely if the device supports it. The ranges are overwritten with
+    /// zeros on devices that do not support discard. The result of the
+    /// erasure reports which devices were not discarded securely.
+    Discard,
+    /// Overwrite the allocated ranges of the devices of an unencrypted pool
+    /// with zeros.
+    Zero,
+}
+
+impl<'a> TryFrom<&'a str> for EraseMode {
+    type Error = StratisError;
+
+    fn try_from(s: &str) -> StratisResult<EraseMode> {
+        match s {
+            "keyslots" => Ok(EraseMode::Keyslots),
+            "discard" => Ok(EraseMode::Discard),
+            "zero" => Ok(EraseMode::Zero),
+            _ => Err(StratisError::Msg(format!("{s} is an invalid erase mode"))),
+        }
+    }
+}
+
+impl Display for EraseMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EraseMode::Keyslots => write!(f, "keyslots"),
+            EraseMode::Discard => write!(f, "discard"),
+            EraseMode::Zero => write!(f, "zero"),
         }
     }
 }
 
+/// Progress in percent of an operation that may be watched while it is
+/// running. Clones share the same progress.
+#[derive(Clone, Debug, Default)]
+pub struct JobProgress(Arc<AtomicU8>);
+
+impl JobProgress {
+    pub fn get(&self) -> u8 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, percent: u8) {
+        self.0.store(percent, Ordering::Relaxed)
+    }
+}
+
+/// The erasure of the data on the block devices of a pool that has been
+/// destroyed. It is run separately from the destruction of the pool as it
+/// may take a long time.
+pub struct Erasure(Box<dyn FnOnce(&JobProgress) -> StratisResult<String> + Send>);
+
+impl Erasure {
+    pub fn new<F>(erase: F) -> Self
+    where
+        F: FnOnce(&JobProgress) -> StratisResult<String> + Send + 'static,
+    {
+        Erasure(Box::new(erase))
+    }
+
+    /// Erase the data, recording the progress of the erasure in progress.
+    /// Return a report of how the data on each block device was erased.
+    pub fn run(self, progress: &JobProgress) -> StratisResult<String> {
+        (self.0)(progress)
+    }
+}
+
+impl Debug for Erasure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Erasure")
+    }
+}
+
 /// Blockdev tier. Used to distinguish between blockdevs used for
 /// data and blockdevs used for a cache.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -158,6 +275,321 @@ pub enum BlockDevTier {
     Cache = 1,
 }
 
+/// The write mode of a cache.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    /// Writes go to both the cache and the origin before completing.
+    #[default]
+    Writethrough,
+    /// Writes complete once they reach the cache and are written back to
+    /// the origin later.
+    Writeback,
+    /// All I/O bypasses the cache; cached blocks are invalidated on write.
+    Passthrough,
+}
+
+impl<'a> TryFrom<&'a str> for CacheMode {
+    type Error = StratisError;
+
+    fn try_from(s: &str) -> StratisResult<CacheMode> {
+        match s {
+            "writethrough" => Ok(CacheMode::Writethrough),
+            "writeback" => Ok(CacheMode::Writeback),
+            "passthrough" => Ok(CacheMode::Passthrough),
+            _ => Err(StratisError::Msg(format!("{s} is an invalid cache mode"))),
+        }
+    }
+}
+
+impl Display for CacheMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheMode::Writethrough => write!(f, "writethrough"),
+            CacheMode::Writeback => write!(f, "writeback"),
+            CacheMode::Passthrough => write!(f, "passthrough"),
+        }
+    }
+}
+
+/// The policy used by a cache if none is specified.
+pub const DEFAULT_CACHE_POLICY: &str = "default";
+
+/// The mode and the replacement policy of a cache, along with the
+/// arguments to pass to the policy.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CacheSettings {
+    pub mode: CacheMode,
+    pub policy: String,
+    pub policy_args: Vec<(String, String)>,
+}
+
+impl CacheSettings {
+    /// Create cache settings, checking that the policy and its arguments
+    /// can be placed in a DM table.
+    pub fn new(
+        mode: CacheMode,
+        policy: String,
+        policy_args: Vec<(String, String)>,
+    ) -> StratisResult<CacheSettings> {
+        if policy.is_empty() {
+            return Err(StratisError::Msg(
+                "The cache policy may not be empty".to_string(),
+            ));
+        }
+        if let Some(word) = once(&policy)
+            .chain(policy_args.iter().flat_map(|(k, v)| once(k).chain(once(v))))
+            .find(|word| word.is_empty() || word.contains(char::is_whitespace))
+        {
+            return Err(StratisError::Msg(format!(
+                "Cache policy names and arguments must be non-empty and may not contain whitespace: \"{word}\""
+            )));
+        }
+        Ok(CacheSettings {
+            mode,
+            policy,
+            policy_args,
+        })
+    }
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        CacheSettings {
+            mode: CacheMode::default(),
+            policy: DEFAULT_CACHE_POLICY.to_string(),
+            policy_args: Vec::new(),
+        }
+    }
+}
+
+impl Display for CacheSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mode {}, policy {}", self.mode, self.policy)?;
+        if !self.policy_args.is_empty() {
+            write!(
+                f,
+                " with arguments {}",
+                self.policy_args
+                    .iter()
+                    .map(|(k, v)| format!("{k}={v}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Counters reported by the dm-cache device of a pool's cache tier.
+/// Hit, miss, promotion, and demotion counts accumulate from the time the
+/// cache device was last set up.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub read_hits: u64,
+    pub read_misses: u64,
+    pub write_hits: u64,
+    pub write_misses: u64,
+    pub dirty_blocks: u64,
+    pub promotions: u64,
+    pub demotions: u64,
+}
+
+/// Whether unused space in the thin pool data device is to be returned to
+/// the backstore. A thin pool data device can not be shrunk while the thin
+/// pool is in use, so it is only shrunk when the pool is started.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DataShrink {
+    /// The data device will be shrunk to the given size when the pool is
+    /// next started.
+    Deferred(Sectors),
+    /// Shrinking the data device to the given size failed for the given
+    /// reason when the pool was last started; it will be attempted again
+    /// when the pool is next started.
+    Failed(Sectors, String),
+    /// The data device has unused space that can not be returned for the
+    /// given reason.
+    Skipped(String),
+}
+
+impl Display for DataShrink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataShrink::Deferred(size) => write!(
+                f,
+                "the data device will be shrunk to {} when the pool is next started",
+                size.bytes()
+            ),
+            DataShrink::Failed(size, reason) => write!(
+                f,
+                "shrinking the data device to {} failed when the pool was started and will be attempted again when it is next started: {}",
+                size.bytes(),
+                reason
+            ),
+            DataShrink::Skipped(reason) => {
+                write!(f, "unused space in the data device can not be returned: {reason}")
+            }
+        }
+    }
+}
+
+/// The redundancy of the data tier of a pool.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Redundancy {
+    /// Data is stored once; the loss of any data device loses the pool.
+    #[default]
+    None = 0,
+    /// Data is mirrored on two legs, each made up of a distinct set of data
+    /// devices.
+    Raid1 = 1,
+}
+
+impl Redundancy {
+    /// The number of copies of the data that are stored in the data tier.
+    pub fn copies(self) -> usize {
+        match self {
+            Redundancy::None => 1,
+            Redundancy::Raid1 => 2,
+        }
+    }
+}
+
+impl TryFrom<u16> for Redundancy {
+    type Error = StratisError;
+
+    fn try_from(code: u16) -> StratisResult<Redundancy> {
+        match code {
+            0 => Ok(Redundancy::None),
+            1 => Ok(Redundancy::Raid1),
+            _ => Err(StratisError::Msg(format!(
+                "{code} is an invalid redundancy code"
+            ))),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Redundancy {
+    type Error = StratisError;
+
+    fn try_from(s: &str) -> StratisResult<Redundancy> {
+        match s {
+            "none" => Ok(Redundancy::None),
+            "raid1" => Ok(Redundancy::Raid1),
+            _ => Err(StratisError::Msg(format!("{s} is an invalid redundancy"))),
+        }
+    }
+}
+
+impl Display for Redundancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Redundancy::None => write!(f, "none"),
+            Redundancy::Raid1 => write!(f, "raid1"),
+        }
+    }
+}
+
+/// The hash with which the dm-integrity layer of each data blockdev of a
+/// pool checks every sector read from the blockdev.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IntegrityHash {
+    /// A 4 byte checksum; cheap, detects accidental corruption.
+    Crc32c,
+    /// A 32 byte cryptographic hash.
+    Sha256,
+}
+
+impl IntegrityHash {
+    /// The name of the kernel crypto API algorithm.
+    pub fn algorithm(self) -> &'static str {
+        match self {
+            IntegrityHash::Crc32c => "crc32c",
+            IntegrityHash::Sha256 => "sha256",
+        }
+    }
+
+    /// The number of bytes of the tag stored for every sector.
+    pub fn tag_size(self) -> u64 {
+        match self {
+            IntegrityHash::Crc32c => 4,
+            IntegrityHash::Sha256 => 32,
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for IntegrityHash {
+    type Error = StratisError;
+
+    fn try_from(s: &str) -> StratisResult<IntegrityHash> {
+        match s {
+            "crc32c" => Ok(IntegrityHash::Crc32c),
+            "sha256" => Ok(IntegrityHash::Sha256),
+            _ => Err(StratisError::Msg(format!(
+                "{s} is an invalid integrity hash"
+            ))),
+        }
+    }
+}
+
+impl Display for IntegrityHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.algorithm())
+    }
+}
+
+/// How space for the cap device is allocated from the blockdevs in the data
+/// tier of a pool.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AllocationPolicy {
+    /// Each new extent is allocated from the blockdevs one after another.
+    #[default]
+    Linear,
+    /// Each new extent is striped across as many blockdevs as possible in
+    /// chunks of the given size.
+    Striped { stripe_size: Sectors },
+}
+
+impl AllocationPolicy {
+    /// The smallest stripe size accepted by the kernel on all supported
+    /// architectures.
+    const MIN_STRIPE_SIZE: Sectors = Sectors(8);
+
+    /// A striped allocation policy with the given stripe size. Return an
+    /// error if the stripe size is not a power of two or is smaller than
+    /// 4 KiB.
+    pub fn striped(stripe_size: Sectors) -> StratisResult<AllocationPolicy> {
+        if !stripe_size.is_power_of_two() || stripe_size < Self::MIN_STRIPE_SIZE {
+            return Err(StratisError::Msg(format!(
+                "Stripe size {stripe_size} must be a power of two no less than {}",
+                Self::MIN_STRIPE_SIZE
+            )));
+        }
+        Ok(AllocationPolicy::Striped { stripe_size })
+    }
+
+    /// The stripe size, if the policy is striped.
+    pub fn stripe_size(self) -> Option<Sectors> {
+        match self {
+            AllocationPolicy::Linear => None,
+            AllocationPolicy::Striped { stripe_size } => Some(stripe_size),
+        }
+    }
+}
+
+impl Display for AllocationPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllocationPolicy::Linear => write!(f, "linear"),
+            AllocationPolicy::Striped { stripe_size } => {
+                write!(f, "striped with stripe size {stripe_size}")
+            }
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 pub struct Name(String);
 
@@ -197,6 +629,108 @@ impl fmt::Display for Name {
     }
 }
 
+/// The minimum interval between snapshots taken according to a snapshot
+/// schedule. Schedules are checked once a minute.
+pub const MIN_SNAPSHOT_INTERVAL: u64 = 60;
+
+/// A schedule by which snapshots of a filesystem are taken automatically,
+/// together with the number of those snapshots to retain.
+///
+/// A snapshot is retained if it is the newest scheduled snapshot in one of
+/// the keep_hourly most recent hours, the keep_daily most recent days, or the
+/// keep_weekly most recent weeks that contain a scheduled snapshot.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotSchedule {
+    /// The number of seconds between snapshots
+    pub interval: u64,
+    pub keep_hourly: u32,
+    pub keep_daily: u32,
+    pub keep_weekly: u32,
+}
+
+impl SnapshotSchedule {
+    /// Create a snapshot schedule, checking that the interval is not too short
+    /// and that at least some snapshots are retained.
+    pub fn new(
+        interval: u64,
+        keep_hourly: u32,
+        keep_daily: u32,
+        keep_weekly: u32,
+    ) -> StratisResult<SnapshotSchedule> {
+        if interval < MIN_SNAPSHOT_INTERVAL {
+            return Err(StratisError::Msg(format!(
+                "The snapshot interval must be at least {MIN_SNAPSHOT_INTERVAL} seconds"
+            )));
+        }
+        if keep_hourly == 0 && keep_daily == 0 && keep_weekly == 0 {
+            return Err(StratisError::Msg(
+                "A snapshot schedule must retain at least one hourly, daily, or weekly snapshot"
+                    .to_string(),
+            ));
+        }
+        Ok(SnapshotSchedule {
+            interval,
+            keep_hourly,
+            keep_daily,
+            keep_weekly,
+        })
+    }
+}
+
+impl Display for SnapshotSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "every {} seconds, keeping {} hourly, {} daily, and {} weekly snapshots",
+            self.interval, self.keep_hourly, self.keep_daily, self.keep_weekly
+        )
+    }
+}
+
+/// The outcome of the most recent run of the snapshot schedule of a
+/// filesystem.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SnapshotScheduleStatus {
+    pub time: DateTime<Utc>,
+    /// The error that prevented the run from completing, if any
+    pub error: Option<String>,
+}
+
+impl Display for SnapshotScheduleStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self.time.to_rfc3339_opts(SecondsFormat::Secs, true);
+        match self.error {
+            Some(ref e) => write!(f, "failed at {time}: {e}"),
+            None => write!(f, "succeeded at {time}"),
+        }
+    }
+}
+
+/// The filesystems that were created and destroyed when the snapshot
+/// schedules of the filesystems in a pool were run.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct SnapshotScheduleRun {
+    pub created: Vec<FilesystemUuid>,
+    pub destroyed: Vec<FilesystemUuid>,
+}
+
+/// The space mapped by the thin device of a filesystem, divided into the
+/// space that is mapped by no other thin device, and so would be freed if the
+/// filesystem were destroyed, and the space that is shared with other thin
+/// devices, e.g., snapshots.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FilesystemSpaceUsage {
+    pub exclusive: Bytes,
+    pub shared: Bytes,
+}
+
+impl FilesystemSpaceUsage {
+    /// The total space mapped by the thin device.
+    pub fn mapped(&self) -> Bytes {
+        self.exclusive + self.shared
+    }
+}
+
 /// The type of report for which to query.
 ///
 /// NOTE: `EngineState` is no longer an option and is now supported in the Manager D-Bus API.
@@ -2,7 +2,13 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use crate::{jsonrpc::client::utils::to_suffix_repr, stratis::StratisResult};
+use std::collections::HashMap;
+
+use crate::{
+    engine::SnapshotSchedule,
+    jsonrpc::client::utils::to_suffix_repr,
+    stratis::{StratisError, StratisResult},
+};
 
 // stratis-min filesystem create
 pub fn filesystem_create(pool_name: String, filesystem_name: String) -> StratisResult<()> {
@@ -12,6 +18,9 @@ pub fn filesystem_create(pool_name: String, filesystem_name: String) -> StratisR
 // stratis-min filesystem [list]
 pub fn filesystem_list() -> StratisResult<()> {
     let (pool_names, fs_names, used, created, paths, uuids) = do_request!(FsList);
+    let space_usage = do_request!(FsSpaceUsage)
+        .into_iter()
+        .collect::<HashMap<_, _>>();
     let used_formatted: Vec<_> = used
         .into_iter()
         .map(|u_opt| {
@@ -20,12 +29,21 @@ pub fn filesystem_list() -> StratisResult<()> {
                 .unwrap_or_else(|| "FAILURE".to_string())
         })
         .collect();
+    let (exclusive_formatted, shared_formatted): (Vec<_>, Vec<_>) = uuids
+        .iter()
+        .map(|u| match space_usage.get(u).cloned().flatten() {
+            Some((exclusive, shared)) => (to_suffix_repr(exclusive), to_suffix_repr(shared)),
+            None => ("N/A".to_string(), "N/A".to_string()),
+        })
+        .unzip();
     let devices_formatted: Vec<_> = paths.into_iter().map(|p| p.display().to_string()).collect();
     let uuids_formatted: Vec<_> = uuids.into_iter().map(|u| u.to_string()).collect();
     print_table!(
         "Pool Name", pool_names, "<";
         "Name", fs_names, "<";
         "Used", used_formatted, "<";
+        "Exclusive", exclusive_formatted, "<";
+        "Shared", shared_formatted, "<";
         "Created", created, "<";
         "Device", devices_formatted, "<";
         "UUID", uuids_formatted, "<"
@@ -46,3 +64,38 @@ pub fn filesystem_rename(
 ) -> StratisResult<()> {
     do_request_standard!(FsRename, pool_name, filesystem_name, new_filesystem_name)
 }
+
+// stratis-min filesystem set-snapshot-schedule/unset-snapshot-schedule
+pub fn filesystem_set_snapshot_schedule(
+    pool_name: String,
+    filesystem_name: String,
+    schedule: Option<SnapshotSchedule>,
+) -> StratisResult<()> {
+    do_request_standard!(FsSetSnapshotSchedule, pool_name, filesystem_name, schedule)
+}
+
+// stratis-min filesystem snapshot-schedule
+pub fn filesystem_snapshot_schedule(
+    pool_name: String,
+    filesystem_name: String,
+) -> StratisResult<()> {
+    let ((schedule, status), rc, rs) = do_request!(FsSnapshotSchedule, pool_name, filesystem_name);
+    if rc != 0 {
+        return Err(StratisError::Msg(rs));
+    }
+    println!(
+        "Schedule: {}",
+        schedule
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "Not set".to_string())
+    );
+    println!(
+        "Last run: {}",
+        match status {
+            Some((time, None)) => format!("succeeded at {time}"),
+            Some((time, Some(err))) => format!("failed at {time}: {err}"),
+            None => "Not run".to_string(),
+        }
+    );
+    Ok(())
+}
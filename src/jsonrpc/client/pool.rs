@@ -2,14 +2,17 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 use nix::unistd::{pipe, write};
 
 use serde_json::Value;
 
 use crate::{
-    engine::{EncryptionInfo, KeyDescription, PoolIdentifier, PoolUuid, UnlockMethod},
+    engine::{
+        CacheSettings, CacheStats, CryptParams, DevUuid, EncryptionInfo, EraseMode, IntegrityHash,
+        KeyDescription, PoolIdentifier, PoolUuid, Redundancy, UnlockMethod,
+    },
     jsonrpc::client::utils::{prompt_password, to_suffix_repr},
     print_table,
     stratis::{StratisError, StratisResult},
@@ -19,9 +22,20 @@ use crate::{
 pub fn pool_create(
     name: String,
     blockdevs: Vec<PathBuf>,
+    redundancy: Redundancy,
     enc_info: Option<EncryptionInfo>,
+    crypt_params: Option<CryptParams>,
+    integrity: Option<IntegrityHash>,
 ) -> StratisResult<()> {
-    do_request_standard!(PoolCreate, name, blockdevs, enc_info)
+    do_request_standard!(
+        PoolCreate,
+        name,
+        blockdevs,
+        redundancy,
+        enc_info,
+        crypt_params,
+        integrity
+    )
 }
 
 // stratis-min pool start
@@ -50,8 +64,17 @@ pub fn pool_stop(id: PoolIdentifier<PoolUuid>) -> StratisResult<()> {
 }
 
 // stratis-min pool init-cache
-pub fn pool_init_cache(name: String, paths: Vec<PathBuf>) -> StratisResult<()> {
-    do_request_standard!(PoolInitCache, name, paths)
+pub fn pool_init_cache(
+    name: String,
+    paths: Vec<PathBuf>,
+    settings: CacheSettings,
+) -> StratisResult<()> {
+    do_request_standard!(PoolInitCache, name, paths, settings)
+}
+
+// stratis-min pool set-cache
+pub fn pool_set_cache_settings(name: String, settings: CacheSettings) -> StratisResult<()> {
+    do_request_standard!(PoolSetCacheSettings, name, settings)
 }
 
 // stratis-min pool init-cache
@@ -64,14 +87,64 @@ pub fn pool_add_data(name: String, paths: Vec<PathBuf>) -> StratisResult<()> {
     do_request_standard!(PoolAddData, name, paths)
 }
 
+// stratis-min pool remove-data
+pub fn pool_remove_data(name: String, paths: Vec<PathBuf>) -> StratisResult<()> {
+    do_request_standard!(PoolRemoveData, name, paths)
+}
+
+// stratis-min pool replace-data
+pub fn pool_replace_data(name: String, old: PathBuf, new: PathBuf) -> StratisResult<()> {
+    do_request_standard!(PoolReplaceData, name, old, new)
+}
+
+// stratis-min pool encrypt
+pub fn pool_encrypt(
+    name: String,
+    enc_info: EncryptionInfo,
+    crypt_params: Option<CryptParams>,
+) -> StratisResult<()> {
+    do_request_standard!(PoolEncrypt, name, enc_info, crypt_params)
+}
+
+// stratis-min pool reencrypt
+pub fn pool_reencrypt(name: String, crypt_params: Option<CryptParams>) -> StratisResult<()> {
+    do_request_standard!(PoolReencrypt, name, crypt_params)
+}
+
+// stratis-min pool back-up-headers
+pub fn pool_back_up_headers(name: String, archive_path: PathBuf) -> StratisResult<()> {
+    do_request_standard!(PoolBackUpHeaders, name, archive_path)
+}
+
+// stratis-min pool restore-header
+pub fn pool_restore_header(
+    pool_uuid: PoolUuid,
+    dev_uuid: DevUuid,
+    physical_path: PathBuf,
+    archive_path: PathBuf,
+) -> StratisResult<()> {
+    do_request_standard!(
+        PoolRestoreHeader,
+        pool_uuid,
+        dev_uuid,
+        physical_path,
+        archive_path
+    )
+}
+
 // stratis-min pool add-cache
 pub fn pool_add_cache(name: String, paths: Vec<PathBuf>) -> StratisResult<()> {
     do_request_standard!(PoolAddCache, name, paths)
 }
 
+// stratis-min pool remove-cache
+pub fn pool_remove_cache(name: String) -> StratisResult<()> {
+    do_request_standard!(PoolRemoveCache, name)
+}
+
 // stratis-min pool destroy
-pub fn pool_destroy(name: String) -> StratisResult<()> {
-    do_request_standard!(PoolDestroy, name)
+pub fn pool_destroy(name: String, erase_mode: Option<EraseMode>) -> StratisResult<()> {
+    do_request_standard!(PoolDestroy, name, erase_mode)
 }
 
 fn size_string(sizes: Vec<(u128, Option<u128>)>) -> Vec<String> {
@@ -95,6 +168,29 @@ fn size_string(sizes: Vec<(u128, Option<u128>)>) -> Vec<String> {
         .collect()
 }
 
+/// Format the fraction of hits among all accesses as a percentage.
+fn hit_ratio_string(hits: u64, misses: u64) -> String {
+    match hits.checked_add(misses) {
+        Some(0) | None => "-".to_string(),
+        Some(total) => format!("{:.1}%", hits as f64 * 100.0 / total as f64),
+    }
+}
+
+fn cache_stats_string(cache_stats: Vec<Option<CacheStats>>) -> Vec<String> {
+    cache_stats
+        .into_iter()
+        .map(|stats| match stats {
+            Some(s) => format!(
+                "{} / {} / {}",
+                hit_ratio_string(s.read_hits, s.read_misses),
+                hit_ratio_string(s.write_hits, s.write_misses),
+                s.dirty_blocks,
+            ),
+            None => "N/A".to_string(),
+        })
+        .collect()
+}
+
 fn properties_string(properties: Vec<(bool, bool)>) -> Vec<String> {
     properties
         .into_iter()
@@ -109,12 +205,22 @@ fn properties_string(properties: Vec<(bool, bool)>) -> Vec<String> {
 // stratis-min pool [list]
 pub fn pool_list() -> StratisResult<()> {
     let (names, sizes, properties, uuids) = do_request!(PoolList);
+    let cache_stats = do_request!(PoolCacheStats)
+        .into_iter()
+        .collect::<HashMap<_, _>>();
     let physical_col = size_string(sizes);
     let properties_col = properties_string(properties);
+    let cache_col = cache_stats_string(
+        uuids
+            .iter()
+            .map(|u| cache_stats.get(u).cloned().flatten())
+            .collect(),
+    );
     print_table!(
         "Name", names, "<";
         "Total Physical", physical_col, ">";
         "Properties", properties_col, ">";
+        "Cache Read / Write Hits / Dirty", cache_col, ">";
         "UUID", uuids.iter().map(|u| u.to_string()).collect::<Vec<_>>(), ">"
     );
 
@@ -204,3 +310,33 @@ pub fn pool_rebind_keyring(
 pub fn pool_rebind_clevis(id: PoolIdentifier<PoolUuid>) -> StratisResult<()> {
     do_request_standard!(PoolRebindClevis, id)
 }
+
+pub fn pool_add_keyring_binding(
+    id: PoolIdentifier<PoolUuid>,
+    key_desc: KeyDescription,
+) -> StratisResult<()> {
+    do_request_standard!(PoolAddKeyringBinding, id, key_desc)
+}
+
+pub fn pool_remove_keyring_binding(
+    id: PoolIdentifier<PoolUuid>,
+    key_desc: KeyDescription,
+) -> StratisResult<()> {
+    do_request_standard!(PoolRemoveKeyringBinding, id, key_desc)
+}
+
+// stratis-min pool keyring-bindings
+pub fn pool_keyring_bindings(id: PoolIdentifier<PoolUuid>) -> StratisResult<()> {
+    let (key_descs, rc, rs): (Vec<KeyDescription>, u16, String) =
+        do_request!(PoolKeyringBindings, id);
+    if rc != 0 {
+        Err(StratisError::Msg(rs))
+    } else {
+        let key_desc_strings = key_descs
+            .into_iter()
+            .map(|kd| kd.as_application_str().to_string())
+            .collect::<Vec<_>>();
+        print_table!("Key Description", key_desc_strings, "<");
+        Ok(())
+    }
+}
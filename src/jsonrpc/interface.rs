@@ -9,7 +9,9 @@ use std::{os::unix::io::RawFd, path::PathBuf};
 use serde_json::Value;
 
 use crate::engine::{
-    EncryptionInfo, FilesystemUuid, KeyDescription, PoolIdentifier, PoolUuid, UnlockMethod,
+    CacheSettings, CacheStats, CryptParams, DevUuid, EncryptionInfo, EraseMode, FilesystemUuid,
+    IntegrityHash, KeyDescription, PoolIdentifier, PoolUuid, Redundancy, SnapshotSchedule,
+    UnlockMethod,
 };
 
 pub type PoolListType = (
@@ -29,27 +31,49 @@ pub type FsListType = (
     Vec<PathBuf>,
     Vec<FilesystemUuid>,
 );
+// The schedule of a filesystem and, if it has run, the time of its last run
+// and the error with which it failed, if any.
+pub type FsSnapshotScheduleType = (Option<SnapshotSchedule>, Option<(String, Option<String>)>);
 
 #[derive(Serialize, Deserialize)]
 pub enum StratisParamType {
     KeySet(KeyDescription),
     KeyUnset(KeyDescription),
     KeyList,
-    PoolCreate(String, Vec<PathBuf>, Option<EncryptionInfo>),
+    PoolCreate(
+        String,
+        Vec<PathBuf>,
+        Redundancy,
+        Option<EncryptionInfo>,
+        Option<CryptParams>,
+        Option<IntegrityHash>,
+    ),
     PoolRename(String, String),
     PoolAddData(String, Vec<PathBuf>),
-    PoolInitCache(String, Vec<PathBuf>),
+    PoolRemoveData(String, Vec<PathBuf>),
+    PoolReplaceData(String, PathBuf, PathBuf),
+    PoolEncrypt(String, EncryptionInfo, Option<CryptParams>),
+    PoolReencrypt(String, Option<CryptParams>),
+    PoolBackUpHeaders(String, PathBuf),
+    PoolRestoreHeader(PoolUuid, DevUuid, PathBuf, PathBuf),
+    PoolInitCache(String, Vec<PathBuf>, CacheSettings),
+    PoolSetCacheSettings(String, CacheSettings),
     PoolAddCache(String, Vec<PathBuf>),
-    PoolDestroy(String),
+    PoolRemoveCache(String),
+    PoolDestroy(String, Option<EraseMode>),
     PoolStart(PoolIdentifier<PoolUuid>, Option<UnlockMethod>),
     PoolStop(PoolIdentifier<PoolUuid>),
     PoolList,
+    PoolCacheStats,
     PoolBindKeyring(PoolIdentifier<PoolUuid>, KeyDescription),
     PoolBindClevis(PoolIdentifier<PoolUuid>, String, Value),
     PoolUnbindKeyring(PoolIdentifier<PoolUuid>),
     PoolUnbindClevis(PoolIdentifier<PoolUuid>),
     PoolRebindKeyring(PoolIdentifier<PoolUuid>, KeyDescription),
     PoolRebindClevis(PoolIdentifier<PoolUuid>),
+    PoolAddKeyringBinding(PoolIdentifier<PoolUuid>, KeyDescription),
+    PoolRemoveKeyringBinding(PoolIdentifier<PoolUuid>, KeyDescription),
+    PoolKeyringBindings(PoolIdentifier<PoolUuid>),
     PoolIsEncrypted(PoolIdentifier<PoolUuid>),
     PoolIsStopped(PoolIdentifier<PoolUuid>),
     PoolIsBound(PoolIdentifier<PoolUuid>),
@@ -59,6 +83,9 @@ pub enum StratisParamType {
     FsDestroy(String, String),
     FsRename(String, String, String),
     FsList,
+    FsSpaceUsage,
+    FsSetSnapshotSchedule(String, String, Option<SnapshotSchedule>),
+    FsSnapshotSchedule(String, String),
     Report,
 }
 
@@ -77,18 +104,30 @@ pub enum StratisRet {
     PoolCreate((bool, u16, String)),
     PoolRename((bool, u16, String)),
     PoolAddData((bool, u16, String)),
+    PoolRemoveData((bool, u16, String)),
+    PoolReplaceData((bool, u16, String)),
+    PoolEncrypt((bool, u16, String)),
+    PoolReencrypt((bool, u16, String)),
+    PoolBackUpHeaders((bool, u16, String)),
+    PoolRestoreHeader((bool, u16, String)),
     PoolInitCache((bool, u16, String)),
+    PoolSetCacheSettings((bool, u16, String)),
     PoolAddCache((bool, u16, String)),
+    PoolRemoveCache((bool, u16, String)),
     PoolDestroy((bool, u16, String)),
     PoolStart((bool, u16, String)),
     PoolStop((bool, u16, String)),
     PoolList(PoolListType),
+    PoolCacheStats(Vec<(PoolUuid, Option<CacheStats>)>),
     PoolBindKeyring((bool, u16, String)),
     PoolBindClevis((bool, u16, String)),
     PoolUnbindKeyring((bool, u16, String)),
     PoolUnbindClevis((bool, u16, String)),
     PoolRebindKeyring((bool, u16, String)),
     PoolRebindClevis((bool, u16, String)),
+    PoolAddKeyringBinding((bool, u16, String)),
+    PoolRemoveKeyringBinding((bool, u16, String)),
+    PoolKeyringBindings((Vec<KeyDescription>, u16, String)),
     PoolIsEncrypted((bool, u16, String)),
     PoolIsStopped((bool, u16, String)),
     PoolIsBound((bool, u16, String)),
@@ -96,7 +135,10 @@ pub enum StratisRet {
     PoolClevisPin((Option<String>, u16, String)),
     FsCreate((bool, u16, String)),
     FsList(FsListType),
+    FsSpaceUsage(Vec<(FilesystemUuid, Option<(u128, u128)>)>),
     FsDestroy((bool, u16, String)),
     FsRename((bool, u16, String)),
+    FsSetSnapshotSchedule((bool, u16, String)),
+    FsSnapshotSchedule((FsSnapshotScheduleType, u16, String)),
     Report(Value),
 }
@@ -0,0 +1,200 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Implementation of the systemd password agent protocol, which allows
+// stratisd-min to ask for a passphrase through whatever agent is running,
+// e.g., Plymouth at boot or the console agent.
+// See https://systemd.io/PASSWORD_AGENTS/.
+
+use std::{
+    fs::{self, create_dir_all, remove_file, rename},
+    io,
+    os::unix::{io::RawFd, net::UnixDatagram},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use libcryptsetup_rs::SafeMemHandle;
+use nix::{
+    time::{clock_gettime, ClockId},
+    unistd::{close, getpid, pipe, write},
+};
+use uuid::Uuid;
+
+use crate::{
+    engine::MAX_STRATIS_PASS_SIZE,
+    stratis::{StratisError, StratisResult},
+};
+
+/// The directory that systemd password agents watch for requests.
+const ASK_PASSWORD_DIR: &str = "/run/systemd/ask-password";
+
+/// How long to wait for an answer from a password agent.
+pub const ASK_PASSWORD_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Removes the files of a password request when the request is done.
+struct AskFiles {
+    ask: PathBuf,
+    socket: PathBuf,
+}
+
+impl Drop for AskFiles {
+    fn drop(&mut self) {
+        for path in [&self.ask, &self.socket] {
+            if let Err(e) = remove_file(path) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    warn!(
+                        "Failed to remove password request file {}: {}",
+                        path.display(),
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Format the contents of a password request file.
+fn ask_file_contents(id: &str, message: &str, socket: &Path, not_after: u64) -> String {
+    format!(
+        "[Ask]\nPID={}\nSocket={}\nAcceptCached=0\nEcho=0\nNotAfter={not_after}\nId={id}\nMessage={message}\n",
+        getpid(),
+        socket.display(),
+    )
+}
+
+/// The value of CLOCK_MONOTONIC in microseconds after the given timeout.
+fn monotonic_deadline(timeout: Duration) -> StratisResult<u64> {
+    let now = Duration::from(clock_gettime(ClockId::CLOCK_MONOTONIC)?);
+    u64::try_from((now + timeout).as_micros())
+        .map_err(|_| StratisError::Msg("Password request deadline is out of range".to_string()))
+}
+
+/// Extract the passphrase from an answer to a password request, which is
+/// "+" followed by the passphrase, or "-" if the request was cancelled.
+/// Return an error if the passphrase is longer than MAX_STRATIS_PASS_SIZE.
+fn parse_answer<'a>(id: &str, answer: &'a [u8]) -> StratisResult<&'a [u8]> {
+    match answer.split_first() {
+        Some((b'+', passphrase)) if passphrase.len() > MAX_STRATIS_PASS_SIZE => {
+            Err(StratisError::Msg(format!(
+                "The passphrase entered for request {id} exceeds the maximum length of {MAX_STRATIS_PASS_SIZE} bytes"
+            )))
+        }
+        Some((b'+', passphrase)) => Ok(passphrase),
+        Some((b'-', _)) | None => Err(StratisError::Msg(format!(
+            "The passphrase request {id} was cancelled"
+        ))),
+        Some(_) => Err(StratisError::Msg(format!(
+            "Received a malformed answer to passphrase request {id}"
+        ))),
+    }
+}
+
+/// Ask the running systemd password agents for a passphrase and wait at most
+/// timeout for an answer. The request is identified by id and the message is
+/// displayed to the user. Return the read end of a pipe from which the
+/// passphrase can be read; the caller is responsible for closing it.
+pub fn ask_password(id: &str, message: &str, timeout: Duration) -> StratisResult<RawFd> {
+    create_dir_all(ASK_PASSWORD_DIR)?;
+
+    let suffix = Uuid::new_v4().simple().to_string();
+    let files = AskFiles {
+        ask: [ASK_PASSWORD_DIR, &format!("ask.{suffix}")]
+            .iter()
+            .collect(),
+        socket: [ASK_PASSWORD_DIR, &format!("sck.{suffix}")]
+            .iter()
+            .collect(),
+    };
+
+    let socket = UnixDatagram::bind(&files.socket)?;
+    socket.set_read_timeout(Some(timeout))?;
+
+    // Agents watch for the ask file to be moved into place, so it must be
+    // complete when it appears.
+    let tmp: PathBuf = [ASK_PASSWORD_DIR, &format!("tmp.{suffix}")]
+        .iter()
+        .collect();
+    fs::write(
+        &tmp,
+        ask_file_contents(id, message, &files.socket, monotonic_deadline(timeout)?),
+    )?;
+    rename(&tmp, &files.ask)?;
+
+    // A datagram that does not fit in the buffer is truncated silently, so
+    // the buffer has room for one byte more than the longest valid answer,
+    // "+" followed by a passphrase of MAX_STRATIS_PASS_SIZE bytes; an answer
+    // that fills it is too long.
+    let mut answer = SafeMemHandle::alloc(MAX_STRATIS_PASS_SIZE + 2)?;
+    let len = match socket.recv(answer.as_mut()) {
+        Ok(len) => len,
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            return Err(StratisError::Msg(format!(
+                "No passphrase was entered for request {id} within {} seconds",
+                timeout.as_secs()
+            )));
+        }
+        Err(e) => return Err(StratisError::from(e)),
+    };
+    drop(files);
+
+    let passphrase = parse_answer(id, &answer.as_ref()[..len])?;
+
+    let (read_end, write_end) = pipe()?;
+    let res = write(write_end, passphrase);
+    close(write_end)?;
+    if let Err(e) = res {
+        close(read_end)?;
+        return Err(StratisError::from(e));
+    }
+    Ok(read_end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Verify that the request contains all the keys that password agents
+    /// require.
+    fn test_ask_file_contents() {
+        let contents = ask_file_contents(
+            "stratis:id",
+            "Enter passphrase",
+            Path::new("/run/systemd/ask-password/sck.a"),
+            1000,
+        );
+        assert!(contents.starts_with("[Ask]\n"));
+        for key in [
+            "PID=",
+            "Socket=/run/systemd/ask-password/sck.a\n",
+            "NotAfter=1000\n",
+            "Id=stratis:id\n",
+            "Message=Enter passphrase\n",
+        ] {
+            assert!(contents.contains(key));
+        }
+    }
+
+    #[test]
+    /// Verify that a passphrase of the maximum length is accepted and that a
+    /// longer one or a cancelled request is rejected.
+    fn test_parse_answer() {
+        let mut answer = vec![b'+'];
+        answer.extend(vec![b'a'; MAX_STRATIS_PASS_SIZE]);
+        assert_eq!(parse_answer("stratis:id", &answer).unwrap(), &answer[1..]);
+
+        answer.push(b'a');
+        assert_matches!(parse_answer("stratis:id", &answer), Err(_));
+
+        assert_matches!(parse_answer("stratis:id", b"-"), Err(_));
+        assert_matches!(parse_answer("stratis:id", b""), Err(_));
+        assert_matches!(parse_answer("stratis:id", b"passphrase"), Err(_));
+    }
+}
@@ -8,8 +8,8 @@ use chrono::SecondsFormat;
 use tokio::task::block_in_place;
 
 use crate::{
-    engine::{Engine, EngineAction, Name, PoolIdentifier},
-    jsonrpc::interface::FsListType,
+    engine::{Engine, EngineAction, FilesystemUuid, Name, PoolIdentifier, SnapshotSchedule},
+    jsonrpc::interface::{FsListType, FsSnapshotScheduleType},
     stratis::{StratisError, StratisResult},
 };
 
@@ -58,6 +58,21 @@ pub async fn filesystem_list(engine: Arc<dyn Engine>) -> FsListType {
     )
 }
 
+// stratis-min filesystem [list]
+pub async fn filesystem_space_usage(
+    engine: Arc<dyn Engine>,
+) -> Vec<(FilesystemUuid, Option<(u128, u128)>)> {
+    let guard = engine.pools().await;
+    guard
+        .iter()
+        .flat_map(|(_, _, pool)| {
+            pool.filesystems()
+                .into_iter()
+                .map(|(_, uuid, fs)| (uuid, fs.space_usage().map(|u| (*u.exclusive, *u.shared))))
+        })
+        .collect()
+}
+
 // stratis-min filesystem destroy
 pub async fn filesystem_destroy<'a>(
     engine: Arc<dyn Engine>,
@@ -94,3 +109,48 @@ pub async fn filesystem_rename<'a>(
             .is_changed())
     })
 }
+
+// stratis-min filesystem set-snapshot-schedule/unset-snapshot-schedule
+pub async fn filesystem_set_snapshot_schedule<'a>(
+    engine: Arc<dyn Engine>,
+    pool_name: &'a str,
+    fs_name: &'a str,
+    schedule: Option<SnapshotSchedule>,
+) -> StratisResult<bool> {
+    // The schedule was deserialized without validation.
+    let schedule = schedule
+        .map(|s| SnapshotSchedule::new(s.interval, s.keep_hourly, s.keep_daily, s.keep_weekly))
+        .transpose()?;
+    let mut pool = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+    let (uuid, _) = pool
+        .get_filesystem_by_name(&Name::new(fs_name.to_string()))
+        .ok_or_else(|| StratisError::Msg(format!("No filesystem named {fs_name} found")))?;
+    block_in_place(|| Ok(pool.set_fs_snapshot_schedule(uuid, schedule)?.is_changed()))
+}
+
+// stratis-min filesystem snapshot-schedule
+pub async fn filesystem_snapshot_schedule<'a>(
+    engine: Arc<dyn Engine>,
+    pool_name: &'a str,
+    fs_name: &'a str,
+) -> StratisResult<FsSnapshotScheduleType> {
+    let pool = engine
+        .get_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+    let (_, fs) = pool
+        .get_filesystem_by_name(&Name::new(fs_name.to_string()))
+        .ok_or_else(|| StratisError::Msg(format!("No filesystem named {fs_name} found")))?;
+    Ok((
+        fs.snapshot_schedule(),
+        fs.snapshot_schedule_status().map(|status| {
+            (
+                status.time.to_rfc3339_opts(SecondsFormat::Secs, true),
+                status.error,
+            )
+        }),
+    ))
+}
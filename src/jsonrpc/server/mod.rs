@@ -5,6 +5,7 @@
 #[macro_use]
 mod utils;
 
+mod ask_password;
 mod filesystem;
 mod key;
 mod pool;
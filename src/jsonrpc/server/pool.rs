@@ -10,12 +10,16 @@ use serde_json::Value;
 
 use crate::{
     engine::{
-        BlockDevTier, CreateAction, DeleteAction, EncryptionInfo, Engine, EngineAction,
-        KeyDescription, Name, PoolIdentifier, PoolUuid, RenameAction, UnlockMethod,
+        BlockDevTier, CacheSettings, CacheStats, CreateAction, CryptParams, DeleteAction, DevUuid,
+        EncryptionInfo, Engine, EngineAction, EraseMode, IntegrityHash, JobProgress,
+        KeyDescription, Name, PoolIdentifier, PoolUuid, Redundancy, RenameAction, UnlockMethod,
     },
     jsonrpc::{
         interface::PoolListType,
-        server::key::{key_get_desc, key_set},
+        server::{
+            ask_password::{ask_password, ASK_PASSWORD_TIMEOUT},
+            key::{key_get_desc, key_set, key_unset},
+        },
     },
     stratis::{StratisError, StratisResult},
 };
@@ -27,11 +31,81 @@ pub async fn pool_start(
     unlock_method: Option<UnlockMethod>,
     prompt: Option<RawFd>,
 ) -> StratisResult<bool> {
+    let mut asked_key = None;
     if let (Some(fd), Some(kd)) = (prompt, key_get_desc(Arc::clone(&engine), id.clone()).await?) {
         key_set(engine.clone(), &kd, fd).await?;
+    } else if prompt.is_none() && unlock_method == Some(UnlockMethod::Keyring) {
+        asked_key = ask_for_passphrase(Arc::clone(&engine), &id).await?;
+    }
+
+    let res = match engine.start_pool(id.clone(), unlock_method.clone()).await {
+        // As a last resort, ask for the passphrase.
+        Err(e) if prompt.is_none() && unlock_method == Some(UnlockMethod::Any) => {
+            info!("Failed to start pool {} with any configured unlock method: {}; asking for a passphrase", id, e);
+            asked_key = ask_for_passphrase(Arc::clone(&engine), &id).await?;
+            if asked_key.is_some() {
+                engine.start_pool(id, Some(UnlockMethod::Keyring)).await
+            } else {
+                Err(e)
+            }
+        }
+        res => res,
+    };
+
+    match res {
+        Ok(action) => Ok(action.is_changed()),
+        Err(e) => {
+            // Do not leave a possibly mistyped passphrase in the keyring so that
+            // the next attempt asks again.
+            if let Some(kd) = asked_key {
+                if let Err(unset_err) = key_unset(engine, &kd).await {
+                    warn!(
+                        "Failed to remove key with key description {} from the keyring: {}",
+                        kd.as_application_str(),
+                        unset_err
+                    );
+                }
+            }
+            Err(e)
+        }
+    }
+}
+
+/// If the key for a locked pool is not in the keyring, ask a systemd password
+/// agent for the passphrase and set it in the keyring. Return the key
+/// description of the key that was set, if any.
+async fn ask_for_passphrase(
+    engine: Arc<dyn Engine>,
+    id: &PoolIdentifier<PoolUuid>,
+) -> StratisResult<Option<KeyDescription>> {
+    let (pool_uuid, kd) = {
+        let locked = engine.locked_pools().await;
+        let pool_uuid = match id {
+            PoolIdentifier::Uuid(u) => Some(*u),
+            PoolIdentifier::Name(n) => locked.name_to_uuid.get(n).copied(),
+        };
+        match pool_uuid.and_then(|u| locked.locked.get(&u).map(|info| (u, info))) {
+            Some((u, info)) => match info.info.key_description()? {
+                Some(kd) => (u, kd.clone()),
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        }
+    };
+
+    if engine.get_key_handler().await.list()?.contains(&kd) {
+        return Ok(None);
     }
 
-    Ok(engine.start_pool(id, unlock_method).await?.is_changed())
+    let fd = block_in_place(|| {
+        ask_password(
+            &format!("stratis:{pool_uuid}"),
+            &format!("Please enter the passphrase for Stratis pool {id}"),
+            ASK_PASSWORD_TIMEOUT,
+        )
+    })?;
+    key_set(engine, &kd, fd).await?;
+    Ok(Some(kd))
 }
 
 // stratis-min pool stop
@@ -47,10 +121,23 @@ pub async fn pool_create<'a>(
     engine: Arc<dyn Engine>,
     name: &'a str,
     blockdev_paths: &'a [&'a Path],
+    redundancy: Redundancy,
     enc_info: Option<&'a EncryptionInfo>,
+    crypt_params: Option<&'a CryptParams>,
+    integrity: Option<IntegrityHash>,
 ) -> StratisResult<bool> {
     Ok(
-        match engine.create_pool(name, blockdev_paths, enc_info).await? {
+        match engine
+            .create_pool(
+                name,
+                blockdev_paths,
+                redundancy,
+                enc_info,
+                crypt_params,
+                integrity,
+            )
+            .await?
+        {
             CreateAction::Created(_) => true,
             CreateAction::Identity => false,
         },
@@ -58,13 +145,22 @@ pub async fn pool_create<'a>(
 }
 
 // stratis-min pool destroy
-pub async fn pool_destroy(engine: Arc<dyn Engine>, name: &str) -> StratisResult<bool> {
+pub async fn pool_destroy(
+    engine: Arc<dyn Engine>,
+    name: &str,
+    erase_mode: Option<EraseMode>,
+) -> StratisResult<bool> {
     let uuid = engine
         .get_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
         .await
         .map(|g| g.as_tuple().1)
         .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
-    Ok(engine.destroy_pool(uuid).await?.is_changed())
+    let (action, erasure) = engine.destroy_pool(uuid, erase_mode).await?;
+    if let Some(erasure) = erasure {
+        let report = block_in_place(|| erasure.run(&JobProgress::default()))?;
+        info!("Erased the devices of pool {name}:\n{report}");
+    }
+    Ok(action.is_changed())
 }
 
 // stratis-min pool init-cache
@@ -72,13 +168,32 @@ pub async fn pool_init_cache<'a>(
     engine: Arc<dyn Engine>,
     name: &'a str,
     paths: &'a [&'a Path],
+    settings: CacheSettings,
 ) -> StratisResult<bool> {
     let mut guard = engine
         .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
         .await
         .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
     let (_, uuid, pool) = guard.as_mut_tuple();
-    block_in_place(|| Ok(pool.init_cache(uuid, name, paths, true)?.is_changed()))
+    block_in_place(|| {
+        Ok(pool
+            .init_cache(uuid, name, paths, true, settings)?
+            .is_changed())
+    })
+}
+
+// stratis-min pool set-cache
+pub async fn pool_set_cache_settings(
+    engine: Arc<dyn Engine>,
+    name: &str,
+    settings: CacheSettings,
+) -> StratisResult<bool> {
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (_, _, pool) = guard.as_mut_tuple();
+    block_in_place(|| Ok(pool.set_cache_settings(name, settings)?.is_changed()))
 }
 
 // stratis-min pool rename
@@ -108,6 +223,134 @@ pub async fn pool_add_data(
     add_blockdevs(engine, name, blockdevs, BlockDevTier::Data).await
 }
 
+// stratis-min pool remove-data
+pub async fn pool_remove_data(
+    engine: Arc<dyn Engine>,
+    name: &str,
+    blockdevs: &[&Path],
+) -> StratisResult<bool> {
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (_, uuid, pool) = guard.as_mut_tuple();
+    let dev_uuids = blockdevs
+        .iter()
+        .map(|path| {
+            pool.blockdevs()
+                .into_iter()
+                .find(|(_, tier, bd)| *tier == BlockDevTier::Data && bd.devnode() == *path)
+                .map(|(dev_uuid, _, _)| dev_uuid)
+                .ok_or_else(|| {
+                    StratisError::Msg(format!(
+                        "Device {} is not in the data tier of pool {name}",
+                        path.display()
+                    ))
+                })
+        })
+        .collect::<StratisResult<Vec<_>>>()?;
+    block_in_place(|| {
+        Ok(pool
+            .remove_blockdevs(uuid, name, &dev_uuids)?
+            .0
+            .is_changed())
+    })
+}
+
+// stratis-min pool replace-data
+pub async fn pool_replace_data(
+    engine: Arc<dyn Engine>,
+    name: &str,
+    old: &Path,
+    new: &Path,
+) -> StratisResult<bool> {
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (_, uuid, pool) = guard.as_mut_tuple();
+    let dev_uuid = pool
+        .blockdevs()
+        .into_iter()
+        .find(|(_, tier, bd)| *tier == BlockDevTier::Data && bd.devnode() == old)
+        .map(|(dev_uuid, _, _)| dev_uuid)
+        .ok_or_else(|| {
+            StratisError::Msg(format!(
+                "Device {} is not in the data tier of pool {name}",
+                old.display()
+            ))
+        })?;
+    block_in_place(|| {
+        Ok(pool
+            .replace_blockdev(uuid, name, dev_uuid, new)?
+            .0
+            .is_changed())
+    })
+}
+
+// stratis-min pool encrypt
+pub async fn pool_encrypt(
+    engine: Arc<dyn Engine>,
+    name: &str,
+    enc_info: &EncryptionInfo,
+    crypt_params: Option<&CryptParams>,
+) -> StratisResult<bool> {
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (_, uuid, pool) = guard.as_mut_tuple();
+    block_in_place(|| {
+        Ok(pool
+            .encrypt_pool(uuid, name, enc_info, crypt_params)?
+            .0
+            .is_changed())
+    })
+}
+
+// stratis-min pool reencrypt
+pub async fn pool_reencrypt(
+    engine: Arc<dyn Engine>,
+    name: &str,
+    crypt_params: Option<&CryptParams>,
+) -> StratisResult<bool> {
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (_, _, pool) = guard.as_mut_tuple();
+    block_in_place(|| Ok(pool.reencrypt_pool(name, crypt_params)?.0.is_changed()))
+}
+
+// stratis-min pool back-up-headers
+pub async fn pool_back_up_headers(
+    engine: Arc<dyn Engine>,
+    name: &str,
+    archive_path: &Path,
+) -> StratisResult<bool> {
+    let guard = engine
+        .get_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (_, uuid, pool) = guard.as_tuple();
+    block_in_place(|| pool.back_up_luks_headers(uuid, archive_path))?;
+    Ok(true)
+}
+
+// stratis-min pool restore-header
+pub async fn pool_restore_header(
+    engine: Arc<dyn Engine>,
+    pool_uuid: PoolUuid,
+    dev_uuid: DevUuid,
+    physical_path: &Path,
+    archive_path: &Path,
+) -> StratisResult<bool> {
+    engine
+        .restore_luks_header(pool_uuid, dev_uuid, physical_path, archive_path)
+        .await?;
+    Ok(true)
+}
+
 // stratis-min pool add-cache
 pub async fn pool_add_cache(
     engine: Arc<dyn Engine>,
@@ -117,6 +360,16 @@ pub async fn pool_add_cache(
     add_blockdevs(engine, name, blockdevs, BlockDevTier::Cache).await
 }
 
+// stratis-min pool remove-cache
+pub async fn pool_remove_cache(engine: Arc<dyn Engine>, name: &str) -> StratisResult<bool> {
+    let uuid = engine
+        .get_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .map(|g| g.as_tuple().1)
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    Ok(engine.remove_cache(uuid).await?.is_changed())
+}
+
 async fn add_blockdevs<'a>(
     engine: Arc<dyn Engine>,
     name: &'a str,
@@ -164,6 +417,15 @@ pub async fn pool_list(engine: Arc<dyn Engine>) -> PoolListType {
         )
 }
 
+// stratis-min pool [list]
+pub async fn pool_cache_stats(engine: Arc<dyn Engine>) -> Vec<(PoolUuid, Option<CacheStats>)> {
+    let guard = engine.pools().await;
+    guard
+        .iter()
+        .map(|(_, u, p)| (*u, p.cache_stats()))
+        .collect()
+}
+
 // stratis-min pool bind keyring
 pub async fn pool_bind_keyring(
     engine: Arc<dyn Engine>,
@@ -269,6 +531,56 @@ pub async fn pool_rebind_clevis(
     Ok(true)
 }
 
+// stratis-min pool add-keyring-binding
+pub async fn pool_add_keyring_binding(
+    engine: Arc<dyn Engine>,
+    id: PoolIdentifier<PoolUuid>,
+    key_desc: &KeyDescription,
+) -> StratisResult<bool> {
+    let mut guard = engine
+        .get_mut_pool(id.clone())
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("Pool with {id} not found")))?;
+
+    let (_, _, pool) = guard.as_mut_tuple();
+    match pool.add_keyring_binding(key_desc)? {
+        CreateAction::Created(_key) => Ok(true),
+        CreateAction::Identity => Ok(false),
+    }
+}
+
+// stratis-min pool remove-keyring-binding
+pub async fn pool_remove_keyring_binding(
+    engine: Arc<dyn Engine>,
+    id: PoolIdentifier<PoolUuid>,
+    key_desc: &KeyDescription,
+) -> StratisResult<bool> {
+    let mut guard = engine
+        .get_mut_pool(id.clone())
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("Pool with {id} not found")))?;
+
+    let (_, _, pool) = guard.as_mut_tuple();
+    match pool.remove_keyring_binding(key_desc)? {
+        DeleteAction::Deleted(_key) => Ok(true),
+        DeleteAction::Identity => Ok(false),
+    }
+}
+
+// stratis-min pool keyring-bindings
+pub async fn pool_keyring_bindings(
+    engine: Arc<dyn Engine>,
+    id: PoolIdentifier<PoolUuid>,
+) -> StratisResult<Vec<KeyDescription>> {
+    let guard = engine
+        .get_pool(id.clone())
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("Pool with {id} not found")))?;
+
+    let (_, _, pool) = guard.as_tuple();
+    pool.keyring_bindings()
+}
+
 // stratis-min pool is-encrypted
 pub async fn pool_is_encrypted(
     engine: Arc<dyn Engine>,
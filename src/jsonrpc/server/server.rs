@@ -66,7 +66,14 @@ impl StratisParams {
                     Vec::new(),
                 )))
             }
-            StratisParamType::PoolCreate(name, paths, encryption_info) => {
+            StratisParamType::PoolCreate(
+                name,
+                paths,
+                redundancy,
+                encryption_info,
+                crypt_params,
+                integrity,
+            ) => {
                 expects_fd!(self.fd_opt, false);
                 let path_ref: Vec<_> = paths.iter().map(|p| p.as_path()).collect();
                 Ok(StratisRet::PoolCreate(stratis_result_to_return(
@@ -74,7 +81,10 @@ impl StratisParams {
                         engine,
                         name.as_str(),
                         path_ref.as_slice(),
+                        redundancy,
                         encryption_info.as_ref(),
+                        crypt_params.as_ref(),
+                        integrity,
                     )
                     .await,
                     false,
@@ -95,11 +105,76 @@ impl StratisParams {
                     false,
                 )))
             }
-            StratisParamType::PoolInitCache(name, paths) => {
+            StratisParamType::PoolRemoveData(name, paths) => {
+                expects_fd!(self.fd_opt, false);
+                let path_ref: Vec<_> = paths.iter().map(|p| p.as_path()).collect();
+                Ok(StratisRet::PoolRemoveData(stratis_result_to_return(
+                    pool::pool_remove_data(engine, name.as_str(), path_ref.as_slice()).await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolReplaceData(name, old, new) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolReplaceData(stratis_result_to_return(
+                    pool::pool_replace_data(engine, name.as_str(), old.as_path(), new.as_path())
+                        .await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolEncrypt(name, enc_info, crypt_params) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolEncrypt(stratis_result_to_return(
+                    pool::pool_encrypt(engine, name.as_str(), &enc_info, crypt_params.as_ref())
+                        .await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolReencrypt(name, crypt_params) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolReencrypt(stratis_result_to_return(
+                    pool::pool_reencrypt(engine, name.as_str(), crypt_params.as_ref()).await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolBackUpHeaders(name, archive_path) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolBackUpHeaders(stratis_result_to_return(
+                    pool::pool_back_up_headers(engine, name.as_str(), &archive_path).await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolRestoreHeader(
+                pool_uuid,
+                dev_uuid,
+                physical_path,
+                archive_path,
+            ) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolRestoreHeader(stratis_result_to_return(
+                    pool::pool_restore_header(
+                        engine,
+                        pool_uuid,
+                        dev_uuid,
+                        &physical_path,
+                        &archive_path,
+                    )
+                    .await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolInitCache(name, paths, settings) => {
                 expects_fd!(self.fd_opt, false);
                 let path_ref: Vec<_> = paths.iter().map(|p| p.as_path()).collect();
                 Ok(StratisRet::PoolInitCache(stratis_result_to_return(
-                    pool::pool_init_cache(engine, name.as_str(), path_ref.as_slice()).await,
+                    pool::pool_init_cache(engine, name.as_str(), path_ref.as_slice(), settings)
+                        .await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolSetCacheSettings(name, settings) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolSetCacheSettings(stratis_result_to_return(
+                    pool::pool_set_cache_settings(engine, name.as_str(), settings).await,
                     false,
                 )))
             }
@@ -111,10 +186,17 @@ impl StratisParams {
                     false,
                 )))
             }
-            StratisParamType::PoolDestroy(name) => {
+            StratisParamType::PoolRemoveCache(name) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolRemoveCache(stratis_result_to_return(
+                    pool::pool_remove_cache(engine, name.as_str()).await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolDestroy(name, erase_mode) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolDestroy(stratis_result_to_return(
-                    pool::pool_destroy(engine, name.as_str()).await,
+                    pool::pool_destroy(engine, name.as_str(), erase_mode).await,
                     false,
                 )))
             }
@@ -135,6 +217,12 @@ impl StratisParams {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolList(pool::pool_list(engine).await))
             }
+            StratisParamType::PoolCacheStats => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolCacheStats(
+                    pool::pool_cache_stats(engine).await,
+                ))
+            }
             StratisParamType::PoolBindKeyring(id, key_desc) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolBindKeyring(stratis_result_to_return(
@@ -177,6 +265,29 @@ impl StratisParams {
                     false,
                 )))
             }
+            StratisParamType::PoolAddKeyringBinding(id, key_desc) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolAddKeyringBinding(stratis_result_to_return(
+                    pool::pool_add_keyring_binding(engine, id, &key_desc).await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolRemoveKeyringBinding(id, key_desc) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolRemoveKeyringBinding(
+                    stratis_result_to_return(
+                        pool::pool_remove_keyring_binding(engine, id, &key_desc).await,
+                        false,
+                    ),
+                ))
+            }
+            StratisParamType::PoolKeyringBindings(id) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolKeyringBindings(stratis_result_to_return(
+                    pool::pool_keyring_bindings(engine, id).await,
+                    Vec::new(),
+                )))
+            }
             StratisParamType::PoolIsEncrypted(id) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolIsEncrypted(stratis_result_to_return(
@@ -225,6 +336,12 @@ impl StratisParams {
                     filesystem::filesystem_list(engine).await,
                 ))
             }
+            StratisParamType::FsSpaceUsage => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::FsSpaceUsage(
+                    filesystem::filesystem_space_usage(engine).await,
+                ))
+            }
             StratisParamType::FsDestroy(pool_name, fs_name) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::FsDestroy(stratis_result_to_return(
@@ -239,6 +356,23 @@ impl StratisParams {
                     false,
                 )))
             }
+            StratisParamType::FsSetSnapshotSchedule(pool_name, fs_name, schedule) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::FsSetSnapshotSchedule(stratis_result_to_return(
+                    filesystem::filesystem_set_snapshot_schedule(
+                        engine, &pool_name, &fs_name, schedule,
+                    )
+                    .await,
+                    false,
+                )))
+            }
+            StratisParamType::FsSnapshotSchedule(pool_name, fs_name) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::FsSnapshotSchedule(stratis_result_to_return(
+                    filesystem::filesystem_snapshot_schedule(engine, &pool_name, &fs_name).await,
+                    (None, None),
+                )))
+            }
             StratisParamType::Report => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::Report(report::report(engine).await))
@@ -6,14 +6,14 @@ use std::{sync::Arc, time::Duration};
 
 #[cfg(feature = "dbus_enabled")]
 use tokio::sync::mpsc::UnboundedSender;
-use tokio::{task::spawn, time::sleep};
+use tokio::{task::spawn, time::sleep, try_join};
 
 #[cfg(feature = "dbus_enabled")]
 use crate::dbus_api::DbusAction;
 use crate::{engine::Engine, stratis::errors::StratisResult};
 
 /// Runs checks on thin pool usage and filesystem usage to determine whether either
-/// need to be extended.
+/// need to be extended. Also refreshes the statistics of pool caches.
 async fn check_pool_and_fs(
     engine: Arc<dyn Engine>,
     #[cfg(feature = "dbus_enabled")] sender: UnboundedSender<DbusAction>,
@@ -67,18 +67,144 @@ async fn check_pool_and_fs(
     }
 }
 
+/// Takes the snapshots that are due according to the snapshot schedules of all
+/// filesystems and destroys the scheduled snapshots that are no longer retained.
+async fn run_snapshot_schedules(
+    engine: Arc<dyn Engine>,
+    #[cfg(feature = "dbus_enabled")] sender: UnboundedSender<DbusAction>,
+) {
+    loop {
+        trace!("Starting scheduled snapshots");
+        #[cfg(not(feature = "dbus_enabled"))]
+        {
+            let _ = engine.run_snapshot_schedules().await;
+        }
+        #[cfg(feature = "dbus_enabled")]
+        {
+            let runs = engine.run_snapshot_schedules().await;
+            for (pool_uuid, run) in runs {
+                if run.created.is_empty() && run.destroyed.is_empty() {
+                    continue;
+                }
+                if let Err(e) = sender.send(DbusAction::ScheduledSnapshots(pool_uuid, run)) {
+                    warn!(
+                        "Failed to update D-Bus API with information on scheduled snapshots: {}",
+                        e
+                    );
+                }
+            }
+        }
+        trace!("Scheduled snapshots finished");
+        sleep(Duration::from_secs(60)).await;
+    }
+}
+
+/// Recomputes the space mapped exclusively by each filesystem and the space
+/// that it shares with other filesystems. This requires reading all of the
+/// thin pool metadata, so it is done much less often than the other checks.
+async fn refresh_space_usage(
+    engine: Arc<dyn Engine>,
+    #[cfg(feature = "dbus_enabled")] sender: UnboundedSender<DbusAction>,
+) {
+    loop {
+        trace!("Starting filesystem space usage refresh");
+        #[cfg(not(feature = "dbus_enabled"))]
+        {
+            let _ = engine.refresh_space_usage().await;
+        }
+        #[cfg(feature = "dbus_enabled")]
+        {
+            let fs_diffs = engine.refresh_space_usage().await;
+            for action in DbusAction::from_fs_diffs(fs_diffs) {
+                if let Err(e) = sender.send(action) {
+                    warn!(
+                        "Failed to update D-Bus API with information on changed properties: {}",
+                        e
+                    );
+                }
+            }
+        }
+        trace!("Filesystem space usage refresh finished");
+        sleep(Duration::from_secs(300)).await;
+    }
+}
+
+/// Continues the online encryption or reencryption of pools. The data of each
+/// pool is processed in slices without holding the lock on the pool, and the
+/// progress is reported after each slice.
+async fn continue_reencryption(
+    engine: Arc<dyn Engine>,
+    #[cfg(feature = "dbus_enabled")] sender: UnboundedSender<DbusAction>,
+) {
+    loop {
+        trace!("Continuing pool reencryption");
+        #[cfg(not(feature = "dbus_enabled"))]
+        {
+            let _ = engine.continue_reencryption().await;
+        }
+        #[cfg(feature = "dbus_enabled")]
+        {
+            let pool_diffs = engine.continue_reencryption().await;
+            for action in DbusAction::from_pool_diffs(pool_diffs) {
+                if let Err(e) = sender.send(action) {
+                    warn!(
+                        "Failed to update D-Bus API with information on changed properties: {}",
+                        e
+                    );
+                }
+            }
+        }
+        trace!("Pool reencryption slice finished");
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Unlocks the encrypted pools for which new devices were discovered. This is
+/// done outside of udev event handling because unlocking a device may have to
+/// wait for a Tang server. The pools are set up when the udev events for the
+/// unlocked devices are handled.
+async fn unlock_pending_pools(engine: Arc<dyn Engine>) {
+    loop {
+        trace!("Unlocking pending pools");
+        engine.unlock_pending_pools().await;
+        trace!("Unlocking pending pools finished");
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
 /// Run all timed background tasks.
 ///
-/// Currently runs a timer to check thin pool and filesystem usage.
+/// Currently runs a timer to check thin pool and filesystem usage and to refresh
+/// cache statistics, a timer to run the snapshot schedules of filesystems, a
+/// timer to refresh the space usage of filesystems, a task that continues
+/// the online encryption or reencryption of pools, and a task that unlocks
+/// newly discovered encrypted pools.
 pub async fn run_timers(
     engine: Arc<dyn Engine>,
     #[cfg(feature = "dbus_enabled")] sender: UnboundedSender<DbusAction>,
 ) -> StratisResult<()> {
-    spawn(check_pool_and_fs(
-        engine,
-        #[cfg(feature = "dbus_enabled")]
-        sender,
-    ))
-    .await?;
+    try_join!(
+        spawn(check_pool_and_fs(
+            Arc::clone(&engine),
+            #[cfg(feature = "dbus_enabled")]
+            sender.clone(),
+        )),
+        spawn(run_snapshot_schedules(
+            Arc::clone(&engine),
+            #[cfg(feature = "dbus_enabled")]
+            sender.clone(),
+        )),
+        spawn(refresh_space_usage(
+            Arc::clone(&engine),
+            #[cfg(feature = "dbus_enabled")]
+            sender.clone(),
+        )),
+        spawn(continue_reencryption(
+            Arc::clone(&engine),
+            #[cfg(feature = "dbus_enabled")]
+            sender,
+        )),
+        spawn(unlock_pending_pools(engine))
+    )?;
     Ok(())
 }
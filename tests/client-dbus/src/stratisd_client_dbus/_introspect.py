@@ -11,15 +11,25 @@ SPECS = {
     <method name="CreatePool">
       <arg name="name" type="s" direction="in" />
       <arg name="devices" type="as" direction="in" />
+      <arg name="redundancy" type="(bq)" direction="in" />
       <arg name="key_desc" type="(bs)" direction="in" />
       <arg name="clevis_info" type="(b(ss))" direction="in" />
+      <arg name="cipher" type="(bs)" direction="in" />
+      <arg name="key_size" type="(bu)" direction="in" />
+      <arg name="sector_size" type="(bu)" direction="in" />
+      <arg name="pbkdf" type="(bs)" direction="in" />
+      <arg name="pbkdf_memory" type="(bu)" direction="in" />
+      <arg name="pbkdf_iterations" type="(bu)" direction="in" />
+      <arg name="pbkdf_parallel" type="(bu)" direction="in" />
+      <arg name="integrity" type="(bs)" direction="in" />
       <arg name="result" type="(b(oao))" direction="out" />
       <arg name="return_code" type="q" direction="out" />
       <arg name="return_string" type="s" direction="out" />
     </method>
     <method name="DestroyPool">
       <arg name="pool" type="o" direction="in" />
-      <arg name="result" type="(bs)" direction="out" />
+      <arg name="erase_mode" type="(bs)" direction="in" />
+      <arg name="result" type="(b(so))" direction="out" />
       <arg name="return_code" type="q" direction="out" />
       <arg name="return_string" type="s" direction="out" />
     </method>
@@ -92,6 +102,7 @@ SPECS = {
     <property name="InitializationTime" type="t" access="read">
       <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const" />
     </property>
+    <property name="IntegrityErrors" type="(bt)" access="read" />
     <property name="NewPhysicalSize" type="(bs)" access="read" />
     <property name="PhysicalPath" type="s" access="read">
       <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const" />
@@ -99,6 +110,7 @@ SPECS = {
     <property name="Pool" type="o" access="read">
       <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const" />
     </property>
+    <property name="Shrunk" type="b" access="read" />
     <property name="Tier" type="q" access="read">
       <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false" />
     </property>
@@ -123,12 +135,22 @@ SPECS = {
     <property name="Devnode" type="s" access="read">
       <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="invalidates" />
     </property>
+    <property name="Exclusive" type="(bs)" access="read" />
     <property name="Name" type="s" access="read" />
+    <property name="Origin" type="(bs)" access="read">
+      <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const" />
+    </property>
     <property name="Pool" type="o" access="read">
       <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const" />
     </property>
+    <property name="ReadOnly" type="b" access="readwrite" />
+    <property name="Shared" type="(bs)" access="read" />
     <property name="Size" type="s" access="read" />
     <property name="SizeLimit" type="(bs)" access="readwrite" />
+    <property name="SnapshotSchedule" type="(b(tuuu))" access="readwrite" />
+    <property name="SnapshotScheduleStatus" type="(b(sbs))" access="read">
+      <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false" />
+    </property>
     <property name="Used" type="(bs)" access="read" />
     <property name="Uuid" type="s" access="read">
       <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const" />
@@ -149,6 +171,12 @@ SPECS = {
       <arg name="return_code" type="q" direction="out" />
       <arg name="return_string" type="s" direction="out" />
     </method>
+    <method name="AddKeyringBinding">
+      <arg name="key_desc" type="s" direction="in" />
+      <arg name="results" type="b" direction="out" />
+      <arg name="return_code" type="q" direction="out" />
+      <arg name="return_string" type="s" direction="out" />
+    </method>
     <method name="BindClevis">
       <arg name="pin" type="s" direction="in" />
       <arg name="json" type="s" direction="in" />
@@ -174,6 +202,20 @@ SPECS = {
       <arg name="return_code" type="q" direction="out" />
       <arg name="return_string" type="s" direction="out" />
     </method>
+    <method name="EncryptPool">
+      <arg name="key_desc" type="(bs)" direction="in" />
+      <arg name="clevis_info" type="(b(ss))" direction="in" />
+      <arg name="cipher" type="(bs)" direction="in" />
+      <arg name="key_size" type="(bu)" direction="in" />
+      <arg name="sector_size" type="(bu)" direction="in" />
+      <arg name="pbkdf" type="(bs)" direction="in" />
+      <arg name="pbkdf_memory" type="(bu)" direction="in" />
+      <arg name="pbkdf_iterations" type="(bu)" direction="in" />
+      <arg name="pbkdf_parallel" type="(bu)" direction="in" />
+      <arg name="results" type="b" direction="out" />
+      <arg name="return_code" type="q" direction="out" />
+      <arg name="return_string" type="s" direction="out" />
+    </method>
     <method name="GrowPhysicalDevice">
       <arg name="dev" type="s" direction="in" />
       <arg name="results" type="b" direction="out" />
@@ -182,6 +224,9 @@ SPECS = {
     </method>
     <method name="InitCache">
       <arg name="devices" type="as" direction="in" />
+      <arg name="mode" type="s" direction="in" />
+      <arg name="policy" type="s" direction="in" />
+      <arg name="policy_args" type="a(ss)" direction="in" />
       <arg name="results" type="(bao)" direction="out" />
       <arg name="return_code" type="q" direction="out" />
       <arg name="return_string" type="s" direction="out" />
@@ -197,6 +242,57 @@ SPECS = {
       <arg name="return_code" type="q" direction="out" />
       <arg name="return_string" type="s" direction="out" />
     </method>
+    <method name="ReencryptPool">
+      <arg name="cipher" type="(bs)" direction="in" />
+      <arg name="key_size" type="(bu)" direction="in" />
+      <arg name="sector_size" type="(bu)" direction="in" />
+      <arg name="pbkdf" type="(bs)" direction="in" />
+      <arg name="pbkdf_memory" type="(bu)" direction="in" />
+      <arg name="pbkdf_iterations" type="(bu)" direction="in" />
+      <arg name="pbkdf_parallel" type="(bu)" direction="in" />
+      <arg name="results" type="b" direction="out" />
+      <arg name="return_code" type="q" direction="out" />
+      <arg name="return_string" type="s" direction="out" />
+    </method>
+    <method name="RemoveBlockdevs">
+      <arg name="blockdevs" type="ao" direction="in" />
+      <arg name="results" type="(bas)" direction="out" />
+      <arg name="return_code" type="q" direction="out" />
+      <arg name="return_string" type="s" direction="out" />
+    </method>
+    <method name="RemoveCache">
+      <arg name="results" type="(bas)" direction="out" />
+      <arg name="return_code" type="q" direction="out" />
+      <arg name="return_string" type="s" direction="out" />
+    </method>
+    <method name="RemoveKeyringBinding">
+      <arg name="key_desc" type="s" direction="in" />
+      <arg name="results" type="b" direction="out" />
+      <arg name="return_code" type="q" direction="out" />
+      <arg name="return_string" type="s" direction="out" />
+    </method>
+    <method name="ReplaceBlockdev">
+      <arg name="blockdev" type="o" direction="in" />
+      <arg name="device" type="s" direction="in" />
+      <arg name="results" type="(bo)" direction="out" />
+      <arg name="return_code" type="q" direction="out" />
+      <arg name="return_string" type="s" direction="out" />
+    </method>
+    <method name="RevertFilesystem">
+      <arg name="origin" type="o" direction="in" />
+      <arg name="snapshot" type="o" direction="in" />
+      <arg name="results" type="b" direction="out" />
+      <arg name="return_code" type="q" direction="out" />
+      <arg name="return_string" type="s" direction="out" />
+    </method>
+    <method name="SetCacheSettings">
+      <arg name="mode" type="s" direction="in" />
+      <arg name="policy" type="s" direction="in" />
+      <arg name="policy_args" type="a(ss)" direction="in" />
+      <arg name="results" type="b" direction="out" />
+      <arg name="return_code" type="q" direction="out" />
+      <arg name="return_string" type="s" direction="out" />
+    </method>
     <method name="SetName">
       <arg name="name" type="s" direction="in" />
       <arg name="result" type="(bs)" direction="out" />
@@ -206,10 +302,17 @@ SPECS = {
     <method name="SnapshotFilesystem">
       <arg name="origin" type="o" direction="in" />
       <arg name="snapshot_name" type="s" direction="in" />
+      <arg name="read_only" type="b" direction="in" />
       <arg name="result" type="(bo)" direction="out" />
       <arg name="return_code" type="q" direction="out" />
       <arg name="return_string" type="s" direction="out" />
     </method>
+    <method name="SnapshotFilesystems">
+      <arg name="specs" type="a(os)" direction="in" />
+      <arg name="results" type="(ba(os))" direction="out" />
+      <arg name="return_code" type="q" direction="out" />
+      <arg name="return_string" type="s" direction="out" />
+    </method>
     <method name="UnbindClevis">
       <arg name="results" type="b" direction="out" />
       <arg name="return_code" type="q" direction="out" />
@@ -222,16 +325,42 @@ SPECS = {
     </method>
     <property name="AllocatedSize" type="s" access="read" />
     <property name="AvailableActions" type="s" access="read" />
+    <property name="CacheDemotions" type="(bt)" access="read" />
+    <property name="CacheDirtyBlocks" type="(bt)" access="read" />
+    <property name="CacheMode" type="(bs)" access="read">
+      <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="invalidates" />
+    </property>
+    <property name="CachePolicy" type="(bs)" access="read">
+      <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="invalidates" />
+    </property>
+    <property name="CachePolicyArgs" type="(ba(ss))" access="read">
+      <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="invalidates" />
+    </property>
+    <property name="CachePromotions" type="(bt)" access="read" />
+    <property name="CacheReadHits" type="(bt)" access="read" />
+    <property name="CacheReadMisses" type="(bt)" access="read" />
+    <property name="CacheWriteHits" type="(bt)" access="read" />
+    <property name="CacheWriteMisses" type="(bt)" access="read" />
     <property name="ClevisInfo" type="(b(b(ss)))" access="read" />
+    <property name="DataShrink" type="(bs)" access="read" />
+    <property name="Degraded" type="b" access="read" />
     <property name="Encrypted" type="b" access="read">
       <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const" />
     </property>
+    <property name="EncryptionProgress" type="(by)" access="read" />
     <property name="FsLimit" type="t" access="readwrite" />
     <property name="HasCache" type="b" access="read" />
     <property name="KeyDescription" type="(b(bs))" access="read" />
+    <property name="KeyringBindings" type="(bas)" access="read" />
     <property name="Name" type="s" access="read" />
     <property name="NoAllocSpace" type="b" access="read" />
     <property name="Overprovisioning" type="b" access="readwrite" />
+    <property name="Redundancy" type="q" access="read">
+      <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const" />
+    </property>
+    <property name="ReencryptionProgress" type="(by)" access="read" />
+    <property name="ReplacementProgress" type="(by)" access="read" />
+    <property name="StripeSize" type="(bs)" access="readwrite" />
     <property name="TotalPhysicalSize" type="s" access="read" />
     <property name="TotalPhysicalUsed" type="(bs)" access="read" />
     <property name="Uuid" type="s" access="read">
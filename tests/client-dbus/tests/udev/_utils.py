@@ -81,12 +81,21 @@ def create_pool(
         {
             "name": name,
             "devices": devices,
+            "redundancy": (False, 0),
             "key_desc": (False, "")
             if key_description is None
             else (True, key_description),
             "clevis_info": (False, ("", ""))
             if clevis_info is None
             else (True, clevis_info),
+            "cipher": (False, ""),
+            "key_size": (False, 0),
+            "sector_size": (False, 0),
+            "pbkdf": (False, ""),
+            "pbkdf_memory": (False, 0),
+            "pbkdf_iterations": (False, 0),
+            "pbkdf_parallel": (False, 0),
+            "integrity": (False, ""),
         },
     )
 